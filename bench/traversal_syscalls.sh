@@ -0,0 +1,54 @@
+#!/usr/bin/env bash
+# Metadata syscall counts for a forced recursive copy over an existing
+# destination and a forced move: the overwrite check, the size total and
+# the transfer itself all need to know what is in the source tree, and
+# this shows how many stat calls that costs per source entry.
+#
+# Pass two binaries to compare them on the same tree.
+#
+# Usage: traversal_syscalls.sh <bcmr_binary> [other_bcmr_binary]
+# Needs strace (Linux).
+set -eu
+
+here="$(cd "$(dirname "$0")" && pwd)"
+work=/tmp/bcmr-traversal-syscalls
+kind="${KIND:-many-small}"
+
+if ! command -v strace >/dev/null; then
+    echo "strace not found" >&2
+    exit 1
+fi
+
+"$here/gen_workload.sh" "$work/src" "$kind" >/dev/null
+entries=$(find "$work/src" -mindepth 1 | wc -l | tr -d ' ')
+echo "workload=$kind entries=$entries"
+
+# Sums the stat family from an `strace -c` summary.
+stat_calls() {
+    awk '$NF ~ /^(statx|newfstatat|fstatat64|stat|lstat|fstat|stat64|lstat64)$/ {
+        n += ($4 ~ /^[0-9]+$/) ? $4 : $3
+    } END { print n + 0 }' "$1"
+}
+
+run() {
+    local bcmr=$1 label=$2
+    shift 2
+    local log="$work/strace.log"
+    strace -c -f -o "$log" "$bcmr" "$@" >/dev/null 2>&1
+    local n
+    n=$(stat_calls "$log")
+    printf '  %-8s %10s stat calls  %6s per entry\n' \
+        "$label" "$n" "$(awk -v n="$n" -v e="$entries" 'BEGIN { printf "%.2f", n / e }')"
+}
+
+for bcmr in "$@"; do
+    echo
+    echo "=== $bcmr ==="
+    rm -rf "$work/dst" && mkdir -p "$work/dst"
+    cp -r "$work/src" "$work/dst/src"
+    run "$bcmr" copy copy -r -f -y "$work/src" "$work/dst"
+
+    rm -rf "$work/moved" "$work/dst" && mkdir -p "$work/dst"
+    cp -r "$work/src" "$work/moved"
+    run "$bcmr" move move -r -f -y "$work/moved" "$work/dst"
+done
@@ -1,28 +1,110 @@
-use crate::app::completions::validate_mode;
-use crate::app::prompts::{confirm_overwrite, confirm_removal, first_display_name};
+use crate::app::prompts::{
+    confirm_overwrite, confirm_removal, confirm_sync_delete, first_display_name,
+};
 use crate::app::runners::{resume_or_new_runner, start_scanning_runner};
-use crate::cli::Commands;
+use crate::cli::{
+    BenchArgs, CheckArgs, ChecksumArgs, Commands, CopyArgs, DuArgs, ListArgs, MoveArgs, RemoveArgs,
+    SyncArgs, UndoArgs, VerifyArgs,
+};
 use crate::commands;
-use crate::commands::remote_copy::{handle_remote_copy, is_plain_mode};
-use crate::config::is_json_mode;
+use crate::commands::copy::ProgressCallback;
+use crate::commands::remote_copy::handle_remote_copy;
+use crate::commands::remove::ProgressState;
+use crate::config::{is_json_mode, is_quiet};
+use crate::core::checksum::Algorithm;
 use crate::core::error::BcmrError;
+use crate::core::verify_report;
 use crate::output;
-use crate::ui::runner::ProgressRunner;
-use crate::ui::utils::format_bytes;
+use crate::ui::runner::{interrupted, record_interrupt, ProgressRunner};
+use crate::ui::scan::ScanIndicator;
+use crate::ui::utils::{display_path, format_bytes, format_count, summarize_paths};
 use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
-pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
+/// `copy --from-tar`: asks before replacing files, as a copy with `-f`
+/// does, when the archive can be read ahead of time.
+async fn extract_tar(
+    args: &CopyArgs,
+    sources: &[std::path::PathBuf],
+    dest: &std::path::Path,
+    excludes: &[regex::Regex],
+) -> Result<()> {
+    let [archive] = sources else {
+        bail!("--from-tar takes a single archive as the source");
+    };
+    if args.common.is_force() && args.common.should_prompt_for_overwrite() {
+        if archive.as_os_str() == "-" {
+            bail!("Cannot ask before overwriting while the archive is read from stdin: pass -y");
+        }
+        let overwrites = commands::tar_copy::archive_overwrites(archive, dest, excludes).await?;
+        if !overwrites.is_empty() && !confirm_overwrite(&overwrites)? {
+            return Err(BcmrError::Cancelled.into());
+        }
+    }
+    commands::tar_copy::handle_tar_extract(args, archive, dest, excludes).await
+}
+
+/// `copy --compress zstd`/`--decompress`: one file, with the overwrite
+/// prompt a copy with `-f` gives.
+async fn codec_copy(
+    args: &CopyArgs,
+    sources: &[std::path::PathBuf],
+    dest: &std::path::Path,
+    codec: commands::codec_copy::FileCodec,
+) -> Result<()> {
+    let [src] = sources else {
+        bail!("--compress zstd and --decompress take a single file source");
+    };
+    let target = commands::codec_copy::target(src, dest, codec);
+    if args.common.is_force()
+        && args.common.should_prompt_for_overwrite()
+        && !args.common.is_dry_run()
+        && target.exists()
+    {
+        let overwrite = commands::copy::FileToOverwrite {
+            path: target.clone(),
+            is_dir: false,
+        };
+        if !confirm_overwrite(&[overwrite])? {
+            return Err(BcmrError::Cancelled.into());
+        }
+    }
+    commands::codec_copy::handle_codec_copy(args, src, &target, codec).await
+}
+
+pub(crate) async fn handle_copy_command(args: &CopyArgs) -> Result<()> {
+    crate::core::cleanup::global().set_keep_partial(args.common.keep_partial());
+    crate::core::bwlimit::set(args.common.bwlimit().map_err(anyhow::Error::msg)?);
     use crate::core::remote::parse_remote_path;
 
-    let excludes = args.compile_excludes()?;
-    let (sources, dest) = args.get_sources_and_dest().map_err(anyhow::Error::msg)?;
+    let excludes = args.common.compile_excludes()?;
+    let (sources, dest) = args
+        .common
+        .get_sources_and_dest()
+        .map_err(anyhow::Error::msg)?;
 
-    if let Some(mode) = args.get_reflink_mode() {
-        validate_mode(&mode, "reflink")?;
+    // Ahead of the remote check, which would read "https:" as a host.
+    if sources
+        .iter()
+        .any(|s| crate::core::http::is_url(&s.to_string_lossy()))
+    {
+        return commands::http_copy::handle_http_copy(args, sources, dest).await;
+    }
+    if args.expected_hash.is_some() || !args.headers.is_empty() {
+        bail!("--expected-hash and --header only apply to URL sources");
+    }
+    commands::copy::check_device_sources(sources, args.device_source)?;
+    crate::core::perms::set_enabled(args.is_fix_perms());
+    if args.from_tar {
+        return extract_tar(args, sources, dest, &excludes).await;
     }
-    if let Some(mode) = args.get_sparse_mode() {
-        validate_mode(&mode, "sparse")?;
+    if commands::tar_copy::is_archive_dest(args, sources, dest) {
+        if parse_remote_path(&dest.to_string_lossy()).is_some() {
+            bail!("A tar archive is written locally: pipe `--to-tar -` to ssh for a remote one");
+        }
+        return commands::tar_copy::handle_tar_create(args, sources, dest, &excludes).await;
     }
 
     let dest_str = dest.to_string_lossy();
@@ -32,8 +114,20 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
         .any(|s| parse_remote_path(&s.to_string_lossy()).is_some());
 
     if remote_dest.is_some() || any_remote_source {
+        if args.decompress.is_some() {
+            bail!("--decompress only applies to local copies");
+        }
+        if args.device_source {
+            bail!("--device-source only applies to local copies");
+        }
         return handle_remote_copy(args, sources, dest, &excludes).await;
     }
+    if !args.common.is_dry_run() && !args.common.global.no_preflight {
+        crate::core::preflight::check_writable(dest)?;
+    }
+    if let Some(codec) = commands::codec_copy::requested(args, sources)? {
+        return codec_copy(args, sources, dest, codec).await;
+    }
 
     if sources.len() > 1 && (!dest.exists() || !dest.is_dir()) {
         bail!(
@@ -42,30 +136,49 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
         );
     }
 
-    let needs_overwrite_prompt = args.is_force() && args.should_prompt_for_overwrite();
+    let needs_overwrite_prompt =
+        args.common.is_force() && args.common.should_prompt_for_overwrite();
+    let cancel = CancellationToken::new();
 
-    if needs_overwrite_prompt || args.is_dry_run() {
+    if needs_overwrite_prompt || args.common.is_dry_run() {
         let first_display = first_display_name(sources);
-        let early = if !args.is_dry_run() {
-            start_scanning_runner(args, "Copying", first_display.as_deref())?
+        let early = if !args.common.is_dry_run() {
+            start_scanning_runner(
+                args.common.progress_style(),
+                "Copying",
+                first_display.as_deref(),
+            )?
         } else {
             None
         };
+        if let Some(r) = &early {
+            r.cancel_on_interrupt(&cancel);
+        }
 
-        let plan =
-            match commands::copy::plan_copy(sources, dest, args.is_recursive(), &excludes).await {
-                Ok(p) => p,
-                Err(e) => {
-                    if let Some(r) = early {
-                        r.finish_with_error(&e.to_string());
-                    }
-                    return Err(e.into());
-                }
-            };
+        let scan = ScanIndicator::start(early.is_none(), &cancel);
+        let planned = commands::copy::plan_copy(
+            sources,
+            dest,
+            args.common.is_recursive(),
+            &excludes,
+            commands::copy::name_check(dest, args),
+            scan.counters(),
+        )
+        .await;
+        let files_found = scan.finish().await;
+        let plan = match planned {
+            Ok(p) => p,
+            Err(e) => {
+                return match early {
+                    Some(r) => r.finish_failed(e),
+                    None => Err(e.into()),
+                };
+            }
+        };
 
-        if args.is_force()
+        if args.common.is_force()
             && !plan.overwrites.is_empty()
-            && args.should_prompt_for_overwrite()
+            && args.common.should_prompt_for_overwrite()
             && !confirm_overwrite(&plan.overwrites)?
         {
             if let Some(r) = early {
@@ -74,10 +187,10 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
             return Err(BcmrError::Cancelled.into());
         }
 
-        if args.is_dry_run() {
+        if args.common.is_dry_run() {
             if !is_json_mode() {
                 println!("DRY RUN MODE: No changes will be made.\n");
-                commands::copy::dry_run_plan(&plan, args)?;
+                commands::copy::dry_run_plan(&plan, args).await;
                 println!(
                     "\nSummary: {} sources, {}",
                     sources.len(),
@@ -89,34 +202,38 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
 
         let runner = resume_or_new_runner(
             early,
-            args,
+            args.common.progress_style(),
             "Copying",
             first_display.as_deref(),
             plan.total_size,
             false,
         )?;
+        runner.cancel_on_interrupt(&cancel);
+        runner.set_paths(&summarize_paths(sources), &display_path(dest));
+        {
+            let mut p = runner.progress().lock();
+            p.set_files_found(files_found);
+            p.set_total_items(files_found as usize);
+        }
 
-        let result = commands::copy::execute_plan(
-            &plan,
-            args,
-            runner.inc_callback(),
-            runner.file_callback(),
-        )
-        .await;
+        let result =
+            commands::copy::execute_plan(&plan, args, progress_callbacks(&runner), &cancel).await;
 
         if let Err(e) = result {
-            return runner.finish_err(e.to_string());
+            return runner.finish_failed(e);
         }
 
         runner.finish_ok()
     } else {
         let runner = ProgressRunner::new(
             0,
-            is_plain_mode(args),
+            args.common.progress_style(),
             false,
             is_json_mode(),
             commands::copy::cleanup_partial_files,
         )?;
+        runner.cancel_on_interrupt(&cancel);
+        runner.set_paths(&summarize_paths(sources), &display_path(dest));
 
         {
             let mut p = runner.progress().lock();
@@ -132,13 +249,31 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
             let p = Arc::clone(runner.progress());
             move |total: u64| p.lock().set_total_bytes(total)
         };
+        let workers = (args.common.local_jobs() > 1).then(|| {
+            runner.set_parallel_mode(args.common.local_jobs());
+            commands::copy::WorkerCallbacks {
+                on_file: Box::new(runner.worker_file_callback()),
+                on_progress: Box::new(runner.worker_progress_callback()),
+                on_done: Box::new(runner.worker_done_callback()),
+            }
+        });
+
+        let files_found = Arc::new(AtomicU64::new(0));
         let scan_done_cb = {
             let p = Arc::clone(runner.progress());
-            move || p.lock().set_scanning(false)
+            let files_found = Arc::clone(&files_found);
+            move || {
+                let mut p = p.lock();
+                p.set_total_items(files_found.load(Ordering::Relaxed) as usize);
+                p.set_scanning(false);
+            }
         };
         let files_found_cb = {
             let p = Arc::clone(runner.progress());
-            move |count: u64| p.lock().set_files_found(count)
+            move |count: u64| {
+                files_found.store(count, Ordering::Relaxed);
+                p.lock().set_files_found(count)
+            }
         };
 
         let result = commands::copy::pipeline_copy(
@@ -152,21 +287,54 @@ pub(crate) async fn handle_copy_command(args: &Commands) -> Result<()> {
                 on_total_update: Box::new(total_cb),
                 on_scan_complete: Box::new(scan_done_cb),
                 on_file_found: Box::new(files_found_cb),
+                on_files_done: Box::new(runner.items_callback()),
+                on_skip: Box::new(runner.skip_callback()),
+                on_verify_queued: Box::new(runner.verify_queued_callback()),
+                on_verified: Box::new(runner.verified_callback()),
+                workers,
             },
+            &cancel,
         )
         .await;
 
         if let Err(e) = result {
-            return runner.finish_err(e.to_string());
+            return runner.finish_failed(e);
         }
 
         runner.finish_ok()
     }
 }
 
-pub(crate) async fn handle_move_command(args: &Commands) -> Result<()> {
-    let excludes = args.compile_excludes()?;
-    let (sources, dest) = args.get_sources_and_dest().map_err(anyhow::Error::msg)?;
+fn progress_callbacks(
+    runner: &ProgressRunner,
+) -> ProgressCallback<impl Fn(u64) + Send + Sync + Clone + 'static> {
+    ProgressCallback::new(runner.inc_callback(), runner.file_callback())
+        .with_files_done(runner.items_callback())
+        .with_skip(runner.skip_callback())
+        .with_verify(runner.verify_queued_callback(), runner.verified_callback())
+}
+
+pub(crate) async fn handle_move_command(args: &MoveArgs) -> Result<()> {
+    crate::core::cleanup::global().set_keep_partial(args.common.keep_partial());
+    crate::core::bwlimit::set(args.common.bwlimit().map_err(anyhow::Error::msg)?);
+    let excludes = args.common.compile_excludes()?;
+    let (sources, dest) = args
+        .common
+        .get_sources_and_dest()
+        .map_err(anyhow::Error::msg)?;
+
+    if let Some(src) = sources
+        .iter()
+        .find(|src| crate::core::device::is_device_path(src))
+    {
+        bail!(
+            "Cannot move '{}': it is a device; copy it with --device-source instead",
+            src.display()
+        );
+    }
+    if !args.common.is_dry_run() && !args.common.global.no_preflight {
+        crate::core::preflight::check_writable(dest)?;
+    }
 
     if sources.len() > 1 && (!dest.exists() || !dest.is_dir()) {
         bail!(
@@ -176,55 +344,68 @@ pub(crate) async fn handle_move_command(args: &Commands) -> Result<()> {
     }
 
     let first_display = first_display_name(sources);
-    let early = if !args.is_dry_run() {
-        start_scanning_runner(args, "Moving", first_display.as_deref())?
+    let early = if !args.common.is_dry_run() {
+        start_scanning_runner(
+            args.common.progress_style(),
+            "Moving",
+            first_display.as_deref(),
+        )?
     } else {
         None
     };
+    let cancel = CancellationToken::new();
+    if let Some(r) = &early {
+        r.cancel_on_interrupt(&cancel);
+    }
 
-    let bail_early = |early: Option<ProgressRunner>, e: anyhow::Error| -> Result<()> {
-        if let Some(r) = early {
-            r.finish_with_error(&e.to_string());
+    let bail_early = |early: Option<ProgressRunner>, e: BcmrError| -> Result<()> {
+        match early {
+            Some(r) => r.finish_failed(e),
+            None => Err(e.into()),
         }
-        Err(e)
     };
 
-    if args.is_force() {
-        let files_to_overwrite = match commands::r#move::check_overwrites(
-            sources,
-            dest,
-            args.is_recursive(),
-            args,
-            &excludes,
-        )
-        .await
-        {
-            Ok(v) => v,
-            Err(e) => return bail_early(early, e.into()),
-        };
+    let scan = ScanIndicator::start(early.is_none(), &cancel);
+    let surveyed = commands::r#move::survey(
+        sources,
+        dest,
+        args.common.is_recursive(),
+        args.common.is_force(),
+        &excludes,
+        args.common.scan_jobs(),
+        scan.counters(),
+    )
+    .await;
+    let files_found = scan.finish().await;
+    let survey = match surveyed {
+        Ok(v) => v,
+        Err(e) => return bail_early(early, e),
+    };
+    let total_size = survey.total_size();
 
-        if !files_to_overwrite.is_empty()
-            && args.should_prompt_for_overwrite()
-            && !confirm_overwrite(&files_to_overwrite)?
-        {
-            return bail_early(early, BcmrError::Cancelled.into());
-        }
+    if !survey.overwrites.is_empty()
+        && args.common.should_prompt_for_overwrite()
+        && !confirm_overwrite(&survey.overwrites)?
+    {
+        return bail_early(early, BcmrError::Cancelled);
     }
 
-    let total_size =
-        match commands::r#move::get_total_size(sources, args.is_recursive(), args, &excludes).await
-        {
-            Ok(v) => v,
-            Err(e) => return bail_early(early, e.into()),
-        };
-
-    if args.is_dry_run() {
+    if args.common.is_dry_run() {
         if !is_json_mode() {
             println!("DRY RUN MODE: No changes will be made.\n");
         }
 
-        for src in sources {
-            commands::r#move::move_path(src, dest, args, &excludes, |_| {}, |_, _| {}).await?;
+        for (src, totals) in sources.iter().zip(&survey.totals) {
+            commands::r#move::move_path(
+                src,
+                dest,
+                args,
+                &excludes,
+                *totals,
+                ProgressCallback::new(|_| {}, |_, _| {}),
+                &cancel,
+            )
+            .await?;
         }
 
         if !is_json_mode() {
@@ -239,41 +420,58 @@ pub(crate) async fn handle_move_command(args: &Commands) -> Result<()> {
 
     let runner = resume_or_new_runner(
         early,
-        args,
+        args.common.progress_style(),
         "Moving",
         first_display.as_deref(),
         total_size,
         false,
     )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&summarize_paths(sources), &display_path(dest));
+    {
+        let mut p = runner.progress().lock();
+        p.set_files_found(files_found);
+        p.set_total_items(files_found as usize);
+    }
 
-    for src in sources {
+    for (src, totals) in sources.iter().zip(&survey.totals) {
         let result = commands::r#move::move_path(
             src,
             dest,
             args,
             &excludes,
-            runner.inc_callback(),
-            runner.file_callback(),
+            *totals,
+            progress_callbacks(&runner),
+            &cancel,
         )
         .await;
 
-        if let Err(e) = result {
-            if !is_json_mode() {
-                eprintln!("Error moving '{}': {}", src.display(), e);
+        // Reported once by main, after the renderer has given the terminal
+        // back; printed here it would be drawn over.
+        match result {
+            Ok(()) => {}
+            Err(e @ BcmrError::Cancelled) => return runner.finish_failed(e),
+            Err(e) => {
+                return runner.finish_err(format!("Error moving '{}': {}", src.display(), e));
             }
-            return runner.finish_err(format!("Error moving '{}': {}", src.display(), e));
         }
     }
 
     runner.finish_ok()
 }
 
-pub(crate) async fn handle_remove_command(args: &Commands) -> Result<()> {
+pub(crate) async fn handle_remove_command(args: &RemoveArgs) -> Result<()> {
     let excludes = args.compile_excludes()?;
-    let paths = args.get_remove_paths().map_err(anyhow::Error::msg)?;
+    let paths = &args.paths;
+    if !args.is_dry_run() && !args.global.no_preflight {
+        for path in paths {
+            crate::core::preflight::check_removable(path)?;
+        }
+    }
 
     let first_display = first_display_name(paths);
-    let early = start_scanning_runner(args, "Removing", first_display.as_deref())?;
+    let early = start_scanning_runner(args.progress_style(), "Removing", first_display.as_deref())?;
+    let cancel = CancellationToken::new();
 
     let files_to_remove =
         match commands::remove::check_removes(paths, args.is_recursive(), args, &excludes).await {
@@ -297,34 +495,30 @@ pub(crate) async fn handle_remove_command(args: &Commands) -> Result<()> {
 
         let runner = resume_or_new_runner(
             early,
-            args,
+            args.progress_style(),
             "Removing",
             first_display.as_deref(),
             total_size,
             true,
         )?;
+        runner.cancel_on_interrupt(&cancel);
         let result = commands::remove::remove_paths(
             paths,
             args,
             &excludes,
-            Arc::clone(runner.progress()),
+            ProgressState::new(files_to_remove.len(), Arc::clone(runner.progress())),
             runner.inc_callback(),
             Box::new(runner.file_callback()),
-            files_to_remove.len(),
+            &cancel,
         )
         .await;
 
         match result {
-            Ok(()) => {
-                runner.finish_ok()?;
-            }
-            Err(e) => {
-                runner.finish_with_error(&e.to_string());
-                return Err(e.into());
-            }
+            Ok(()) => runner.finish_ok()?,
+            Err(e) => return runner.finish_failed(e),
         }
 
-        if !is_json_mode() {
+        if !is_json_mode() && !is_quiet() {
             print!("\nSummary: {} files", file_count);
             if dir_count > 0 {
                 print!(", {} directories", dir_count);
@@ -334,10 +528,7 @@ pub(crate) async fn handle_remove_command(args: &Commands) -> Result<()> {
         return Ok(());
     }
 
-    if !files_to_remove.is_empty()
-        && !args.is_force()
-        && !args.is_yes()
-        && (!args.is_interactive() || files_to_remove.len() > 1)
+    if args.confirms_up_front(files_to_remove.len(), &crate::config::CONFIG.confirm)
         && !confirm_removal(&files_to_remove)?
     {
         if let Some(r) = early {
@@ -349,37 +540,502 @@ pub(crate) async fn handle_remove_command(args: &Commands) -> Result<()> {
     let total_size: u64 = files_to_remove.iter().map(|f| f.size).sum();
     let runner = resume_or_new_runner(
         early,
-        args,
+        args.progress_style(),
         "Removing",
         first_display.as_deref(),
         total_size,
         false,
     )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&format!("removing: {}", summarize_paths(paths)), "");
 
     let result = commands::remove::remove_paths(
         paths,
         args,
         &excludes,
-        Arc::clone(runner.progress()),
+        ProgressState::new(files_to_remove.len(), Arc::clone(runner.progress())),
         runner.inc_callback(),
         Box::new(runner.file_callback()),
-        files_to_remove.len(),
+        &cancel,
     )
     .await;
 
     match result {
         Ok(()) => runner.finish_ok(),
+        Err(e) => runner.finish_failed(e),
+    }
+}
+
+pub(crate) async fn handle_sync_command(args: &SyncArgs) -> Result<()> {
+    let excludes = args.compile_excludes()?;
+    let first_display = first_display_name(std::slice::from_ref(&args.src));
+    let early = if !args.global.dry_run {
+        start_scanning_runner(
+            args.global.progress_style(),
+            "Syncing",
+            first_display.as_deref(),
+        )?
+    } else {
+        None
+    };
+    let cancel = CancellationToken::new();
+    if let Some(r) = &early {
+        r.cancel_on_interrupt(&cancel);
+    }
+
+    let planned = commands::sync::plan(&args.src, &args.dst, args.compare(), &excludes)
+        .await
+        .and_then(|plan| {
+            if args.delete {
+                commands::sync::check_delete(&plan, args)?;
+            }
+            Ok(plan)
+        });
+    let plan = match planned {
+        Ok(p) => p,
         Err(e) => {
-            runner.finish_with_error(&e.to_string());
-            Err(e.into())
+            return match early {
+                Some(r) => r.finish_failed(e),
+                None => Err(e.into()),
+            };
+        }
+    };
+    let removals = if args.delete { plan.extra_items } else { 0 };
+
+    if args.global.dry_run {
+        if !is_json_mode() {
+            use crate::ui::display::ActionType;
+            println!("DRY RUN MODE: No changes will be made.\n");
+            commands::sync::print_plan(&plan, args.delete);
+            print!(
+                "\nSummary: {} to add, {} to overwrite, {} up to date",
+                plan.count(ActionType::Add),
+                plan.count(ActionType::Overwrite),
+                plan.count(ActionType::Skip)
+            );
+            if args.delete {
+                print!(", {} to remove", plan.count(ActionType::Remove));
+            }
+            println!(", {}", format_bytes(plan.copy.total_size as f64));
         }
+        return Ok(());
+    }
+
+    if args.confirms_delete(removals, &crate::config::CONFIG.confirm)
+        && !confirm_sync_delete(&plan.extra, removals)?
+    {
+        if let Some(r) = early {
+            r.finish_with_error("cancelled by user");
+        }
+        return Err(BcmrError::Cancelled.into());
+    }
+
+    let runner = resume_or_new_runner(
+        early,
+        args.global.progress_style(),
+        "Syncing",
+        first_display.as_deref(),
+        plan.copy.total_size,
+        false,
+    )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&display_path(&args.src), &display_path(&args.dst));
+    let total_items = plan.files_to_copy() + removals;
+
+    let result = commands::sync::execute(
+        &plan,
+        args,
+        &excludes,
+        progress_callbacks(&runner),
+        ProgressState::new(total_items, Arc::clone(runner.progress())),
+        Box::new(runner.file_callback()),
+        &cancel,
+    )
+    .await;
+
+    match result {
+        Ok(()) => runner.finish_ok(),
+        Err(e) => runner.finish_failed(e),
     }
 }
 
-pub(crate) async fn handle_check_command(args: &Commands) -> Result<output::CheckResult> {
+pub(crate) async fn handle_list_command(args: &ListArgs) -> Result<()> {
+    use std::io::Write;
+
+    let excludes = args.compile_excludes()?;
+    let patterns = args.global.exclude.clone().unwrap_or_default();
+    let mut listed = crate::core::traversal::in_background({
+        let (paths, recursive, excluded) = (args.paths.clone(), args.recursive, args.excluded);
+        move || commands::list::entries(paths, recursive, excludes, excluded)
+    });
+
+    let mut out = std::io::BufWriter::new(std::io::stdout());
+    let (mut files, mut dirs, mut bytes) = (0u64, 0u64, 0u64);
+    let terminator: &[u8] = if args.null { b"\0" } else { b"\n" };
+    while let Some(entry) = listed.recv().await {
+        let entry = entry?;
+        if entry.is_dir {
+            dirs += 1;
+        } else {
+            files += 1;
+            bytes += entry.size;
+        }
+        if args.count {
+            continue;
+        }
+        let written = out
+            .write_all(entry.path.as_os_str().as_encoded_bytes())
+            .and_then(|()| match entry.pattern {
+                Some(i) if !args.null => write!(out, "  (excluded by '{}')", patterns[i]),
+                _ => Ok(()),
+            })
+            .and_then(|()| out.write_all(terminator));
+        // `bcmr list ... | head` closes the pipe early; that is not a failure.
+        match written {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            other => other?,
+        }
+    }
+    if args.count {
+        writeln!(
+            out,
+            "{} files, {} directories, {}",
+            format_count(files),
+            format_count(dirs),
+            format_bytes(bytes as f64)
+        )?;
+    }
+    match out.flush() {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => Ok(other?),
+    }
+}
+
+pub(crate) async fn handle_du_command(args: &DuArgs) -> Result<output::DuResult> {
+    let excludes = args.compile_excludes()?;
+    let opts = commands::du::DuOptions {
+        recursive: args.recursive,
+        max_depth: args.max_depth,
+        blocks: args.blocks,
+        largest: args.files.unwrap_or(0),
+    };
+    let cancel = CancellationToken::new();
+    let scan = ScanIndicator::start(!is_json_mode(), &cancel);
+    let measured = commands::du::measure(&args.paths, &excludes, opts, scan.counters()).await;
+    scan.finish().await;
+    Ok(measured?)
+}
+
+pub(crate) async fn handle_bench_command(args: &BenchArgs) -> Result<output::BenchResult> {
+    let opts = commands::bench::BenchOptions {
+        dir: args.dir.clone(),
+        size: args.size,
+        files: args.files,
+        engines: args.engines.clone(),
+    };
+    let cancel = CancellationToken::new();
+    let bench = commands::bench::run(&opts, &cancel);
+    tokio::pin!(bench);
+    // A signal lets the bench stop at its next chunk and remove its data.
+    let result = tokio::select! {
+        result = &mut bench => result,
+        signal = interrupted() => {
+            record_interrupt(signal);
+            cancel.cancel();
+            bench.await
+        }
+    };
+    Ok(result?)
+}
+
+/// Returns the exit status: 1 when something could not be restored.
+pub(crate) fn handle_undo_command(args: &UndoArgs, dry_run: bool) -> Result<i32> {
+    use crate::core::history;
+
+    let path = history::default_path()
+        .ok_or_else(|| anyhow::anyhow!("cannot find a state directory for the undo journal"))?;
+    let ops = commands::undo::operations(history::load(&path)?);
+    if args.list {
+        for op in &ops {
+            println!(
+                "{}  {}  {:>6} action(s)  {}{}",
+                op.id,
+                op.time,
+                op.events.len(),
+                op.args.join(" "),
+                if op.undone { "  (undone)" } else { "" }
+            );
+        }
+        return Ok(0);
+    }
+
+    let op = commands::undo::pick(&ops, args.id.as_deref())?;
+    let steps = commands::undo::undo(op, dry_run);
+    for step in &steps {
+        if step.restored {
+            println!("{}", step.line);
+        } else {
+            eprintln!("kept {}", step.line);
+        }
+    }
+    let restored = steps.iter().filter(|s| s.restored).count();
+    if !dry_run {
+        history::mark_undone(&path, &op.id)?;
+    }
+    println!(
+        "{} {} of {} action(s) of {} ({})",
+        if dry_run { "Would undo" } else { "Undid" },
+        restored,
+        steps.len(),
+        op.id,
+        op.args.join(" ")
+    );
+    Ok(if restored == steps.len() { 0 } else { 1 })
+}
+
+pub(crate) async fn handle_check_command(args: &CheckArgs) -> Result<output::CheckResult> {
     let excludes = args.compile_excludes()?;
     let (sources, dest) = args.get_sources_and_dest().map_err(anyhow::Error::msg)?;
-    Ok(commands::check::run(sources, dest, args.is_recursive(), &excludes).await?)
+    Ok(commands::check::run(sources, dest, args.recursive, &excludes).await?)
+}
+
+pub(crate) async fn handle_verify_command(args: &VerifyArgs) -> Result<output::VerifyResult> {
+    let excludes = args.compile_excludes()?;
+    let comparison =
+        commands::verify::compare(&args.src, &args.dst, args.recursive, &excludes).await?;
+    let mut differences = comparison.differences;
+    let mut hashed = 0;
+
+    // Whatever the listings already settled fails without a digest.
+    let single_file = !args.src.is_dir();
+    for d in &differences {
+        let (src, dst) = if single_file {
+            (args.src.clone(), args.dst.clone())
+        } else {
+            (args.src.join(&d.path), args.dst.join(&d.path))
+        };
+        verify_report::record(&verify_report::Outcome {
+            src: &src,
+            dst: &dst,
+            size: d.src_size.unwrap_or(0),
+            src_digest: None,
+            dst_digest: None,
+        });
+    }
+
+    if args.hash && !comparison.pairs.is_empty() {
+        hashed = comparison.pairs.len() as u64;
+        let total_size = comparison.pairs.iter().map(|p| p.size).sum();
+        // The JSON result goes to stdout at the end; progress events there
+        // would precede it.
+        let runner = ProgressRunner::new(
+            total_size,
+            args.global.progress_style(),
+            is_json_mode(),
+            false,
+            || None,
+        )?;
+        {
+            let mut p = runner.progress().lock();
+            p.set_operation_type("Verifying");
+            p.set_total_items(comparison.pairs.len());
+        }
+        runner.set_paths(&display_path(&args.src), &display_path(&args.dst));
+        let cancel = CancellationToken::new();
+        runner.cancel_on_interrupt(&cancel);
+
+        let (inc, items) = (runner.inc_callback(), runner.items_callback());
+        let result = commands::verify::compare_hashes(
+            comparison.pairs,
+            args.hash_jobs(),
+            |pair, src_digest, dst_digest| {
+                inc(pair.size);
+                items(1);
+                verify_report::record(&verify_report::Outcome {
+                    src: &pair.src,
+                    dst: &pair.dst,
+                    size: pair.size,
+                    src_digest: Some(src_digest),
+                    dst_digest: Some(dst_digest),
+                });
+            },
+            &cancel,
+        )
+        .await;
+        match result {
+            Ok(mismatched) => {
+                runner.finish_ok()?;
+                differences.extend(mismatched);
+                differences.sort_by(|a, b| a.path.cmp(&b.path));
+            }
+            Err(e) => runner.finish_failed(e)?,
+        }
+    }
+
+    Ok(output::VerifyResult::new(
+        differences,
+        comparison.files,
+        hashed,
+    ))
+}
+
+/// Returns the exit status: always 0 when writing a manifest, see
+/// `CheckCounts::exit_code` when checking one.
+pub(crate) async fn handle_checksum_command(args: &ChecksumArgs) -> Result<i32> {
+    use std::io::{IsTerminal, Write};
+
+    if args.check {
+        return check_manifest(args).await;
+    }
+
+    let algo = args.algo.unwrap_or(Algorithm::Sha256);
+    let excludes = args.compile_excludes()?;
+    let entries = commands::checksum::collect(
+        &args.path,
+        args.recursive,
+        &excludes,
+        args.output.as_deref(),
+    )
+    .await?;
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(out, "{}", commands::checksum::manifest_header(algo))?;
+    // Manifest lines on the terminal are progress enough, and a frame
+    // redrawn under them would garble both.
+    let silent = is_json_mode() || (args.output.is_none() && std::io::stdout().is_terminal());
+    let total_size = entries.iter().map(|e| e.size).sum();
+    let runner = ProgressRunner::new(
+        total_size,
+        args.global.progress_style(),
+        silent,
+        false,
+        || None,
+    )?;
+    {
+        let mut p = runner.progress().lock();
+        p.set_operation_type("Hashing");
+        p.set_total_items(entries.len());
+    }
+    runner.set_paths(&display_path(&args.path), "");
+    let cancel = CancellationToken::new();
+    runner.cancel_on_interrupt(&cancel);
+
+    let items = runner.items_callback();
+    let result = commands::checksum::hash_entries(
+        entries,
+        algo,
+        args.jobs(),
+        runner.inc_callback(),
+        &cancel,
+        |entry, digest| {
+            items(1);
+            writeln!(
+                out,
+                "{}",
+                commands::checksum::manifest_line(digest, &entry.name)
+            )
+        },
+    )
+    .await
+    .and_then(|()| Ok(out.flush()?));
+    match result {
+        Ok(()) => runner.finish_ok()?,
+        Err(e) => runner.finish_failed(e)?,
+    }
+    Ok(0)
+}
+
+async fn check_manifest(args: &ChecksumArgs) -> Result<i32> {
+    use commands::checksum::CheckStatus;
+    use std::io::IsTerminal;
+
+    let text = tokio::fs::read_to_string(&args.path)
+        .await
+        .map_err(|e| anyhow::anyhow!("cannot read manifest '{}': {}", args.path.display(), e))?;
+    let commands::checksum::Manifest {
+        algorithm,
+        lines,
+        malformed,
+    } = commands::checksum::parse_manifest(&text);
+    if lines.is_empty() {
+        bail!(
+            "no properly formatted checksum lines found in '{}'",
+            args.path.display()
+        );
+    }
+    let root = match &args.root {
+        Some(root) => root.clone(),
+        None => match args.path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        },
+    };
+    let mut listed = commands::checksum::locate(lines, &root).await?;
+    if args.ignore_missing {
+        listed.retain(|l| l.size.is_some());
+    }
+
+    let silent = is_json_mode() || std::io::stdout().is_terminal();
+    let total_size = listed.iter().filter_map(|l| l.size).sum();
+    let runner = ProgressRunner::new(
+        total_size,
+        args.global.progress_style(),
+        silent,
+        false,
+        || None,
+    )?;
+    {
+        let mut p = runner.progress().lock();
+        p.set_operation_type("Checking");
+        p.set_total_items(listed.len());
+    }
+    runner.set_paths(&display_path(&args.path), "");
+    let cancel = CancellationToken::new();
+    runner.cancel_on_interrupt(&cancel);
+
+    // --quiet keeps the failures: they are the point of checking.
+    let items = runner.items_callback();
+    let result = commands::checksum::check_listed(
+        listed,
+        args.algo.or(algorithm).unwrap_or(Algorithm::Sha256),
+        args.jobs(),
+        runner.inc_callback(),
+        &cancel,
+        |item, status| {
+            items(1);
+            if status != CheckStatus::Ok || !is_quiet() {
+                println!("{}: {}", item.line.name, status);
+            }
+            Ok(())
+        },
+    )
+    .await;
+    let counts = match result {
+        Ok(counts) => {
+            runner.finish_ok()?;
+            counts
+        }
+        Err(e) => return runner.finish_failed(e).map(|()| 1),
+    };
+
+    if malformed > 0 {
+        crate::ui::progress::print_warnings(&[format!(
+            "{}: {} improperly formatted line{} skipped",
+            args.path.display(),
+            malformed,
+            if malformed == 1 { "" } else { "s" }
+        )]);
+    }
+    if !is_quiet() {
+        println!(
+            "\nSummary: {} OK, {} FAILED, {} MISSING, {} unreadable",
+            counts.ok, counts.failed, counts.missing, counts.unreadable
+        );
+    }
+    Ok(counts.exit_code())
 }
 
 pub(crate) fn handle_init_command(args: &Commands) -> Result<()> {
@@ -391,15 +1047,41 @@ pub(crate) fn handle_init_command(args: &Commands) -> Result<()> {
             suffix,
             path,
             no_cmd,
+            completions,
+            no_completions,
+            uninstall,
+            absolute_path,
+            quiet_fallback,
+            force_override,
+            always,
         } => {
-            let script = commands::init::generate_init_script(
-                shell,
-                cmd.as_deref().unwrap_or(""),
-                prefix.as_deref(),
-                suffix.as_deref(),
-                path.as_ref(),
-                *no_cmd,
-            );
+            let opts = commands::init::InitOptions {
+                cmd_compat: cmd.as_deref().unwrap_or(""),
+                prefix: prefix.as_deref(),
+                suffix: suffix.as_deref(),
+                path: path.as_ref(),
+                no_cmd: *no_cmd,
+                absolute_path: *absolute_path,
+                quiet_fallback: *quiet_fallback,
+                completions: !*no_completions && shell.completion_shell().is_some(),
+                force_override: *force_override,
+                always: *always,
+            };
+            if *completions && shell.completion_shell().is_none() {
+                bail!("--completions is not supported for {}", shell);
+            }
+            if !*no_cmd {
+                commands::init::check_command_names(shell, &opts)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+            }
+            if *uninstall {
+                print!(
+                    "{}",
+                    commands::init::generate_uninstall_script(shell, &opts)
+                );
+                return Ok(());
+            }
+            let script = commands::init::generate_init_script(shell, &opts);
             print!("{}", script);
             Ok(())
         }
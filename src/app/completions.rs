@@ -1,20 +1,7 @@
 use crate::cli;
-use crate::core::error::BcmrError;
-use anyhow::Result;
 
-pub(crate) fn validate_mode(mode: &str, name: &str) -> Result<()> {
-    match mode.to_lowercase().as_str() {
-        "force" | "disable" | "never" | "auto" => Ok(()),
-        other => Err(BcmrError::InvalidInput(format!(
-            "Invalid {} mode '{}'. Supported modes: force, disable, never, auto.",
-            name, other
-        ))
-        .into()),
-    }
-}
-
-pub(crate) const POWERSHELL_REMOTE_INJECT: &str = r#"    $tokens = $commandAst.ToString() -split '\s+'
-    if ($wordToComplete -match '.+:.+' -and $tokens.Count -ge 2 -and ($tokens[1] -eq 'copy' -or $tokens[1] -eq 'move')) {
+const POWERSHELL_REMOTE_INJECT: &str = r#"    $tokens = $commandAst.ToString() -split '\s+'
+    if ($wordToComplete -match '.+:.+' -and $tokens.Count -ge 2 -and ($tokens[1] -in 'copy', 'cp', 'move', 'mv')) {
         $results = bcmr __complete-remote $wordToComplete 2>$null
         if ($results) {
             $results | ForEach-Object {
@@ -24,11 +11,36 @@ pub(crate) const POWERSHELL_REMOTE_INJECT: &str = r#"    $tokens = $commandAst.T
         }
     }"#;
 
-pub(crate) fn build_completion_command() -> clap::Command {
-    let full = <cli::Cli as clap::CommandFactory>::command();
+/// The script `bcmr completions SHELL` prints: clap's completions plus the
+/// remote path hook for copy and move.
+pub(crate) fn completion_script(shell: clap_complete::Shell) -> String {
+    let mut cmd = build_completion_command();
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, &mut cmd, "bcmr", &mut buf);
+    let base = String::from_utf8(buf).expect("clap generated invalid UTF-8");
+
+    if shell == clap_complete::Shell::PowerShell {
+        base.replacen(
+            "param($wordToComplete, $commandAst, $cursorPosition)\n",
+            &format!(
+                "param($wordToComplete, $commandAst, $cursorPosition)\n{}\n",
+                POWERSHELL_REMOTE_INJECT
+            ),
+            1,
+        )
+    } else {
+        base + remote_completion_script(&shell)
+    }
+}
+
+fn build_completion_command() -> clap::Command {
+    let mut full = <cli::Cli as clap::CommandFactory>::command();
+    // Copies global options down into each subcommand, where the shells
+    // complete them. It also adds `help`, which the new root adds again.
+    full.build();
     let visible: Vec<clap::Command> = full
         .get_subcommands()
-        .filter(|s| !s.is_hide_set())
+        .filter(|s| !s.is_hide_set() && s.get_name() != "help")
         .cloned()
         .collect();
     let mut cmd = clap::Command::new("bcmr");
@@ -38,7 +50,7 @@ pub(crate) fn build_completion_command() -> clap::Command {
     cmd
 }
 
-pub(crate) fn remote_completion_script(shell: &clap_complete::Shell) -> &'static str {
+fn remote_completion_script(shell: &clap_complete::Shell) -> &'static str {
     use clap_complete::Shell;
     match shell {
         Shell::Zsh => {
@@ -46,7 +58,7 @@ pub(crate) fn remote_completion_script(shell: &clap_complete::Shell) -> &'static
 
 _bcmr_with_remote() {
     local cur="${words[CURRENT]}"
-    if [[ "$cur" == *:* ]] && [[ "${words[2]}" == "copy" || "${words[2]}" == "move" ]]; then
+    if [[ "$cur" == *:* ]] && [[ "${words[2]}" == (copy|cp|move|mv) ]]; then
         local -a results
         results=("${(@f)$(bcmr __complete-remote "$cur" 2>/dev/null)}")
         if [[ ${#results[@]} -gt 0 && -n "${results[1]}" ]]; then
@@ -65,7 +77,7 @@ compdef _bcmr_with_remote bcmr
 _bcmr_with_remote() {
     local cur="${COMP_WORDS[COMP_CWORD]}"
     local cmd="${COMP_WORDS[1]}"
-    if [[ "$cur" == *:* ]] && [[ "$cmd" == "copy" || "$cmd" == "move" ]]; then
+    if [[ "$cur" == *:* ]] && [[ "$cmd" == "copy" || "$cmd" == "cp" || "$cmd" == "move" || "$cmd" == "mv" ]]; then
         local IFS=$'\n'
         COMPREPLY=($(bcmr __complete-remote "$cur" 2>/dev/null))
         if [[ ${#COMPREPLY[@]} -gt 0 ]]; then
@@ -81,10 +93,53 @@ complete -F _bcmr_with_remote bcmr
         Shell::Fish => {
             r#"
 
-complete -c bcmr -n '__fish_seen_subcommand_from copy move; and string match -q "*:*" -- (commandline -ct)' -f -a '(bcmr __complete-remote (commandline -ct) 2>/dev/null)'
+complete -c bcmr -n '__fish_seen_subcommand_from copy cp move mv; and string match -q "*:*" -- (commandline -ct)' -f -a '(bcmr __complete-remote (commandline -ct) 2>/dev/null)'
 "#
         }
         Shell::PowerShell => "",
         _ => "",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap_complete::Shell;
+
+    /// Regenerates every script, so a subcommand or flag added to the CLI
+    /// without reaching the completions fails here.
+    #[test]
+    fn scripts_cover_every_visible_command_and_flag() {
+        let cmd = build_completion_command();
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let script = completion_script(shell);
+            for sub in cmd.get_subcommands() {
+                assert!(
+                    script.contains(sub.get_name()),
+                    "{shell}: no '{}'",
+                    sub.get_name()
+                );
+                for arg in sub.get_arguments().filter(|a| !a.is_hide_set()) {
+                    if let Some(long) = arg.get_long() {
+                        assert!(
+                            script.contains(long),
+                            "{shell}: no '--{long}' for {}",
+                            sub.get_name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mode_flags_complete_their_values() {
+        for shell in [Shell::Zsh, Shell::Fish] {
+            let script = completion_script(shell);
+            for mode in ["force", "auto", "disable", "never"] {
+                assert!(script.contains(mode), "{shell}: no '{mode}'");
+            }
+        }
+        assert!(completion_script(Shell::Zsh).contains("compdef _bcmr_with_remote bcmr"));
+    }
+}
@@ -2,12 +2,17 @@ use crate::commands;
 use crate::config::is_json_mode;
 use crate::ui::utils::format_bytes;
 use anyhow::Result;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 
+/// Asks on the terminal. Without one there is nobody to answer, so this
+/// fails rather than wait on a pipe that may never say anything.
 pub(crate) fn prompt_yes_no(message: &str) -> Result<bool> {
     if is_json_mode() {
         return Ok(true);
     }
+    if !io::stdin().is_terminal() {
+        anyhow::bail!("confirmation required but no TTY; pass --yes");
+    }
     print!("{} [y/N] ", message);
     io::stdout().flush()?;
     let mut input = String::new();
@@ -70,6 +75,20 @@ pub(crate) fn confirm_removal(files: &[commands::remove::FileToRemove]) -> Resul
     prompt_yes_no("\nDo you want to proceed?")
 }
 
+pub(crate) fn confirm_sync_delete(extra: &[std::path::PathBuf], items: usize) -> Result<bool> {
+    if is_json_mode() {
+        return Ok(true);
+    }
+    println!(
+        "\nThese destination entries are not in the source and will be removed ({} items):",
+        items
+    );
+    for path in extra {
+        println!("  {}", path.display());
+    }
+    prompt_yes_no("\nDo you want to proceed?")
+}
+
 pub(crate) fn first_display_name(paths: &[std::path::PathBuf]) -> Option<String> {
     paths.first().map(|p| {
         p.file_name()
@@ -1,25 +1,18 @@
-use crate::cli::Commands;
 use crate::commands;
-use crate::commands::remote_copy::is_plain_mode;
 use crate::config::is_json_mode;
+use crate::ui::progress::ProgressStyle;
 use crate::ui::runner::ProgressRunner;
 use anyhow::Result;
 
 pub(crate) fn start_scanning_runner(
-    args: &Commands,
+    style: ProgressStyle,
     operation: &str,
     first_display: Option<&str>,
 ) -> Result<Option<ProgressRunner>> {
     if !is_json_mode() {
         return Ok(None);
     }
-    let runner = ProgressRunner::new(
-        0,
-        is_plain_mode(args),
-        false,
-        true,
-        commands::copy::cleanup_partial_files,
-    )?;
+    let runner = ProgressRunner::new(0, style, false, true, commands::copy::cleanup_partial_files)?;
     {
         let mut p = runner.progress().lock();
         p.set_operation_type(operation);
@@ -33,7 +26,7 @@ pub(crate) fn start_scanning_runner(
 
 pub(crate) fn resume_or_new_runner(
     early: Option<ProgressRunner>,
-    args: &Commands,
+    style: ProgressStyle,
     operation: &str,
     first_display: Option<&str>,
     total_size: u64,
@@ -52,7 +45,7 @@ pub(crate) fn resume_or_new_runner(
     }
     let r = ProgressRunner::new(
         total_size,
-        is_plain_mode(args),
+        style,
         silent,
         is_json_mode(),
         commands::copy::cleanup_partial_files,
@@ -13,6 +13,7 @@ pub(crate) fn background_update_check(
             | Commands::CompleteRemote { .. }
             | Commands::Serve { .. }
             | Commands::Deploy { .. }
+            | Commands::Doctor { .. }
     ) {
         return None;
     }
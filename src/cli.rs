@@ -1,5 +1,12 @@
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use crate::config::{Config, ConfirmConfig, Confirmation, CopyConfig, FlagDefaults, RemoveConfig};
+use crate::core::bwlimit::{Limit, Schedule, Window};
+use crate::core::checksum::Algorithm;
+use crate::core::priority::IoClass;
+use crate::ui::progress::ProgressStyle;
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -12,12 +19,132 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
+    #[command(flatten)]
+    pub global: GlobalOpts,
+
+    #[arg(long = "_bg", hide = true)]
+    pub _bg: Option<String>,
+}
+
+/// Options accepted before or after the subcommand. `parse_args` gives
+/// copy, move, remove and check their own copy of them.
+#[derive(Args, Clone, Debug, Default)]
+#[command(next_help_heading = "Global options")]
+pub struct GlobalOpts {
     /// Output results as JSON; copy/move/remove detach to background (query with `bcmr status`)
     #[arg(long, global = true)]
     pub json: bool,
 
-    #[arg(long = "_bg", hide = true)]
-    pub _bg: Option<String>,
+    /// Print nothing but errors: no progress, summary, verbose or warning lines
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Append a timestamped record of every action to FILE (`-` for stderr)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+
+    /// Append a JSON audit record of this run and every file it touches to FILE
+    #[arg(long, global = true, value_name = "FILE")]
+    pub journal: Option<PathBuf>,
+
+    /// Stop the run if the journal cannot be written, instead of warning
+    #[arg(long, global = true, requires = "journal")]
+    pub journal_strict: bool,
+
+    /// Wait up to DURATION (30s, 10m, 1h) for another bcmr writing to the same destination
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+    pub wait_lock: Option<Duration>,
+
+    /// Run at lower CPU priority: niceness N, 10 when bare (--nice=N)
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        value_parser = clap::value_parser!(i32).range(-20..=19)
+    )]
+    pub nice: Option<i32>,
+
+    /// Disk priority on Linux: idle, best-effort or best-effort:0-7
+    #[arg(long, global = true, value_name = "CLASS")]
+    pub ionice: Option<IoClass>,
+
+    /// Minimum milliseconds between progress redraws (0 redraws on every update)
+    #[arg(
+        long,
+        global = true,
+        value_name = "MS",
+        value_parser = clap::value_parser!(u64).range(0..=crate::config::MAX_REFRESH_MS)
+    )]
+    pub refresh_ms: Option<u64>,
+
+    /// Draw progress on this file descriptor instead of stderr (or the terminal when stderr is redirected)
+    #[arg(
+        long,
+        global = true,
+        value_name = "FD",
+        value_parser = clap::value_parser!(i32).range(1..)
+    )]
+    pub progress_fd: Option<i32>,
+
+    /// Memory-map files to hash them instead of reading them (faster on local disks)
+    #[arg(long, global = true)]
+    pub hash_mmap: bool,
+
+    /// Exclude paths matching regex pattern
+    #[arg(short = 'e', long, global = true, value_name = "PATTERN")]
+    pub exclude: Option<Vec<String>>,
+
+    /// Ignore `[filters] default_excludes` for this run
+    #[arg(long, global = true)]
+    pub no_default_excludes: bool,
+
+    /// Refuse to run when the config file has an error or an unknown key, instead of warning
+    #[arg(long, global = true)]
+    pub strict_config: bool,
+
+    /// Skip the destination checks run first: a read-only mount, names too long for it
+    #[arg(long, global = true)]
+    pub no_preflight: bool,
+
+    /// Exit 0 rather than 10 when the run finished with warnings
+    #[arg(long, global = true)]
+    pub warnings_ok: bool,
+
+    /// Print transfer statistics after the run: counts, bytes written, reflinked and hard-linked
+    #[arg(long, global = true)]
+    pub stats: bool,
+
+    /// Layer `[profile.NAME]` from the config file over its other settings
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Progress display: fancy, inline, plain or none (default: `[progress] style`)
+    #[arg(long, global = true, value_name = "STYLE")]
+    pub progress: Option<ProgressStyle>,
+
+    /// Same as --progress inline
+    #[arg(
+        short,
+        long,
+        global = true,
+        alias = "plain-progress",
+        conflicts_with = "progress"
+    )]
+    pub tui: bool,
+
+    /// Run in dry-run mode (no changes)
+    #[arg(short = 'n', long, global = true)]
+    pub dry_run: bool,
+
+    /// Explain what is being done
+    #[arg(short = 'v', long, global = true)]
+    pub verbose: bool,
+
+    #[arg(long, global = true, hide = true, value_parser = parse_test_mode)]
+    pub test_mode: Option<TestMode>,
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -25,6 +152,8 @@ pub enum Shell {
     Bash,
     Zsh,
     Fish,
+    #[value(alias = "nushell")]
+    Nu,
 }
 
 #[derive(Clone, Debug)]
@@ -34,60 +163,87 @@ pub enum SparseMode {
     Never,
 }
 
+impl Shell {
+    /// The shell `bcmr completions` generates for; clap has none for nu.
+    pub fn completion_shell(&self) -> Option<clap_complete::Shell> {
+        match self {
+            Shell::Bash => Some(clap_complete::Shell::Bash),
+            Shell::Zsh => Some(clap_complete::Shell::Zsh),
+            Shell::Fish => Some(clap_complete::Shell::Fish),
+            Shell::Nu => None,
+        }
+    }
+}
+
 impl std::fmt::Display for Shell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Shell::Bash => write!(f, "bash"),
             Shell::Zsh => write!(f, "zsh"),
             Shell::Fish => write!(f, "fish"),
+            Shell::Nu => write!(f, "nu"),
         }
     }
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 pub struct CopyMoveArgs {
     /// Source files and destination directory (last argument is the destination)
     #[arg(required = true, num_args = 2..)]
     pub paths: Vec<PathBuf>,
 
     /// Recursively process directories
-    #[arg(short, long)]
+    #[arg(short, long, short_alias = 'R')]
     pub recursive: bool,
 
-    /// Preserve file attributes
-    #[arg(short, long)]
-    pub preserve: bool,
+    /// Preserve file attributes (--preserve=false overrides the config)
+    #[arg(
+        short,
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL",
+        hide_possible_values = true
+    )]
+    pub preserve: Option<bool>,
 
     /// Overwrite existing files
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_clobber")]
     pub force: bool,
 
+    /// Skip destination files that already exist instead of failing (cp -n)
+    #[arg(
+        long,
+        overrides_with = "force",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL",
+        hide_possible_values = true
+    )]
+    pub no_clobber: Option<bool>,
+
+    /// Follow symbolic links in sources, which is what bcmr always does (-H is accepted too)
+    #[arg(short = 'L', long, short_alias = 'H')]
+    pub dereference: bool,
+
     /// Skip confirmation prompt when using force
     #[arg(short = 'y', long = "yes")]
     pub yes: bool,
 
-    /// Explain what is being done
-    #[arg(short = 'v', long)]
-    pub verbose: bool,
-
-    /// Exclude paths matching regex pattern
-    #[arg(short = 'e', long)]
-    pub exclude: Option<Vec<String>>,
-
-    /// Enable inline TUI mode (classic 3-line display)
-    #[arg(short, long)]
-    pub tui: bool,
-
-    /// Run in dry-run mode (no changes)
-    #[arg(short = 'n', long)]
-    pub dry_run: bool,
-
-    #[arg(long, hide = true, value_parser = parse_test_mode)]
-    pub test_mode: Option<TestMode>,
-
-    /// Verify file integrity after operation
-    #[arg(short = 'V', long, default_value_t = false)]
-    pub verify: bool,
+    /// Verify file integrity after operation (the source is hashed as it is
+    /// copied; only the destination is read again)
+    #[arg(
+        short = 'V',
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL",
+        hide_possible_values = true
+    )]
+    pub verify: Option<bool>,
 
     /// Resume interrupted operation
     #[arg(short = 'C', long, default_value_t = false)]
@@ -97,19 +253,60 @@ pub struct CopyMoveArgs {
     #[arg(short = 's', long, default_value_t = false)]
     pub strict: bool,
 
+    /// With --verify, read the destination back with O_DIRECT (or F_NOCACHE)
+    /// so the check sees the disk rather than the page cache; slower
+    #[arg(long, requires = "verify")]
+    pub verify_direct: bool,
+
+    /// Files hashed at once by --verify while the copy goes on (default: CPU count)
+    #[arg(long, value_name = "N", requires = "verify")]
+    pub verify_jobs: Option<usize>,
+
+    /// With --verify, write a JSON-lines record of every verified file to FILE
+    #[arg(long, value_name = "FILE", requires = "verify")]
+    pub verify_report: Option<PathBuf>,
+
+    /// Hash for --verify and --strict: blake3 (default), sha256 or xxh3
+    #[arg(long, value_name = "ALGO")]
+    pub checksum_algo: Option<Algorithm>,
+
     /// Append data to existing file (ignores mtime, checks size only)
     #[arg(short = 'a', long, default_value_t = false)]
     pub append: bool,
 
     /// Sync data to disk after operation (fsync)
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL",
+        hide_possible_values = true
+    )]
+    pub sync: Option<bool>,
+
+    /// On Ctrl+C or SIGTERM, leave the file being written in place instead of removing it
     #[arg(long, default_value_t = false)]
-    pub sync: bool,
+    pub keep_partial: bool,
 
-    /// Parallel local file copies (default: CPU count, capped at 8)
+    /// Parallel local file copies (default: `[copy] jobs`, else CPU count capped at 8)
     #[arg(short = 'j', long = "jobs")]
     pub jobs: Option<usize>,
 
-    /// Wire compression: auto, zstd, lz4, none
+    /// Threads for the size scan before a move or upload (default: CPU count, capped at 8)
+    #[arg(long, value_name = "N")]
+    pub scan_jobs: Option<usize>,
+
+    /// Cap throughput at RATE bytes/s shared by all workers (10M, 500K; 0 = unlimited)
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    pub bwlimit: Option<u64>,
+
+    /// Vary the cap by local time, e.g. "08:00-18:00=5M,18:00-08:00=0"
+    #[arg(long, value_name = "SPEC", value_parser = parse_schedule, conflicts_with = "bwlimit")]
+    pub bwlimit_schedule: Option<Schedule>,
+
+    /// Wire compression: auto, zstd, lz4, none. On a local single-file copy,
+    /// zstd[:LEVEL] writes the destination as a .zst file
     #[arg(long, default_value = "auto")]
     pub compress: String,
 
@@ -120,6 +317,9 @@ pub struct CopyMoveArgs {
     /// Data-plane transport: ssh (default) or direct (AES-256-GCM TCP)
     #[arg(long, value_enum, default_value_t = DirectMode::Ssh)]
     pub direct: DirectMode,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
@@ -128,23 +328,655 @@ pub enum DirectMode {
     Direct,
 }
 
+/// What --reflink and --sparse accept; listed so shells can complete them.
+const MODE_VALUES: [&str; 4] = ["force", "auto", "disable", "never"];
+
+#[derive(Args, Debug)]
+pub struct CopyArgs {
+    #[command(flatten)]
+    pub common: CopyMoveArgs,
+
+    /// Copy-on-Write (reflink): force, auto, disable
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "auto",
+        ignore_case = true,
+        value_parser = MODE_VALUES
+    )]
+    pub reflink: Option<String>,
+
+    /// Sparse file creation: force, auto, disable
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "auto",
+        ignore_case = true,
+        value_parser = MODE_VALUES
+    )]
+    pub sparse: Option<String>,
+
+    /// Number of parallel connections (default from scp.parallel_transfers)
+    #[arg(short = 'P', long)]
+    pub parallel: Option<usize>,
+
+    /// Refuse host-to-host copies instead of relaying them through this machine
+    #[arg(long)]
+    pub no_remote_relay: bool,
+
+    /// Hash a URL download must match (hex, in --checksum-algo); implies --verify
+    #[arg(long, value_name = "HEX")]
+    pub expected_hash: Option<String>,
+
+    /// Extra HTTP request header for URL sources, e.g. "Authorization: Bearer TOKEN"
+    #[arg(long = "header", value_name = "NAME: VALUE")]
+    pub headers: Vec<String>,
+
+    /// Write the sources into a tar archive at the destination (- for stdout);
+    /// implied by a destination ending in .tar when a source is a directory
+    #[arg(long, conflicts_with = "from_tar")]
+    pub to_tar: bool,
+
+    /// Extract the source, a tar archive (- for stdin), into the destination directory
+    #[arg(long)]
+    pub from_tar: bool,
+
+    /// Decompress the source on the way, by its .zst extension or as --decompress=zstd
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    pub decompress: Option<String>,
+
+    /// Allow sources that are block devices (disks, partitions), read to their full size
+    #[arg(long)]
+    pub device_source: bool,
+
+    /// Open up source directories you own but cannot read (mode 0000) for the
+    /// copy, then give them their mode back (default: on with --preserve)
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_name = "BOOL",
+        hide_possible_values = true
+    )]
+    pub fix_perms: Option<bool>,
+
+    /// Skip, with a warning, files whose name or path is too long for the
+    /// destination filesystem, instead of refusing the copy
+    #[arg(long)]
+    pub skip_too_long: bool,
+}
+
+impl From<&MoveArgs> for CopyArgs {
+    /// The copy a cross-device move falls back to: the move's own flags and
+    /// none of the copy-only ones.
+    fn from(args: &MoveArgs) -> Self {
+        CopyArgs {
+            common: args.common.clone(),
+            reflink: None,
+            sparse: None,
+            parallel: None,
+            no_remote_relay: false,
+            expected_hash: None,
+            headers: Vec::new(),
+            to_tar: false,
+            from_tar: false,
+            decompress: None,
+            device_source: false,
+            fix_perms: None,
+            skip_too_long: false,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MoveArgs {
+    #[command(flatten)]
+    pub common: CopyMoveArgs,
+}
+
+/// What `--preserve-root` protects. `Root` is what the bare flag means.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreserveRoot {
+    #[value(hide = true)]
+    Root,
+    /// Also refuse arguments mounted from a different device than their parent
+    All,
+}
+
+const RM_COMPAT_HELP: &str = "\
+rm compatibility:
+  -r, -R, --recursive      supported
+  -d, --dir                supported
+  -f, --force              supported
+  -i, --interactive        supported (prompts per item)
+  -I                       supported (one prompt for more than 3 items or -r)
+  -v, --verbose            supported
+  --one-file-system        supported
+  --preserve-root[=all]    supported (the default)
+  --no-preserve-root       supported
+  --interactive=WHEN       accepted with a warning, ignored
+  anything else            accepted with a warning, ignored
+
+Pass --strict-flags to turn the warnings into errors.";
+
+#[derive(Args, Debug)]
+#[command(after_long_help = RM_COMPAT_HELP)]
+pub struct RemoveArgs {
+    /// Files or directories to remove
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Recursively remove directories (like rm -r; -R works too)
+    #[arg(short, long, short_alias = 'R')]
+    pub recursive: bool,
+
+    /// Force removal without confirmation (like rm -f)
+    #[arg(short = 'f', long)]
+    pub force: bool,
+
+    /// Prompt once, only for more than three items or a recursive removal (like rm -I)
+    #[arg(short = 'I', overrides_with_all = ["force", "interactive"])]
+    pub interactive_once: bool,
+
+    /// Leave directories on other file systems alone when removing recursively
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// Refuse to remove '/' recursively (the default); `all` also refuses mount points
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "root",
+        overrides_with = "no_preserve_root"
+    )]
+    pub preserve_root: Option<PreserveRoot>,
+
+    /// Allow removing '/' recursively
+    #[arg(long)]
+    pub no_preserve_root: bool,
+
+    /// Reject rm options bcmr does not support instead of ignoring them with a warning
+    #[arg(long)]
+    pub strict_flags: bool,
+
+    /// Skip confirmation prompts, including those of -i
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Interactively prompt before removal
+    #[arg(short = 'i', long)]
+    pub interactive: bool,
+
+    /// Remove empty directories (like rmdir)
+    #[arg(short = 'd', long)]
+    pub dir: bool,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Source files and destination (last argument is the destination)
+    #[arg(required = true, num_args = 2..)]
+    pub paths: Vec<PathBuf>,
+
+    /// Recursively compare directories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+/// How `sync` decides that a file both trees have is already up to date.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyncCompare {
+    SizeAndMtime,
+    Checksum,
+    SizeOnly,
+    Mtime,
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    /// Source directory
+    pub src: PathBuf,
+
+    /// Destination directory, created when missing
+    pub dst: PathBuf,
+
+    /// Remove destination entries the source does not have, after copying
+    #[arg(long)]
+    pub delete: bool,
+
+    /// With --delete, go ahead even when the source is empty
+    #[arg(long, requires = "delete")]
+    pub force_delete: bool,
+
+    /// Compare files with equal sizes by BLAKE3 digest instead of mtime
+    #[arg(long, group = "compare")]
+    pub checksum: bool,
+
+    /// Treat files with equal sizes as up to date
+    #[arg(long, group = "compare")]
+    pub size_only: bool,
+
+    /// Treat files with equal modification times as up to date
+    #[arg(long, group = "compare")]
+    pub mtime: bool,
+
+    /// Verify file integrity after copying
+    #[arg(short = 'V', long)]
+    pub verify: bool,
+
+    /// Parallel local file copies (default: CPU count, capped at 8)
+    #[arg(short = 'j', long = "jobs")]
+    pub jobs: Option<usize>,
+
+    /// Skip the confirmation `[confirm] require_confirmation_for` asks for before --delete
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+impl From<&SyncArgs> for CopyArgs {
+    /// The copy behind a sync: recursive, attribute-preserving and free to
+    /// replace the files the comparison picked.
+    fn from(args: &SyncArgs) -> Self {
+        CopyArgs {
+            common: CopyMoveArgs {
+                paths: vec![args.src.clone(), args.dst.clone()],
+                recursive: true,
+                preserve: Some(true),
+                force: true,
+                yes: true,
+                no_clobber: None,
+                dereference: false,
+                verify: Some(args.verify),
+                verify_direct: false,
+                verify_jobs: None,
+                verify_report: None,
+                resume: false,
+                strict: false,
+                checksum_algo: None,
+                append: false,
+                sync: None,
+                keep_partial: false,
+                jobs: args.jobs,
+                scan_jobs: None,
+                bwlimit: None,
+                bwlimit_schedule: None,
+                compress: "auto".to_string(),
+                fast: false,
+                direct: DirectMode::Ssh,
+                global: args.global.clone(),
+            },
+            reflink: None,
+            sparse: None,
+            parallel: None,
+            no_remote_relay: false,
+            expected_hash: None,
+            headers: Vec::new(),
+            to_tar: false,
+            from_tar: false,
+            decompress: None,
+            device_source: false,
+            fix_perms: None,
+            skip_too_long: false,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Source directory to watch
+    pub src: PathBuf,
+
+    /// Destination directory kept current, created when missing
+    pub dst: PathBuf,
+
+    /// Remove destination entries when they go away in the source
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Quiet time (500ms, 2s) to wait for a burst of changes to settle before applying it
+    #[arg(long, value_name = "DURATION", default_value = "500ms", value_parser = parse_duration)]
+    pub debounce: Duration,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+impl WatchArgs {
+    /// The sync that brings `dst` (under the watched destination) in line
+    /// with `src`, as `bcmr sync` with the watch's options would.
+    pub fn sync_args(&self, src: PathBuf, dst: PathBuf) -> SyncArgs {
+        SyncArgs {
+            src,
+            dst,
+            delete: self.delete,
+            force_delete: false,
+            checksum: false,
+            size_only: false,
+            mtime: false,
+            verify: false,
+            jobs: None,
+            // Nobody is there to answer between changes.
+            yes: true,
+            global: self.global.clone(),
+        }
+    }
+
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
+    }
+}
+
+impl From<&SyncArgs> for RemoveArgs {
+    /// The removal --delete runs: recursive and without prompts, since the
+    /// sync already decided what goes.
+    fn from(args: &SyncArgs) -> Self {
+        RemoveArgs {
+            paths: vec![args.dst.clone()],
+            recursive: true,
+            force: true,
+            interactive_once: false,
+            one_file_system: false,
+            preserve_root: None,
+            no_preserve_root: false,
+            strict_flags: false,
+            yes: true,
+            interactive: false,
+            dir: false,
+            global: args.global.clone(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Source tree
+    pub src: PathBuf,
+
+    /// Destination tree to compare against the source
+    pub dst: PathBuf,
+
+    /// Recursively compare directories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Also compare SHA-256 digests of files whose sizes match
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Files hashed at once with --hash (default: CPU count, capped at 8)
+    #[arg(long, value_name = "N")]
+    pub hash_jobs: Option<usize>,
+
+    /// With --hash, write a JSON-lines record of every compared file to FILE
+    #[arg(long, value_name = "FILE", requires = "hash")]
+    pub verify_report: Option<PathBuf>,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct DuArgs {
+    /// Files or directories to measure
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Measure directories recursively
+    #[arg(short, long, short_alias = 'R')]
+    pub recursive: bool,
+
+    /// Also show the total of each directory down to depth N
+    #[arg(short = 'd', long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Count file lengths, which is what a copy moves (the default)
+    #[arg(long, group = "usage")]
+    pub apparent: bool,
+
+    /// Count allocated disk blocks, hardlinked files once
+    #[arg(long, group = "usage")]
+    pub blocks: bool,
+
+    /// List the N largest files found
+    #[arg(long, value_name = "N")]
+    pub files: Option<usize>,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct UndoArgs {
+    /// Operation to undo, or a unique prefix of its id (see --list)
+    pub id: Option<String>,
+
+    /// Undo the most recent operation not undone yet (the default)
+    #[arg(long, conflicts_with = "id")]
+    pub last: bool,
+
+    /// List the journaled operations instead, newest last
+    #[arg(long, conflicts_with_all = ["id", "last"])]
+    pub list: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JournalAction {
+    /// Print a journal as one readable line per record
+    Show {
+        /// Journal file
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// List the files bcmr reads its settings from, in order, and which exist
+    Path,
+    /// Print the settings in effect, merged from every config file, as TOML
+    Show {
+        /// Print the built-in defaults instead
+        #[arg(long)]
+        default: bool,
+    },
+    /// Write a commented config file with every default to ~/.config/bcmr
+    Init {
+        /// Replace a config file that already exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Open the config file in $VISUAL or $EDITOR, creating it if needed
+    Edit,
+}
+
+/// A copy path `bcmr bench` can time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BenchEngine {
+    /// In-kernel copy (copy_file_range on Linux)
+    Kernel,
+    /// Read/write loop through bcmr's buffers, holes detected
+    Stream,
+    /// Copy-on-write clone
+    Reflink,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Data to copy per run, e.g. 512M or 2G
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    pub size: u64,
+
+    /// Number of files the data is split into
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub files: u64,
+
+    /// Directory to run in; the test data and copies go in a scratch directory under it
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Copy paths to time
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "kernel,stream,reflink"
+    )]
+    pub engines: Vec<BenchEngine>,
+}
+
+impl From<BenchEngine> for CopyArgs {
+    /// A plain file copy, with --reflink and --sparse set so that it takes
+    /// the engine's path and no other.
+    fn from(engine: BenchEngine) -> Self {
+        let (reflink, sparse) = match engine {
+            BenchEngine::Kernel => ("never", "never"),
+            BenchEngine::Stream => ("never", "auto"),
+            BenchEngine::Reflink => ("force", "never"),
+        };
+        CopyArgs {
+            common: CopyMoveArgs {
+                paths: Vec::new(),
+                recursive: false,
+                preserve: None,
+                force: true,
+                yes: true,
+                no_clobber: None,
+                dereference: false,
+                verify: None,
+                verify_direct: false,
+                verify_jobs: None,
+                verify_report: None,
+                resume: false,
+                strict: false,
+                checksum_algo: None,
+                append: false,
+                sync: None,
+                keep_partial: false,
+                jobs: None,
+                scan_jobs: None,
+                bwlimit: None,
+                bwlimit_schedule: None,
+                compress: "auto".to_string(),
+                fast: false,
+                direct: DirectMode::Ssh,
+                global: GlobalOpts::default(),
+            },
+            reflink: Some(reflink.to_string()),
+            sparse: Some(sparse.to_string()),
+            parallel: None,
+            no_remote_relay: false,
+            expected_hash: None,
+            headers: Vec::new(),
+            to_tar: false,
+            from_tar: false,
+            decompress: None,
+            device_source: false,
+            fix_perms: None,
+            skip_too_long: false,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// Files or directories to list
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// List directories recursively
+    #[arg(short, long, short_alias = 'R')]
+    pub recursive: bool,
+
+    /// List what --exclude filters out instead, with the pattern that caught each entry
+    #[arg(long)]
+    pub excluded: bool,
+
+    /// End each path with NUL instead of a newline (for xargs -0)
+    #[arg(short = '0', long = "null")]
+    pub null: bool,
+
+    /// Print only the number of files and directories and their size
+    #[arg(long, conflicts_with = "null")]
+    pub count: bool,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
+#[derive(Args, Debug)]
+pub struct ChecksumArgs {
+    /// File or directory to hash, or the manifest to read with -c
+    pub path: PathBuf,
+
+    /// Check the files a manifest lists instead of writing one (exit 1 when
+    /// some differ, 2 when any are missing or unreadable)
+    #[arg(short = 'c', long, conflicts_with_all = ["recursive", "output"])]
+    pub check: bool,
+
+    /// Directory the manifest's paths are relative to (default: the
+    /// manifest's own directory)
+    #[arg(long, value_name = "DIR", requires = "check")]
+    pub root: Option<PathBuf>,
+
+    /// Skip files the manifest lists that no longer exist
+    #[arg(long, requires = "check")]
+    pub ignore_missing: bool,
+
+    /// Hash every file under a directory
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Write the manifest to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Hash algorithm: sha256 (default), blake3 or xxh3; -c takes it from
+    /// the manifest's header unless given
+    #[arg(long, value_name = "ALGO")]
+    pub algo: Option<Algorithm>,
+
+    /// Files hashed at once (default: CPU count, capped at 8)
+    #[arg(short = 'j', long = "jobs", value_name = "N")]
+    pub jobs: Option<usize>,
+
+    #[arg(skip)]
+    pub global: GlobalOpts,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Initialize shell integration
     Init {
-        /// Shell to initialize (bash, zsh, fish)
+        /// Shell to initialize (bash, zsh, fish, nu)
         shell: Shell,
 
-        /// Command prefix (base for aliases; empty = no prefix)
+        /// Command prefix (deprecated: use --prefix, which wins over it)
         #[arg(long, num_args = 0..=1, default_missing_value = "")]
         cmd: Option<String>,
 
-        /// Explicit command prefix (overrides cmd if present)
-        #[arg(long, requires = "cmd")]
+        /// Prefix for the cp, mv and rm commands, e.g. b for bcp
+        #[arg(long)]
         prefix: Option<String>,
 
-        /// Command suffix
-        #[arg(long, requires = "cmd")]
+        /// Suffix for the cp, mv and rm commands, e.g. + for cp+
+        #[arg(long)]
         suffix: Option<String>,
 
         /// Path to add to PATH
@@ -154,31 +986,43 @@ pub enum Commands {
         /// No command prefix
         #[arg(long)]
         no_cmd: bool,
-    },
 
-    /// Copy files or directories
-    Copy {
-        #[command(flatten)]
-        args: CopyMoveArgs,
+        /// Load tab completion for bcmr and the commands it defines (the default)
+        #[arg(long, overrides_with = "no_completions")]
+        completions: bool,
+
+        /// Leave tab completion out of the script
+        #[arg(long, overrides_with = "completions")]
+        no_completions: bool,
+
+        /// Print a script removing the commands these --cmd, --prefix and --suffix define
+        #[arg(long, conflicts_with_all = ["path", "no_cmd", "completions"])]
+        uninstall: bool,
+
+        /// Run this bcmr binary by its full path instead of looking bcmr up on PATH
+        #[arg(long, conflicts_with = "uninstall")]
+        absolute_path: bool,
 
-        /// Copy-on-Write (reflink): force, auto, disable
-        #[arg(long, num_args = 0..=1, default_missing_value = "auto")]
-        reflink: Option<String>,
+        /// Define the commands in scripts and other non-interactive shells too
+        #[arg(long, conflicts_with = "uninstall")]
+        always: bool,
 
-        /// Sparse file creation: force, auto, disable
-        #[arg(long, num_args = 0..=1, default_missing_value = "auto")]
-        sparse: Option<String>,
+        /// Replace aliases and functions that already use the names instead of skipping those names
+        #[arg(long, conflicts_with = "uninstall")]
+        force_override: bool,
 
-        /// Number of parallel connections (default from scp.parallel_transfers)
-        #[arg(short = 'P', long)]
-        parallel: Option<usize>,
+        /// Say nothing when options bcmr lacks send a command to the system cp, mv or rm
+        #[arg(long, conflicts_with = "uninstall")]
+        quiet_fallback: bool,
     },
 
+    /// Copy files or directories
+    #[command(visible_alias = "cp")]
+    Copy(CopyArgs),
+
     /// Move files or directories
-    Move {
-        #[command(flatten)]
-        args: CopyMoveArgs,
-    },
+    #[command(visible_alias = "mv")]
+    Move(MoveArgs),
 
     /// Show status of background jobs
     Status {
@@ -189,6 +1033,12 @@ pub enum Commands {
     /// Check for updates and self-update
     Update,
 
+    /// Check the config, terminal, file system and shell setup bcmr runs with
+    Doctor {
+        /// Directory to probe for reflink and sparse file support (default: the current one)
+        dir: Option<PathBuf>,
+    },
+
     #[command(name = "__complete-remote", hide = true)]
     CompleteRemote { partial: String },
 
@@ -219,276 +1069,677 @@ pub enum Commands {
     },
 
     /// Compare source and destination without making changes
-    Check {
-        /// Source files and destination (last argument is the destination)
-        #[arg(required = true, num_args = 2..)]
-        paths: Vec<PathBuf>,
-
-        /// Recursively compare directories
-        #[arg(short, long)]
-        recursive: bool,
-
-        /// Exclude paths matching regex pattern
-        #[arg(short = 'e', long)]
-        exclude: Option<Vec<String>>,
-    },
+    Check(CheckArgs),
 
-    /// Remove files or directories
-    Remove {
-        /// Files or directories to remove
-        #[arg(required = true)]
-        paths: Vec<PathBuf>,
+    /// Compare two trees file by file: presence, size and optionally content
+    Verify(VerifyArgs),
 
-        /// Recursively remove directories (like rm -r)
-        #[arg(short, long)]
-        recursive: bool,
+    /// Make a destination directory match a source directory
+    Sync(SyncArgs),
 
-        /// Force removal without confirmation (like rm -f)
-        #[arg(short = 'f', long)]
-        force: bool,
+    /// Keep a destination directory in step with a source as it changes
+    Watch(WatchArgs),
 
-        /// Skip confirmation prompt
-        #[arg(short = 'y', long = "yes")]
-        yes: bool,
+    /// Write a sha256sum-style manifest of file digests
+    Checksum(ChecksumArgs),
 
-        /// Interactively prompt before removal
-        #[arg(short = 'i', long)]
-        interactive: bool,
+    /// List the paths copy, move and remove would see, excludes applied
+    List(ListArgs),
 
-        /// Explain what is being done
-        #[arg(short = 'v', long)]
-        verbose: bool,
+    /// Show how much files weigh, excluded paths left out as bcmr leaves them out
+    Du(DuArgs),
 
-        /// Remove empty directories (like rmdir)
-        #[arg(short = 'd', long)]
-        dir: bool,
+    /// Time bcmr's copy paths on generated data
+    Bench(BenchArgs),
 
-        /// Exclude files/directories that match these regex patterns
-        #[arg(short = 'e', long, value_name = "PATTERN", value_delimiter = ',')]
-        exclude: Option<Vec<String>>,
+    /// Reverse a journaled move, or remove the files a copy created
+    Undo(UndoArgs),
 
-        /// Enable inline TUI mode (classic 3-line display)
-        #[arg(short, long)]
-        tui: bool,
-
-        /// Run in dry-run mode (no changes)
-        #[arg(short = 'n', long)]
-        dry_run: bool,
+    /// Read a file written with the journal option
+    Journal {
+        #[command(subcommand)]
+        action: JournalAction,
+    },
 
-        #[arg(long, hide = true, value_parser = parse_test_mode)]
-        test_mode: Option<TestMode>,
+    /// Show, create or edit the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
+
+    /// Remove files or directories
+    #[command(visible_alias = "rm")]
+    Remove(RemoveArgs),
 }
 
 #[derive(Debug, Clone)]
 pub enum TestMode {
     Delay(u64),
-    SpeedLimit(u64),
     None,
 }
 
-impl Commands {
-    fn copy_move_args(&self) -> Option<&CopyMoveArgs> {
-        match self {
-            Commands::Copy { args, .. } | Commands::Move { args, .. } => Some(args),
-            _ => None,
+impl CopyMoveArgs {
+    pub fn get_test_mode(&self) -> TestMode {
+        self.global.test_mode.clone().unwrap_or(TestMode::None)
+    }
+
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        self.global
+            .compile_excludes_with_defaults(&crate::config::CONFIG.filters.default_excludes)
+    }
+
+    pub fn get_sources_and_dest(&self) -> std::result::Result<(&[PathBuf], &PathBuf), String> {
+        split_sources_and_dest(&self.paths)
+    }
+
+    pub fn should_prompt_for_overwrite(&self) -> bool {
+        self.force && !self.yes
+    }
+
+    pub fn progress_style(&self) -> ProgressStyle {
+        self.global.progress_style()
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.global.dry_run
+    }
+
+    pub fn is_verify(&self) -> bool {
+        self.verify.unwrap_or(false)
+    }
+
+    pub fn is_verify_direct(&self) -> bool {
+        self.verify_direct
+    }
+
+    pub fn is_resume(&self) -> bool {
+        self.resume
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn is_append(&self) -> bool {
+        self.append
+    }
+
+    pub fn checksum_algo(&self) -> Algorithm {
+        self.checksum_algo
+            .or(crate::config::CONFIG.copy.checksum_algo)
+            .unwrap_or(Algorithm::Blake3)
+    }
+
+    pub fn is_sync(&self) -> bool {
+        self.sync.unwrap_or(false)
+    }
+
+    pub fn keep_partial(&self) -> bool {
+        self.keep_partial
+    }
+
+    pub fn local_jobs(&self) -> usize {
+        self.jobs
+            .or(crate::config::CONFIG.copy.jobs)
+            .unwrap_or_else(|| num_cpus::get().clamp(1, 8))
+    }
+
+    pub fn verify_jobs(&self) -> usize {
+        self.verify_jobs.unwrap_or_else(num_cpus::get).max(1)
+    }
+
+    pub fn scan_jobs(&self) -> usize {
+        self.scan_jobs
+            .or(crate::config::CONFIG.copy.scan_jobs)
+            .unwrap_or_else(|| num_cpus::get().clamp(1, 8))
+            .max(1)
+    }
+
+    /// `--bwlimit` or `--bwlimit-schedule`, else `[copy] bwlimit_schedule`,
+    /// else `[copy] bwlimit`.
+    pub fn bwlimit(&self) -> Result<Limit, String> {
+        self.bwlimit_over(&crate::config::CONFIG.copy)
+    }
+
+    fn bwlimit_over(&self, config: &CopyConfig) -> Result<Limit, String> {
+        if let Some(rate) = self.bwlimit {
+            return Ok(Limit::Rate(rate));
+        }
+        if let Some(schedule) = &self.bwlimit_schedule {
+            return Ok(Limit::Schedule(schedule.clone()));
+        }
+        if let Some(spec) = config.bwlimit_schedule.as_deref() {
+            return parse_schedule(spec).map(Limit::Schedule);
         }
+        Ok(Limit::Rate(config.bwlimit.as_ref().map_or(0, |r| r.bytes)))
     }
 
-    pub fn get_test_mode(&self) -> TestMode {
-        match self {
-            Commands::Copy { args, .. } | Commands::Move { args, .. } => {
-                args.test_mode.clone().unwrap_or(TestMode::None)
-            }
-            Commands::Remove { test_mode, .. } => test_mode.clone().unwrap_or(TestMode::None),
-            _ => TestMode::None,
+    pub fn compression_caps(&self) -> u8 {
+        use crate::core::protocol::{CAP_LZ4, CAP_ZSTD};
+        let algo = self.compress.to_lowercase();
+        match algo.split(':').next().unwrap_or_default() {
+            "none" | "off" | "disable" => 0,
+            "lz4" => CAP_LZ4,
+            "zstd" => CAP_ZSTD,
+            _ => CAP_LZ4 | CAP_ZSTD,
         }
     }
 
+    pub fn protocol_caps(&self) -> u8 {
+        use crate::core::protocol::{CAP_DEDUP, CAP_FAST, CAP_SYNC};
+        let mut caps = self.compression_caps() | CAP_DEDUP;
+        if self.fast {
+            caps |= CAP_FAST;
+        }
+        if self.is_sync() {
+            caps |= CAP_SYNC;
+        }
+        caps
+    }
+
+    pub fn use_direct_tcp(&self) -> bool {
+        self.direct == DirectMode::Direct
+    }
+
+    pub fn is_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    pub fn is_preserve(&self) -> bool {
+        self.preserve.unwrap_or(false)
+    }
+
+    pub fn is_force(&self) -> bool {
+        self.force
+    }
+
+    pub fn is_no_clobber(&self) -> bool {
+        self.no_clobber.unwrap_or(false)
+    }
+
+    /// Fills the flags the command line left unset from a config section.
+    /// `-f` keeps a configured `no_clobber` from undoing it.
+    pub fn apply_defaults(&mut self, defaults: FlagDefaults) {
+        self.preserve = self.preserve.or(defaults.preserve);
+        self.verify = self.verify.or(defaults.verify);
+        self.sync = self.sync.or(defaults.sync);
+        if !self.force {
+            self.no_clobber = self.no_clobber.or(defaults.no_clobber);
+        }
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        self.global.verbose && !crate::config::is_quiet()
+    }
+}
+
+impl CopyArgs {
+    pub fn is_fix_perms(&self) -> bool {
+        self.fix_perms.unwrap_or_else(|| self.common.is_preserve())
+    }
+
+    pub fn get_reflink_mode(&self) -> Option<String> {
+        self.reflink.clone()
+    }
+
+    pub fn get_sparse_mode(&self) -> Option<String> {
+        self.sparse.clone()
+    }
+}
+
+impl RemoveArgs {
+    pub fn get_test_mode(&self) -> TestMode {
+        self.global.test_mode.clone().unwrap_or(TestMode::None)
+    }
+
     pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
-        let patterns = match self {
-            Commands::Copy { args, .. } | Commands::Move { args, .. } => args.exclude.as_ref(),
-            Commands::Remove { exclude, .. } | Commands::Check { exclude, .. } => exclude.as_ref(),
-            _ => None,
-        };
+        self.global
+            .compile_excludes_with_defaults(&crate::config::CONFIG.filters.default_excludes)
+    }
+
+    pub fn progress_style(&self) -> ProgressStyle {
+        self.global.progress_style()
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.global.dry_run
+    }
+
+    pub fn is_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    pub fn is_force(&self) -> bool {
+        self.force
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
 
-        match patterns {
-            Some(p) => p.iter().map(|s| regex::Regex::new(s)).collect(),
-            None => Ok(Vec::new()),
+    /// `[remove] interactive`, unless `-f` or `-I` asked for less. `-y`
+    /// answers the per-item prompts of `-i` as well, so a script without a
+    /// terminal has a way through them.
+    pub fn apply_defaults(&mut self, defaults: &RemoveConfig) {
+        if self.yes {
+            self.interactive = false;
+        } else if !self.force && !self.interactive_once {
+            self.interactive |= defaults.interactive;
         }
     }
 
-    pub fn is_yes(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.yes)
-            || matches!(self, Commands::Remove { yes: true, .. })
+    pub fn is_verbose(&self) -> bool {
+        self.global.verbose && !crate::config::is_quiet()
     }
 
-    pub fn should_prompt_for_overwrite(&self) -> bool {
-        match self {
-            Commands::Copy { args, .. } | Commands::Move { args, .. } => args.force && !args.yes,
-            Commands::Remove {
-                force, interactive, ..
-            } => !*force && *interactive,
-            _ => false,
+    pub fn is_dir_only(&self) -> bool {
+        self.dir
+    }
+
+    pub fn is_one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    /// None with --no-preserve-root, otherwise what --preserve-root asked
+    /// for (plain `/` protection when it was not given).
+    pub fn preserve_root(&self) -> Option<PreserveRoot> {
+        if self.no_preserve_root {
+            None
+        } else {
+            Some(self.preserve_root.unwrap_or(PreserveRoot::Root))
         }
     }
 
-    pub fn is_tui_mode(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.tui)
-            || matches!(self, Commands::Remove { tui: true, .. })
+    /// Whether to ask once before removing `count` items. -I narrows it to
+    /// what rm -I asks about; -y and -f skip it, as does -i for a single
+    /// item since that gets its own prompt. What `confirm` requires, a
+    /// recursive or a large removal, is asked even with -f.
+    pub fn confirms_up_front(&self, count: usize, confirm: &ConfirmConfig) -> bool {
+        if count == 0 || self.yes {
+            return false;
+        }
+        if self.interactive_once {
+            return count > 3 || self.recursive;
+        }
+        let required = (self.recursive && confirm.requires(Confirmation::RemoveRecursive))
+            || confirm.remove_large_threshold.is_some_and(|n| count > n);
+        required || (!self.force && (!self.interactive || count > 1))
     }
+}
+
+impl CheckArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
+    }
+
+    pub fn get_sources_and_dest(&self) -> std::result::Result<(&[PathBuf], &PathBuf), String> {
+        split_sources_and_dest(&self.paths)
+    }
+}
+
+impl VerifyArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
+    }
+
+    pub fn hash_jobs(&self) -> usize {
+        self.hash_jobs
+            .unwrap_or_else(|| num_cpus::get().clamp(1, 8))
+            .max(1)
+    }
+}
 
-    pub fn is_dry_run(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.dry_run)
-            || matches!(self, Commands::Remove { dry_run: true, .. })
+impl SyncArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
     }
 
-    pub fn get_sources_and_dest(&self) -> std::result::Result<(&[PathBuf], &PathBuf), String> {
-        let paths = match self {
-            Commands::Copy { args, .. } | Commands::Move { args, .. } => &args.paths,
-            Commands::Check { paths, .. } => paths,
-            _ => return Err("command does not have source/destination structure".to_string()),
-        };
-        let (dest, sources) = paths
-            .split_last()
-            .ok_or_else(|| "missing source/destination arguments".to_string())?;
-        Ok((sources, dest))
+    /// Whether to ask before --delete removes `count` entries.
+    pub fn confirms_delete(&self, count: usize, confirm: &ConfirmConfig) -> bool {
+        self.delete && count > 0 && !self.yes && confirm.requires(Confirmation::SyncDelete)
     }
 
-    pub fn is_verify(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.verify)
+    pub fn is_verbose(&self) -> bool {
+        self.global.verbose && !crate::config::is_quiet()
     }
 
-    pub fn is_resume(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.resume)
+    pub fn compare(&self) -> SyncCompare {
+        if self.checksum {
+            SyncCompare::Checksum
+        } else if self.size_only {
+            SyncCompare::SizeOnly
+        } else if self.mtime {
+            SyncCompare::Mtime
+        } else {
+            SyncCompare::SizeAndMtime
+        }
     }
+}
 
-    pub fn is_strict(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.strict)
+impl ListArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
     }
+}
 
-    pub fn is_append(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.append)
+impl DuArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
     }
+}
 
-    pub fn is_sync(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.sync)
+impl ChecksumArgs {
+    pub fn compile_excludes(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        compile_patterns(self.global.exclude.as_deref())
     }
 
-    pub fn local_jobs(&self) -> usize {
-        self.copy_move_args()
-            .and_then(|a| a.jobs)
+    pub fn jobs(&self) -> usize {
+        self.jobs
             .unwrap_or_else(|| num_cpus::get().clamp(1, 8))
+            .max(1)
     }
+}
 
-    pub fn compression_caps(&self) -> u8 {
-        use crate::core::protocol::{CAP_LZ4, CAP_ZSTD};
-        match self
-            .copy_move_args()
-            .map(|a| a.compress.as_str())
-            .unwrap_or("auto")
-            .to_lowercase()
-            .as_str()
-        {
-            "none" | "off" | "disable" => 0,
-            "lz4" => CAP_LZ4,
-            "zstd" => CAP_ZSTD,
-            _ => CAP_LZ4 | CAP_ZSTD,
+impl GlobalOpts {
+    /// `--progress` or `--tui`, else `[progress] style`.
+    pub fn progress_style(&self) -> ProgressStyle {
+        if self.tui {
+            return ProgressStyle::Inline;
         }
+        self.progress
+            .unwrap_or(crate::config::CONFIG.progress.style)
     }
 
-    pub fn protocol_caps(&self) -> u8 {
-        use crate::core::protocol::{CAP_DEDUP, CAP_FAST, CAP_SYNC};
-        let mut caps = self.compression_caps() | CAP_DEDUP;
-        if self.copy_move_args().is_some_and(|a| a.fast) {
-            caps |= CAP_FAST;
-        }
-        if self.is_sync() {
-            caps |= CAP_SYNC;
-        }
-        caps
+    /// `defaults` (`[filters] default_excludes`) ahead of `--exclude`,
+    /// unless `--no-default-excludes`. Every phase of copy, move and
+    /// remove matches against this one list, and the traversal is told
+    /// how many of its patterns are defaults so it can count what they
+    /// keep out.
+    fn compile_excludes_with_defaults(
+        &self,
+        defaults: &[String],
+    ) -> Result<Vec<regex::Regex>, regex::Error> {
+        let defaults = if self.no_default_excludes {
+            &[]
+        } else {
+            defaults
+        };
+        let mut excludes = compile_patterns(Some(defaults))?;
+        crate::core::traversal::set_default_excludes(excludes.len());
+        excludes.extend(compile_patterns(self.exclude.as_deref())?);
+        Ok(excludes)
     }
+}
 
-    pub fn use_direct_tcp(&self) -> bool {
-        matches!(
-            self.copy_move_args().map(|a| a.direct),
-            Some(DirectMode::Direct)
-        )
+fn compile_patterns(patterns: Option<&[String]>) -> Result<Vec<regex::Regex>, regex::Error> {
+    patterns
+        .unwrap_or_default()
+        .iter()
+        .map(|s| regex::Regex::new(s))
+        .collect()
+}
+
+fn split_sources_and_dest(
+    paths: &[PathBuf],
+) -> std::result::Result<(&[PathBuf], &PathBuf), String> {
+    let (dest, sources) = paths
+        .split_last()
+        .ok_or_else(|| "missing source/destination arguments".to_string())?;
+    Ok((sources, dest))
+}
+
+impl Cli {
+    /// Clap records global options on the top level wherever they were
+    /// typed; the operations read them from their own arguments.
+    fn share_globals(mut self) -> Self {
+        let global = self.global.clone();
+        match &mut self.command {
+            Commands::Copy(args) => args.common.global = global,
+            Commands::Move(args) => args.common.global = global,
+            Commands::Remove(args) => args.global = global,
+            Commands::Check(args) => args.global = global,
+            Commands::Verify(args) => args.global = global,
+            Commands::Checksum(args) => args.global = global,
+            Commands::Sync(args) => args.global = global,
+            Commands::Watch(args) => args.global = global,
+            Commands::Du(args) => args.global = global,
+            Commands::List(args) => args.global = global,
+            _ => {}
+        }
+        self
     }
 
-    pub fn get_reflink_mode(&self) -> Option<String> {
-        match self {
-            Commands::Copy { reflink, .. } => reflink.clone(),
-            _ => None,
+    /// Config defaults under flags the command line did not give.
+    fn apply_config(mut self, config: &Config) -> Self {
+        let assume_yes = config.confirm.assume_yes;
+        match &mut self.command {
+            Commands::Copy(args) => {
+                args.common.apply_defaults(config.copy.flags());
+                args.common.yes |= assume_yes;
+            }
+            Commands::Move(args) => {
+                args.common.apply_defaults(config.r#move);
+                args.common.yes |= assume_yes;
+            }
+            Commands::Remove(args) => {
+                args.apply_defaults(&config.remove);
+                args.yes |= assume_yes;
+            }
+            Commands::Sync(args) => args.yes |= assume_yes,
+            _ => {}
         }
+        self
     }
+}
 
-    pub fn get_sparse_mode(&self) -> Option<String> {
-        match self {
-            Commands::Copy { sparse, .. } => sparse.clone(),
-            _ => None,
+pub fn parse_args() -> Cli {
+    let mut argv: Vec<String> = std::env::args().collect();
+    if argv.get(1).is_some_and(|arg| arg == CHECK_ARGS) {
+        argv.remove(1);
+        let code = match check_args(argv) {
+            Ok(()) => 0,
+            Err(reason) => {
+                eprintln!("{}", reason);
+                2
+            }
+        };
+        std::process::exit(code);
+    }
+    match parse_lenient(argv) {
+        Ok((cli, ignored)) => {
+            for flag in ignored {
+                eprintln!(
+                    "warning: ignoring unsupported option '{}' (use --strict-flags to reject it)",
+                    flag
+                );
+            }
+            if let Some(name) = &cli.global.profile {
+                if let Err(msg) = crate::config::select_profile(name) {
+                    Cli::command().error(ErrorKind::InvalidValue, msg).exit();
+                }
+            }
+            cli.apply_config(&crate::config::CONFIG)
         }
+        Err(e) => e.exit(),
     }
+}
 
-    pub fn get_parallel(&self) -> Option<usize> {
-        match self {
-            Commands::Copy { parallel, .. } => *parallel,
-            _ => None,
+/// `bcmr --check-args copy ARGS...` parses the rest as a run would and
+/// does nothing else, for the functions `bcmr init` writes to ask whether
+/// bcmr takes their arguments. Handled before clap so a refused command
+/// line still gets the probe's exit code.
+const CHECK_ARGS: &str = "--check-args";
+
+/// Whether bcmr would run `argv`; if not, the first line of clap's error.
+fn check_args(argv: Vec<String>) -> Result<(), String> {
+    let Err(e) = parse_lenient(argv) else {
+        return Ok(());
+    };
+    match e.kind() {
+        ErrorKind::DisplayHelp
+        | ErrorKind::DisplayVersion
+        | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => Ok(()),
+        _ => {
+            let text = e.to_string();
+            let line = text.lines().next().unwrap_or_default();
+            Err(line.trim_start_matches("error: ").to_string())
         }
     }
+}
 
-    pub fn is_recursive(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.recursive)
-            || matches!(
-                self,
-                Commands::Remove {
-                    recursive: true,
-                    ..
-                } | Commands::Check {
-                    recursive: true,
-                    ..
+/// Parses `argv`, letting `remove` drop options it does not know so rm
+/// invocations from existing scripts still run. Returns the options that
+/// were dropped. Anything else, or a run with --strict-flags, gets the
+/// error clap reported for the full command line.
+fn parse_lenient(argv: Vec<String>) -> Result<(Cli, Vec<String>), clap::Error> {
+    let first_error = match Cli::try_parse_from(&argv) {
+        Ok(cli) => return Ok((cli.share_globals(), Vec::new())),
+        Err(e) => e,
+    };
+    let mut argv = argv;
+    let mut ignored = Vec::new();
+    loop {
+        let err = match Cli::try_parse_from(&argv) {
+            Ok(cli) => match cli.command {
+                Commands::Remove(ref args) if !args.strict_flags => {
+                    return Ok((cli.share_globals(), ignored))
                 }
-            )
+                _ => return Err(first_error),
+            },
+            Err(e) => e,
+        };
+        let Some(ContextValue::String(flag)) = err.get(ContextKind::InvalidArg) else {
+            return Err(first_error);
+        };
+        // A value given to a plain switch (rm's --interactive=never) is
+        // reported against the switch; the whole `--flag=value` goes.
+        let flag = match (err.kind(), err.get(ContextKind::InvalidValue)) {
+            (ErrorKind::UnknownArgument, _) => flag.clone(),
+            (ErrorKind::TooManyValues, Some(ContextValue::String(value))) => {
+                format!("{}={}", flag, value)
+            }
+            _ => return Err(first_error),
+        };
+        match drop_flag(&mut argv, &flag) {
+            Some(dropped) => ignored.push(dropped),
+            None => return Err(first_error),
+        }
     }
+}
 
-    pub fn is_preserve(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.preserve)
+/// Takes `flag` out of `argv`: a whole argument (or `--flag=value`), or a
+/// single letter of a bundle like `-rfx`. Returns what was taken out.
+fn drop_flag(argv: &mut Vec<String>, flag: &str) -> Option<String> {
+    let end = argv.iter().position(|a| a == "--").unwrap_or(argv.len());
+    let whole = argv[1..end]
+        .iter()
+        .position(|a| a == flag || a.split_once('=').is_some_and(|(name, _)| name == flag));
+    if let Some(i) = whole {
+        return Some(argv.remove(i + 1));
     }
-
-    pub fn is_force(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.force)
-            || matches!(self, Commands::Remove { force: true, .. })
+    let letter = flag.strip_prefix('-').filter(|l| l.chars().count() == 1)?;
+    for arg in &mut argv[1..end] {
+        let Some(bundle) = arg.strip_prefix('-').filter(|b| !b.starts_with('-')) else {
+            continue;
+        };
+        if bundle.contains(letter) {
+            *arg = format!("-{}", bundle.replacen(letter, "", 1));
+            return Some(flag.to_string());
+        }
     }
+    None
+}
 
-    pub fn is_interactive(&self) -> bool {
-        matches!(
-            self,
-            Commands::Remove {
-                interactive: true,
-                ..
-            }
-        )
-    }
+/// `4096`, `64K`, `512M`, `2G`: binary units, an optional `iB`/`B` after.
+pub(crate) fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I', 'K', 'M', 'G', 'T']);
+    let shift = match &upper[digits.len()..] {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => {
+            return Err(format!(
+                "Invalid size '{}'. Expected e.g. 4096, 64K, 512M or 2G",
+                s
+            ))
+        }
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("Invalid size '{}'. Expected e.g. 4096, 64K, 512M or 2G", s))?;
+    n.checked_mul(1 << shift)
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("Size '{}' is out of range", s))
+}
 
-    pub fn is_verbose(&self) -> bool {
-        self.copy_move_args().is_some_and(|a| a.verbose)
-            || matches!(self, Commands::Remove { verbose: true, .. })
+/// A `parse_size` per second, or `0` for no limit.
+pub(crate) fn parse_rate(s: &str) -> Result<u64, String> {
+    if s.trim() == "0" {
+        return Ok(0);
     }
+    parse_size(s)
+}
 
-    pub fn is_dir_only(&self) -> bool {
-        matches!(self, Commands::Remove { dir: true, .. })
-    }
+/// `08:00-18:00=5M,18:00-08:00=0`: comma-separated windows of local
+/// time, each with a `parse_rate`.
+fn parse_schedule(s: &str) -> Result<Schedule, String> {
+    let windows = s
+        .split(',')
+        .map(|entry| {
+            let invalid = || {
+                format!(
+                    "Invalid schedule entry '{}'. Expected HH:MM-HH:MM=RATE",
+                    entry.trim()
+                )
+            };
+            let (span, rate) = entry.split_once('=').ok_or_else(invalid)?;
+            let (start, end) = span.split_once('-').ok_or_else(invalid)?;
+            Ok(Window {
+                start: parse_clock(start).ok_or_else(invalid)?,
+                end: parse_clock(end).ok_or_else(invalid)?,
+                rate: parse_rate(rate)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Schedule::new(windows)
+}
 
-    pub fn get_remove_paths(&self) -> std::result::Result<&[PathBuf], String> {
-        match self {
-            Commands::Remove { paths, .. } => Ok(paths),
-            _ => Err("command does not support remove paths".to_string()),
-        }
+/// `HH:MM` as minutes past midnight; `24:00` is midnight too.
+fn parse_clock(s: &str) -> Option<u16> {
+    let (h, m) = s.trim().split_once(':')?;
+    if h.len() != 2 || m.len() != 2 {
+        return None;
+    }
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    match (h, m) {
+        (24, 0) => Some(0),
+        (0..=23, 0..=59) => Some(h * 60 + m),
+        _ => None,
     }
 }
 
-pub fn parse_args() -> Cli {
-    Cli::parse()
+/// `90`, `90s`, `500ms`, `10m`, `2h`: a bare number is seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let invalid = || format!("Invalid duration '{}'. Expected e.g. 30s, 10m or 1h", s);
+    let n: u64 = digits.parse().map_err(|_| invalid())?;
+    match &s[digits.len()..] {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(Duration::from_secs(n)),
+        "m" => n
+            .checked_mul(60)
+            .map(Duration::from_secs)
+            .ok_or_else(invalid),
+        "h" => n
+            .checked_mul(3600)
+            .map(Duration::from_secs)
+            .ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
 }
 
 fn parse_test_mode(s: &str) -> Result<TestMode, String> {
@@ -499,12 +1750,11 @@ fn parse_test_mode(s: &str) -> Result<TestMode, String> {
     if parts.len() == 2 {
         match (parts[0], parts[1].parse::<u64>()) {
             ("delay", Ok(ms)) => Ok(TestMode::Delay(ms)),
-            ("speed_limit", Ok(bps)) => Ok(TestMode::SpeedLimit(bps)),
             _ => Err(format!("Invalid test mode format: {}", s)),
         }
     } else {
         Err(format!(
-            "Invalid test mode '{}'. Expected: none, delay:<ms>, or speed_limit:<bps>",
+            "Invalid test mode '{}'. Expected: none or delay:<ms>",
             s
         ))
     }
@@ -514,6 +1764,31 @@ fn parse_test_mode(s: &str) -> Result<TestMode, String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("512m"), Ok(512 << 20));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("1TB"), Ok(1 << 40));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
     #[test]
     fn test_parse_test_mode_delay() {
         match parse_test_mode("delay:100").unwrap() {
@@ -523,11 +1798,46 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_test_mode_speed_limit() {
-        match parse_test_mode("speed_limit:1048576").unwrap() {
-            TestMode::SpeedLimit(bps) => assert_eq!(bps, 1048576),
-            _ => panic!("Expected SpeedLimit"),
-        }
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("10M"), Ok(10 << 20));
+        assert_eq!(parse_rate("500k"), Ok(500 << 10));
+        assert_eq!(parse_rate("0"), Ok(0));
+        assert!(parse_rate("fast").is_err());
+        // Throttling is a real option now, not a test mode.
+        assert!(parse_test_mode("speed_limit:1048576").is_err());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let s = parse_schedule("08:00-18:00=5M, 18:00-08:00=0").unwrap();
+        assert_eq!(
+            s,
+            Schedule::new(vec![
+                Window {
+                    start: 8 * 60,
+                    end: 18 * 60,
+                    rate: 5 << 20,
+                },
+                Window {
+                    start: 18 * 60,
+                    end: 8 * 60,
+                    rate: 0,
+                },
+            ])
+            .unwrap()
+        );
+        assert!(parse_schedule("00:00-24:00=1M").is_ok());
+        assert_eq!(
+            parse_schedule("08:00-18:00=5M,18:00-8:00=0").unwrap_err(),
+            "Invalid schedule entry '18:00-8:00=0'. Expected HH:MM-HH:MM=RATE"
+        );
+        assert!(parse_schedule("08:00-25:00=5M").is_err());
+        assert!(parse_schedule("08:00-18:00").is_err());
+        assert!(parse_schedule("08:00-18:00=fast,18:00-08:00=0").is_err());
+        assert_eq!(
+            parse_schedule("08:00-18:00=5M,17:30-08:00=0").unwrap_err(),
+            "Bandwidth schedule windows 08:00-18:00 and 17:30-08:00 overlap"
+        );
     }
 
     #[test]
@@ -547,76 +1857,90 @@ mod tests {
         CopyMoveArgs {
             paths,
             recursive: false,
-            preserve: false,
+            preserve: None,
             force: false,
             yes: false,
-            verbose: false,
-            exclude: None,
-            tui: false,
-            dry_run: false,
-            test_mode: None,
-            verify: false,
+            no_clobber: None,
+            dereference: false,
+            verify: None,
+            verify_direct: false,
+            verify_jobs: None,
+            verify_report: None,
             resume: false,
             strict: false,
+            checksum_algo: None,
             append: false,
-            sync: false,
+            sync: None,
+            keep_partial: false,
             jobs: None,
+            scan_jobs: None,
+            bwlimit: None,
+            bwlimit_schedule: None,
             compress: "auto".to_string(),
             fast: false,
             direct: DirectMode::Ssh,
+            global: GlobalOpts::default(),
         }
     }
 
     #[test]
     fn test_commands_copy_accessors() {
-        let cmd = Commands::Copy {
-            args: CopyMoveArgs {
+        let cmd = CopyArgs {
+            common: CopyMoveArgs {
                 recursive: true,
-                preserve: true,
+                preserve: Some(true),
                 force: true,
-                verbose: true,
-                exclude: Some(vec!["*.log".to_string()]),
-                dry_run: true,
-                verify: true,
+                verify: Some(true),
                 resume: true,
                 strict: true,
+                checksum_algo: Some(Algorithm::Xxh3),
+                global: GlobalOpts {
+                    verbose: true,
+                    exclude: Some(vec!["*.log".to_string()]),
+                    dry_run: true,
+                    ..GlobalOpts::default()
+                },
                 ..test_args(vec![PathBuf::from("src"), PathBuf::from("dst")])
             },
             reflink: Some("auto".to_string()),
             sparse: None,
             parallel: Some(4),
+            no_remote_relay: false,
+            expected_hash: None,
+            headers: Vec::new(),
+            to_tar: false,
+            from_tar: false,
+            decompress: None,
+            device_source: false,
+            fix_perms: None,
+            skip_too_long: false,
         };
 
-        assert!(cmd.is_recursive());
-        assert!(cmd.is_preserve());
-        assert!(cmd.is_force());
-        assert!(!cmd.is_yes());
-        assert!(cmd.is_verbose());
-        assert!(cmd.is_dry_run());
-        assert!(!cmd.is_tui_mode());
-        assert!(cmd.is_verify());
-        assert!(cmd.is_resume());
-        assert!(cmd.is_strict());
-        assert!(!cmd.is_append());
-        assert!(!cmd.is_sync());
+        assert!(cmd.common.is_recursive());
+        assert!(cmd.common.is_preserve());
+        assert!(cmd.common.is_force());
+        assert!(cmd.common.is_verbose());
+        assert!(cmd.common.is_dry_run());
+        assert!(!cmd.common.global.tui);
+        assert!(cmd.common.is_verify());
+        assert!(cmd.common.is_resume());
+        assert!(cmd.common.is_strict());
+        assert_eq!(cmd.common.checksum_algo(), Algorithm::Xxh3);
+        assert!(!cmd.common.is_append());
+        assert!(!cmd.common.is_sync());
         assert_eq!(cmd.get_reflink_mode(), Some("auto".to_string()));
         assert_eq!(cmd.get_sparse_mode(), None);
-        assert_eq!(cmd.get_parallel(), Some(4));
-        assert!(cmd.should_prompt_for_overwrite());
+        assert_eq!(cmd.parallel, Some(4));
+        assert!(cmd.common.should_prompt_for_overwrite());
     }
 
     #[test]
     fn test_commands_get_sources_and_dest() {
-        let cmd = Commands::Copy {
-            args: test_args(vec![
-                PathBuf::from("a"),
-                PathBuf::from("b"),
-                PathBuf::from("dest"),
-            ]),
-            reflink: None,
-            sparse: None,
-            parallel: None,
-        };
+        let cmd = test_args(vec![
+            PathBuf::from("a"),
+            PathBuf::from("b"),
+            PathBuf::from("dest"),
+        ]);
 
         let (sources, dest) = cmd.get_sources_and_dest().unwrap();
         assert_eq!(sources.len(), 2);
@@ -625,50 +1949,583 @@ mod tests {
 
     #[test]
     fn test_commands_remove_accessors() {
-        let cmd = Commands::Remove {
+        let cmd = RemoveArgs {
             paths: vec![PathBuf::from("file.txt")],
             recursive: false,
             force: true,
             yes: false,
             interactive: true,
-            verbose: false,
             dir: true,
-            exclude: None,
-            tui: false,
-            dry_run: false,
-            test_mode: None,
+            interactive_once: false,
+            one_file_system: false,
+            preserve_root: None,
+            no_preserve_root: false,
+            strict_flags: false,
+            global: GlobalOpts::default(),
         };
 
         assert!(cmd.is_force());
         assert!(cmd.is_interactive());
         assert!(cmd.is_dir_only());
         assert!(!cmd.is_recursive());
-        let paths = cmd.get_remove_paths().unwrap();
-        assert_eq!(paths.len(), 1);
     }
 
     #[test]
-    fn test_commands_non_file_defaults() {
-        let cmd = Commands::Update;
-        assert!(!cmd.is_recursive());
-        assert!(!cmd.is_force());
-        assert!(!cmd.is_preserve());
-        assert!(!cmd.is_verify());
-        assert!(!cmd.is_dry_run());
-        assert!(!cmd.is_verbose());
-        assert_eq!(cmd.get_parallel(), None);
+    fn test_flag_defaults_precedence() {
+        // (command line, config, result): the command line wins, then the
+        // config, then off.
+        let table = [
+            (None, None, false),
+            (None, Some(false), false),
+            (None, Some(true), true),
+            (Some(false), None, false),
+            (Some(false), Some(false), false),
+            (Some(false), Some(true), false),
+            (Some(true), None, true),
+            (Some(true), Some(false), true),
+            (Some(true), Some(true), true),
+        ];
+        for (flag, config, expected) in table {
+            let mut args = CopyMoveArgs {
+                preserve: flag,
+                verify: flag,
+                sync: flag,
+                no_clobber: flag,
+                ..test_args(vec![PathBuf::from("a"), PathBuf::from("b")])
+            };
+            args.apply_defaults(FlagDefaults {
+                preserve: config,
+                verify: config,
+                sync: config,
+                no_clobber: config,
+            });
+            let got = [
+                args.is_preserve(),
+                args.is_verify(),
+                args.is_sync(),
+                args.is_no_clobber(),
+            ];
+            assert_eq!(got, [expected; 4], "flag {flag:?}, config {config:?}");
+        }
+    }
+
+    #[test]
+    fn test_config_sections_reach_their_commands() {
+        let mut config = Config::default();
+        config.copy.verify = Some(true);
+        config.copy.no_clobber = Some(true);
+        config.r#move.preserve = Some(true);
+        config.remove.interactive = true;
+        let apply = |args: &[&str]| {
+            Cli::try_parse_from(args)
+                .unwrap()
+                .share_globals()
+                .apply_config(&config)
+                .command
+        };
+
+        let Commands::Copy(copy) = apply(&["bcmr", "copy", "a", "b"]) else {
+            panic!("Expected Copy");
+        };
+        assert!(copy.common.is_verify() && copy.common.is_no_clobber());
+        assert!(!copy.common.is_preserve());
+
+        let Commands::Copy(copy) = apply(&["bcmr", "copy", "--verify=false", "-f", "a", "b"])
+        else {
+            panic!("Expected Copy");
+        };
+        assert!(!copy.common.is_verify());
+        assert!(copy.common.is_force() && !copy.common.is_no_clobber());
+
+        let Commands::Move(mv) = apply(&["bcmr", "move", "a", "b"]) else {
+            panic!("Expected Move");
+        };
+        assert!(mv.common.is_preserve() && !mv.common.is_verify());
+
+        let Commands::Remove(rm) = apply(&["bcmr", "remove", "x"]) else {
+            panic!("Expected Remove");
+        };
+        assert!(rm.is_interactive());
+        for flag in ["-f", "-I"] {
+            let Commands::Remove(rm) = apply(&["bcmr", "remove", flag, "x"]) else {
+                panic!("Expected Remove");
+            };
+            assert!(!rm.is_interactive(), "{flag}");
+        }
+    }
+
+    #[test]
+    fn test_flags_override_profile_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[copy]\npreserve = true\n\n[profile.safe.copy]\nverify = true\nno_clobber = true\n",
+        )
+        .unwrap();
+        let (config, _) = Config::load(&[path], Some("safe")).unwrap();
+        let apply = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap().share_globals();
+            assert_eq!(cli.global.profile.as_deref(), Some("safe"));
+            let Commands::Copy(copy) = cli.apply_config(&config).command else {
+                panic!("Expected Copy");
+            };
+            copy
+        };
+
+        let copy = apply(&["bcmr", "--profile", "safe", "copy", "a", "b"]);
+        assert!(copy.common.is_preserve() && copy.common.is_verify());
+        assert!(copy.common.is_no_clobber());
+
+        let copy = apply(&[
+            "bcmr",
+            "copy",
+            "--profile=safe",
+            "--verify=false",
+            "-p=false",
+            "a",
+            "b",
+        ]);
+        assert!(!copy.common.is_verify() && !copy.common.is_preserve());
+        assert!(copy.common.is_no_clobber());
+    }
+
+    #[test]
+    fn test_bwlimit_flag_overrides_the_config() {
+        let mut config = CopyConfig::default();
+        let limit = |args: &[&str], config: &CopyConfig| {
+            let Commands::Copy(copy) = parse(args) else {
+                panic!("Expected Copy");
+            };
+            copy.common.bwlimit_over(config).unwrap()
+        };
+        assert_eq!(limit(&["bcmr", "copy", "a", "b"], &config), Limit::Rate(0));
+
+        config.bwlimit = Some(crate::config::Size {
+            text: "5M".to_string(),
+            bytes: 5 << 20,
+        });
+        assert_eq!(
+            limit(&["bcmr", "copy", "a", "b"], &config),
+            Limit::Rate(5 << 20)
+        );
+        assert_eq!(
+            limit(&["bcmr", "copy", "--bwlimit", "1M", "a", "b"], &config),
+            Limit::Rate(1 << 20)
+        );
+        assert_eq!(
+            limit(&["bcmr", "copy", "--bwlimit", "0", "a", "b"], &config),
+            Limit::Rate(0)
+        );
+    }
+
+    #[test]
+    fn test_move_falls_back_to_copy_without_copy_only_flags() {
+        let cli = Cli::try_parse_from(["bcmr", "move", "-r", "-V", "a", "b"]).unwrap();
+        let Commands::Move(args) = cli.command else {
+            panic!("Expected Move");
+        };
+        let copy = CopyArgs::from(&args);
+        assert!(copy.common.is_recursive());
+        assert!(copy.common.is_verify());
+        assert_eq!(copy.get_reflink_mode(), None);
+        assert_eq!(copy.get_sparse_mode(), None);
+        assert_eq!(copy.parallel, None);
+    }
+
+    fn parse(args: &[&str]) -> Commands {
+        Cli::try_parse_from(args).unwrap().share_globals().command
+    }
+
+    #[test]
+    fn test_global_options_parse_on_either_side_of_the_subcommand() {
+        let before = parse(&["bcmr", "--exclude", r"\.git", "-v", "copy", "-r", "a", "b"]);
+        let after = parse(&["bcmr", "copy", "-r", "a", "b", "--exclude", r"\.git", "-v"]);
+        for cmd in [before, after] {
+            let Commands::Copy(args) = cmd else {
+                panic!("Expected Copy");
+            };
+            assert!(args.common.is_recursive());
+            assert!(args.common.is_verbose());
+            let excludes = args.common.compile_excludes().unwrap();
+            assert_eq!(excludes.len(), 1);
+            assert!(excludes[0].is_match("repo/.git"));
+        }
+    }
+
+    #[test]
+    fn test_nice_defaults_to_ten_when_bare() {
+        let cli = Cli::try_parse_from(["bcmr", "copy", "--nice", "a", "b"]).unwrap();
+        assert_eq!(cli.global.nice, Some(10));
+        let Commands::Copy(args) = cli.command else {
+            panic!("Expected Copy");
+        };
+        assert_eq!(args.common.paths.len(), 2);
+
+        let cli = Cli::try_parse_from(["bcmr", "--nice=19", "--ionice", "idle", "copy", "a", "b"])
+            .unwrap();
+        assert_eq!(cli.global.nice, Some(19));
+        assert_eq!(cli.global.ionice, Some(IoClass::Idle));
+        assert!(Cli::try_parse_from(["bcmr", "--nice=20", "copy", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn test_default_excludes_come_first_and_can_be_disabled() {
+        let defaults = [r"\.DS_Store$".to_string(), r"\.swp$".to_string()];
+        let mut global = GlobalOpts {
+            exclude: Some(vec![r"\.log$".to_string()]),
+            ..GlobalOpts::default()
+        };
+        let excludes = global.compile_excludes_with_defaults(&defaults).unwrap();
+        let patterns: Vec<_> = excludes.iter().map(|re| re.as_str()).collect();
+        assert_eq!(patterns, [r"\.DS_Store$", r"\.swp$", r"\.log$"]);
+
+        global.no_default_excludes = true;
+        let excludes = global.compile_excludes_with_defaults(&defaults).unwrap();
+        let patterns: Vec<_> = excludes.iter().map(|re| re.as_str()).collect();
+        assert_eq!(patterns, [r"\.log$"]);
+
+        let Commands::Remove(args) = parse(&["bcmr", "remove", "--no-default-excludes", "x"])
+        else {
+            panic!("Expected Remove");
+        };
+        assert!(args.global.no_default_excludes);
+    }
+
+    #[test]
+    fn test_progress_style_flag() {
+        let Commands::Copy(args) = parse(&["bcmr", "copy", "--progress", "plain", "a", "b"]) else {
+            panic!("Expected Copy");
+        };
+        assert_eq!(args.common.progress_style(), ProgressStyle::Plain);
+
+        let Commands::Remove(args) = parse(&["bcmr", "--progress=none", "remove", "x"]) else {
+            panic!("Expected Remove");
+        };
+        assert_eq!(args.progress_style(), ProgressStyle::None);
+
+        let err = Cli::try_parse_from(["bcmr", "copy", "--progress", "box", "a", "b"]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Expected fancy, inline, plain or none"),
+            "{err}"
+        );
+        let err = Cli::try_parse_from(["bcmr", "copy", "--tui", "--progress", "plain", "a", "b"])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_global_options_reach_every_operation() {
+        let Commands::Move(args) = parse(&["bcmr", "-n", "move", "a", "b", "--tui"]) else {
+            panic!("Expected Move");
+        };
+        assert!(args.common.is_dry_run());
+        assert_eq!(args.common.progress_style(), ProgressStyle::Inline);
+
+        let Commands::Remove(args) = parse(&["bcmr", "remove", "-v", "-n", "x", "-e", "a,b"])
+        else {
+            panic!("Expected Remove");
+        };
+        assert!(args.is_verbose());
+        assert!(args.is_dry_run());
+        assert_eq!(args.compile_excludes().unwrap().len(), 1);
+
+        let Commands::Check(args) = parse(&["bcmr", "-e", "tmp", "check", "a", "b"]) else {
+            panic!("Expected Check");
+        };
+        assert_eq!(args.compile_excludes().unwrap().len(), 1);
+
+        let Commands::Verify(args) = parse(&[
+            "bcmr",
+            "-e",
+            "tmp",
+            "verify",
+            "-r",
+            "--hash",
+            "--hash-jobs",
+            "0",
+            "a",
+            "b",
+        ]) else {
+            panic!("Expected Verify");
+        };
+        assert_eq!(args.compile_excludes().unwrap().len(), 1);
+        assert!(args.recursive && args.hash);
+        assert_eq!(args.hash_jobs(), 1);
+
+        let Commands::Checksum(args) = parse(&["bcmr", "checksum", "-r", "--algo", "B3", "d"])
+        else {
+            panic!("Expected Checksum");
+        };
+        assert_eq!(args.algo, Some(Algorithm::Blake3));
+        assert!(Cli::try_parse_from(["bcmr", "checksum", "--algo", "md5", "d"]).is_err());
+        assert!(Cli::try_parse_from(["bcmr", "checksum", "--ignore-missing", "m"]).is_err());
+        assert!(Cli::try_parse_from(["bcmr", "checksum", "-c", "-r", "m"]).is_err());
+
+        let Commands::Copy(args) = parse(&["bcmr", "--test-mode", "delay:5", "copy", "a", "b"])
+        else {
+            panic!("Expected Copy");
+        };
+        assert!(matches!(args.common.get_test_mode(), TestMode::Delay(5)));
+    }
+
+    #[test]
+    fn test_cp_flag_spellings() {
+        let copy = |args: &[&str]| {
+            let mut argv = vec!["bcmr", "copy"];
+            argv.extend_from_slice(args);
+            argv.extend(["a", "b"]);
+            match parse(&argv) {
+                Commands::Copy(args) => args.common,
+                _ => panic!("Expected Copy"),
+            }
+        };
+
+        assert!(copy(&["-R"]).is_recursive());
+        let rv = copy(&["-Rv"]);
+        assert!(rv.is_recursive() && rv.is_verbose());
+        // -n stays dry-run, so cp's -pn is preserve plus a dry run here.
+        let pn = copy(&["-pn"]);
+        assert!(pn.is_preserve() && pn.is_dry_run() && !pn.is_no_clobber());
+
+        let later = copy(&["-f", "--no-clobber"]);
+        assert!(later.is_no_clobber() && !later.is_force());
+        let later = copy(&["--no-clobber", "-f"]);
+        assert!(later.is_force() && !later.is_no_clobber());
+
+        assert!(copy(&["-L"]).dereference);
+        assert!(copy(&["-H"]).dereference);
+
+        let Commands::Copy(args) = parse(&["bcmr", "copy", "--", "-odd", "b"]) else {
+            panic!("Expected Copy");
+        };
+        let (sources, dest) = args.common.get_sources_and_dest().unwrap();
+        assert_eq!(sources, [PathBuf::from("-odd")]);
+        assert_eq!(dest, &PathBuf::from("b"));
+    }
+
+    #[test]
+    fn test_cp_mv_rm_aliases_parse_like_the_full_names() {
+        for name in ["copy", "cp"] {
+            let Commands::Copy(args) = parse(&["bcmr", name, "-r", "--reflink=force", "a", "b"])
+            else {
+                panic!("{name}: expected Copy");
+            };
+            assert!(args.common.is_recursive());
+            assert_eq!(args.reflink.as_deref(), Some("force"));
+            assert_eq!(args.common.paths, [PathBuf::from("a"), PathBuf::from("b")]);
+        }
+        for name in ["move", "mv"] {
+            let Commands::Move(args) = parse(&["bcmr", name, "-f", "a", "b"]) else {
+                panic!("{name}: expected Move");
+            };
+            assert!(args.common.is_force());
+            assert_eq!(args.common.paths, [PathBuf::from("a"), PathBuf::from("b")]);
+        }
+        for name in ["remove", "rm"] {
+            let Commands::Remove(args) = parse(&["bcmr", name, "-rf", "x"]) else {
+                panic!("{name}: expected Remove");
+            };
+            assert!(args.is_recursive() && args.is_force());
+            assert_eq!(args.paths, [PathBuf::from("x")]);
+        }
+    }
+
+    #[test]
+    fn test_mistyped_subcommand_suggests_the_nearest_one() {
+        let err = Cli::try_parse_from(["bcmr", "cpoy", "a", "b"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidSubcommand);
+        let rendered = err.to_string();
+        assert!(rendered.contains("'copy'"), "{rendered}");
+    }
+
+    fn remove(args: &[&str]) -> std::result::Result<(RemoveArgs, Vec<String>), clap::Error> {
+        let mut argv = vec!["bcmr", "remove"];
+        argv.extend_from_slice(args);
+        argv.push("x");
+        let (cli, ignored) = parse_lenient(argv.iter().map(|a| a.to_string()).collect())?;
+        match cli.command {
+            Commands::Remove(args) => Ok((args, ignored)),
+            _ => panic!("Expected Remove"),
+        }
+    }
+
+    #[test]
+    fn test_rm_flag_spellings() {
+        let (args, _) = remove(&["-Rf"]).unwrap();
+        assert!(args.is_recursive() && args.is_force());
+
+        let (args, _) = remove(&["-rf", "--no-preserve-root"]).unwrap();
+        assert_eq!(args.preserve_root(), None);
+        let (args, _) = remove(&["-r"]).unwrap();
+        assert_eq!(args.preserve_root(), Some(PreserveRoot::Root));
+        let (args, _) = remove(&["-r", "--preserve-root"]).unwrap();
+        assert_eq!(args.preserve_root(), Some(PreserveRoot::Root));
+        let (args, _) = remove(&["--no-preserve-root", "--preserve-root=all"]).unwrap();
+        assert_eq!(args.preserve_root(), Some(PreserveRoot::All));
+        let (args, _) = remove(&["--preserve-root=all", "--no-preserve-root"]).unwrap();
+        assert_eq!(args.preserve_root(), None);
+
+        let (args, _) = remove(&["--one-file-system", "-r"]).unwrap();
+        assert!(args.is_one_file_system());
+    }
+
+    #[test]
+    fn test_confirm_config_asks_past_force_but_not_past_yes() {
+        let confirm = ConfirmConfig {
+            assume_yes: false,
+            remove_large_threshold: Some(10_000),
+            require_confirmation_for: vec![],
+        };
+        let (args, _) = remove(&["-f"]).unwrap();
+        assert!(!args.confirms_up_front(10_000, &confirm));
+        assert!(args.confirms_up_front(10_001, &confirm));
+        let (args, _) = remove(&["-fy"]).unwrap();
+        assert!(!args.confirms_up_front(10_001, &confirm));
+        // -I keeps rm's rule: three files are never asked about.
+        let (args, _) = remove(&["-I"]).unwrap();
+        assert!(!args.confirms_up_front(3, &confirm));
+
+        let confirm = ConfirmConfig {
+            require_confirmation_for: vec![Confirmation::RemoveRecursive],
+            ..ConfirmConfig::default()
+        };
+        let (args, _) = remove(&["-rf"]).unwrap();
+        assert!(args.confirms_up_front(1, &confirm));
+        let (args, _) = remove(&["-f"]).unwrap();
+        assert!(!args.confirms_up_front(1, &confirm));
+    }
+
+    #[test]
+    fn test_assume_yes_and_sync_delete_confirmation() {
+        let mut config = Config::default();
+        config.confirm.require_confirmation_for = vec![Confirmation::SyncDelete];
+        let Commands::Sync(sync) = parse(&["bcmr", "sync", "--delete", "a", "b"]) else {
+            panic!("Expected Sync");
+        };
+        assert!(sync.confirms_delete(2, &config.confirm));
+        assert!(!sync.confirms_delete(0, &config.confirm));
+        let Commands::Sync(sync) = parse(&["bcmr", "sync", "--delete", "-y", "a", "b"]) else {
+            panic!("Expected Sync");
+        };
+        assert!(!sync.confirms_delete(2, &config.confirm));
+
+        config.confirm.assume_yes = true;
+        let apply = |args: &[&str]| {
+            Cli::try_parse_from(args)
+                .unwrap()
+                .share_globals()
+                .apply_config(&config)
+                .command
+        };
+        let Commands::Remove(rm) = apply(&["bcmr", "remove", "-r", "x"]) else {
+            panic!("Expected Remove");
+        };
+        assert!(!rm.confirms_up_front(5, &config.confirm));
+        let Commands::Copy(copy) = apply(&["bcmr", "copy", "-f", "a", "b"]) else {
+            panic!("Expected Copy");
+        };
+        assert!(!copy.common.should_prompt_for_overwrite());
+        let Commands::Sync(sync) = apply(&["bcmr", "sync", "--delete", "a", "b"]) else {
+            panic!("Expected Sync");
+        };
+        assert!(!sync.confirms_delete(2, &config.confirm));
+    }
+
+    #[test]
+    fn test_rm_interactive_flags_last_one_wins() {
+        let none = ConfirmConfig::default();
+        let (args, _) = remove(&["-Iv"]).unwrap();
+        assert!(args.is_verbose());
+        assert!(!args.confirms_up_front(3, &none));
+        assert!(args.confirms_up_front(4, &none));
+
+        let (args, _) = remove(&["-rI"]).unwrap();
+        assert!(args.confirms_up_front(1, &none));
+
+        let (args, _) = remove(&["-f", "-I"]).unwrap();
+        assert!(!args.is_force() && args.confirms_up_front(4, &none));
+        let (args, _) = remove(&["-I", "-f"]).unwrap();
+        assert!(args.is_force() && !args.confirms_up_front(4, &none));
+        let (args, _) = remove(&["-I", "-i"]).unwrap();
+        assert!(args.is_interactive() && !args.confirms_up_front(1, &none));
+
+        let (args, _) = remove(&[]).unwrap();
+        assert!(args.confirms_up_front(1, &none));
+        assert!(!args.confirms_up_front(0, &none));
+        let (args, _) = remove(&["-y"]).unwrap();
+        assert!(!args.confirms_up_front(10, &none));
+    }
+
+    #[test]
+    fn test_unknown_rm_flags_are_dropped_with_a_note() {
+        let (args, ignored) = remove(&["-rfZ", "--interactive=never", "--bogus=1"]).unwrap();
+        assert!(args.is_recursive() && args.is_force());
+        assert_eq!(ignored, ["-Z", "--interactive=never", "--bogus=1"]);
+        assert_eq!(args.paths, [PathBuf::from("x")]);
+
+        let (_, ignored) = remove(&["--", "-Z"]).unwrap();
+        assert!(ignored.is_empty());
+
+        let err = remove(&["--strict-flags", "-Z"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+
+        // Only remove is lenient.
+        let argv = ["bcmr", "copy", "-Z", "a", "b"].map(String::from).to_vec();
+        assert_eq!(
+            parse_lenient(argv).unwrap_err().kind(),
+            ErrorKind::UnknownArgument
+        );
+    }
+
+    #[test]
+    fn test_check_args_answers_for_the_run() {
+        let check = |args: &[&str]| {
+            let argv = std::iter::once("bcmr").chain(args.iter().copied());
+            check_args(argv.map(String::from).collect())
+        };
+        assert_eq!(check(&["copy", "-r", "a", "b"]), Ok(()));
+        assert_eq!(check(&["copy", "--help"]), Ok(()));
+        // remove drops what it does not know, so bcmr still runs it.
+        assert_eq!(check(&["remove", "--interactive=once", "x"]), Ok(()));
+        assert_eq!(
+            check(&["remove", "--strict-flags", "--zap", "x"]),
+            Err("unexpected argument '--zap' found".to_string())
+        );
+        let err = check(&["copy", "--reflink=always", "a", "b"]).unwrap_err();
+        assert!(
+            err.starts_with("invalid value 'always' for '--reflink"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_rm_compatibility_is_in_long_help() {
+        use clap::CommandFactory;
+        let mut cmd = Cli::command();
+        let help = cmd
+            .find_subcommand_mut("remove")
+            .unwrap()
+            .render_long_help()
+            .to_string();
+        assert!(help.contains("rm compatibility:"), "{help}");
+        assert!(help.contains("--strict-flags"), "{help}");
+    }
+
+    #[test]
+    fn test_global_options_are_listed_once_in_help() {
+        use clap::CommandFactory;
+        let help = Cli::command().render_long_help().to_string();
+        assert_eq!(help.matches("Global options:").count(), 1, "{help}");
+        assert_eq!(help.matches("--exclude").count(), 1, "{help}");
     }
 
     #[test]
     fn test_protocol_caps_sync_gate() {
         use crate::core::protocol::{CAP_FAST, CAP_SYNC};
 
-        let cmd_no_sync = Commands::Copy {
-            args: test_args(vec![PathBuf::from("dst")]),
-            reflink: None,
-            sparse: None,
-            parallel: None,
-        };
+        let cmd_no_sync = test_args(vec![PathBuf::from("dst")]);
         assert_eq!(
             cmd_no_sync.protocol_caps() & CAP_SYNC,
             0,
@@ -676,15 +2533,9 @@ mod tests {
         );
 
         let mut a = test_args(vec![PathBuf::from("dst")]);
-        a.sync = true;
+        a.sync = Some(true);
         a.fast = true;
-        let cmd_sync_fast = Commands::Copy {
-            args: a,
-            reflink: None,
-            sparse: None,
-            parallel: None,
-        };
-        let caps = cmd_sync_fast.protocol_caps();
+        let caps = a.protocol_caps();
         assert_eq!(caps & CAP_SYNC, CAP_SYNC, "--sync sets CAP_SYNC");
         assert_eq!(caps & CAP_FAST, CAP_FAST, "--fast still sets CAP_FAST");
     }
@@ -0,0 +1,254 @@
+use crate::cli::{BenchEngine, CopyArgs};
+use crate::commands::copy;
+use crate::core::error::BcmrError;
+use crate::output::{BenchResult, BenchRun, Status};
+use crate::ui::utils::format_bytes;
+
+use clap::ValueEnum;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+pub struct BenchOptions {
+    pub dir: PathBuf,
+    pub size: u64,
+    pub files: u64,
+    pub engines: Vec<BenchEngine>,
+}
+
+/// The two data sets every engine copies: incompressible bytes, and files
+/// that are nothing but a hole.
+const DATA: [&str; 2] = ["random", "sparse"];
+
+/// Removes the scratch directory however the bench ends.
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub async fn run(
+    opts: &BenchOptions,
+    cancel: &CancellationToken,
+) -> Result<BenchResult, BcmrError> {
+    if !opts.dir.is_dir() {
+        return Err(BcmrError::InvalidInput(format!(
+            "'{}' is not a directory",
+            opts.dir.display()
+        )));
+    }
+    // The random data and one copy of it exist at the same time.
+    let needed = opts.size.saturating_mul(2);
+    if let Some(available) = available_space(&opts.dir).filter(|&a| a < needed) {
+        return Err(BcmrError::InvalidInput(format!(
+            "Not enough free space in '{}': the bench needs {}, {} is available",
+            opts.dir.display(),
+            format_bytes(needed as f64),
+            format_bytes(available as f64)
+        )));
+    }
+
+    let scratch = Scratch(opts.dir.join(format!(".bcmr-bench-{}", std::process::id())));
+    std::fs::create_dir(&scratch.0)?;
+    let sizes = split(opts.size, opts.files);
+
+    let mut runs = Vec::new();
+    for data in DATA {
+        let source = scratch.0.join(data);
+        let root = source.clone();
+        let (sizes, stop) = (sizes.clone(), cancel.clone());
+        tokio::task::spawn_blocking(move || generate(&root, data, &sizes, &stop)).await??;
+
+        for &engine in &opts.engines {
+            let out = scratch.0.join("out");
+            runs.push(time_copy(&source, &out, data, engine, cancel).await?);
+            tokio::fs::remove_dir_all(&out).await?;
+        }
+        tokio::fs::remove_dir_all(&source).await?;
+    }
+
+    Ok(BenchResult {
+        status: Status::Success,
+        dir: opts.dir.clone(),
+        size: opts.size,
+        files: opts.files,
+        runs,
+    })
+}
+
+/// `total` bytes over `files` files, the remainder on the last one.
+fn split(total: u64, files: u64) -> Vec<u64> {
+    let each = total / files;
+    let mut sizes = vec![each; files as usize];
+    if let Some(last) = sizes.last_mut() {
+        *last += total - each * files;
+    }
+    sizes
+}
+
+fn file_name(index: usize) -> String {
+    format!("file-{index:05}.bin")
+}
+
+fn generate(
+    dir: &Path,
+    data: &str,
+    sizes: &[u64],
+    cancel: &CancellationToken,
+) -> Result<(), BcmrError> {
+    std::fs::create_dir(dir)?;
+    let mut bytes = blake3::Hasher::new_derive_key("bcmr bench").finalize_xof();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    for (index, &size) in sizes.iter().enumerate() {
+        let mut file = std::fs::File::create(dir.join(file_name(index)))?;
+        if data == "sparse" {
+            file.set_len(size)?;
+            continue;
+        }
+        let mut left = size;
+        while left > 0 {
+            if cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
+            }
+            let n = left.min(buffer.len() as u64) as usize;
+            bytes.fill(&mut buffer[..n]);
+            file.write_all(&buffer[..n])?;
+            left -= n as u64;
+        }
+        // Written back now, so it does not compete with the timed copies.
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+async fn time_copy(
+    source: &Path,
+    out: &Path,
+    data: &'static str,
+    engine: BenchEngine,
+    cancel: &CancellationToken,
+) -> Result<BenchRun, BcmrError> {
+    tokio::fs::create_dir(out).await?;
+    let args = CopyArgs::from(engine);
+    let mut run = BenchRun {
+        data,
+        engine: engine
+            .to_possible_value()
+            .map_or_else(String::new, |v| v.get_name().to_string()),
+        seconds: 0.0,
+        bytes_per_sec: 0.0,
+        allocated: None,
+        error: None,
+    };
+
+    let mut names: Vec<PathBuf> = std::fs::read_dir(source)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    names.sort();
+    let mut bytes = 0;
+    let started = Instant::now();
+    for src in &names {
+        let dst = out.join(src.file_name().unwrap_or_default());
+        match copy::copy_single_file(src, &dst, &args, cancel).await {
+            Ok(()) => bytes += std::fs::metadata(&dst)?.len(),
+            Err(BcmrError::Cancelled) => return Err(BcmrError::Cancelled),
+            Err(BcmrError::Reflink(_)) => {
+                run.error = Some("not supported on this file system".into());
+                return Ok(run);
+            }
+            Err(e) => {
+                run.error = Some(e.to_string());
+                return Ok(run);
+            }
+        }
+    }
+    run.seconds = started.elapsed().as_secs_f64();
+    run.bytes_per_sec = bytes as f64 / run.seconds.max(1e-9);
+    run.allocated = allocated(out)?;
+    Ok(run)
+}
+
+#[cfg(unix)]
+fn allocated(dir: &Path) -> Result<Option<u64>, BcmrError> {
+    use std::os::unix::fs::MetadataExt;
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir)? {
+        total += entry?.metadata()?.blocks() * 512;
+    }
+    Ok(Some(total))
+}
+
+#[cfg(not(unix))]
+fn allocated(_dir: &Path) -> Result<Option<u64>, BcmrError> {
+    Ok(None)
+}
+
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_keeps_the_total() {
+        assert_eq!(split(10, 3), vec![3, 3, 4]);
+        assert_eq!(split(4096, 1), vec![4096]);
+        assert_eq!(split(2, 4), vec![0, 0, 0, 2]);
+    }
+
+    #[tokio::test]
+    async fn runs_every_engine_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = BenchOptions {
+            dir: dir.path().to_path_buf(),
+            size: 3 * 1024 * 1024 + 17,
+            files: 3,
+            engines: vec![BenchEngine::Kernel, BenchEngine::Stream],
+        };
+        let r = run(&opts, &CancellationToken::new()).await.unwrap();
+
+        let runs: Vec<(&str, &str)> = r
+            .runs
+            .iter()
+            .map(|run| (run.data, run.engine.as_str()))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("random", "kernel"),
+                ("random", "stream"),
+                ("sparse", "kernel"),
+                ("sparse", "stream"),
+            ]
+        );
+        assert!(r.runs.iter().all(|run| run.error.is_none()), "{:?}", r.runs);
+        assert!(r.runs.iter().all(|run| run.bytes_per_sec > 0.0));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let opts = BenchOptions {
+            size: u64::MAX / 2,
+            ..opts
+        };
+        assert!(matches!(
+            run(&opts, &CancellationToken::new()).await,
+            Err(BcmrError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::core::checksum::{self, Algorithm};
+use crate::core::error::BcmrError;
+use crate::core::traversal::{self, EntryKind};
+
+use futures::stream::{self, StreamExt};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+/// A regular file to hash and the name it is listed under.
+pub struct ManifestEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Regular files under `root` (or `root` itself when it is a file), sorted
+/// bytewise by name so manifests of the same tree diff cleanly. `manifest`
+/// is the file the listing is about to be written to, kept out of it.
+pub async fn collect(
+    root: &Path,
+    recursive: bool,
+    excludes: &[regex::Regex],
+    manifest: Option<&Path>,
+) -> Result<Vec<ManifestEntry>, BcmrError> {
+    let md = crate::core::io::stat(root)
+        .await
+        .ok_or_else(|| BcmrError::SourceNotFound(root.to_path_buf()))?;
+    if !md.is_dir() {
+        let name = root.file_name().unwrap_or(root.as_os_str());
+        return Ok(vec![ManifestEntry {
+            name: name.to_string_lossy().into_owned(),
+            path: root.to_path_buf(),
+            size: md.len(),
+        }]);
+    }
+    if !recursive {
+        return Err(BcmrError::InvalidInput(format!(
+            "'{}' is a directory. Use -r flag to hash its contents.",
+            root.display()
+        )));
+    }
+
+    let root = root.to_path_buf();
+    let excludes = excludes.to_vec();
+    let manifest = manifest.map(Path::to_path_buf);
+    tokio::task::spawn_blocking(move || {
+        let skip = manifest.and_then(|m| Some((canonical(&root)?, canonical(&m)?)));
+        let mut entries = Vec::new();
+        for entry in traversal::scan(&root, &excludes) {
+            let entry = entry?;
+            if entry.kind != EntryKind::File {
+                continue;
+            }
+            if let Some((root, manifest)) = &skip {
+                if root.join(&entry.relative) == *manifest {
+                    continue;
+                }
+            }
+            entries.push(ManifestEntry {
+                name: entry.relative.to_string_lossy().into_owned(),
+                path: entry.path,
+                size: entry.size,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    })
+    .await?
+}
+
+// The manifest may not exist yet, so only its directory is resolved.
+fn canonical(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// Hashes `entries` with `jobs` files in flight and hands each digest to
+/// `emit` in the order given. `on_read` sees every chunk as it is hashed.
+pub async fn hash_entries(
+    entries: Vec<ManifestEntry>,
+    algo: Algorithm,
+    jobs: usize,
+    on_read: impl Fn(u64) + Send + Sync + Clone + 'static,
+    cancel: &CancellationToken,
+    mut emit: impl FnMut(&ManifestEntry, &str) -> std::io::Result<()>,
+) -> Result<(), BcmrError> {
+    let mut hashed = stream::iter(entries)
+        .map(|entry| {
+            let on_read = on_read.clone();
+            tokio::task::spawn_blocking(move || {
+                let digest = checksum::hash_file(&entry.path, algo, on_read)?;
+                Ok::<_, BcmrError>((entry, digest))
+            })
+        })
+        .buffered(jobs.max(1));
+
+    while let Some(result) = hashed.next().await {
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let (entry, digest) = result??;
+        emit(&entry, &digest)?;
+    }
+    Ok(())
+}
+
+/// One line in `sha256sum` format. Like coreutils, a name holding a
+/// backslash or line break is escaped and the line marked with a leading
+/// backslash.
+pub fn manifest_line(digest: &str, name: &str) -> String {
+    if !name.contains(['\\', '\n', '\r']) {
+        return format!("{}  {}", digest, name);
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\\{}  {}", digest, escaped)
+}
+
+/// First line of every manifest bcmr writes, so `-c` knows what to hash
+/// with. The checksum tools skip it as a comment.
+pub fn manifest_header(algo: Algorithm) -> String {
+    format!("{}{}", HEADER_PREFIX, algo)
+}
+
+const HEADER_PREFIX: &str = "# algorithm: ";
+
+/// A manifest entry: the digest it expects and the path it names.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ManifestLine {
+    pub digest: String,
+    pub name: String,
+}
+
+pub struct Manifest {
+    /// Named by a `manifest_header` line, if the manifest has one.
+    pub algorithm: Option<Algorithm>,
+    pub lines: Vec<ManifestLine>,
+    /// Lines that could not be parsed.
+    pub malformed: usize,
+}
+
+/// Reads `sha256sum`/`b3sum` output, including the `*` binary-mode marker,
+/// escaped names and CRLF line ends. Blank and `#` lines other than the
+/// algorithm header are skipped.
+pub fn parse_manifest(text: &str) -> Manifest {
+    let mut manifest = Manifest {
+        algorithm: None,
+        lines: Vec::new(),
+        malformed: 0,
+    };
+    for line in text.lines().map(|l| l.trim_end_matches('\r')) {
+        if let Some(name) = line.strip_prefix(HEADER_PREFIX) {
+            manifest.algorithm = manifest.algorithm.or(name.trim().parse().ok());
+            continue;
+        }
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Some(parsed) => manifest.lines.push(parsed),
+            None => manifest.malformed += 1,
+        }
+    }
+    manifest
+}
+
+fn parse_line(line: &str) -> Option<ManifestLine> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (digest, rest) = line.split_once(' ')?;
+    if digest.is_empty() || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let name = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(ManifestLine {
+        digest: digest.to_ascii_lowercase(),
+        name: if escaped {
+            unescape(name)?
+        } else {
+            name.to_string()
+        },
+    })
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+/// A manifest entry resolved against the root it is checked under; `size`
+/// is `None` when the file is gone.
+pub struct Listed {
+    pub line: ManifestLine,
+    pub path: PathBuf,
+    pub size: Option<u64>,
+}
+
+pub async fn locate(lines: Vec<ManifestLine>, root: &Path) -> Result<Vec<Listed>, BcmrError> {
+    let root = root.to_path_buf();
+    let listed = tokio::task::spawn_blocking(move || {
+        lines
+            .into_iter()
+            .map(|line| {
+                let path = root.join(&line.name);
+                // Anything but "not there" is left for the hash to report.
+                let size = match std::fs::metadata(&path) {
+                    Ok(md) if md.is_file() => Some(md.len()),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                    _ => Some(0),
+                };
+                Listed { line, path, size }
+            })
+            .collect()
+    })
+    .await?;
+    Ok(listed)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    Missing,
+    Unreadable,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Failed => "FAILED",
+            CheckStatus::Missing => "MISSING",
+            CheckStatus::Unreadable => "FAILED open or read",
+        })
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct CheckCounts {
+    pub ok: u64,
+    pub failed: u64,
+    pub missing: u64,
+    pub unreadable: u64,
+}
+
+impl CheckCounts {
+    /// 1 only when every listed file was there and some differ, so a
+    /// script can tell corruption from an incomplete tree (2).
+    pub fn exit_code(&self) -> i32 {
+        if self.missing + self.unreadable > 0 {
+            2
+        } else if self.failed > 0 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Hashes each listed file, `jobs` at a time, and hands the outcome to
+/// `emit` in manifest order.
+pub async fn check_listed(
+    listed: Vec<Listed>,
+    algo: Algorithm,
+    jobs: usize,
+    on_read: impl Fn(u64) + Send + Sync + Clone + 'static,
+    cancel: &CancellationToken,
+    mut emit: impl FnMut(&Listed, CheckStatus) -> std::io::Result<()>,
+) -> Result<CheckCounts, BcmrError> {
+    let mut checked = stream::iter(listed)
+        .map(|item| {
+            let on_read = on_read.clone();
+            tokio::task::spawn_blocking(move || {
+                let status = match item.size {
+                    None => CheckStatus::Missing,
+                    Some(_) => match checksum::hash_file(&item.path, algo, on_read) {
+                        Ok(digest) if digest == item.line.digest => CheckStatus::Ok,
+                        Ok(_) => CheckStatus::Failed,
+                        Err(_) => CheckStatus::Unreadable,
+                    },
+                };
+                (item, status)
+            })
+        })
+        .buffered(jobs.max(1));
+
+    let mut counts = CheckCounts::default();
+    while let Some(result) = checked.next().await {
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let (item, status) = result?;
+        *match status {
+            CheckStatus::Ok => &mut counts.ok,
+            CheckStatus::Failed => &mut counts.failed,
+            CheckStatus::Missing => &mut counts.missing,
+            CheckStatus::Unreadable => &mut counts.unreadable,
+        } += 1;
+        emit(&item, status)?;
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn collect_sorts_and_leaves_out_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("B")).unwrap();
+        for name in ["z.txt", "a/2", "a/10", "B/x", "a-b", "out.sha256"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let manifest = root.join("out.sha256");
+        let names: Vec<String> = collect(root, true, &[], Some(&manifest))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["B/x", "a-b", "a/10", "a/2", "z.txt"]);
+
+        let excludes = [regex::Regex::new("/a$").unwrap()];
+        let entries = collect(root, true, &excludes, None).await.unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().any(|e| e.name == "out.sha256"));
+    }
+
+    #[tokio::test]
+    async fn hash_entries_keeps_the_listing_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = Vec::new();
+        for i in 0..20 {
+            let path = dir.path().join(format!("f{:02}", i));
+            // Larger files first, so finishing order would be reversed.
+            fs::write(&path, vec![i as u8; (20 - i) * 50_000]).unwrap();
+            entries.push(ManifestEntry {
+                name: format!("f{:02}", i),
+                size: 0,
+                path,
+            });
+        }
+
+        let mut seen = Vec::new();
+        hash_entries(
+            entries,
+            Algorithm::Blake3,
+            8,
+            |_| {},
+            &CancellationToken::new(),
+            |entry, digest| {
+                assert_eq!(
+                    digest,
+                    checksum::hash_file(&entry.path, Algorithm::Blake3, |_| {}).unwrap()
+                );
+                seen.push(entry.name.clone());
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+        let expected: Vec<String> = (0..20).map(|i| format!("f{:02}", i)).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn manifest_line_escapes_like_sha256sum() {
+        assert_eq!(manifest_line("ab", "dir/f.txt"), "ab  dir/f.txt");
+        assert_eq!(manifest_line("ab", "a\\b\nc"), "\\ab  a\\\\b\\nc");
+    }
+
+    #[test]
+    fn parse_manifest_reads_what_the_tools_write() {
+        let text = "# algorithm: xxh3\r\n\
+                    # algorithm: blake3\n\
+                    AB01  plain name.txt\r\n\
+                    cd02 *binary.bin\n\
+                    \n\
+                    not a digest line\n\
+                    ef03 missing-separator\n";
+        let escaped = manifest_line("0f", "a\\b\nc");
+        let manifest = parse_manifest(&format!("{text}{escaped}\n"));
+        assert_eq!(manifest.algorithm, Some(Algorithm::Xxh3));
+        let parsed: Vec<(&str, &str)> = manifest
+            .lines
+            .iter()
+            .map(|l| (l.digest.as_str(), l.name.as_str()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("ab01", "plain name.txt"),
+                ("cd02", "binary.bin"),
+                ("0f", "a\\b\nc"),
+            ]
+        );
+        assert_eq!(manifest.malformed, 2);
+        assert_eq!(parse_manifest("00  x\n").algorithm, None);
+    }
+
+    #[tokio::test]
+    async fn check_listed_sorts_out_each_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("good"), b"good").unwrap();
+        fs::write(root.join("bad"), b"bad").unwrap();
+        fs::create_dir(root.join("dir")).unwrap();
+        let digest = |data: &[u8]| {
+            let path = root.join("probe");
+            fs::write(&path, data).unwrap();
+            let d = checksum::hash_file(&path, Algorithm::Sha256, |_| {}).unwrap();
+            fs::remove_file(path).unwrap();
+            d
+        };
+        let manifest = format!(
+            "{}  good\n{}  bad\n{}  gone\n{}  dir\n",
+            digest(b"good"),
+            digest(b"was good"),
+            digest(b"x"),
+            digest(b"y"),
+        );
+
+        let listed = locate(parse_manifest(&manifest).lines, root).await.unwrap();
+        let mut seen = Vec::new();
+        let counts = check_listed(
+            listed,
+            Algorithm::Sha256,
+            4,
+            |_| {},
+            &CancellationToken::new(),
+            |item, status| {
+                seen.push(format!("{}: {}", item.line.name, status));
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            seen,
+            [
+                "good: OK",
+                "bad: FAILED",
+                "gone: MISSING",
+                "dir: FAILED open or read"
+            ]
+        );
+        assert_eq!(counts.exit_code(), 2);
+        assert_eq!(
+            CheckCounts {
+                ok: 3,
+                failed: 1,
+                ..CheckCounts::default()
+            }
+            .exit_code(),
+            1
+        );
+    }
+}
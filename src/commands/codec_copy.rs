@@ -0,0 +1,464 @@
+use crate::cli::CopyArgs;
+use crate::commands::copy::{resolve_sparse_mode, temp_path_for, TempFileGuard};
+use crate::commands::copy_strategies::SparseWriter;
+use crate::core::checksum::{self, Algorithm};
+use crate::core::error::BcmrError;
+use crate::core::io as durable_io;
+use crate::core::oplog::{self, Action, WriteKind};
+use crate::core::session::COPY_BLOCK_SIZE;
+use crate::core::verify_report;
+use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::runner::ProgressRunner;
+use crate::ui::utils::display_path;
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+const EXTENSION: &str = "zst";
+const DEFAULT_LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileCodec {
+    /// `--compress zstd[:LEVEL]`: the destination is a zstd frame.
+    Compress(i32),
+    /// `--decompress`: the source is one.
+    Decompress,
+}
+
+fn parse_level(spec: &str) -> Result<Option<i32>, BcmrError> {
+    let spec = spec.to_ascii_lowercase();
+    let (algo, level) = match spec.split_once(':') {
+        Some((algo, level)) => (algo, Some(level)),
+        None => (spec.as_str(), None),
+    };
+    if algo != "zstd" {
+        return Ok(None);
+    }
+    let Some(level) = level else {
+        return Ok(Some(DEFAULT_LEVEL));
+    };
+    let range = zstd::compression_level_range();
+    match level.parse::<i32>() {
+        Ok(n) if n >= 1 && range.contains(&n) => Ok(Some(n)),
+        _ => Err(BcmrError::InvalidInput(format!(
+            "Invalid zstd level '{}' (expected 1-{})",
+            level,
+            range.end()
+        ))),
+    }
+}
+
+fn has_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// The file codec a local copy asks for. `--compress` names wire
+/// compression for a remote copy too, so only `zstd[:LEVEL]` counts here;
+/// `--decompress` with no format goes by the source's `.zst` extension.
+pub fn requested(args: &CopyArgs, sources: &[PathBuf]) -> Result<Option<FileCodec>> {
+    let level = parse_level(&args.common.compress)?;
+    let Some(format) = args.decompress.as_deref() else {
+        return Ok(level.map(FileCodec::Compress));
+    };
+    if level.is_some() {
+        bail!("--compress and --decompress cannot be combined");
+    }
+    match format.to_ascii_lowercase().as_str() {
+        "zstd" => {}
+        "auto" => {
+            if let Some(src) = sources.iter().find(|s| !has_extension(s)) {
+                bail!(
+                    "Cannot tell how '{}' is compressed from its name: pass --decompress=zstd",
+                    src.display()
+                );
+            }
+        }
+        other => bail!(
+            "Unsupported --decompress format '{}' (expected zstd)",
+            other
+        ),
+    }
+    Ok(Some(FileCodec::Decompress))
+}
+
+/// Where `src` lands: inside `dest` when it is a directory, with `.zst`
+/// added or taken off, else at `dest` as named.
+pub fn target(src: &Path, dest: &Path, codec: FileCodec) -> PathBuf {
+    if !dest.is_dir() && !dest.to_string_lossy().ends_with('/') {
+        return dest.to_path_buf();
+    }
+    let name = src.file_name().unwrap_or_default();
+    match codec {
+        FileCodec::Compress(_) => {
+            let mut name = name.to_os_string();
+            name.push(".");
+            name.push(EXTENSION);
+            dest.join(name)
+        }
+        FileCodec::Decompress if has_extension(src) => {
+            dest.join(Path::new(name).file_stem().unwrap_or(name))
+        }
+        FileCodec::Decompress => dest.join(name),
+    }
+}
+
+/// Copies one file through a zstd encoder or decoder. Progress counts the
+/// source's bytes as they are read; `--verify` compares the uncompressed
+/// content on both sides.
+pub async fn handle_codec_copy(
+    args: &CopyArgs,
+    src: &Path,
+    dst: &Path,
+    codec: FileCodec,
+) -> Result<()> {
+    let flag = match codec {
+        FileCodec::Compress(_) => "--compress",
+        FileCodec::Decompress => "--decompress",
+    };
+    if args.common.is_resume() || args.common.is_append() || args.common.is_strict() {
+        bail!(
+            "--resume/--append/--strict cannot be combined with {} yet",
+            flag
+        );
+    }
+    let src_md = std::fs::metadata(src).map_err(|_| BcmrError::SourceNotFound(src.into()))?;
+    if src_md.is_dir() {
+        bail!(
+            "{} applies to a single file: '{}' is a directory (try --to-tar - piped to zstd)",
+            flag,
+            src.display()
+        );
+    }
+
+    let existed = dst.symlink_metadata().is_ok();
+    if args.common.is_dry_run() {
+        if !crate::config::is_json_mode() {
+            println!("DRY RUN MODE: No changes will be made.\n");
+            let action = match existed {
+                false => ActionType::Add,
+                true if args.common.is_no_clobber() => ActionType::Skip,
+                true => ActionType::Overwrite,
+            };
+            print_dry_run(action, &src.to_string_lossy(), Some(&dst.to_string_lossy()));
+        }
+        return Ok(());
+    }
+    if existed && args.common.is_no_clobber() {
+        oplog::report(
+            Action::Skip {
+                path: dst,
+                reason: "exists",
+            },
+            args.common.is_verbose(),
+        );
+        return Ok(());
+    }
+    if existed && !args.common.is_force() {
+        return Err(BcmrError::TargetExists(dst.to_path_buf()).into());
+    }
+
+    let cancel = CancellationToken::new();
+    let runner = ProgressRunner::new(
+        src_md.len(),
+        args.common.progress_style(),
+        false,
+        crate::config::is_json_mode(),
+        crate::commands::copy::cleanup_partial_files,
+    )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&display_path(src), &display_path(dst));
+    runner.progress().lock().set_operation_type(match codec {
+        FileCodec::Compress(_) => "Compressing",
+        FileCodec::Decompress => "Decompressing",
+    });
+    (runner.file_callback())(
+        &src.file_name().unwrap_or_default().to_string_lossy(),
+        src_md.len(),
+    );
+
+    let result = codec_copy(args, src, dst, codec, &runner, &cancel).await;
+    let (raw, compressed) = match result {
+        Ok(sizes) => sizes,
+        Err(e) => return runner.finish_failed(oplog::failed(dst, e)),
+    };
+    oplog::report(
+        Action::Copy {
+            src,
+            dst,
+            bytes: match codec {
+                FileCodec::Compress(_) => compressed,
+                FileCodec::Decompress => raw,
+            },
+            write: if existed {
+                WriteKind::Overwritten
+            } else {
+                WriteKind::Created
+            },
+        },
+        args.common.is_verbose(),
+    );
+    (runner.items_callback())(1);
+    runner.progress().lock().set_codec_sizes(raw, compressed);
+    runner.finish_ok()
+}
+
+/// Copies, finalizes and verifies; returns the uncompressed and the
+/// compressed size.
+async fn codec_copy(
+    args: &CopyArgs,
+    src: &Path,
+    dst: &Path,
+    codec: FileCodec,
+    runner: &ProgressRunner,
+    cancel: &CancellationToken,
+) -> Result<(u64, u64), BcmrError> {
+    let tmp = temp_path_for(dst);
+    let mut guard = TempFileGuard::new(tmp.clone(), dst, cancel);
+    let opts = StreamOptions {
+        codec,
+        sparse: resolve_sparse_mode(&args.get_sparse_mode()),
+        verify_algo: args.common.is_verify().then(|| args.common.checksum_algo()),
+        sync: args.common.is_sync(),
+        cancel: cancel.clone(),
+    };
+    let (src_file, dst_file) = (File::open(src)?, File::create(&tmp)?);
+    let on_read = runner.inc_callback();
+    let streamed =
+        tokio::task::spawn_blocking(move || stream_sync(src_file, dst_file, opts, on_read))
+            .await??;
+
+    tokio::fs::rename(&tmp, dst).await?;
+    guard.disarm();
+    if args.common.is_sync() {
+        if let Some(parent) = dst.parent() {
+            durable_io::fsync_dir_async(parent).await;
+        }
+    }
+    if args.common.is_preserve() {
+        crate::commands::copy::preserve_attributes(src, dst).await?;
+    }
+
+    let compressed_size = match codec {
+        FileCodec::Compress(_) => tokio::fs::metadata(dst).await?.len(),
+        FileCodec::Decompress => tokio::fs::metadata(src).await?.len(),
+    };
+    if let Some(src_hash) = streamed.hash {
+        verify(
+            src,
+            dst,
+            codec,
+            args.common.checksum_algo(),
+            &src_hash,
+            streamed.raw,
+        )
+        .await?;
+    }
+    Ok((streamed.raw, compressed_size))
+}
+
+struct StreamOptions {
+    codec: FileCodec,
+    sparse: crate::cli::SparseMode,
+    verify_algo: Option<Algorithm>,
+    sync: bool,
+    cancel: CancellationToken,
+}
+
+struct Streamed {
+    /// Uncompressed bytes that went through the loop.
+    raw: u64,
+    /// Their digest, when `--verify` asked for one.
+    hash: Option<String>,
+}
+
+/// The source file, counted for progress before any decoding.
+struct CountingReader<R, F> {
+    inner: R,
+    on_read: F,
+}
+
+impl<R: Read, F: Fn(u64)> Read for CountingReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.on_read)(n as u64);
+        Ok(n)
+    }
+}
+
+enum Sink {
+    Encoder(zstd::stream::write::Encoder<'static, File>),
+    Sparse(SparseWriter),
+}
+
+impl Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Sink::Encoder(e) => e.write_all(buf),
+            Sink::Sparse(w) => w.write(buf),
+        }
+    }
+
+    fn finish(self) -> io::Result<File> {
+        match self {
+            Sink::Encoder(e) => e.finish(),
+            Sink::Sparse(w) => w.finish(),
+        }
+    }
+}
+
+/// The copy loop with a zstd stage between read and write: plain bytes
+/// are encoded on the way out, or the source decoded on the way in, so the
+/// buffer always holds uncompressed content for the hash.
+fn stream_sync(
+    src: File,
+    dst: File,
+    opts: StreamOptions,
+    on_read: impl Fn(u64) + Send + Sync + Clone + 'static,
+) -> Result<Streamed, BcmrError> {
+    let counted = CountingReader {
+        inner: src,
+        on_read,
+    };
+    let (mut reader, mut sink): (Box<dyn Read + Send>, Sink) = match opts.codec {
+        FileCodec::Compress(level) => (
+            Box::new(counted),
+            Sink::Encoder(zstd::stream::write::Encoder::new(dst, level)?),
+        ),
+        FileCodec::Decompress => (
+            Box::new(zstd::stream::read::Decoder::new(counted)?),
+            Sink::Sparse(SparseWriter::new(dst, opts.sparse)),
+        ),
+    };
+    let mut hasher = opts.verify_algo.map(Algorithm::hasher);
+    let mut buffer = vec![0u8; COPY_BLOCK_SIZE as usize];
+    let mut raw = 0u64;
+
+    loop {
+        if opts.cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let want = crate::core::bwlimit::step(buffer.len());
+        let n = reader
+            .read(&mut buffer[..want])
+            .map_err(|e| match opts.codec {
+                FileCodec::Decompress => corrupt_frame(e),
+                FileCodec::Compress(_) => e.into(),
+            })?;
+        if n == 0 {
+            break;
+        }
+        if let Some(h) = hasher.as_mut() {
+            h.update(&buffer[..n]);
+        }
+        sink.write(&buffer[..n])?;
+        raw += n as u64;
+        crate::core::bwlimit::wait(n as u64);
+    }
+
+    let file = sink.finish()?;
+    if opts.sync {
+        durable_io::durable_sync(&file)?;
+    }
+    Ok(Streamed {
+        raw,
+        hash: hasher.map(|h| h.finish()),
+    })
+}
+
+/// zstd reports a damaged or foreign source as a plain I/O error; say
+/// what it means.
+fn corrupt_frame(e: io::Error) -> BcmrError {
+    if e.kind() == io::ErrorKind::Other || e.kind() == io::ErrorKind::InvalidData {
+        BcmrError::InvalidInput(format!("source is not valid zstd data: {}", e))
+    } else {
+        e.into()
+    }
+}
+
+/// Digest of what `dst` holds once uncompressed.
+fn logical_hash(dst: &Path, codec: FileCodec, algo: Algorithm) -> io::Result<String> {
+    if codec == FileCodec::Decompress {
+        return checksum::calculate_hash(dst, algo);
+    }
+    let mut decoder = zstd::stream::read::Decoder::new(File::open(dst)?)?;
+    let mut hasher = algo.hasher();
+    let mut buffer = vec![0u8; COPY_BLOCK_SIZE as usize];
+    loop {
+        let n = decoder.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+/// Compares the source's uncompressed digest, taken on the way through,
+/// with the destination's, removing the destination on a mismatch as a
+/// failed `--verify` of a copy does.
+async fn verify(
+    src: &Path,
+    dst: &Path,
+    codec: FileCodec,
+    algo: Algorithm,
+    src_hash: &str,
+    raw: u64,
+) -> Result<(), BcmrError> {
+    let path = dst.to_path_buf();
+    let dst_hash = tokio::task::spawn_blocking(move || logical_hash(&path, codec, algo)).await??;
+
+    crate::core::journal::verified(src, dst, algo, src_hash, &dst_hash);
+    if verify_report::is_open() {
+        verify_report::record(&verify_report::Outcome {
+            src,
+            dst,
+            size: raw,
+            src_digest: Some(src_hash),
+            dst_digest: Some(&dst_hash),
+        });
+    }
+    if dst_hash != src_hash {
+        let _ = tokio::fs::remove_file(dst).await;
+        return Err(BcmrError::VerificationError(dst.to_path_buf(), None));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("zstd").unwrap(), Some(DEFAULT_LEVEL));
+        assert_eq!(parse_level("ZSTD:7").unwrap(), Some(7));
+        assert_eq!(parse_level("auto").unwrap(), None);
+        assert_eq!(parse_level("lz4").unwrap(), None);
+        assert!(parse_level("zstd:0").is_err());
+        assert!(parse_level("zstd:99").is_err());
+        assert!(parse_level("zstd:fast").is_err());
+    }
+
+    #[test]
+    fn test_target_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        let src = Path::new("/data/huge.log");
+        assert_eq!(
+            target(src, d, FileCodec::Compress(3)),
+            d.join("huge.log.zst")
+        );
+        assert_eq!(
+            target(Path::new("/data/huge.log.ZST"), d, FileCodec::Decompress),
+            d.join("huge.log")
+        );
+        assert_eq!(target(src, d, FileCodec::Decompress), d.join("huge.log"));
+        assert_eq!(
+            target(src, &d.join("named.zst"), FileCodec::Compress(3)),
+            d.join("named.zst")
+        );
+    }
+}
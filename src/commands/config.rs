@@ -0,0 +1,202 @@
+//! `bcmr config`: where the settings come from, what they add up to, and
+//! a commented file to start one from.
+
+use crate::config::{self, Config};
+use crate::core::error::BcmrError;
+use crate::ui::progress::print_warnings;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Prints every file looked for, in the order they are layered, and
+/// whether it exists.
+pub fn path() {
+    for path in config::candidate_paths() {
+        println!("{}", path_line(&path, path.exists()));
+    }
+}
+
+fn path_line(path: &Path, exists: bool) -> String {
+    let state = if exists { "found" } else { "missing" };
+    format!("{:<7}  {}", state, path.display())
+}
+
+/// Prints the merged settings, with `profile` layered over them when given,
+/// or with `default` the built-in ones, as TOML.
+pub fn show(default: bool, profile: Option<&str>) -> Result<(), BcmrError> {
+    let config = match (default, profile) {
+        (true, None) => Config::default(),
+        (true, Some(_)) => {
+            return Err(BcmrError::InvalidInput(
+                "--default shows the built-in settings, which have no profiles".to_string(),
+            ))
+        }
+        (false, profile) => Config::load(&config::config_paths(), profile)?.0,
+    };
+    let text = config
+        .to_toml()
+        .map_err(|e| BcmrError::InvalidInput(format!("cannot print the config: {}", e)))?;
+    print!("{}", annotate_sizes(&text, &config));
+    Ok(())
+}
+
+/// Comments each `[copy]` size with the bytes it was read as, so a unit
+/// taken the wrong way shows.
+fn annotate_sizes(text: &str, config: &Config) -> String {
+    let sizes = config.copy.sizes();
+    let mut section = "";
+    text.lines()
+        .map(|line| {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name;
+            }
+            let key = line.split_once(" = ").map(|(key, _)| key);
+            match sizes
+                .iter()
+                .find(|(k, _)| section == "copy" && key == Some(*k))
+            {
+                Some((_, parsed)) => format!("{}  # {}\n", line, parsed),
+                None => format!("{}\n", line),
+            }
+        })
+        .collect()
+}
+
+/// Writes the commented template to `~/.config/bcmr/config.toml`.
+pub fn init(force: bool) -> Result<(), BcmrError> {
+    let path = user_path()?;
+    if path.exists() && !force {
+        return Err(BcmrError::TargetExists(path));
+    }
+    write_template(&path)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Opens the first config file in use, or a fresh template when there is
+/// none, in the user's editor, then checks what was saved.
+pub fn edit() -> Result<(), BcmrError> {
+    let path = match config::config_paths().into_iter().next() {
+        Some(path) => path,
+        None => {
+            let path = user_path()?;
+            write_template(&path)?;
+            eprintln!("Created {}", path.display());
+            path
+        }
+    };
+
+    let editor = editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .map_err(|e| BcmrError::InvalidInput(format!("cannot run editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(BcmrError::InvalidInput(format!(
+            "editor '{}' exited with {}",
+            editor, status
+        )));
+    }
+
+    let problems = match Config::load(&config::config_paths(), None) {
+        Ok((config, unknown)) => config
+            .progress
+            .validate()
+            .err()
+            .into_iter()
+            .chain(
+                unknown
+                    .into_iter()
+                    .map(|key| format!("unknown config key '{}' ignored", key)),
+            )
+            .collect(),
+        Err(e) => vec![format!(
+            "{} does not load, so defaults apply: {}",
+            path.display(),
+            e
+        )],
+    };
+    print_warnings(&problems);
+    Ok(())
+}
+
+fn user_path() -> Result<PathBuf, BcmrError> {
+    config::user_config_path()
+        .ok_or_else(|| BcmrError::InvalidInput("cannot find the home directory".to_string()))
+}
+
+fn write_template(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, config::TEMPLATE)
+}
+
+/// `$VISUAL`, else `$EDITOR`, else the platform's stock editor. The value
+/// may carry arguments, as in `code --wait`.
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_line_marks_missing_files() {
+        let path = Path::new("/home/u/.config/bcmr/config.toml");
+        assert_eq!(
+            path_line(path, true),
+            "found    /home/u/.config/bcmr/config.toml"
+        );
+        assert_eq!(
+            path_line(path, false),
+            "missing  /home/u/.config/bcmr/config.toml"
+        );
+    }
+
+    #[test]
+    fn test_show_puts_the_bytes_beside_each_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "[copy]\nbwlimit = \"5M\"\nbuffer_size = \"64K\"\ndirect_io_threshold = \"1G\"\n",
+        )
+        .unwrap();
+        let config = Config::from_files(&[path]).unwrap();
+        let text = annotate_sizes(&config.to_toml().unwrap(), &config);
+        assert!(
+            text.contains("\nbwlimit = \"5M\"  # 5242880 bytes/s\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("\nbuffer_size = \"64K\"  # 65536 bytes\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("\ndirect_io_threshold = \"1G\"  # 1073741824 bytes\n"),
+            "{text}"
+        );
+        assert_eq!(
+            annotate_sizes("[hash]\nbuffer_size = 1048576\n", &config),
+            "[hash]\nbuffer_size = 1048576\n"
+        );
+    }
+
+    #[test]
+    fn test_template_is_written_with_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bcmr").join("config.toml");
+        write_template(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), config::TEMPLATE);
+    }
+}
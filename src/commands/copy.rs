@@ -1,42 +1,72 @@
-use crate::cli::Commands;
+use crate::cli::CopyArgs;
 use crate::core::checksum;
-use crate::core::cleanup::{self, CleanupRegistry};
+use crate::core::cleanup::{self, Cleaned, CleanupRegistry, Partial};
+use crate::core::device;
 use crate::core::error::BcmrError;
-use crate::core::traversal;
+use crate::core::io as durable_io;
+use crate::core::oplog;
+use crate::core::preflight::{NameCheck, NameLimits};
+use crate::core::traversal::{self, EntryKind};
+use crate::core::verify_report;
 use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::scan::ScanCounters;
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 
 mod file_copy;
 mod overwrite;
 mod pipeline_batch;
+mod verify_pool;
 
-pub use overwrite::{check_overwrites, get_total_size, FileToOverwrite};
-pub use pipeline_batch::{pipeline_copy, PipelineCallbacks};
+pub use overwrite::FileToOverwrite;
+pub use pipeline_batch::{pipeline_copy, PipelineCallbacks, WorkerCallbacks};
+pub use verify_pool::VerifyPool;
 
 use file_copy::{copy_file, CopyFileOptions};
-use overwrite::{check_overwrite, determine_dry_run_action, is_normal_write};
-
-pub fn cleanup_partial_files() {
-    cleanup::global().drain_and_remove();
+pub(crate) use file_copy::{resolve_sparse_mode, temp_path_for};
+use overwrite::{check_overwrite, determine_dry_run_action, is_normal_write, refuses_existing};
+
+/// Interrupt cleanup for whatever was mid-copy when the run was cancelled:
+/// removes or truncates those files (unless `--keep-partial`) and gives up
+/// the destination lock. Returns a note for the closing line, if anything
+/// was in flight.
+pub fn cleanup_partial_files() -> Option<String> {
+    crate::core::lock::release();
+    crate::core::perms::restore();
+    match cleanup::global().drain_and_remove().as_slice() {
+        [] => None,
+        [one] => Some(one.to_string()),
+        many => {
+            let kept = many.iter().all(|c| matches!(c, Cleaned::Kept(_)));
+            let what = if kept { "kept" } else { "cleaned up" };
+            Some(format!("{} partial files {}", many.len(), what))
+        }
+    }
 }
 
+/// A copy that was cancelled leaves its file registered for
+/// `cleanup_partial_files`, which knows about `--keep-partial` and reports
+/// what it did; any other failure cleans up on the spot.
 pub(crate) struct TempFileGuard {
     registry: &'static CleanupRegistry,
     path: PathBuf,
     active: bool,
+    cancel: CancellationToken,
 }
 
 impl TempFileGuard {
-    pub(crate) fn new(path: PathBuf) -> Self {
+    pub(crate) fn new(path: PathBuf, dst: &Path, cancel: &CancellationToken) -> Self {
         let registry = cleanup::global();
-        registry.register(&path);
+        registry.register_partial(&path, dst, Partial::Remove);
         Self {
             registry,
             path,
             active: true,
+            cancel: cancel.clone(),
         }
     }
 
@@ -48,16 +78,73 @@ impl TempFileGuard {
 
 impl Drop for TempFileGuard {
     fn drop(&mut self) {
-        if self.active {
+        if self.active && !self.cancel.is_cancelled() {
             let _ = std::fs::remove_file(&self.path);
             self.registry.unregister(&self.path);
         }
     }
 }
 
+/// Registers a destination written in place (`--resume`/`--append`/
+/// `--strict`) for interrupt cleanup only: a copy that fails normally
+/// leaves it for the next `--resume` to pick up.
+pub(crate) struct InPlaceGuard {
+    path: PathBuf,
+    cancel: CancellationToken,
+}
+
+impl InPlaceGuard {
+    pub(crate) fn new(dst: &Path, start_offset: u64, cancel: &CancellationToken) -> Self {
+        let partial = if start_offset > 0 {
+            Partial::TruncateTo(start_offset)
+        } else {
+            Partial::Remove
+        };
+        cleanup::global().register_partial(dst, dst, partial);
+        Self {
+            path: dst.to_path_buf(),
+            cancel: cancel.clone(),
+        }
+    }
+}
+
+impl Drop for InPlaceGuard {
+    fn drop(&mut self) {
+        if !self.cancel.is_cancelled() {
+            cleanup::global().unregister(&self.path);
+        }
+    }
+}
+
 pub enum PlanEntry {
-    CreateDir { src: PathBuf, dst: PathBuf },
-    CopyFile { src: PathBuf, dst: PathBuf },
+    CreateDir {
+        src: PathBuf,
+        dst: PathBuf,
+    },
+    /// `label` is the name progress shows (see `traversal::display_relative`).
+    /// `size` and `mtime` are the source's as the scan found them.
+    CopyFile {
+        src: PathBuf,
+        dst: PathBuf,
+        label: String,
+        size: u64,
+        mtime: Option<SystemTime>,
+    },
+}
+
+impl PlanEntry {
+    pub fn size(&self) -> u64 {
+        match self {
+            PlanEntry::CreateDir { .. } => 0,
+            PlanEntry::CopyFile { size, .. } => *size,
+        }
+    }
+
+    pub fn dst(&self) -> &Path {
+        match self {
+            PlanEntry::CreateDir { dst, .. } | PlanEntry::CopyFile { dst, .. } => dst,
+        }
+    }
 }
 
 pub struct CopyPlan {
@@ -66,12 +153,37 @@ pub struct CopyPlan {
     pub overwrites: Vec<FileToOverwrite>,
 }
 
-pub(super) fn scan_sources(
+/// Devices are only read with `--device-source`, so a mistyped `/dev`
+/// path cannot quietly start reading a whole disk.
+pub(crate) fn check_device_sources(
+    sources: &[PathBuf],
+    acknowledged: bool,
+) -> std::result::Result<(), BcmrError> {
+    match sources.iter().find(|src| device::is_device_path(src)) {
+        Some(src) if !acknowledged => Err(BcmrError::InvalidInput(format!(
+            "'{}' is a device; pass --device-source to copy from it",
+            src.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// What a copy's scan holds destination names to: the limits of the
+/// filesystem at `dst`, or none with `--no-preflight`.
+pub(crate) fn name_check(dst: &Path, cli: &CopyArgs) -> NameCheck {
+    let limits = match cli.common.global.no_preflight {
+        true => NameLimits::default(),
+        false => NameLimits::of(dst),
+    };
+    NameCheck::new(dst, limits, cli.skip_too_long)
+}
+
+pub(crate) fn scan_sources(
     sources: &[PathBuf],
     dst: &Path,
     recursive: bool,
     excludes: &[regex::Regex],
-    mut on_entry: impl FnMut(PlanEntry, u64) -> std::result::Result<(), BcmrError>,
+    mut on_entry: impl FnMut(PlanEntry) -> std::result::Result<(), BcmrError>,
 ) -> std::result::Result<(), BcmrError> {
     let dst_is_dir = dst.exists() && dst.is_dir();
 
@@ -80,7 +192,7 @@ pub(super) fn scan_sources(
             continue;
         }
 
-        if src.is_file() {
+        if src.is_file() || device::is_device_path(src) {
             let dst_path =
                 if dst_is_dir {
                     dst.join(src.file_name().ok_or_else(|| {
@@ -90,14 +202,14 @@ pub(super) fn scan_sources(
                     dst.to_path_buf()
                 };
 
-            let size = src.metadata()?.len();
-            on_entry(
-                PlanEntry::CopyFile {
-                    src: src.clone(),
-                    dst: dst_path,
-                },
-                size,
-            )?;
+            let md = src.metadata()?;
+            on_entry(PlanEntry::CopyFile {
+                src: src.clone(),
+                dst: dst_path,
+                label: traversal::display_relative(src, src),
+                size: device::source_size(src)?,
+                mtime: md.modified().ok(),
+            })?;
         } else if recursive && src.is_dir() {
             let src_name = src
                 .file_name()
@@ -108,37 +220,29 @@ pub(super) fn scan_sources(
                 dst.to_path_buf()
             };
 
-            on_entry(
-                PlanEntry::CreateDir {
-                    src: src.clone(),
-                    dst: new_dst.clone(),
-                },
-                0,
-            )?;
+            on_entry(PlanEntry::CreateDir {
+                src: src.clone(),
+                dst: new_dst.clone(),
+            })?;
 
-            for entry in traversal::walk(src, true, false, 1, excludes) {
+            for entry in traversal::scan(src, excludes) {
                 let entry = entry?;
-                let path = entry.path();
-                let relative = path.strip_prefix(src)?;
-                let target = new_dst.join(relative);
-
-                if path.is_dir() {
-                    on_entry(
-                        PlanEntry::CreateDir {
-                            src: path.to_path_buf(),
-                            dst: target,
-                        },
-                        0,
-                    )?;
-                } else if path.is_file() {
-                    let size = entry.metadata()?.len();
-                    on_entry(
-                        PlanEntry::CopyFile {
-                            src: path.to_path_buf(),
-                            dst: target,
-                        },
-                        size,
-                    )?;
+                let target = new_dst.join(&entry.relative);
+                match entry.kind {
+                    EntryKind::Dir => on_entry(PlanEntry::CreateDir {
+                        src: entry.path,
+                        dst: target,
+                    })?,
+                    EntryKind::File => on_entry(PlanEntry::CopyFile {
+                        label: traversal::display_relative(&entry.path, src),
+                        src: entry.path,
+                        dst: target,
+                        size: entry.size,
+                        mtime: entry.mtime,
+                    })?,
+                    EntryKind::Other => {
+                        oplog::warn(&entry.path, traversal::skip_reason(&entry.path))
+                    }
                 }
             }
         } else if src.is_dir() {
@@ -154,38 +258,70 @@ pub(super) fn scan_sources(
     Ok(())
 }
 
+/// Picks out the destinations scanned entries would replace. Entries come
+/// in walk order, so once a directory is missing at the destination
+/// nothing below it needs a look.
+pub(crate) struct OverwriteFinder<'a> {
+    excludes: &'a [regex::Regex],
+    missing: Option<PathBuf>,
+}
+
+impl<'a> OverwriteFinder<'a> {
+    pub(crate) fn new(excludes: &'a [regex::Regex]) -> Self {
+        Self {
+            excludes,
+            missing: None,
+        }
+    }
+
+    pub(crate) fn check(&mut self, entry: &PlanEntry) -> Option<FileToOverwrite> {
+        let (path, is_dir) = match entry {
+            PlanEntry::CopyFile { dst, .. } => (dst, false),
+            PlanEntry::CreateDir { dst, .. } => (dst, true),
+        };
+        if self.missing.as_ref().is_some_and(|m| path.starts_with(m)) {
+            return None;
+        }
+        if !path.exists() {
+            self.missing = is_dir.then(|| path.clone());
+            return None;
+        }
+        self.missing = None;
+        (!traversal::is_excluded(path, self.excludes)).then(|| FileToOverwrite {
+            path: path.clone(),
+            is_dir,
+        })
+    }
+}
+
 fn plan_copy_sync(
     sources: Vec<PathBuf>,
     dst: PathBuf,
     recursive: bool,
     excludes: Vec<regex::Regex>,
+    mut names: NameCheck,
+    counters: &ScanCounters,
 ) -> std::result::Result<CopyPlan, BcmrError> {
     let mut entries = Vec::new();
     let mut total_size = 0u64;
     let mut overwrites = Vec::new();
+    let mut finder = OverwriteFinder::new(&excludes);
 
-    scan_sources(&sources, &dst, recursive, &excludes, |entry, size| {
+    scan_sources(&sources, &dst, recursive, &excludes, |entry| {
+        if !names.admit(entry.dst()) {
+            return Ok(());
+        }
+        let size = entry.size();
         total_size += size;
-
-        let target = match &entry {
-            PlanEntry::CopyFile { dst, .. } => Some((dst.clone(), false)),
-            PlanEntry::CreateDir { dst, .. } => {
-                if dst.exists() {
-                    Some((dst.clone(), true))
-                } else {
-                    None
-                }
-            }
-        };
-        if let Some((path, is_dir)) = target {
-            if path.exists() && !traversal::is_excluded(&path, &excludes) {
-                overwrites.push(FileToOverwrite { path, is_dir });
-            }
+        if let PlanEntry::CopyFile { src, .. } = &entry {
+            counters.record(src, size)?;
         }
 
+        overwrites.extend(finder.check(&entry));
         entries.push(entry);
         Ok(())
     })?;
+    names.finish()?;
 
     Ok(CopyPlan {
         entries,
@@ -199,18 +335,23 @@ pub async fn plan_copy(
     dst: &Path,
     recursive: bool,
     excludes: &[regex::Regex],
+    names: NameCheck,
+    counters: Arc<ScanCounters>,
 ) -> std::result::Result<CopyPlan, BcmrError> {
     let sources = sources.to_vec();
     let dst = dst.to_path_buf();
     let excludes = excludes.to_vec();
-    tokio::task::spawn_blocking(move || plan_copy_sync(sources, dst, recursive, excludes)).await?
+    tokio::task::spawn_blocking(move || {
+        plan_copy_sync(sources, dst, recursive, excludes, names, &counters)
+    })
+    .await?
 }
 
-pub fn dry_run_plan(plan: &CopyPlan, cli: &Commands) -> std::result::Result<(), BcmrError> {
+pub async fn dry_run_plan(plan: &CopyPlan, cli: &CopyArgs) {
     for entry in &plan.entries {
         match entry {
             PlanEntry::CreateDir { src, dst } => {
-                if !dst.exists() {
+                if !durable_io::exists(dst).await {
                     print_dry_run(
                         ActionType::Add,
                         &src.to_string_lossy(),
@@ -218,62 +359,79 @@ pub fn dry_run_plan(plan: &CopyPlan, cli: &Commands) -> std::result::Result<(),
                     );
                 }
             }
-            PlanEntry::CopyFile { src, dst } => {
-                let action = determine_dry_run_action(src, dst, cli)?;
+            PlanEntry::CopyFile {
+                src,
+                dst,
+                size,
+                mtime,
+                ..
+            } => {
+                let dst_md = durable_io::stat(dst).await;
+                let action = determine_dry_run_action(*size, *mtime, dst_md.as_ref(), cli);
                 print_dry_run(action, &src.to_string_lossy(), Some(&dst.to_string_lossy()));
             }
         }
     }
+}
+
+fn create_dirs(dirs: &[PathBuf]) -> std::io::Result<()> {
+    for dir in dirs {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+            oplog::dir_created(dir);
+        }
+    }
     Ok(())
 }
 
 pub async fn execute_plan<F>(
     plan: &CopyPlan,
-    cli: &Commands,
-    progress_callback: F,
-    on_new_file: impl Fn(&str, u64) + Send + Sync + 'static,
+    cli: &CopyArgs,
+    callback: ProgressCallback<F>,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError>
 where
     F: Fn(u64) + Send + Sync + Clone + 'static,
 {
-    let test_mode = cli.get_test_mode();
-    let callback = ProgressCallback {
-        callback: progress_callback,
-        on_new_file: Arc::new(on_new_file),
-    };
+    let test_mode = cli.common.get_test_mode();
 
-    for entry in &plan.entries {
-        if let PlanEntry::CreateDir { dst, .. } = entry {
-            if !dst.exists() {
-                fs::create_dir_all(dst).await?;
-            }
-        }
-    }
+    let dirs: Vec<PathBuf> = plan
+        .entries
+        .iter()
+        .filter_map(|e| match e {
+            PlanEntry::CreateDir { dst, .. } => Some(dst.clone()),
+            _ => None,
+        })
+        .collect();
+    tokio::task::spawn_blocking(move || create_dirs(&dirs)).await??;
 
     use futures::stream::{self, StreamExt};
 
-    let jobs = cli.local_jobs();
-    let verbose = cli.is_verbose();
+    let jobs = cli.common.local_jobs();
+    let verify_pool = VerifyPool::from_cli(cli, &callback);
 
-    let file_entries: Vec<(&PathBuf, &PathBuf)> = plan
+    let file_entries: Vec<(&PathBuf, &PathBuf, &String)> = plan
         .entries
         .iter()
         .filter_map(|e| match e {
-            PlanEntry::CopyFile { src, dst } => Some((src, dst)),
+            PlanEntry::CopyFile {
+                src, dst, label, ..
+            } => Some((src, dst, label)),
             _ => None,
         })
         .collect();
 
-    let stream = stream::iter(file_entries).map(|(src, dst)| {
+    let stream = stream::iter(file_entries).map(|(src, dst, label)| {
         let cb = &callback;
-        let opts = CopyFileOptions::from_cli(cli, test_mode.clone());
+        let opts = CopyFileOptions::from_cli(cli, test_mode.clone(), cancel)
+            .with_label(label)
+            .with_verify_pool(&verify_pool);
         async move {
-            check_overwrite(dst, cli).await?;
-            copy_file(src, dst, opts, cb).await?;
-            if verbose {
-                eprintln!("'{}' -> '{}'", src.display(), dst.display());
+            if cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
             }
-            Ok::<(), BcmrError>(())
+            check_overwrite(dst, cli).await?;
+            copy_file(src, dst, opts, cb).await
         }
     });
 
@@ -281,8 +439,11 @@ where
     while let Some(res) = buf.next().await {
         res?;
     }
+    if let Some(pool) = &verify_pool {
+        pool.finish().await?;
+    }
 
-    if cli.is_preserve() {
+    if cli.common.is_preserve() {
         for entry in plan.entries.iter().rev() {
             if let PlanEntry::CreateDir { src, dst } = entry {
                 preserve_attributes(src, dst).await?;
@@ -294,10 +455,62 @@ where
 }
 
 type OnNewFileFn = Arc<dyn Fn(&str, u64) + Send + Sync>;
+type OnFilesDoneFn = Arc<dyn Fn(usize) + Send + Sync>;
+type OnSkipFn = Arc<dyn Fn(u64) + Send + Sync>;
 
 pub struct ProgressCallback<F> {
     pub(super) callback: F,
     pub(super) on_new_file: OnNewFileFn,
+    pub(super) on_files_done: OnFilesDoneFn,
+    pub(super) on_skip: Option<OnSkipFn>,
+    pub(super) on_verify_queued: OnFilesDoneFn,
+    pub(super) on_verified: OnFilesDoneFn,
+}
+
+impl<F: Fn(u64)> ProgressCallback<F> {
+    pub fn new(callback: F, on_new_file: impl Fn(&str, u64) + Send + Sync + 'static) -> Self {
+        Self {
+            callback,
+            on_new_file: Arc::new(on_new_file),
+            on_files_done: Arc::new(|_| {}),
+            on_skip: None,
+            on_verify_queued: Arc::new(|_| {}),
+            on_verified: Arc::new(|_| {}),
+        }
+    }
+
+    pub fn with_files_done(
+        mut self,
+        on_files_done: impl Fn(usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_files_done = Arc::new(on_files_done);
+        self
+    }
+
+    pub fn with_skip(mut self, on_skip: impl Fn(u64) + Send + Sync + 'static) -> Self {
+        self.on_skip = Some(Arc::new(on_skip));
+        self
+    }
+
+    /// Files handed to the `--verify` workers, and files they are done with.
+    pub fn with_verify(
+        mut self,
+        on_queued: impl Fn(usize) + Send + Sync + 'static,
+        on_verified: impl Fn(usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_verify_queued = Arc::new(on_queued);
+        self.on_verified = Arc::new(on_verified);
+        self
+    }
+
+    /// Credit for bytes already at the destination (resume, up-to-date
+    /// files). Without a skip hook it counts as ordinary progress.
+    pub(super) fn skip(&self, bytes: u64) {
+        match &self.on_skip {
+            Some(on_skip) => on_skip(bytes),
+            None => (self.callback)(bytes),
+        }
+    }
 }
 
 impl<F: Clone> Clone for ProgressCallback<F> {
@@ -305,47 +518,79 @@ impl<F: Clone> Clone for ProgressCallback<F> {
         Self {
             callback: self.callback.clone(),
             on_new_file: Arc::clone(&self.on_new_file),
+            on_files_done: Arc::clone(&self.on_files_done),
+            on_skip: self.on_skip.clone(),
+            on_verify_queued: Arc::clone(&self.on_verify_queued),
+            on_verified: Arc::clone(&self.on_verified),
         }
     }
 }
 
+/// A single file through the same path a copy takes, with nothing
+/// listening for progress. `bcmr bench` times this.
+pub(crate) async fn copy_single_file(
+    src: &Path,
+    dst: &Path,
+    cli: &CopyArgs,
+    cancel: &CancellationToken,
+) -> std::result::Result<(), BcmrError> {
+    let callback = ProgressCallback::new(|_| {}, |_, _| {});
+    copy_file(
+        src,
+        dst,
+        CopyFileOptions::from_cli(cli, cli.common.get_test_mode(), cancel),
+        &callback,
+    )
+    .await
+}
+
 pub async fn copy_path<F>(
     src: &Path,
     dst: &Path,
-    cli: &Commands,
+    cli: &CopyArgs,
     excludes: &[regex::Regex],
-    progress_callback: F,
-    on_new_file: impl Fn(&str, u64) + Send + Sync + 'static,
+    callback: ProgressCallback<F>,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError>
 where
     F: Fn(u64) + Send + Sync + Clone + 'static,
 {
-    let test_mode = cli.get_test_mode();
-    let callback = ProgressCallback {
-        callback: progress_callback,
-        on_new_file: Arc::new(on_new_file),
-    };
+    let test_mode = cli.common.get_test_mode();
 
     if traversal::is_excluded(src, excludes) {
         return Ok(());
     }
+    let verify_pool = VerifyPool::from_cli(cli, &callback);
 
-    if src.is_file() {
+    let src_md = durable_io::stat(src).await;
+    let src_is_dir = src_md.as_ref().is_some_and(|md| md.is_dir());
+    let dst_is_dir = durable_io::stat(dst).await.is_some_and(|md| md.is_dir());
+
+    if let Some(src_md) = src_md.filter(|md| md.is_file()) {
         let dst_path =
-            if dst.is_dir() {
+            if dst_is_dir {
                 dst.join(src.file_name().ok_or_else(|| {
                     BcmrError::InvalidInput("Invalid source file name".to_string())
                 })?)
             } else {
                 dst.to_path_buf()
             };
+        let dst_md = durable_io::stat(&dst_path).await;
 
-        if dst_path.exists() && !cli.is_force() && is_normal_write(cli) {
-            return Err(BcmrError::TargetExists(dst_path));
+        if dst_md.is_some() && refuses_existing(cli) {
+            return Err(oplog::failed(
+                &dst_path,
+                BcmrError::TargetExists(dst_path.clone()),
+            ));
         }
 
-        if cli.is_dry_run() {
-            let action = determine_dry_run_action(src, &dst_path, cli)?;
+        if cli.common.is_dry_run() {
+            let action = determine_dry_run_action(
+                src_md.len(),
+                src_md.modified().ok(),
+                dst_md.as_ref(),
+                cli,
+            );
             print_dry_run(
                 action,
                 &src.to_string_lossy(),
@@ -354,114 +599,131 @@ where
             return Ok(());
         }
 
-        if dst_path.exists() && cli.is_force() && !is_normal_write(cli) {
+        if dst_md.is_some() && cli.common.is_force() && !is_normal_write(cli) {
             fs::remove_file(&dst_path).await?;
         }
 
         copy_file(
             src,
             &dst_path,
-            CopyFileOptions::from_cli(cli, test_mode),
+            CopyFileOptions::from_cli(cli, test_mode, cancel).with_verify_pool(&verify_pool),
             &callback,
         )
         .await?;
-
-        if cli.is_verbose() {
-            eprintln!("'{}' -> '{}'", src.display(), dst_path.display());
-        }
-    } else if cli.is_recursive() && src.is_dir() {
+    } else if cli.common.is_recursive() && src_is_dir {
         let src_dir_name = src
             .file_name()
             .ok_or_else(|| BcmrError::InvalidInput("Invalid source directory name".to_string()))?;
-        let new_dst = if dst.is_dir() {
+        let new_dst = if dst_is_dir {
             dst.join(src_dir_name)
         } else {
             dst.to_path_buf()
         };
 
-        if cli.is_dry_run() && !new_dst.exists() {
-            print_dry_run(
-                ActionType::Add,
-                &src.to_string_lossy(),
-                Some(&format!("(DIR) -> {}", new_dst.display())),
-            );
-        }
-
-        if !new_dst.exists() && !cli.is_dry_run() {
-            fs::create_dir_all(&new_dst).await?;
+        if !durable_io::exists(&new_dst).await {
+            if cli.common.is_dry_run() {
+                print_dry_run(
+                    ActionType::Add,
+                    &src.to_string_lossy(),
+                    Some(&format!("(DIR) -> {}", new_dst.display())),
+                );
+            } else {
+                fs::create_dir_all(&new_dst).await?;
+                oplog::dir_created(&new_dst);
+            }
         }
 
-        let mut files_to_copy = Vec::new();
-        let mut dir_pairs: Vec<(PathBuf, PathBuf)> = Vec::new();
-        for entry in traversal::walk(src, true, false, 1, excludes) {
+        // Entries are handled as the walk yields them, so memory stays flat
+        // however large the tree. Directory attributes wait until the walk
+        // has left the directory (its files would bump the mtime), which
+        // only takes the chain of directories above the current entry.
+        let preserve = cli.common.is_preserve() && !cli.common.is_dry_run();
+        let mut open_dirs: Vec<(usize, PathBuf, PathBuf)> = Vec::new();
+        let mut entries = traversal::in_background({
+            let (src, excludes) = (src.to_path_buf(), excludes.to_vec());
+            move || traversal::scan(&src, &excludes)
+        });
+        while let Some(entry) = entries.recv().await {
+            if cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
+            }
             let entry = entry?;
-            let path = entry.path();
+            let path = entry.path.as_path();
+
+            while open_dirs
+                .last()
+                .is_some_and(|(depth, ..)| *depth >= entry.depth)
+            {
+                if let Some((_, src_dir, dst_dir)) = open_dirs.pop() {
+                    preserve_attributes(&src_dir, &dst_dir).await?;
+                }
+            }
 
-            let relative_path = path.strip_prefix(src)?;
-            let target_path = new_dst.join(relative_path);
+            let dst_path = new_dst.join(&entry.relative);
+            let dst_md = durable_io::stat(&dst_path).await;
 
-            if path.is_dir() {
-                if !cli.is_dry_run() {
-                    if !target_path.exists() {
-                        fs::create_dir_all(&target_path).await?;
+            if entry.kind == EntryKind::Dir {
+                if !cli.common.is_dry_run() {
+                    if dst_md.is_none() {
+                        fs::create_dir_all(&dst_path).await?;
+                        oplog::dir_created(&dst_path);
+                    }
+                    if preserve {
+                        open_dirs.push((entry.depth, path.to_path_buf(), dst_path));
                     }
-                    dir_pairs.push((path.to_path_buf(), target_path));
-                } else if !target_path.exists() {
+                } else if dst_md.is_none() {
                     print_dry_run(
                         ActionType::Add,
                         &path.to_string_lossy(),
-                        Some(&format!("(DIR) -> {}", target_path.display())),
+                        Some(&format!("(DIR) -> {}", dst_path.display())),
                     );
                 }
-            } else if path.is_file() {
-                files_to_copy.push((path.to_path_buf(), target_path));
+                continue;
             }
-        }
-
-        for (src_path, dst_path) in files_to_copy {
-            if let Some(parent) = dst_path.parent() {
-                if !parent.exists() && !cli.is_dry_run() {
-                    fs::create_dir_all(parent).await?;
-                }
+            if entry.kind == EntryKind::Other {
+                oplog::warn(path, traversal::skip_reason(path));
+                continue;
             }
 
-            if dst_path.exists() && !cli.is_force() && is_normal_write(cli) {
-                return Err(BcmrError::TargetExists(dst_path));
+            if dst_md.is_some() && refuses_existing(cli) {
+                return Err(oplog::failed(
+                    &dst_path,
+                    BcmrError::TargetExists(dst_path.clone()),
+                ));
             }
 
-            if cli.is_dry_run() {
-                let action = determine_dry_run_action(&src_path, &dst_path, cli)?;
+            if cli.common.is_dry_run() {
+                let action =
+                    determine_dry_run_action(entry.size, entry.mtime, dst_md.as_ref(), cli);
                 print_dry_run(
                     action,
-                    &src_path.to_string_lossy(),
+                    &path.to_string_lossy(),
                     Some(&dst_path.to_string_lossy()),
                 );
             } else {
-                if dst_path.exists() && cli.is_force() && !is_normal_write(cli) {
+                if dst_md.is_some() && cli.common.is_force() && !is_normal_write(cli) {
                     fs::remove_file(&dst_path).await?;
                 }
 
                 copy_file(
-                    &src_path,
+                    path,
                     &dst_path,
-                    CopyFileOptions::from_cli(cli, test_mode.clone()),
+                    CopyFileOptions::from_cli(cli, test_mode.clone(), cancel)
+                        .with_label(&traversal::display_relative(path, src))
+                        .with_verify_pool(&verify_pool),
                     &callback,
                 )
                 .await?;
-
-                if cli.is_verbose() {
-                    eprintln!("'{}' -> '{}'", src_path.display(), dst_path.display());
-                }
             }
         }
 
-        if cli.is_preserve() && !cli.is_dry_run() {
-            for (src_dir, dst_dir) in dir_pairs.iter().rev() {
-                preserve_attributes(src_dir, dst_dir).await?;
+        if preserve {
+            while let Some((_, src_dir, dst_dir)) = open_dirs.pop() {
+                preserve_attributes(&src_dir, &dst_dir).await?;
             }
             preserve_attributes(src, &new_dst).await?;
         }
-    } else if src.is_dir() {
+    } else if src_is_dir {
         return Err(BcmrError::InvalidInput(format!(
             "Source '{}' is a directory. Use -r flag for recursive copy.",
             src.display()
@@ -470,57 +732,153 @@ where
         return Err(BcmrError::SourceNotFound(src.to_path_buf()));
     }
 
-    Ok(())
+    match &verify_pool {
+        Some(pool) => pool.finish().await,
+        None => Ok(()),
+    }
 }
 
 pub(crate) async fn preserve_attributes(
     src: &Path,
     dst: &Path,
 ) -> std::result::Result<(), BcmrError> {
-    let src_metadata = src.metadata()?;
-    let permissions = src_metadata.permissions();
-    tokio::fs::set_permissions(dst, permissions).await?;
+    let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+    tokio::task::spawn_blocking(move || preserve_attributes_sync(&src, &dst)).await?
+}
 
+fn preserve_attributes_sync(src: &Path, dst: &Path) -> std::result::Result<(), BcmrError> {
+    let src_metadata = src.metadata()?;
+    // A link already at the destination gets the attributes itself:
+    // following it would hand them to its target, which may lie anywhere.
+    let dst_is_link = dst
+        .symlink_metadata()
+        .is_ok_and(|md| md.file_type().is_symlink());
+    #[allow(unused_mut)]
+    let mut permissions = src_metadata.permissions();
     #[cfg(unix)]
-    {
-        use std::os::unix::fs::MetadataExt;
-        let atime = filetime::FileTime::from_unix_time(src_metadata.atime(), 0);
-        let mtime = filetime::FileTime::from_unix_time(src_metadata.mtime(), 0);
-        filetime::set_file_times(dst, atime, mtime)?;
+    if let Some(mode) = crate::core::perms::original_mode(src) {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(mode);
+    }
+    if dst_is_link {
+        set_link_permissions(dst, &permissions)?;
+    } else {
+        std::fs::set_permissions(dst, permissions)?;
     }
 
+    #[cfg(unix)]
+    let (atime, mtime) = {
+        use std::os::unix::fs::MetadataExt;
+        (
+            filetime::FileTime::from_unix_time(src_metadata.atime(), 0),
+            filetime::FileTime::from_unix_time(src_metadata.mtime(), 0),
+        )
+    };
     #[cfg(windows)]
-    {
-        let atime = filetime::FileTime::from_last_access_time(&src_metadata);
-        let mtime = filetime::FileTime::from_last_modification_time(&src_metadata);
+    let (atime, mtime) = (
+        filetime::FileTime::from_last_access_time(&src_metadata),
+        filetime::FileTime::from_last_modification_time(&src_metadata),
+    );
+    #[cfg(any(unix, windows))]
+    if dst_is_link {
+        filetime::set_symlink_file_times(dst, atime, mtime)?;
+    } else {
         filetime::set_file_times(dst, atime, mtime)?;
     }
 
     #[cfg(any(target_os = "linux", target_os = "macos"))]
-    file_copy::copy_xattrs(src, dst)?;
+    if !dst_is_link {
+        file_copy::copy_xattrs(src, dst)?;
+    }
+
+    Ok(())
+}
+
+/// `lchmod` for the platforms that have one. Elsewhere a link has no mode
+/// of its own (Linux) or none worth setting, and this leaves it alone.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn set_link_permissions(link: &Path, permissions: &std::fs::Permissions) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    let path = std::ffi::CString::new(link.as_os_str().as_bytes())?;
+    let mode = (permissions.mode() & 0o7777) as libc::mode_t;
+    // SAFETY: fchmodat only reads the NUL-terminated path.
+    let rc = unsafe {
+        libc::fchmodat(
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            mode,
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    match rc {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
 
+#[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
+fn set_link_permissions(_link: &Path, _permissions: &std::fs::Permissions) -> std::io::Result<()> {
     Ok(())
 }
 
 pub(crate) async fn verify_copy(
     src: &Path,
     dst: &Path,
-    inline_src_hash: Option<blake3::Hash>,
+    algo: checksum::Algorithm,
+    direct: bool,
+    inline_src_hash: Option<String>,
+    verbose: bool,
 ) -> std::result::Result<(), BcmrError> {
     let src_hash_str = if let Some(h) = inline_src_hash {
-        h.to_hex().to_string()
+        h
     } else {
         let src_path = src.to_path_buf();
-        tokio::task::spawn_blocking(move || checksum::calculate_hash(&src_path)).await??
+        tokio::task::spawn_blocking(move || checksum::calculate_hash(&src_path, algo)).await??
     };
 
+    // Read back past the page cache with --verify-direct, or the check
+    // only proves the cache holds the right bytes.
     let dst_path = dst.to_path_buf();
-    let dst_hash_str =
-        tokio::task::spawn_blocking(move || checksum::calculate_hash(&dst_path)).await??;
+    let opts = checksum::ReadOptions {
+        direct,
+        ..checksum::read_options()
+    };
+    let dst_hash_str = tokio::task::spawn_blocking(move || {
+        checksum::hash_file_with(&dst_path, algo, opts, |_| {})
+    })
+    .await??;
+
+    crate::core::journal::verified(src, dst, algo, &src_hash_str, &dst_hash_str);
+    crate::core::history::verified(dst, algo, &dst_hash_str);
+    if verify_report::is_open() {
+        verify_report::record(&verify_report::Outcome {
+            src,
+            dst,
+            size: fs::metadata(dst).await?.len(),
+            src_digest: Some(&src_hash_str),
+            dst_digest: Some(&dst_hash_str),
+        });
+    }
 
     if src_hash_str != dst_hash_str {
+        // Where the copy went wrong tells a torn tail from scattered bit
+        // rot; counting every bad block costs a full read, so only -v does.
+        let (src_path, dst_path) = (src.to_path_buf(), dst.to_path_buf());
+        let mismatch = tokio::task::spawn_blocking(move || {
+            checksum::find_mismatch(
+                &src_path,
+                &dst_path,
+                crate::core::session::COPY_BLOCK_SIZE as usize,
+                verbose,
+            )
+        })
+        .await
+        .ok()
+        .and_then(|r| r.ok())
+        .flatten();
         let _ = fs::remove_file(dst).await;
-        return Err(BcmrError::VerificationError(dst.to_path_buf()));
+        return Err(BcmrError::VerificationError(dst.to_path_buf(), mismatch));
     }
     Ok(())
 }
@@ -1,14 +1,20 @@
-use crate::cli::{Commands, SparseMode, TestMode};
+use crate::cli::{CopyArgs, SparseMode, TestMode};
+use crate::core::checksum::Algorithm;
+use crate::core::device;
 use crate::core::error::BcmrError;
+use crate::core::io as durable_io;
+use crate::core::oplog::{self, Action, WriteKind};
 
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::{self, File};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tokio_util::sync::CancellationToken;
 
-use super::{ProgressCallback, TempFileGuard};
+use super::{InPlaceGuard, ProgressCallback, TempFileGuard, VerifyPool};
 
-fn temp_path_for(dst: &Path) -> PathBuf {
+pub(crate) fn temp_path_for(dst: &Path) -> PathBuf {
     let name = dst.file_name().unwrap_or_default().to_string_lossy();
     dst.with_file_name(format!(".{}.bcmr.tmp", name))
 }
@@ -19,24 +25,29 @@ async fn try_copy_file_range(
     dst: &Path,
     file_size: u64,
     callback: &impl Fn(u64),
+    cancel: &CancellationToken,
 ) -> Option<Result<(), BcmrError>> {
     use std::os::unix::io::AsRawFd;
 
-    let src_file = std::fs::File::open(src).ok()?;
-    let dst_file = std::fs::OpenOptions::new()
+    let src_file = File::open(src).await.ok()?.into_std().await;
+    let dst_file = fs::OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
         .open(dst)
-        .ok()?;
+        .await
+        .ok()?
+        .into_std()
+        .await;
 
     let src_fd = src_file.as_raw_fd();
     let dst_fd = dst_file.as_raw_fd();
 
     if file_size > 0 {
-        unsafe {
-            let _ = libc::fallocate(dst_fd, 0, 0, file_size as libc::off_t);
-        }
+        let _ = tokio::task::spawn_blocking(move || unsafe {
+            libc::fallocate(dst_fd, 0, 0, file_size as libc::off_t)
+        })
+        .await;
     }
 
     const CHUNK: usize = 4 * 1024 * 1024;
@@ -44,6 +55,9 @@ async fn try_copy_file_range(
 
     while remaining > 0 {
         let to_copy = (remaining as usize).min(CHUNK);
+        if cancel.is_cancelled() {
+            return Some(Err(BcmrError::Cancelled));
+        }
         let sfd = src_fd;
         let dfd = dst_fd;
         let result = tokio::task::spawn_blocking(move || {
@@ -75,7 +89,7 @@ async fn try_copy_file_range(
                     || errno == libc::EOPNOTSUPP
                 {
                     drop(dst_file);
-                    let _ = std::fs::remove_file(dst);
+                    let _ = fs::remove_file(dst).await;
                     return None;
                 }
                 return Some(Err(BcmrError::Io(err)));
@@ -104,13 +118,26 @@ pub(super) fn copy_xattrs(src: &Path, dst: &Path) -> std::result::Result<(), Bcm
             Ok(Some(v)) => v,
             Ok(None) => continue,
             Err(e) if is_unsupported(&e) => continue,
-            Err(_) => continue,
+            Err(e) => {
+                xattr_warning(src, &name, &e);
+                continue;
+            }
         };
-        let _ = xattr::set(dst, &name, &value);
+        if let Err(e) = xattr::set(dst, &name, &value) {
+            xattr_warning(dst, &name, &e);
+        }
     }
     Ok(())
 }
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn xattr_warning(path: &Path, name: &std::ffi::OsStr, e: &std::io::Error) {
+    oplog::warn(
+        path,
+        &format!("could not preserve xattr {}: {}", name.to_string_lossy(), e),
+    );
+}
+
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 fn is_unsupported(e: &std::io::Error) -> bool {
     // 95 = ENOTSUP on Linux, 45 = ENOTSUP on macOS.
@@ -121,25 +148,65 @@ pub(super) struct CopyFileOptions {
     transfer: crate::core::remote::TransferOptions,
     reflink_arg: Option<String>,
     sparse_arg: Option<String>,
+    checksum_algo: Algorithm,
+    verify_direct: bool,
     test_mode: TestMode,
+    verbose: bool,
+    no_clobber: bool,
+    label: Option<String>,
+    verify_pool: Option<Arc<VerifyPool>>,
+    cancel: CancellationToken,
+    /// `[copy] buffer_size`.
+    buffer_size: usize,
+    /// `[copy] direct_io_threshold`.
+    direct_io_threshold: Option<u64>,
 }
 
 impl CopyFileOptions {
-    pub(super) fn from_cli(cli: &Commands, test_mode: TestMode) -> Self {
+    pub(super) fn from_cli(
+        cli: &CopyArgs,
+        test_mode: TestMode,
+        cancel: &CancellationToken,
+    ) -> Self {
         Self {
             transfer: crate::core::remote::TransferOptions {
-                preserve: cli.is_preserve(),
-                verify: cli.is_verify(),
-                resume: cli.is_resume(),
-                strict: cli.is_strict(),
-                append: cli.is_append(),
-                sync: cli.is_sync(),
+                preserve: cli.common.is_preserve(),
+                verify: cli.common.is_verify(),
+                resume: cli.common.is_resume(),
+                strict: cli.common.is_strict(),
+                append: cli.common.is_append(),
+                sync: cli.common.is_sync(),
             },
             reflink_arg: cli.get_reflink_mode(),
             sparse_arg: cli.get_sparse_mode(),
+            checksum_algo: cli.common.checksum_algo(),
+            verify_direct: cli.common.is_verify_direct(),
             test_mode,
+            verbose: cli.common.is_verbose(),
+            no_clobber: cli.common.is_no_clobber(),
+            label: None,
+            verify_pool: None,
+            cancel: cancel.clone(),
+            buffer_size: crate::config::CONFIG.copy.buffer_bytes(),
+            direct_io_threshold: crate::config::CONFIG
+                .copy
+                .direct_io_threshold
+                .as_ref()
+                .map(|t| t.bytes),
         }
     }
+
+    /// Name to report to progress instead of the bare file name.
+    pub(super) fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Hands `--verify` to the pool rather than hashing before returning.
+    pub(super) fn with_verify_pool(mut self, pool: &Option<Arc<VerifyPool>>) -> Self {
+        self.verify_pool = pool.clone();
+        self
+    }
 }
 
 fn resolve_reflink_mode(arg: &Option<String>) -> (bool, bool) {
@@ -153,7 +220,7 @@ fn resolve_reflink_mode(arg: &Option<String>) -> (bool, bool) {
     }
 }
 
-fn resolve_sparse_mode(arg: &Option<String>) -> SparseMode {
+pub(crate) fn resolve_sparse_mode(arg: &Option<String>) -> SparseMode {
     let mode_str = arg.as_deref().unwrap_or(&crate::config::CONFIG.copy.sparse);
     match mode_str.to_lowercase().as_str() {
         "force" => SparseMode::Always,
@@ -164,11 +231,37 @@ fn resolve_sparse_mode(arg: &Option<String>) -> SparseMode {
 
 type FinalizeCtx<'a> = super::super::copy_strategies::FinalizeParams<'a>;
 
+enum CopyOutcome {
+    /// `reflinked` when the data was cloned rather than written.
+    Copied {
+        bytes: u64,
+        appended: bool,
+        reflinked: bool,
+    },
+    UpToDate,
+    /// The data was current; `-p` only had attributes to set.
+    AttributesSet,
+}
+
 async fn run_finalize(
     ctx: FinalizeCtx<'_>,
     dst_file: fs::File,
-) -> std::result::Result<(), BcmrError> {
-    super::super::copy_strategies::finalize(dst_file, ctx).await
+    file_size: u64,
+    appended: bool,
+    reflinked: bool,
+) -> std::result::Result<CopyOutcome, BcmrError> {
+    super::super::copy_strategies::finalize(dst_file, ctx).await?;
+    Ok(CopyOutcome::Copied {
+        bytes: file_size,
+        appended,
+        reflinked,
+    })
+}
+
+/// Whether `-p` would change anything on a destination whose data is
+/// already the source's: its permissions or modification time.
+fn attributes_differ(src: &std::fs::Metadata, dst: &std::fs::Metadata) -> bool {
+    src.permissions() != dst.permissions() || src.modified().ok() != dst.modified().ok()
 }
 
 pub(super) async fn copy_file<F>(
@@ -177,6 +270,84 @@ pub(super) async fn copy_file<F>(
     opts: CopyFileOptions,
     callback: &ProgressCallback<F>,
 ) -> std::result::Result<(), BcmrError>
+where
+    F: Fn(u64) + Send + Sync + Clone + 'static,
+{
+    let verbose = opts.verbose;
+    let existed = durable_io::exists(dst).await;
+    if existed && opts.no_clobber {
+        callback.skip(device::source_size(src)?);
+        oplog::report(
+            Action::Skip {
+                path: dst,
+                reason: "exists",
+            },
+            verbose,
+        );
+        (callback.on_files_done)(1);
+        return Ok(());
+    }
+    match copy_file_inner(src, dst, opts, callback).await {
+        Ok(CopyOutcome::Copied {
+            bytes,
+            appended,
+            reflinked,
+        }) => {
+            let write = if appended {
+                WriteKind::Appended
+            } else if existed {
+                WriteKind::Overwritten
+            } else {
+                WriteKind::Created
+            };
+            oplog::report(
+                Action::Copy {
+                    src,
+                    dst,
+                    bytes,
+                    write,
+                },
+                verbose,
+            );
+            if reflinked {
+                oplog::reflinked(bytes);
+            }
+            (callback.on_files_done)(1);
+            Ok(())
+        }
+        Ok(CopyOutcome::AttributesSet) => {
+            oplog::report(
+                Action::Skip {
+                    path: dst,
+                    reason: "attributes updated",
+                },
+                verbose,
+            );
+            oplog::metadata_updated();
+            (callback.on_files_done)(1);
+            Ok(())
+        }
+        Ok(CopyOutcome::UpToDate) => {
+            oplog::report(
+                Action::Skip {
+                    path: dst,
+                    reason: "up to date",
+                },
+                verbose,
+            );
+            (callback.on_files_done)(1);
+            Ok(())
+        }
+        Err(e) => Err(oplog::failed(src, e)),
+    }
+}
+
+async fn copy_file_inner<F>(
+    src: &Path,
+    dst: &Path,
+    opts: CopyFileOptions,
+    callback: &ProgressCallback<F>,
+) -> std::result::Result<CopyOutcome, BcmrError>
 where
     F: Fn(u64) + Send + Sync + Clone + 'static,
 {
@@ -184,7 +355,16 @@ where
         transfer,
         ref reflink_arg,
         ref sparse_arg,
+        checksum_algo,
+        verify_direct,
         test_mode,
+        ref label,
+        ref verify_pool,
+        ref cancel,
+        verbose,
+        buffer_size,
+        direct_io_threshold,
+        ..
     } = opts;
     let crate::core::remote::TransferOptions {
         preserve,
@@ -195,19 +375,31 @@ where
         sync,
     } = transfer;
 
-    let file_size = src.metadata()?.len();
-    let file_name = src
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
+    let is_device = device::is_device(&fs::metadata(src).await?);
+    let file_size = device::source_size(src)?;
+    // A whole block per read keeps device reads large and sector-aligned
+    // whatever `[copy] buffer_size` says.
+    let buffer_size = if is_device {
+        crate::core::session::COPY_BLOCK_SIZE as usize
+    } else {
+        buffer_size
+    };
+    let uncached = direct_io_threshold.is_some_and(|t| file_size >= t);
+    let file_name = match label {
+        Some(label) => label.clone(),
+        None => src
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    };
     (*callback.on_new_file)(&file_name, file_size);
 
     let (try_reflink, fail_on_error) = resolve_reflink_mode(reflink_arg);
     let sparse_mode = resolve_sparse_mode(sparse_arg);
 
     if let Some(parent) = dst.parent() {
-        if !parent.exists() {
+        if !durable_io::exists(parent).await {
             fs::create_dir_all(parent).await?;
         }
     }
@@ -218,10 +410,8 @@ where
 
     if use_atomic {
         let temp = temp_path_for(dst);
-        if temp.exists() {
-            let _ = fs::remove_file(&temp).await;
-        }
-        guard = Some(TempFileGuard::new(temp.clone()));
+        let _ = fs::remove_file(&temp).await;
+        guard = Some(TempFileGuard::new(temp.clone(), dst, cancel));
         write_target = temp;
     } else {
         write_target = dst.to_path_buf();
@@ -247,15 +437,36 @@ where
             sync,
             preserve,
             verify,
+            checksum_algo,
+            verify_direct,
             inline_src_hash: None,
+            verify_pool: verify_pool.as_deref(),
+            verbose,
         };
-        return run_finalize(ctx, fs::File::open(&write_target).await?).await;
+        return run_finalize(
+            ctx,
+            fs::File::open(&write_target).await?,
+            file_size,
+            false,
+            true,
+        )
+        .await;
     }
 
+    // With --verify the streaming copy below is cheaper: it hashes the
+    // source as it goes, where the kernel copy would need it read again.
+    // The kernel copy also goes through the page cache, and only takes
+    // regular files.
     #[cfg(target_os = "linux")]
-    if use_atomic && matches!(test_mode, TestMode::None) && matches!(sparse_mode, SparseMode::Never)
+    if use_atomic
+        && !is_device
+        && !verify
+        && !uncached
+        && matches!(test_mode, TestMode::None)
+        && matches!(sparse_mode, SparseMode::Never)
+        && !crate::core::bwlimit::is_limited()
     {
-        match try_copy_file_range(src, &write_target, file_size, &callback.callback).await {
+        match try_copy_file_range(src, &write_target, file_size, &callback.callback, cancel).await {
             Some(Ok(())) => {
                 let ctx = FinalizeCtx {
                     write_target: &write_target,
@@ -266,9 +477,20 @@ where
                     sync,
                     preserve,
                     verify,
+                    checksum_algo,
+                    verify_direct,
                     inline_src_hash: None,
+                    verify_pool: verify_pool.as_deref(),
+                    verbose,
                 };
-                return run_finalize(ctx, fs::File::open(&write_target).await?).await;
+                return run_finalize(
+                    ctx,
+                    fs::File::open(&write_target).await?,
+                    file_size,
+                    false,
+                    false,
+                )
+                .await;
             }
             Some(Err(e)) => return Err(e),
             None => {}
@@ -280,14 +502,18 @@ where
         dst,
         file_size,
         resume,
-        strict,
+        strict.then_some(checksum_algo),
         append,
-        &callback.callback,
+        &|n| callback.skip(n),
     )
     .await?;
 
     if resume_state.already_complete {
-        return Ok(());
+        if preserve && attributes_differ(&fs::metadata(src).await?, &fs::metadata(dst).await?) {
+            super::preserve_attributes(src, dst).await?;
+            return Ok(CopyOutcome::AttributesSet);
+        }
+        return Ok(CopyOutcome::UpToDate);
     }
 
     let start_offset = resume_state.start_offset;
@@ -302,6 +528,7 @@ where
     }
 
     let mut src_file = File::open(src).await?;
+    let _in_place = (!use_atomic).then(|| InPlaceGuard::new(dst, start_offset, cancel));
     let mut dst_file = file_flags.open(&write_target).await?;
 
     if start_offset > 0 {
@@ -339,6 +566,9 @@ where
         TestMode::Delay(ms) => {
             let mut buffer = vec![0u8; crate::core::session::COPY_BLOCK_SIZE as usize];
             loop {
+                if cancel.is_cancelled() {
+                    return Err(BcmrError::Cancelled);
+                }
                 let n = src_file.read(&mut buffer).await?;
                 if n == 0 {
                     break;
@@ -349,35 +579,19 @@ where
             }
             None
         }
-        TestMode::SpeedLimit(bps) => {
-            let mut buffer = vec![0u8; crate::core::session::COPY_BLOCK_SIZE as usize];
-            let chunk_size = bps.min(buffer.len() as u64);
-            let mut start_time = Instant::now();
-            loop {
-                let n = src_file.read(&mut buffer[..chunk_size as usize]).await?;
-                if n == 0 {
-                    break;
-                }
-                dst_file.write_all(&buffer[..n]).await?;
-                let elapsed = start_time.elapsed();
-                let target = Duration::from_secs_f64(n as f64 / bps as f64);
-                if elapsed < target {
-                    tokio::time::sleep(target - elapsed).await;
-                    start_time = Instant::now();
-                }
-                (callback.callback)(n as u64);
-            }
-            None
-        }
         TestMode::None => {
-            let need_src_hash = verify || session.is_some();
             super::super::copy_strategies::streaming_copy(
                 &mut src_file,
                 &mut dst_file,
                 &mut session,
-                &sparse_mode,
-                start_offset,
-                need_src_hash,
+                super::super::copy_strategies::StreamOptions {
+                    sparse_mode: sparse_mode.clone(),
+                    start_offset,
+                    verify_algo: verify.then_some(checksum_algo),
+                    cancel: cancel.clone(),
+                    buffer_size,
+                    uncached,
+                },
                 &callback.callback,
             )
             .await?
@@ -393,7 +607,11 @@ where
         sync,
         preserve,
         verify,
+        checksum_algo,
+        verify_direct,
         inline_src_hash,
+        verify_pool: verify_pool.as_deref(),
+        verbose,
     };
-    run_finalize(ctx, dst_file).await
+    run_finalize(ctx, dst_file, file_size, start_offset > 0, false).await
 }
@@ -1,13 +1,18 @@
-use crate::cli::Commands;
+use crate::cli::CopyArgs;
 use crate::core::error::BcmrError;
+use crate::core::io as durable_io;
+use crate::core::oplog;
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 
 use super::file_copy::{copy_file, CopyFileOptions};
 use super::overwrite::check_overwrite;
-use super::{preserve_attributes, scan_sources, PlanEntry, ProgressCallback};
+use super::{
+    name_check, preserve_attributes, scan_sources, PlanEntry, ProgressCallback, VerifyPool,
+};
 
 enum ScanMessage {
     Entry(PlanEntry),
@@ -17,6 +22,17 @@ enum ScanMessage {
 type BoxCallback = Box<dyn Fn(u64) + Send + Sync>;
 type BoxFileCallback = Box<dyn Fn(&str, u64) + Send + Sync>;
 type BoxNotify = Box<dyn Fn() + Send + Sync>;
+type BoxCountCallback = Box<dyn Fn(usize) + Send + Sync>;
+type BoxWorkerFileCallback = Box<dyn Fn(usize, &str, u64) + Send + Sync>;
+type BoxWorkerCallback = Box<dyn Fn(usize, u64) + Send + Sync>;
+
+/// Per-slot progress for `--jobs` > 1. Each in-flight copy owns a slot until
+/// it finishes, so a renderer can give every worker its own row.
+pub struct WorkerCallbacks {
+    pub on_file: BoxWorkerFileCallback,
+    pub on_progress: BoxWorkerCallback,
+    pub on_done: BoxCountCallback,
+}
 
 pub struct PipelineCallbacks<F: Fn(u64) + Send + Sync> {
     pub on_progress: F,
@@ -24,44 +40,67 @@ pub struct PipelineCallbacks<F: Fn(u64) + Send + Sync> {
     pub on_total_update: BoxCallback,
     pub on_scan_complete: BoxNotify,
     pub on_file_found: BoxCallback,
+    pub on_files_done: BoxCountCallback,
+    pub on_skip: BoxCallback,
+    pub on_verify_queued: BoxCountCallback,
+    pub on_verified: BoxCountCallback,
+    pub workers: Option<WorkerCallbacks>,
 }
 
 pub async fn pipeline_copy<F>(
     sources: &[PathBuf],
     dst: &Path,
-    cli: &Commands,
+    cli: &CopyArgs,
     excludes: &[regex::Regex],
     cb: PipelineCallbacks<F>,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError>
 where
     F: Fn(u64) + Send + Sync + Clone + 'static,
 {
-    let test_mode = cli.get_test_mode();
-    let recursive = cli.is_recursive();
-    let jobs = cli.local_jobs();
-    let verbose = cli.is_verbose();
+    let test_mode = cli.common.get_test_mode();
+    let recursive = cli.common.is_recursive();
+    let jobs = cli.common.local_jobs();
     let callback = ProgressCallback {
         callback: cb.on_progress,
         on_new_file: Arc::from(cb.on_new_file),
+        on_files_done: Arc::from(cb.on_files_done),
+        on_skip: Some(Arc::from(cb.on_skip)),
+        on_verify_queued: Arc::from(cb.on_verify_queued),
+        on_verified: Arc::from(cb.on_verified),
     };
+    let verify_pool = VerifyPool::from_cli(cli, &callback);
     let on_total_update = cb.on_total_update;
     let on_scan_complete = cb.on_scan_complete;
     let on_file_found = cb.on_file_found;
+    let workers = cb.workers.filter(|_| jobs > 1).map(Arc::new);
+    let mut free_slots: Vec<usize> = (0..jobs).rev().collect();
 
     let (tx, mut rx) = tokio::sync::mpsc::channel::<ScanMessage>(256);
 
     let sources = sources.to_vec();
     let dst = dst.to_path_buf();
     let excludes = excludes.to_vec();
+    let scan_cancel = cancel.clone();
+    let mut names = name_check(&dst, cli);
     let scanner = tokio::task::spawn_blocking(move || {
         let mut total_size = 0u64;
         let mut files_found = 0u64;
 
-        let result = scan_sources(&sources, &dst, recursive, &excludes, |entry, size| {
+        let result = scan_sources(&sources, &dst, recursive, &excludes, |entry| {
+            if scan_cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
+            }
+            if !names.admit(entry.dst()) {
+                return Ok(());
+            }
+            let size = entry.size();
             total_size += size;
             if size > 0 {
-                files_found += 1;
                 on_total_update(total_size);
+            }
+            if matches!(entry, PlanEntry::CopyFile { .. }) {
+                files_found += 1;
                 on_file_found(files_found);
             }
             if tx.blocking_send(ScanMessage::Entry(entry)).is_err() {
@@ -71,7 +110,7 @@ where
         });
 
         let _ = tx.blocking_send(ScanMessage::Done);
-        result
+        result.and(names.finish())
     });
 
     let mut dir_entries: Vec<(PathBuf, PathBuf)> = Vec::new();
@@ -81,32 +120,67 @@ where
         match msg {
             ScanMessage::Entry(entry) => match entry {
                 PlanEntry::CreateDir { ref src, ref dst } => {
-                    if !dst.exists() {
+                    if !durable_io::exists(dst).await {
                         fs::create_dir_all(dst).await?;
+                        oplog::dir_created(dst);
                     }
                     dir_entries.push((src.clone(), dst.clone()));
                 }
-                PlanEntry::CopyFile { ref src, ref dst } => {
+                PlanEntry::CopyFile {
+                    ref src,
+                    ref dst,
+                    ref label,
+                    ..
+                } => {
+                    if cancel.is_cancelled() {
+                        return Err(BcmrError::Cancelled);
+                    }
                     check_overwrite(dst, cli).await?;
 
                     while in_flight.len() >= jobs {
                         match in_flight.join_next().await {
-                            Some(res) => res??,
+                            Some(res) => {
+                                let (slot, result) = res?;
+                                free_slots.push(slot);
+                                result?;
+                            }
                             None => break,
                         }
                     }
 
                     let src = src.clone();
                     let dst = dst.clone();
-                    let opts = CopyFileOptions::from_cli(cli, test_mode.clone());
-                    let cb = callback.clone();
-                    in_flight.spawn(async move {
-                        copy_file(&src, &dst, opts, &cb).await?;
-                        if verbose {
-                            eprintln!("'{}' -> '{}'", src.display(), dst.display());
+                    let opts = CopyFileOptions::from_cli(cli, test_mode.clone(), cancel)
+                        .with_label(label)
+                        .with_verify_pool(&verify_pool);
+                    let slot = free_slots.pop().unwrap_or(0);
+                    match &workers {
+                        Some(w) => {
+                            let (w_file, w_progress) = (Arc::clone(w), Arc::clone(w));
+                            let cb = ProgressCallback {
+                                callback: move |n| (w_progress.on_progress)(slot, n),
+                                on_new_file: Arc::new(move |name: &str, size| {
+                                    (w_file.on_file)(slot, name, size)
+                                }),
+                                on_files_done: Arc::clone(&callback.on_files_done),
+                                on_skip: callback.on_skip.clone(),
+                                on_verify_queued: Arc::clone(&callback.on_verify_queued),
+                                on_verified: Arc::clone(&callback.on_verified),
+                            };
+                            let w = Arc::clone(w);
+                            in_flight.spawn(async move {
+                                let result = copy_file(&src, &dst, opts, &cb).await;
+                                (w.on_done)(slot);
+                                (slot, result)
+                            });
+                        }
+                        None => {
+                            let cb = callback.clone();
+                            in_flight.spawn(async move {
+                                (slot, copy_file(&src, &dst, opts, &cb).await)
+                            });
                         }
-                        Ok::<(), BcmrError>(())
-                    });
+                    }
                 }
             },
             ScanMessage::Done => {
@@ -117,12 +191,15 @@ where
     }
 
     while let Some(res) = in_flight.join_next().await {
-        res??;
+        res?.1?;
+    }
+    if let Some(pool) = &verify_pool {
+        pool.finish().await?;
     }
 
     scanner.await??;
 
-    if cli.is_preserve() {
+    if cli.common.is_preserve() {
         for (src, dst) in dir_entries.iter().rev() {
             preserve_attributes(src, dst).await?;
         }
@@ -0,0 +1,159 @@
+use crate::cli::CopyArgs;
+use crate::core::checksum::Algorithm;
+use crate::core::error::BcmrError;
+use crate::core::oplog;
+
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::{verify_copy, ProgressCallback};
+
+type CountFn = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// `--verify` taken off the copy path: each copied file is queued here and
+/// hashed by up to `--verify-jobs` workers while the next files are copied.
+/// A mismatch does not stop the run; `finish` waits for the queue and
+/// reports every file that failed.
+pub struct VerifyPool {
+    algo: Algorithm,
+    direct: bool,
+    verbose: bool,
+    permits: Arc<Semaphore>,
+    tasks: Mutex<JoinSet<()>>,
+    failed: Arc<Mutex<Vec<(PathBuf, BcmrError)>>>,
+    on_queued: CountFn,
+    on_verified: CountFn,
+}
+
+impl VerifyPool {
+    fn new(jobs: usize, algo: Algorithm, direct: bool, verbose: bool) -> Self {
+        Self {
+            algo,
+            direct,
+            verbose,
+            permits: Arc::new(Semaphore::new(jobs.max(1))),
+            tasks: Mutex::new(JoinSet::new()),
+            failed: Arc::default(),
+            on_queued: Arc::new(|_| {}),
+            on_verified: Arc::new(|_| {}),
+        }
+    }
+
+    pub(super) fn from_cli<F>(cli: &CopyArgs, callback: &ProgressCallback<F>) -> Option<Arc<Self>> {
+        cli.common.is_verify().then(|| {
+            let mut pool = Self::new(
+                cli.common.verify_jobs(),
+                cli.common.checksum_algo(),
+                cli.common.is_verify_direct(),
+                cli.common.is_verbose(),
+            );
+            pool.on_queued = Arc::clone(&callback.on_verify_queued);
+            pool.on_verified = Arc::clone(&callback.on_verified);
+            Arc::new(pool)
+        })
+    }
+
+    pub(crate) fn submit(&self, src: &Path, dst: &Path, inline_src_hash: Option<String>) {
+        (self.on_queued)(1);
+        let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+        let (algo, direct, verbose) = (self.algo, self.direct, self.verbose);
+        let permits = Arc::clone(&self.permits);
+        let failed = Arc::clone(&self.failed);
+        let on_verified = Arc::clone(&self.on_verified);
+        self.tasks.lock().spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            if let Err(e) = verify_copy(&src, &dst, algo, direct, inline_src_hash, verbose).await {
+                failed.lock().push((dst, oplog::failed(&src, e)));
+            }
+            on_verified(1);
+        });
+    }
+
+    /// Waits for everything queued. A single failure comes back as it is;
+    /// several are named together, each having been logged on its own.
+    pub(super) async fn finish(&self) -> Result<(), BcmrError> {
+        let mut tasks = std::mem::take(&mut *self.tasks.lock());
+        while let Some(res) = tasks.join_next().await {
+            res?;
+        }
+        let mut failed = std::mem::take(&mut *self.failed.lock());
+        match failed.len() {
+            0 => Ok(()),
+            1 => Err(failed.remove(0).1),
+            _ => {
+                let mut paths: Vec<PathBuf> = failed.into_iter().map(|(dst, _)| dst).collect();
+                paths.sort();
+                Err(BcmrError::VerificationFailures(paths))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn one_bad_file_does_not_stop_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let verified = Arc::new(AtomicUsize::new(0));
+        let mut pool = VerifyPool::new(3, Algorithm::Blake3, false, false);
+        pool.on_verified = {
+            let verified = Arc::clone(&verified);
+            Arc::new(move |n| {
+                verified.fetch_add(n, Ordering::Relaxed);
+            })
+        };
+
+        let pairs: Vec<(PathBuf, PathBuf)> = (0..20)
+            .map(|i| {
+                let (src, dst) = (
+                    dir.path().join(format!("{i}.src")),
+                    dir.path().join(format!("{i}.dst")),
+                );
+                let data = vec![i as u8; 10_000 + i * 313];
+                fs::write(&src, &data).unwrap();
+                fs::write(&dst, &data).unwrap();
+                (src, dst)
+            })
+            .collect();
+        let victim = &pairs[7].1;
+        let mut data = fs::read(victim).unwrap();
+        data[4_321] ^= 0x10;
+        fs::write(victim, data).unwrap();
+
+        for (src, dst) in &pairs {
+            pool.submit(src, dst, None);
+        }
+        match pool.finish().await {
+            Err(BcmrError::VerificationError(path, Some(mismatch))) => {
+                assert_eq!(&path, victim);
+                assert_eq!(mismatch.offset, 4_321);
+            }
+            other => panic!("expected one verification failure, got {other:?}"),
+        }
+        assert_eq!(verified.load(Ordering::Relaxed), pairs.len());
+        assert!(!victim.exists());
+        assert_eq!(pairs.iter().filter(|(_, dst)| dst.exists()).count(), 19);
+
+        // More than one failure is reported as a list.
+        for (src, dst) in &pairs[..2] {
+            fs::write(dst, b"x").unwrap();
+            pool.submit(src, dst, None);
+        }
+        match pool.finish().await {
+            Err(e @ BcmrError::VerificationFailures(_)) => {
+                assert!(
+                    e.to_string().starts_with("2 files failed verification: '"),
+                    "{e}"
+                );
+            }
+            other => panic!("expected two verification failures, got {other:?}"),
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use crate::cli::SparseMode;
+use crate::core::checksum::Algorithm;
 use crate::core::error::BcmrError;
 use crate::core::io as durable_io;
 use crate::core::session::{Session, CHECKPOINT_INTERVAL_BLOCKS, COPY_BLOCK_SIZE};
 use std::path::Path;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 
 use super::copy::TempFileGuard;
 
@@ -16,7 +18,11 @@ pub struct FinalizeParams<'a> {
     pub sync: bool,
     pub preserve: bool,
     pub verify: bool,
-    pub inline_src_hash: Option<blake3::Hash>,
+    pub checksum_algo: Algorithm,
+    pub verify_direct: bool,
+    pub inline_src_hash: Option<String>,
+    pub verify_pool: Option<&'a super::copy::VerifyPool>,
+    pub verbose: bool,
 }
 
 pub async fn finalize(dst_file: tokio::fs::File, p: FinalizeParams<'_>) -> Result<(), BcmrError> {
@@ -42,10 +48,23 @@ pub async fn finalize(dst_file: tokio::fs::File, p: FinalizeParams<'_>) -> Resul
     }
 
     if p.verify {
-        super::copy::verify_copy(p.src, p.dst, p.inline_src_hash).await?;
+        match p.verify_pool {
+            Some(pool) => pool.submit(p.src, p.dst, p.inline_src_hash),
+            None => {
+                super::copy::verify_copy(
+                    p.src,
+                    p.dst,
+                    p.checksum_algo,
+                    p.verify_direct,
+                    p.inline_src_hash,
+                    p.verbose,
+                )
+                .await?
+            }
+        }
     }
 
-    Session::remove(p.src, p.dst);
+    let _ = fs::remove_file(Session::session_path(p.src, p.dst)).await;
     Ok(())
 }
 
@@ -58,7 +77,11 @@ pub async fn try_reflink(
     sparse_mode: &SparseMode,
     callback: &impl Fn(u64),
 ) -> Result<bool, BcmrError> {
-    if !try_reflink || matches!(sparse_mode, SparseMode::Always) {
+    // A clone can't be paced, so a --bwlimit copy takes the copy loop.
+    if !try_reflink
+        || matches!(sparse_mode, SparseMode::Always)
+        || crate::core::bwlimit::is_limited()
+    {
         return Ok(false);
     }
 
@@ -131,34 +154,167 @@ pub fn create_session(
     Some(s)
 }
 
+/// Writes a stream leaving holes where `--sparse` finds runs of zeros.
+/// Holes are only seeked over, so `finish` sets the length a trailing one
+/// leaves short.
+pub(crate) struct SparseWriter {
+    file: std::fs::File,
+    mode: SparseMode,
+    pending_hole: u64,
+}
+
+impl SparseWriter {
+    const DETECT_SIZE: usize = 4096;
+
+    pub(crate) fn new(file: std::fs::File, mode: SparseMode) -> Self {
+        Self {
+            file,
+            mode,
+            pending_hole: 0,
+        }
+    }
+
+    pub(crate) fn file(&self) -> &std::fs::File {
+        &self.file
+    }
+
+    pub(crate) fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let min_block = match self.mode {
+            SparseMode::Never => return self.file.write_all(buf),
+            SparseMode::Always => 1,
+            SparseMode::Auto => Self::DETECT_SIZE,
+        };
+        for chunk in buf.chunks(Self::DETECT_SIZE) {
+            if chunk.len() >= min_block && chunk.iter().all(|&b| b == 0) {
+                self.pending_hole += chunk.len() as u64;
+            } else {
+                if self.pending_hole > 0 {
+                    self.file
+                        .seek(SeekFrom::Current(self.pending_hole as i64))?;
+                    self.pending_hole = 0;
+                }
+                self.file.write_all(chunk)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> std::io::Result<std::fs::File> {
+        use std::io::Seek;
+
+        if self.pending_hole > 0 {
+            let current_pos = self.file.stream_position()?;
+            self.file.set_len(current_pos + self.pending_hole)?;
+        }
+        Ok(self.file)
+    }
+}
+
+pub struct StreamOptions {
+    pub sparse_mode: SparseMode,
+    pub start_offset: u64,
+    /// Hash the source with this algorithm on the way through, so
+    /// `--verify` only has to re-read the destination.
+    pub verify_algo: Option<Algorithm>,
+    /// Checked before every chunk, so a cancelled copy stops at a chunk
+    /// boundary rather than mid-write.
+    pub cancel: CancellationToken,
+    /// Bytes per read and write, up to a resume block.
+    pub buffer_size: usize,
+    /// Keep the copy out of the page cache, as `[copy]
+    /// direct_io_threshold` asks for large files.
+    pub uncached: bool,
+}
+
+/// Keeps a copy out of the page cache. macOS reads and writes past it with
+/// F_NOCACHE; Linux has no such switch for buffered I/O, so each chunk is
+/// written back and dropped from the cache once it is copied, leaving at
+/// most a chunk of either file cached.
+struct CacheBypass {
+    /// Source and destination offsets up to which the cache is clear.
+    dropped: (u64, u64),
+}
+
+impl CacheBypass {
+    fn new(src: &std::fs::File, dst: &std::fs::File) -> Self {
+        #[cfg(target_os = "macos")]
+        {
+            use std::os::unix::io::AsRawFd;
+            for fd in [src.as_raw_fd(), dst.as_raw_fd()] {
+                // SAFETY: F_NOCACHE takes an int and touches nothing else.
+                unsafe { libc::fcntl(fd, libc::F_NOCACHE, 1) };
+            }
+        }
+        #[cfg(not(target_os = "macos"))]
+        let _ = (src, dst);
+        Self { dropped: (0, 0) }
+    }
+
+    /// Writes back and drops what was copied since the last call.
+    #[cfg(target_os = "linux")]
+    fn drop_behind(&mut self, src: &std::fs::File, dst: &std::fs::File) {
+        use std::io::Seek;
+        use std::os::unix::io::AsRawFd;
+
+        let (src_from, dst_from) = self.dropped;
+        let src_end = (&*src).stream_position().unwrap_or(src_from);
+        let dst_end = (&*dst).stream_position().unwrap_or(dst_from);
+        if dst_end > dst_from {
+            let (from, len) = (
+                dst_from as libc::off64_t,
+                (dst_end - dst_from) as libc::off64_t,
+            );
+            // SAFETY: writeback and advice on a descriptor we own, over
+            // bytes already written.
+            unsafe {
+                libc::sync_file_range(
+                    dst.as_raw_fd(),
+                    from,
+                    len,
+                    libc::SYNC_FILE_RANGE_WAIT_BEFORE
+                        | libc::SYNC_FILE_RANGE_WRITE
+                        | libc::SYNC_FILE_RANGE_WAIT_AFTER,
+                );
+                libc::posix_fadvise(dst.as_raw_fd(), from, len, libc::POSIX_FADV_DONTNEED);
+            }
+        }
+        if src_end > src_from {
+            // SAFETY: as above, over bytes already read.
+            unsafe {
+                libc::posix_fadvise(
+                    src.as_raw_fd(),
+                    src_from as libc::off_t,
+                    (src_end - src_from) as libc::off_t,
+                    libc::POSIX_FADV_DONTNEED,
+                );
+            }
+        }
+        self.dropped = (src_end.max(src_from), dst_end.max(dst_from));
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn drop_behind(&mut self, _src: &std::fs::File, _dst: &std::fs::File) {}
+}
+
 pub async fn streaming_copy(
     src_file: &mut tokio::fs::File,
     dst_file: &mut tokio::fs::File,
     session: &mut Option<Session>,
-    sparse_mode: &SparseMode,
-    start_offset: u64,
-    need_src_hash: bool,
+    opts: StreamOptions,
     callback: &(impl Fn(u64) + Send + Sync + Clone + 'static),
-) -> Result<Option<blake3::Hash>, BcmrError> {
+) -> Result<Option<String>, BcmrError> {
     // dup fds into std handles so the whole copy loop runs under one
     // spawn_blocking — tokio::fs would dispatch per-read/-write, costing
     // ~1024 pool bounces per 2 GB (≈6× slowdown observed on Linux NVMe).
     let src_std = src_file.try_clone().await?.into_std().await;
     let dst_std = dst_file.try_clone().await?.into_std().await;
     let session_in = session.take();
-    let sparse_mode = sparse_mode.clone();
     let cb = callback.clone();
 
     let join = tokio::task::spawn_blocking(move || {
-        streaming_copy_sync(
-            src_std,
-            dst_std,
-            session_in,
-            &sparse_mode,
-            start_offset,
-            need_src_hash,
-            cb,
-        )
+        streaming_copy_sync(src_std, dst_std, session_in, opts, cb)
     });
 
     let (returned_session, hash) = join.await??;
@@ -168,65 +324,90 @@ pub async fn streaming_copy(
 
 fn streaming_copy_sync(
     mut src_file: std::fs::File,
-    mut dst_file: std::fs::File,
+    dst_file: std::fs::File,
     mut session: Option<Session>,
-    sparse_mode: &SparseMode,
-    start_offset: u64,
-    need_src_hash: bool,
+    opts: StreamOptions,
     callback: impl Fn(u64) + Send + Sync,
-) -> Result<(Option<Session>, Option<blake3::Hash>), BcmrError> {
-    use std::io::{Read, Seek, SeekFrom as StdSeekFrom, Write};
-
-    const SPARSE_DETECT_SIZE: usize = 4096;
-
-    let mut buffer = vec![0u8; COPY_BLOCK_SIZE as usize];
-    let mut pending_hole = 0u64;
-    let mut src_hasher = need_src_hash.then(blake3::Hasher::new);
+) -> Result<(Option<Session>, Option<String>), BcmrError> {
+    use std::io::{Read, Seek, SeekFrom as StdSeekFrom};
+
+    let StreamOptions {
+        sparse_mode,
+        start_offset,
+        verify_algo,
+        cancel,
+        buffer_size,
+        uncached,
+    } = opts;
+
+    let mut buffer = vec![0u8; buffer_size.clamp(1, COPY_BLOCK_SIZE as usize)];
+    let mut writer = SparseWriter::new(dst_file, sparse_mode);
+    let mut cache_bypass = uncached.then(|| CacheBypass::new(&src_file, writer.file()));
+    // The session records the source's BLAKE3 whatever `--verify` uses;
+    // any other algorithm gets a hasher of its own.
+    let mut src_hasher =
+        (session.is_some() || verify_algo == Some(Algorithm::Blake3)).then(blake3::Hasher::new);
+    let mut verify_hasher = verify_algo
+        .filter(|&algo| algo != Algorithm::Blake3)
+        .map(Algorithm::hasher);
+
+    // A resumed copy starts mid-file: hash the part copied last time first,
+    // so the digest still covers the whole source.
+    if start_offset > 0 && verify_algo.is_some() {
+        src_file.seek(StdSeekFrom::Start(0))?;
+        let mut prefix = (&mut src_file).take(start_offset);
+        loop {
+            if cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
+            }
+            let n = prefix.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            if let Some(h) = src_hasher.as_mut() {
+                h.update(&buffer[..n]);
+            }
+            if let Some(h) = verify_hasher.as_mut() {
+                h.update(&buffer[..n]);
+            }
+        }
+        src_file.seek(StdSeekFrom::Start(start_offset))?;
+    }
     let mut block_hasher = session.as_ref().map(|_| blake3::Hasher::new());
     let mut bytes_in_block = 0u64;
     let mut blocks_since_checkpoint = 0u32;
 
     loop {
-        let n = src_file.read(&mut buffer)?;
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        // Throttled reads stop at block boundaries, which the session's
+        // per-block hashes depend on.
+        let want = crate::core::bwlimit::step(
+            (COPY_BLOCK_SIZE - bytes_in_block).min(buffer.len() as u64) as usize,
+        );
+        let n = src_file.read(&mut buffer[..want])?;
         if n == 0 {
             break;
         }
 
+        // Hashed before hole detection, so skipped zeros still count.
         if let Some(h) = src_hasher.as_mut() {
             h.update(&buffer[..n]);
         }
+        if let Some(h) = verify_hasher.as_mut() {
+            h.update(&buffer[..n]);
+        }
         if let Some(h) = block_hasher.as_mut() {
             h.update(&buffer[..n]);
         }
         bytes_in_block += n as u64;
 
-        match sparse_mode {
-            SparseMode::Never => {
-                dst_file.write_all(&buffer[..n])?;
-            }
-            SparseMode::Always | SparseMode::Auto => {
-                let min_block = if matches!(sparse_mode, SparseMode::Always) {
-                    1
-                } else {
-                    SPARSE_DETECT_SIZE
-                };
-                let mut offset = 0;
-                while offset < n {
-                    let end = (offset + SPARSE_DETECT_SIZE).min(n);
-                    let chunk = &buffer[offset..end];
-                    if chunk.len() >= min_block && chunk.iter().all(|&b| b == 0) {
-                        pending_hole += chunk.len() as u64;
-                    } else {
-                        if pending_hole > 0 {
-                            dst_file.seek(StdSeekFrom::Current(pending_hole as i64))?;
-                            pending_hole = 0;
-                        }
-                        dst_file.write_all(chunk)?;
-                    }
-                    offset = end;
-                }
-            }
+        writer.write(&buffer[..n])?;
+        if let Some(bypass) = cache_bypass.as_mut() {
+            bypass.drop_behind(&src_file, writer.file());
         }
+        crate::core::bwlimit::wait(n as u64);
 
         callback(n as u64);
 
@@ -241,7 +422,7 @@ fn streaming_copy_sync(
 
             if blocks_since_checkpoint >= CHECKPOINT_INTERVAL_BLOCKS {
                 if let Some(ref s) = session {
-                    durable_io::durable_sync(&dst_file)?;
+                    durable_io::durable_sync(writer.file())?;
                     let _ = s.save();
                 }
                 blocks_since_checkpoint = 0;
@@ -250,7 +431,7 @@ fn streaming_copy_sync(
                 {
                     use std::os::unix::io::AsRawFd;
                     let src_end = src_file.stream_position().unwrap_or(0) as libc::off_t;
-                    let dst_end = dst_file.stream_position().unwrap_or(0) as libc::off_t;
+                    let dst_end = writer.file().stream_position().unwrap_or(0) as libc::off_t;
                     unsafe {
                         libc::posix_fadvise(
                             src_file.as_raw_fd(),
@@ -259,7 +440,7 @@ fn streaming_copy_sync(
                             libc::POSIX_FADV_DONTNEED,
                         );
                         libc::posix_fadvise(
-                            dst_file.as_raw_fd(),
+                            writer.file().as_raw_fd(),
                             0,
                             dst_end,
                             libc::POSIX_FADV_DONTNEED,
@@ -277,10 +458,7 @@ fn streaming_copy_sync(
         }
     }
 
-    if pending_hole > 0 {
-        let current_pos = dst_file.stream_position()?;
-        dst_file.set_len(current_pos + pending_hole)?;
-    }
+    writer.finish()?;
 
     let final_hash = src_hasher.map(|h| h.finalize());
     if start_offset == 0 {
@@ -288,8 +466,188 @@ fn streaming_copy_sync(
             s.set_src_hash(*h.as_bytes());
             let _ = s.save();
         }
-        Ok((session, final_hash))
-    } else {
-        Ok((session, None))
+    }
+    let verify_hash = match verify_hasher {
+        Some(h) => Some(h.finish()),
+        None if verify_algo.is_some() => final_hash.map(|h| h.to_hex().to_string()),
+        None => None,
+    };
+    Ok((session, verify_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::checksum;
+    use tokio::io::AsyncSeekExt;
+
+    // Data, a run of zeros long enough to become a hole, then data again.
+    fn sparse_source() -> Vec<u8> {
+        let mut data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        data.resize(data.len() + 2 * COPY_BLOCK_SIZE as usize, 0);
+        data.extend((0..70_000u32).map(|i| (i % 13) as u8 + 1));
+        data
+    }
+
+    async fn copy(
+        src: &Path,
+        dst: &Path,
+        sparse_mode: SparseMode,
+        start_offset: u64,
+        verify_algo: Option<Algorithm>,
+    ) -> Option<String> {
+        let mut src_file = tokio::fs::File::open(src).await.unwrap();
+        let mut dst_file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(start_offset == 0)
+            .open(dst)
+            .await
+            .unwrap();
+        src_file
+            .seek(std::io::SeekFrom::Start(start_offset))
+            .await
+            .unwrap();
+        dst_file
+            .seek(std::io::SeekFrom::Start(start_offset))
+            .await
+            .unwrap();
+        let opts = StreamOptions {
+            sparse_mode,
+            start_offset,
+            verify_algo,
+            cancel: CancellationToken::new(),
+            buffer_size: COPY_BLOCK_SIZE as usize,
+            uncached: false,
+        };
+        streaming_copy(&mut src_file, &mut dst_file, &mut None, opts, &|_| {})
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn small_uncached_chunks_copy_the_same_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        let data = sparse_source();
+        std::fs::write(&src, &data).unwrap();
+
+        let mut src_file = tokio::fs::File::open(&src).await.unwrap();
+        let mut dst_file = tokio::fs::File::create(&dst).await.unwrap();
+        let opts = StreamOptions {
+            sparse_mode: SparseMode::Auto,
+            start_offset: 0,
+            verify_algo: Some(Algorithm::Blake3),
+            cancel: CancellationToken::new(),
+            buffer_size: 65_537,
+            uncached: true,
+        };
+        let hash = streaming_copy(&mut src_file, &mut dst_file, &mut None, opts, &|_| {})
+            .await
+            .unwrap();
+        drop(dst_file);
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+        assert_eq!(
+            hash,
+            Some(checksum::calculate_hash(&src, Algorithm::Blake3).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn inline_hash_matches_reading_the_source_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::write(&src, sparse_source()).unwrap();
+
+        for algo in [Algorithm::Blake3, Algorithm::Sha256, Algorithm::Xxh3] {
+            let expected = checksum::calculate_hash(&src, algo).unwrap();
+            for mode in [SparseMode::Never, SparseMode::Auto, SparseMode::Always] {
+                let dst = dir.path().join("dst");
+                let inline = copy(&src, &dst, mode, 0, Some(algo)).await;
+                assert_eq!(inline.as_deref(), Some(expected.as_str()), "{algo}");
+                assert_eq!(std::fs::read(&dst).unwrap(), sparse_source());
+            }
+        }
+
+        let dst = dir.path().join("unverified");
+        assert_eq!(copy(&src, &dst, SparseMode::Auto, 0, None).await, None);
+    }
+
+    #[tokio::test]
+    async fn resumed_copy_hashes_the_prefix_it_skips() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        let data = sparse_source();
+        std::fs::write(&src, &data).unwrap();
+        let offset = COPY_BLOCK_SIZE + 12_345;
+        std::fs::write(&dst, &data[..offset as usize]).unwrap();
+
+        let inline = copy(&src, &dst, SparseMode::Never, offset, Some(Algorithm::Xxh3)).await;
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+        assert_eq!(
+            inline,
+            Some(checksum::calculate_hash(&src, Algorithm::Xxh3).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn corruption_fails_verification_with_or_without_the_inline_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::write(&src, sparse_source()).unwrap();
+
+        for corrupt in [false, true] {
+            for (use_inline, direct) in [(false, false), (true, false), (true, true)] {
+                let algo = Algorithm::Sha256;
+                let inline = copy(&src, &dst, SparseMode::Auto, 0, Some(algo)).await;
+                if corrupt {
+                    let mut data = std::fs::read(&dst).unwrap();
+                    data[250_000] ^= 0x40;
+                    std::fs::write(&dst, data).unwrap();
+                }
+                let inline = if use_inline { inline } else { None };
+                let result =
+                    super::super::copy::verify_copy(&src, &dst, algo, direct, inline, false).await;
+                assert_eq!(
+                    result.is_err(),
+                    corrupt,
+                    "inline: {use_inline}, direct: {direct}"
+                );
+                assert_eq!(dst.exists(), !corrupt);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn verification_error_names_the_first_bad_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::write(&src, sparse_source()).unwrap();
+
+        for verbose in [false, true] {
+            let inline = copy(&src, &dst, SparseMode::Never, 0, Some(Algorithm::Blake3)).await;
+            let mut data = std::fs::read(&dst).unwrap();
+            data[5_000_000] ^= 0x01;
+            data[8_500_000] ^= 0x01;
+            std::fs::write(&dst, data).unwrap();
+
+            let err = super::super::copy::verify_copy(
+                &src,
+                &dst,
+                Algorithm::Blake3,
+                false,
+                inline,
+                verbose,
+            )
+            .await
+            .unwrap_err();
+            let expected = if verbose {
+                "first difference at offset 5,000,000 (block 1), 2 blocks differ"
+            } else {
+                "first difference at offset 5,000,000 (block 1)"
+            };
+            assert!(err.to_string().ends_with(expected), "{err}");
+        }
     }
 }
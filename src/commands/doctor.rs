@@ -0,0 +1,376 @@
+use crate::config::{self, Config};
+
+use crossterm::style::{Color, ResetColor, SetForegroundColor};
+use std::io::{IsTerminal, Seek, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+pub struct Check {
+    pub level: Level,
+    pub name: &'static str,
+    pub detail: String,
+    /// What to do about a WARN or FAIL.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(level: Level, name: &'static str, detail: impl Into<String>, hint: &str) -> Self {
+        Self {
+            level,
+            name,
+            detail: detail.into(),
+            hint: (!hint.is_empty()).then(|| hint.to_string()),
+        }
+    }
+
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(Level::Ok, name, detail, "")
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: &str) -> Self {
+        Self::new(Level::Warn, name, detail, hint)
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: &str) -> Self {
+        Self::new(Level::Fail, name, detail, hint)
+    }
+}
+
+/// Every check, probing `dir` for what copies into it can use.
+pub fn run(dir: &Path) -> Vec<Check> {
+    let mut checks = vec![version()];
+    checks.extend(check_config(&config::config_paths()));
+    checks.extend(check_terminal());
+    checks.extend(probe_dir(dir));
+    checks.extend(check_shell(&rc_files()));
+    checks
+}
+
+fn version() -> Check {
+    Check::ok(
+        "version",
+        format!(
+            "bcmr {} on {}/{}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+    )
+}
+
+fn check_config(paths: &[PathBuf]) -> Vec<Check> {
+    if paths.is_empty() {
+        return vec![Check::ok("config", "no config file, using defaults")];
+    }
+    let mut checks: Vec<Check> = paths
+        .iter()
+        .map(
+            |path| match Config::from_files(std::slice::from_ref(path)) {
+                Ok(_) => Check::ok("config", format!("{} parses", path.display())),
+                Err(e) => Check::fail(
+                    "config",
+                    format!("{}: {}", path.display(), e),
+                    "fix the file; until then every command runs with the default settings",
+                ),
+            },
+        )
+        .collect();
+    if checks.iter().all(|c| c.level == Level::Ok) {
+        match Config::load(paths, None).map(|(c, unknown)| (c.progress.validate(), unknown)) {
+            Ok((Ok(()), unknown)) => checks.extend(unknown.into_iter().map(|key| {
+                Check::warn(
+                    "config",
+                    format!("unknown key '{key}' is ignored"),
+                    "check the spelling against docs/configuration.md",
+                )
+            })),
+            Ok((Err(e), _)) => checks.push(Check::fail(
+                "config",
+                e,
+                "every command refuses to start until this is fixed",
+            )),
+            Err(e) => checks.push(Check::fail(
+                "config",
+                format!("files do not combine: {e}"),
+                "fix the file; until then every command runs with the default settings",
+            )),
+        }
+    }
+    checks
+}
+
+fn check_terminal() -> Vec<Check> {
+    let mut checks = Vec::new();
+    if std::io::stderr().is_terminal() {
+        let size = terminal_size::terminal_size()
+            .map(|(w, h)| format!("{}x{}", w.0, h.0))
+            .unwrap_or_else(|| "unknown size".into());
+        checks.push(Check::ok(
+            "terminal",
+            format!("stderr is a terminal, {size}"),
+        ));
+    } else {
+        checks.push(Check::warn(
+            "terminal",
+            "stderr is not a terminal",
+            "progress is drawn only on a terminal; --progress-fd can point it at one",
+        ));
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    if std::env::var_os("NO_COLOR").is_some() {
+        checks.push(Check::ok("color", "off (NO_COLOR is set)"));
+    } else if term.is_empty() || term == "dumb" {
+        checks.push(Check::warn(
+            "color",
+            format!("TERM is '{term}'"),
+            "set TERM to your terminal's type (e.g. xterm-256color) for colors and the TUI",
+        ));
+    } else {
+        checks.push(Check::ok("color", format!("TERM={term}")));
+    }
+    checks
+}
+
+/// Reflink and sparse support where copies land, tried on scratch files in
+/// a directory of their own under `dir`.
+fn probe_dir(dir: &Path) -> Vec<Check> {
+    let scratch = dir.join(format!(".bcmr-doctor-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir(&scratch) {
+        return vec![Check::warn(
+            "filesystem",
+            format!("cannot probe {}: {e}", dir.display()),
+            "run bcmr doctor DIR with a directory you can write to",
+        )];
+    }
+    let checks = vec![probe_reflink(&scratch), probe_sparse(&scratch)];
+    let _ = std::fs::remove_dir_all(&scratch);
+    checks
+}
+
+fn probe_reflink(scratch: &Path) -> Check {
+    let (src, dst) = (scratch.join("src"), scratch.join("dst"));
+    if let Err(e) = std::fs::write(&src, vec![0x5a; 64 * 1024]) {
+        return Check::warn("reflink", format!("cannot write a probe file: {e}"), "");
+    }
+    match reflink_copy::reflink(&src, &dst) {
+        Ok(()) => Check::ok("reflink", "supported"),
+        Err(e) => Check::warn(
+            "reflink",
+            format!("not supported here ({e})"),
+            "copies fall back to a regular copy; --reflink=force will fail",
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn probe_sparse(scratch: &Path) -> Check {
+    use std::os::unix::fs::MetadataExt;
+
+    const LEN: u64 = 8 * 1024 * 1024;
+    let path = scratch.join("sparse");
+    let written = std::fs::File::create(&path).and_then(|mut f| {
+        f.set_len(LEN - 4096)?;
+        f.seek(std::io::SeekFrom::End(0))?;
+        f.write_all(&[1; 4096])?;
+        f.sync_all()?;
+        f.metadata()
+    });
+    match written {
+        Ok(md) if md.blocks() * 512 < LEN / 2 => Check::ok("sparse", "holes are kept"),
+        Ok(_) => Check::warn(
+            "sparse",
+            "holes are filled in",
+            "sparse files are copied at their full size here",
+        ),
+        Err(e) => Check::warn("sparse", format!("cannot write a probe file: {e}"), ""),
+    }
+}
+
+#[cfg(not(unix))]
+fn probe_sparse(_scratch: &Path) -> Check {
+    Check::ok("sparse", "not checked on this platform")
+}
+
+fn rc_files() -> Vec<PathBuf> {
+    let Some(home) = directories::UserDirs::new().map(|u| u.home_dir().to_path_buf()) else {
+        return Vec::new();
+    };
+    [
+        ".bashrc",
+        ".bash_profile",
+        ".zshrc",
+        ".config/fish/config.fish",
+    ]
+    .iter()
+    .map(|name| home.join(name))
+    .filter(|path| path.is_file())
+    .collect()
+}
+
+/// Whether the `bcmr` a shell finds is this one, and whether rc files name
+/// bcmr binaries that are gone (a pasted `bcmr init` script keeps the path
+/// it was generated with).
+fn check_shell(rc_files: &[PathBuf]) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let current = std::env::current_exe().and_then(|p| p.canonicalize()).ok();
+    match on_path("bcmr") {
+        None => checks.push(Check::warn(
+            "shell",
+            "bcmr is not on PATH",
+            "eval \"$(bcmr init SHELL)\" needs it there; add its directory to PATH",
+        )),
+        Some(found) if found.canonicalize().ok() == current => checks.push(Check::ok(
+            "shell",
+            format!("PATH finds {}", found.display()),
+        )),
+        Some(found) => checks.push(Check::warn(
+            "shell",
+            format!("PATH finds {}, not this binary", found.display()),
+            "shell functions from bcmr init run that one; remove or update it",
+        )),
+    }
+
+    let quoted = regex::Regex::new(r#""(/[^"]*/bcmr[^"/]*)""#).expect("valid regex");
+    for rc in rc_files {
+        let Ok(text) = std::fs::read_to_string(rc) else {
+            continue;
+        };
+        let mut missing: Vec<&str> = quoted
+            .captures_iter(&text)
+            .map(|c| c.get(1).expect("group 1").as_str())
+            .filter(|p| !Path::new(p).exists())
+            .collect();
+        missing.dedup();
+        for path in missing {
+            checks.push(Check::fail(
+                "shell",
+                format!("{} runs {path}, which does not exist", rc.display()),
+                "replace the pasted functions with eval \"$(bcmr init SHELL)\"",
+            ));
+        }
+    }
+    checks
+}
+
+fn on_path(name: &str) -> Option<PathBuf> {
+    let exe = format!("{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&exe))
+        .find(|p| p.is_file())
+}
+
+pub fn print(checks: &[Check]) {
+    for check in checks {
+        let (tag, color) = match check.level {
+            Level::Ok => ("OK", Color::Green),
+            Level::Warn => ("WARN", Color::Yellow),
+            Level::Fail => ("FAIL", Color::Red),
+        };
+        println!(
+            "{}{:<5}{} {:<11} {}",
+            SetForegroundColor(color),
+            tag,
+            ResetColor,
+            check.name,
+            check.detail
+        );
+        if let Some(hint) = &check.hint {
+            println!("{:18}{}", "", hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn config_errors_are_named_not_swallowed() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.toml");
+        fs::write(&good, "[progress]\nstyle = \"plain\"\n").unwrap();
+        let bad = dir.path().join("bad.toml");
+        fs::write(&bad, "[progress]\nrefresh_ms = \"soon\"\n").unwrap();
+        let out_of_range = dir.path().join("range.toml");
+        fs::write(&out_of_range, "[progress]\nrefresh_ms = 999999\n").unwrap();
+
+        let checks = check_config(std::slice::from_ref(&good));
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].level, Level::Ok);
+
+        let checks = check_config(&[good.clone(), bad.clone()]);
+        assert_eq!(checks[1].level, Level::Fail);
+        assert!(
+            checks[1].detail.contains("bad.toml"),
+            "{}",
+            checks[1].detail
+        );
+        assert!(
+            checks[1].detail.contains("refresh_ms"),
+            "{}",
+            checks[1].detail
+        );
+
+        let typo = dir.path().join("typo.toml");
+        fs::write(&typo, "[copy]\nverfy = true\n").unwrap();
+        let checks = check_config(&[typo]);
+        assert_eq!(checks.last().unwrap().level, Level::Warn);
+        assert!(checks.last().unwrap().detail.contains("copy.verfy"));
+
+        let checks = check_config(&[out_of_range]);
+        assert_eq!(checks.last().unwrap().level, Level::Fail);
+        assert!(checks
+            .last()
+            .unwrap()
+            .detail
+            .contains("progress.refresh_ms"));
+    }
+
+    #[test]
+    fn probes_clean_up_and_never_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = probe_dir(dir.path());
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(|c| c.level != Level::Fail));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let checks = probe_dir(&dir.path().join("missing"));
+        assert_eq!(checks[0].level, Level::Warn);
+    }
+
+    #[test]
+    fn rc_files_naming_a_deleted_binary_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let rc = dir.path().join(".zshrc");
+        let gone = dir.path().join("old/bin/bcmr");
+        let here = std::env::current_exe().unwrap();
+        fs::write(
+            &rc,
+            format!(
+                "function cp() {{\n    \"{}\" copy \"$@\"\n}}\nfunction mv() {{\n    \"{}\" move \"$@\"\n}}\n",
+                gone.display(),
+                here.display()
+            ),
+        )
+        .unwrap();
+
+        let fails: Vec<Check> = check_shell(&[rc])
+            .into_iter()
+            .filter(|c| c.level == Level::Fail)
+            .collect();
+        assert_eq!(fails.len(), 1);
+        assert!(
+            fails[0].detail.contains("old/bin/bcmr"),
+            "{}",
+            fails[0].detail
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::core::error::BcmrError;
+use crate::core::traversal;
+use crate::output::{DuFile, DuResult, DuUsage, Status};
+use crate::ui::scan::ScanCounters;
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DuOptions {
+    pub recursive: bool,
+    /// Directories deeper than this are only counted in their parents.
+    pub max_depth: Option<usize>,
+    pub blocks: bool,
+    /// How many of the largest files to keep.
+    pub largest: usize,
+}
+
+pub async fn measure(
+    paths: &[PathBuf],
+    excludes: &[regex::Regex],
+    opts: DuOptions,
+    counters: Arc<ScanCounters>,
+) -> Result<DuResult, BcmrError> {
+    let paths = paths.to_vec();
+    let excludes = excludes.to_vec();
+    tokio::task::spawn_blocking(move || measure_sync(&paths, &excludes, opts, &counters)).await?
+}
+
+fn measure_sync(
+    paths: &[PathBuf],
+    excludes: &[regex::Regex],
+    opts: DuOptions,
+    counters: &ScanCounters,
+) -> Result<DuResult, BcmrError> {
+    let mut tally = Tally {
+        opts,
+        counters,
+        seen: HashSet::new(),
+        largest: BinaryHeap::new(),
+    };
+    let mut usages = Vec::new();
+    for path in paths {
+        // Skipped as a copy would skip it.
+        if traversal::is_excluded(path, excludes) {
+            continue;
+        }
+        usages.push(tally.measure(path, excludes)?);
+    }
+
+    let mut largest: Vec<DuFile> = tally
+        .largest
+        .into_iter()
+        .map(|Reverse((bytes, path))| DuFile { path, bytes })
+        .collect();
+    largest.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    Ok(DuResult {
+        status: Status::Success,
+        blocks: opts.blocks,
+        total_bytes: usages.iter().map(|u| u.bytes).sum(),
+        paths: usages,
+        largest,
+    })
+}
+
+struct Tally<'a> {
+    opts: DuOptions,
+    counters: &'a ScanCounters,
+    /// Hardlinked files already counted, by device and inode.
+    seen: HashSet<(u64, u64)>,
+    largest: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl Tally<'_> {
+    fn measure(&mut self, root: &Path, excludes: &[regex::Regex]) -> Result<DuUsage, BcmrError> {
+        let md = std::fs::metadata(root).map_err(|_| BcmrError::SourceNotFound(root.into()))?;
+        if md.is_dir() && !self.opts.recursive {
+            return Err(BcmrError::InvalidInput(format!(
+                "'{}' is a directory. Use -r to measure it.",
+                root.display()
+            )));
+        }
+
+        let mut usage = DuUsage {
+            path: root.to_path_buf(),
+            bytes: 0,
+            files: 0,
+            dirs: Vec::new(),
+        };
+        let max_depth = self.opts.max_depth.unwrap_or(0);
+        let mut dirs: BTreeMap<PathBuf, u64> = BTreeMap::new();
+
+        for entry in traversal::walk(root, true, false, 0, excludes) {
+            let entry = entry?;
+            // Links count as what they point at, as the copy sees them.
+            let md = if entry.path_is_symlink() {
+                std::fs::metadata(entry.path()).ok()
+            } else {
+                Some(entry.metadata()?)
+            };
+            let Some(md) = md.filter(|md| md.is_file()) else {
+                if entry.file_type().is_dir() && (1..=max_depth).contains(&entry.depth()) {
+                    dirs.entry(entry.into_path()).or_default();
+                }
+                continue;
+            };
+
+            let bytes = self.usage(&md);
+            self.counters.record(entry.path(), bytes)?;
+            usage.bytes += bytes;
+            usage.files += 1;
+
+            let mut dir = entry.path().parent();
+            for depth in (1..entry.depth()).rev() {
+                let Some(d) = dir else { break };
+                if depth <= max_depth {
+                    *dirs.entry(d.to_path_buf()).or_default() += bytes;
+                }
+                dir = d.parent();
+            }
+
+            if self.opts.largest > 0 {
+                self.largest.push(Reverse((bytes, entry.into_path())));
+                if self.largest.len() > self.opts.largest {
+                    self.largest.pop();
+                }
+            }
+        }
+
+        usage.dirs = dirs
+            .into_iter()
+            .map(|(path, bytes)| DuFile { path, bytes })
+            .collect();
+        Ok(usage)
+    }
+
+    #[cfg(unix)]
+    fn usage(&mut self, md: &Metadata) -> u64 {
+        use std::os::unix::fs::MetadataExt;
+        if !self.opts.blocks {
+            return md.len();
+        }
+        if md.nlink() > 1 && !self.seen.insert((md.dev(), md.ino())) {
+            return 0;
+        }
+        md.blocks() * 512
+    }
+
+    #[cfg(not(unix))]
+    fn usage(&mut self, md: &Metadata) -> u64 {
+        md.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn fixture(root: &Path) {
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("top.bin"), vec![0u8; 1000]).unwrap();
+        fs::write(root.join("a/one.bin"), vec![0u8; 300]).unwrap();
+        fs::write(root.join("a/b/two.bin"), vec![0u8; 20]).unwrap();
+        fs::write(root.join("logs/x.log"), vec![0u8; 4000]).unwrap();
+    }
+
+    fn run(paths: &[PathBuf], excludes: &[&str], opts: DuOptions) -> DuResult {
+        let excludes: Vec<regex::Regex> = excludes
+            .iter()
+            .map(|e| regex::Regex::new(e).unwrap())
+            .collect();
+        measure_sync(paths, &excludes, opts, &ScanCounters::default()).unwrap()
+    }
+
+    #[test]
+    fn totals_follow_excludes_and_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("tree");
+        fixture(&root);
+        let opts = DuOptions {
+            recursive: true,
+            max_depth: Some(1),
+            largest: 2,
+            ..DuOptions::default()
+        };
+
+        let r = run(std::slice::from_ref(&root), &[r"\.log$"], opts);
+        assert_eq!(r.total_bytes, 1320);
+        assert_eq!(r.paths[0].files, 3);
+        let dirs: Vec<(PathBuf, u64)> = r.paths[0]
+            .dirs
+            .iter()
+            .map(|d| (d.path.strip_prefix(&root).unwrap().to_path_buf(), d.bytes))
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![(PathBuf::from("a"), 320), (PathBuf::from("logs"), 0)]
+        );
+        let largest: Vec<u64> = r.largest.iter().map(|f| f.bytes).collect();
+        assert_eq!(largest, vec![1000, 300]);
+
+        // An excluded argument is left out, a directory needs -r.
+        let r = run(&[root.join("logs")], &["logs"], opts);
+        assert!(r.paths.is_empty());
+        let err = measure_sync(
+            std::slice::from_ref(&root),
+            &[],
+            DuOptions::default(),
+            &ScanCounters::default(),
+        );
+        assert!(matches!(err, Err(BcmrError::InvalidInput(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn blocks_count_hardlinks_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("tree");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("data"), vec![1u8; 64 * 1024]).unwrap();
+        let opts = DuOptions {
+            recursive: true,
+            blocks: true,
+            ..DuOptions::default()
+        };
+        let single = run(std::slice::from_ref(&root), &[], opts).total_bytes;
+        fs::hard_link(root.join("data"), root.join("link")).unwrap();
+
+        let r = run(std::slice::from_ref(&root), &[], opts);
+        assert_eq!(r.total_bytes, single);
+        assert_eq!(r.paths[0].files, 2);
+        let apparent = run(
+            std::slice::from_ref(&root),
+            &[],
+            DuOptions {
+                blocks: false,
+                ..opts
+            },
+        );
+        assert_eq!(apparent.total_bytes, 2 * 64 * 1024);
+    }
+}
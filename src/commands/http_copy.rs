@@ -0,0 +1,266 @@
+use crate::cli::CopyArgs;
+use crate::commands::copy::{temp_path_for, InPlaceGuard, TempFileGuard};
+use crate::core::checksum;
+use crate::core::error::BcmrError;
+use crate::core::http::{self, Opened};
+use crate::core::oplog::{self, Action, WriteKind};
+use crate::core::remote::parse_remote_path;
+use crate::core::verify_report;
+use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::runner::ProgressRunner;
+use crate::ui::utils::display_path;
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+/// Where each URL lands: inside `dest` when it is a directory (or several
+/// URLs share it), else at `dest` itself.
+fn download_targets(urls: &[String], dest: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let into_dir = dest.is_dir() || dest.to_string_lossy().ends_with('/');
+    if urls.len() > 1 && !dest.is_dir() {
+        bail!(
+            "When copying multiple sources, destination '{}' must be an existing directory",
+            dest.display()
+        );
+    }
+    Ok(urls
+        .iter()
+        .map(|url| {
+            let dst = if into_dir || urls.len() > 1 {
+                dest.join(http::file_name(url))
+            } else {
+                dest.to_path_buf()
+            };
+            (url.clone(), dst)
+        })
+        .collect())
+}
+
+/// Downloads HTTP(S) URLs given as copy sources, with the progress,
+/// `--resume` and `--verify` handling a local copy gets.
+pub async fn handle_http_copy(args: &CopyArgs, sources: &[PathBuf], dest: &Path) -> Result<()> {
+    let urls: Vec<String> = sources
+        .iter()
+        .map(|s| s.to_string_lossy().into_owned())
+        .collect();
+    if !urls.iter().all(|u| http::is_url(u)) {
+        bail!("Cannot mix URL sources with local or remote paths");
+    }
+    let dest_str = dest.to_string_lossy();
+    if http::is_url(&dest_str) || parse_remote_path(&dest_str).is_some() {
+        bail!("URL sources can only be downloaded to a local destination");
+    }
+    if args.common.is_verify() && args.expected_hash.is_none() {
+        bail!("--verify on a URL source needs --expected-hash: there is no source file to hash");
+    }
+    if args.expected_hash.is_some() && urls.len() > 1 {
+        bail!("--expected-hash applies to a single URL source");
+    }
+
+    let targets = download_targets(&urls, dest)?;
+
+    if args.common.is_dry_run() {
+        if !crate::config::is_json_mode() {
+            println!("DRY RUN MODE: No changes will be made.\n");
+            for (url, dst) in &targets {
+                let action = match fs::metadata(dst).await {
+                    Err(_) => ActionType::Add,
+                    Ok(_) if args.common.is_no_clobber() => ActionType::Skip,
+                    Ok(_) if is_in_place(args) => ActionType::Append,
+                    Ok(_) => ActionType::Overwrite,
+                };
+                print_dry_run(action, &format!("{} -> {}", url, dst.display()), None);
+            }
+        }
+        return Ok(());
+    }
+
+    let client = http::client(http::parse_headers(&args.headers)?)?;
+    let cancel = CancellationToken::new();
+    let runner = ProgressRunner::new(
+        0,
+        args.common.progress_style(),
+        false,
+        crate::config::is_json_mode(),
+        crate::commands::copy::cleanup_partial_files,
+    )?;
+    runner.cancel_on_interrupt(&cancel);
+    let src_label = match urls.as_slice() {
+        [one] => one.clone(),
+        many => format!("{} URLs", many.len()),
+    };
+    runner.set_paths(&src_label, &display_path(dest));
+    {
+        let mut p = runner.progress().lock();
+        p.set_operation_type("Downloading");
+        if urls.len() > 1 {
+            p.set_total_items(urls.len());
+        }
+    }
+
+    let mut total = 0;
+    for (url, dst) in &targets {
+        if let Err(e) = download(&client, url, dst, args, &runner, &mut total, &cancel).await {
+            return runner.finish_failed(oplog::failed(dst, e));
+        }
+    }
+    runner.finish_ok()
+}
+
+/// `--resume`/`--append`/`--strict`: an existing file is continued from
+/// its length rather than replaced.
+fn is_in_place(args: &CopyArgs) -> bool {
+    args.common.is_resume() || args.common.is_append() || args.common.is_strict()
+}
+
+async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    dst: &Path,
+    args: &CopyArgs,
+    runner: &ProgressRunner,
+    total: &mut u64,
+    cancel: &CancellationToken,
+) -> Result<(), BcmrError> {
+    let verbose = args.common.is_verbose();
+    let existing = fs::metadata(dst).await.ok().map(|m| m.len());
+    if existing.is_some() && args.common.is_no_clobber() {
+        oplog::report(
+            Action::Skip {
+                path: dst,
+                reason: "exists",
+            },
+            verbose,
+        );
+        (runner.items_callback())(1);
+        return Ok(());
+    }
+    if existing.is_some() && !args.common.is_force() && !is_in_place(args) {
+        return Err(BcmrError::TargetExists(dst.to_path_buf()));
+    }
+
+    let name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let mut offset = existing.filter(|_| is_in_place(args)).unwrap_or(0);
+    let mut download = match http::open(client, url, offset).await? {
+        Opened::Body(download) => download,
+        Opened::Complete => {
+            (runner.file_callback())(&name, offset);
+            *total += offset;
+            runner.progress().lock().set_total_bytes(*total);
+            (runner.skip_callback())(offset);
+            oplog::report(
+                Action::Skip {
+                    path: dst,
+                    reason: "complete",
+                },
+                verbose,
+            );
+            (runner.items_callback())(1);
+            return verify_download(url, dst, args).await;
+        }
+    };
+    if offset > 0 && !download.resumed {
+        oplog::warn(
+            dst,
+            "server ignored the range request; downloading from the start",
+        );
+        offset = 0;
+    }
+
+    match download.length {
+        Some(len) => {
+            *total += offset + len;
+            runner.progress().lock().set_total_bytes(*total);
+        }
+        None => runner.progress().lock().set_size_unknown(true),
+    }
+    (runner.file_callback())(&name, download.length.map_or(0, |len| offset + len));
+    (runner.skip_callback())(offset);
+
+    let inc = runner.inc_callback();
+    let (mut file, write_path, mut temp_guard, _in_place_guard) = if offset > 0 {
+        let file = fs::OpenOptions::new().append(true).open(dst).await?;
+        let guard = InPlaceGuard::new(dst, offset, cancel);
+        (file, dst.to_path_buf(), None, Some(guard))
+    } else {
+        let tmp = temp_path_for(dst);
+        let guard = TempFileGuard::new(tmp.clone(), dst, cancel);
+        (fs::File::create(&tmp).await?, tmp, Some(guard), None)
+    };
+
+    let mut written = 0u64;
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => return Err(BcmrError::Cancelled),
+            chunk = download.response.chunk() => chunk.map_err(|e| http::request_error(url, &e))?,
+        };
+        let Some(chunk) = chunk else { break };
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        crate::core::bwlimit::wait_async(chunk.len() as u64).await;
+        inc(chunk.len() as u64);
+    }
+    if let Some(len) = download.length.filter(|len| *len != written) {
+        return Err(BcmrError::HttpConnection(format!(
+            "{}: connection closed after {} of {} bytes",
+            url, written, len
+        )));
+    }
+    file.flush().await?;
+    if args.common.is_sync() {
+        crate::core::io::durable_sync_async(&file).await?;
+    }
+    drop(file);
+
+    if let Some(guard) = temp_guard.as_mut() {
+        fs::rename(&write_path, dst).await?;
+        guard.disarm();
+    }
+    oplog::report(
+        Action::Copy {
+            src: Path::new(url),
+            dst,
+            bytes: written,
+            write: match existing {
+                None => WriteKind::Created,
+                Some(_) if offset > 0 => WriteKind::Appended,
+                Some(_) => WriteKind::Overwritten,
+            },
+        },
+        verbose,
+    );
+    (runner.items_callback())(1);
+    verify_download(url, dst, args).await
+}
+
+/// Checks the file against `--expected-hash`, removing it on a mismatch
+/// as a failed `--verify` of a copy does.
+async fn verify_download(url: &str, dst: &Path, args: &CopyArgs) -> Result<(), BcmrError> {
+    let Some(expected) = args.expected_hash.as_deref() else {
+        return Ok(());
+    };
+    let algo = args.common.checksum_algo();
+    let path = dst.to_path_buf();
+    let actual =
+        tokio::task::spawn_blocking(move || checksum::calculate_hash(&path, algo)).await??;
+    let expected = expected.trim().to_ascii_lowercase();
+
+    crate::core::journal::verified(Path::new(url), dst, algo, &expected, &actual);
+    if verify_report::is_open() {
+        verify_report::record(&verify_report::Outcome {
+            src: Path::new(url),
+            dst,
+            size: fs::metadata(dst).await?.len(),
+            src_digest: Some(&expected),
+            dst_digest: Some(&actual),
+        });
+    }
+
+    if actual != expected {
+        let _ = fs::remove_file(dst).await;
+        return Err(BcmrError::VerificationError(dst.to_path_buf(), None));
+    }
+    Ok(())
+}
@@ -1,168 +1,579 @@
 use crate::cli::Shell;
 use std::path::PathBuf;
 
-pub fn generate_init_script(
-    shell: &Shell,
-    cmd_compat: &str,
-    prefix_arg: Option<&str>,
-    suffix_arg: Option<&str>,
-    path: Option<&PathBuf>,
-    no_cmd: bool,
-) -> String {
-    let exe_path = std::env::current_exe()
-        .unwrap_or_else(|_| PathBuf::from("bcmr"))
-        .display()
-        .to_string();
-
-    match shell {
-        Shell::Bash | Shell::Zsh => {
-            let mut script = String::new();
+/// What `bcmr init` was asked for, past the shell.
+#[derive(Debug, Default)]
+pub struct InitOptions<'a> {
+    /// `--cmd`: the prefix when `--prefix` is not given.
+    pub cmd_compat: &'a str,
+    pub prefix: Option<&'a str>,
+    pub suffix: Option<&'a str>,
+    /// Directory to put in front of PATH.
+    pub path: Option<&'a PathBuf>,
+    /// Only the PATH change, no functions.
+    pub no_cmd: bool,
+    /// Run this binary by its full path instead of looking bcmr up.
+    pub absolute_path: bool,
+    /// Hand arguments bcmr refuses to the system command without a notice.
+    pub quiet_fallback: bool,
+    /// Load tab completion in interactive shells.
+    pub completions: bool,
+    /// Replace aliases and functions already using the names.
+    pub force_override: bool,
+    /// Define the functions in non-interactive shells too.
+    pub always: bool,
+}
 
-            if let Some(path) = path {
-                script.push_str(&format!(
-                    r#"
-# Add bcmr directory to PATH
-export PATH="{}:$PATH"
-"#,
-                    path.display()
-                ));
-            }
+/// Each function's system command and the bcmr subcommand it runs.
+const COMMANDS: [(&str, &str); 3] = [("cp", "copy"), ("mv", "move"), ("rm", "remove")];
 
-            if !no_cmd {
-                let prefix = prefix_arg.unwrap_or(if cmd_compat.is_empty() {
-                    ""
-                } else {
-                    cmd_compat
-                });
-                let suffix = suffix_arg.unwrap_or("");
+pub fn generate_init_script(shell: &Shell, opts: &InitOptions) -> String {
+    let mut script = String::new();
 
-                script.push_str(&format!(
-                    r#"
-# bcmr shell integration for {shell_name}
-function {prefix}cp{suffix}() {{
-    "{exe_path}" copy "$@"
-}}
-
-function {prefix}mv{suffix}() {{
-    "{exe_path}" move "$@"
-}}
+    if let Some(path) = opts.path {
+        let path = path.display().to_string();
+        script.push_str("\n# Add bcmr directory to PATH\n");
+        script.push_str(&match shell {
+            Shell::Bash | Shell::Zsh => format!("export PATH=\"{}:$PATH\"\n", path),
+            Shell::Fish => format!("fish_add_path \"{}\"\n", path),
+            Shell::Nu => format!(
+                "$env.PATH = ($env.PATH | split row (char esep) | prepend {})\n",
+                nu_quote(&path)
+            ),
+        });
+    }
 
-function {prefix}rm{suffix}() {{
-    "{exe_path}" remove "$@"
-}}
-"#,
-                    shell_name = shell,
-                    prefix = prefix,
-                    suffix = suffix,
-                    exe_path = exe_path
-                ));
+    let bcmr = invocation(shell, opts.absolute_path);
+    let names = (!opts.no_cmd).then(|| command_names(opts));
+    if let Some(names) = &names {
+        script.push_str(&match shell {
+            Shell::Fish => "\n# bcmr shell integration\n".to_string(),
+            _ => format!("\n# bcmr shell integration for {}\n", shell),
+        });
 
-                if matches!(shell, Shell::Zsh) {
-                    script.push_str(&format!(
-                        r#"
-# Completion wrappers for aliased commands. Use _bcmr_with_remote
-# (remote path completion) if present, else fall back to _bcmr.
-_{prefix}cp{suffix}() {{
-    words=("bcmr" "copy" "${{words[@]:1}}")
-    (( CURRENT += 1 ))
-    if (( $+functions[_bcmr_with_remote] )); then
-        _bcmr_with_remote "$@"
-    else
-        _bcmr "$@"
-    fi
-}}
-compdef _{prefix}cp{suffix} {prefix}cp{suffix}
+        let mut block = String::new();
+        if !opts.absolute_path {
+            let warning = not_on_path(names);
+            block.push_str(&match shell {
+                Shell::Bash | Shell::Zsh => {
+                    format!(
+                        "command -v bcmr >/dev/null 2>&1 || echo \"{}\" >&2\n\n",
+                        warning
+                    )
+                }
+                Shell::Fish => format!("command -q bcmr; or echo \"{}\" >&2\n\n", warning),
+                Shell::Nu => format!(
+                    "if (which bcmr | is-empty) {{ print --stderr \"{}\" }}\n\n",
+                    warning
+                ),
+            });
+        }
+        block.push_str(&claim_helper(shell, opts.force_override));
+        let wrappers: Vec<String> = names
+            .iter()
+            .zip(COMMANDS)
+            .map(|(name, (system, sub))| {
+                claimed(
+                    shell,
+                    name,
+                    &wrapper(shell, name, system, sub, &bcmr, opts.quiet_fallback),
+                )
+            })
+            .collect();
+        block.push_str(&wrappers.join("\n"));
+        block.push_str(match shell {
+            Shell::Bash | Shell::Zsh => "unset -f __bcmr_claim\n",
+            Shell::Fish => "functions -e __bcmr_claim\n",
+            Shell::Nu => "",
+        });
 
-_{prefix}mv{suffix}() {{
-    words=("bcmr" "move" "${{words[@]:1}}")
-    (( CURRENT += 1 ))
-    if (( $+functions[_bcmr_with_remote] )); then
-        _bcmr_with_remote "$@"
-    else
-        _bcmr "$@"
-    fi
-}}
-compdef _{prefix}mv{suffix} {prefix}mv{suffix}
+        match interactive_guard(shell).filter(|_| !opts.always) {
+            Some((open, close)) => {
+                script.push_str(&format!("{}\n{}{}\n", open, indent(&block), close))
+            }
+            None => script.push_str(&block),
+        }
+    }
 
-_{prefix}rm{suffix}() {{
-    words=("bcmr" "remove" "${{words[@]:1}}")
-    (( CURRENT += 1 ))
-    _bcmr "$@"
-}}
-compdef _{prefix}rm{suffix} {prefix}rm{suffix}
-"#,
-                        prefix = prefix,
-                        suffix = suffix,
-                    ));
-                }
+    if opts.completions {
+        script.push_str(&completion_bootstrap(shell, &bcmr, names.as_ref()));
+    }
 
-                script.push_str(&format!(
-                    r#"
+    if names.is_none() {
+        return script;
+    }
+    match shell {
+        Shell::Bash | Shell::Zsh => script.push_str(&format!(
+            r#"
 # To initialize bcmr, add this to your shell rc file:
 #   eval "$(bcmr init {shell_name})"
 # For a custom prefix (e.g. 'b' creates bcp/bmv/brm):
 #   eval "$(bcmr init {shell_name} --cmd b)"
 "#,
-                    shell_name = shell,
+            shell_name = shell,
+        )),
+        Shell::Fish => {}
+        // nu sources files at parse time, so the script cannot be
+        // piped in the way the other shells eval it.
+        Shell::Nu => script.push_str(
+            r#"
+# To initialize bcmr, save this script once:
+#   bcmr init nu --cmd b | save -f ~/.config/bcmr/init.nu
+# then add this to your config.nu:
+#   source ~/.config/bcmr/init.nu
+"#,
+        ),
+    }
+
+    script
+}
+
+/// Tab completion for bcmr and for each function, through the bcmr
+/// subcommand it runs. Only interactive shells load it, as there is
+/// nobody to press tab in the others.
+fn completion_bootstrap(shell: &Shell, bcmr: &str, names: Option<&[String; 3]>) -> String {
+    let aliases = || names.into_iter().flatten().zip(COMMANDS);
+    let body = match shell {
+        Shell::Bash => {
+            let mut body = format!("source <({} completions bash)\n", bcmr);
+            for (name, (system, sub)) in aliases() {
+                body.push_str(&format!(
+                    r#"
+_bcmr_{system}() {{
+    COMP_WORDS=("bcmr" "{sub}" "${{COMP_WORDS[@]:1}}")
+    (( COMP_CWORD += 1 ))
+    _bcmr_with_remote bcmr "$2" "${{COMP_WORDS[COMP_CWORD-1]}}"
+}}
+complete -F _bcmr_{system} -o bashdefault -o default {name}
+"#
                 ));
             }
-
-            script
+            body
+        }
+        Shell::Zsh => {
+            let mut body = format!("source <({} completions zsh)\n", bcmr);
+            if let Some(names) = names {
+                body.push_str(&zsh_completers(names));
+            }
+            body
         }
         Shell::Fish => {
-            let mut script = String::new();
+            let mut body = format!("{} completions fish | source\n", bcmr);
+            for (name, (_, sub)) in aliases() {
+                body.push_str(&format!("complete -c {} --wraps 'bcmr {}'\n", name, sub));
+            }
+            body
+        }
+        Shell::Nu => return String::new(),
+    };
+    let (open, close) = match shell {
+        Shell::Zsh => (
+            "if [[ -o interactive ]] && (( $+functions[compdef] )); then",
+            "fi",
+        ),
+        _ => interactive_guard(shell).unwrap_or_default(),
+    };
+    let indented = indent(&body);
+    let what = match names {
+        Some(_) => "bcmr and the commands above",
+        None => "bcmr",
+    };
+    format!(
+        "\n# Tab completion for {}\n{}\n{}{}\n",
+        what, open, indented, close
+    )
+}
 
-            if let Some(path) = path {
-                script.push_str(&format!(
-                    r#"
-# Add bcmr directory to PATH
-fish_add_path "{}"
+/// The test that keeps the functions out of scripts, ssh commands and
+/// other non-interactive shells, where a `cp` that is really bcmr breaks
+/// rsync, scp and provisioning tools. nu reads its config only when
+/// interactive, so it needs none.
+fn interactive_guard(shell: &Shell) -> Option<(&'static str, &'static str)> {
+    match shell {
+        Shell::Bash | Shell::Zsh => Some(("if [[ $- == *i* ]]; then", "fi")),
+        Shell::Fish => Some(("if status is-interactive", "end")),
+        Shell::Nu => None,
+    }
+}
+
+/// `__bcmr_claim NAME`, run before each function is defined: true when
+/// NAME is free or one of ours from an earlier init. An alias or someone
+/// else's function of that name is reported with where it comes from and
+/// left alone, or with `force` removed. nu resolves `def` when it parses
+/// the file, so it cannot skip one and gets no check.
+fn claim_helper(shell: &Shell, force: bool) -> String {
+    let (found, taken) = match shell {
+        Shell::Bash => (
+            r#"    local what
+    if alias "$1" >/dev/null 2>&1; then
+        what="an alias: $(alias "$1")"
+    elif declare -F "$1" >/dev/null && ! declare -f "$1" | grep -q -- --check-args; then
+        what="a function from $(shopt -s extdebug; declare -F "$1" | cut -d' ' -f3-)"
+    else
+        return 0
+    fi
 "#,
-                    path.display()
-                ));
-            }
+            r#"unalias "$1" 2>/dev/null
+    unset -f "$1""#,
+        ),
+        Shell::Zsh => (
+            r#"    if (( ! $+aliases[$1] )) && { (( ! $+functions[$1] )) || [[ $functions[$1] == *--check-args* ]] }; then
+        return 0
+    fi
+    local what=${$(whence -v -- $1)#$1 is }
+"#,
+            r#"unalias $1 2>/dev/null
+    unfunction $1 2>/dev/null"#,
+        ),
+        Shell::Fish => (
+            r#"    if not functions -q -- $argv[1]; or functions -- $argv[1] | string match -q -- '*--check-args*'
+        return 0
+    end
+    set -l what "a function from "(functions --details -- $argv[1])
+"#,
+            "functions -e -- $argv[1]",
+        ),
+        Shell::Nu => return String::new(),
+    };
+    let (name, what) = match shell {
+        Shell::Fish => ("$argv[1]", "$what"),
+        _ => ("$1", "$what"),
+    };
+    let verdict = if force {
+        format!("    echo \"bcmr init: replacing {name}, {what}\" >&2\n    {taken}\n    return 0\n")
+    } else {
+        format!(
+            "    echo \"bcmr init: {name} is already {what}; not defining it (--force-override replaces it)\" >&2\n    return 1\n"
+        )
+    };
+    let (open, close) = match shell {
+        Shell::Fish => ("function __bcmr_claim", "end"),
+        _ => ("__bcmr_claim() {", "}"),
+    };
+    format!("{}\n{}{}{}\n\n", open, found, verdict, close)
+}
 
-            if !no_cmd {
-                let prefix = prefix_arg.unwrap_or(if cmd_compat.is_empty() {
-                    ""
-                } else {
-                    cmd_compat
-                });
-                let suffix = suffix_arg.unwrap_or("");
+/// `definition`, made only when `__bcmr_claim` gives up `name`.
+fn claimed(shell: &Shell, name: &str, definition: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => {
+            format!("if __bcmr_claim {}; then\n{}fi\n", name, indent(definition))
+        }
+        Shell::Fish => format!("if __bcmr_claim {}\n{}end\n", name, indent(definition)),
+        Shell::Nu => definition.to_string(),
+    }
+}
 
-                script.push_str(&format!(
-                    r#"
-# bcmr shell integration
-function {prefix}cp{suffix}
-    "{exe_path}" copy $argv
-end
+/// `text` four spaces in, blank lines left blank.
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("    {}\n", line)
+            }
+        })
+        .collect()
+}
 
-function {prefix}mv{suffix}
-    "{exe_path}" move $argv
+/// One function: ask bcmr whether it takes the arguments, and when it
+/// refuses them (exit 2) run the system command instead, so a script
+/// using an option bcmr lacks keeps working.
+fn wrapper(shell: &Shell, name: &str, system: &str, sub: &str, bcmr: &str, quiet: bool) -> String {
+    let notice = |line: String| if quiet { String::new() } else { line };
+    match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            r#"function {name}() {{
+    local reason
+    reason=$({bcmr} --check-args {sub} "$@" 2>&1)
+    if [ $? -eq 2 ]; then
+{notice}        command {system} "$@"
+        return
+    fi
+    {bcmr} {sub} "$@"
+}}
+"#,
+            notice = notice(format!(
+                "        echo \"bcmr: $reason; running the system {}\" >&2\n",
+                system
+            )),
+        ),
+        Shell::Fish => format!(
+            r#"function {name}
+    set -l reason ({bcmr} --check-args {sub} $argv 2>&1)
+    if test $status -eq 2
+{notice}        command {system} $argv
+        return
+    end
+    {bcmr} {sub} $argv
 end
+"#,
+            notice = notice(format!(
+                "        echo \"bcmr: $reason; running the system {}\" >&2\n",
+                system
+            )),
+        ),
+        Shell::Nu => format!(
+            r#"def --wrapped {name} [...args] {{
+    let probe = (do {{ {bcmr} --check-args {sub} ...$args }} | complete)
+    if $probe.exit_code == 2 {{
+{notice}        ^{system} ...$args
+        return
+    }}
+    {bcmr} {sub} ...$args
+}}
+"#,
+            notice = notice(format!(
+                "        print --stderr $\"bcmr: ($probe.stderr | str trim); running the system {}\"\n",
+                system
+            )),
+        ),
+    }
+}
 
-function {prefix}rm{suffix}
-    "{exe_path}" remove $argv
-end
+/// Completion for the zsh functions, through bcmr's own: `_bcmr_with_remote`
+/// (remote path completion) when present, else `_bcmr`.
+fn zsh_completers(names: &[String; 3]) -> String {
+    let mut script = String::from(
+        "\n# Completion wrappers for aliased commands. Use _bcmr_with_remote\n\
+         # (remote path completion) if present, else fall back to _bcmr.\n",
+    );
+    let blocks: Vec<String> = names
+        .iter()
+        .zip(COMMANDS)
+        .map(|(name, (_, sub))| {
+            let complete = if sub == "remove" {
+                "    _bcmr \"$@\"\n".to_string()
+            } else {
+                "    if (( $+functions[_bcmr_with_remote] )); then\n        _bcmr_with_remote \"$@\"\n    else\n        _bcmr \"$@\"\n    fi\n".to_string()
+            };
+            format!(
+                "_{name}() {{\n    words=(\"bcmr\" \"{sub}\" \"${{words[@]:1}}\")\n    (( CURRENT += 1 ))\n{complete}}}\ncompdef _{name} {name}\n"
+            )
+        })
+        .collect();
+    script.push_str(&blocks.join("\n"));
+    script
+}
+
+/// How the functions run bcmr: looked up on PATH when called, so an
+/// upgraded or moved binary is still found, or with `absolute_path` the
+/// path of this binary.
+fn invocation(shell: &Shell, absolute_path: bool) -> String {
+    if !absolute_path {
+        return match shell {
+            Shell::Nu => "^bcmr",
+            _ => "command bcmr",
+        }
+        .to_string();
+    }
+    let exe_path = std::env::current_exe()
+        .unwrap_or_else(|_| PathBuf::from("bcmr"))
+        .display()
+        .to_string();
+    match shell {
+        Shell::Nu => format!("^{}", nu_quote(&exe_path)),
+        _ => format!("\"{}\"", exe_path),
+    }
+}
+
+/// Warning for a shell where bcmr cannot be found when the script runs.
+fn not_on_path(names: &[String; 3]) -> String {
+    format!(
+        "bcmr init: bcmr is not on PATH, so {}, {} and {} will fail; \
+         add its directory with --path, or use --absolute-path",
+        names[0], names[1], names[2]
+    )
+}
+
+/// Prints a script that removes the functions `generate_init_script`
+/// defines for the same `--cmd`, `--prefix` and `--suffix`.
+pub fn generate_uninstall_script(shell: &Shell, opts: &InitOptions) -> String {
+    let names = command_names(opts);
+    let list = names.join(" ");
+
+    match shell {
+        Shell::Bash => format!(
+            r#"
+# Remove the bcmr shell integration
+unset -f {list}
+"#
+        ),
+        Shell::Zsh => format!(
+            r#"
+# Remove the bcmr shell integration
+unset -f {list} 2>/dev/null
+unset -f {completers} 2>/dev/null
+(( $+functions[compdef] )) && compdef -d {list}
 "#,
-                    prefix = prefix,
-                    suffix = suffix,
-                    exe_path = exe_path
-                ));
+            completers = names
+                .iter()
+                .map(|name| format!("_{}", name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        Shell::Fish => format!(
+            r#"
+# Remove the bcmr shell integration
+functions -e {list}
+"#
+        ),
+        Shell::Nu => {
+            let mut script = String::from("\n# Remove the bcmr shell integration\n");
+            for name in &names {
+                script.push_str(&format!("hide {}\n", name));
             }
-
+            script.push_str(
+                r#"
+# Also delete the `source ~/.config/bcmr/init.nu` line from config.nu,
+# or the commands come back in the next session.
+"#,
+            );
             script
         }
     }
 }
 
+/// Checks `--cmd`, `--prefix` and `--suffix`, then the names they add up
+/// to, so a bad value fails before any script is printed.
+pub fn check_command_names(shell: &Shell, opts: &InitOptions) -> Result<(), String> {
+    let prefix = match opts.prefix {
+        Some(prefix) => ("--prefix", prefix),
+        None => ("--cmd", opts.cmd_compat),
+    };
+    for (flag, value) in [prefix, ("--suffix", opts.suffix.unwrap_or(""))] {
+        if !value.is_empty() && value.trim().is_empty() {
+            return Err(format!(
+                "{} '{}' is blank; pass '' for no {}",
+                flag,
+                value,
+                flag.trim_start_matches('-')
+            ));
+        }
+        if let Some(c) = value.chars().find(|c| !name_char(shell, *c)) {
+            return Err(format!(
+                "{} '{}' cannot be part of a {} command name: '{}' is not allowed \
+                 (use letters, digits and {})",
+                flag,
+                value,
+                shell,
+                c,
+                punctuation(shell)
+            ));
+        }
+    }
+    command_names(opts)
+        .iter()
+        .try_for_each(|name| check_name(shell, name))
+}
+
+/// Punctuation a function name may hold: all of it safe to type unquoted.
+/// nu reads `.` as a cell path, so it has one fewer.
+fn punctuation(shell: &Shell) -> &'static str {
+    match shell {
+        Shell::Nu => "_+-",
+        _ => "_+-.",
+    }
+}
+
+fn name_char(shell: &Shell, c: char) -> bool {
+    c.is_ascii_alphanumeric() || punctuation(shell).contains(c)
+}
+
+fn check_name(shell: &Shell, name: &str) -> Result<(), String> {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return Err(format!(
+            "'{}' is not a usable command name: it must start with a letter or _",
+            name
+        ));
+    }
+    if let Some(c) = name.chars().find(|c| !name_char(shell, *c)) {
+        return Err(format!(
+            "'{}' is not a usable {} command name: '{}' is not allowed",
+            name, shell, c
+        ));
+    }
+    if reserved_words(shell).contains(&name) {
+        return Err(format!("'{}' is a reserved word in {}", name, shell));
+    }
+    Ok(())
+}
+
+/// Words `shell` will not take as a function name.
+fn reserved_words(shell: &Shell) -> &'static [&'static str] {
+    match shell {
+        Shell::Bash => &[
+            "case", "coproc", "do", "done", "elif", "else", "esac", "fi", "for", "function", "if",
+            "in", "select", "then", "time", "until", "while",
+        ],
+        Shell::Zsh => &[
+            "case",
+            "coproc",
+            "do",
+            "done",
+            "elif",
+            "else",
+            "end",
+            "esac",
+            "fi",
+            "for",
+            "foreach",
+            "function",
+            "if",
+            "in",
+            "nocorrect",
+            "noglob",
+            "repeat",
+            "select",
+            "then",
+            "time",
+            "until",
+            "while",
+        ],
+        Shell::Fish => &[
+            "and", "begin", "break", "builtin", "case", "command", "continue", "else", "end",
+            "exec", "for", "function", "if", "not", "or", "return", "set", "switch", "test",
+            "while",
+        ],
+        Shell::Nu => &[
+            "alias", "break", "const", "continue", "def", "do", "else", "export", "extern", "for",
+            "hide", "if", "let", "loop", "match", "module", "mut", "overlay", "return", "source",
+            "try", "use", "where", "while",
+        ],
+    }
+}
+
+/// The copy, move and remove command names: `--prefix` falls back to
+/// `--cmd`, and `--suffix` to nothing.
+fn command_names(opts: &InitOptions) -> [String; 3] {
+    let prefix = opts.prefix.unwrap_or(opts.cmd_compat);
+    let suffix = opts.suffix.unwrap_or("");
+    COMMANDS.map(|(system, _)| format!("{}{}{}", prefix, system, suffix))
+}
+
+/// A nu string literal for `s`: raw in single quotes unless it holds one.
+fn nu_quote(s: &str) -> String {
+    if s.contains('\'') {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("'{}'", s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_bash_init_script() {
-        let script = generate_init_script(&Shell::Bash, "b", None, None, None, false);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function bcp()"));
         assert!(script.contains("function bmv()"));
         assert!(script.contains("function brm()"));
@@ -170,7 +581,12 @@ mod tests {
 
     #[test]
     fn test_zsh_init_script() {
-        let script = generate_init_script(&Shell::Zsh, "", None, None, None, false);
+        let script = generate_init_script(
+            &Shell::Zsh,
+            &InitOptions {
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function cp()"));
         assert!(script.contains("function mv()"));
         assert!(script.contains("function rm()"));
@@ -178,22 +594,502 @@ mod tests {
 
     #[test]
     fn test_fish_init_script() {
-        let script = generate_init_script(&Shell::Fish, "b", None, None, None, false);
+        let script = generate_init_script(
+            &Shell::Fish,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function bcp"));
         assert!(script.contains("function bmv"));
         assert!(script.contains("function brm"));
     }
 
+    #[test]
+    fn test_nu_init_script() {
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("def --wrapped bcp [...args] {"));
+        assert!(script.contains("def --wrapped bmv [...args] {"));
+        assert!(script.contains("def --wrapped brm [...args] {"));
+        assert!(script.contains(" remove ...$args\n"));
+        assert!(script.contains("source ~/.config/bcmr/init.nu"));
+        assert!(!script.contains("$env.PATH"));
+    }
+
+    #[test]
+    fn test_nu_with_path_prefix_suffix_and_no_cmd() {
+        let path = PathBuf::from("/some/path");
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                prefix: Some("p"),
+                suffix: Some("+"),
+                path: Some(&path),
+                ..Default::default()
+            },
+        );
+        assert!(script
+            .contains("$env.PATH = ($env.PATH | split row (char esep) | prepend '/some/path')"));
+        assert!(script.contains("def --wrapped pcp+ [...args]"));
+
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                cmd_compat: "b",
+                path: Some(&path),
+                no_cmd: true,
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("prepend '/some/path'"));
+        assert!(!script.contains("def "));
+    }
+
+    #[test]
+    fn test_nu_quote() {
+        assert_eq!(nu_quote("/usr/bin/bcmr"), "'/usr/bin/bcmr'");
+        assert_eq!(nu_quote(r"C:\Tools\bcmr.exe"), r"'C:\Tools\bcmr.exe'");
+        assert_eq!(nu_quote(r#"/it's "here"\x"#), r#""/it's \"here\"\\x""#);
+    }
+
+    #[test]
+    fn test_uninstall_removes_what_init_defined() {
+        let args: [(&str, Option<&str>, Option<&str>); 4] = [
+            ("b", None, None),
+            ("", None, None),
+            ("", Some("p"), Some("+")),
+            ("x", None, Some("2")),
+        ];
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Nu] {
+            for (cmd, prefix, suffix) in args {
+                let opts = InitOptions {
+                    cmd_compat: cmd,
+                    prefix,
+                    suffix,
+                    ..Default::default()
+                };
+                let init = generate_init_script(
+                    &shell,
+                    &InitOptions {
+                        cmd_compat: cmd,
+                        prefix,
+                        suffix,
+                        ..Default::default()
+                    },
+                );
+                let uninstall = generate_uninstall_script(&shell, &opts);
+                let names = command_names(&opts);
+                for name in &names {
+                    let defined = match shell {
+                        Shell::Bash | Shell::Zsh => format!("function {}() {{", name),
+                        Shell::Fish => format!("function {}\n", name),
+                        Shell::Nu => format!("def --wrapped {} [", name),
+                    };
+                    assert!(init.contains(&defined), "{shell}: {init}");
+                }
+                let removed = match shell {
+                    Shell::Bash | Shell::Zsh => format!("unset -f {}", names.join(" ")),
+                    Shell::Fish => format!("functions -e {}", names.join(" ")),
+                    Shell::Nu => names.map(|n| format!("hide {}", n)).join("\n"),
+                };
+                assert!(uninstall.contains(&removed), "{shell}: {uninstall}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_zsh_uninstall_drops_the_completers() {
+        let script = generate_uninstall_script(
+            &Shell::Zsh,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("unset -f _bcp _bmv _brm"));
+        assert!(script.contains("compdef -d bcp bmv brm"));
+    }
+
+    #[test]
+    fn test_prefix_and_suffix_from_the_command_line() {
+        use crate::cli::{Cli, Commands};
+        use clap::Parser;
+
+        let script = |argv: &[&str]| {
+            let cli = Cli::try_parse_from(argv).unwrap();
+            let Commands::Init {
+                shell,
+                cmd,
+                prefix,
+                suffix,
+                absolute_path,
+                ..
+            } = &cli.command
+            else {
+                panic!("not init: {argv:?}");
+            };
+            let cmd = cmd.as_deref().unwrap_or("");
+            let opts = InitOptions {
+                cmd_compat: cmd,
+                prefix: prefix.as_deref(),
+                suffix: suffix.as_deref(),
+                absolute_path: *absolute_path,
+                ..Default::default()
+            };
+            check_command_names(shell, &opts).unwrap();
+            generate_init_script(shell, &opts)
+        };
+        assert!(script(&["bcmr", "init", "bash", "--suffix", "+"]).contains("function cp+()"));
+        assert!(
+            script(&["bcmr", "init", "zsh", "--prefix", "p", "--suffix", "+"])
+                .contains("function pcp+()")
+        );
+        assert!(
+            script(&["bcmr", "init", "fish", "--cmd", "b", "--prefix", "x"])
+                .contains("function xcp\n")
+        );
+        assert!(
+            script(&["bcmr", "init", "bash", "--cmd", "b", "--suffix", "2"])
+                .contains("function bcp2()")
+        );
+    }
+
+    #[test]
+    fn test_unusable_names_are_rejected() {
+        let check = |shell, prefix: &str, suffix: &str| {
+            check_command_names(
+                shell,
+                &InitOptions {
+                    prefix: Some(prefix),
+                    suffix: Some(suffix),
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(check(&Shell::Bash, "b", "+").is_ok());
+        assert!(check(&Shell::Bash, "_my-", "").is_ok());
+        assert!(check(&Shell::Bash, "", "").is_ok());
+        assert_eq!(
+            check(&Shell::Bash, "my ", "").unwrap_err(),
+            "--prefix 'my ' cannot be part of a bash command name: ' ' is not allowed \
+             (use letters, digits and _+-.)"
+        );
+        assert_eq!(
+            check(&Shell::Zsh, "2", "").unwrap_err(),
+            "'2cp' is not a usable command name: it must start with a letter or _"
+        );
+        assert_eq!(
+            check(&Shell::Fish, "", "  ").unwrap_err(),
+            "--suffix '  ' is blank; pass '' for no suffix"
+        );
+        assert!(check(&Shell::Fish, "", "/x").is_err());
+        assert!(check(&Shell::Nu, "-", "").is_err());
+        assert!(check(&Shell::Nu, "b", ".old").is_err());
+        assert!(check(&Shell::Bash, "b", ".old").is_ok());
+
+        let cmd = |value| InitOptions {
+            cmd_compat: value,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_command_names(&Shell::Bash, &cmd("my cmd")).unwrap_err(),
+            "--cmd 'my cmd' cannot be part of a bash command name: ' ' is not allowed \
+             (use letters, digits and _+-.)"
+        );
+        assert!(check_command_names(&Shell::Bash, &cmd(" ")).is_err());
+        // --cmd is not used once --prefix is given.
+        let ignored = InitOptions {
+            prefix: Some("b"),
+            ..cmd("my cmd")
+        };
+        assert!(check_command_names(&Shell::Bash, &ignored).is_ok());
+    }
+
+    #[test]
+    fn test_prefix_beats_cmd_and_suffix_goes_last() {
+        let names = |cmd_compat, prefix, suffix| {
+            command_names(&InitOptions {
+                cmd_compat,
+                prefix,
+                suffix,
+                ..Default::default()
+            })
+        };
+        assert_eq!(names("", None, None), ["cp", "mv", "rm"]);
+        assert_eq!(names("b", None, None), ["bcp", "bmv", "brm"]);
+        assert_eq!(names("b", Some("x"), None), ["xcp", "xmv", "xrm"]);
+        assert_eq!(names("b", Some(""), None), ["cp", "mv", "rm"]);
+        assert_eq!(names("b", None, Some("+")), ["bcp+", "bmv+", "brm+"]);
+        assert_eq!(names("b", Some("p"), Some("2")), ["pcp2", "pmv2", "prm2"]);
+    }
+
+    #[test]
+    fn test_reserved_words_are_per_shell() {
+        assert_eq!(
+            check_name(&Shell::Fish, "end").unwrap_err(),
+            "'end' is a reserved word in fish"
+        );
+        assert!(check_name(&Shell::Zsh, "foreach").is_err());
+        assert!(check_name(&Shell::Bash, "foreach").is_ok());
+        assert!(check_name(&Shell::Nu, "def").is_err());
+    }
+
+    #[test]
+    fn test_functions_look_bcmr_up_when_called() {
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("    command bcmr copy \"$@\"\n"));
+        assert!(script.contains("command -v bcmr >/dev/null 2>&1 || echo \"bcmr init: "));
+        let script = generate_init_script(
+            &Shell::Fish,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("    command bcmr move $argv\n"));
+        assert!(script.contains("command -q bcmr; or echo"));
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                cmd_compat: "b",
+                ..Default::default()
+            },
+        );
+        assert!(script.contains("    ^bcmr remove ...$args\n"));
+        assert!(script.contains("if (which bcmr | is-empty)"));
+    }
+
+    #[test]
+    fn test_refused_arguments_go_to_the_system_command() {
+        let opts = InitOptions {
+            cmd_compat: "b",
+            ..Default::default()
+        };
+        let script = wrapper(&Shell::Bash, "bcp", "cp", "copy", "command bcmr", false);
+        assert!(script.contains(
+            "    reason=$(command bcmr --check-args copy \"$@\" 2>&1)\n    if [ $? -eq 2 ]; then\n        \
+             echo \"bcmr: $reason; running the system cp\" >&2\n        command cp \"$@\"\n"
+        ));
+        let script = wrapper(&Shell::Fish, "bmv", "mv", "move", "command bcmr", false);
+        assert!(script.contains(
+            "    set -l reason (command bcmr --check-args move $argv 2>&1)\n    if test $status -eq 2\n"
+        ));
+        assert!(script.contains("        command mv $argv\n"));
+        let script = generate_init_script(&Shell::Nu, &opts);
+        assert!(script.contains("(do { ^bcmr --check-args remove ...$args } | complete)"));
+        assert!(script.contains("        ^rm ...$args\n"));
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Nu] {
+            let quiet = InitOptions {
+                quiet_fallback: true,
+                ..Default::default()
+            };
+            let script = generate_init_script(&shell, &quiet);
+            assert!(!script.contains("running the system"), "{shell}: {script}");
+            assert!(script.contains("--check-args remove"), "{shell}: {script}");
+        }
+    }
+
+    #[test]
+    fn test_completions_load_only_when_asked() {
+        let expected = [
+            (
+                Shell::Bash,
+                "if [[ $- == *i* ]]; then\n    source <(command bcmr completions bash)\n",
+            ),
+            (
+                Shell::Zsh,
+                "if [[ -o interactive ]] && (( $+functions[compdef] )); then\n    \
+                 source <(command bcmr completions zsh)\n",
+            ),
+            (
+                Shell::Fish,
+                "if status is-interactive\n    command bcmr completions fish | source\n",
+            ),
+        ];
+        for (shell, bootstrap) in expected {
+            let mut opts = InitOptions {
+                cmd_compat: "b",
+                completions: true,
+                ..Default::default()
+            };
+            let script = generate_init_script(&shell, &opts);
+            assert!(script.contains(bootstrap), "{shell}: {script}");
+            opts.completions = false;
+            let script = generate_init_script(&shell, &opts);
+            assert!(!script.contains("completions"), "{shell}: {script}");
+            assert!(!script.contains("compdef"), "{shell}: {script}");
+        }
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                completions: true,
+                ..Default::default()
+            },
+        );
+        assert!(!script.contains("completions"), "{script}");
+    }
+
+    #[test]
+    fn test_functions_complete_as_their_subcommand() {
+        let opts = InitOptions {
+            suffix: Some("+"),
+            completions: true,
+            ..Default::default()
+        };
+        let script = generate_init_script(&Shell::Bash, &opts);
+        assert!(script.contains("        COMP_WORDS=(\"bcmr\" \"move\" \"${COMP_WORDS[@]:1}\")\n"));
+        assert!(script.contains("    complete -F _bcmr_mv -o bashdefault -o default mv+\n"));
+        let script = generate_init_script(&Shell::Zsh, &opts);
+        assert!(script.contains("    compdef _rm+ rm+\n"));
+        let script = generate_init_script(&Shell::Fish, &opts);
+        assert!(script.contains("    complete -c cp+ --wraps 'bcmr copy'\n"));
+
+        let no_cmd = InitOptions {
+            no_cmd: true,
+            ..opts
+        };
+        let script = generate_init_script(&Shell::Fish, &no_cmd);
+        assert!(script.contains("# Tab completion for bcmr\n"), "{script}");
+        assert!(!script.contains("--wraps"), "{script}");
+    }
+
+    #[test]
+    fn test_each_function_is_guarded_by_a_claim() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let opts = InitOptions {
+                cmd_compat: "b",
+                suffix: Some("+"),
+                always: true,
+                ..Default::default()
+            };
+            let script = generate_init_script(&shell, &opts);
+            let (open, close) = match shell {
+                Shell::Fish => ("if __bcmr_claim bcp+\n    function bcp+\n", "end\nend\n"),
+                _ => (
+                    "if __bcmr_claim bcp+; then\n    function bcp+() {\n",
+                    "}\nfi\n",
+                ),
+            };
+            let start = script.find(open).expect(&script);
+            assert!(script[start..].contains(close), "{shell}: {script}");
+            assert!(script.contains("bcmr init: $"), "{shell}: {script}");
+            assert!(script.contains("(--force-override replaces it)"));
+            assert!(script.trim_end().contains("__bcmr_claim\n"));
+            assert!(!script.contains("unalias") && !script.contains("functions -e -- "));
+
+            let forced = InitOptions {
+                force_override: true,
+                ..opts
+            };
+            let script = generate_init_script(&shell, &forced);
+            assert!(!script.contains("not defining it"), "{shell}: {script}");
+            let removes = match shell {
+                Shell::Bash => "unalias \"$1\" 2>/dev/null\n    unset -f \"$1\"\n",
+                Shell::Zsh => "unalias $1 2>/dev/null\n    unfunction $1 2>/dev/null\n",
+                _ => "functions -e -- $argv[1]\n",
+            };
+            assert!(script.contains(removes), "{shell}: {script}");
+        }
+
+        let script = generate_init_script(&Shell::Nu, &InitOptions::default());
+        assert!(!script.contains("__bcmr_claim"), "{script}");
+    }
+
+    #[test]
+    fn test_guard_wraps_exactly_the_functions() {
+        let path = PathBuf::from("/opt/bcmr");
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let opts = |always| InitOptions {
+                cmd_compat: "b",
+                path: Some(&path),
+                always,
+                ..Default::default()
+            };
+            let everywhere = generate_init_script(&shell, &opts(true));
+            let start = everywhere.find("command -").expect(&everywhere);
+            let end = everywhere.rfind("__bcmr_claim\n").expect(&everywhere) + 13;
+            let (open, close) = interactive_guard(&shell).unwrap();
+            let expected = format!(
+                "{}{}\n{}{}\n{}",
+                &everywhere[..start],
+                open,
+                indent(&everywhere[start..end]),
+                close,
+                &everywhere[end..]
+            );
+            assert_eq!(generate_init_script(&shell, &opts(false)), expected);
+            assert!(everywhere[..start].contains("/opt/bcmr"), "{shell}");
+        }
+
+        let nu = InitOptions {
+            cmd_compat: "b",
+            ..Default::default()
+        };
+        assert!(!generate_init_script(&Shell::Nu, &nu).contains("interactive"));
+    }
+
+    #[test]
+    fn test_absolute_path_embeds_this_binary() {
+        let exe = std::env::current_exe().unwrap().display().to_string();
+        let script = generate_init_script(
+            &Shell::Zsh,
+            &InitOptions {
+                cmd_compat: "b",
+                absolute_path: true,
+                ..Default::default()
+            },
+        );
+        assert!(script.contains(&format!("    \"{}\" copy \"$@\"\n", exe)));
+        assert!(!script.contains("command -v bcmr"));
+        let script = generate_init_script(
+            &Shell::Nu,
+            &InitOptions {
+                cmd_compat: "b",
+                absolute_path: true,
+                ..Default::default()
+            },
+        );
+        assert!(script.contains(&format!("    ^{} copy ...$args\n", nu_quote(&exe))));
+        assert!(!script.contains("which bcmr"));
+    }
+
     #[test]
     fn test_with_path() {
         let path = PathBuf::from("/some/path");
-        let script = generate_init_script(&Shell::Bash, "", None, None, Some(&path), false);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                path: Some(&path),
+                ..Default::default()
+            },
+        );
         assert!(script.contains("export PATH=\"/some/path:$PATH\""));
     }
 
     #[test]
     fn test_no_cmd() {
-        let script = generate_init_script(&Shell::Bash, "b", None, None, None, true);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                cmd_compat: "b",
+                no_cmd: true,
+                ..Default::default()
+            },
+        );
         assert!(!script.contains("function bcp()"));
         assert!(!script.contains("function bmv()"));
         assert!(!script.contains("function brm()"));
@@ -201,19 +1097,39 @@ mod tests {
 
     #[test]
     fn test_suffix_only() {
-        let script = generate_init_script(&Shell::Bash, "", None, Some("+"), None, false);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                suffix: Some("+"),
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function cp+()"));
     }
 
     #[test]
     fn test_prefix_and_suffix() {
-        let script = generate_init_script(&Shell::Bash, "", Some("b"), Some("+"), None, false);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                prefix: Some("b"),
+                suffix: Some("+"),
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function bcp+()"));
     }
 
     #[test]
     fn test_compat_cmd_with_suffix() {
-        let script = generate_init_script(&Shell::Bash, "b", None, Some("+"), None, false);
+        let script = generate_init_script(
+            &Shell::Bash,
+            &InitOptions {
+                cmd_compat: "b",
+                suffix: Some("+"),
+                ..Default::default()
+            },
+        );
         assert!(script.contains("function bcp+()"));
     }
 }
@@ -0,0 +1,90 @@
+use crate::core::error::BcmrError;
+use crate::core::journal::{self, Entry, Kind, Outcome};
+use crate::ui::utils::format_bytes;
+
+use std::path::Path;
+
+/// Prints the journal at `path`, one line per record.
+pub fn show(path: &Path) -> Result<(), BcmrError> {
+    let entries = journal::load(path).map_err(|e| {
+        BcmrError::InvalidInput(format!("cannot read journal '{}': {}", path.display(), e))
+    })?;
+    for entry in &entries {
+        println!("{}", line(entry));
+    }
+    Ok(())
+}
+
+fn line(entry: &Entry) -> String {
+    match entry {
+        Entry::Header(h) => {
+            let mut line = format!("{}  started  {}", h.started, h.args.join(" "));
+            if let Some(host) = &h.hostname {
+                line.push_str(&format!("  on {host}"));
+            }
+            if let Some(uid) = h.uid {
+                line.push_str(&format!("  uid {uid}"));
+            }
+            line
+        }
+        Entry::Action(a) => {
+            let kind = match a.action {
+                Kind::Create => "create",
+                Kind::Overwrite => "overwrite",
+                Kind::Append => "append",
+                Kind::Move => "move",
+                Kind::Remove => "remove",
+                Kind::Skip => "skip",
+                Kind::Error => "error",
+            };
+            let mut line = format!("{}  {:<9}  ", a.time, kind);
+            if let Some(src) = &a.src {
+                line.push_str(&format!("{} -> ", src.display()));
+            }
+            line.push_str(&a.path.display().to_string());
+            if let Some(size) = a.size {
+                line.push_str(&format!("  ({})", format_bytes(size as f64)));
+            }
+            if let Some(detail) = &a.detail {
+                line.push_str(&format!(": {detail}"));
+            }
+            line
+        }
+        Entry::Verify(v) => {
+            let result = match v.outcome {
+                Outcome::Ok => "verified",
+                _ => "MISMATCH",
+            };
+            let mut line = format!(
+                "{}  {:<9}  {} {}:{}",
+                v.time,
+                result,
+                v.dst.display(),
+                v.algorithm,
+                v.digest
+            );
+            if let Some(dst) = &v.dst_digest {
+                line.push_str(&format!(" (destination {dst})"));
+            }
+            line
+        }
+        Entry::Footer(f) => {
+            let s = &f.stats;
+            let mut line = format!(
+                "{}  finished  {} created, {} overwritten, {} appended, {} moved, {} removed, {} skipped, {} failed",
+                f.finished,
+                s.created,
+                s.overwritten,
+                s.appended,
+                s.moved,
+                s.removed,
+                s.skipped,
+                s.failed
+            );
+            if let Some(error) = &f.error {
+                line.push_str(&format!(": {error}"));
+            }
+            line
+        }
+    }
+}
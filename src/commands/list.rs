@@ -0,0 +1,131 @@
+use crate::core::error::BcmrError;
+use crate::core::traversal;
+
+use std::path::PathBuf;
+use walkdir::DirEntry;
+
+/// One entry `bcmr list` prints. `pattern` is the index of the exclude
+/// that filtered it out, for `--excluded`.
+pub struct Listed {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub pattern: Option<usize>,
+}
+
+impl Listed {
+    fn new(entry: DirEntry, pattern: Option<usize>) -> Self {
+        // Sized as the copy sizes it: links by what they point at.
+        let md = std::fs::metadata(entry.path()).ok();
+        Self {
+            is_dir: entry.file_type().is_dir(),
+            size: md.filter(|md| md.is_file()).map_or(0, |md| md.len()),
+            path: entry.into_path(),
+            pattern,
+        }
+    }
+}
+
+type Entries = Box<dyn Iterator<Item = Result<Listed, BcmrError>> + Send>;
+
+/// What copy, move and remove would see under `paths`, walked with the
+/// same filter they apply, or with `excluded` only what that filter drops.
+/// An excluded directory stands for everything under it.
+pub fn entries(
+    paths: Vec<PathBuf>,
+    recursive: bool,
+    excludes: Vec<regex::Regex>,
+    excluded: bool,
+) -> impl Iterator<Item = Result<Listed, BcmrError>> {
+    paths
+        .into_iter()
+        .flat_map(move |root| -> Entries { list_root(root, recursive, &excludes, excluded) })
+}
+
+fn list_root(root: PathBuf, recursive: bool, excludes: &[regex::Regex], excluded: bool) -> Entries {
+    let md = match std::fs::metadata(&root) {
+        Ok(md) => md,
+        Err(_) => return Box::new(std::iter::once(Err(BcmrError::SourceNotFound(root)))),
+    };
+    let hit = traversal::excluded_by(&root, excludes);
+    if hit.is_some() || !md.is_dir() {
+        if hit.is_some() != excluded {
+            return Box::new(std::iter::empty());
+        }
+        return Box::new(std::iter::once(Ok(Listed {
+            is_dir: md.is_dir(),
+            size: if md.is_file() { md.len() } else { 0 },
+            path: root,
+            pattern: hit,
+        })));
+    }
+
+    if excluded {
+        Box::new(
+            traversal::walk_tagged(&root, recursive, 1, excludes).filter_map(|entry| match entry {
+                Ok((entry, Some(hit))) => Some(Ok(Listed::new(entry, Some(hit)))),
+                Ok((_, None)) => None,
+                Err(e) => Some(Err(e.into())),
+            }),
+        )
+    } else {
+        Box::new(
+            traversal::walk(&root, recursive, false, 1, excludes)
+                .map(|entry| Ok(Listed::new(entry?, None))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn excluded_is_the_complement_of_the_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("tree");
+        fs::create_dir_all(root.join("build/out")).unwrap();
+        fs::write(root.join("a.rs"), b"fn main() {}").unwrap();
+        fs::write(root.join("b.log"), b"log").unwrap();
+        fs::write(root.join("build/out/x.o"), b"obj").unwrap();
+        let excludes = vec![
+            regex::Regex::new(r"\.log$").unwrap(),
+            regex::Regex::new(r"/build$").unwrap(),
+        ];
+        let names = |excluded: bool| -> Vec<(String, Option<usize>)> {
+            let mut names: Vec<_> = entries(vec![root.clone()], true, excludes.clone(), excluded)
+                .map(|l| {
+                    let l = l.unwrap();
+                    (
+                        l.path.strip_prefix(&root).unwrap().display().to_string(),
+                        l.pattern,
+                    )
+                })
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(false), vec![("a.rs".to_string(), None)]);
+        assert_eq!(
+            names(true),
+            vec![
+                ("b.log".to_string(), Some(0)),
+                ("build".to_string(), Some(1))
+            ]
+        );
+
+        // A file argument is listed as itself; an excluded one only with --excluded.
+        let log = root.join("b.log");
+        assert_eq!(
+            entries(vec![log.clone()], false, excludes.clone(), false).count(),
+            0
+        );
+        let hit = entries(vec![log], false, excludes, true)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!((hit.size, hit.pattern), (3, Some(0)));
+    }
+}
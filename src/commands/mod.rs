@@ -1,11 +1,25 @@
+pub mod bench;
 pub mod check;
+pub mod checksum;
+pub mod codec_copy;
+pub mod config;
 pub mod copy;
 mod copy_strategies;
 pub mod deploy;
+pub mod doctor;
+pub mod du;
+pub mod http_copy;
 pub mod init;
 pub mod jobs;
+pub mod journal;
+pub mod list;
 pub mod r#move;
 pub mod remote_copy;
 pub mod remove;
 pub mod serve;
+pub mod sync;
+pub mod tar_copy;
+pub mod undo;
 pub mod update;
+pub mod verify;
+pub mod watch;
@@ -1,15 +1,19 @@
-use crate::cli::Commands;
-use crate::commands::copy;
+use crate::cli::{CopyArgs, MoveArgs};
+use crate::commands::copy::{self, PlanEntry, ProgressCallback};
 use crate::core::error::BcmrError;
 use crate::core::io as durable_io;
-use crate::core::traversal;
+use crate::core::oplog::{self, Action};
+use crate::core::traversal::{self, EntryKind, TreeTotals};
 use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::scan::ScanCounters;
 
 pub use copy::FileToOverwrite;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 
-fn is_cross_device_error(err: &std::io::Error) -> bool {
+pub(crate) fn is_cross_device_error(err: &std::io::Error) -> bool {
     #[cfg(unix)]
     {
         err.raw_os_error() == Some(libc::EXDEV)
@@ -27,56 +31,155 @@ fn is_cross_device_error(err: &std::io::Error) -> bool {
     }
 }
 
-pub async fn check_overwrites(
+/// What a single walk of the sources finds before anything is moved.
+pub struct MoveSurvey {
+    /// One entry per source, in order.
+    pub totals: Vec<TreeTotals>,
+    /// Only filled in when the survey was asked to look for them.
+    pub overwrites: Vec<FileToOverwrite>,
+}
+
+impl MoveSurvey {
+    pub fn total_size(&self) -> u64 {
+        self.totals.iter().map(|t| t.bytes).sum()
+    }
+}
+
+/// Walks every source once for the overwrite check, the progress total and
+/// the sizes reported when a directory is renamed in one step. Without an
+/// overwrite check to make, directories are totalled on `jobs` threads.
+pub async fn survey(
     sources: &[PathBuf],
     dst: &Path,
     recursive: bool,
-    cli: &Commands,
+    find_overwrites: bool,
     excludes: &[regex::Regex],
-) -> std::result::Result<Vec<FileToOverwrite>, BcmrError> {
-    copy::check_overwrites(sources, dst, recursive, cli, excludes).await
+    jobs: usize,
+    counters: Arc<ScanCounters>,
+) -> std::result::Result<MoveSurvey, BcmrError> {
+    let sources = sources.to_vec();
+    let dst = dst.to_path_buf();
+    let excludes = excludes.to_vec();
+    tokio::task::spawn_blocking(move || {
+        survey_sync(
+            &sources,
+            &dst,
+            recursive,
+            find_overwrites,
+            &excludes,
+            jobs,
+            &counters,
+        )
+    })
+    .await?
 }
 
-pub async fn get_total_size(
+fn survey_sync(
     sources: &[PathBuf],
+    dst: &Path,
     recursive: bool,
-    cli: &Commands,
+    find_overwrites: bool,
     excludes: &[regex::Regex],
-) -> std::result::Result<u64, BcmrError> {
-    copy::get_total_size(sources, recursive, cli, excludes).await
+    jobs: usize,
+    counters: &ScanCounters,
+) -> std::result::Result<MoveSurvey, BcmrError> {
+    let mut totals = Vec::with_capacity(sources.len());
+    let mut overwrites = Vec::new();
+    let mut finder = copy::OverwriteFinder::new(excludes);
+
+    for src in sources {
+        if !find_overwrites && recursive && src.is_dir() && !traversal::is_excluded(src, excludes) {
+            let record = |path: &Path, size| counters.record(path, size);
+            totals.push(traversal::sum_tree(src, excludes, jobs, record)?);
+            continue;
+        }
+        let mut source = TreeTotals::default();
+        copy::scan_sources(
+            std::slice::from_ref(src),
+            dst,
+            recursive,
+            excludes,
+            |entry| {
+                let size = entry.size();
+                if let PlanEntry::CopyFile { src, .. } = &entry {
+                    counters.record(src, size)?;
+                    source.files += 1;
+                }
+                source.bytes += size;
+                if find_overwrites {
+                    overwrites.extend(finder.check(&entry));
+                }
+                Ok(())
+            },
+        )?;
+        totals.push(source);
+    }
+
+    Ok(MoveSurvey { totals, overwrites })
 }
 
 pub async fn move_path<F>(
     src: &Path,
     dst: &Path,
-    cli: &Commands,
+    cli: &MoveArgs,
     excludes: &[regex::Regex],
-    progress_callback: F,
-    on_new_file: impl Fn(&str, u64) + Send + Sync + 'static + Clone,
+    totals: TreeTotals,
+    callback: ProgressCallback<F>,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError>
 where
     F: Fn(u64) + Send + Sync + Clone + 'static,
 {
-    let recursive = cli.is_recursive();
+    let recursive = cli.common.is_recursive();
     if traversal::is_excluded(src, excludes) {
         return Ok(());
     }
+    if cancel.is_cancelled() {
+        return Err(BcmrError::Cancelled);
+    }
+
+    let src_md = durable_io::stat(src).await;
+    let src_is_dir = src_md.as_ref().is_some_and(|md| md.is_dir());
+    let dst_is_dir = durable_io::stat(dst).await.is_some_and(|md| md.is_dir());
 
-    if src.is_file() {
+    if let Some(src_md) = src_md.filter(|md| md.is_file()) {
         let dst_path =
-            if dst.is_dir() {
+            if dst_is_dir {
                 dst.join(src.file_name().ok_or_else(|| {
                     BcmrError::InvalidInput("Invalid source file name".to_string())
                 })?)
             } else {
                 dst.to_path_buf()
             };
+        let dst_exists = durable_io::exists(&dst_path).await;
 
-        if dst_path.exists() && !cli.is_force() {
-            return Err(BcmrError::TargetExists(dst_path));
+        if dst_exists && cli.common.is_no_clobber() {
+            if cli.common.is_dry_run() {
+                print_dry_run(
+                    ActionType::Skip,
+                    &src.to_string_lossy(),
+                    Some(&dst_path.to_string_lossy()),
+                );
+            } else {
+                oplog::report(
+                    Action::Skip {
+                        path: &dst_path,
+                        reason: "exists",
+                    },
+                    cli.common.is_verbose(),
+                );
+            }
+            return Ok(());
         }
 
-        if cli.is_dry_run() {
+        if dst_exists && !cli.common.is_force() {
+            return Err(oplog::failed(
+                &dst_path,
+                BcmrError::TargetExists(dst_path.clone()),
+            ));
+        }
+
+        if cli.common.is_dry_run() {
             print_dry_run(
                 ActionType::Move,
                 &src.to_string_lossy(),
@@ -85,11 +188,11 @@ where
             return Ok(());
         }
 
-        if dst_path.exists() && cli.is_force() {
+        if dst_exists && cli.common.is_force() {
             fs::remove_file(&dst_path).await?;
         }
 
-        let file_size = src.metadata()?.len();
+        let file_size = src_md.len();
         let file_name = src
             .file_name()
             .unwrap_or_default()
@@ -97,44 +200,42 @@ where
             .to_string();
         if let Err(e) = fs::rename(src, &dst_path).await {
             if is_cross_device_error(&e) {
-                copy::copy_path(
-                    src,
-                    &dst_path,
-                    cli,
-                    excludes,
-                    progress_callback.clone(),
-                    on_new_file.clone(),
-                )
-                .await?;
+                copy_for_move(src, &dst_path, cli, excludes, callback.clone(), cancel).await?;
                 fs::remove_file(src).await?;
             } else {
                 return Err(BcmrError::Io(e));
             }
         } else {
-            if cli.is_sync() {
+            if cli.common.is_sync() {
                 if let Some(parent) = dst_path.parent() {
                     durable_io::fsync_dir_async(parent).await;
                 }
             }
-            on_new_file(&file_name, file_size);
-            progress_callback(file_size);
-            if cli.is_verbose() {
-                eprintln!("renamed '{}' -> '{}'", src.display(), dst_path.display());
-            }
+            (callback.on_new_file)(&file_name, file_size);
+            (callback.callback)(file_size);
+            (callback.on_files_done)(1);
+            oplog::report(
+                Action::Move {
+                    src,
+                    dst: &dst_path,
+                    bytes: 0,
+                },
+                cli.common.is_verbose(),
+            );
         }
-    } else if recursive && src.is_dir() {
+    } else if recursive && src_is_dir {
         let src_name = src
             .file_name()
             .ok_or_else(|| BcmrError::InvalidInput("Invalid source directory name".to_string()))?;
-        let new_dst = if dst.is_dir() {
+        let new_dst = if dst_is_dir {
             dst.join(src_name)
         } else {
             dst.to_path_buf()
         };
 
-        if !excludes.is_empty() || cli.is_dry_run() {
-            if cli.is_dry_run() {
-                if !new_dst.exists() {
+        if !excludes.is_empty() || cli.common.is_dry_run() {
+            if cli.common.is_dry_run() {
+                if !durable_io::exists(&new_dst).await {
                     print_dry_run(
                         ActionType::Add,
                         &src.to_string_lossy(),
@@ -142,14 +243,17 @@ where
                     );
                 }
 
-                for entry in traversal::walk(src, true, false, 1, excludes) {
+                let mut entries = traversal::in_background({
+                    let (src, excludes) = (src.to_path_buf(), excludes.to_vec());
+                    move || traversal::scan(&src, &excludes)
+                });
+                while let Some(entry) = entries.recv().await {
                     let entry = entry?;
-                    let path = entry.path();
-                    let relative_path = path.strip_prefix(src)?;
-                    let target_path = new_dst.join(relative_path);
+                    let path = entry.path.as_path();
+                    let target_path = new_dst.join(&entry.relative);
 
-                    if path.is_dir() {
-                        if !target_path.exists() {
+                    if entry.kind == EntryKind::Dir {
+                        if !durable_io::exists(&target_path).await {
                             print_dry_run(
                                 ActionType::Add,
                                 &path.to_string_lossy(),
@@ -167,22 +271,16 @@ where
                 return Ok(());
             }
 
-            copy::copy_path(
-                src,
-                dst,
-                cli,
-                excludes,
-                progress_callback.clone(),
-                on_new_file.clone(),
-            )
-            .await?;
-
-            remove_directory_contents(src, excludes).await?;
-            let _ = fs::remove_dir(src).await;
+            copy_for_move(src, dst, cli, excludes, callback.clone(), cancel).await?;
+
+            remove_directory_contents(src, excludes, cancel).await?;
+            // Excluded files are meant to stay behind, keeping the directory.
+            if let Err(e) = fs::remove_dir(src).await {
+                if excludes.is_empty() {
+                    oplog::warn(src, &format!("could not remove source directory: {}", e));
+                }
+            }
         } else {
-            let dir_size = copy::get_total_size(&[src.to_path_buf()], true, cli, excludes)
-                .await
-                .unwrap_or(0);
             let dir_name = src
                 .file_name()
                 .unwrap_or_default()
@@ -191,33 +289,31 @@ where
 
             if let Err(e) = fs::rename(src, &new_dst).await {
                 if is_cross_device_error(&e) {
-                    copy::copy_path(
-                        src,
-                        dst,
-                        cli,
-                        excludes,
-                        progress_callback.clone(),
-                        on_new_file.clone(),
-                    )
-                    .await?;
+                    copy_for_move(src, dst, cli, excludes, callback.clone(), cancel).await?;
                     fs::remove_dir_all(src).await?;
                 } else {
                     return Err(e.into());
                 }
             } else {
-                if cli.is_sync() {
+                if cli.common.is_sync() {
                     if let Some(parent) = new_dst.parent() {
                         durable_io::fsync_dir_async(parent).await;
                     }
                 }
-                on_new_file(&dir_name, dir_size);
-                progress_callback(dir_size);
-                if cli.is_verbose() {
-                    eprintln!("renamed '{}' -> '{}'", src.display(), new_dst.display());
-                }
+                (callback.on_new_file)(&dir_name, totals.bytes);
+                (callback.callback)(totals.bytes);
+                (callback.on_files_done)(totals.files as usize);
+                oplog::report(
+                    Action::Move {
+                        src,
+                        dst: &new_dst,
+                        bytes: 0,
+                    },
+                    cli.common.is_verbose(),
+                );
             }
         }
-    } else if src.is_dir() {
+    } else if src_is_dir {
         return Err(BcmrError::InvalidInput(format!(
             "Source '{}' is a directory. Use -r flag for recursive move.",
             src.display()
@@ -229,17 +325,51 @@ where
     Ok(())
 }
 
+/// Copies what a move cannot rename into place. Each file copied is
+/// reported as moved, since the caller removes the sources next.
+async fn copy_for_move<F>(
+    src: &Path,
+    dst: &Path,
+    cli: &MoveArgs,
+    excludes: &[regex::Regex],
+    callback: ProgressCallback<F>,
+    cancel: &CancellationToken,
+) -> std::result::Result<(), BcmrError>
+where
+    F: Fn(u64) + Send + Sync + Clone + 'static,
+{
+    oplog::copies_are_moves(true);
+    let copied = copy::copy_path(src, dst, &CopyArgs::from(cli), excludes, callback, cancel).await;
+    oplog::copies_are_moves(false);
+    copied
+}
+
 async fn remove_directory_contents(
     dir: &Path,
     excludes: &[regex::Regex],
+    cancel: &CancellationToken,
+) -> std::result::Result<(), BcmrError> {
+    let (dir, excludes, cancel) = (dir.to_path_buf(), excludes.to_vec(), cancel.clone());
+    tokio::task::spawn_blocking(move || remove_directory_contents_sync(&dir, &excludes, &cancel))
+        .await?
+}
+
+fn remove_directory_contents_sync(
+    dir: &Path,
+    excludes: &[regex::Regex],
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError> {
     for entry in traversal::walk(dir, true, true, 0, excludes) {
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
         let entry = entry?;
         let path = entry.path();
-        if path.is_file() {
-            fs::remove_file(path).await?;
-        } else if path.is_dir() {
-            let _ = fs::remove_dir(path).await;
+        let ft = entry.file_type();
+        if ft.is_dir() || traversal::is_dir_link(&ft) {
+            let _ = std::fs::remove_dir(path);
+        } else {
+            std::fs::remove_file(path)?;
         }
     }
 
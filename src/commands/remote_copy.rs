@@ -1,35 +1,33 @@
-use crate::cli::Commands;
+use crate::cli::CopyArgs;
 use crate::config::CONFIG;
 use crate::core::error::BcmrError;
-use crate::core::remote::{self, parse_remote_path, RemotePath};
+use crate::core::remote::{self, confine, parse_remote_path, RemotePath};
 use crate::ui::progress::ProgressRenderer;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use parking_lot::Mutex;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
 
 mod legacy;
+mod relay;
 mod serve;
 
 use legacy::{handle_remote_download, handle_remote_upload};
+use relay::handle_remote_relay;
 use serve::{handle_serve_download, handle_serve_upload};
 
-pub(super) fn transfer_options_from_cli(cli: &Commands) -> remote::TransferOptions {
+pub(super) fn transfer_options_from_cli(cli: &CopyArgs) -> remote::TransferOptions {
     remote::TransferOptions {
-        preserve: cli.is_preserve(),
-        verify: cli.is_verify(),
-        resume: cli.is_resume(),
-        strict: cli.is_strict(),
-        append: cli.is_append(),
-        sync: cli.is_sync(),
+        preserve: cli.common.is_preserve(),
+        verify: cli.common.is_verify(),
+        resume: cli.common.is_resume(),
+        strict: cli.common.is_strict(),
+        append: cli.common.is_append(),
+        sync: cli.common.is_sync(),
     }
 }
 
-pub fn is_plain_mode(args: &Commands) -> bool {
-    args.is_tui_mode() || CONFIG.progress.style.eq_ignore_ascii_case("plain")
-}
-
 pub(super) struct TransferItem {
     pub local_path: PathBuf,
     pub remote: RemotePath,
@@ -73,7 +71,7 @@ pub(super) async fn run_parallel_transfers(
 ) -> Result<(), BcmrError> {
     let semaphore = Arc::new(tokio::sync::Semaphore::new(parallel));
     let slot_pool: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new((0..parallel).rev().collect()));
-    let errors: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let errors: Arc<Mutex<Vec<BcmrError>>> = Arc::new(Mutex::new(Vec::new()));
     let mut handles = Vec::new();
 
     for item in items {
@@ -88,7 +86,8 @@ pub(super) async fn run_parallel_transfers(
             let slot = match pool.lock().pop() {
                 Some(s) => s,
                 None => {
-                    errs.lock().push("no available worker slot".to_string());
+                    errs.lock()
+                        .push(BcmrError::InvalidInput("no available worker slot".into()));
                     return;
                 }
             };
@@ -161,7 +160,7 @@ pub(super) async fn run_parallel_transfers(
             };
 
             if let Err(e) = result {
-                errs.lock().push(e.to_string());
+                errs.lock().push(e);
             }
 
             prog.lock().finish_worker(slot);
@@ -173,11 +172,23 @@ pub(super) async fn run_parallel_transfers(
         handle.await?;
     }
 
-    let errs = errors.lock();
-    if !errs.is_empty() {
-        return Err(BcmrError::InvalidInput(errs.join("; ")));
+    let mut errs = std::mem::take(&mut *errors.lock());
+    if errs.len() > 1 {
+        // All of them are reported, under the kind of the first, which
+        // decides the exit status.
+        let message = errs
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(match errs.swap_remove(0) {
+            BcmrError::SshConnection(_) => BcmrError::SshConnection(message),
+            BcmrError::SshAuth(_) => BcmrError::SshAuth(message),
+            BcmrError::RemoteIo(_) => BcmrError::RemoteIo(message),
+            _ => BcmrError::InvalidInput(message),
+        });
     }
-    Ok(())
+    errs.pop().map_or(Ok(()), Err)
 }
 
 pub(super) fn collect_upload_files(
@@ -212,23 +223,72 @@ pub(super) fn resolve_upload_remote(
     rdest: &RemotePath,
     multi_source: bool,
 ) -> RemotePath {
-    if multi_source || rdest.path == "." {
+    if multi_source || rdest.path == "." || rdest.path.ends_with('/') {
         rdest.join(&src.file_name().unwrap_or_default().to_string_lossy())
     } else {
         rdest.clone()
     }
 }
 
+/// Every remote path an upload of `sources` would create, so that both
+/// transports are held to the same destination check before either writes.
+fn upload_targets(
+    sources: &[std::path::PathBuf],
+    rdest: &RemotePath,
+    recursive: bool,
+    excludes: &[regex::Regex],
+) -> Result<Vec<String>> {
+    use crate::core::traversal;
+
+    let multi_source = sources.len() > 1;
+    let mut targets = Vec::new();
+    for src in sources {
+        if traversal::is_excluded(src, excludes) {
+            continue;
+        }
+        if src.is_file() {
+            targets.push(resolve_upload_remote(src, rdest, multi_source).path);
+        } else if src.is_dir() && recursive {
+            let dir_remote = rdest.join(&src.file_name().unwrap_or_default().to_string_lossy());
+            for entry in traversal::walk(src, true, false, 1, excludes) {
+                let entry = entry?;
+                let rel = entry.path().strip_prefix(src)?;
+                targets.push(confine::join(&dir_remote, &rel.to_string_lossy())?.path);
+            }
+            targets.push(dir_remote.path);
+        }
+    }
+    Ok(targets)
+}
+
 pub async fn handle_remote_copy(
-    args: &Commands,
+    args: &CopyArgs,
     sources: &[std::path::PathBuf],
     dest: &std::path::Path,
     excludes: &[regex::Regex],
 ) -> Result<()> {
     let dest_str = dest.to_string_lossy();
-    let remote_dest = parse_remote_path(&dest_str);
+    let remote_dest = parse_remote_path(&dest_str)
+        .map(|mut rdest| -> Result<RemotePath, BcmrError> {
+            rdest.path = confine::normalize(&rdest.path)?;
+            Ok(rdest)
+        })
+        .transpose()?;
     let is_upload = remote_dest.is_some();
 
+    if let Some(ref rdest) = remote_dest {
+        let remote_sources = sources
+            .iter()
+            .filter(|s| parse_remote_path(&s.to_string_lossy()).is_some())
+            .count();
+        if remote_sources == sources.len() {
+            return handle_remote_relay(args, sources, rdest, excludes).await;
+        }
+        if remote_sources > 0 {
+            bail!("Cannot mix local and remote sources when the destination is remote");
+        }
+    }
+
     let compression_mode = CONFIG.scp.compression.to_lowercase();
     let compress = match compression_mode.as_str() {
         "force" => true,
@@ -282,7 +342,17 @@ pub async fn handle_remote_copy(
     };
     remote::validate_ssh_connection(&check_target).await?;
 
-    let parallel = args.get_parallel().unwrap_or(CONFIG.scp.parallel_transfers);
+    if let Some(ref rdest) = remote_dest {
+        if !args.common.is_dry_run() {
+            let guard =
+                confine::DestinationGuard::scan(rdest, CONFIG.scp.allowed_root.as_deref()).await?;
+            for target in upload_targets(sources, rdest, args.common.is_recursive(), excludes)? {
+                guard.check(&target)?;
+            }
+        }
+    }
+
+    let parallel = args.parallel.unwrap_or(CONFIG.scp.parallel_transfers);
     let serve_parallel = parallel.max(1);
 
     let ssh_target = check_target.ssh_target();
@@ -300,7 +370,7 @@ pub async fn handle_remote_copy(
             let is_resume_redirect = msg.contains("not yet supported, fallback to legacy")
                 || msg.contains("not supported, fallback to legacy");
             if !is_dry_run_redirect && !is_resume_redirect && CONFIG.transfer.fallback_warning {
-                let sync_caveat = if args.is_sync() && is_upload {
+                let sync_caveat = if args.common.is_sync() && is_upload {
                     "\n                     bcmr: --sync on legacy uploads is best-effort \
                      (scp does not guarantee remote fsync)."
                 } else {
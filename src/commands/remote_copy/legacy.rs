@@ -1,15 +1,16 @@
 use super::{
-    collect_upload_files, is_plain_mode, resolve_upload_remote, run_parallel_transfers,
-    transfer_options_from_cli, TransferItem,
+    collect_upload_files, resolve_upload_remote, run_parallel_transfers, transfer_options_from_cli,
+    TransferItem,
 };
-use crate::cli::Commands;
-use crate::core::remote::{self, parse_remote_path, RemotePath};
+use crate::cli::CopyArgs;
+use crate::core::remote::{self, confine, parse_remote_path, RemotePath};
+use crate::core::traversal;
 use crate::ui::runner::ProgressRunner;
 use crate::ui::utils::format_bytes;
 use anyhow::{bail, Result};
 
 pub(super) async fn handle_remote_upload(
-    args: &Commands,
+    args: &CopyArgs,
     sources: &[std::path::PathBuf],
     rdest: &RemotePath,
     parallel: usize,
@@ -23,10 +24,13 @@ pub(super) async fn handle_remote_upload(
         }
         if src.is_file() {
             total_size += src.metadata()?.len();
-        } else if src.is_dir() && args.is_recursive() {
-            total_size +=
-                crate::commands::copy::get_total_size(std::slice::from_ref(src), true, args, &[])
-                    .await?;
+        } else if src.is_dir() && args.common.is_recursive() {
+            let (dir, jobs) = (src.clone(), args.common.scan_jobs());
+            total_size += tokio::task::spawn_blocking(move || {
+                traversal::sum_tree(&dir, &[], jobs, |_, _| Ok(()))
+            })
+            .await??
+            .bytes;
         } else if src.is_dir() {
             bail!(
                 "Source '{}' is a directory. Use -r flag for recursive copy.",
@@ -37,7 +41,7 @@ pub(super) async fn handle_remote_upload(
         }
     }
 
-    if args.is_dry_run() {
+    if args.common.is_dry_run() {
         println!(
             "Dry-run: would upload {} to {}",
             format_bytes(total_size as f64),
@@ -50,7 +54,7 @@ pub(super) async fn handle_remote_upload(
                     src.display(),
                     resolve_upload_remote(src, rdest, sources.len() > 1)
                 );
-            } else if src.is_dir() && args.is_recursive() {
+            } else if src.is_dir() && args.common.is_recursive() {
                 let dir_remote = rdest.join(&src.file_name().unwrap_or_default().to_string_lossy());
                 for item in collect_upload_files(src, &dir_remote, &excludes)? {
                     println!("  {} -> {}", item.local_path.display(), item.remote);
@@ -64,7 +68,7 @@ pub(super) async fn handle_remote_upload(
 
     let runner = ProgressRunner::new(
         total_size,
-        is_plain_mode(args),
+        args.common.progress_style(),
         false,
         crate::config::is_json_mode(),
         crate::commands::copy::cleanup_partial_files,
@@ -85,16 +89,14 @@ pub(super) async fn handle_remote_upload(
                     size: src.metadata()?.len(),
                     is_upload: true,
                 });
-            } else if src.is_dir() && args.is_recursive() {
+            } else if src.is_dir() && args.common.is_recursive() {
                 let dir_remote = rdest.join(&src.file_name().unwrap_or_default().to_string_lossy());
                 remote::ensure_remote_tree(src, &dir_remote).await?;
                 items.extend(collect_upload_files(src, &dir_remote, &excludes)?);
             }
         }
 
-        run_parallel_transfers(items, parallel, runner.progress(), &opts)
-            .await
-            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        run_parallel_transfers(items, parallel, runner.progress(), &opts).await?;
     } else {
         for src in sources {
             if src.is_file() {
@@ -111,7 +113,7 @@ pub(super) async fn handle_remote_upload(
                     None,
                 )
                 .await?;
-            } else if src.is_dir() && args.is_recursive() {
+            } else if src.is_dir() && args.common.is_recursive() {
                 let dir_remote = rdest.join(&src.file_name().unwrap_or_default().to_string_lossy());
                 remote::upload_directory(
                     src,
@@ -133,7 +135,7 @@ pub(super) async fn handle_remote_upload(
 }
 
 pub(super) async fn handle_remote_download(
-    args: &Commands,
+    args: &CopyArgs,
     sources: &[std::path::PathBuf],
     dest_local: &std::path::Path,
     parallel: usize,
@@ -146,13 +148,13 @@ pub(super) async fn handle_remote_download(
         let rsrc = parse_remote_path(&src.to_string_lossy()).ok_or_else(|| {
             anyhow::anyhow!("Mixed local/remote sources without remote destination")
         })?;
-        let size = remote::remote_total_size(&rsrc, args.is_recursive()).await?;
+        let size = remote::remote_total_size(&rsrc, args.common.is_recursive()).await?;
         remote_sources.push((rsrc, size));
     }
 
     let total_size: u64 = remote_sources.iter().map(|(_, s)| *s).sum();
 
-    if args.is_dry_run() {
+    if args.common.is_dry_run() {
         println!(
             "Dry-run: would download {} to {}",
             format_bytes(total_size as f64),
@@ -160,7 +162,7 @@ pub(super) async fn handle_remote_download(
         );
         for (rsrc, _) in &remote_sources {
             let info = remote::remote_stat(rsrc).await?;
-            if info.is_dir && args.is_recursive() {
+            if info.is_dir && args.common.is_recursive() {
                 let dir_name = rsrc.path.rsplit('/').next().unwrap_or(&rsrc.path);
                 let local_dir = if dest_local.is_dir() {
                     dest_local.join(dir_name)
@@ -181,7 +183,7 @@ pub(super) async fn handle_remote_download(
                     println!(
                         "  {} -> {}",
                         rsrc.join(rel_path),
-                        local_dir.join(rel_path).display()
+                        confine::local_join(&local_dir, rel_path)?.display()
                     );
                 }
             } else {
@@ -200,7 +202,7 @@ pub(super) async fn handle_remote_download(
 
     let runner = ProgressRunner::new(
         total_size,
-        is_plain_mode(args),
+        args.common.progress_style(),
         false,
         crate::config::is_json_mode(),
         crate::commands::copy::cleanup_partial_files,
@@ -214,7 +216,7 @@ pub(super) async fn handle_remote_download(
         for (rsrc, _) in &remote_sources {
             let info = remote::remote_stat(rsrc).await?;
             if info.is_dir {
-                if !args.is_recursive() {
+                if !args.common.is_recursive() {
                     bail!("Remote source '{}' is a directory. Use -r flag.", rsrc);
                 }
                 let dir_name = rsrc.path.rsplit('/').next().unwrap_or(&rsrc.path);
@@ -231,7 +233,8 @@ pub(super) async fn handle_remote_download(
                             &excludes,
                         )
                     {
-                        tokio::fs::create_dir_all(local_dir.join(rel_path)).await?;
+                        tokio::fs::create_dir_all(confine::local_join(&local_dir, rel_path)?)
+                            .await?;
                     }
                 }
                 for (rel_path, size, is_dir_entry) in &entries {
@@ -245,7 +248,7 @@ pub(super) async fn handle_remote_download(
                         continue;
                     }
                     items.push(TransferItem {
-                        local_path: local_dir.join(rel_path),
+                        local_path: confine::local_join(&local_dir, rel_path)?,
                         remote: rsrc.join(rel_path),
                         size: *size,
                         is_upload: false,
@@ -266,9 +269,7 @@ pub(super) async fn handle_remote_download(
             }
         }
 
-        run_parallel_transfers(items, parallel, runner.progress(), &opts)
-            .await
-            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        run_parallel_transfers(items, parallel, runner.progress(), &opts).await?;
     } else {
         for (rsrc, _) in &remote_sources {
             let info = remote::remote_stat(rsrc).await?;
@@ -277,7 +278,7 @@ pub(super) async fn handle_remote_download(
             let file_cb = runner.file_callback();
 
             if info.is_dir {
-                if !args.is_recursive() {
+                if !args.common.is_recursive() {
                     bail!("Remote source '{}' is a directory. Use -r flag.", rsrc);
                 }
                 let dir_name = rsrc.path.rsplit('/').next().unwrap_or(&rsrc.path);
@@ -0,0 +1,218 @@
+use super::transfer_options_from_cli;
+use crate::cli::CopyArgs;
+use crate::config::CONFIG;
+use crate::core::error::BcmrError;
+use crate::core::remote::{self, confine, parse_remote_path, RemotePath};
+use crate::ui::runner::ProgressRunner;
+use crate::ui::utils::format_bytes;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+/// Where `src` lands under `rdest`: inside it when it is a directory (or
+/// several sources share it), else at `rdest` itself.
+fn relay_target(src: &RemotePath, rdest: &RemotePath, into_dir: bool) -> RemotePath {
+    if !into_dir {
+        return rdest.clone();
+    }
+    let name = src
+        .path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(&src.path);
+    let base = rdest.path.trim_end_matches('/');
+    RemotePath {
+        user: rdest.user.clone(),
+        host: rdest.host.clone(),
+        path: if base.is_empty() && rdest.path.starts_with('/') {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", base, name)
+        },
+    }
+}
+
+fn refusal(src: &RemotePath, rdest: &RemotePath) -> BcmrError {
+    let staged = relay_target(
+        src,
+        &RemotePath {
+            user: None,
+            host: String::new(),
+            path: "./staging".to_string(),
+        },
+        true,
+    );
+    let dst = relay_target(src, rdest, rdest.path.ends_with('/') || rdest.path == ".");
+    BcmrError::InvalidInput(format!(
+        "Not relaying {} -> {} through this machine (--no-remote-relay). \
+         Copy in two steps instead: `bcmr copy -r {} ./staging/ && bcmr copy -r {} {}`, \
+         or pipe a single file: `ssh {} cat '{}' | ssh {} 'cat > {}'`",
+        src,
+        rdest,
+        src,
+        staged.path,
+        rdest,
+        src.ssh_target(),
+        src.path,
+        dst.ssh_target(),
+        dst.path
+    ))
+}
+
+/// Copies between two remote hosts by streaming every file through this
+/// machine; neither host needs to reach the other.
+pub(super) async fn handle_remote_relay(
+    args: &CopyArgs,
+    sources: &[std::path::PathBuf],
+    rdest: &RemotePath,
+    excludes: &[regex::Regex],
+) -> Result<()> {
+    let rsources: Vec<RemotePath> = sources
+        .iter()
+        .filter_map(|s| parse_remote_path(&s.to_string_lossy()))
+        .filter(|r| !crate::core::traversal::is_excluded(std::path::Path::new(&r.path), excludes))
+        .collect();
+
+    if args.no_remote_relay {
+        if let Some(first) = rsources.first() {
+            return Err(refusal(first, rdest).into());
+        }
+    }
+    if args.common.is_resume() || args.common.is_strict() || args.common.is_append() {
+        bail!("--resume/--strict/--append are not supported when relaying between remote hosts");
+    }
+
+    let hosts: BTreeMap<String, &RemotePath> = rsources
+        .iter()
+        .chain(std::iter::once(rdest))
+        .map(|r| (r.ssh_target(), r))
+        .collect();
+    for host in hosts.values() {
+        remote::validate_ssh_connection(host).await?;
+    }
+
+    let mut plan = Vec::new();
+    for rsrc in &rsources {
+        let info = remote::remote_stat(rsrc).await?;
+        let size = if info.is_dir {
+            remote::remote_total_size(rsrc, args.common.is_recursive()).await?
+        } else {
+            info.size
+        };
+        plan.push((rsrc, info.is_dir, size));
+    }
+    let total_size: u64 = plan.iter().map(|(_, _, s)| *s).sum();
+
+    let guard = confine::DestinationGuard::scan(rdest, CONFIG.scp.allowed_root.as_deref()).await?;
+    let into_dir = rsources.len() > 1
+        || rdest.path.ends_with('/')
+        || rdest.path == "."
+        || remote::remote_stat(rdest)
+            .await
+            .map(|i| i.is_dir)
+            .unwrap_or(false);
+
+    if args.common.is_dry_run() {
+        println!(
+            "Dry-run: would relay {} to {}",
+            format_bytes(total_size as f64),
+            rdest
+        );
+        for (rsrc, is_dir, _) in &plan {
+            let dst = relay_target(rsrc, rdest, into_dir);
+            if !*is_dir {
+                println!("  {} -> {}", rsrc, dst);
+                continue;
+            }
+            for (rel_path, _, is_dir_entry) in remote::remote_list_files(rsrc).await? {
+                if is_dir_entry
+                    || crate::core::traversal::is_excluded(
+                        std::path::Path::new(&rel_path),
+                        excludes,
+                    )
+                {
+                    continue;
+                }
+                println!(
+                    "  {} -> {}",
+                    rsrc.join(&rel_path),
+                    confine::join(&dst, &rel_path)?
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let opts = transfer_options_from_cli(args);
+
+    let runner = ProgressRunner::new(
+        total_size,
+        args.common.progress_style(),
+        false,
+        crate::config::is_json_mode(),
+        crate::commands::copy::cleanup_partial_files,
+    )?;
+    runner.progress().lock().set_operation_type("Relaying");
+
+    for (rsrc, is_dir, size) in &plan {
+        let dst = relay_target(rsrc, rdest, into_dir);
+        let cb = remote::TransferCallbacks {
+            on_progress: &runner.inc_callback(),
+            on_skip: &runner.skip_callback(),
+            on_new_file: &runner.file_callback(),
+        };
+        if *is_dir {
+            remote::relay_directory(rsrc, &dst, cb, excludes, &opts, &guard).await?;
+        } else {
+            guard.check(&dst.path)?;
+            remote::relay_file(rsrc, &dst, cb, *size, &opts).await?;
+        }
+    }
+
+    runner.finish_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rp(s: &str) -> RemotePath {
+        parse_remote_path(s).unwrap()
+    }
+
+    #[test]
+    fn test_relay_target_into_directory() {
+        let src = rp("host1:data/a.txt");
+        assert_eq!(
+            relay_target(&src, &rp("host2:b/"), true).display(),
+            "host2:b/a.txt"
+        );
+        assert_eq!(
+            relay_target(&src, &rp("host2:/"), true).display(),
+            "host2:/a.txt"
+        );
+        assert_eq!(
+            relay_target(&rp("host1:dir/"), &rp("u@host2:b"), true).display(),
+            "u@host2:b/dir"
+        );
+    }
+
+    #[test]
+    fn test_relay_target_renames_single_source() {
+        assert_eq!(
+            relay_target(&rp("host1:a"), &rp("host2:b"), false).display(),
+            "host2:b"
+        );
+    }
+
+    #[test]
+    fn test_refusal_suggests_two_steps() {
+        let msg = refusal(&rp("host1:a"), &rp("host2:b/")).to_string();
+        assert!(msg.contains("--no-remote-relay"), "{msg}");
+        assert!(msg.contains("bcmr copy -r ./staging/a host2:b/"), "{msg}");
+        assert!(
+            msg.contains("ssh host1 cat 'a' | ssh host2 'cat > b/a'"),
+            "{msg}"
+        );
+    }
+}
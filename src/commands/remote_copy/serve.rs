@@ -1,7 +1,10 @@
-use super::{is_plain_mode, transfer_options_from_cli, STRIPING_MIN_FILE_SIZE};
-use crate::cli::Commands;
-use crate::core::remote::{check_resume_state, parse_remote_path, RemotePath, ResumeDecision};
+use super::{resolve_upload_remote, transfer_options_from_cli, STRIPING_MIN_FILE_SIZE};
+use crate::cli::CopyArgs;
+use crate::core::remote::{
+    check_resume_state, confine, parse_remote_path, RemotePath, ResumeDecision,
+};
 use crate::core::serve_client::{FileTransfer, ServeClientPool};
+use crate::core::traversal;
 use crate::ui::runner::ProgressRunner;
 use anyhow::{bail, Result};
 use std::path::{Path, PathBuf};
@@ -14,7 +17,7 @@ enum UploadDecision {
 
 async fn upload_resume_offset(
     pool: &mut ServeClientPool,
-    args: &Commands,
+    args: &CopyArgs,
     local_src: &Path,
     remote_path: &str,
     local_size: u64,
@@ -44,14 +47,20 @@ async fn upload_resume_offset(
         },
         async move || {
             let p = local_clone.clone();
-            let h = tokio::task::spawn_blocking(move || crate::core::checksum::calculate_hash(&p))
-                .await??;
+            let h = tokio::task::spawn_blocking(move || {
+                crate::core::checksum::calculate_hash(&p, crate::core::checksum::Algorithm::Blake3)
+            })
+            .await??;
             Ok(h)
         },
         async move |limit| {
             let p = local_src.to_path_buf();
             let h = tokio::task::spawn_blocking(move || {
-                crate::core::checksum::calculate_partial_hash(&p, limit)
+                crate::core::checksum::calculate_partial_hash(
+                    &p,
+                    limit,
+                    crate::core::checksum::Algorithm::Blake3,
+                )
             })
             .await??;
             Ok(h)
@@ -84,21 +93,22 @@ fn to_upload_decision(d: ResumeDecision) -> UploadDecision {
 }
 
 pub(super) async fn handle_serve_upload(
-    args: &Commands,
+    args: &CopyArgs,
     sources: &[PathBuf],
     rdest: &RemotePath,
     ssh_target: &str,
     excludes: &[regex::Regex],
     parallel: usize,
 ) -> Result<()> {
-    let mut pool = if args.use_direct_tcp() {
-        ServeClientPool::connect_direct_with_caps(ssh_target, args.protocol_caps(), parallel).await
+    let mut pool = if args.common.use_direct_tcp() {
+        ServeClientPool::connect_direct_with_caps(ssh_target, args.common.protocol_caps(), parallel)
+            .await
     } else {
-        ServeClientPool::connect_with_caps(ssh_target, args.protocol_caps(), parallel).await
+        ServeClientPool::connect_with_caps(ssh_target, args.common.protocol_caps(), parallel).await
     }
     .map_err(|e| anyhow::anyhow!("serve unavailable: {}", e))?;
 
-    if args.is_dry_run() {
+    if args.common.is_dry_run() {
         pool.close().await?;
         return Err(anyhow::anyhow!("serve: dry-run fallback to legacy"));
     }
@@ -107,16 +117,19 @@ pub(super) async fn handle_serve_upload(
     for src in sources {
         if src.is_file() {
             total_size += src.metadata()?.len();
-        } else if src.is_dir() && args.is_recursive() {
-            total_size +=
-                crate::commands::copy::get_total_size(std::slice::from_ref(src), true, args, &[])
-                    .await?;
+        } else if src.is_dir() && args.common.is_recursive() {
+            let (dir, jobs) = (src.clone(), args.common.scan_jobs());
+            total_size += tokio::task::spawn_blocking(move || {
+                traversal::sum_tree(&dir, &[], jobs, |_, _| Ok(()))
+            })
+            .await??
+            .bytes;
         }
     }
 
     let runner = ProgressRunner::new(
         total_size,
-        is_plain_mode(args),
+        args.common.progress_style(),
         false,
         crate::config::is_json_mode(),
         crate::commands::copy::cleanup_partial_files,
@@ -132,15 +145,7 @@ pub(super) async fn handle_serve_upload(
             continue;
         }
         if src.is_file() {
-            let remote_path = if multi_source || rdest.path.ends_with('/') {
-                format!(
-                    "{}/{}",
-                    rdest.path,
-                    src.file_name().unwrap_or_default().to_string_lossy()
-                )
-            } else {
-                rdest.path.clone()
-            };
+            let remote_path = resolve_upload_remote(src, rdest, multi_source).path;
             let size = src.metadata()?.len();
             (runner.file_callback())(&src.file_name().unwrap_or_default().to_string_lossy(), size);
 
@@ -155,20 +160,23 @@ pub(super) async fn handle_serve_upload(
                 _ => 0,
             };
 
-            let use_stripe = args.use_direct_tcp()
+            let use_stripe = args.common.use_direct_tcp()
                 && pool.len() > 1
                 && size >= STRIPING_MIN_FILE_SIZE
-                && !args.is_verify()
+                && !args.common.is_verify()
                 && offset == 0;
 
             if use_stripe {
                 let _ = pool.striped_put_file(src, &remote_path).await?;
             } else if offset > 0 {
                 pool.first_mut().put_at(&remote_path, src, offset).await?;
-                if args.is_verify() {
+                if args.common.is_verify() {
                     let p = src.to_path_buf();
                     let local_hash = tokio::task::spawn_blocking(move || {
-                        crate::core::checksum::calculate_hash(&p)
+                        crate::core::checksum::calculate_hash(
+                            &p,
+                            crate::core::checksum::Algorithm::Blake3,
+                        )
                     })
                     .await??;
                     let remote_hash = pool.first_mut().hash(&remote_path, 0, None).await?;
@@ -181,10 +189,13 @@ pub(super) async fn handle_serve_upload(
                 }
             } else {
                 let server_hash = pool.first_mut().put(&remote_path, src).await?;
-                if args.is_verify() {
+                if args.common.is_verify() {
                     let p = src.to_path_buf();
                     let local_hash = tokio::task::spawn_blocking(move || {
-                        crate::core::checksum::calculate_hash(&p)
+                        crate::core::checksum::calculate_hash(
+                            &p,
+                            crate::core::checksum::Algorithm::Blake3,
+                        )
                     })
                     .await??;
                     let server_hex: String =
@@ -195,7 +206,7 @@ pub(super) async fn handle_serve_upload(
                     }
                 }
             }
-            if args.is_preserve() {
+            if args.common.is_preserve() {
                 let target = RemotePath {
                     user: rdest.user.clone(),
                     host: rdest.host.clone(),
@@ -204,8 +215,8 @@ pub(super) async fn handle_serve_upload(
                 crate::core::remote::preserve_remote_attrs(src, &target).await?;
             }
             (runner.inc_callback())(size);
-        } else if src.is_dir() && args.is_recursive() {
-            if args.is_resume() || args.is_strict() || args.is_append() {
+        } else if src.is_dir() && args.common.is_recursive() {
+            if args.common.is_resume() || args.common.is_strict() || args.common.is_append() {
                 pool.close().await?;
                 return Err(anyhow::anyhow!(
                     "serve: recursive --resume/--strict/--append not supported, fallback to legacy"
@@ -225,7 +236,7 @@ async fn serve_upload_dir(
     remote_base: &RemotePath,
     runner: &ProgressRunner,
     excludes: &[regex::Regex],
-    args: &Commands,
+    args: &CopyArgs,
 ) -> Result<()> {
     let dir_name = local_dir.file_name().unwrap_or_default().to_string_lossy();
     let remote_dir = format!("{}/{}", remote_base.path, dir_name);
@@ -264,19 +275,20 @@ async fn serve_upload_dir(
         })
         .await?;
 
-    if args.is_verify() {
+    if args.common.is_verify() {
         for ((local_path, _), server_hash) in per_file_inputs.iter().zip(server_hashes.iter()) {
             let p = local_path.clone();
-            let local_hash =
-                tokio::task::spawn_blocking(move || crate::core::checksum::calculate_hash(&p))
-                    .await??;
+            let local_hash = tokio::task::spawn_blocking(move || {
+                crate::core::checksum::calculate_hash(&p, crate::core::checksum::Algorithm::Blake3)
+            })
+            .await??;
             let server_hex: String = server_hash.iter().map(|b| format!("{:02x}", b)).collect();
             if server_hex != local_hash {
                 bail!("hash mismatch for {}", local_path.display());
             }
         }
     }
-    if args.is_preserve() {
+    if args.common.is_preserve() {
         for (local_path, remote_path) in &per_file_inputs {
             let target = RemotePath {
                 user: remote_base.user.clone(),
@@ -290,28 +302,29 @@ async fn serve_upload_dir(
 }
 
 pub(super) async fn handle_serve_download(
-    args: &Commands,
+    args: &CopyArgs,
     sources: &[PathBuf],
     dest: &std::path::Path,
     ssh_target: &str,
     excludes: &[regex::Regex],
     parallel: usize,
 ) -> Result<()> {
-    let mut pool = if args.use_direct_tcp() {
-        ServeClientPool::connect_direct_with_caps(ssh_target, args.protocol_caps(), parallel).await
+    let mut pool = if args.common.use_direct_tcp() {
+        ServeClientPool::connect_direct_with_caps(ssh_target, args.common.protocol_caps(), parallel)
+            .await
     } else {
-        ServeClientPool::connect_with_caps(ssh_target, args.protocol_caps(), parallel).await
+        ServeClientPool::connect_with_caps(ssh_target, args.common.protocol_caps(), parallel).await
     }
     .map_err(|e| anyhow::anyhow!("serve unavailable: {}", e))?;
 
-    if args.is_resume() || args.is_strict() || args.is_append() {
+    if args.common.is_resume() || args.common.is_strict() || args.common.is_append() {
         pool.close().await?;
         return Err(anyhow::anyhow!(
             "serve: download --resume/--strict/--append not yet supported, fallback to legacy"
         ));
     }
 
-    if args.is_dry_run() {
+    if args.common.is_dry_run() {
         pool.close().await?;
         return Err(anyhow::anyhow!("serve: dry-run fallback to legacy"));
     }
@@ -333,7 +346,7 @@ pub(super) async fn handle_serve_download(
         let src_str = src.to_string_lossy();
         if let Some(rp) = parse_remote_path(&src_str) {
             let (size, _mtime, is_dir) = pool.first_mut().stat(&rp.path).await?;
-            if is_dir && args.is_recursive() {
+            if is_dir && args.common.is_recursive() {
                 let entries = pool.first_mut().list(&rp.path).await?;
                 let dir_name = rp.path.rsplit('/').next().unwrap_or(&rp.path);
                 let local_base = dest.join(dir_name);
@@ -350,7 +363,7 @@ pub(super) async fn handle_serve_download(
                     ) {
                         continue;
                     }
-                    let local = local_base.join(&entry.path);
+                    let local = confine::local_join(&local_base, &entry.path)?;
                     let remote = format!("{}/{}", rp.path, entry.path);
                     if entry.is_dir {
                         items.push(DownloadItem {
@@ -388,7 +401,7 @@ pub(super) async fn handle_serve_download(
 
     let runner = ProgressRunner::new(
         total_size,
-        is_plain_mode(args),
+        args.common.progress_style(),
         false,
         crate::config::is_json_mode(),
         crate::commands::copy::cleanup_partial_files,
@@ -398,7 +411,7 @@ pub(super) async fn handle_serve_download(
         .lock()
         .set_operation_type("Downloading (serve)");
 
-    let use_stripe = args.use_direct_tcp() && pool.len() > 1 && !args.is_verify();
+    let use_stripe = args.common.use_direct_tcp() && pool.len() > 1 && !args.common.is_verify();
     let mut big_files: Vec<(String, PathBuf, u64)> = Vec::new();
     let mut files_to_get: Vec<FileTransfer> = Vec::new();
     for item in &items {
@@ -423,7 +436,7 @@ pub(super) async fn handle_serve_download(
         let _ = pool
             .striped_get_file(remote_path, local_path, *size)
             .await?;
-        if args.is_sync() {
+        if args.common.is_sync() {
             let f = tokio::fs::File::open(local_path).await?;
             crate::core::io::durable_sync_async(&f).await?;
         }
@@ -433,7 +446,7 @@ pub(super) async fn handle_serve_download(
     if !files_to_get.is_empty() {
         let file_cb = runner.file_callback();
         let inc = runner.inc_callback();
-        let sync = args.is_sync();
+        let sync = args.common.is_sync();
         pool.pipelined_get_files_striped(
             files_to_get,
             sync,
@@ -448,15 +461,16 @@ pub(super) async fn handle_serve_download(
         .await?;
     }
 
-    if args.is_verify() {
+    if args.common.is_verify() {
         for item in &items {
             if item.is_dir {
                 continue;
             }
             let p = item.local_path.clone();
-            let local_hash =
-                tokio::task::spawn_blocking(move || crate::core::checksum::calculate_hash(&p))
-                    .await??;
+            let local_hash = tokio::task::spawn_blocking(move || {
+                crate::core::checksum::calculate_hash(&p, crate::core::checksum::Algorithm::Blake3)
+            })
+            .await??;
             let remote_hash = pool.first_mut().hash(&item.remote_path, 0, None).await?;
             let remote_hex: String = remote_hash.iter().map(|b| format!("{:02x}", b)).collect();
             if remote_hex != local_hash {
@@ -466,7 +480,7 @@ pub(super) async fn handle_serve_download(
             }
         }
     }
-    if args.is_preserve() {
+    if args.common.is_preserve() {
         let (user, host) = match ssh_target.split_once('@') {
             Some((u, h)) => (Some(u.to_string()), h.to_string()),
             None => (None, ssh_target.to_string()),
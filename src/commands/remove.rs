@@ -1,14 +1,16 @@
-use crate::cli::{Commands, TestMode};
+use crate::cli::{PreserveRoot, RemoveArgs, TestMode};
 use crate::core::error::BcmrError;
+use crate::core::oplog::{self, Action};
 use crate::core::traversal;
 use crate::ui::display::{print_dry_run, ActionType};
-use crate::ui::progress::ProgressRenderer;
+use crate::ui::progress::{ProgressRenderer, ProgressStyle};
 
 use parking_lot::Mutex;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 
 pub struct FileToRemove {
     pub path: PathBuf,
@@ -16,11 +18,132 @@ pub struct FileToRemove {
     pub size: u64,
 }
 
+/// Why a directory stays behind under --one-file-system.
+enum Kept {
+    OtherDevice,
+    Above,
+}
+
+/// With --one-file-system, a directory mounted from another file system
+/// stays, and so does everything above it, since those can't end up empty.
+/// A directory that cannot be read stays the same way, flag or not.
+struct DeviceFence {
+    root: Option<u64>,
+    kept: Vec<PathBuf>,
+}
+
+impl DeviceFence {
+    fn new(root: &std::fs::Metadata, one_file_system: bool) -> Self {
+        Self {
+            root: traversal::device_id(root).filter(|_| one_file_system),
+            kept: Vec::new(),
+        }
+    }
+
+    fn keeps(&mut self, entry: &walkdir::DirEntry) -> Option<Kept> {
+        if !entry.file_type().is_dir() {
+            return None;
+        }
+        if self.kept.iter().any(|k| k.starts_with(entry.path())) {
+            return Some(Kept::Above);
+        }
+        let root = self.root?;
+        let device = entry.metadata().ok().and_then(|m| traversal::device_id(&m));
+        if device.is_some_and(|d| d != root) {
+            self.kept.push(entry.path().to_path_buf());
+            return Some(Kept::OtherDevice);
+        }
+        None
+    }
+
+    /// Whether the walk of `top` can go on past `err`, keeping the entry it
+    /// could not read (see `traversal::skip_unreadable`).
+    fn passes_over(&mut self, err: &walkdir::Error, top: &Path) -> bool {
+        if !traversal::skip_unreadable(err, top) {
+            return false;
+        }
+        self.kept.extend(err.path().map(Path::to_path_buf));
+        true
+    }
+}
+
+/// What the removal loop gets from the walk, found off the runtime: the
+/// entries past the --one-file-system fence, each with its size.
+fn removal_entries(
+    root: PathBuf,
+    root_md: std::fs::Metadata,
+    one_file_system: bool,
+    excludes: Vec<regex::Regex>,
+) -> impl Iterator<Item = std::result::Result<(walkdir::DirEntry, u64), BcmrError>> {
+    let mut fence = DeviceFence::new(&root_md, one_file_system);
+    traversal::walk_for_removal(&root, one_file_system, &excludes)
+        .filter_map(move |entry| sized_past_fence(&mut fence, &root, entry).transpose())
+}
+
+fn sized_past_fence(
+    fence: &mut DeviceFence,
+    root: &Path,
+    entry: walkdir::Result<walkdir::DirEntry>,
+) -> std::result::Result<Option<(walkdir::DirEntry, u64)>, BcmrError> {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(e) if fence.passes_over(&e, root) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match fence.keeps(&entry) {
+        Some(Kept::OtherDevice) => {
+            oplog::warn(entry.path(), "on a different file system, skipped");
+            return Ok(None);
+        }
+        Some(Kept::Above) => return Ok(None),
+        None => {}
+    }
+    let size = if entry.file_type().is_file() {
+        entry.metadata()?.len()
+    } else {
+        0
+    };
+    Ok(Some((entry, size)))
+}
+
+/// rm's --preserve-root: no recursive removal of `/`, and with `all` none
+/// of a mount point either.
+fn check_preserve_root(
+    path: &Path,
+    md: &std::fs::Metadata,
+    preserve: PreserveRoot,
+) -> std::result::Result<(), BcmrError> {
+    let Ok(resolved) = path.canonicalize() else {
+        return Ok(());
+    };
+    let Some(parent) = resolved.parent() else {
+        return Err(BcmrError::InvalidInput(format!(
+            "It is dangerous to remove '{}' recursively (use --no-preserve-root to override)",
+            path.display()
+        )));
+    };
+    if preserve == PreserveRoot::All {
+        let parent_device = parent
+            .symlink_metadata()
+            .ok()
+            .and_then(|m| traversal::device_id(&m));
+        if parent_device.is_some_and(|d| Some(d) != traversal::device_id(md)) {
+            return Err(BcmrError::InvalidInput(format!(
+                "Cannot remove '{}': It is on a different device from its parent (--preserve-root=all)",
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
 fn check_removes_sync(
     paths: Vec<PathBuf>,
     recursive: bool,
     dir_only: bool,
     force: bool,
+    one_file_system: bool,
+    preserve_root: Option<PreserveRoot>,
     excludes: Vec<regex::Regex>,
 ) -> std::result::Result<Vec<FileToRemove>, BcmrError> {
     let mut files_to_remove = Vec::new();
@@ -36,6 +159,10 @@ fn check_removes_sync(
             Err(_) => return Err(BcmrError::SourceNotFound(path.to_path_buf())),
         };
 
+        if let Some(preserve) = preserve_root.filter(|_| recursive && md.is_dir()) {
+            check_preserve_root(&path, &md, preserve)?;
+        }
+
         if md.is_dir() {
             if !recursive && !dir_only {
                 return Err(BcmrError::InvalidInput(format!(
@@ -61,8 +188,16 @@ fn check_removes_sync(
             }
 
             if recursive {
-                for entry in traversal::walk(&path, true, true, 0, &excludes) {
-                    let entry = entry?;
+                let mut fence = DeviceFence::new(&md, one_file_system);
+                for entry in traversal::walk_for_removal(&path, one_file_system, &excludes) {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(e) if fence.passes_over(&e, &path) => continue,
+                        Err(e) => return Err(e.into()),
+                    };
+                    if fence.keeps(&entry).is_some() {
+                        continue;
+                    }
                     let entry_path = entry.path();
                     let ft = entry.file_type();
 
@@ -93,16 +228,26 @@ fn check_removes_sync(
 pub async fn check_removes(
     paths: &[PathBuf],
     recursive: bool,
-    cli: &Commands,
+    cli: &RemoveArgs,
     excludes: &[regex::Regex],
 ) -> std::result::Result<Vec<FileToRemove>, BcmrError> {
     let paths = paths.to_vec();
     let dir_only = cli.is_dir_only();
     let force = cli.is_force();
+    let one_file_system = cli.is_one_file_system();
+    let preserve_root = cli.preserve_root();
     let excludes = excludes.to_vec();
 
     tokio::task::spawn_blocking(move || {
-        check_removes_sync(paths, recursive, dir_only, force, excludes)
+        check_removes_sync(
+            paths,
+            recursive,
+            dir_only,
+            force,
+            one_file_system,
+            preserve_root,
+            excludes,
+        )
     })
     .await?
 }
@@ -117,7 +262,14 @@ async fn confirm_remove(
         execute,
         terminal::{disable_raw_mode, enable_raw_mode},
     };
-    use std::io::{self, Write};
+    use std::io::{self, IsTerminal, Write};
+
+    // As with the up-front prompt, no terminal means nobody to answer.
+    if !io::stdin().is_terminal() {
+        return Err(BcmrError::InvalidInput(
+            "confirmation required but no TTY; pass --yes".into(),
+        ));
+    }
 
     let mut stdout = io::stdout();
     if restore_raw {
@@ -153,17 +305,6 @@ async fn report_progress(size: u64, test_mode: &TestMode, callback: &(impl Fn(u6
             callback(size);
             tokio::time::sleep(Duration::from_millis(*ms)).await;
         }
-        TestMode::SpeedLimit(bps) => {
-            let mut remaining = size;
-            while remaining > 0 {
-                let chunk = remaining.min(*bps);
-                callback(chunk);
-                remaining -= chunk;
-                if remaining > 0 {
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                }
-            }
-        }
         TestMode::None => {
             callback(size);
         }
@@ -172,19 +313,25 @@ async fn report_progress(size: u64, test_mode: &TestMode, callback: &(impl Fn(u6
 
 pub async fn remove_path(
     path: &Path,
-    is_dir: bool,
-    cli: &Commands,
+    cli: &RemoveArgs,
     excludes: &[regex::Regex],
     progress_state: Arc<Mutex<ProgressState>>,
     progress_callback: impl Fn(u64) + Send + Sync,
     on_new_file: impl Fn(&str, u64) + Send + Sync,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError> {
     let test_mode = cli.get_test_mode();
     if traversal::is_excluded(path, excludes) {
         return Ok(());
     }
+    if cancel.is_cancelled() {
+        return Err(BcmrError::Cancelled);
+    }
+    let is_dir = fs::metadata(path).await.is_ok_and(|md| md.is_dir());
 
-    let is_tui = cli.is_tui_mode();
+    // A dry run never draws a renderer, so there is no raw mode to restore
+    // around the prompt.
+    let is_tui = cli.progress_style() == ProgressStyle::Inline && !cli.is_dry_run();
     if cli.is_interactive() && !cli.is_force() && !confirm_remove(path, is_dir, is_tui).await? {
         return Ok(());
     }
@@ -195,7 +342,7 @@ pub async fn remove_path(
         .to_string_lossy()
         .to_string();
 
-    let md = match path.symlink_metadata() {
+    let md = match fs::symlink_metadata(path).await {
         Ok(m) => m,
         Err(_) if cli.is_force() => return Ok(()),
         Err(e) => return Err(e.into()),
@@ -204,17 +351,19 @@ pub async fn remove_path(
     if md.is_dir() && (cli.is_recursive() || cli.is_dir_only()) {
         on_new_file(&file_name, 0);
 
-        for entry in traversal::walk(path, true, true, 0, excludes) {
-            let entry = entry?;
+        let mut entries = traversal::in_background({
+            let (root, root_md) = (path.to_path_buf(), md.clone());
+            let (one_file_system, excludes) = (cli.is_one_file_system(), excludes.to_vec());
+            move || removal_entries(root, root_md, one_file_system, excludes)
+        });
+        while let Some(entry) = entries.recv().await {
+            if cancel.is_cancelled() {
+                return Err(BcmrError::Cancelled);
+            }
+            let (entry, size) = entry?;
             let entry_path = entry.path();
             let ft = entry.file_type();
 
-            let size = if ft.is_file() {
-                entry.metadata()?.len()
-            } else {
-                0
-            };
-
             if cli.is_interactive()
                 && !cli.is_force()
                 && !confirm_remove(entry_path, ft.is_dir(), is_tui).await?
@@ -222,16 +371,11 @@ pub async fn remove_path(
                 continue;
             }
 
-            let entry_name = entry_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
-
+            let entry_name = traversal::display_relative(entry_path, path);
             on_new_file(&entry_name, size);
 
             if !cli.is_dry_run() {
-                if ft.is_dir() {
+                if ft.is_dir() || traversal::is_dir_link(&ft) {
                     fs::remove_dir(entry_path).await?;
                 } else {
                     fs::remove_file(entry_path).await?;
@@ -248,8 +392,8 @@ pub async fn remove_path(
                 progress_state.lock().inc_processed();
             }
 
-            if cli.is_verbose() && !cli.is_dry_run() {
-                println!("removed {}", entry_path.display());
+            if !cli.is_dry_run() {
+                oplog::report(Action::Remove { path: entry_path }, cli.is_verbose());
             }
         }
     } else if md.is_dir() {
@@ -270,12 +414,14 @@ pub async fn remove_path(
             report_progress(size, &test_mode, &progress_callback).await;
         }
 
-        fs::remove_file(path).await?;
+        if traversal::is_dir_link(&md.file_type()) {
+            fs::remove_dir(path).await?;
+        } else {
+            fs::remove_file(path).await?;
+        }
         progress_state.lock().inc_processed();
 
-        if cli.is_verbose() {
-            println!("removed {}", path.display());
-        }
+        oplog::report(Action::Remove { path }, cli.is_verbose());
     }
 
     Ok(())
@@ -292,7 +438,7 @@ impl ProgressState {
     }
 
     pub fn inc_processed(&mut self) {
-        self.progress.lock().inc_items_processed();
+        self.progress.lock().inc_items_processed(1);
     }
 }
 
@@ -300,27 +446,24 @@ type FileCallback = Box<dyn Fn(&str, u64) + Send + Sync>;
 
 pub async fn remove_paths(
     paths: &[PathBuf],
-    cli: &Commands,
+    cli: &RemoveArgs,
     excludes: &[regex::Regex],
-    progress: Arc<Mutex<Box<dyn ProgressRenderer>>>,
+    progress_state: ProgressState,
     progress_callback: impl Fn(u64) + Send + Sync + Clone + 'static,
     on_new_file: FileCallback,
-    total_items: usize,
+    cancel: &CancellationToken,
 ) -> std::result::Result<(), BcmrError> {
-    let progress_state = Arc::new(Mutex::new(ProgressState::new(
-        total_items,
-        Arc::clone(&progress),
-    )));
+    let progress_state = Arc::new(Mutex::new(progress_state));
 
     for path in paths {
         remove_path(
             path,
-            path.is_dir(),
             cli,
             excludes,
             Arc::clone(&progress_state),
             progress_callback.clone(),
             &*on_new_file,
+            cancel,
         )
         .await?;
     }
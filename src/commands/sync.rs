@@ -0,0 +1,305 @@
+use crate::cli::{CopyArgs, RemoveArgs, SyncArgs, SyncCompare};
+use crate::commands::copy::{self, CopyPlan, PlanEntry, ProgressCallback};
+use crate::commands::remove::{self, ProgressState};
+use crate::commands::verify::{list_in_background, Listing};
+use crate::core::checksum::{self, Algorithm};
+use crate::core::error::BcmrError;
+use crate::core::oplog::{self, Action};
+use crate::core::traversal::{self, EntryKind, ScanEntry};
+use crate::ui::display::{print_dry_run, ActionType};
+
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// One destination path and what the sync does to it.
+pub struct Step {
+    pub action: ActionType,
+    pub relative: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Everything a sync will do, worked out from listings of both trees
+/// before anything is touched.
+pub struct SyncPlan {
+    /// Directories to create and files to copy.
+    pub copy: CopyPlan,
+    /// Destination entries of the wrong kind, removed before copying.
+    pub replace: Vec<PathBuf>,
+    /// Destination entries the source lacks, topmost only. Nothing under
+    /// a source entry the scan could not read counts as lacking.
+    pub extra: Vec<PathBuf>,
+    /// Entries removing `extra` takes away, those under it included.
+    pub extra_items: usize,
+    /// In path order.
+    pub steps: Vec<Step>,
+    pub source_empty: bool,
+}
+
+impl SyncPlan {
+    pub fn count(&self, action: ActionType) -> usize {
+        self.steps.iter().filter(|s| s.action == action).count()
+    }
+
+    pub fn files_to_copy(&self) -> usize {
+        self.copy
+            .entries
+            .iter()
+            .filter(|e| matches!(e, PlanEntry::CopyFile { .. }))
+            .count()
+    }
+}
+
+pub async fn plan(
+    src: &Path,
+    dst: &Path,
+    compare: SyncCompare,
+    excludes: &[regex::Regex],
+) -> Result<SyncPlan, BcmrError> {
+    let src_md = crate::core::io::stat(src)
+        .await
+        .ok_or_else(|| BcmrError::SourceNotFound(src.to_path_buf()))?;
+    if !src_md.is_dir() {
+        return Err(BcmrError::InvalidInput(format!(
+            "Cannot sync '{}': not a directory",
+            src.display()
+        )));
+    }
+    let dst_listing = match crate::core::io::stat(dst).await {
+        None => Listing::new(),
+        Some(md) if md.is_dir() => list_in_background(dst, excludes).await?,
+        Some(_) => {
+            return Err(BcmrError::InvalidInput(format!(
+                "Cannot sync into '{}': not a directory",
+                dst.display()
+            )))
+        }
+    };
+    let src_listing = list_in_background(src, excludes).await?;
+    let unread: Vec<PathBuf> = traversal::skipped_below(src)
+        .iter()
+        .filter_map(|path| path.strip_prefix(src).ok())
+        .map(Path::to_path_buf)
+        .collect();
+
+    let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        diff(&src, &dst, &src_listing, &dst_listing, &unread, compare)
+    })
+    .await?
+}
+
+fn diff(
+    src_root: &Path,
+    dst_root: &Path,
+    src: &Listing,
+    dst: &Listing,
+    unread: &[PathBuf],
+    compare: SyncCompare,
+) -> Result<SyncPlan, BcmrError> {
+    let mut plan = SyncPlan {
+        copy: CopyPlan {
+            entries: vec![PlanEntry::CreateDir {
+                src: src_root.to_path_buf(),
+                dst: dst_root.to_path_buf(),
+            }],
+            total_size: 0,
+            overwrites: Vec::new(),
+        },
+        replace: Vec::new(),
+        extra: Vec::new(),
+        extra_items: 0,
+        steps: Vec::new(),
+        source_empty: src.is_empty(),
+    };
+
+    for (rel, s) in src {
+        let target = dst_root.join(rel);
+        let existing = dst.get(rel);
+        let action = match (s.kind, existing) {
+            (EntryKind::Other, _) => continue,
+            (_, None) => ActionType::Add,
+            (EntryKind::Dir, Some(d)) if d.kind == EntryKind::Dir => ActionType::Skip,
+            (EntryKind::File, Some(d))
+                if d.kind == EntryKind::File && is_current(s, d, compare)? =>
+            {
+                ActionType::Skip
+            }
+            _ => ActionType::Overwrite,
+        };
+        if existing.is_some_and(|d| d.kind != s.kind) {
+            plan.replace.push(target.clone());
+        }
+        let is_dir = s.kind == EntryKind::Dir;
+        // Existing directories still get the source's attributes.
+        if is_dir {
+            plan.copy.entries.push(PlanEntry::CreateDir {
+                src: s.path.clone(),
+                dst: target,
+            });
+        } else if action != ActionType::Skip {
+            plan.copy.total_size += s.size;
+            plan.copy.entries.push(PlanEntry::CopyFile {
+                src: s.path.clone(),
+                dst: target,
+                label: traversal::display_relative(&s.path, src_root),
+                size: s.size,
+                mtime: s.mtime,
+            });
+        }
+        if !(is_dir && action == ActionType::Skip) {
+            plan.steps.push(Step {
+                action,
+                relative: rel.clone(),
+                is_dir,
+            });
+        }
+    }
+
+    // Below a directory that goes away, whether replaced or extraneous,
+    // nothing needs a decision of its own.
+    let mut gone: Option<&Path> = None;
+    for (rel, d) in dst {
+        if gone.is_some_and(|g| rel.starts_with(g)) {
+            continue;
+        }
+        if unread.iter().any(|u| rel.starts_with(u)) {
+            continue;
+        }
+        let kept = src.get(rel).map(|s| s.kind);
+        if kept == Some(d.kind) {
+            continue;
+        }
+        if d.kind == EntryKind::Dir {
+            gone = Some(rel);
+        }
+        if kept.is_none() {
+            plan.extra_items += dst
+                .range::<Path, _>((Bound::Included(rel.as_path()), Bound::Unbounded))
+                .take_while(|(p, _)| p.starts_with(rel))
+                .count();
+            plan.extra.push(dst_root.join(rel));
+            plan.steps.push(Step {
+                action: ActionType::Remove,
+                relative: rel.clone(),
+                is_dir: d.kind == EntryKind::Dir,
+            });
+        }
+    }
+    plan.steps.sort_by(|a, b| a.relative.cmp(&b.relative));
+
+    Ok(plan)
+}
+
+/// Whether a destination file can stay as it is. Times are compared to
+/// the second, which is all a preserved mtime keeps.
+pub(crate) fn is_current(
+    src: &ScanEntry,
+    dst: &ScanEntry,
+    compare: SyncCompare,
+) -> Result<bool, BcmrError> {
+    let same_size = src.size == dst.size;
+    let same_mtime = matches!(
+        (whole_seconds(src.mtime), whole_seconds(dst.mtime)),
+        (Some(a), Some(b)) if a == b
+    );
+    Ok(match compare {
+        SyncCompare::SizeAndMtime => same_size && same_mtime,
+        SyncCompare::SizeOnly => same_size,
+        SyncCompare::Mtime => same_mtime,
+        SyncCompare::Checksum => {
+            same_size
+                && checksum::calculate_hash(&src.path, Algorithm::Blake3)?
+                    == checksum::calculate_hash(&dst.path, Algorithm::Blake3)?
+        }
+    })
+}
+
+fn whole_seconds(time: Option<SystemTime>) -> Option<u64> {
+    time?.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// --delete against an empty source would empty the destination, and an
+/// empty source is more often a missing mount than an intent.
+pub fn check_delete(plan: &SyncPlan, args: &SyncArgs) -> Result<(), BcmrError> {
+    if plan.source_empty && !plan.extra.is_empty() && !args.force_delete {
+        return Err(BcmrError::InvalidInput(format!(
+            "Refusing to delete {} entries from '{}': source '{}' is empty (use --force-delete to go ahead)",
+            plan.extra_items,
+            args.dst.display(),
+            args.src.display()
+        )));
+    }
+    Ok(())
+}
+
+pub fn print_plan(plan: &SyncPlan, delete: bool) {
+    for step in &plan.steps {
+        if step.action == ActionType::Remove && !delete {
+            continue;
+        }
+        let mut path = step.relative.to_string_lossy().into_owned();
+        if step.is_dir {
+            path.push('/');
+        }
+        print_dry_run(step.action, &path, None);
+    }
+}
+
+type FileCallback = Box<dyn Fn(&str, u64) + Send + Sync>;
+
+/// Copies first and removes last, so an interrupted sync never leaves the
+/// destination with less than it had.
+pub async fn execute<F>(
+    plan: &SyncPlan,
+    args: &SyncArgs,
+    excludes: &[regex::Regex],
+    callback: ProgressCallback<F>,
+    removals: ProgressState,
+    on_removed: FileCallback,
+    cancel: &CancellationToken,
+) -> Result<(), BcmrError>
+where
+    F: Fn(u64) + Send + Sync + Clone + 'static,
+{
+    let verbose = args.is_verbose();
+    for step in plan.steps.iter().filter(|s| s.action == ActionType::Skip) {
+        let path = args.dst.join(&step.relative);
+        oplog::report(
+            Action::Skip {
+                path: &path,
+                reason: "up to date",
+            },
+            verbose,
+        );
+    }
+
+    for path in &plan.replace {
+        let md = tokio::fs::symlink_metadata(path).await?;
+        if md.is_dir() {
+            tokio::fs::remove_dir_all(path).await?;
+        } else if traversal::is_dir_link(&md.file_type()) {
+            tokio::fs::remove_dir(path).await?;
+        } else {
+            tokio::fs::remove_file(path).await?;
+        }
+        oplog::report(Action::Remove { path }, verbose);
+    }
+
+    copy::execute_plan(&plan.copy, &CopyArgs::from(args), callback, cancel).await?;
+
+    if args.delete && !plan.extra.is_empty() {
+        remove::remove_paths(
+            &plan.extra,
+            &RemoveArgs::from(args),
+            excludes,
+            removals,
+            |_| {},
+            on_removed,
+            cancel,
+        )
+        .await?;
+    }
+    Ok(())
+}
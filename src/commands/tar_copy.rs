@@ -0,0 +1,816 @@
+use crate::cli::CopyArgs;
+use crate::commands::copy::{resolve_sparse_mode, temp_path_for, FileToOverwrite, TempFileGuard};
+use crate::commands::copy_strategies::SparseWriter;
+use crate::core::error::BcmrError;
+use crate::core::io as durable_io;
+use crate::core::oplog::{self, Action, WriteKind};
+use crate::core::traversal;
+use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::runner::ProgressRunner;
+use crate::ui::utils::{display_path, format_bytes, summarize_paths};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Builder, EntryType, Header, HeaderMode};
+use tokio_util::sync::CancellationToken;
+
+const STDIO: &str = "-";
+
+/// Whether this copy writes a tar archive: `--to-tar`, or a destination
+/// named `*.tar` for a tree (a single file copied to `x.tar` stays a copy).
+pub fn is_archive_dest(args: &CopyArgs, sources: &[PathBuf], dest: &Path) -> bool {
+    let named_tar = dest
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tar"));
+    args.to_tar || (named_tar && args.common.is_recursive() && sources.iter().any(|s| s.is_dir()))
+}
+
+fn check_flags(args: &CopyArgs) -> Result<()> {
+    if args.common.is_resume() || args.common.is_append() || args.common.is_strict() {
+        bail!("--resume/--append/--strict are not supported with tar archives");
+    }
+    if args.common.is_verify() {
+        bail!("--verify is not supported with tar archives: compare the extracted tree with `bcmr check`");
+    }
+    Ok(())
+}
+
+enum MemberKind {
+    Dir,
+    File,
+    Symlink(PathBuf),
+    /// A second name for a file already in the archive under this one.
+    HardLink(PathBuf),
+}
+
+struct Member {
+    path: PathBuf,
+    name: PathBuf,
+    kind: MemberKind,
+    md: fs::Metadata,
+}
+
+#[cfg(unix)]
+fn inode(md: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (md.nlink() > 1 && !md.is_dir()).then(|| (md.dev(), md.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_md: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Everything the archive will hold, in walk order, and the bytes of file
+/// data it will carry. A directory source is stored under its own name, as
+/// `bcmr copy -r src dst/` would create `dst/src`.
+fn plan_members(
+    sources: &[PathBuf],
+    recursive: bool,
+    excludes: &[regex::Regex],
+) -> Result<(Vec<Member>, u64), BcmrError> {
+    let mut members = Vec::new();
+    let mut links: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    let mut total = 0;
+
+    let mut add = |path: PathBuf, name: PathBuf, md: fs::Metadata| -> Result<(), BcmrError> {
+        let ft = md.file_type();
+        let kind = if ft.is_dir() {
+            MemberKind::Dir
+        } else if ft.is_symlink() {
+            MemberKind::Symlink(fs::read_link(&path)?)
+        } else if !ft.is_file() {
+            oplog::warn(&path, "skipped: not a regular file, directory or symlink");
+            return Ok(());
+        } else if let Some(first) = inode(&md).and_then(|id| links.get(&id)) {
+            MemberKind::HardLink(first.clone())
+        } else {
+            if let Some(id) = inode(&md) {
+                links.insert(id, name.clone());
+            }
+            total += md.len();
+            MemberKind::File
+        };
+        members.push(Member {
+            path,
+            name,
+            kind,
+            md,
+        });
+        Ok(())
+    };
+
+    for src in sources {
+        if traversal::is_excluded(src, excludes) {
+            continue;
+        }
+        let md = fs::symlink_metadata(src).map_err(|_| BcmrError::SourceNotFound(src.clone()))?;
+        let name = PathBuf::from(src.file_name().ok_or_else(|| {
+            BcmrError::InvalidInput(format!("Invalid source name '{}'", src.display()))
+        })?);
+        if !md.is_dir() {
+            add(src.clone(), name, md)?;
+            continue;
+        }
+        if !recursive {
+            return Err(BcmrError::InvalidInput(format!(
+                "Source '{}' is a directory. Use -r flag for recursive copy.",
+                src.display()
+            )));
+        }
+        for entry in traversal::walk(src, true, false, 0, excludes) {
+            let entry = entry.map_err(io::Error::from)?;
+            let rel = entry.path().strip_prefix(src).unwrap_or(Path::new(""));
+            add(
+                entry.path().to_path_buf(),
+                name.join(rel),
+                entry.path().symlink_metadata()?,
+            )?;
+        }
+    }
+    Ok((members, total))
+}
+
+/// File data on its way into the archive: counted for progress, checked
+/// for cancellation, and held to the size the header already promised.
+struct MemberReader<'a, R> {
+    inner: R,
+    left: u64,
+    on_bytes: &'a (dyn Fn(u64) + Send + Sync),
+    cancel: &'a CancellationToken,
+}
+
+impl<R: Read> Read for MemberReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancel.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let want = buf.len().min(self.left as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file shrank while it was being archived",
+            ));
+        }
+        self.left -= n as u64;
+        (self.on_bytes)(n as u64);
+        Ok(n)
+    }
+}
+
+struct Callbacks<'a> {
+    on_bytes: &'a (dyn Fn(u64) + Send + Sync),
+    on_file: &'a (dyn Fn(&str, u64) + Send + Sync),
+    on_done: &'a (dyn Fn(usize) + Send + Sync),
+}
+
+fn write_archive<W: Write>(
+    out: W,
+    members: &[Member],
+    cb: &Callbacks<'_>,
+    cancel: &CancellationToken,
+) -> Result<W, BcmrError> {
+    let mut builder = Builder::new(out);
+    builder.follow_symlinks(false);
+    for m in members {
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(&m.md, HeaderMode::Complete);
+        header.set_size(0);
+        match &m.kind {
+            MemberKind::Dir => {
+                header.set_entry_type(EntryType::Directory);
+                builder.append_data(&mut header, &m.name, io::empty())?;
+            }
+            MemberKind::Symlink(target) => {
+                header.set_entry_type(EntryType::Symlink);
+                builder.append_link(&mut header, &m.name, target)?;
+            }
+            MemberKind::HardLink(first) => {
+                header.set_entry_type(EntryType::Link);
+                builder.append_link(&mut header, &m.name, first)?;
+            }
+            MemberKind::File => {
+                let file = File::open(&m.path)?;
+                let size = file.metadata()?.len();
+                header.set_size(size);
+                (cb.on_file)(&m.name.to_string_lossy(), size);
+                let reader = MemberReader {
+                    inner: file,
+                    left: size,
+                    on_bytes: cb.on_bytes,
+                    cancel,
+                };
+                builder
+                    .append_data(&mut header, &m.name, reader)
+                    .map_err(|e| cancelled_or(e, cancel))?;
+                (cb.on_done)(1);
+            }
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn cancelled_or(e: io::Error, cancel: &CancellationToken) -> BcmrError {
+    if cancel.is_cancelled() {
+        BcmrError::Cancelled
+    } else {
+        e.into()
+    }
+}
+
+/// Streams the source tree into a tar archive at `dest`, or to stdout when
+/// it is `-`. A file is written next to the destination and renamed into
+/// place once complete.
+pub async fn handle_tar_create(
+    args: &CopyArgs,
+    sources: &[PathBuf],
+    dest: &Path,
+    excludes: &[regex::Regex],
+) -> Result<()> {
+    check_flags(args)?;
+    let to_stdout = dest == Path::new(STDIO);
+    if to_stdout && crate::config::is_json_mode() {
+        bail!("--json and an archive on stdout would share it: write the archive to a file");
+    }
+
+    let (members, total) = {
+        let (sources, excludes) = (sources.to_vec(), excludes.to_vec());
+        let recursive = args.common.is_recursive();
+        tokio::task::spawn_blocking(move || plan_members(&sources, recursive, &excludes)).await??
+    };
+
+    if args.common.is_dry_run() {
+        if !crate::config::is_json_mode() {
+            println!("DRY RUN MODE: No changes will be made.\n");
+            for m in &members {
+                print_dry_run(ActionType::Add, &m.name.to_string_lossy(), None);
+            }
+            println!(
+                "\nSummary: {} entries, {} into {}",
+                members.len(),
+                format_bytes(total as f64),
+                display_path(dest)
+            );
+        }
+        return Ok(());
+    }
+
+    let existed = !to_stdout && dest.symlink_metadata().is_ok();
+    if existed && args.common.is_no_clobber() {
+        oplog::report(
+            Action::Skip {
+                path: dest,
+                reason: "exists",
+            },
+            args.common.is_verbose(),
+        );
+        return Ok(());
+    }
+    if existed && !args.common.is_force() {
+        return Err(BcmrError::TargetExists(dest.to_path_buf()).into());
+    }
+
+    let cancel = CancellationToken::new();
+    let runner = ProgressRunner::new(
+        total,
+        args.common.progress_style(),
+        false,
+        crate::config::is_json_mode(),
+        crate::commands::copy::cleanup_partial_files,
+    )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&summarize_paths(sources), &display_path(dest));
+    {
+        let mut p = runner.progress().lock();
+        p.set_operation_type("Archiving");
+        let files = members
+            .iter()
+            .filter(|m| matches!(m.kind, MemberKind::File))
+            .count();
+        p.set_total_items(files);
+    }
+
+    let sync = args.common.is_sync();
+    let on_bytes = runner.inc_callback();
+    let on_file = runner.file_callback();
+    let on_done = runner.items_callback();
+    let write = {
+        let cancel = cancel.clone();
+        move |out: Box<dyn Write + Send>| -> Result<(), BcmrError> {
+            let cb = Callbacks {
+                on_bytes: &on_bytes,
+                on_file: &on_file,
+                on_done: &on_done,
+            };
+            write_archive(out, &members, &cb, &cancel)?.flush()?;
+            Ok(())
+        }
+    };
+
+    let result = if to_stdout {
+        tokio::task::spawn_blocking(move || write(Box::new(io::BufWriter::new(io::stdout()))))
+            .await
+            .map_err(io::Error::other)
+            .map_err(BcmrError::from)
+            .and_then(|r| r)
+    } else {
+        write_archive_file(dest, write, sync, &cancel).await
+    };
+    if let Err(e) = result {
+        return runner.finish_failed(oplog::failed(dest, e));
+    }
+
+    if !to_stdout {
+        oplog::report(
+            Action::Copy {
+                src: &sources[0],
+                dst: dest,
+                bytes: fs::metadata(dest)?.len(),
+                write: if existed {
+                    WriteKind::Overwritten
+                } else {
+                    WriteKind::Created
+                },
+            },
+            args.common.is_verbose(),
+        );
+    }
+    runner.finish_ok()
+}
+
+async fn write_archive_file(
+    dest: &Path,
+    write: impl FnOnce(Box<dyn Write + Send>) -> Result<(), BcmrError> + Send + 'static,
+    sync: bool,
+    cancel: &CancellationToken,
+) -> Result<(), BcmrError> {
+    let tmp = temp_path_for(dest);
+    let mut guard = TempFileGuard::new(tmp.clone(), dest, cancel);
+    let file = File::create(&tmp)?;
+    let handle = file.try_clone()?;
+    tokio::task::spawn_blocking(move || write(Box::new(io::BufWriter::new(file))))
+        .await
+        .map_err(io::Error::other)??;
+    if sync {
+        durable_io::durable_sync(&handle)?;
+    }
+    drop(handle);
+    fs::rename(&tmp, dest)?;
+    guard.disarm();
+    Ok(())
+}
+
+/// An archive member's path as a path below the destination: `.` segments
+/// dropped, anything absolute or climbing out with `..` refused.
+fn member_path(name: &Path) -> Result<PathBuf, BcmrError> {
+    let mut clean = PathBuf::new();
+    for c in name.components() {
+        match c {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(BcmrError::InvalidInput(format!(
+                    "Refusing '{}' from the archive: it would leave the destination",
+                    name.display()
+                )))
+            }
+        }
+    }
+    Ok(clean)
+}
+
+/// Refuses a write whose path runs through a symlink below `dest`, which
+/// an earlier member of the same archive may have planted.
+fn check_no_symlink_parents(dest: &Path, rel: &Path) -> Result<(), BcmrError> {
+    let mut at = dest.to_path_buf();
+    let mut parts = rel.components().peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            break;
+        }
+        at.push(part);
+        if at
+            .symlink_metadata()
+            .is_ok_and(|md| md.file_type().is_symlink())
+        {
+            return Err(BcmrError::InvalidInput(format!(
+                "Refusing to extract '{}': '{}' is a symbolic link",
+                rel.display(),
+                at.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Files in `archive` that extracting into `dest` would replace, for the
+/// overwrite prompt.
+pub async fn archive_overwrites(
+    archive: &Path,
+    dest: &Path,
+    excludes: &[regex::Regex],
+) -> Result<Vec<FileToOverwrite>, BcmrError> {
+    let (archive, dest, excludes) = (archive.to_path_buf(), dest.to_path_buf(), excludes.to_vec());
+    tokio::task::spawn_blocking(move || {
+        let mut found = Vec::new();
+        for entry in Archive::new(File::open(&archive)?).entries()? {
+            let entry = entry?;
+            let rel = member_path(&entry.path()?)?;
+            if rel.as_os_str().is_empty()
+                || traversal::is_excluded(&rel, &excludes)
+                || entry.header().entry_type().is_dir()
+            {
+                continue;
+            }
+            let target = dest.join(&rel);
+            if target.symlink_metadata().is_ok() {
+                found.push(FileToOverwrite {
+                    path: target,
+                    is_dir: false,
+                });
+            }
+        }
+        Ok(found)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Counts the archive's bytes as they are read, which is what progress is
+/// measured in while extracting.
+struct CountingReader<R> {
+    inner: R,
+    on_bytes: Box<dyn Fn(u64) + Send + Sync>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.on_bytes)(n as u64);
+        Ok(n)
+    }
+}
+
+struct ExtractOptions {
+    force: bool,
+    no_clobber: bool,
+    sync: bool,
+    verbose: bool,
+    sparse: crate::cli::SparseMode,
+    excludes: Vec<regex::Regex>,
+    cancel: CancellationToken,
+}
+
+#[derive(Clone, Copy)]
+enum Existing {
+    Missing,
+    Replace,
+    Skip,
+}
+
+impl ExtractOptions {
+    /// What to do about whatever is already at `target`: an error unless
+    /// `-f` or `-n` say otherwise.
+    fn existing(&self, target: &Path) -> Result<Existing, BcmrError> {
+        let Ok(md) = target.symlink_metadata() else {
+            return Ok(Existing::Missing);
+        };
+        if self.no_clobber {
+            oplog::report(
+                Action::Skip {
+                    path: target,
+                    reason: "exists",
+                },
+                self.verbose,
+            );
+            return Ok(Existing::Skip);
+        }
+        if !self.force {
+            return Err(BcmrError::TargetExists(target.to_path_buf()));
+        }
+        if md.is_dir() {
+            return Err(BcmrError::InvalidInput(format!(
+                "Cannot replace directory '{}' with a file from the archive",
+                target.display()
+            )));
+        }
+        Ok(Existing::Replace)
+    }
+
+    fn report(&self, name: &Path, target: &Path, bytes: u64, existing: Existing) {
+        oplog::report(
+            Action::Copy {
+                src: name,
+                dst: target,
+                bytes,
+                write: if matches!(existing, Existing::Replace) {
+                    WriteKind::Overwritten
+                } else {
+                    WriteKind::Created
+                },
+            },
+            self.verbose,
+        );
+    }
+}
+
+fn set_times(path: &Path, header: &Header, symlink: bool) -> io::Result<()> {
+    let Ok(mtime) = header.mtime() else {
+        return Ok(());
+    };
+    let t = filetime::FileTime::from_unix_time(mtime as i64, 0);
+    if symlink {
+        filetime::set_symlink_file_times(path, t, t)
+    } else {
+        filetime::set_file_times(path, t, t)
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, header: &Header) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match header.mode() {
+        Ok(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777)),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _header: &Header) -> io::Result<()> {
+    Ok(())
+}
+
+fn extract_file<R: Read>(
+    entry: &mut tar::Entry<'_, R>,
+    target: &Path,
+    opts: &ExtractOptions,
+) -> Result<u64, BcmrError> {
+    let tmp = temp_path_for(target);
+    let mut guard = TempFileGuard::new(tmp.clone(), target, &opts.cancel);
+    let mut writer = SparseWriter::new(File::create(&tmp)?, opts.sparse.clone());
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut written = 0;
+    loop {
+        if opts.cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let n = entry.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        writer.write(&buffer[..n])?;
+        written += n as u64;
+    }
+    let file = writer.finish()?;
+    if opts.sync {
+        durable_io::durable_sync(&file)?;
+    }
+    drop(file);
+    set_mode(&tmp, entry.header())?;
+    set_times(&tmp, entry.header(), false)?;
+    fs::rename(&tmp, target)?;
+    guard.disarm();
+    Ok(written)
+}
+
+fn extract_archive<R: Read>(
+    reader: R,
+    dest: &Path,
+    opts: &ExtractOptions,
+    on_file: &(dyn Fn(&str, u64) + Send + Sync),
+    on_done: &(dyn Fn(usize) + Send + Sync),
+) -> Result<(), BcmrError> {
+    let mut archive = Archive::new(reader);
+    let mut dirs = Vec::new();
+    fs::create_dir_all(dest)?;
+
+    for entry in archive.entries()? {
+        if opts.cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let rel = member_path(&name)?;
+        if rel.as_os_str().is_empty() || traversal::is_excluded(&rel, &opts.excludes) {
+            continue;
+        }
+        check_no_symlink_parents(dest, &rel)?;
+        let target = dest.join(&rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let kind = entry.header().entry_type();
+        match kind {
+            EntryType::Directory => {
+                if !target.is_dir() {
+                    fs::create_dir(&target)?;
+                }
+                dirs.push((target, entry.header().clone()));
+            }
+            EntryType::Regular | EntryType::Continuous => {
+                on_file(&rel.to_string_lossy(), entry.size());
+                let existing = opts.existing(&target)?;
+                if matches!(existing, Existing::Skip) {
+                    on_done(1);
+                    continue;
+                }
+                let bytes = extract_file(&mut entry, &target, opts)
+                    .map_err(|e| oplog::failed(&target, e))?;
+                opts.report(&name, &target, bytes, existing);
+                on_done(1);
+            }
+            EntryType::Symlink | EntryType::Link => {
+                let link = entry.link_name()?.ok_or_else(|| {
+                    BcmrError::InvalidInput(format!(
+                        "'{}' in the archive has no link target",
+                        name.display()
+                    ))
+                })?;
+                let existing = opts.existing(&target)?;
+                match existing {
+                    Existing::Skip => continue,
+                    Existing::Replace => fs::remove_file(&target)?,
+                    Existing::Missing => {}
+                }
+                if kind == EntryType::Link {
+                    let first = member_path(&link)?;
+                    check_no_symlink_parents(dest, &first)?;
+                    fs::hard_link(dest.join(first), &target)?;
+                    oplog::hard_linked(fs::metadata(&target)?.len());
+                } else {
+                    make_symlink(&link, &target)?;
+                    set_times(&target, entry.header(), true)?;
+                }
+                opts.report(&name, &target, 0, existing);
+            }
+            _ => oplog::warn(&name, "skipped: not a file, directory or link"),
+        }
+    }
+
+    // Last, and deepest first: writing into a directory moves its mtime,
+    // and a read-only mode would stop the writes.
+    for (dir, header) in dirs.iter().rev() {
+        set_mode(dir, header)?;
+        set_times(dir, header, false)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link, target)
+}
+
+#[cfg(not(unix))]
+fn make_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    oplog::warn(target, &format!("skipped: symlink to '{}'", link.display()));
+    Ok(())
+}
+
+/// Extracts `archive` (stdin when `-`) into the directory `dest`, file by
+/// file with `--force`, `-n` and `--sparse` applied as a copy applies them.
+pub async fn handle_tar_extract(
+    args: &CopyArgs,
+    archive: &Path,
+    dest: &Path,
+    excludes: &[regex::Regex],
+) -> Result<()> {
+    check_flags(args)?;
+    let from_stdin = archive == Path::new(STDIO);
+    if dest.exists() && !dest.is_dir() {
+        bail!(
+            "Destination '{}' must be a directory to extract an archive into",
+            dest.display()
+        );
+    }
+
+    if args.common.is_dry_run() {
+        if from_stdin {
+            bail!("--dry-run needs the archive as a file, not stdin");
+        }
+        let (archive, dest, excludes) =
+            (archive.to_path_buf(), dest.to_path_buf(), excludes.to_vec());
+        let no_clobber = args.common.is_no_clobber();
+        let lines = tokio::task::spawn_blocking(move || -> Result<Vec<_>, BcmrError> {
+            let mut lines = Vec::new();
+            for entry in Archive::new(File::open(&archive)?).entries()? {
+                let entry = entry?;
+                let rel = member_path(&entry.path()?)?;
+                if rel.as_os_str().is_empty() || traversal::is_excluded(&rel, &excludes) {
+                    continue;
+                }
+                let target = dest.join(&rel);
+                let action = match target.symlink_metadata() {
+                    Err(_) => ActionType::Add,
+                    Ok(_) if entry.header().entry_type().is_dir() => continue,
+                    Ok(_) if no_clobber => ActionType::Skip,
+                    Ok(_) => ActionType::Overwrite,
+                };
+                lines.push((action, target));
+            }
+            Ok(lines)
+        })
+        .await
+        .map_err(io::Error::other)??;
+        if !crate::config::is_json_mode() {
+            println!("DRY RUN MODE: No changes will be made.\n");
+            for (action, target) in lines {
+                print_dry_run(action, &target.to_string_lossy(), None);
+            }
+        }
+        return Ok(());
+    }
+
+    let total = if from_stdin {
+        0
+    } else {
+        fs::metadata(archive)
+            .map_err(|_| BcmrError::SourceNotFound(archive.to_path_buf()))?
+            .len()
+    };
+    let cancel = CancellationToken::new();
+    let runner = ProgressRunner::new(
+        total,
+        args.common.progress_style(),
+        false,
+        crate::config::is_json_mode(),
+        crate::commands::copy::cleanup_partial_files,
+    )?;
+    runner.cancel_on_interrupt(&cancel);
+    runner.set_paths(&display_path(archive), &display_path(dest));
+    {
+        let mut p = runner.progress().lock();
+        p.set_operation_type("Extracting");
+        p.set_size_unknown(from_stdin);
+    }
+
+    let opts = ExtractOptions {
+        force: args.common.is_force(),
+        no_clobber: args.common.is_no_clobber(),
+        sync: args.common.is_sync(),
+        verbose: args.common.is_verbose(),
+        sparse: resolve_sparse_mode(&args.get_sparse_mode()),
+        excludes: excludes.to_vec(),
+        cancel: cancel.clone(),
+    };
+    let input: Box<dyn Read + Send> = if from_stdin {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(archive)?)
+    };
+    let reader = CountingReader {
+        inner: io::BufReader::new(input),
+        on_bytes: Box::new(runner.inc_callback()),
+    };
+    let (dest_owned, on_file, on_done) = (
+        dest.to_path_buf(),
+        runner.file_callback(),
+        runner.items_callback(),
+    );
+    let result = tokio::task::spawn_blocking(move || {
+        extract_archive(reader, &dest_owned, &opts, &on_file, &on_done)
+    })
+    .await
+    .map_err(io::Error::other)
+    .map_err(BcmrError::from)
+    .and_then(|r| r);
+
+    if let Err(e) = result {
+        return runner.finish_failed(e);
+    }
+    runner.finish_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_path_refuses_escapes() {
+        assert_eq!(member_path(Path::new("./a/./b")).unwrap(), Path::new("a/b"));
+        assert_eq!(member_path(Path::new("./")).unwrap(), Path::new(""));
+        for hostile in ["../x", "a/../../x", "/etc/passwd"] {
+            assert!(member_path(Path::new(hostile)).is_err(), "{hostile}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_writes_through_planted_symlinks_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink("/tmp", dir.path().join("link")).unwrap();
+        assert!(check_no_symlink_parents(dir.path(), Path::new("link/x")).is_err());
+        assert!(check_no_symlink_parents(dir.path(), Path::new("link")).is_ok());
+        assert!(check_no_symlink_parents(dir.path(), Path::new("a/b")).is_ok());
+    }
+}
@@ -0,0 +1,460 @@
+use crate::commands::r#move::is_cross_device_error;
+use crate::core::checksum::Algorithm;
+use crate::core::error::BcmrError;
+use crate::core::history::{self, Event, Record, Stamp};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// One run of bcmr as the journal has it.
+pub struct Operation {
+    pub id: String,
+    pub time: String,
+    pub args: Vec<String>,
+    pub events: Vec<Event>,
+    pub undone: bool,
+}
+
+/// Groups journal records into runs, oldest first.
+pub fn operations(records: Vec<Record>) -> Vec<Operation> {
+    let mut ops: Vec<Operation> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        if let Event::Undone { operation } = &record.event {
+            if let Some(&i) = index.get(operation) {
+                ops[i].undone = true;
+            }
+            continue;
+        }
+        let i = *index.entry(record.id.clone()).or_insert_with(|| {
+            ops.push(Operation {
+                id: record.id.clone(),
+                time: record.time.clone(),
+                args: Vec::new(),
+                events: Vec::new(),
+                undone: false,
+            });
+            ops.len() - 1
+        });
+        match record.event {
+            Event::Begin { args, .. } => ops[i].args = args,
+            event => ops[i].events.push(event),
+        }
+    }
+    ops
+}
+
+/// The run `id` names (a unique prefix will do), or without one the most
+/// recent run not undone yet.
+pub fn pick<'a>(ops: &'a [Operation], id: Option<&str>) -> Result<&'a Operation, BcmrError> {
+    let Some(id) = id else {
+        return ops
+            .iter()
+            .rev()
+            .find(|op| !op.undone)
+            .ok_or_else(|| BcmrError::InvalidInput("Nothing left to undo".to_string()));
+    };
+    let mut matches = ops.iter().filter(|op| op.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(op), None) => Ok(op),
+        (None, _) => Err(BcmrError::InvalidInput(format!(
+            "No operation '{}' in the journal",
+            id
+        ))),
+        (Some(_), Some(_)) => Err(BcmrError::InvalidInput(format!(
+            "'{}' matches more than one operation",
+            id
+        ))),
+    }
+}
+
+/// What undoing one journaled action came to.
+pub struct Step {
+    pub restored: bool,
+    pub line: String,
+}
+
+impl Step {
+    fn restored(line: String) -> Self {
+        Self {
+            restored: true,
+            line,
+        }
+    }
+
+    fn kept(path: &Path, why: &str) -> Self {
+        Self {
+            restored: false,
+            line: format!("{}: {}", path.display(), why),
+        }
+    }
+}
+
+/// Reverses `op` newest action first. Anything changed since bcmr touched
+/// it is left alone; so is a copy whose source is gone, since it is then
+/// the only one left. Directories are made before what goes in them, so
+/// going backwards reaches them innermost first, once their files are gone.
+pub fn undo(op: &Operation, dry_run: bool) -> Vec<Step> {
+    let verified: HashMap<&Path, (Algorithm, &str)> = op
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Verified {
+                path,
+                algorithm,
+                digest,
+            } => Some((path.as_path(), (*algorithm, digest.as_str()))),
+            _ => None,
+        })
+        .collect();
+    op.events
+        .iter()
+        .rev()
+        .filter_map(|event| undo_event(event, &verified, dry_run))
+        .collect()
+}
+
+fn undo_event(
+    event: &Event,
+    verified: &HashMap<&Path, (Algorithm, &str)>,
+    dry_run: bool,
+) -> Option<Step> {
+    Some(match event {
+        Event::Move { src, dst, stamp } => {
+            if let Err(why) = unchanged(dst, stamp, "move") {
+                Step::kept(dst, &why)
+            } else if std::fs::symlink_metadata(src).is_ok() {
+                Step::kept(src, "something else is there now")
+            } else if dry_run {
+                Step::restored(format!(
+                    "would move {} back to {}",
+                    dst.display(),
+                    src.display()
+                ))
+            } else {
+                match move_back(dst, src) {
+                    Ok(()) => {
+                        Step::restored(format!("moved {} back to {}", dst.display(), src.display()))
+                    }
+                    Err(e) => Step::kept(dst, &e.to_string()),
+                }
+            }
+        }
+        Event::Copy {
+            dst,
+            created: false,
+            ..
+        } => Step::kept(dst, "the copy overwrote it, so the old contents are gone"),
+        Event::Copy {
+            src,
+            dst,
+            created: true,
+            stamp,
+        } => {
+            if let Err(why) = unchanged(dst, stamp, "copy") {
+                Step::kept(dst, &why)
+            } else if std::fs::symlink_metadata(src).is_err() {
+                Step::kept(dst, "its source is gone, so this is the only copy")
+            } else if let Err(why) = same_content(src, dst, verified.get(dst.as_path())) {
+                Step::kept(dst, why)
+            } else if dry_run {
+                Step::restored(format!("would remove {}", dst.display()))
+            } else {
+                match std::fs::remove_file(dst) {
+                    Ok(()) => Step::restored(format!("removed {}", dst.display())),
+                    Err(e) => Step::kept(dst, &e.to_string()),
+                }
+            }
+        }
+        Event::Remove { path } => Step::kept(path, "removed files cannot be brought back"),
+        Event::CreateDir { path } => {
+            if !std::fs::symlink_metadata(path).is_ok_and(|md| md.is_dir()) {
+                Step::kept(path, "no longer there")
+            } else if dry_run {
+                Step::restored(format!("would remove {} if it is empty", path.display()))
+            } else {
+                match std::fs::remove_dir(path) {
+                    Ok(()) => Step::restored(format!("removed {}", path.display())),
+                    Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+                        Step::kept(path, "something else is in it now")
+                    }
+                    Err(e) => Step::kept(path, &e.to_string()),
+                }
+            }
+        }
+        Event::Begin { .. } | Event::Verified { .. } | Event::Undone { .. } => return None,
+    })
+}
+
+/// Size and mtime can be kept through an edit, so a copy is only removed
+/// while its bytes are still the ones `--verify` read back, or without
+/// that, still the source's. Hashed here rather than during the copy.
+fn same_content(
+    src: &Path,
+    dst: &Path,
+    verified: Option<&(Algorithm, &str)>,
+) -> Result<(), &'static str> {
+    let Some(&(algorithm, digest)) = verified else {
+        let dst_digest = history::digest(dst, Algorithm::Blake3);
+        if dst_digest.is_none() || dst_digest != history::digest(src, Algorithm::Blake3) {
+            return Err("no longer the same as its source");
+        }
+        return Ok(());
+    };
+    if history::digest(dst, algorithm).as_deref() != Some(digest) {
+        return Err("changed since the copy");
+    }
+    Ok(())
+}
+
+fn unchanged(path: &Path, stamp: &Stamp, by: &str) -> Result<(), String> {
+    let now = Stamp::of(path).ok_or("no longer there")?;
+    let same_mtime = stamp.mtime_ns.is_none() || now.mtime_ns == stamp.mtime_ns;
+    if now.size != stamp.size || !same_mtime {
+        return Err(format!("changed since the {by}"));
+    }
+    Ok(())
+}
+
+/// A file the move copied across filesystems goes back the same way:
+/// copied with its mode and mtime, then removed.
+fn move_back(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::rename(from, to) {
+        Err(e) if is_cross_device_error(&e) && from.is_file() => {
+            std::fs::copy(from, to)?;
+            let mtime = filetime::FileTime::from_last_modification_time(&from.metadata()?);
+            filetime::set_file_mtime(to, mtime)?;
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn record(id: &str, event: Event) -> Record {
+        Record {
+            id: id.into(),
+            time: "2026-01-01T00:00:00Z".into(),
+            event,
+        }
+    }
+
+    fn moved(src: &Path, dst: &Path) -> Event {
+        fs::rename(src, dst).unwrap();
+        Event::Move {
+            src: src.into(),
+            dst: dst.into(),
+            stamp: Stamp::of(dst).unwrap(),
+        }
+    }
+
+    #[test]
+    fn operations_group_by_run_and_pick_skips_undone() {
+        let begin = |args: &[&str]| Event::Begin {
+            args: args.iter().map(|a| a.to_string()).collect(),
+            cwd: PathBuf::from("/"),
+        };
+        let remove = |p: &str| Event::Remove { path: p.into() };
+        let ops = operations(vec![
+            record("aaaa1111", begin(&["bcmr", "rm", "x"])),
+            record("aaaa1111", remove("/x")),
+            record("bbbb2222", begin(&["bcmr", "rm", "y", "z"])),
+            record("bbbb2222", remove("/y")),
+            record("bbbb2222", remove("/z")),
+            record(
+                "cccc3333",
+                Event::Undone {
+                    operation: "bbbb2222".into(),
+                },
+            ),
+        ]);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[1].args, ["bcmr", "rm", "y", "z"]);
+        assert_eq!(ops[1].events.len(), 2);
+        assert!(ops[1].undone);
+
+        assert_eq!(pick(&ops, None).unwrap().id, "aaaa1111");
+        assert_eq!(pick(&ops, Some("bbbb")).unwrap().id, "bbbb2222");
+        assert!(pick(&ops, Some("dddd")).is_err());
+        assert!(pick(&ops[..0], None).is_err());
+    }
+
+    #[test]
+    fn undo_restores_only_what_is_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = |name: &str| dir.path().join(name);
+        fs::create_dir(p("to")).unwrap();
+        for name in ["a", "b", "c", "src"] {
+            fs::write(p(name), name).unwrap();
+        }
+
+        let events = vec![
+            moved(&p("a"), &p("to/a")),
+            moved(&p("b"), &p("to/b")),
+            moved(&p("c"), &p("to/c")),
+        ];
+        fs::write(p("to/b"), "changed").unwrap();
+        fs::write(p("c"), "new c").unwrap();
+        fs::copy(p("src"), p("to/src")).unwrap();
+        let copied = Event::Copy {
+            src: p("src"),
+            dst: p("to/src"),
+            created: true,
+            stamp: Stamp::of(&p("to/src")).unwrap(),
+        };
+        let op = Operation {
+            id: "x".into(),
+            time: String::new(),
+            args: Vec::new(),
+            events: events.into_iter().chain([copied]).collect(),
+            undone: false,
+        };
+
+        let dry: Vec<bool> = undo(&op, true).iter().map(|s| s.restored).collect();
+        assert_eq!(dry, [true, false, false, true]);
+        assert!(p("to/a").exists());
+
+        let steps = undo(&op, false);
+        let restored: Vec<bool> = steps.iter().map(|s| s.restored).collect();
+        assert_eq!(restored, [true, false, false, true]);
+        assert!(steps[1].line.contains("something else is there now"));
+        assert!(steps[2].line.contains("changed since the move"));
+        assert_eq!(fs::read(p("a")).unwrap(), b"a");
+        assert!(!p("to/src").exists());
+        assert_eq!(fs::read(p("to/b")).unwrap(), b"changed");
+    }
+
+    #[test]
+    fn made_directories_go_innermost_first_and_only_when_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = |name: &str| dir.path().join(name);
+        fs::create_dir_all(p("a/b/c")).unwrap();
+        fs::create_dir(p("d")).unwrap();
+        fs::write(p("d/new"), "since").unwrap();
+        let made = |name: &str| Event::CreateDir { path: p(name) };
+        let op = Operation {
+            id: "x".into(),
+            time: String::new(),
+            args: Vec::new(),
+            events: vec![made("a"), made("a/b"), made("a/b/c"), made("d")],
+            undone: false,
+        };
+
+        let steps = undo(&op, false);
+        let restored: Vec<bool> = steps.iter().map(|s| s.restored).collect();
+        assert_eq!(restored, [false, true, true, true]);
+        assert!(steps[0].line.contains("something else is in it now"));
+        assert!(!p("a").exists());
+        assert!(p("d/new").exists());
+    }
+
+    #[test]
+    fn a_copy_without_its_source_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::write(&dst, "only copy").unwrap();
+        let op = Operation {
+            id: "x".into(),
+            time: String::new(),
+            args: Vec::new(),
+            events: vec![
+                Event::Copy {
+                    src,
+                    dst: dst.clone(),
+                    created: true,
+                    stamp: Stamp::of(&dst).unwrap(),
+                },
+                Event::Remove {
+                    path: dir.path().join("gone"),
+                },
+            ],
+            undone: false,
+        };
+        let steps = undo(&op, false);
+        assert!(steps.iter().all(|s| !s.restored));
+        assert!(dst.exists());
+    }
+
+    #[test]
+    fn a_copy_is_removed_only_while_its_content_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::write(&src, "abc").unwrap();
+        let copy = |name: &str| {
+            let dst = dir.path().join(name);
+            fs::copy(&src, &dst).unwrap();
+            Event::Copy {
+                src: src.clone(),
+                dst: dst.clone(),
+                created: true,
+                stamp: Stamp::of(&dst).unwrap(),
+            }
+        };
+        let verified = |name: &str| Event::Verified {
+            path: dir.path().join(name),
+            algorithm: Algorithm::Xxh3,
+            digest: history::digest(&dir.path().join(name), Algorithm::Xxh3).unwrap(),
+        };
+        let events = vec![
+            copy("same"),
+            copy("edited"),
+            verified("edited"),
+            copy("checked"),
+            verified("checked"),
+        ];
+        // Same size and mtime: only the content tells.
+        let edited = dir.path().join("edited");
+        let mtime =
+            filetime::FileTime::from_last_modification_time(&fs::metadata(&edited).unwrap());
+        fs::write(&edited, "xyz").unwrap();
+        filetime::set_file_mtime(&edited, mtime).unwrap();
+        let op = Operation {
+            id: "x".into(),
+            time: String::new(),
+            args: Vec::new(),
+            events,
+            undone: false,
+        };
+
+        let steps = undo(&op, false);
+        let restored: Vec<bool> = steps.iter().map(|s| s.restored).collect();
+        assert_eq!(restored, [true, false, true]);
+        assert!(steps[1].line.contains("changed since the copy"));
+        assert!(!dir.path().join("same").exists());
+        assert!(!dir.path().join("checked").exists());
+        assert_eq!(fs::read(&edited).unwrap(), b"xyz");
+    }
+
+    #[test]
+    fn a_copy_whose_source_changed_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::write(&src, "abc").unwrap();
+        fs::copy(&src, &dst).unwrap();
+        fs::write(&src, "new").unwrap();
+        let op = Operation {
+            id: "x".into(),
+            time: String::new(),
+            args: Vec::new(),
+            events: vec![Event::Copy {
+                src,
+                dst: dst.clone(),
+                created: true,
+                stamp: Stamp::of(&dst).unwrap(),
+            }],
+            undone: false,
+        };
+        let steps = undo(&op, false);
+        assert!(!steps[0].restored);
+        assert!(steps[0].line.contains("no longer the same as its source"));
+        assert!(dst.exists());
+    }
+}
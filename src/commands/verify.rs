@@ -0,0 +1,359 @@
+use crate::core::checksum::{self, Algorithm};
+use crate::core::error::BcmrError;
+use crate::core::traversal::{self, EntryKind, ScanEntry};
+use crate::output::{Difference, DifferenceKind};
+
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+/// A file present on both sides with the same size, whose contents only a
+/// hash can tell apart.
+pub struct Pair {
+    pub relative: PathBuf,
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub size: u64,
+}
+
+/// What the listings alone can tell: differences in presence, kind and
+/// size, plus the pairs left for `compare_hashes`.
+pub struct Comparison {
+    pub differences: Vec<Difference>,
+    pub files: u64,
+    pub pairs: Vec<Pair>,
+}
+
+// Keyed by `Path` so entries sort component-wise and a directory is
+// directly followed by everything under it.
+pub(crate) type Listing = BTreeMap<PathBuf, ScanEntry>;
+
+pub async fn compare(
+    src: &Path,
+    dst: &Path,
+    recursive: bool,
+    excludes: &[regex::Regex],
+) -> Result<Comparison, BcmrError> {
+    let src_md = crate::core::io::stat(src)
+        .await
+        .ok_or_else(|| BcmrError::SourceNotFound(src.to_path_buf()))?;
+    let dst_md = crate::core::io::stat(dst).await.ok_or_else(|| {
+        BcmrError::InvalidInput(format!("Destination '{}' not found", dst.display()))
+    })?;
+
+    if !src_md.is_dir() {
+        if dst_md.is_dir() {
+            return Err(BcmrError::InvalidInput(format!(
+                "Cannot compare file '{}' with directory '{}'",
+                src.display(),
+                dst.display()
+            )));
+        }
+        let relative = PathBuf::from(src.file_name().unwrap_or(src.as_os_str()));
+        let mut comparison = Comparison {
+            differences: Vec::new(),
+            files: 1,
+            pairs: Vec::new(),
+        };
+        if src_md.len() != dst_md.len() {
+            comparison
+                .differences
+                .push(size_mismatch(relative, src_md.len(), dst_md.len()));
+        } else {
+            comparison.pairs.push(Pair {
+                relative,
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                size: src_md.len(),
+            });
+        }
+        return Ok(comparison);
+    }
+
+    if !recursive {
+        return Err(BcmrError::InvalidInput(format!(
+            "Source '{}' is a directory. Use -r flag for recursive verify.",
+            src.display()
+        )));
+    }
+    if !dst_md.is_dir() {
+        return Err(BcmrError::InvalidInput(format!(
+            "Cannot compare directory '{}' with file '{}'",
+            src.display(),
+            dst.display()
+        )));
+    }
+
+    let (src_listing, dst_listing) = tokio::try_join!(
+        list_in_background(src, excludes),
+        list_in_background(dst, excludes)
+    )?;
+    Ok(diff_listings(&src_listing, &dst_listing))
+}
+
+pub(crate) async fn list_in_background(
+    root: &Path,
+    excludes: &[regex::Regex],
+) -> Result<Listing, BcmrError> {
+    let root = root.to_path_buf();
+    let excludes = excludes.to_vec();
+    tokio::task::spawn_blocking(move || {
+        traversal::scan(&root, &excludes)
+            .map(|entry| entry.map(|e| (e.relative.clone(), e)))
+            .collect()
+    })
+    .await?
+}
+
+fn diff_listings(src: &Listing, dst: &Listing) -> Comparison {
+    let mut differences = Vec::new();
+    let mut pairs = Vec::new();
+    let mut files = 0;
+
+    // Below a directory missing on the other side every entry is missing
+    // too; only the directory itself is reported.
+    let mut gone: Option<&Path> = None;
+    for (rel, s) in src {
+        if gone.is_some_and(|g| rel.starts_with(g)) {
+            continue;
+        }
+        if s.kind == EntryKind::File {
+            files += 1;
+        }
+        let Some(d) = dst.get(rel) else {
+            differences.push(one_sided(DifferenceKind::MissingDst, rel, s));
+            if s.kind == EntryKind::Dir {
+                gone = Some(rel);
+            }
+            continue;
+        };
+        if s.kind != d.kind {
+            differences.push(Difference {
+                kind: DifferenceKind::Type,
+                path: rel.clone(),
+                src_size: None,
+                dst_size: None,
+                offset: None,
+                is_dir: s.kind == EntryKind::Dir,
+            });
+            if s.kind == EntryKind::Dir {
+                gone = Some(rel);
+            }
+        } else if s.kind == EntryKind::File {
+            if s.size != d.size {
+                differences.push(size_mismatch(rel.clone(), s.size, d.size));
+            } else {
+                pairs.push(Pair {
+                    relative: rel.clone(),
+                    src: s.path.clone(),
+                    dst: d.path.clone(),
+                    size: s.size,
+                });
+            }
+        }
+    }
+
+    gone = None;
+    for (rel, d) in dst {
+        if gone.is_some_and(|g| rel.starts_with(g)) {
+            continue;
+        }
+        match src.get(rel) {
+            Some(s) if s.kind == d.kind => continue,
+            Some(_) => {}
+            None => differences.push(one_sided(DifferenceKind::MissingSrc, rel, d)),
+        }
+        if d.kind == EntryKind::Dir {
+            gone = Some(rel);
+        }
+    }
+
+    differences.sort_by(|a, b| a.path.cmp(&b.path));
+    Comparison {
+        differences,
+        files,
+        pairs,
+    }
+}
+
+fn one_sided(kind: DifferenceKind, rel: &Path, entry: &ScanEntry) -> Difference {
+    Difference {
+        kind,
+        path: rel.to_path_buf(),
+        src_size: None,
+        dst_size: None,
+        offset: None,
+        is_dir: entry.kind == EntryKind::Dir,
+    }
+}
+
+fn size_mismatch(path: PathBuf, src: u64, dst: u64) -> Difference {
+    Difference {
+        kind: DifferenceKind::Size,
+        path,
+        src_size: Some(src),
+        dst_size: Some(dst),
+        offset: None,
+        is_dir: false,
+    }
+}
+
+const DIFF_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Hashes both sides of every pair, `jobs` pairs at a time, and returns the
+/// ones whose SHA-256 differs in path order, each with the offset where it
+/// first differs. `on_done` gets each pair with both digests once it is
+/// hashed.
+pub async fn compare_hashes(
+    pairs: Vec<Pair>,
+    jobs: usize,
+    on_done: impl Fn(&Pair, &str, &str),
+    cancel: &CancellationToken,
+) -> Result<Vec<Difference>, BcmrError> {
+    let mut hashed = stream::iter(pairs)
+        .map(|pair| {
+            tokio::task::spawn_blocking(move || {
+                let sha256 = |path: &Path| checksum::hash_file(path, Algorithm::Sha256, |_| {});
+                let digests = (sha256(&pair.src)?, sha256(&pair.dst)?);
+                let offset = if digests.0 == digests.1 {
+                    None
+                } else {
+                    checksum::find_first_difference(&pair.src, &pair.dst, DIFF_BLOCK_SIZE)?
+                };
+                Ok::<_, BcmrError>((pair, digests, offset))
+            })
+        })
+        .buffer_unordered(jobs.max(1));
+
+    let mut differences = Vec::new();
+    while let Some(result) = hashed.next().await {
+        if cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        let (pair, (src_digest, dst_digest), offset) = result??;
+        on_done(&pair, &src_digest, &dst_digest);
+        if src_digest != dst_digest {
+            differences.push(Difference {
+                kind: DifferenceKind::Hash,
+                path: pair.relative,
+                src_size: None,
+                dst_size: None,
+                offset,
+                is_dir: false,
+            });
+        }
+    }
+    differences.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tree(files: &[(&str, &[u8])]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (rel, data) in files {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    fn lines(differences: &[Difference]) -> Vec<String> {
+        differences
+            .iter()
+            .map(|d| format!("{:?} {}", d.kind, d.path.display()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn identical_trees_leave_only_pairs_to_hash() {
+        let files: &[(&str, &[u8])] = &[("a.txt", b"aaa"), ("sub/b.txt", b"bb")];
+        let (src, dst) = (tree(files), tree(files));
+        let c = compare(src.path(), dst.path(), true, &[]).await.unwrap();
+        assert!(c.differences.is_empty());
+        assert_eq!(c.files, 2);
+        assert_eq!(c.pairs.len(), 2);
+
+        let cancel = CancellationToken::new();
+        let hashed = compare_hashes(c.pairs, 2, |_, _, _| {}, &cancel)
+            .await
+            .unwrap();
+        assert!(hashed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_each_side_once_per_missing_directory() {
+        let src = tree(&[("keep.txt", b"1"), ("only/x", b"x"), ("only/y/z", b"z")]);
+        let dst = tree(&[("keep.txt", b"22"), ("extra/w", b"w"), ("only-not", b"")]);
+        let c = compare(src.path(), dst.path(), true, &[]).await.unwrap();
+        assert_eq!(
+            lines(&c.differences),
+            [
+                "MissingSrc extra",
+                "Size keep.txt",
+                "MissingDst only",
+                "MissingSrc only-not",
+            ]
+        );
+        assert_eq!(c.files, 1);
+        assert!(c.pairs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn file_against_directory_is_a_type_mismatch() {
+        let src = tree(&[("p/inner", b"i")]);
+        let dst = tree(&[("p", b"i")]);
+        let c = compare(src.path(), dst.path(), true, &[]).await.unwrap();
+        assert_eq!(lines(&c.differences), ["Type p"]);
+        assert!(c.differences[0].is_dir);
+    }
+
+    #[tokio::test]
+    async fn excluded_paths_are_out_of_scope() {
+        let src = tree(&[("a.txt", b"a"), ("cache/x.tmp", b"x")]);
+        let dst = tree(&[("a.txt", b"a"), ("b.tmp", b"b")]);
+        let excludes = [regex::Regex::new(r"\.tmp$|/cache$").unwrap()];
+        let c = compare(src.path(), dst.path(), true, &excludes)
+            .await
+            .unwrap();
+        assert!(c.differences.is_empty(), "{:?}", lines(&c.differences));
+    }
+
+    #[tokio::test]
+    async fn hashing_catches_a_flipped_byte() {
+        let src = tree(&[("a.bin", b"0123456789"), ("b.bin", b"same")]);
+        let dst = tree(&[("a.bin", b"0123456780"), ("b.bin", b"same")]);
+        let c = compare(src.path(), dst.path(), true, &[]).await.unwrap();
+        assert!(c.differences.is_empty());
+
+        let cancel = CancellationToken::new();
+        let done = std::sync::atomic::AtomicU64::new(0);
+        let hashed = compare_hashes(
+            c.pairs,
+            1,
+            |pair, _, _| {
+                done.fetch_add(pair.size, std::sync::atomic::Ordering::Relaxed);
+            },
+            &cancel,
+        )
+        .await
+        .unwrap();
+        assert_eq!(lines(&hashed), ["Hash a.bin"]);
+        assert_eq!(hashed[0].offset, Some(9));
+        assert_eq!(done.into_inner(), 14);
+    }
+
+    #[tokio::test]
+    async fn directories_need_recursive() {
+        let (src, dst) = (tree(&[]), tree(&[]));
+        assert!(matches!(
+            compare(src.path(), dst.path(), false, &[]).await,
+            Err(BcmrError::InvalidInput(_))
+        ));
+    }
+}
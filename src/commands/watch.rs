@@ -0,0 +1,474 @@
+use crate::cli::{CopyArgs, RemoveArgs, SyncCompare, WatchArgs};
+use crate::commands::copy::{self, ProgressCallback};
+use crate::commands::remove::{self, ProgressState};
+use crate::commands::sync::{self, Step};
+use crate::core::error::BcmrError;
+use crate::core::oplog::{self, Action};
+use crate::core::traversal::{self, EntryKind, ScanEntry};
+use crate::ui::display::{print_dry_run, ActionType};
+use crate::ui::progress::{self, ProgressRenderer};
+use crate::ui::runner::{interrupted, record_interrupt};
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Wait before a change that failed is tried again, doubled each time.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Tries before a failing path is left alone until it changes again.
+const RETRY_LIMIT: u32 = 5;
+/// A burst that keeps going is applied anyway after this many debounce
+/// periods, so a file written without pause still reaches the mirror.
+const MAX_SETTLE: u32 = 10;
+
+type Events = mpsc::UnboundedReceiver<notify::Result<Event>>;
+
+struct Retry {
+    attempts: u32,
+    due: Instant,
+}
+
+/// Mirrors `args.src` into `args.dst` until Ctrl+C or SIGTERM: a sync on
+/// startup, then each settled burst of changes applied path by path.
+pub async fn run(args: &WatchArgs) -> Result<(), BcmrError> {
+    if args.global.dry_run {
+        return Err(BcmrError::InvalidInput(
+            "bcmr watch has no dry run; `bcmr sync -n` previews its first pass".into(),
+        ));
+    }
+    let excludes = args.compile_excludes()?;
+    let root = args
+        .src
+        .canonicalize()
+        .map_err(|_| BcmrError::SourceNotFound(args.src.clone()))?;
+    if !root.is_dir() {
+        return Err(BcmrError::InvalidInput(format!(
+            "Cannot watch '{}': not a directory",
+            args.src.display()
+        )));
+    }
+    // The mirror's own writes would come back as changes to mirror.
+    if crate::core::lock::canonical(&args.dst).starts_with(&root) {
+        return Err(BcmrError::InvalidInput(format!(
+            "Cannot watch '{}' into '{}': the destination is inside the source",
+            args.src.display(),
+            args.dst.display()
+        )));
+    }
+
+    // Watching starts before the first sync, so nothing changed during it
+    // is missed.
+    let (_watcher, mut events) = start_watcher(&root)?;
+
+    let stop = CancellationToken::new();
+    tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            record_interrupt(interrupted().await);
+            stop.cancel();
+        }
+    });
+
+    // The startup sync can be long, so a signal cuts it short, as it
+    // would a `bcmr sync`.
+    match reconcile(args, Path::new(""), &excludes, &stop).await {
+        Ok(steps) => print_steps(&steps),
+        Err(e) => {
+            if matches!(e, BcmrError::Cancelled) {
+                copy::cleanup_partial_files();
+            }
+            return Err(e);
+        }
+    }
+    if !crate::config::is_quiet() {
+        eprintln!(
+            "Watching '{}' -> '{}' (Ctrl+C to stop)",
+            args.src.display(),
+            args.dst.display()
+        );
+    }
+
+    // Later changes are never cut short: one under way finishes, and what
+    // arrived meanwhile is applied, before the watch stops.
+    let uncancelled = CancellationToken::new();
+    let mut retries: BTreeMap<PathBuf, Retry> = BTreeMap::new();
+    loop {
+        let mut batch = BTreeSet::new();
+        let next_retry = retries.values().map(|r| r.due).min();
+        let stopping = tokio::select! {
+            () = stop.cancelled() => true,
+            event = events.recv() => match event {
+                Some(event) => {
+                    batch.extend(changed(&root, event));
+                    settle(&root, &mut events, &mut batch, args.debounce, &stop).await;
+                    stop.is_cancelled()
+                }
+                None => true,
+            },
+            () = tokio::time::sleep_until(next_retry.unwrap_or_else(Instant::now)),
+                if next_retry.is_some() => false,
+        };
+        if stopping {
+            while let Ok(event) = events.try_recv() {
+                batch.extend(changed(&root, event));
+            }
+        } else {
+            let now = Instant::now();
+            batch.extend(
+                retries
+                    .iter()
+                    .filter(|(_, r)| r.due <= now)
+                    .map(|(rel, _)| rel.clone()),
+            );
+        }
+        apply(args, batch, &excludes, &uncancelled, &mut retries).await;
+        if stopping {
+            break;
+        }
+    }
+    if !crate::config::is_quiet() {
+        eprintln!("Stopped watching '{}'", args.src.display());
+    }
+    Ok(())
+}
+
+fn start_watcher(root: &Path) -> Result<(RecommendedWatcher, Events), BcmrError> {
+    let fail = |e: notify::Error| BcmrError::Watch(root.to_path_buf(), e.to_string());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(fail)?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .map_err(fail)?;
+    Ok((watcher, rx))
+}
+
+/// Collects events into `batch` until none has come for `debounce`.
+async fn settle(
+    root: &Path,
+    events: &mut Events,
+    batch: &mut BTreeSet<PathBuf>,
+    debounce: Duration,
+    stop: &CancellationToken,
+) {
+    let deadline = Instant::now() + debounce * MAX_SETTLE;
+    loop {
+        let quiet = (Instant::now() + debounce).min(deadline);
+        tokio::select! {
+            () = stop.cancelled() => return,
+            event = tokio::time::timeout_at(quiet, events.recv()) => match event {
+                Ok(Some(event)) => batch.extend(changed(root, event)),
+                _ => return,
+            },
+        }
+    }
+}
+
+/// The paths below `root` an event says may have changed. The root itself
+/// (an empty path) when the watcher lost track and the whole tree needs
+/// another look.
+fn changed(root: &Path, event: notify::Result<Event>) -> Vec<PathBuf> {
+    let event = match event {
+        Ok(event) if !event.need_rescan() => event,
+        _ => return vec![PathBuf::new()],
+    };
+    match event.kind {
+        // The copies themselves open and read the source.
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => {}
+        EventKind::Access(_) => return Vec::new(),
+        _ => {}
+    }
+    event
+        .paths
+        .iter()
+        .filter_map(|p| p.strip_prefix(root).ok())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// `paths` without those below another one: reconciling a directory takes
+/// in everything under it.
+fn coalesce(paths: BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    let mut top: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if top.last().is_some_and(|t| path.starts_with(t)) {
+            continue;
+        }
+        top.push(path);
+    }
+    top
+}
+
+async fn apply(
+    args: &WatchArgs,
+    batch: BTreeSet<PathBuf>,
+    excludes: &[regex::Regex],
+    cancel: &CancellationToken,
+    retries: &mut BTreeMap<PathBuf, Retry>,
+) {
+    for rel in coalesce(batch) {
+        match reconcile(args, &rel, excludes, cancel).await {
+            Ok(steps) => {
+                retries.retain(|path, _| !path.starts_with(&rel));
+                print_steps(&steps);
+            }
+            Err(e) => {
+                let attempts = retries.get(&rel).map_or(1, |r| r.attempts + 1);
+                let path = under(&args.src, &rel);
+                if attempts > RETRY_LIMIT {
+                    retries.remove(&rel);
+                    oplog::warn(&path, &format!("{}; giving up until it changes again", e));
+                } else {
+                    let delay = RETRY_DELAY * 2u32.pow(attempts - 1);
+                    oplog::warn(&path, &format!("{}; retrying in {}s", e, delay.as_secs()));
+                    retries.insert(
+                        rel,
+                        Retry {
+                            attempts,
+                            due: Instant::now() + delay,
+                        },
+                    );
+                }
+                progress::print_warnings(&oplog::take_warnings());
+            }
+        }
+    }
+}
+
+/// Brings `rel` under the destination in line with `rel` under the source,
+/// whatever happened to it: a directory is synced as a whole, a file copied
+/// unless it is current, and a path the source lost removed with --delete.
+/// Returns what was done.
+async fn reconcile(
+    args: &WatchArgs,
+    rel: &Path,
+    excludes: &[regex::Regex],
+    cancel: &CancellationToken,
+) -> Result<Vec<Step>, BcmrError> {
+    let (src, dst) = (under(&args.src, rel), under(&args.dst, rel));
+    if rel
+        .ancestors()
+        .filter(|a| !a.as_os_str().is_empty())
+        .any(|a| traversal::is_excluded(&args.src.join(a), excludes))
+    {
+        return Ok(Vec::new());
+    }
+    let sync_args = args.sync_args(src.clone(), dst.clone());
+
+    let md = match tokio::fs::metadata(&src).await {
+        Ok(md) => md,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // A dangling link is still there; sync leaves those alone too.
+            if !args.delete || tokio::fs::symlink_metadata(&src).await.is_ok() {
+                return Ok(Vec::new());
+            }
+            let Ok(existing) = tokio::fs::symlink_metadata(&dst).await else {
+                return Ok(Vec::new());
+            };
+            remove::remove_paths(
+                std::slice::from_ref(&dst),
+                &RemoveArgs::from(&sync_args),
+                excludes,
+                ProgressState::new(1, no_progress()?),
+                |_| {},
+                Box::new(|_, _| {}),
+                cancel,
+            )
+            .await?;
+            return Ok(vec![Step {
+                action: ActionType::Remove,
+                relative: rel.to_path_buf(),
+                is_dir: existing.is_dir(),
+            }]);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if md.is_dir() {
+        // The sync below lists what goes into the directory, not the
+        // directory itself.
+        let mut steps = Vec::new();
+        if !rel.as_os_str().is_empty() {
+            match tokio::fs::symlink_metadata(&dst).await {
+                Ok(d) if d.is_dir() => {}
+                Ok(_) => {
+                    tokio::fs::remove_file(&dst).await?;
+                    oplog::report(Action::Remove { path: &dst }, sync_args.is_verbose());
+                    steps.push(dir_step(ActionType::Overwrite, rel));
+                }
+                Err(_) => steps.push(dir_step(ActionType::Add, rel)),
+            }
+        }
+        let plan = sync::plan(&src, &dst, SyncCompare::SizeAndMtime, excludes).await?;
+        if args.delete && rel.as_os_str().is_empty() {
+            sync::check_delete(&plan, &sync_args)?;
+        }
+        sync::execute(
+            &plan,
+            &sync_args,
+            excludes,
+            ProgressCallback::new(|_| {}, |_, _| {}),
+            ProgressState::new(plan.extra_items, no_progress()?),
+            Box::new(|_, _| {}),
+            cancel,
+        )
+        .await?;
+        steps.extend(
+            plan.steps
+                .into_iter()
+                .filter(|s| match s.action {
+                    ActionType::Skip => false,
+                    ActionType::Remove => args.delete,
+                    _ => true,
+                })
+                .map(|s| Step {
+                    relative: rel.join(&s.relative),
+                    ..s
+                }),
+        );
+        return Ok(steps);
+    }
+    if !md.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let existing = tokio::fs::metadata(&dst).await.ok();
+    let action = match &existing {
+        None => ActionType::Add,
+        Some(d)
+            if d.is_file()
+                && sync::is_current(
+                    &entry(&src, &md),
+                    &entry(&dst, d),
+                    SyncCompare::SizeAndMtime,
+                )? =>
+        {
+            return Ok(Vec::new())
+        }
+        Some(_) => ActionType::Overwrite,
+    };
+    if existing.as_ref().is_some_and(|d| d.is_dir()) {
+        tokio::fs::remove_dir_all(&dst).await?;
+        oplog::report(Action::Remove { path: &dst }, sync_args.is_verbose());
+    }
+    if let Some(parent) = dst.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    copy::copy_single_file(&src, &dst, &CopyArgs::from(&sync_args), cancel).await?;
+    Ok(vec![Step {
+        action,
+        relative: rel.to_path_buf(),
+        is_dir: false,
+    }])
+}
+
+/// `root` joined with `rel`, without the trailing separator joining an
+/// empty path leaves.
+fn under(root: &Path, rel: &Path) -> PathBuf {
+    if rel.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(rel)
+    }
+}
+
+fn dir_step(action: ActionType, rel: &Path) -> Step {
+    Step {
+        action,
+        relative: rel.to_path_buf(),
+        is_dir: true,
+    }
+}
+
+fn entry(path: &Path, md: &std::fs::Metadata) -> ScanEntry {
+    ScanEntry {
+        path: path.to_path_buf(),
+        relative: PathBuf::new(),
+        depth: 0,
+        kind: EntryKind::File,
+        size: md.len(),
+        mtime: md.modified().ok(),
+    }
+}
+
+/// The copies and removals behind a change report to nothing; the watch
+/// prints a line per change instead.
+fn no_progress() -> Result<Arc<Mutex<Box<dyn ProgressRenderer>>>, BcmrError> {
+    let renderer =
+        progress::create_renderer(0, progress::ProgressStyle::default(), true, false, None)?;
+    Ok(Arc::new(Mutex::new(renderer)))
+}
+
+fn print_steps(steps: &[Step]) {
+    if crate::config::is_quiet() {
+        return;
+    }
+    for step in steps {
+        let mut path = step.relative.to_string_lossy().into_owned();
+        if step.is_dir {
+            path.push('/');
+        }
+        print_dry_run(step.action, &path, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesce_keeps_only_the_topmost_paths() {
+        let paths: BTreeSet<PathBuf> = ["a/b/c", "a.txt", "a", "a/b", "b/x", "b/y"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(
+            coalesce(paths),
+            ["a", "a.txt", "b/x", "b/y"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn coalesce_gives_way_to_a_whole_tree_rescan() {
+        let paths: BTreeSet<PathBuf> = [PathBuf::new(), PathBuf::from("a/b")].into();
+        assert_eq!(coalesce(paths), vec![PathBuf::new()]);
+    }
+
+    #[test]
+    fn reads_of_the_source_are_not_changes() {
+        use notify::event::{CreateKind, Flag, ModifyKind};
+        let root = Path::new("/src");
+        let event = |kind| Ok(Event::new(kind).add_path(root.join("a/f.txt")));
+        assert!(changed(root, event(EventKind::Access(AccessKind::Read))).is_empty());
+        assert_eq!(
+            changed(
+                root,
+                event(EventKind::Access(AccessKind::Close(AccessMode::Write)))
+            ),
+            vec![PathBuf::from("a/f.txt")]
+        );
+        assert_eq!(
+            changed(root, event(EventKind::Create(CreateKind::File))),
+            vec![PathBuf::from("a/f.txt")]
+        );
+        assert_eq!(
+            changed(
+                root,
+                event(EventKind::Modify(ModifyKind::Any)).map(|e| e.set_flag(Flag::Rescan))
+            ),
+            vec![PathBuf::new()]
+        );
+    }
+}
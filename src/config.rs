@@ -1,7 +1,10 @@
+use crate::core::checksum::Algorithm;
+use crate::ui::progress::ProgressStyle;
 use config::{Config as ConfigLoader, ConfigError, File};
 use directories::ProjectDirs;
-use once_cell::sync::Lazy;
-use serde::Deserialize;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static JSON_MODE: AtomicBool = AtomicBool::new(false);
@@ -14,8 +17,33 @@ pub fn is_json_mode() -> bool {
     JSON_MODE.load(Ordering::Relaxed)
 }
 
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::Relaxed);
+}
+
+/// `--quiet`: no progress, summaries, verbose lines or warnings on the
+/// console. Errors are still reported.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+static STATS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_stats(enabled: bool) {
+    STATS.store(enabled, Ordering::Relaxed);
+}
+
+/// `--stats`: the transfer statistics block after the done line, and in
+/// the JSON result.
+pub fn show_stats() -> bool {
+    STATS.load(Ordering::Relaxed)
+}
+
 use parking_lot::Mutex;
 use std::path::PathBuf;
+use std::time::Duration;
 
 static LOG_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
 
@@ -27,20 +55,92 @@ pub fn log_file() -> Option<PathBuf> {
     LOG_FILE.lock().clone()
 }
 
-#[derive(Debug, Deserialize, Clone)]
+static PROGRESS_FD: Mutex<Option<i32>> = Mutex::new(None);
+
+pub fn set_progress_fd(fd: i32) {
+    *PROGRESS_FD.lock() = Some(fd);
+}
+
+pub fn progress_fd() -> Option<i32> {
+    *PROGRESS_FD.lock()
+}
+
+static REFRESH_MS_OVERRIDE: Mutex<Option<u64>> = Mutex::new(None);
+
+pub fn set_refresh_ms(ms: u64) {
+    *REFRESH_MS_OVERRIDE.lock() = Some(ms);
+}
+
+/// `--refresh-ms` if given, else `[progress] refresh_ms`.
+pub fn refresh_interval() -> Duration {
+    let ms = REFRESH_MS_OVERRIDE
+        .lock()
+        .unwrap_or(CONFIG.progress.refresh_ms);
+    Duration::from_millis(ms)
+}
+
+/// Beyond a minute the display is no longer "progress" in any useful sense.
+pub const MAX_REFRESH_MS: u64 = 60_000;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub progress: ProgressConfig,
     #[serde(default)]
     pub copy: CopyConfig,
     #[serde(default)]
+    pub r#move: FlagDefaults,
+    #[serde(default)]
+    pub remove: RemoveConfig,
+    #[serde(default)]
+    pub confirm: ConfirmConfig,
+    #[serde(default)]
+    pub filters: FiltersConfig,
+    #[serde(default)]
     pub scp: ScpConfig,
     #[serde(default)]
+    pub hash: HashConfig,
+    #[serde(default)]
     pub transfer: TransferConfig,
     #[serde(default)]
+    pub undo: UndoConfig,
+    #[serde(default)]
+    pub behavior: BehaviorConfig,
+    #[serde(default)]
     pub update_check: UpdateCheck,
+    /// `[profile.NAME]` tables; `--profile` layers one over the rest.
+    #[serde(default, skip_serializing)]
+    pub profile: BTreeMap<String, Profile>,
+}
+
+/// What a `[profile.NAME]` table may set. Only its shape matters here: the
+/// values reach the settings by being layered over the files, and this
+/// catches a misspelt or mistyped key in a profile that is not in use.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub progress: ProfileProgress,
+    #[serde(default)]
+    pub copy: CopyConfig,
+    #[serde(default)]
+    pub r#move: FlagDefaults,
+    #[serde(default)]
+    pub remove: RemoveConfig,
+    #[serde(default)]
+    pub filters: FiltersConfig,
+    #[serde(default)]
+    pub behavior: BehaviorConfig,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// The part of `[progress]` a profile may set.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProfileProgress {
+    #[serde(default)]
+    pub style: Option<ProgressStyle>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TransferConfig {
     #[serde(default = "default_fallback_warning")]
     pub fallback_warning: bool,
@@ -58,7 +158,44 @@ impl Default for TransferConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UndoConfig {
+    /// Record copies, moves and removals for `bcmr undo`.
+    #[serde(default = "default_undo_journal")]
+    pub journal: bool,
+    /// Size in bytes past which the journal is rotated; 0 never rotates it.
+    #[serde(default = "default_undo_max_size")]
+    pub max_size: u64,
+}
+
+fn default_undo_journal() -> bool {
+    true
+}
+
+fn default_undo_max_size() -> u64 {
+    8 * 1024 * 1024
+}
+
+impl Default for UndoConfig {
+    fn default() -> Self {
+        Self {
+            journal: default_undo_journal(),
+            max_size: default_undo_max_size(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct BehaviorConfig {
+    /// Default `--nice`.
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// Default `--ionice`, e.g. "idle".
+    #[serde(default)]
+    pub ionice: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub enum UpdateCheck {
     Notify,
@@ -67,12 +204,202 @@ pub enum UpdateCheck {
     Off,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CopyConfig {
     #[serde(default = "default_reflink")]
     pub reflink: String,
     #[serde(default = "default_sparse")]
     pub sparse: String,
+    #[serde(default)]
+    pub scan_jobs: Option<usize>,
+    #[serde(default)]
+    pub checksum_algo: Option<Algorithm>,
+    /// Default `--bwlimit`, e.g. "10M".
+    #[serde(default, deserialize_with = "rate")]
+    pub bwlimit: Option<Size>,
+    /// Default `--bwlimit-schedule`, e.g. "08:00-18:00=5M,18:00-08:00=0".
+    #[serde(default)]
+    pub bwlimit_schedule: Option<String>,
+    #[serde(default)]
+    pub preserve: Option<bool>,
+    #[serde(default)]
+    pub verify: Option<bool>,
+    #[serde(default)]
+    pub sync: Option<bool>,
+    #[serde(default)]
+    pub no_clobber: Option<bool>,
+    /// Bytes per read and write in a streamed copy, e.g. "1M".
+    #[serde(default, deserialize_with = "buffer_size")]
+    pub buffer_size: Option<Size>,
+    /// Default `--jobs`.
+    #[serde(default, deserialize_with = "jobs")]
+    pub jobs: Option<usize>,
+    /// Files this large or larger are copied past the page cache, e.g. "1G".
+    #[serde(default, deserialize_with = "size")]
+    pub direct_io_threshold: Option<Size>,
+}
+
+/// A byte count from the config file, kept as written so `bcmr config
+/// show` can print both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Size {
+    pub text: String,
+    pub bytes: u64,
+}
+
+impl Serialize for Size {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+/// Reads an optional `Size` with one of the CLI's size parsers. The file
+/// may give a plain number of bytes too.
+fn parse_with<'de, D>(
+    deserializer: D,
+    parse: fn(&str) -> Result<u64, String>,
+) -> Result<Option<Size>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| {
+            parse(&text)
+                .map(|bytes| Size { text, bytes })
+                .map_err(serde::de::Error::custom)
+        })
+        .transpose()
+}
+
+fn size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Size>, D::Error> {
+    parse_with(deserializer, crate::cli::parse_size)
+}
+
+fn rate<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Size>, D::Error> {
+    parse_with(deserializer, crate::cli::parse_rate)
+}
+
+/// A streamed copy reads no more than one resume block at a time.
+fn buffer_size<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Size>, D::Error> {
+    let max = crate::core::session::COPY_BLOCK_SIZE;
+    let size = size(deserializer)?;
+    match size {
+        Some(Size { bytes, .. }) if bytes > max => Err(serde::de::Error::custom(format!(
+            "must be at most {} (got {} bytes)",
+            crate::ui::utils::format_bytes(max as f64),
+            bytes
+        ))),
+        _ => Ok(size),
+    }
+}
+
+fn jobs<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    match Option::<usize>::deserialize(deserializer)? {
+        Some(0) => Err(serde::de::Error::custom("must be at least 1")),
+        jobs => Ok(jobs),
+    }
+}
+
+impl CopyConfig {
+    /// The byte-size settings that are set, each with what it was parsed
+    /// to, for `bcmr config show` to print beside the text.
+    pub fn sizes(&self) -> Vec<(&'static str, String)> {
+        let mut sizes = Vec::new();
+        if let Some(rate) = &self.bwlimit {
+            sizes.push((
+                "bwlimit",
+                match rate.bytes {
+                    0 => "unlimited".to_string(),
+                    n => format!("{} bytes/s", n),
+                },
+            ));
+        }
+        for (key, size) in [
+            ("buffer_size", &self.buffer_size),
+            ("direct_io_threshold", &self.direct_io_threshold),
+        ] {
+            if let Some(size) = size {
+                sizes.push((key, format!("{} bytes", size.bytes)));
+            }
+        }
+        sizes
+    }
+
+    /// Bytes per read and write in a streamed copy.
+    pub fn buffer_bytes(&self) -> usize {
+        self.buffer_size
+            .as_ref()
+            .map_or(crate::core::session::COPY_BLOCK_SIZE, |s| s.bytes) as usize
+    }
+
+    pub fn flags(&self) -> FlagDefaults {
+        FlagDefaults {
+            preserve: self.preserve,
+            verify: self.verify,
+            sync: self.sync,
+            no_clobber: self.no_clobber,
+        }
+    }
+}
+
+/// Defaults for copy and move flags, from `[copy]` or `[move]`. A flag
+/// given on the command line wins; one set nowhere is off.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlagDefaults {
+    #[serde(default)]
+    pub preserve: Option<bool>,
+    #[serde(default)]
+    pub verify: Option<bool>,
+    #[serde(default)]
+    pub sync: Option<bool>,
+    #[serde(default)]
+    pub no_clobber: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FiltersConfig {
+    /// Exclude patterns copy, move and remove apply before `--exclude`'s.
+    #[serde(default)]
+    pub default_excludes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RemoveConfig {
+    /// Prompt before every removal, as `-i` does, unless `-f` or `-I` is given.
+    #[serde(default)]
+    pub interactive: bool,
+}
+
+/// When bcmr asks before going ahead, beyond what the flags say.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConfirmConfig {
+    /// Answer every confirmation yes, as `-y` does.
+    #[serde(default)]
+    pub assume_yes: bool,
+    /// Ask before removing more than this many items, even with `-f`.
+    #[serde(default)]
+    pub remove_large_threshold: Option<usize>,
+    /// Operations that ask first even with `-f`.
+    #[serde(default)]
+    pub require_confirmation_for: Vec<Confirmation>,
+}
+
+impl ConfirmConfig {
+    pub fn requires(&self, what: Confirmation) -> bool {
+        self.require_confirmation_for.contains(&what)
+    }
+}
+
+/// An operation `[confirm] require_confirmation_for` can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Confirmation {
+    /// `bcmr remove -r`.
+    RemoveRecursive,
+    /// The removals of `bcmr sync --delete`.
+    SyncDelete,
 }
 
 impl Default for CopyConfig {
@@ -80,6 +407,17 @@ impl Default for CopyConfig {
         Self {
             reflink: default_reflink(),
             sparse: default_sparse(),
+            scan_jobs: None,
+            checksum_algo: None,
+            bwlimit: None,
+            bwlimit_schedule: None,
+            preserve: None,
+            verify: None,
+            sync: None,
+            no_clobber: None,
+            buffer_size: None,
+            jobs: None,
+            direct_io_threshold: None,
         }
     }
 }
@@ -92,16 +430,39 @@ fn default_sparse() -> String {
     "auto".to_string()
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HashConfig {
+    #[serde(default = "default_hash_buffer_size")]
+    pub buffer_size: usize,
+    #[serde(default)]
+    pub mmap_threshold: Option<u64>,
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: default_hash_buffer_size(),
+            mmap_threshold: None,
+        }
+    }
+}
+
+fn default_hash_buffer_size() -> usize {
+    crate::core::checksum::DEFAULT_BUFFER_SIZE
+}
+
 fn default_parallel_transfers() -> usize {
     4
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ScpConfig {
     #[serde(default = "default_parallel_transfers")]
     pub parallel_transfers: usize,
     #[serde(default = "default_compression")]
     pub compression: String,
+    #[serde(default)]
+    pub allowed_root: Option<String>,
 }
 
 impl Default for ScpConfig {
@@ -109,6 +470,7 @@ impl Default for ScpConfig {
         Self {
             parallel_transfers: default_parallel_transfers(),
             compression: default_compression(),
+            allowed_root: None,
         }
     }
 }
@@ -117,14 +479,48 @@ fn default_compression() -> String {
     "auto".to_string()
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ProgressConfig {
-    pub style: String,
+    pub style: ProgressStyle,
+    #[serde(default = "default_refresh_ms")]
+    pub refresh_ms: u64,
+    #[serde(default)]
+    pub decimals: u8,
+    #[serde(default)]
+    pub sparkline: bool,
+    #[serde(default = "default_stall_secs")]
+    pub stall_secs: u64,
     pub theme: ThemeConfig,
     pub layout: LayoutConfig,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+fn default_refresh_ms() -> u64 {
+    100
+}
+
+fn default_stall_secs() -> u64 {
+    5
+}
+
+impl ProgressConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.refresh_ms > MAX_REFRESH_MS {
+            return Err(format!(
+                "progress.refresh_ms must be between 0 and {} (got {})",
+                MAX_REFRESH_MS, self.refresh_ms
+            ));
+        }
+        if self.decimals > 1 {
+            return Err(format!(
+                "progress.decimals must be 0 or 1 (got {})",
+                self.decimals
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ThemeConfig {
     pub bar_complete_char: String,
     pub bar_incomplete_char: String,
@@ -134,7 +530,7 @@ pub struct ThemeConfig {
     pub title_color: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LayoutConfig {
     pub box_style: String,
 }
@@ -143,7 +539,11 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             progress: ProgressConfig {
-                style: "fancy".to_string(),
+                style: ProgressStyle::Fancy,
+                refresh_ms: default_refresh_ms(),
+                decimals: 0,
+                sparkline: false,
+                stall_secs: default_stall_secs(),
                 theme: ThemeConfig {
                     bar_complete_char: "█".to_string(),
                     bar_incomplete_char: "░".to_string(),
@@ -157,23 +557,220 @@ impl Default for Config {
                 },
             },
             copy: CopyConfig::default(),
+            r#move: FlagDefaults::default(),
+            remove: RemoveConfig::default(),
+            confirm: ConfirmConfig::default(),
+            filters: FiltersConfig::default(),
             scp: ScpConfig::default(),
+            hash: HashConfig::default(),
             transfer: TransferConfig::default(),
+            undo: UndoConfig::default(),
+            behavior: BehaviorConfig::default(),
             update_check: UpdateCheck::default(),
+            profile: BTreeMap::new(),
+        }
+    }
+}
+
+/// Every place a config file is looked for, in the order the ones that
+/// exist are layered over the defaults.
+pub fn candidate_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut add_dir = |dir: &std::path::Path| {
+        for name in ["config.toml", "config.yaml"] {
+            paths.push(dir.join(name));
+        }
+    };
+
+    let user_config_dir =
+        directories::UserDirs::new().map(|u| u.home_dir().join(".config").join("bcmr"));
+    if let Some(dir) = &user_config_dir {
+        add_dir(dir);
+    }
+    if let Some(proj_dirs) = ProjectDirs::from("com", "bcmr", "bcmr") {
+        let config_dir = proj_dirs.config_dir();
+        if user_config_dir.as_deref() != Some(config_dir) {
+            add_dir(config_dir);
+        }
+    }
+    paths
+}
+
+/// The config files that exist, in the order they are layered over the
+/// defaults.
+pub fn config_paths() -> Vec<PathBuf> {
+    candidate_paths()
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect()
+}
+
+/// The file `bcmr config init` writes: `~/.config/bcmr/config.toml`, the
+/// first one looked for.
+pub fn user_config_path() -> Option<PathBuf> {
+    candidate_paths().into_iter().next()
+}
+
+/// Every setting at its default, commented.
+pub const TEMPLATE: &str = include_str!("default_config.toml");
+
+/// `--profile`, set before anything reads `CONFIG`.
+static PROFILE: OnceCell<String> = OnceCell::new();
+
+/// Layers `[profile.NAME]` over the config files for the rest of the run.
+/// A name the files do not define is an error that lists the ones they do;
+/// when the files do not load at all, the load error says so instead.
+pub fn select_profile(name: &str) -> Result<(), String> {
+    if let Ok(names) = Config::profile_names(&config_paths()) {
+        if !names.iter().any(|n| n == name) {
+            return Err(unknown_profile(name, &names));
+        }
+    }
+    let _ = PROFILE.set(name.to_string());
+    Ok(())
+}
+
+fn unknown_profile(name: &str, names: &[String]) -> String {
+    if names.is_empty() {
+        format!("unknown profile '{}' (no profiles are defined)", name)
+    } else {
+        format!(
+            "unknown profile '{}' (available: {})",
+            name,
+            names.join(", ")
+        )
+    }
+}
+
+/// The config files, loaded once.
+static LOADED: Lazy<Loaded> =
+    Lazy::new(|| Loaded::load(&config_paths(), PROFILE.get().map(String::as_str)));
+
+pub static CONFIG: Lazy<Config> = Lazy::new(|| LOADED.config.clone());
+
+/// Keys in the config files that no setting reads: typos, most likely.
+pub fn unknown_keys() -> Vec<String> {
+    LOADED.unknown.clone()
+}
+
+/// Why the config files were set aside for the defaults, if they were.
+pub fn load_error() -> Option<&'static LoadError> {
+    LOADED.error.as_ref()
+}
+
+struct Loaded {
+    config: Config,
+    unknown: Vec<String>,
+    error: Option<LoadError>,
+}
+
+impl Loaded {
+    fn load(paths: &[PathBuf], profile: Option<&str>) -> Self {
+        match Config::load(paths, profile) {
+            Ok((config, unknown)) => Self {
+                config,
+                unknown,
+                error: None,
+            },
+            Err(e) => Self {
+                config: Config::default(),
+                unknown: Vec::new(),
+                error: Some(LoadError::new(paths, e)),
+            },
         }
     }
 }
 
-pub static CONFIG: Lazy<Config> = Lazy::new(|| Config::new().unwrap_or_else(|_| Config::default()));
+/// A config error on one line: the file, the key and what is wrong with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    /// The first file that fails on its own; none when only the combination
+    /// does.
+    pub path: Option<PathBuf>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl LoadError {
+    fn new(paths: &[PathBuf], error: ConfigError) -> Self {
+        let path = paths
+            .iter()
+            .find(|path| Config::from_files(std::slice::from_ref(path)).is_err())
+            .cloned();
+        let (key, message) = match error {
+            ConfigError::Type {
+                unexpected,
+                expected,
+                key,
+                ..
+            } => (
+                key,
+                format!("invalid type: {}, expected {}", unexpected, expected),
+            ),
+            ConfigError::At { error, key, .. } => (key, one_line(&error.to_string())),
+            ConfigError::FileParse { cause, .. } => (None, one_line(&cause.to_string())),
+            other => (None, one_line(&other.to_string())),
+        };
+        Self { path, key, message }
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: ", path.display())?,
+            None => write!(f, "config files: ")?,
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Parsers quote the offending line under their message and name the
+/// problem last; keep the position and the problem.
+fn one_line(message: &str) -> String {
+    let mut lines = message.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().unwrap_or_default();
+    match lines.next_back() {
+        Some(last) => format!("{}: {}", first, last),
+        None => first.to_string(),
+    }
+}
 
 impl Config {
-    pub fn new() -> Result<Self, ConfigError> {
+    /// The defaults with each of `paths` layered over them in turn.
+    pub fn from_files(paths: &[PathBuf]) -> Result<Self, ConfigError> {
+        Self::load(paths, None).map(|(config, _)| config)
+    }
+
+    /// The profiles `paths` define, sorted.
+    pub fn profile_names(paths: &[PathBuf]) -> Result<Vec<String>, ConfigError> {
+        Ok(Self::from_files(paths)?.profile.into_keys().collect())
+    }
+
+    /// `from_files` with `[profile.NAME]` layered over the files when
+    /// `profile` names one, plus the keys in `paths` that no setting reads,
+    /// as `section.key` paths.
+    pub fn load(
+        paths: &[PathBuf],
+        profile: Option<&str>,
+    ) -> Result<(Self, Vec<String>), ConfigError> {
         let mut s = ConfigLoader::builder();
 
         let defaults = Config::default();
 
         s = s
-            .set_default("progress.style", defaults.progress.style)
+            .set_default("progress.style", defaults.progress.style.to_string())
+            .unwrap()
+            .set_default("progress.refresh_ms", defaults.progress.refresh_ms)
+            .unwrap()
+            .set_default("progress.decimals", defaults.progress.decimals)
+            .unwrap()
+            .set_default("progress.sparkline", defaults.progress.sparkline)
+            .unwrap()
+            .set_default("progress.stall_secs", defaults.progress.stall_secs)
             .unwrap()
             .set_default(
                 "progress.theme.bar_complete_char",
@@ -221,46 +818,95 @@ impl Config {
             .unwrap()
             .set_default("scp.compression", defaults.scp.compression)
             .unwrap()
+            .set_default("hash.buffer_size", defaults.hash.buffer_size as i64)
+            .unwrap()
             .set_default(
                 "transfer.fallback_warning",
                 defaults.transfer.fallback_warning,
             )
             .unwrap()
+            .set_default("undo.journal", defaults.undo.journal)
+            .unwrap()
+            .set_default("undo.max_size", defaults.undo.max_size as i64)
+            .unwrap()
             .set_default("update_check", "off")
             .unwrap();
 
-        if let Some(user_dirs) = directories::UserDirs::new() {
-            let config_dir = user_dirs.home_dir().join(".config").join("bcmr");
-            let config_path = config_dir.join("config.toml");
-            if config_path.exists() {
-                s = s.add_source(File::from(config_path));
-            }
-            let yaml_path = config_dir.join("config.yaml");
-            if yaml_path.exists() {
-                s = s.add_source(File::from(yaml_path));
-            }
+        for path in paths {
+            s = s.add_source(File::from(path.as_path()));
         }
 
-        if let Some(proj_dirs) = ProjectDirs::from("com", "bcmr", "bcmr") {
-            let config_dir = proj_dirs.config_dir();
-            let user_config_dir =
-                directories::UserDirs::new().map(|u| u.home_dir().join(".config").join("bcmr"));
-            let is_duplicate = user_config_dir
-                .as_ref()
-                .is_some_and(|u| config_dir == u.as_path());
-            if !is_duplicate {
-                let config_path = config_dir.join("config.toml");
-                if config_path.exists() {
-                    s = s.add_source(File::from(config_path));
-                }
-                let yaml_path = config_dir.join("config.yaml");
-                if yaml_path.exists() {
-                    s = s.add_source(File::from(yaml_path));
-                }
-            }
+        let mut built = s.build()?;
+        if let Some(name) = profile {
+            let table = built.get_table(&format!("profile.{}", name)).map_err(|_| {
+                let mut names: Vec<String> = built
+                    .get_table("profile")
+                    .map(|t| t.into_keys().collect())
+                    .unwrap_or_default();
+                names.sort();
+                ConfigError::Message(unknown_profile(name, &names))
+            })?;
+            built = ConfigLoader::builder()
+                .add_source(built)
+                .add_source(Overlay(profile_overlay(table)))
+                .build()?;
+        }
+
+        let mut unknown = Vec::new();
+        let config = serde_ignored::deserialize(built, |key| unknown.push(key.to_string()))?;
+        // A profile's unknown keys show up again where it was layered.
+        if let Some(name) = profile {
+            let prefix = format!("profile.{}.", name);
+            let layered: Vec<String> = unknown
+                .iter()
+                .filter_map(|key| key.strip_prefix(&prefix).map(str::to_string))
+                .collect();
+            unknown.retain(|key| !layered.contains(key));
+        }
+        // The merged sources come back in hash order.
+        unknown.sort();
+        Ok((config, unknown))
+    }
+
+    /// The settings as a config file would spell them; unset options are
+    /// left out.
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+/// The part of a profile's table that `Profile` lets through; the rest is
+/// reported as unknown, so it must not take effect either.
+fn profile_overlay(
+    mut table: config::Map<String, config::Value>,
+) -> config::Map<String, config::Value> {
+    table.retain(|section, _| {
+        matches!(
+            section.as_str(),
+            "progress" | "copy" | "move" | "remove" | "filters" | "behavior"
+        )
+    });
+    if let Some(progress) = table.get_mut("progress") {
+        if let Ok(mut settings) = progress.clone().into_table() {
+            settings.retain(|key, _| key == "style");
+            *progress = settings.into();
         }
+    }
+    table
+}
 
-        s.build()?.try_deserialize()
+/// A profile's table as one more source over the files, merged key by key
+/// like the files are over each other.
+#[derive(Debug, Clone)]
+struct Overlay(config::Map<String, config::Value>);
+
+impl config::Source for Overlay {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, ConfigError> {
+        Ok(self.0.clone())
     }
 }
 
@@ -271,7 +917,7 @@ mod tests {
     #[test]
     fn test_default_config() {
         let cfg = Config::default();
-        assert_eq!(cfg.progress.style, "fancy");
+        assert_eq!(cfg.progress.style, ProgressStyle::Fancy);
         assert_eq!(cfg.update_check, UpdateCheck::Off);
     }
 
@@ -283,13 +929,323 @@ mod tests {
     }
 
     #[test]
-    fn test_config_new_loads_defaults() {
-        let cfg = Config::new().unwrap();
-        assert!(!cfg.progress.style.is_empty());
+    fn test_refresh_ms_range() {
+        let mut progress = Config::default().progress;
+        assert_eq!(progress.refresh_ms, 100);
+        assert!(progress.validate().is_ok());
+        progress.refresh_ms = 0;
+        assert!(progress.validate().is_ok());
+        progress.refresh_ms = MAX_REFRESH_MS + 1;
+        let err = progress.validate().unwrap_err();
+        assert!(err.contains("progress.refresh_ms"), "{err}");
+
+        let mut progress = Config::default().progress;
+        progress.decimals = 1;
+        assert!(progress.validate().is_ok());
+        progress.decimals = 2;
+        assert!(progress.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_loads_defaults() {
+        let cfg = Config::from_files(&[]).unwrap();
+        assert_eq!(cfg.progress.style, ProgressStyle::Fancy);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[copy]\nverify = true\nverfy = true\n\n[move]\npreserve = true\n\n[trash]\nkeep = 1\n",
+        )
+        .unwrap();
+        let (cfg, unknown) = Config::load(&[path], None).unwrap();
+        assert_eq!(cfg.copy.verify, Some(true));
+        assert_eq!(cfg.r#move.preserve, Some(true));
+        assert_eq!(cfg.r#move.verify, None);
+        assert_eq!(unknown, ["copy.verfy", "trash"]);
+    }
+
+    #[test]
+    fn test_load_error_names_file_and_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.toml");
+        let bad = dir.path().join("bad.toml");
+        std::fs::write(&good, "[copy]\nverify = true\n").unwrap();
+        std::fs::write(&bad, "[progress.theme]\nbar_gradient = \"#fff\"\n").unwrap();
+        let loaded = Loaded::load(&[good, bad.clone()], None);
+        let error = loaded.error.unwrap();
+        assert_eq!(error.path.as_ref(), Some(&bad));
+        assert_eq!(error.key.as_deref(), Some("progress.theme.bar_gradient"));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}: progress.theme.bar_gradient: invalid type: string \"#fff\", expected a sequence",
+                bad.display()
+            )
+        );
+        // The whole file is set aside, the good one with it.
+        assert_eq!(loaded.config.copy.verify, None);
+    }
+
+    #[test]
+    fn test_parse_error_is_one_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[copy]\nverify = true\n[copy]\n").unwrap();
+        let error = Loaded::load(std::slice::from_ref(&path), None)
+            .error
+            .unwrap();
+        assert_eq!(error.key, None);
+        assert_eq!(
+            error.message,
+            "TOML parse error at line 3, column 2: duplicate key"
+        );
+    }
+
+    fn write_config(dir: &std::path::Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_profile_layers_over_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            dir.path(),
+            "config.toml",
+            "[copy]\npreserve = true\nsync = true\n\n[filters]\ndefault_excludes = ['a']\n\n\
+             [profile.backup.copy]\nverify = true\nsync = false\n\n\
+             [profile.backup.progress]\nstyle = \"plain\"\n\n\
+             [profile.backup.filters]\ndefault_excludes = ['b']\n",
+        );
+
+        let (cfg, unknown) = Config::load(std::slice::from_ref(&path), None).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+        assert_eq!(cfg.copy.verify, None);
+        assert_eq!(cfg.progress.style, ProgressStyle::Fancy);
+
+        let (cfg, _) = Config::load(&[path], Some("backup")).unwrap();
+        // Keys the profile leaves alone keep the file's values.
+        assert_eq!(cfg.copy.preserve, Some(true));
+        assert_eq!(cfg.copy.reflink, "auto");
+        assert_eq!(cfg.copy.verify, Some(true));
+        assert_eq!(cfg.copy.sync, Some(false));
+        assert_eq!(cfg.progress.style, ProgressStyle::Plain);
+        assert_eq!(cfg.progress.refresh_ms, 100);
+        assert_eq!(cfg.filters.default_excludes, ["b"]);
+    }
+
+    #[test]
+    fn test_profile_merges_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = write_config(
+            dir.path(),
+            "first.toml",
+            "[profile.fast.copy]\nverify = true\nsync = true\n",
+        );
+        let second = write_config(
+            dir.path(),
+            "second.toml",
+            "[copy]\nverify = false\n\n[profile.fast.copy]\nsync = false\n\n\
+             [profile.slow.behavior]\nnice = 19\n",
+        );
+        let paths = [first, second];
+
+        let (cfg, _) = Config::load(&paths, Some("fast")).unwrap();
+        assert_eq!(cfg.copy.verify, Some(true));
+        assert_eq!(cfg.copy.sync, Some(false));
+        assert_eq!(cfg.behavior.nice, None);
+
+        let (cfg, _) = Config::load(&paths, Some("slow")).unwrap();
+        assert_eq!(cfg.copy.verify, Some(false));
+        assert_eq!(cfg.behavior.nice, Some(19));
+
+        assert_eq!(Config::profile_names(&paths).unwrap(), ["fast", "slow"]);
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_the_defined_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            dir.path(),
+            "config.toml",
+            "[profile.slow.copy]\nsync = true\n\n[profile.fast.copy]\nsync = false\n",
+        );
+        let err = Config::load(&[path], Some("quick")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown profile 'quick' (available: fast, slow)"
+        );
+        assert_eq!(
+            unknown_profile("quick", &[]),
+            "unknown profile 'quick' (no profiles are defined)"
+        );
+    }
+
+    #[test]
+    fn test_profile_keys_are_checked_and_only_known_ones_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            dir.path(),
+            "config.toml",
+            "[profile.x.copy]\nverfy = true\n\n[profile.x.scp]\nparallel_transfers = 9\n\n\
+             [profile.x.progress]\nstyle = \"none\"\nrefresh_ms = 5\n",
+        );
+        let (cfg, unknown) = Config::load(std::slice::from_ref(&path), Some("x")).unwrap();
+        assert_eq!(
+            unknown,
+            [
+                "profile.x.copy.verfy",
+                "profile.x.progress.refresh_ms",
+                "profile.x.scp"
+            ]
+        );
+        assert_eq!(cfg.progress.style, ProgressStyle::None);
+        assert_eq!(cfg.progress.refresh_ms, 100);
+        assert_eq!(cfg.scp.parallel_transfers, 4);
+
+        write_config(
+            dir.path(),
+            "config.toml",
+            "[profile.x.behavior]\nnice = \"low\"\n",
+        );
+        let err = Config::load(&[path], None).unwrap_err();
+        assert!(err.to_string().contains("profile.x.behavior.nice"), "{err}");
+    }
+
+    #[test]
+    fn test_copy_sizes_parse_like_the_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            dir.path(),
+            "config.toml",
+            "[copy]\nbwlimit = \"5M\"\nbuffer_size = \"256K\"\njobs = 4\n\
+             direct_io_threshold = \"1G\"\n",
+        );
+        let (cfg, unknown) = Config::load(&[path], None).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+        let bwlimit = cfg.copy.bwlimit.as_ref().unwrap();
+        assert_eq!((bwlimit.text.as_str(), bwlimit.bytes), ("5M", 5 << 20));
+        assert_eq!(cfg.copy.buffer_bytes(), 256 << 10);
+        assert_eq!(cfg.copy.jobs, Some(4));
+        assert_eq!(
+            cfg.copy.direct_io_threshold.as_ref().unwrap().bytes,
+            1 << 30
+        );
+        assert_eq!(
+            cfg.copy.sizes(),
+            [
+                ("bwlimit", "5242880 bytes/s".to_string()),
+                ("buffer_size", "262144 bytes".to_string()),
+                ("direct_io_threshold", "1073741824 bytes".to_string()),
+            ]
+        );
+
+        // As with --bwlimit, 0 lifts the limit, and a bare number is bytes.
+        let path = write_config(
+            dir.path(),
+            "zero.toml",
+            "[copy]\nbwlimit = 0\nbuffer_size = 4096\n",
+        );
+        let (cfg, _) = Config::load(&[path], None).unwrap();
+        assert_eq!(cfg.copy.bwlimit.as_ref().unwrap().bytes, 0);
+        assert_eq!(cfg.copy.buffer_bytes(), 4096);
+        assert_eq!(Config::default().copy.buffer_bytes(), 4 << 20);
+    }
+
+    #[test]
+    fn test_bad_copy_sizes_fail_the_load_naming_the_key() {
+        let dir = tempfile::tempdir().unwrap();
+        for (text, key, message) in [
+            (
+                "direct_io_threshold = \"1X\"",
+                "copy.direct_io_threshold",
+                "Invalid size '1X'. Expected e.g. 4096, 64K, 512M or 2G",
+            ),
+            (
+                "bwlimit = \"5Q\"",
+                "copy.bwlimit",
+                "Invalid size '5Q'. Expected e.g. 4096, 64K, 512M or 2G",
+            ),
+            (
+                "buffer_size = \"8M\"",
+                "copy.buffer_size",
+                "must be at most 4.00 MiB (got 8388608 bytes)",
+            ),
+            ("jobs = 0", "copy.jobs", "must be at least 1"),
+        ] {
+            let path = write_config(dir.path(), "config.toml", &format!("[copy]\n{}\n", text));
+            let error = Loaded::load(&[path], None).error.unwrap();
+            assert_eq!(error.key.as_deref(), Some(key), "{error}");
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_flag_defaults_are_unset_by_default() {
+        let cfg = Config::default();
+        assert_eq!(cfg.copy.flags(), FlagDefaults::default());
+        assert_eq!(cfg.r#move, FlagDefaults::default());
+        assert!(!cfg.remove.interactive);
+    }
+
+    #[test]
+    fn test_template_spells_out_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, TEMPLATE).unwrap();
+        let (_, unknown) = Config::load(&[path], None).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+
+        // Every default is written out, not just implied by a missing key.
+        let template: toml::Table = toml::from_str(TEMPLATE).unwrap();
+        let defaults: toml::Table = toml::from_str(&Config::default().to_toml().unwrap()).unwrap();
+        assert_eq!(template, defaults);
+    }
+
+    #[test]
+    fn test_commented_template_options_are_known() {
+        let uncommented: String = TEMPLATE
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(rest)
+                    if rest.starts_with('[')
+                        || rest
+                            .split_once(" = ")
+                            .is_some_and(|(k, _)| !k.contains(' ')) =>
+                {
+                    rest
+                }
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, uncommented).unwrap();
+        let (cfg, unknown) = Config::load(&[path], None).unwrap();
+        assert!(unknown.is_empty(), "{unknown:?}");
+        assert_eq!(cfg.copy.scan_jobs, Some(8));
+        assert_eq!(cfg.r#move.no_clobber, Some(false));
+        assert_eq!(cfg.behavior.ionice.as_deref(), Some("idle"));
+        assert_eq!(cfg.copy.buffer_bytes(), 4 << 20);
+        assert_eq!(cfg.copy.jobs, Some(4));
+        assert!(cfg.profile.contains_key("backup"));
+    }
+
+    #[test]
+    fn test_config_paths_are_the_existing_candidates() {
+        let candidates = candidate_paths();
+        assert_eq!(user_config_path().as_ref(), candidates.first());
+        assert!(config_paths().iter().all(|p| candidates.contains(p)));
     }
 
     #[test]
     fn test_static_config() {
-        assert!(!CONFIG.progress.style.is_empty());
+        assert!(CONFIG.progress.refresh_ms <= MAX_REFRESH_MS);
     }
 }
@@ -0,0 +1,341 @@
+//! `--bwlimit`: a token bucket for the whole process, so every worker of
+//! an operation draws from the same rate. `--bwlimit-schedule` changes
+//! that rate by local time of day.
+
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static LIMIT: OnceCell<Limiter> = OnceCell::new();
+
+/// Pacing step: a fiftieth of a second's worth, so a slow limit moves
+/// small slices often instead of sleeping a whole buffer at a time.
+const STEPS_PER_SEC: u64 = 50;
+const MIN_STEP: usize = 4096;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// How often a scheduled limit looks at the clock again.
+const RECHECK: Duration = Duration::from_secs(60);
+
+/// What `--bwlimit` / `--bwlimit-schedule` resolved to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Limit {
+    /// Bytes per second; zero is unlimited.
+    Rate(u64),
+    Schedule(Schedule),
+}
+
+/// One `HH:MM-HH:MM=RATE` entry, in minutes past local midnight. `end`
+/// is exclusive and wraps past midnight when it is not after `start`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub start: u16,
+    pub end: u16,
+    /// Bytes per second; zero is unlimited.
+    pub rate: u64,
+}
+
+impl Window {
+    fn len(&self) -> u16 {
+        match (self.end + MINUTES_PER_DAY - self.start) % MINUTES_PER_DAY {
+            0 => MINUTES_PER_DAY,
+            n => n,
+        }
+    }
+
+    fn contains(&self, minute: u16) -> bool {
+        (minute + MINUTES_PER_DAY - self.start) % MINUTES_PER_DAY < self.len()
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", clock_time(self.start), clock_time(self.end))
+    }
+}
+
+fn clock_time(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Windows that cover the whole day exactly once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    /// Rejects windows that overlap or leave part of the day uncovered.
+    pub fn new(mut windows: Vec<Window>) -> Result<Self, String> {
+        if windows.is_empty() {
+            return Err("Bandwidth schedule is empty".to_string());
+        }
+        windows.sort_by_key(|w| w.start);
+        for (i, w) in windows.iter().enumerate() {
+            let next = &windows[(i + 1) % windows.len()];
+            if windows.len() > 1 && w.contains(next.start) {
+                return Err(format!(
+                    "Bandwidth schedule windows {} and {} overlap",
+                    w, next
+                ));
+            }
+            if w.end != next.start {
+                return Err(format!(
+                    "Bandwidth schedule leaves {}-{} uncovered",
+                    clock_time(w.end),
+                    clock_time(next.start)
+                ));
+            }
+        }
+        Ok(Self { windows })
+    }
+
+    fn rate_at(&self, minute: u16) -> u64 {
+        self.windows
+            .iter()
+            .find(|w| w.contains(minute))
+            .map_or(0, |w| w.rate)
+    }
+}
+
+/// Caps this process at `limit`; a zero rate leaves it unlimited.
+pub fn set(limit: Limit) {
+    let limited = match &limit {
+        Limit::Rate(rate) => *rate > 0,
+        Limit::Schedule(s) => s.windows.iter().any(|w| w.rate > 0),
+    };
+    if limited {
+        let _ = LIMIT.set(Limiter::new(limit, local_minute));
+    }
+}
+
+pub fn is_limited() -> bool {
+    LIMIT.get().is_some()
+}
+
+/// The rate in force right now, for the progress display.
+pub fn current() -> Option<u64> {
+    LIMIT
+        .get()
+        .map(|l| l.bucket.lock().rate as u64)
+        .filter(|&rate| rate > 0)
+}
+
+/// How much of a `max`-byte buffer to move before the next `wait`.
+pub fn step(max: usize) -> usize {
+    match LIMIT.get() {
+        Some(l) => l.step().min(max),
+        None => max,
+    }
+}
+
+/// Blocks until `n` more bytes fit under the limit.
+pub fn wait(n: u64) {
+    if let Some(l) = LIMIT.get() {
+        let d = l.reserve(n);
+        if !d.is_zero() {
+            std::thread::sleep(d);
+        }
+    }
+}
+
+/// `wait` for async transfers.
+pub async fn wait_async(n: u64) {
+    if let Some(l) = LIMIT.get() {
+        let d = l.reserve(n);
+        if !d.is_zero() {
+            tokio::time::sleep(d).await;
+        }
+    }
+}
+
+/// Minutes past local midnight; UTC where the local zone is unknown.
+fn local_minute() -> u16 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    #[cfg(unix)]
+    {
+        let t = secs as libc::time_t;
+        // SAFETY: localtime_r only writes the `tm` we hand it.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if !unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+            return (tm.tm_hour * 60 + tm.tm_min) as u16;
+        }
+    }
+    ((secs % 86_400) / 60) as u16
+}
+
+struct Limiter {
+    schedule: Option<Schedule>,
+    clock: fn() -> u16,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Bytes per second; zero while a schedule window is unlimited.
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+    /// When the schedule was last consulted.
+    checked: Instant,
+}
+
+impl Limiter {
+    fn new(limit: Limit, clock: fn() -> u16) -> Self {
+        let (rate, schedule) = match limit {
+            Limit::Rate(rate) => (rate, None),
+            Limit::Schedule(s) => (s.rate_at(clock()), Some(s)),
+        };
+        let now = Instant::now();
+        Self {
+            schedule,
+            clock,
+            bucket: Mutex::new(Bucket {
+                rate: rate as f64,
+                tokens: 0.0,
+                last: now,
+                checked: now,
+            }),
+        }
+    }
+
+    fn step(&self) -> usize {
+        match self.bucket.lock().rate as u64 {
+            0 => usize::MAX,
+            rate => ((rate / STEPS_PER_SEC) as usize).max(MIN_STEP),
+        }
+    }
+
+    /// Takes `n` bytes from the bucket, going into debt when it runs dry,
+    /// and returns how long the caller must wait for the debt to clear.
+    /// Concurrent callers queue up behind each other's debt, so the rate
+    /// holds however many workers share it.
+    fn reserve(&self, n: u64) -> Duration {
+        let mut b = self.bucket.lock();
+        let now = Instant::now();
+        if let Some(schedule) = &self.schedule {
+            if now.duration_since(b.checked) >= RECHECK {
+                b.checked = now;
+                b.rate = schedule.rate_at((self.clock)()) as f64;
+            }
+        }
+        if b.rate == 0.0 {
+            // Unlimited for now: start from empty when a limit resumes.
+            b.tokens = 0.0;
+            b.last = now;
+            return Duration::ZERO;
+        }
+        // Credit an idle spell may bank: a tenth of a second's worth.
+        let burst = b.rate / 10.0;
+        let earned = now.duration_since(b.last).as_secs_f64() * b.rate;
+        b.tokens = (b.tokens + earned).min(burst);
+        b.last = now;
+        b.tokens -= n as f64;
+        if b.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-b.tokens / b.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    fn window(start: u16, end: u16, rate: u64) -> Window {
+        Window { start, end, rate }
+    }
+
+    fn fixed(rate: u64) -> Limiter {
+        Limiter::new(Limit::Rate(rate), || 0)
+    }
+
+    #[test]
+    fn test_debt_is_shared_between_callers() {
+        let l = fixed(1000);
+        let first = l.reserve(500);
+        let second = l.reserve(500);
+        assert!((first.as_secs_f64() - 0.5).abs() < 0.05, "{first:?}");
+        assert!((second.as_secs_f64() - 1.0).abs() < 0.05, "{second:?}");
+    }
+
+    #[test]
+    fn test_idle_credit_is_capped() {
+        let l = fixed(1000);
+        l.bucket.lock().last = Instant::now() - Duration::from_secs(60);
+        assert_eq!(l.reserve(100), Duration::ZERO);
+        assert!(l.reserve(100) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_step_is_a_fiftieth_of_a_second() {
+        assert_eq!(fixed(2 << 20).step(), (2 << 20) / 50);
+        assert_eq!(fixed(1000).step(), MIN_STEP);
+    }
+
+    #[test]
+    fn test_schedule_wraps_past_midnight() {
+        let s =
+            Schedule::new(vec![window(18 * 60, 8 * 60, 0), window(8 * 60, 18 * 60, 5)]).unwrap();
+        assert_eq!(s.rate_at(8 * 60), 5);
+        assert_eq!(s.rate_at(18 * 60 - 1), 5);
+        assert_eq!(s.rate_at(18 * 60), 0);
+        assert_eq!(s.rate_at(0), 0);
+        assert_eq!(s.rate_at(8 * 60 - 1), 0);
+
+        let all_day = Schedule::new(vec![window(6 * 60, 6 * 60, 7)]).unwrap();
+        assert_eq!(all_day.rate_at(0), 7);
+        assert_eq!(all_day.rate_at(6 * 60 - 1), 7);
+    }
+
+    #[test]
+    fn test_schedule_rejects_overlaps_and_gaps() {
+        assert_eq!(
+            Schedule::new(vec![window(8 * 60, 18 * 60, 5), window(17 * 60, 8 * 60, 0)]),
+            Err("Bandwidth schedule windows 08:00-18:00 and 17:00-08:00 overlap".to_string())
+        );
+        assert_eq!(
+            Schedule::new(vec![window(8 * 60, 18 * 60, 5), window(19 * 60, 8 * 60, 0)]),
+            Err("Bandwidth schedule leaves 18:00-19:00 uncovered".to_string())
+        );
+        assert_eq!(
+            Schedule::new(vec![window(8 * 60, 18 * 60, 5)]),
+            Err("Bandwidth schedule leaves 18:00-08:00 uncovered".to_string())
+        );
+        assert!(Schedule::new(vec![window(0, 0, 5), window(12 * 60, 0, 1)]).is_err());
+    }
+
+    static MOCK_MINUTE: AtomicU16 = AtomicU16::new(0);
+
+    #[test]
+    fn test_schedule_switches_rate_live() {
+        let s = Schedule::new(vec![
+            window(8 * 60, 18 * 60, 1000),
+            window(18 * 60, 8 * 60, 0),
+        ])
+        .unwrap();
+        MOCK_MINUTE.store(17 * 60 + 59, Ordering::Relaxed);
+        let l = Limiter::new(Limit::Schedule(s), || MOCK_MINUTE.load(Ordering::Relaxed));
+        assert!(l.reserve(500) > Duration::ZERO);
+
+        // The evening window opens, but only the next minute's recheck
+        // sees it.
+        MOCK_MINUTE.store(18 * 60, Ordering::Relaxed);
+        assert!(l.reserve(500) > Duration::ZERO);
+        l.bucket.lock().checked -= RECHECK;
+        assert_eq!(l.reserve(1 << 30), Duration::ZERO);
+        assert_eq!(l.step(), usize::MAX);
+
+        // And back to office hours.
+        MOCK_MINUTE.store(8 * 60, Ordering::Relaxed);
+        l.bucket.lock().checked -= RECHECK;
+        let d = l.reserve(500);
+        assert!((d.as_secs_f64() - 0.5).abs() < 0.05, "{d:?}");
+    }
+}
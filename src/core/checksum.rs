@@ -1,41 +1,474 @@
 use blake3::Hasher;
+use parking_lot::Mutex;
+use ring::digest;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use xxhash_rust::xxh3::Xxh3;
 
-const BUFFER_SIZE: usize = 4 * 1024 * 1024;
+/// How files are read for hashing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Bytes per read, and per hasher update when mapped.
+    pub buffer_size: usize,
+    /// Map files at least this large instead of reading them; `None` always
+    /// reads. A mapped file truncated underneath the hash kills the process,
+    /// so this stays off unless asked for.
+    pub mmap_threshold: Option<u64>,
+    /// Read past the page cache, so the digest reflects what is on the
+    /// disk: O_DIRECT on Linux, F_NOCACHE on macOS. Where neither works the
+    /// file's cached pages are dropped first instead, with a warning.
+    pub direct: bool,
+}
+
+pub const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            mmap_threshold: None,
+            direct: false,
+        }
+    }
+}
+
+static READ_OPTIONS: Mutex<ReadOptions> = Mutex::new(ReadOptions {
+    buffer_size: DEFAULT_BUFFER_SIZE,
+    mmap_threshold: None,
+    direct: false,
+});
+static DIRECT_FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Sets how every later hash in this process reads its file.
+pub fn set_read_options(opts: ReadOptions) {
+    *READ_OPTIONS.lock() = opts;
+}
+
+pub fn read_options() -> ReadOptions {
+    *READ_OPTIONS.lock()
+}
+
+/// Digest algorithms a manifest or a comparison can be computed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Blake3,
+    /// 128-bit XXH3: not cryptographic, but the fastest way to catch
+    /// corruption.
+    Xxh3,
+}
+
+impl Algorithm {
+    pub fn hasher(self) -> Box<dyn FileHasher> {
+        match self {
+            Algorithm::Sha256 => Box::new(digest::Context::new(&digest::SHA256)),
+            Algorithm::Blake3 => Box::new(Hasher::new()),
+            Algorithm::Xxh3 => Box::new(Xxh3::new()),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Xxh3 => "xxh3",
+        })
+    }
+}
+
+// Config files name the algorithm the way the command line does.
+impl<'de> serde::Deserialize<'de> for Algorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for Algorithm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(Algorithm::Sha256),
+            "blake3" | "b3" => Ok(Algorithm::Blake3),
+            "xxh3" | "xxh3-128" | "xxh128" => Ok(Algorithm::Xxh3),
+            _ => Err(format!(
+                "unknown hash algorithm '{}' (expected sha256, blake3 or xxh3)",
+                s
+            )),
+        }
+    }
+}
+
+/// Incremental digest fed a file's contents chunk by chunk.
+pub trait FileHasher: Send {
+    fn update(&mut self, data: &[u8]);
+    /// Lowercase hex, as the matching `*sum` tool prints it.
+    fn finish(self: Box<Self>) -> String;
+}
+
+impl FileHasher for Hasher {
+    fn update(&mut self, data: &[u8]) {
+        Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        self.finalize().to_hex().to_string()
+    }
+}
+
+impl FileHasher for digest::Context {
+    fn update(&mut self, data: &[u8]) {
+        digest::Context::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        (*self)
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+impl FileHasher for Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh3::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        format!("{:032x}", self.digest128())
+    }
+}
+
+/// Digest of the whole file with `algo`; `on_read` gets the size of each
+/// chunk as it is hashed.
+pub fn hash_file(path: &Path, algo: Algorithm, on_read: impl FnMut(u64)) -> io::Result<String> {
+    hash_range(path, 0, u64::MAX, algo, read_options(), on_read)
+}
+
+/// `hash_file` with `opts` in place of the process-wide read options.
+pub fn hash_file_with(
+    path: &Path,
+    algo: Algorithm,
+    opts: ReadOptions,
+    on_read: impl FnMut(u64),
+) -> io::Result<String> {
+    hash_range(path, 0, u64::MAX, algo, opts, on_read)
+}
+
+pub fn calculate_hash(path: &Path, algo: Algorithm) -> io::Result<String> {
+    hash_file(path, algo, |_| {})
+}
+
+/// Digest of the first `limit` bytes, for matching a partial destination
+/// against the start of its source.
+pub fn calculate_partial_hash(path: &Path, limit: u64, algo: Algorithm) -> io::Result<String> {
+    calculate_range_hash(path, 0, limit, algo)
+}
+
+/// Digest of up to `len` bytes starting at `offset`; a range running past
+/// the end stops there.
+pub fn calculate_range_hash(
+    path: &Path,
+    offset: u64,
+    len: u64,
+    algo: Algorithm,
+) -> io::Result<String> {
+    hash_range(path, offset, len, algo, read_options(), |_| {})
+}
+
+fn hash_range(
+    path: &Path,
+    offset: u64,
+    len: u64,
+    algo: Algorithm,
+    opts: ReadOptions,
+    mut on_read: impl FnMut(u64),
+) -> io::Result<String> {
+    let chunk = opts.buffer_size.max(4096);
+    #[cfg(target_os = "linux")]
+    if opts.direct {
+        if let Some(digest) = hash_direct(path, offset, len, algo, chunk, &mut on_read)? {
+            return Ok(digest);
+        }
+    }
 
-pub fn calculate_hash(path: &Path) -> io::Result<String> {
     let mut file = File::open(path)?;
-    let mut hasher = Hasher::new();
-    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut hasher = algo.hasher();
+    if opts.direct {
+        bypass_cache(&file, path);
+    }
+
+    let file_len = file.metadata()?.len();
+    let len = len.min(file_len.saturating_sub(offset));
+    // Mapped pages come from the cache whatever the file's flags say.
+    let map = !opts.direct && opts.mmap_threshold.is_some_and(|t| file_len >= t);
+    if map && len > 0 {
+        // Falls back to reading where the file system cannot map.
+        if let Ok(map) = map_range(&file, offset, len) {
+            for slice in map.chunks(chunk) {
+                hasher.update(slice);
+                on_read(slice.len() as u64);
+            }
+            return Ok(hasher.finish());
+        }
+    }
 
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = file.take(len);
+    let mut buffer = vec![0; chunk];
     loop {
-        let count = file.read(&mut buffer)?;
+        let count = reader.read(&mut buffer)?;
         if count == 0 {
             break;
         }
         hasher.update(&buffer[..count]);
+        on_read(count as u64);
     }
 
-    Ok(hasher.finalize().to_hex().to_string())
+    Ok(hasher.finish())
 }
 
-pub fn calculate_partial_hash(path: &Path, limit: u64) -> io::Result<String> {
-    let file = File::open(path)?;
-    let mut reader = file.take(limit);
-    let mut hasher = Hasher::new();
-    let mut buffer = vec![0; BUFFER_SIZE];
+const DIRECT_ALIGN: usize = 4096;
 
-    loop {
-        let count = reader.read(&mut buffer)?;
-        if count == 0 {
+/// Hashes through an O_DIRECT descriptor, reading aligned blocks into an
+/// aligned buffer and keeping the bytes inside the range. `None` when the
+/// file system refuses O_DIRECT, before anything was hashed.
+#[cfg(target_os = "linux")]
+fn hash_direct(
+    path: &Path,
+    offset: u64,
+    len: u64,
+    algo: Algorithm,
+    chunk: usize,
+    on_read: &mut impl FnMut(u64),
+) -> io::Result<Option<String>> {
+    use std::os::unix::fs::{FileExt, OpenOptionsExt};
+
+    let unsupported = |e: &io::Error| {
+        matches!(
+            e.raw_os_error(),
+            Some(libc::EINVAL) | Some(libc::EOPNOTSUPP)
+        )
+    };
+    let file = match std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if unsupported(&e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let end = offset.saturating_add(len).min(file.metadata()?.len());
+    let chunk = chunk.div_ceil(DIRECT_ALIGN) * DIRECT_ALIGN;
+    let mut storage = vec![0u8; chunk + DIRECT_ALIGN];
+    let pad = storage.as_ptr().align_offset(DIRECT_ALIGN);
+    let buffer = &mut storage[pad..pad + chunk];
+
+    let mut hasher = algo.hasher();
+    let first = offset - offset % DIRECT_ALIGN as u64;
+    let mut pos = first;
+    while pos < end {
+        let count = match file.read_at(buffer, pos) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if pos == first && unsupported(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let skip = offset.saturating_sub(pos).min(count as u64) as usize;
+        let keep = (end - pos).min(count as u64) as usize;
+        if keep > skip {
+            hasher.update(&buffer[skip..keep]);
+            on_read((keep - skip) as u64);
+        }
+        pos += count as u64;
+        // Only the end of the file reads short; the next offset would not
+        // be aligned anyway.
+        if count % DIRECT_ALIGN != 0 {
             break;
         }
-        hasher.update(&buffer[..count]);
     }
+    Ok(Some(hasher.finish()))
+}
+
+/// Reads `file` past the cache where the platform allows it (F_NOCACHE on
+/// macOS). Otherwise writes back and drops its cached pages, which only
+/// approximates a direct read, and says so once.
+fn bypass_cache(file: &File, path: &Path) {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: F_NOCACHE takes an int and touches nothing else.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } != -1 {
+            return;
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        let _ = file.sync_data();
+        // SAFETY: advice on a descriptor we own; the range 0,0 is the
+        // whole file.
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+    if !DIRECT_FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+        crate::core::oplog::warn(
+            path,
+            "direct reads are not supported here; verifying from a dropped page cache instead",
+        );
+    }
+}
+
+fn map_range(file: &File, offset: u64, len: u64) -> io::Result<memmap2::Mmap> {
+    let len = usize::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    // SAFETY: the map is read-only and dropped before returning; a file
+    // shrunk by another process meanwhile is the risk `mmap_threshold`
+    // documents.
+    let map = unsafe {
+        memmap2::MmapOptions::new()
+            .offset(offset)
+            .len(len)
+            .map(file)?
+    };
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Ok(map)
+}
+
+/// Where two files that should be equal first part ways.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Offset of the first differing byte, or where the shorter file ends.
+    pub offset: u64,
+    pub block: u64,
+    /// Blocks that differ anywhere, when they were counted.
+    pub blocks_differing: Option<u64>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "first difference at offset {} (block {})",
+            group_thousands(self.offset),
+            self.block
+        )?;
+        if let Some(n) = self.blocks_differing {
+            let (noun, verb) = if n == 1 {
+                ("block", "differs")
+            } else {
+                ("blocks", "differ")
+            };
+            write!(f, ", {} {} {}", n, noun, verb)?;
+        }
+        Ok(())
+    }
+}
+
+fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
 
-    Ok(hasher.finalize().to_hex().to_string())
+/// Reads both files a block at a time and returns the first offset where
+/// they differ, or `None` when they are identical.
+pub fn find_first_difference(a: &Path, b: &Path, block_size: usize) -> io::Result<Option<u64>> {
+    Ok(find_mismatch(a, b, block_size, false)?.map(|m| m.offset))
+}
+
+/// The first difference and its block; with `count_blocks` it reads on to
+/// the end to count every block that differs.
+pub fn find_mismatch(
+    a: &Path,
+    b: &Path,
+    block_size: usize,
+    count_blocks: bool,
+) -> io::Result<Option<Mismatch>> {
+    let mut mismatch: Option<Mismatch> = None;
+    compare_blocks(a, b, block_size, |offset| {
+        let m = mismatch.get_or_insert(Mismatch {
+            offset,
+            block: offset / block_size.max(1) as u64,
+            blocks_differing: count_blocks.then_some(0),
+        });
+        m.blocks_differing = m.blocks_differing.map(|n| n + 1);
+        count_blocks
+    })?;
+    Ok(mismatch)
+}
+
+/// Calls `on_diff` with the first differing offset of each differing block
+/// until it returns false. Bytes past the end of the shorter file differ.
+fn compare_blocks(
+    a: &Path,
+    b: &Path,
+    block_size: usize,
+    mut on_diff: impl FnMut(u64) -> bool,
+) -> io::Result<()> {
+    let block_size = block_size.max(1);
+    let (mut fa, mut fb) = (File::open(a)?, File::open(b)?);
+    let (mut ba, mut bb) = (vec![0; block_size], vec![0; block_size]);
+    let mut offset = 0u64;
+    loop {
+        let na = read_full(&mut fa, &mut ba)?;
+        let nb = read_full(&mut fb, &mut bb)?;
+        if na == 0 && nb == 0 {
+            return Ok(());
+        }
+        let same = na.min(nb);
+        let at = ba[..same]
+            .iter()
+            .zip(&bb[..same])
+            .position(|(x, y)| x != y)
+            .or((na != nb).then_some(same));
+        if let Some(at) = at {
+            if !on_diff(offset + at as u64) {
+                return Ok(());
+            }
+        }
+        offset += na.max(nb) as u64;
+    }
+}
+
+// Fills the buffer unless the file ends first, so blocks of both files
+// stay aligned whatever sizes the reads return.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
 }
 
 #[cfg(test)]
@@ -49,7 +482,7 @@ mod tests {
         let path = dir.path().join("test.txt");
         std::fs::write(&path, b"hello world").unwrap();
 
-        let hash = calculate_hash(&path).unwrap();
+        let hash = calculate_hash(&path, Algorithm::Blake3).unwrap();
         let expected = blake3::hash(b"hello world").to_hex().to_string();
         assert_eq!(hash, expected);
     }
@@ -60,18 +493,72 @@ mod tests {
         let path = dir.path().join("empty.txt");
         std::fs::write(&path, b"").unwrap();
 
-        let hash = calculate_hash(&path).unwrap();
+        let hash = calculate_hash(&path, Algorithm::Blake3).unwrap();
         let expected = blake3::hash(b"").to_hex().to_string();
         assert_eq!(hash, expected);
     }
 
+    #[test]
+    fn test_hash_file_known_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.txt");
+        std::fs::write(&path, b"hello world").unwrap();
+
+        let digest = |algo| hash_file(&path, algo, |_| {}).unwrap();
+        assert_eq!(
+            digest(Algorithm::Sha256),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(
+            digest(Algorithm::Blake3),
+            "d74981efa70a0c880b8d8c1985d075dbcbf679b99a5f9914e5aaf96b831a9e24"
+        );
+        assert_eq!(digest(Algorithm::Xxh3), "df8d09e93f874900a99b8775cc15b6c7");
+    }
+
+    #[test]
+    fn test_hash_file_reports_every_byte() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.bin");
+        std::fs::write(&path, vec![1u8; DEFAULT_BUFFER_SIZE + 17]).unwrap();
+
+        let mut seen = 0;
+        hash_file(&path, Algorithm::Xxh3, |n| seen += n).unwrap();
+        assert_eq!(seen, DEFAULT_BUFFER_SIZE as u64 + 17);
+    }
+
+    #[test]
+    fn test_partial_hash_follows_the_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partial.txt");
+        std::fs::write(&path, b"hello world").unwrap();
+        let prefix = dir.path().join("prefix.txt");
+        std::fs::write(&prefix, b"hello").unwrap();
+
+        for algo in [Algorithm::Sha256, Algorithm::Blake3, Algorithm::Xxh3] {
+            assert_eq!(
+                calculate_partial_hash(&path, 5, algo).unwrap(),
+                calculate_hash(&prefix, algo).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_algorithm_names_round_trip() {
+        for algo in [Algorithm::Sha256, Algorithm::Blake3, Algorithm::Xxh3] {
+            assert_eq!(algo.to_string().parse::<Algorithm>(), Ok(algo));
+        }
+        assert_eq!("XXH3-128".parse::<Algorithm>(), Ok(Algorithm::Xxh3));
+        assert!("md5".parse::<Algorithm>().is_err());
+    }
+
     #[test]
     fn test_calculate_partial_hash() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("partial.txt");
         std::fs::write(&path, b"hello world").unwrap();
 
-        let partial = calculate_partial_hash(&path, 5).unwrap();
+        let partial = calculate_partial_hash(&path, 5, Algorithm::Blake3).unwrap();
         let expected = blake3::hash(b"hello").to_hex().to_string();
         assert_eq!(partial, expected);
     }
@@ -82,11 +569,169 @@ mod tests {
         let path = dir.path().join("short.txt");
         std::fs::write(&path, b"abc").unwrap();
 
-        let partial = calculate_partial_hash(&path, 100).unwrap();
-        let full = calculate_hash(&path).unwrap();
+        let partial = calculate_partial_hash(&path, 100, Algorithm::Blake3).unwrap();
+        let full = calculate_hash(&path, Algorithm::Blake3).unwrap();
         assert_eq!(partial, full);
     }
 
+    #[test]
+    fn test_range_hash_matches_the_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("range.bin");
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        for (offset, len) in [(0, 10), (4097, 30_000), (99_990, 50), (200_000, 5)] {
+            let end = (offset + len).min(data.len());
+            let start = offset.min(end);
+            let expected = blake3::hash(&data[start..end]).to_hex().to_string();
+            for mmap_threshold in [None, Some(0)] {
+                let opts = ReadOptions {
+                    buffer_size: 4096,
+                    mmap_threshold,
+                    ..ReadOptions::default()
+                };
+                let got = hash_range(
+                    &path,
+                    offset as u64,
+                    len as u64,
+                    Algorithm::Blake3,
+                    opts,
+                    |_| {},
+                )
+                .unwrap();
+                assert_eq!(got, expected, "{offset}+{len} {mmap_threshold:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_direct_reads_match_cached_ones() {
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+        // The build directory is normally on a disk file system that takes
+        // O_DIRECT; /dev/shm is tmpfs, which may not and must fall back.
+        let mut dirs = vec![tempfile::tempdir_in(env!("CARGO_MANIFEST_DIR")).unwrap()];
+        if Path::new("/dev/shm").is_dir() {
+            dirs.push(tempfile::tempdir_in("/dev/shm").unwrap());
+        }
+        for dir in &dirs {
+            let path = dir.path().join("direct.bin");
+            std::fs::write(&path, &data).unwrap();
+            for (offset, len) in [(0u64, u64::MAX), (4097, 1_000_000), (2_999_990, 100)] {
+                let start = (offset as usize).min(data.len());
+                let end = offset.saturating_add(len).min(data.len() as u64) as usize;
+                let expected = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&data[start..end]));
+                let opts = ReadOptions {
+                    buffer_size: 64 * 1024 + 1,
+                    direct: true,
+                    ..ReadOptions::default()
+                };
+                let got = hash_range(&path, offset, len, Algorithm::Xxh3, opts, |_| {}).unwrap();
+                assert_eq!(got, expected, "{} {offset}+{len}", dir.path().display());
+
+                #[cfg(target_os = "linux")]
+                if let Some(got) =
+                    hash_direct(&path, offset, len, Algorithm::Xxh3, 8192, &mut |_| {}).unwrap()
+                {
+                    assert_eq!(got, expected, "O_DIRECT {offset}+{len}");
+                }
+            }
+        }
+    }
+
+    // Benchmark-style: every way of reading must give the same digest, and
+    // the default buffer must not lose to the old 8 KiB one.
+    #[test]
+    fn test_read_strategies_agree_and_large_buffers_keep_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bench.bin");
+        let data: Vec<u8> = (0..32 * 1024 * 1024u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let strategies = [
+            ("8 KiB reads", 8 * 1024, None),
+            ("1 MiB reads", DEFAULT_BUFFER_SIZE, None),
+            ("mmap", DEFAULT_BUFFER_SIZE, Some(0)),
+        ];
+        let mut best = Vec::new();
+        for (name, buffer_size, mmap_threshold) in strategies {
+            let opts = ReadOptions {
+                buffer_size,
+                mmap_threshold,
+                ..ReadOptions::default()
+            };
+            let mut fastest = std::time::Duration::MAX;
+            for _ in 0..3 {
+                let start = std::time::Instant::now();
+                let digest = hash_range(&path, 0, u64::MAX, Algorithm::Xxh3, opts, |_| {}).unwrap();
+                fastest = fastest.min(start.elapsed());
+                assert_eq!(
+                    digest,
+                    format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&data)),
+                    "{name}"
+                );
+            }
+            best.push(fastest);
+        }
+        // Generous: a loaded machine should not fail this, a regression
+        // that makes large reads slower than small ones should.
+        assert!(best[1] <= best[0] * 2, "{best:?}");
+    }
+
+    #[test]
+    fn test_find_first_difference_reports_the_exact_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 241) as u8).collect();
+        std::fs::write(&a, &data).unwrap();
+        std::fs::write(&b, &data).unwrap();
+        assert_eq!(find_first_difference(&a, &b, 4096).unwrap(), None);
+        assert_eq!(find_mismatch(&a, &b, 4096, true).unwrap(), None);
+
+        let mut corrupt = data.clone();
+        corrupt[123_457] ^= 1;
+        corrupt[900_000] ^= 1;
+        std::fs::write(&b, &corrupt).unwrap();
+        assert_eq!(find_first_difference(&a, &b, 4096).unwrap(), Some(123_457));
+        assert_eq!(
+            find_mismatch(&a, &b, 65536, true).unwrap(),
+            Some(Mismatch {
+                offset: 123_457,
+                block: 1,
+                blocks_differing: Some(2),
+            })
+        );
+
+        // A torn tail: everything matches up to where the copy stopped.
+        std::fs::write(&b, &data[..700_001]).unwrap();
+        assert_eq!(find_first_difference(&a, &b, 4096).unwrap(), Some(700_001));
+    }
+
+    #[test]
+    fn test_mismatch_message() {
+        let m = Mismatch {
+            offset: 1_073_745_920,
+            block: 256,
+            blocks_differing: None,
+        };
+        assert_eq!(
+            m.to_string(),
+            "first difference at offset 1,073,745,920 (block 256)"
+        );
+        let m = Mismatch {
+            offset: 12,
+            block: 0,
+            blocks_differing: Some(3),
+        };
+        assert_eq!(
+            m.to_string(),
+            "first difference at offset 12 (block 0), 3 blocks differ"
+        );
+    }
+
     #[test]
     fn test_calculate_hash_large_data() {
         let dir = tempfile::tempdir().unwrap();
@@ -98,7 +743,7 @@ mod tests {
         }
         drop(f);
 
-        let hash = calculate_hash(&path).unwrap();
+        let hash = calculate_hash(&path, Algorithm::Blake3).unwrap();
         let mut hasher = blake3::Hasher::new();
         for _ in 0..5 {
             hasher.update(&chunk);
@@ -1,32 +1,103 @@
+use std::fmt;
+use std::fs::OpenOptions;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 
+/// What interrupt cleanup does with a file that was still being written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Partial {
+    /// A temp file, or a destination this run created from scratch.
+    Remove,
+    /// A destination written in place (`--resume`/`--append`): cut back to
+    /// the length it had before this run, the last point a resume can trust.
+    TruncateTo(u64),
+}
+
+struct Entry {
+    path: PathBuf,
+    dst: PathBuf,
+    partial: Partial,
+}
+
+/// What happened to one in-flight file, worded for the closing line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cleaned {
+    Removed(PathBuf),
+    Truncated(PathBuf, u64),
+    Kept(PathBuf),
+}
+
+impl fmt::Display for Cleaned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cleaned::Removed(dst) => write!(f, "partial '{}' removed", dst.display()),
+            Cleaned::Truncated(dst, len) => {
+                write!(f, "partial '{}' truncated to {} bytes", dst.display(), len)
+            }
+            Cleaned::Kept(dst) => write!(f, "partial '{}' kept", dst.display()),
+        }
+    }
+}
+
 pub struct CleanupRegistry {
-    paths: Mutex<Vec<PathBuf>>,
+    paths: Mutex<Vec<Entry>>,
+    keep: AtomicBool,
 }
 
 impl CleanupRegistry {
     pub const fn new() -> Self {
         Self {
             paths: Mutex::new(Vec::new()),
+            keep: AtomicBool::new(false),
         }
     }
 
-    pub fn register(&self, path: &Path) {
-        self.paths.lock().push(path.to_path_buf());
+    /// `path` is the file being written, `dst` the name it will end up
+    /// under (they differ for temp files).
+    pub fn register_partial(&self, path: &Path, dst: &Path, partial: Partial) {
+        self.paths.lock().push(Entry {
+            path: path.to_path_buf(),
+            dst: dst.to_path_buf(),
+            partial,
+        });
     }
 
     pub fn unregister(&self, path: &Path) {
-        self.paths.lock().retain(|p| p != path);
+        self.paths.lock().retain(|e| e.path != path);
     }
 
-    pub fn drain_and_remove(&self) {
-        let drained: Vec<PathBuf> = self.paths.lock().drain(..).collect();
-        for path in drained {
-            let _ = std::fs::remove_file(&path);
-        }
+    /// `--keep-partial`: cleanup reports in-flight files but leaves them be.
+    pub fn set_keep_partial(&self, keep: bool) {
+        self.keep.store(keep, Ordering::Relaxed);
+    }
+
+    pub fn drain_and_remove(&self) -> Vec<Cleaned> {
+        let drained: Vec<Entry> = self.paths.lock().drain(..).collect();
+        let keep = self.keep.load(Ordering::Relaxed);
+        drained
+            .into_iter()
+            .map(|entry| {
+                if keep {
+                    return Cleaned::Kept(entry.dst);
+                }
+                match entry.partial {
+                    Partial::Remove => {
+                        let _ = std::fs::remove_file(&entry.path);
+                        Cleaned::Removed(entry.dst)
+                    }
+                    Partial::TruncateTo(len) => {
+                        let _ = OpenOptions::new()
+                            .write(true)
+                            .open(&entry.path)
+                            .and_then(|f| f.set_len(len));
+                        Cleaned::Truncated(entry.dst, len)
+                    }
+                }
+            })
+            .collect()
     }
 
     #[cfg(test)]
@@ -61,7 +132,7 @@ mod tests {
         let r = CleanupRegistry::new();
         let p = PathBuf::from("/tmp/does-not-matter-42");
         assert!(r.is_empty());
-        r.register(&p);
+        r.register_partial(&p, &p, Partial::Remove);
         assert_eq!(r.len(), 1);
         r.unregister(&p);
         assert!(r.is_empty());
@@ -75,8 +146,8 @@ mod tests {
         std::fs::write(&a, b"hi").unwrap();
         std::fs::write(&b, b"yo").unwrap();
         let r = CleanupRegistry::new();
-        r.register(&a);
-        r.register(&b);
+        r.register_partial(&a, &a, Partial::Remove);
+        r.register_partial(&b, &b, Partial::Remove);
         r.drain_and_remove();
         assert!(!a.exists());
         assert!(!b.exists());
@@ -86,7 +157,40 @@ mod tests {
     #[test]
     fn drain_tolerates_missing_files() {
         let r = CleanupRegistry::new();
-        r.register(Path::new("/tmp/this-does-not-exist-xyz"));
+        let missing = Path::new("/tmp/this-does-not-exist-xyz");
+        r.register_partial(missing, missing, Partial::Remove);
         r.drain_and_remove();
     }
+
+    #[test]
+    fn drain_truncates_in_place_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("resumed.bin");
+        std::fs::write(&dst, vec![1u8; 4096]).unwrap();
+        let r = CleanupRegistry::new();
+        r.register_partial(&dst, &dst, Partial::TruncateTo(1024));
+        assert_eq!(
+            r.drain_and_remove(),
+            vec![Cleaned::Truncated(dst.clone(), 1024)]
+        );
+        assert_eq!(std::fs::metadata(&dst).unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn keep_partial_leaves_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = dir.path().join(".a.tmp");
+        let dst = dir.path().join("a");
+        std::fs::write(&temp, b"half").unwrap();
+        let r = CleanupRegistry::new();
+        r.set_keep_partial(true);
+        r.register_partial(&temp, &dst, Partial::Remove);
+        let cleaned = r.drain_and_remove();
+        assert_eq!(cleaned, vec![Cleaned::Kept(dst.clone())]);
+        assert_eq!(
+            cleaned[0].to_string(),
+            format!("partial '{}' kept", dst.display())
+        );
+        assert!(temp.exists());
+    }
 }
@@ -0,0 +1,233 @@
+//! `--device-source`: reading a disk or partition like a file. A device
+//! stats with a length of 0, so its size has to be asked of the driver.
+
+use std::fs::{File, Metadata};
+use std::io::{self, Seek, SeekFrom};
+use std::path::Path;
+
+/// Whether `md` is a disk bcmr can read as a source: a block device, or on
+/// macOS and the BSDs, where raw disks are character devices, one of those.
+#[cfg(unix)]
+pub fn is_device(md: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    let ft = md.file_type();
+    ft.is_block_device()
+        || (cfg!(any(target_os = "macos", target_os = "freebsd")) && ft.is_char_device())
+}
+
+#[cfg(not(unix))]
+pub fn is_device(_md: &Metadata) -> bool {
+    false
+}
+
+/// Whether `path`, followed through links, is a device by `is_device`.
+pub fn is_device_path(path: &Path) -> bool {
+    path.metadata().is_ok_and(|md| is_device(&md))
+}
+
+/// The calls `device_size` makes, so tests can stand in for a disk.
+pub trait SizeQuery {
+    /// What the driver reports, in bytes.
+    fn ioctl_size(&mut self) -> io::Result<u64>;
+    /// How far the device seeks, for drivers without the ioctl.
+    fn seek_end(&mut self) -> io::Result<u64>;
+}
+
+impl SizeQuery for File {
+    #[cfg(target_os = "linux")]
+    fn ioctl_size(&mut self) -> io::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+        // linux/fs.h: _IOR(0x12, 114, size_t), in the encoding of the
+        // architectures whose read bit and size field sit elsewhere.
+        #[cfg(any(
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "powerpc",
+            target_arch = "powerpc64",
+            target_arch = "sparc64"
+        ))]
+        const BLKGETSIZE64: libc::c_ulong = 0x4008_1272;
+        #[cfg(not(any(
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "powerpc",
+            target_arch = "powerpc64",
+            target_arch = "sparc64"
+        )))]
+        const BLKGETSIZE64: libc::c_ulong = 0x8008_1272;
+        let mut size: u64 = 0;
+        // SAFETY: BLKGETSIZE64 writes one u64 through the pointer.
+        if unsafe { libc::ioctl(self.as_raw_fd(), BLKGETSIZE64 as _, &mut size) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(size)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn ioctl_size(&mut self) -> io::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+        // sys/disk.h: _IOR('d', 24, uint32_t) and _IOR('d', 25, uint64_t).
+        const DKIOCGETBLOCKSIZE: libc::c_ulong = 0x4004_6418;
+        const DKIOCGETBLOCKCOUNT: libc::c_ulong = 0x4008_6419;
+        let (mut block_size, mut blocks): (u32, u64) = (0, 0);
+        // SAFETY: each ioctl writes one integer of the type given above.
+        unsafe {
+            if libc::ioctl(self.as_raw_fd(), DKIOCGETBLOCKSIZE, &mut block_size) == -1
+                || libc::ioctl(self.as_raw_fd(), DKIOCGETBLOCKCOUNT, &mut blocks) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(u64::from(block_size) * blocks)
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn ioctl_size(&mut self) -> io::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+        // sys/disk.h: _IOR('d', 129, off_t).
+        const DIOCGMEDIASIZE: libc::c_ulong = 0x4008_6481;
+        let mut size: libc::off_t = 0;
+        // SAFETY: DIOCGMEDIASIZE writes one off_t through the pointer.
+        if unsafe { libc::ioctl(self.as_raw_fd(), DIOCGMEDIASIZE, &mut size) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(size as u64)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+    fn ioctl_size(&mut self) -> io::Result<u64> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn seek_end(&mut self) -> io::Result<u64> {
+        let end = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(0))?;
+        Ok(end)
+    }
+}
+
+/// The device's size in bytes: what the driver reports, else how far it
+/// seeks. A size of 0 either way is an error, since copying nothing from
+/// a disk is never what was meant.
+pub fn device_size(dev: &mut impl SizeQuery) -> io::Result<u64> {
+    let reported = dev.ioctl_size();
+    if let Ok(size @ 1..) = reported {
+        return Ok(size);
+    }
+    match dev.seek_end() {
+        Ok(size @ 1..) => Ok(size),
+        _ => Err(io::Error::other(match reported {
+            Err(e) => format!("cannot tell the device's size: {}", e),
+            Ok(_) => "the device reports a size of 0".to_string(),
+        })),
+    }
+}
+
+/// Bytes a copy from `path` reads: the file's length, or a device's size.
+pub fn source_size(path: &Path) -> io::Result<u64> {
+    let md = path.metadata()?;
+    if !is_device(&md) {
+        return Ok(md.len());
+    }
+    device_size(&mut File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A disk that answers each query with the value or error given.
+    struct FakeDisk {
+        ioctl: io::Result<u64>,
+        seek: io::Result<u64>,
+        seeked: bool,
+    }
+
+    impl FakeDisk {
+        fn new(ioctl: io::Result<u64>, seek: io::Result<u64>) -> Self {
+            Self {
+                ioctl,
+                seek,
+                seeked: false,
+            }
+        }
+    }
+
+    fn take(result: &mut io::Result<u64>) -> io::Result<u64> {
+        std::mem::replace(result, Ok(0))
+    }
+
+    impl SizeQuery for FakeDisk {
+        fn ioctl_size(&mut self) -> io::Result<u64> {
+            take(&mut self.ioctl)
+        }
+
+        fn seek_end(&mut self) -> io::Result<u64> {
+            self.seeked = true;
+            take(&mut self.seek)
+        }
+    }
+
+    #[test]
+    fn test_the_driver_answer_comes_first() {
+        let mut disk = FakeDisk::new(Ok(512 << 20), Ok(1));
+        assert_eq!(device_size(&mut disk).unwrap(), 512 << 20);
+        assert!(!disk.seeked);
+    }
+
+    #[test]
+    fn test_seeking_stands_in_for_a_missing_ioctl() {
+        let mut disk = FakeDisk::new(Err(io::ErrorKind::Unsupported.into()), Ok(4096));
+        assert_eq!(device_size(&mut disk).unwrap(), 4096);
+        let mut disk = FakeDisk::new(Ok(0), Ok(8192));
+        assert_eq!(device_size(&mut disk).unwrap(), 8192);
+    }
+
+    #[test]
+    fn test_no_size_is_an_error() {
+        let mut disk = FakeDisk::new(Err(io::Error::from_raw_os_error(25)), Ok(0));
+        let err = device_size(&mut disk).unwrap_err().to_string();
+        assert!(err.starts_with("cannot tell the device's size: "), "{err}");
+        let mut disk = FakeDisk::new(Ok(0), Err(io::ErrorKind::InvalidInput.into()));
+        assert_eq!(
+            device_size(&mut disk).unwrap_err().to_string(),
+            "the device reports a size of 0"
+        );
+    }
+
+    #[test]
+    fn test_regular_files_keep_their_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("f");
+        std::fs::write(&path, b"12345").unwrap();
+        assert!(!is_device_path(&path));
+        assert_eq!(source_size(&path).unwrap(), 5);
+    }
+
+    /// Attaches a loop device, which needs root; skipped where that fails.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_loop_device_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("disk.img");
+        std::fs::write(&image, vec![7u8; 3 << 20]).unwrap();
+        let Ok(output) = std::process::Command::new("losetup")
+            .args(["--find", "--show"])
+            .arg(&image)
+            .output()
+        else {
+            return;
+        };
+        if !output.status.success() {
+            return;
+        }
+        let dev = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let size = source_size(Path::new(&dev));
+        let is_device = is_device_path(Path::new(&dev));
+        let _ = std::process::Command::new("losetup")
+            .args(["-d", &dev])
+            .status();
+        assert!(is_device);
+        assert_eq!(size.unwrap(), 3 << 20);
+    }
+}
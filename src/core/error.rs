@@ -33,8 +33,56 @@ pub enum BcmrError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
-    #[error("Verification failed for '{0}'")]
-    VerificationError(PathBuf),
+    #[error("Verification failed for '{}'{}", .0.display(), .1.map(|m| format!(": {}", m)).unwrap_or_default())]
+    VerificationError(PathBuf, Option<crate::core::checksum::Mismatch>),
+
+    #[error(
+        "{} files failed verification: {}",
+        .0.len(),
+        .0.iter().map(|p| format!("'{}'", p.display())).collect::<Vec<_>>().join(", ")
+    )]
+    VerificationFailures(Vec<PathBuf>),
+
+    #[error(
+        "'{}' is mounted read-only; nothing under it can be written or removed",
+        .0.display()
+    )]
+    ReadOnlyDestination(PathBuf),
+
+    #[error(
+        "Paths too long for the destination ({}): {}; pass --skip-too-long to copy the rest",
+        .0,
+        .1.iter().map(|p| format!("'{}'", p.display())).collect::<Vec<_>>().join(", ")
+    )]
+    NamesTooLong(crate::core::preflight::NameLimits, Vec<PathBuf>),
+
+    #[error("Destination '{}' is in use by {}", .0.display(), .1)]
+    Locked(PathBuf, String),
+
+    #[error("Cannot watch '{}': {}", .0.display(), .1)]
+    Watch(PathBuf, String),
+
+    #[error("{0}")]
+    SshConnection(String),
+
+    #[error("{0}")]
+    SshAuth(String),
+
+    #[error("{0}")]
+    RemoteIo(String),
+
+    #[error("{0}")]
+    HttpConnection(String),
+
+    #[error("{0}")]
+    HttpTimeout(String),
+
+    #[error("{url}: HTTP {status} {reason}")]
+    HttpStatus {
+        url: String,
+        status: u16,
+        reason: String,
+    },
 
     #[error("Operation cancelled")]
     Cancelled,
@@ -42,3 +90,28 @@ pub enum BcmrError {
     #[error("Cryptographic failure: {0}")]
     CryptoFailure(String),
 }
+
+/// The exit status of a run that finished but warned along the way, so a
+/// script can tell it from a clean one; `--warnings-ok` makes it 0.
+pub const EXIT_WARNINGS: i32 = 10;
+
+impl BcmrError {
+    /// The exit status a run ending in this error gets. Remote failures
+    /// have their own, so a script can tell a host that is down from keys
+    /// that are refused from a remote disk that is full. HTTP sources use
+    /// the same three: unreachable, refused (401/403/407), anything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BcmrError::SshConnection(_)
+            | BcmrError::HttpConnection(_)
+            | BcmrError::HttpTimeout(_) => 3,
+            BcmrError::SshAuth(_)
+            | BcmrError::HttpStatus {
+                status: 401 | 403 | 407,
+                ..
+            } => 4,
+            BcmrError::RemoteIo(_) | BcmrError::HttpStatus { .. } => 5,
+            _ => 1,
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::checksum::{self, Algorithm};
+use crate::core::oplog::{timestamp, Action, WriteKind};
+
+/// The per-user journal `bcmr undo` reads: one JSON line per completed
+/// copy, move and removal, grouped by the id of the run that did them.
+struct Journal {
+    file: File,
+    id: String,
+    args: Vec<String>,
+    begun: bool,
+}
+
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub id: String,
+    pub time: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// First record of a run, written with its first action.
+    Begin {
+        args: Vec<String>,
+        cwd: PathBuf,
+    },
+    Copy {
+        src: PathBuf,
+        dst: PathBuf,
+        /// The copy made `dst` rather than overwriting or appending to it.
+        created: bool,
+        #[serde(flatten)]
+        stamp: Stamp,
+    },
+    /// `--verify` read `path` back with this digest; undo checks a created
+    /// copy against it instead of hashing the source again.
+    Verified {
+        path: PathBuf,
+        algorithm: Algorithm,
+        digest: String,
+    },
+    Move {
+        src: PathBuf,
+        dst: PathBuf,
+        #[serde(flatten)]
+        stamp: Stamp,
+    },
+    Remove {
+        path: PathBuf,
+    },
+    /// A directory that was not there before the copy.
+    CreateDir {
+        path: PathBuf,
+    },
+    /// `bcmr undo` went through the run `operation`.
+    Undone {
+        operation: String,
+    },
+}
+
+/// Size and mtime of a destination right after it was written, to tell
+/// whether it has been touched since.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stamp {
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime_ns: Option<u64>,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let md = std::fs::symlink_metadata(path).ok()?;
+        Some(Self {
+            size: md.len(),
+            mtime_ns: md
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos() as u64),
+        })
+    }
+}
+
+/// `$XDG_STATE_HOME/bcmr/journal.jsonl`, or the platform's equivalent.
+pub fn default_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("journal.jsonl"))
+}
+
+/// `$XDG_STATE_HOME/bcmr`, or the platform's equivalent.
+pub fn state_dir() -> Option<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            let dirs = directories::BaseDirs::new()?;
+            Some(
+                dirs.state_dir()
+                    .unwrap_or_else(|| dirs.data_local_dir())
+                    .to_path_buf(),
+            )
+        })?;
+    Some(state.join("bcmr"))
+}
+
+/// Journals this run's actions to `path`. Nothing is written until the
+/// first action, so runs that change nothing leave no trace. A journal of
+/// `max_size` bytes or more is first moved aside to `rotated(path)`,
+/// replacing the one moved there before.
+pub fn open(path: &Path, args: &[String], max_size: u64) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if max_size > 0 && std::fs::metadata(path).is_ok_and(|md| md.len() >= max_size) {
+        std::fs::rename(path, rotated(path))?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *JOURNAL.lock() = Some(Journal {
+        file,
+        id: new_id(),
+        args: args.to_vec(),
+        begun: false,
+    });
+    Ok(())
+}
+
+/// Where the previous journal goes when `path` is rotated: `journal.jsonl.1`.
+pub fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+fn new_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&nanos.to_le_bytes());
+    hasher.update(&std::process::id().to_le_bytes());
+    hasher.finalize().to_hex()[..8].to_string()
+}
+
+/// Called from `oplog::report` for every action; skips and errors change
+/// nothing and are left out.
+pub(crate) fn record(action: &Action<'_>) {
+    if JOURNAL.lock().is_none() {
+        return;
+    }
+    let event = match *action {
+        Action::Copy {
+            src, dst, write, ..
+        } => Event::Copy {
+            src: absolute(src),
+            dst: absolute(dst),
+            created: matches!(write, WriteKind::Created),
+            stamp: Stamp::of(dst).unwrap_or_default(),
+        },
+        Action::Move { src, dst, .. } => Event::Move {
+            src: absolute(src),
+            dst: absolute(dst),
+            stamp: Stamp::of(dst).unwrap_or_default(),
+        },
+        Action::Remove { path } => Event::Remove {
+            path: absolute(path),
+        },
+        Action::Skip { .. } | Action::Error { .. } => return,
+    };
+    append(event);
+}
+
+/// Called next to `journal::verified` with the digest `--verify` read back
+/// from `dst`, so undo can reuse it.
+pub fn verified(dst: &Path, algorithm: Algorithm, digest: &str) {
+    if JOURNAL.lock().is_none() {
+        return;
+    }
+    append(Event::Verified {
+        path: absolute(dst),
+        algorithm,
+        digest: digest.to_string(),
+    });
+}
+
+/// Called from `oplog::dir_created`.
+pub(crate) fn dir_created(path: &Path) {
+    if JOURNAL.lock().is_none() {
+        return;
+    }
+    append(Event::CreateDir {
+        path: absolute(path),
+    });
+}
+
+fn append(event: Event) {
+    let mut journal = JOURNAL.lock();
+    let Some(journal) = journal.as_mut() else {
+        return;
+    };
+    if !journal.begun {
+        journal.begun = true;
+        let begin = Event::Begin {
+            args: std::mem::take(&mut journal.args),
+            cwd: std::env::current_dir().unwrap_or_default(),
+        };
+        let _ = write(&mut journal.file, &journal.id, begin);
+    }
+    // A journal that cannot be written must not fail the operation.
+    let _ = write(&mut journal.file, &journal.id, event);
+}
+
+/// What undo compares a copied file against before removing it.
+pub fn digest(path: &Path, algorithm: Algorithm) -> Option<String> {
+    checksum::calculate_hash(path, algorithm).ok()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn write(file: &mut File, id: &str, event: Event) -> io::Result<()> {
+    let record = Record {
+        id: id.to_string(),
+        time: timestamp(SystemTime::now()),
+        event,
+    };
+    let mut line = serde_json::to_vec(&record)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    file.flush()
+}
+
+/// Marks `operation` as undone, so `undo --last` moves on to the one before.
+pub fn mark_undone(path: &Path, operation: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    write(
+        &mut file,
+        &new_id(),
+        Event::Undone {
+            operation: operation.to_string(),
+        },
+    )
+}
+
+/// Every record in the journal and the one rotated out before it, oldest
+/// first. Lines that do not parse (a write cut short, a newer format) are
+/// passed over.
+pub fn load(path: &Path) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for path in [rotated(path), path.to_path_buf()] {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(file).lines() {
+            if let Ok(record) = serde_json::from_str(&line?) {
+                records.push(record);
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trip_and_bad_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let record = Record {
+            id: "0a1b2c3d".into(),
+            time: "2026-01-02T03:04:05Z".into(),
+            event: Event::Move {
+                src: "/a/x".into(),
+                dst: "/b/x".into(),
+                stamp: Stamp {
+                    size: 3,
+                    mtime_ns: Some(7),
+                },
+            },
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            line,
+            r#"{"id":"0a1b2c3d","time":"2026-01-02T03:04:05Z","type":"move","src":"/a/x","dst":"/b/x","size":3,"mtime_ns":7}"#
+        );
+        std::fs::write(&path, format!("{line}\n{{\"id\":\"cut\n")).unwrap();
+        mark_undone(&path, "0a1b2c3d").unwrap();
+
+        let records = load(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], record);
+        assert_eq!(
+            records[1].event,
+            Event::Undone {
+                operation: "0a1b2c3d".into()
+            }
+        );
+        assert!(load(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_full_journal_is_rotated_and_still_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        mark_undone(&path, "old").unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        open(&path, &[], size + 1).unwrap();
+        assert!(!rotated(&path).exists());
+        open(&path, &[], size).unwrap();
+        *JOURNAL.lock() = None;
+        assert!(rotated(&path).exists());
+        assert_eq!(rotated(&path), dir.path().join("journal.jsonl.1"));
+
+        mark_undone(&path, "new").unwrap();
+        let operations: Vec<String> = load(&path)
+            .unwrap()
+            .into_iter()
+            .filter_map(|r| match r.event {
+                Event::Undone { operation } => Some(operation),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(operations, ["old", "new"]);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::core::error::BcmrError;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_RANGE, RANGE};
+use reqwest::{Client, Response, StatusCode};
+use std::error::Error as _;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a download may go without a single byte before it is given up.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether a copy source is an HTTP(S) URL rather than a path or `host:path`.
+pub fn is_url(s: &str) -> bool {
+    let lower = s.get(..8).unwrap_or(s).to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// The file name a download lands under when the destination is a
+/// directory: the URL's last path segment, decoded, or `index.html` when
+/// the path names none.
+pub fn file_name(url: &str) -> String {
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .split_once("://")
+        .map_or("", |(_, rest)| rest.split_once('/').map_or("", |(_, p)| p));
+    let name = percent_decode(path.rsplit('/').next().unwrap_or(""));
+    match name.as_str() {
+        "" | "." | ".." => "index.html".to_string(),
+        _ if name.contains(['/', '\\', '\0']) => "index.html".to_string(),
+        _ => name,
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parses `--header "Name: value"` arguments.
+pub fn parse_headers(raw: &[String]) -> Result<HeaderMap, BcmrError> {
+    let mut headers = HeaderMap::new();
+    for h in raw {
+        let invalid = || BcmrError::InvalidInput(format!("Invalid --header '{}'", h));
+        let (name, value) = h.split_once(':').ok_or_else(|| {
+            BcmrError::InvalidInput(format!(
+                "Invalid --header '{}': expected \"Name: value\"",
+                h
+            ))
+        })?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+        let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// A client that follows redirects and honors the `HTTP(S)_PROXY`/
+/// `NO_PROXY` environment, sending `headers` with every request.
+pub fn client(headers: HeaderMap) -> Result<Client, BcmrError> {
+    Client::builder()
+        .user_agent(concat!("bcmr/", env!("CARGO_PKG_VERSION")))
+        .default_headers(headers)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()
+        .map_err(|e| request_error("HTTP client", &e))
+}
+
+pub struct Download {
+    pub response: Response,
+    /// Bytes the body will carry, when the server said.
+    pub length: Option<u64>,
+    /// The server honored the range: the body continues from the offset
+    /// asked for rather than starting over.
+    pub resumed: bool,
+}
+
+pub enum Opened {
+    Body(Download),
+    /// A resume asked for bytes past the end: the file is already whole.
+    Complete,
+}
+
+/// Requests `url`, from byte `offset` on when it is nonzero.
+pub async fn open(client: &Client, url: &str, offset: u64) -> Result<Opened, BcmrError> {
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let response = request.send().await.map_err(|e| request_error(url, &e))?;
+    let status = response.status();
+
+    if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        if content_range(&response).and_then(|(_, total)| total) == Some(offset) {
+            return Ok(Opened::Complete);
+        }
+        return Err(status_error(url, status));
+    }
+    if !status.is_success() {
+        return Err(status_error(url, status));
+    }
+
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    if resumed && content_range(&response).map(|(start, _)| start) != Some(offset) {
+        return Err(BcmrError::HttpConnection(format!(
+            "{}: server answered the range request with different bytes",
+            url
+        )));
+    }
+    let length = response.content_length();
+    Ok(Opened::Body(Download {
+        response,
+        length,
+        resumed,
+    }))
+}
+
+/// `Content-Range: bytes START-END/TOTAL` (or `bytes */TOTAL`) as the start
+/// and the total, when the total is known.
+fn content_range(response: &Response) -> Option<(u64, Option<u64>)> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range
+        .split_once('-')
+        .and_then(|(s, _)| s.trim().parse().ok())
+        .unwrap_or(0);
+    Some((start, total.trim().parse().ok()))
+}
+
+pub fn status_error(url: &str, status: StatusCode) -> BcmrError {
+    BcmrError::HttpStatus {
+        url: url.to_string(),
+        status: status.as_u16(),
+        reason: status.canonical_reason().unwrap_or("").to_string(),
+    }
+}
+
+/// Sorts a failed request or read into the error it should exit with,
+/// keeping the underlying cause (reqwest's own message rarely names it).
+pub fn request_error(url: &str, e: &reqwest::Error) -> BcmrError {
+    if let Some(status) = e.status() {
+        return status_error(url, status);
+    }
+    let mut detail = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        detail.push_str(": ");
+        detail.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    if e.is_timeout() {
+        BcmrError::HttpTimeout(format!("{}: timed out ({})", url, detail))
+    } else {
+        BcmrError::HttpConnection(format!("{}: {}", url, detail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/big.iso"));
+        assert!(is_url("HTTP://example.com"));
+        assert!(!is_url("host:path"));
+        assert!(!is_url("./https:/x"));
+        assert!(!is_url("http"));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("https://example.com/big.iso"), "big.iso");
+        assert_eq!(
+            file_name("https://example.com/a/b%20c.txt?x=1#f"),
+            "b c.txt"
+        );
+        assert_eq!(file_name("https://example.com/"), "index.html");
+        assert_eq!(file_name("https://example.com"), "index.html");
+        assert_eq!(file_name("https://example.com/a/.."), "index.html");
+        assert_eq!(file_name("https://example.com/%2e%2e"), "index.html");
+        assert_eq!(file_name("https://example.com/a%2Fb"), "index.html");
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let h = parse_headers(&["Authorization: Bearer t0k".into(), "X-A:1".into()]).unwrap();
+        assert_eq!(h["authorization"], "Bearer t0k");
+        assert_eq!(h["x-a"], "1");
+        assert!(parse_headers(&["no colon".into()]).is_err());
+        assert!(parse_headers(&["bad name: v".into()]).is_err());
+    }
+}
@@ -37,6 +37,17 @@ pub async fn fsync_dir_async(dir: &Path) {
     let _ = tokio::task::spawn_blocking(move || fsync_dir(&dir)).await;
 }
 
+/// `Path::exists` for async code: the stat runs on the blocking pool.
+pub async fn exists(path: &Path) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or(false)
+}
+
+/// Metadata following links, or `None` where `Path::metadata` would fail,
+/// without blocking a runtime worker.
+pub async fn stat(path: &Path) -> Option<std::fs::Metadata> {
+    tokio::fs::metadata(path).await.ok()
+}
+
 #[cfg(unix)]
 pub fn get_inode(path: &Path) -> io::Result<u64> {
     use std::os::unix::fs::MetadataExt;
@@ -0,0 +1,409 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::checksum::Algorithm;
+use crate::core::oplog::{self, timestamp, Action, OperationStats, WriteKind};
+
+/// Bumped whenever a record changes shape; readers check it in the header.
+pub const VERSION: u32 = 1;
+
+/// The `--journal` audit trail: a header describing the invocation, one
+/// record per file as it is decided, and a footer with the totals.
+struct Journal {
+    file: File,
+    path: PathBuf,
+    strict: bool,
+    warned: bool,
+}
+
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    Header(Header),
+    Action(ActionRecord),
+    Verify(VerifyRecord),
+    Footer(Footer),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub started: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Create,
+    Overwrite,
+    Append,
+    Move,
+    Remove,
+    Skip,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ActionRecord {
+    pub time: String,
+    pub action: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<PathBuf>,
+    /// The destination, or the path removed, skipped or failed.
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub outcome: Outcome,
+    /// Why a file was skipped or failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Digests from `--verify`, written when the destination has been read back.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifyRecord {
+    pub time: String,
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub algorithm: String,
+    pub digest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dst_digest: Option<String>,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Footer {
+    pub finished: String,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub stats: OperationStats,
+}
+
+/// Starts a journal at `path`, appending to what is there, and writes the
+/// header. With `strict`, a record that cannot be written ends the run;
+/// otherwise it is a warning.
+pub fn open(path: &Path, args: &[String], strict: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let header = Entry::Header(Header {
+        version: VERSION,
+        started: timestamp(SystemTime::now()),
+        args: args.to_vec(),
+        cwd: std::env::current_dir().unwrap_or_default(),
+        uid: uid(),
+        hostname: hostname(),
+    });
+    write_entry(&mut file, &header)?;
+    *JOURNAL.lock() = Some(Journal {
+        file,
+        path: path.to_path_buf(),
+        strict,
+        warned: false,
+    });
+    Ok(())
+}
+
+/// Called from `oplog::report` for every action.
+pub(crate) fn record(action: &Action<'_>) {
+    if JOURNAL.lock().is_none() {
+        return;
+    }
+    write(&Entry::Action(action_record(action)));
+}
+
+fn action_record(action: &Action<'_>) -> ActionRecord {
+    let mut record = ActionRecord {
+        time: timestamp(SystemTime::now()),
+        action: Kind::Error,
+        src: None,
+        path: PathBuf::new(),
+        size: None,
+        outcome: Outcome::Ok,
+        detail: None,
+    };
+    match *action {
+        Action::Copy {
+            src,
+            dst,
+            bytes,
+            write,
+        } => {
+            record.action = match write {
+                WriteKind::Created => Kind::Create,
+                WriteKind::Overwritten => Kind::Overwrite,
+                WriteKind::Appended => Kind::Append,
+            };
+            record.src = Some(absolute(src));
+            record.path = absolute(dst);
+            record.size = Some(bytes);
+        }
+        Action::Move { src, dst, bytes } => {
+            record.action = Kind::Move;
+            record.src = Some(absolute(src));
+            record.path = absolute(dst);
+            record.size = (bytes > 0).then_some(bytes);
+        }
+        Action::Remove { path } => {
+            record.action = Kind::Remove;
+            record.path = absolute(path);
+        }
+        Action::Skip { path, reason } => {
+            record.action = Kind::Skip;
+            record.path = absolute(path);
+            record.outcome = Outcome::Skipped;
+            record.detail = Some(reason.to_string());
+        }
+        Action::Error { path, message } => {
+            record.path = absolute(path);
+            record.outcome = Outcome::Failed;
+            record.detail = Some(message.to_string());
+        }
+    }
+    record
+}
+
+/// Records the digests `--verify` compared for one file.
+pub fn verified(src: &Path, dst: &Path, algorithm: Algorithm, digest: &str, dst_digest: &str) {
+    if JOURNAL.lock().is_none() {
+        return;
+    }
+    let matched = digest == dst_digest;
+    write(&Entry::Verify(VerifyRecord {
+        time: timestamp(SystemTime::now()),
+        src: absolute(src),
+        dst: absolute(dst),
+        algorithm: algorithm.to_string(),
+        digest: digest.to_string(),
+        dst_digest: (!matched).then(|| dst_digest.to_string()),
+        outcome: if matched {
+            Outcome::Ok
+        } else {
+            Outcome::Failed
+        },
+    }));
+}
+
+/// Writes the footer and syncs the journal to disk. Does nothing when no
+/// journal is open, so it is safe to call on every exit path.
+pub fn finish(error: Option<&str>) -> io::Result<()> {
+    let Some(mut journal) = JOURNAL.lock().take() else {
+        return Ok(());
+    };
+    let footer = Entry::Footer(Footer {
+        finished: timestamp(SystemTime::now()),
+        outcome: if error.is_some() {
+            Outcome::Failed
+        } else {
+            Outcome::Ok
+        },
+        error: error.map(str::to_string),
+        stats: oplog::stats(),
+    });
+    write_entry(&mut journal.file, &footer)?;
+    journal.file.sync_all()
+}
+
+fn write(entry: &Entry) {
+    let mut guard = JOURNAL.lock();
+    let Some(journal) = guard.as_mut() else {
+        return;
+    };
+    let Err(e) = write_entry(&mut journal.file, entry) else {
+        return;
+    };
+    if journal.strict {
+        // The partial files of whatever is in flight go too, as on Ctrl+C.
+        eprintln!(
+            "bcmr: cannot write journal '{}': {} (--journal-strict)",
+            journal.path.display(),
+            e
+        );
+        crate::core::cleanup::global().drain_and_remove();
+        crate::core::lock::release();
+        crate::core::perms::restore();
+        std::process::exit(1);
+    }
+    if !journal.warned {
+        journal.warned = true;
+        let path = journal.path.clone();
+        drop(guard);
+        oplog::warn(&path, &format!("cannot write journal: {e}"));
+    }
+}
+
+fn write_entry(file: &mut File, entry: &Entry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    file.write_all(&line)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn uid() -> Option<u32> {
+    Some(unsafe { libc::getuid() })
+}
+
+#[cfg(not(unix))]
+fn uid() -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..end]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// Every entry in a journal file, in order. A line that does not parse is
+/// an error naming its line number; a journal from a newer bcmr is refused.
+pub fn load(path: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", index + 1, e),
+            )
+        })?;
+        if let Entry::Header(h) = &entry {
+            if h.version > VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "line {}: journal version {} is newer than this bcmr reads ({})",
+                        index + 1,
+                        h.version,
+                        VERSION
+                    ),
+                ));
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_records_by_kind() {
+        let copy = action_record(&Action::Copy {
+            src: Path::new("/a/x"),
+            dst: Path::new("/b/x"),
+            bytes: 5,
+            write: WriteKind::Overwritten,
+        });
+        assert_eq!(copy.action, Kind::Overwrite);
+        assert_eq!(copy.src.as_deref(), Some(Path::new("/a/x")));
+        assert_eq!((copy.size, copy.outcome), (Some(5), Outcome::Ok));
+
+        let skip = action_record(&Action::Skip {
+            path: Path::new("/b/y"),
+            reason: "exists",
+        });
+        assert_eq!((skip.action, skip.outcome), (Kind::Skip, Outcome::Skipped));
+        assert_eq!(skip.detail.as_deref(), Some("exists"));
+
+        let error = action_record(&Action::Error {
+            path: Path::new("/b/z"),
+            message: "boom",
+        });
+        assert_eq!(error.outcome, Outcome::Failed);
+
+        // Relative paths are recorded absolute.
+        let removed = action_record(&Action::Remove {
+            path: Path::new("rel/file"),
+        });
+        assert!(removed.path.is_absolute());
+        assert!(removed.path.ends_with("rel/file"));
+    }
+
+    #[test]
+    fn entries_round_trip_and_newer_versions_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("j.jsonl");
+        let entries = vec![
+            Entry::Header(Header {
+                version: VERSION,
+                started: "2026-01-01T00:00:00Z".into(),
+                args: vec!["bcmr".into(), "copy".into()],
+                cwd: "/".into(),
+                uid: Some(1000),
+                hostname: Some("box".into()),
+            }),
+            Entry::Action(action_record(&Action::Move {
+                src: Path::new("/a"),
+                dst: Path::new("/b"),
+                bytes: 0,
+            })),
+            Entry::Footer(Footer {
+                finished: "2026-01-01T00:00:01Z".into(),
+                outcome: Outcome::Ok,
+                error: None,
+                stats: OperationStats {
+                    moved: 1,
+                    ..OperationStats::default()
+                },
+            }),
+        ];
+        let mut file = File::create(&path).unwrap();
+        for entry in &entries {
+            write_entry(&mut file, entry).unwrap();
+        }
+        assert_eq!(load(&path).unwrap(), entries);
+
+        let line = serde_json::to_string(&entries[1]).unwrap();
+        assert!(line.starts_with(r#"{"type":"action","time":"#), "{line}");
+        assert!(line.contains(r#""action":"move","src":"/a","path":"/b","outcome":"ok""#));
+
+        std::fs::write(
+            &path,
+            r#"{"type":"header","version":99,"started":"","args":[],"cwd":"/"}"#,
+        )
+        .unwrap();
+        let err = load(&path).unwrap_err();
+        assert!(err.to_string().contains("version 99"), "{err}");
+        std::fs::write(&path, "{}\n").unwrap();
+        assert!(load(&path).unwrap_err().to_string().starts_with("line 1"));
+    }
+}
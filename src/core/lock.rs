@@ -0,0 +1,227 @@
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::BcmrError;
+use crate::core::oplog::{self, timestamp};
+
+/// How often a run waiting with `--wait-lock` tries again.
+const POLL: Duration = Duration::from_millis(200);
+
+/// The destination lock this run holds, so that two runs into one tree
+/// (overlapping cron jobs, say) cannot interleave their writes.
+struct Held {
+    file: File,
+    path: PathBuf,
+}
+
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+/// What a lock file says about the run holding it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub started: String,
+    pub destination: PathBuf,
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pid {} (started {})", self.pid, self.started)
+    }
+}
+
+/// `path` with symlinks and `..` resolved as far as it exists, so every
+/// spelling of one destination takes the same lock.
+pub fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let abs = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (abs.parent(), abs.file_name()) {
+        (Some(parent), Some(name)) => canonical(parent).join(name),
+        _ => abs,
+    }
+}
+
+/// The lock file for `destination` (already canonical) in `dir`.
+pub fn lock_path(dir: &Path, destination: &Path) -> PathBuf {
+    let key = blake3::hash(destination.as_os_str().as_encoded_bytes()).to_hex();
+    dir.join(format!("{}.lock", &key[..16]))
+}
+
+/// Takes the lock on `destination` for the rest of the run. A lock another
+/// run holds is waited on for up to `wait`, or without it is an error
+/// naming the holder. A lock left by a run that died is taken over.
+pub async fn acquire(
+    dir: &Path,
+    destination: &Path,
+    wait: Option<Duration>,
+) -> Result<(), BcmrError> {
+    std::fs::create_dir_all(dir)?;
+    let destination = canonical(destination);
+    let path = lock_path(dir, &destination);
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        let holder = match try_lock(&path)? {
+            Lock::Taken(mut file, stale) => {
+                if let Some(stale) = stale {
+                    oplog::warn(
+                        &destination,
+                        &format!("took over the lock left by bcmr {stale}, which is gone"),
+                    );
+                }
+                write_holder(&mut file, &destination)?;
+                *HELD.lock() = Some(Held { file, path });
+                return Ok(());
+            }
+            Lock::Busy(holder) => {
+                holder.map_or_else(|| "another bcmr".into(), |h| format!("bcmr {h}"))
+            }
+        };
+        match wait {
+            Some(wait) if started.elapsed() < wait => {
+                if !waiting {
+                    waiting = true;
+                    eprintln!(
+                        "Waiting for {} to finish with '{}'...",
+                        holder,
+                        destination.display()
+                    );
+                }
+                tokio::time::sleep(POLL).await;
+            }
+            Some(wait) => {
+                return Err(BcmrError::Locked(
+                    destination,
+                    format!("{holder}; gave up after {}s", wait.as_secs_f64()),
+                ))
+            }
+            None => {
+                return Err(BcmrError::Locked(
+                    destination,
+                    format!("{holder}; --wait-lock DURATION waits for it"),
+                ))
+            }
+        }
+    }
+}
+
+/// Drops the lock and its file. Safe to call on every exit path.
+pub fn release() {
+    if let Some(held) = HELD.lock().take() {
+        // Removed while still locked: a run that opened it meanwhile sees
+        // the file is gone once it gets the lock, and starts over.
+        let _ = std::fs::remove_file(&held.path);
+        drop(held.file);
+    }
+}
+
+enum Lock {
+    /// Ours now, with whoever the file still names if its run died.
+    Taken(File, Option<Holder>),
+    Busy(Option<Holder>),
+}
+
+fn try_lock(path: &Path) -> io::Result<Lock> {
+    loop {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let holder = read_holder(&mut file);
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(Lock::Busy(holder)),
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        if same_file(&file, path) {
+            return Ok(Lock::Taken(file, holder));
+        }
+    }
+}
+
+fn read_holder(file: &mut File) -> Option<Holder> {
+    let mut text = String::new();
+    file.read_to_string(&mut text).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_holder(file: &mut File, destination: &Path) -> io::Result<()> {
+    let holder = Holder {
+        pid: std::process::id(),
+        started: timestamp(SystemTime::now()),
+        destination: destination.to_path_buf(),
+    };
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(&serde_json::to_vec(&holder)?)?;
+    file.flush()
+}
+
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_of_one_destination_share_a_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        let plain = canonical(&dir.path().join("new"));
+        let roundabout = canonical(&dir.path().join("a/../new"));
+        assert_eq!(plain, roundabout);
+        assert_eq!(
+            lock_path(dir.path(), &plain),
+            lock_path(dir.path(), &roundabout)
+        );
+        assert_ne!(
+            lock_path(dir.path(), &plain),
+            lock_path(dir.path(), &canonical(&dir.path().join("a")))
+        );
+    }
+
+    #[test]
+    fn a_held_lock_is_busy_and_a_dead_one_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("x.lock");
+
+        let Lock::Taken(mut file, None) = try_lock(&path).unwrap() else {
+            panic!("a fresh lock should be free");
+        };
+        write_holder(&mut file, Path::new("/dst")).unwrap();
+        let Lock::Busy(Some(holder)) = try_lock(&path).unwrap() else {
+            panic!("a held lock should be busy");
+        };
+        assert_eq!(holder.pid, std::process::id());
+        assert_eq!(holder.destination, Path::new("/dst"));
+
+        // Dropped without removing the file, as when a run is killed.
+        drop(file);
+        let Lock::Taken(_file, Some(stale)) = try_lock(&path).unwrap() else {
+            panic!("a dead run's lock should be taken over");
+        };
+        assert_eq!(stale, holder);
+    }
+}
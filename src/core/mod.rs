@@ -1,10 +1,20 @@
+pub mod bwlimit;
 pub mod cas;
 pub mod checksum;
 pub mod cleanup;
 pub mod compress;
+pub mod device;
 pub mod error;
 pub mod framing;
+pub mod history;
+pub mod http;
 pub mod io;
+pub mod journal;
+pub mod lock;
+pub mod oplog;
+pub mod perms;
+pub mod preflight;
+pub mod priority;
 pub mod protocol;
 pub mod protocol_aead;
 pub mod remote;
@@ -13,3 +23,4 @@ pub mod serve_client;
 pub mod session;
 pub mod transport;
 pub mod traversal;
+pub mod verify_report;
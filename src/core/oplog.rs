@@ -0,0 +1,500 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::BcmrError;
+
+enum Sink {
+    Stderr,
+    File(File),
+}
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static HOLD_VERBOSE: AtomicBool = AtomicBool::new(false);
+static COPIES_MOVE: AtomicBool = AtomicBool::new(false);
+static VERBOSE: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+static COPIED: AtomicU64 = AtomicU64::new(0);
+static CREATED: AtomicU64 = AtomicU64::new(0);
+static OVERWRITTEN: AtomicU64 = AtomicU64::new(0);
+static APPENDED: AtomicU64 = AtomicU64::new(0);
+static DIRS_CREATED: AtomicU64 = AtomicU64::new(0);
+static MOVED: AtomicU64 = AtomicU64::new(0);
+static REMOVED: AtomicU64 = AtomicU64::new(0);
+static SKIPPED: AtomicU64 = AtomicU64::new(0);
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+static WARNED: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+static REFLINKED: AtomicU64 = AtomicU64::new(0);
+static HARDLINKED: AtomicU64 = AtomicU64::new(0);
+static METADATA_UPDATED: AtomicU64 = AtomicU64::new(0);
+
+/// What a copy did to its destination.
+#[derive(Clone, Copy)]
+pub enum WriteKind {
+    Created,
+    Overwritten,
+    Appended,
+}
+
+pub enum Action<'a> {
+    Copy {
+        src: &'a Path,
+        dst: &'a Path,
+        bytes: u64,
+        write: WriteKind,
+    },
+    /// `bytes` is what had to be copied: 0 for a rename.
+    Move {
+        src: &'a Path,
+        dst: &'a Path,
+        bytes: u64,
+    },
+    Remove {
+        path: &'a Path,
+    },
+    Skip {
+        path: &'a Path,
+        reason: &'a str,
+    },
+    Error {
+        path: &'a Path,
+        message: &'a str,
+    },
+}
+
+impl fmt::Display for Action<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Copy {
+                src, dst, bytes, ..
+            } => write!(
+                f,
+                "COPY {} -> {} {} bytes ok",
+                src.display(),
+                dst.display(),
+                bytes
+            ),
+            Action::Move { src, dst, .. } => {
+                write!(f, "MOVE {} -> {} ok", src.display(), dst.display())
+            }
+            Action::Remove { path } => write!(f, "REMOVE {} ok", path.display()),
+            Action::Skip { path, reason } => write!(f, "SKIP {} {}", path.display(), reason),
+            Action::Error { path, message } => write!(f, "ERROR {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Action<'_> {
+    // The wording predates the log file and mirrors cp/mv/rm -v, so scripts
+    // parsing verbose output keep working.
+    fn print_verbose(&self) {
+        let (line, stdout) = match self {
+            Action::Copy { src, dst, .. } => {
+                (format!("'{}' -> '{}'", src.display(), dst.display()), false)
+            }
+            Action::Move { src, dst, .. } => (
+                format!("renamed '{}' -> '{}'", src.display(), dst.display()),
+                false,
+            ),
+            Action::Remove { path } => (format!("removed {}", path.display()), true),
+            Action::Skip { .. } | Action::Error { .. } => return,
+        };
+        let on_terminal = if stdout {
+            io::stdout().is_terminal()
+        } else {
+            io::stderr().is_terminal()
+        };
+        if on_terminal && HOLD_VERBOSE.load(Ordering::Relaxed) {
+            VERBOSE.lock().push((line, stdout));
+        } else if stdout {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn count(&self) {
+        let counter = match self {
+            Action::Copy { bytes, write, .. } => {
+                BYTES.fetch_add(*bytes, Ordering::Relaxed);
+                let kind = match write {
+                    WriteKind::Created => &CREATED,
+                    WriteKind::Overwritten => &OVERWRITTEN,
+                    WriteKind::Appended => &APPENDED,
+                };
+                kind.fetch_add(1, Ordering::Relaxed);
+                &COPIED
+            }
+            Action::Move { bytes, .. } => {
+                BYTES.fetch_add(*bytes, Ordering::Relaxed);
+                &MOVED
+            }
+            Action::Remove { .. } => &REMOVED,
+            Action::Skip { .. } => &SKIPPED,
+            Action::Error { .. } => &ERRORS,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// `-` selects stderr; anything else is opened in append mode so repeated
+/// runs accumulate in one audit file.
+pub fn open(path: &Path) -> io::Result<()> {
+    let sink = if path == Path::new("-") {
+        Sink::Stderr
+    } else {
+        Sink::File(OpenOptions::new().create(true).append(true).open(path)?)
+    };
+    *SINK.lock() = Some(sink);
+    Ok(())
+}
+
+/// Single hook for per-file events: verbose console output, the log file and
+/// both journals are all fed from here so they cannot disagree.
+pub fn report(action: Action<'_>, verbose: bool) {
+    let action = match action {
+        Action::Copy {
+            src, dst, bytes, ..
+        } if COPIES_MOVE.load(Ordering::Relaxed) => Action::Move { src, dst, bytes },
+        action => action,
+    };
+    if verbose {
+        action.print_verbose();
+    }
+    action.count();
+    crate::core::history::record(&action);
+    crate::core::journal::record(&action);
+    write_line(&action.to_string());
+}
+
+/// While a move copies what it cannot rename, each copy is the move of
+/// that file: its source goes once the copying is done. Reporting them as
+/// moves keeps the log, the counts and `bcmr undo` in line with that.
+pub fn copies_are_moves(on: bool) {
+    COPIES_MOVE.store(on, Ordering::Relaxed);
+}
+
+/// Records `err` against `path` and hands it back, for use as
+/// `return Err(oplog::failed(path, err))`.
+pub fn failed(path: &Path, err: BcmrError) -> BcmrError {
+    report(
+        Action::Error {
+            path,
+            message: &err.to_string(),
+        },
+        false,
+    );
+    err
+}
+
+/// Non-fatal problem worth telling the user about. Queued rather than
+/// printed, since a progress renderer may own the terminal; whoever shows
+/// progress collects them with `take_warnings`.
+pub fn warn(path: &Path, message: &str) {
+    let line = format!("{}: {}", path.display(), message);
+    write_line(&format!("WARN {}", line));
+    WARNED.fetch_add(1, Ordering::Relaxed);
+    WARNINGS.lock().push(line);
+}
+
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock())
+}
+
+/// While a renderer redraws its frame on the terminal, verbose lines headed
+/// there are queued for it (`take_verbose`) so it can print them above the
+/// frame instead of having them drawn over. Releasing prints what is left.
+pub fn hold_verbose(hold: bool) {
+    HOLD_VERBOSE.store(hold, Ordering::Relaxed);
+    if !hold {
+        for (line, stdout) in std::mem::take(&mut *VERBOSE.lock()) {
+            if stdout {
+                println!("{}", line);
+            } else {
+                eprintln!("{}", line);
+            }
+        }
+    }
+}
+
+pub fn take_verbose() -> Vec<String> {
+    let lines = std::mem::take(&mut *VERBOSE.lock());
+    lines.into_iter().map(|(line, _)| line).collect()
+}
+
+/// `path` did not exist and was made for the copy.
+pub fn dir_created(path: &Path) {
+    DIRS_CREATED.fetch_add(1, Ordering::Relaxed);
+    crate::core::history::dir_created(path);
+}
+
+/// `bytes` of a copy already reported were cloned rather than written.
+pub fn reflinked(bytes: u64) {
+    REFLINKED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// A file of `bytes` was made a hard link to one already in place.
+pub fn hard_linked(bytes: u64) {
+    HARDLINKED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// A destination whose data was current had only its attributes set.
+pub fn metadata_updated() {
+    METADATA_UPDATED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Per-outcome file counts for the whole run. Renderers print it and the
+/// JSON result embeds it, both read from the counters `report` feeds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationStats {
+    pub created: u64,
+    pub overwritten: u64,
+    pub appended: u64,
+    pub skipped: u64,
+    pub moved: u64,
+    pub removed: u64,
+    pub dirs_created: u64,
+    pub failed: u64,
+    /// Problems `warn` reported that the run carried on past.
+    #[serde(default)]
+    pub warnings: u64,
+}
+
+impl OperationStats {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub fn stats() -> OperationStats {
+    OperationStats {
+        created: CREATED.load(Ordering::Relaxed),
+        overwritten: OVERWRITTEN.load(Ordering::Relaxed),
+        appended: APPENDED.load(Ordering::Relaxed),
+        skipped: SKIPPED.load(Ordering::Relaxed),
+        moved: MOVED.load(Ordering::Relaxed),
+        removed: REMOVED.load(Ordering::Relaxed),
+        dirs_created: DIRS_CREATED.load(Ordering::Relaxed),
+        failed: ERRORS.load(Ordering::Relaxed),
+        warnings: WARNED.load(Ordering::Relaxed),
+    }
+}
+
+/// Which rows of `TransferStats` an operation fills in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsScope {
+    /// Copies, syncs and the like: everything but `moved`.
+    Copy,
+    /// Everything but `deleted`, since a move takes its sources away.
+    Move,
+    /// The counts and sizes only; nothing is written.
+    Remove,
+}
+
+/// The `--stats` block: the counters behind `OperationStats`, the bytes
+/// behind them and the run's totals. Rows outside the operation's
+/// `StatsScope` are `None` and left out of the block and the JSON alike.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TransferStats {
+    pub files_considered: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    /// Overwritten or appended to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved: Option<u64>,
+    pub skipped: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_only: Option<u64>,
+    pub total_size: u64,
+    /// Literal plus reflinked bytes: everything a copy produced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transferred_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub literal_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reflinked_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hardlinked_bytes: Option<u64>,
+    /// Already in the JSON result line as `duration_secs`.
+    #[serde(skip)]
+    pub elapsed: Duration,
+    #[serde(skip)]
+    pub bytes_per_sec: f64,
+}
+
+/// The counters so far, in `scope`, over a run that went through
+/// `files_considered` files of `total_size` bytes in `elapsed`.
+pub fn transfer_stats(
+    scope: StatsScope,
+    files_considered: u64,
+    total_size: u64,
+    elapsed: Duration,
+    bytes_per_sec: f64,
+) -> TransferStats {
+    let stats = stats();
+    let written = BYTES.load(Ordering::Relaxed);
+    let reflinked = REFLINKED.load(Ordering::Relaxed);
+    let writes = scope != StatsScope::Remove;
+    let when = |applies: bool, n: u64| applies.then_some(n);
+    TransferStats {
+        files_considered,
+        created: when(writes, stats.created),
+        updated: when(writes, stats.overwritten + stats.appended),
+        moved: when(scope == StatsScope::Move, stats.moved),
+        skipped: stats.skipped,
+        deleted: when(scope != StatsScope::Move, stats.removed),
+        metadata_only: when(writes, METADATA_UPDATED.load(Ordering::Relaxed)),
+        total_size,
+        transferred_bytes: when(writes, written),
+        literal_bytes: when(writes, written.saturating_sub(reflinked)),
+        reflinked_bytes: when(writes, reflinked),
+        hardlinked_bytes: when(writes, HARDLINKED.load(Ordering::Relaxed)),
+        elapsed,
+        bytes_per_sec,
+    }
+}
+
+pub fn header(args: &[String]) {
+    let cwd = std::env::current_dir()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    write_line(&format!("BEGIN {} (cwd: {})", args.join(" "), cwd));
+}
+
+pub fn footer(error: Option<&str>) {
+    let summary = format!(
+        "{} copied, {} moved, {} removed, {} skipped, {} errors, {} bytes",
+        COPIED.load(Ordering::Relaxed),
+        MOVED.load(Ordering::Relaxed),
+        REMOVED.load(Ordering::Relaxed),
+        SKIPPED.load(Ordering::Relaxed),
+        ERRORS.load(Ordering::Relaxed),
+        BYTES.load(Ordering::Relaxed),
+    );
+    match error {
+        None => write_line(&format!("END ok: {}", summary)),
+        Some(e) => write_line(&format!("END failed: {} ({})", summary, e)),
+    }
+}
+
+fn write_line(line: &str) {
+    let mut sink = SINK.lock();
+    let Some(sink) = sink.as_mut() else {
+        return;
+    };
+    let stamped = format!("{} {}\n", timestamp(SystemTime::now()), line);
+    // A failing log must not abort the transfer it is describing.
+    let _ = match sink {
+        Sink::Stderr => io::stderr().lock().write_all(stamped.as_bytes()),
+        Sink::File(f) => f.write_all(stamped.as_bytes()).and_then(|_| f.flush()),
+    };
+}
+
+pub(crate) fn timestamp(t: SystemTime) -> String {
+    let secs = t
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (y, m, d) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        y,
+        m,
+        d,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil algorithm (proleptic Gregorian, UTC).
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamp_formats_utc() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let t = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723);
+        assert_eq!(timestamp(t), "2000-02-29T01:02:03Z");
+    }
+
+    #[test]
+    fn action_lines() {
+        let src = Path::new("a.txt");
+        let dst = Path::new("b/a.txt");
+        assert_eq!(
+            Action::Copy {
+                src,
+                dst,
+                bytes: 12345,
+                write: WriteKind::Created,
+            }
+            .to_string(),
+            "COPY a.txt -> b/a.txt 12345 bytes ok"
+        );
+        assert_eq!(
+            Action::Skip {
+                path: dst,
+                reason: "exists"
+            }
+            .to_string(),
+            "SKIP b/a.txt exists"
+        );
+        assert_eq!(
+            Action::Error {
+                path: src,
+                message: "boom"
+            }
+            .to_string(),
+            "ERROR a.txt: boom"
+        );
+    }
+
+    #[test]
+    fn transfer_stats_json_leaves_out_rows_outside_the_scope() {
+        let remove = TransferStats {
+            files_considered: 3,
+            deleted: Some(2),
+            total_size: 10,
+            elapsed: Duration::from_secs(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&remove).unwrap(),
+            serde_json::json!({
+                "files_considered": 3,
+                "skipped": 0,
+                "deleted": 2,
+                "total_size": 10,
+            })
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! `--fix-perms`: directories in a source tree that their owner cannot
+//! read (mode 0000 and the like, as some build tools leave them) are
+//! opened up for the copy and given their mode back afterwards.
+
+use parking_lot::Mutex;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Directories opened up so far, with the modes they had.
+static RELAXED: Mutex<Vec<(PathBuf, u32)>> = Mutex::new(Vec::new());
+
+/// Owner read and search: what listing and entering a directory takes.
+#[cfg(unix)]
+const OWNER_RX: u32 = 0o500;
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Gives `dir` owner read and search after reading it failed with `err`,
+/// when `--fix-perms` is on and this user owns it. Returns whether it did,
+/// in which case the read is worth another try.
+#[cfg(unix)]
+pub fn relax(dir: &Path, err: &io::Error) -> bool {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    if !ENABLED.load(Ordering::Relaxed) || err.kind() != io::ErrorKind::PermissionDenied {
+        return false;
+    }
+    let Ok(md) = dir.symlink_metadata() else {
+        return false;
+    };
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    let mode = md.mode() & 0o7777;
+    if !md.is_dir() || md.uid() != euid || mode & OWNER_RX == OWNER_RX {
+        return false;
+    }
+    let relaxed = std::fs::Permissions::from_mode(mode | OWNER_RX);
+    if std::fs::set_permissions(dir, relaxed).is_err() {
+        return false;
+    }
+    RELAXED.lock().push((dir.to_path_buf(), mode));
+    true
+}
+
+#[cfg(not(unix))]
+pub fn relax(_dir: &Path, _err: &io::Error) -> bool {
+    false
+}
+
+/// The mode `path` had before `relax` changed it, for `--preserve` to
+/// give the copy.
+pub fn original_mode(path: &Path) -> Option<u32> {
+    RELAXED
+        .lock()
+        .iter()
+        .find(|(dir, _)| dir == path)
+        .map(|&(_, mode)| mode)
+}
+
+/// Puts back every mode `relax` changed, innermost first. Safe to call on
+/// every exit path.
+#[cfg(unix)]
+pub fn restore() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let relaxed = std::mem::take(&mut *RELAXED.lock());
+    for (dir, mode) in relaxed.into_iter().rev() {
+        if let Err(e) = std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(mode)) {
+            eprintln!(
+                "bcmr: cannot restore mode {:04o} on '{}': {}",
+                mode,
+                dir.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn restore() {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        path.metadata().unwrap().permissions().mode() & 0o7777
+    }
+
+    #[test]
+    fn test_relax_opens_owned_directories_and_restore_closes_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+
+        set_enabled(true);
+        assert!(!relax(&locked, &io::Error::from(io::ErrorKind::NotFound)));
+        assert!(relax(&locked, &denied));
+        assert_eq!(mode(&locked), 0o500);
+        assert_eq!(original_mode(&locked), Some(0o000));
+        // Already readable: nothing left to relax.
+        assert!(!relax(&locked, &denied));
+
+        restore();
+        set_enabled(false);
+        assert_eq!(mode(&locked), 0o000);
+        assert_eq!(original_mode(&locked), None);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
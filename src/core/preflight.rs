@@ -0,0 +1,317 @@
+//! Checks run before a copy, move or remove touches anything, so a
+//! destination that cannot be written fails at once instead of after the
+//! scan and the prompt. `--no-preflight` skips them.
+
+use crate::core::error::BcmrError;
+use crate::core::oplog::{self, Action};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Fails with `ReadOnlyDestination` when `path`, or the closest of its
+/// ancestors that exists, is on a filesystem mounted read-only.
+pub fn check_writable(path: &Path) -> Result<(), BcmrError> {
+    let Some(existing) = nearest_existing(path) else {
+        return Ok(());
+    };
+    if read_only(&existing) == Some(true) {
+        return Err(BcmrError::ReadOnlyDestination(mount_point(&existing)));
+    }
+    Ok(())
+}
+
+/// The same check for removing `path`: on its own filesystem when it is a
+/// directory, whose entries go too, else on its parent's. A missing path
+/// is left for the removal to report.
+pub fn check_removable(path: &Path) -> Result<(), BcmrError> {
+    match path.symlink_metadata() {
+        Ok(md) if md.is_dir() => check_writable(path),
+        Ok(_) => check_writable(path.parent().unwrap_or(Path::new("."))),
+        Err(_) => Ok(()),
+    }
+}
+
+/// `path` itself when it exists, else its closest existing ancestor, with
+/// links resolved. A bare relative name stands for the working directory.
+fn nearest_existing(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .map(|p| {
+            if p.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                p
+            }
+        })
+        .find_map(|p| p.canonicalize().ok())
+}
+
+/// Whether mount flags `flags`, as `statvfs` gives them, make the
+/// filesystem read-only.
+#[cfg(unix)]
+fn flags_read_only(flags: libc::c_ulong) -> bool {
+    flags & libc::ST_RDONLY != 0
+}
+
+#[cfg(unix)]
+fn read_only(path: &Path) -> Option<bool> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct it is given.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(flags_read_only(stat.f_flag as libc::c_ulong))
+}
+
+/// Without mount flags to read, creates and deletes a hidden file to see.
+#[cfg(not(unix))]
+fn read_only(path: &Path) -> Option<bool> {
+    let dir = if path.is_dir() { path } else { path.parent()? };
+    let probe = dir.join(format!(".bcmr-preflight-{}", std::process::id()));
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Some(false)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::ReadOnlyFilesystem => Some(true),
+        Err(_) => None,
+    }
+}
+
+/// The topmost ancestor of `path` on the same device: where it is mounted.
+#[cfg(unix)]
+fn mount_point(path: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+    let Ok(dev) = path.metadata().map(|md| md.dev()) else {
+        return path.to_path_buf();
+    };
+    path.ancestors()
+        .take_while(|p| p.metadata().is_ok_and(|md| md.dev() == dev))
+        .last()
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+#[cfg(not(unix))]
+fn mount_point(path: &Path) -> PathBuf {
+    path.ancestors().last().unwrap_or(path).to_path_buf()
+}
+
+/// The longest name and path, in bytes, a filesystem takes. `None` where
+/// it sets no limit or will not say.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NameLimits {
+    pub name_max: Option<usize>,
+    pub path_max: Option<usize>,
+}
+
+impl NameLimits {
+    /// The limits `pathconf` gives for where `path` would be created.
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> Self {
+        use std::os::unix::ffi::OsStrExt;
+        let Some(c_path) = nearest_existing(path)
+            .and_then(|p| std::ffi::CString::new(p.as_os_str().as_bytes()).ok())
+        else {
+            return Self::default();
+        };
+        // SAFETY: pathconf only reads the NUL-terminated path.
+        let query = |name| match unsafe { libc::pathconf(c_path.as_ptr(), name) } {
+            n if n > 0 => Some(n as usize),
+            _ => None,
+        };
+        Self {
+            name_max: query(libc::_PC_NAME_MAX),
+            // PATH_MAX counts the terminating NUL.
+            path_max: query(libc::_PC_PATH_MAX).map(|n| n - 1),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> Self {
+        Self::default()
+    }
+
+    /// Why a path of `path_len` bytes ending in a `name_len`-byte name
+    /// cannot be created, if it cannot.
+    fn violation(&self, name_len: usize, path_len: usize) -> Option<String> {
+        match (self.name_max, self.path_max) {
+            (Some(max), _) if name_len > max => Some(format!(
+                "name is {} bytes, over the {}-byte limit",
+                name_len, max
+            )),
+            (_, Some(max)) if path_len > max => Some(format!(
+                "path is {} bytes, over the {}-byte limit",
+                path_len, max
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NameLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit =
+            |max: Option<usize>| max.map_or("any length".to_string(), |n| format!("{} bytes", n));
+        write!(
+            f,
+            "names up to {}, paths up to {}",
+            limit(self.name_max),
+            limit(self.path_max)
+        )
+    }
+}
+
+/// Holds each destination a copy scans against the destination's
+/// `NameLimits`. Every offender is collected for one error at the end, or
+/// with `skip`, warned about and left out along with whatever is under it.
+pub struct NameCheck {
+    limits: NameLimits,
+    root: PathBuf,
+    /// What `root` lacks of its absolute form, for the path limit.
+    prefix_len: usize,
+    skip: bool,
+    offenders: Vec<PathBuf>,
+    skipped_dirs: Vec<PathBuf>,
+}
+
+impl NameCheck {
+    pub fn new(root: &Path, limits: NameLimits, skip: bool) -> Self {
+        let prefix_len = match root.is_absolute() {
+            true => 0,
+            false => std::env::current_dir().map_or(0, |cwd| cwd.as_os_str().len() + 1),
+        };
+        Self {
+            limits,
+            root: root.to_path_buf(),
+            prefix_len,
+            skip,
+            offenders: Vec::new(),
+            skipped_dirs: Vec::new(),
+        }
+    }
+
+    /// Whether the scanned entry that creates `dst` goes ahead. Once an
+    /// offender turns up without `skip`, nothing does, since the copy is
+    /// going to fail.
+    pub fn admit(&mut self, dst: &Path) -> bool {
+        if self.skipped_dirs.iter().any(|dir| dst.starts_with(dir)) {
+            return false;
+        }
+        let name_len = dst.file_name().map_or(0, |name| name.len());
+        let path_len = self.prefix_len + dst.as_os_str().len();
+        let Some(reason) = self.limits.violation(name_len, path_len) else {
+            return self.offenders.is_empty();
+        };
+        if !self.skip {
+            self.offenders.push(self.relative(dst));
+            return false;
+        }
+        let reason = format!("skipped: {}", reason);
+        oplog::warn(dst, &reason);
+        oplog::report(
+            Action::Skip {
+                path: dst,
+                reason: &reason,
+            },
+            false,
+        );
+        self.skipped_dirs.push(dst.to_path_buf());
+        false
+    }
+
+    fn relative(&self, dst: &Path) -> PathBuf {
+        match dst.strip_prefix(&self.root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel.to_path_buf(),
+            _ => dst.to_path_buf(),
+        }
+    }
+
+    /// The error naming every offender, if there were any.
+    pub fn finish(self) -> Result<(), BcmrError> {
+        if self.offenders.is_empty() {
+            return Ok(());
+        }
+        Err(BcmrError::NamesTooLong(self.limits, self.offenders))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_the_rdonly_flag_counts() {
+        assert!(flags_read_only(libc::ST_RDONLY));
+        assert!(flags_read_only(libc::ST_RDONLY | libc::ST_NOSUID));
+        assert!(!flags_read_only(libc::ST_NOSUID));
+        assert!(!flags_read_only(0));
+    }
+
+    #[test]
+    fn test_missing_paths_are_checked_where_they_would_go() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("a/b/c.txt");
+        assert_eq!(
+            nearest_existing(&missing),
+            Some(dir.path().canonicalize().unwrap())
+        );
+        assert!(nearest_existing(Path::new("no-such-name")).is_some());
+        assert!(check_writable(&missing).is_ok());
+    }
+
+    fn limits(name_max: usize, path_max: usize) -> NameLimits {
+        NameLimits {
+            name_max: Some(name_max),
+            path_max: Some(path_max),
+        }
+    }
+
+    #[test]
+    fn test_every_long_name_and_path_is_listed() {
+        let root = Path::new("/mnt/card/backup");
+        let mut check = NameCheck::new(root, limits(8, 30), false);
+        assert!(check.admit(&root.join("short")));
+        assert!(!check.admit(&root.join("a-long-name.txt")));
+        assert!(!check.admit(&root.join("short/fine")));
+        assert!(!check.admit(&root.join("short/deeper/path")));
+        let err = check.finish().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Paths too long for the destination (names up to 8 bytes, paths up to 30 bytes): \
+             'a-long-name.txt', 'short/deeper/path'; pass --skip-too-long to copy the rest"
+        );
+    }
+
+    #[test]
+    fn test_skipping_leaves_out_what_is_under_a_long_directory() {
+        let root = Path::new("/dst");
+        let mut check = NameCheck::new(root, limits(8, 4095), true);
+        assert!(!check.admit(&root.join("long-directory")));
+        assert!(!check.admit(&root.join("long-directory/a")));
+        assert!(check.admit(&root.join("ok/a")));
+        assert!(check.finish().is_ok());
+    }
+
+    #[test]
+    fn test_relative_roots_count_the_working_directory() {
+        let cwd_len = std::env::current_dir().unwrap().as_os_str().len();
+        let mut check = NameCheck::new(Path::new("d"), limits(255, cwd_len + 4), false);
+        assert!(check.admit(Path::new("d/a")));
+        assert!(!check.admit(Path::new("d/ab")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pathconf_reports_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = NameLimits::of(&dir.path().join("not/yet"));
+        assert!(limits.name_max.is_some_and(|n| n >= 14), "{limits:?}");
+    }
+}
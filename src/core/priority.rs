@@ -0,0 +1,231 @@
+//! `--nice` / `--ionice`: lower bcmr's CPU and disk priority so a large
+//! background copy leaves the desktop responsive.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Best-effort level when `--ionice best-effort` names none, as ionice(1).
+const DEFAULT_BE_LEVEL: u8 = 4;
+
+/// `--ionice` scheduling class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// Disk time only when nothing else wants it.
+    Idle,
+    /// The normal class at level 0 (highest) to 7 (lowest).
+    BestEffort(u8),
+}
+
+impl FromStr for IoClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid I/O class '{}'. Expected idle, best-effort or best-effort:0-7",
+                s
+            )
+        };
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.split_once(':') {
+            None => match lower.as_str() {
+                "idle" => Ok(IoClass::Idle),
+                "best-effort" => Ok(IoClass::BestEffort(DEFAULT_BE_LEVEL)),
+                _ => Err(invalid()),
+            },
+            Some(("best-effort", level)) => match level.parse() {
+                Ok(n @ 0..=7) => Ok(IoClass::BestEffort(n)),
+                _ => Err(invalid()),
+            },
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoClass::Idle => write!(f, "idle"),
+            IoClass::BestEffort(n) => write!(f, "best-effort:{}", n),
+        }
+    }
+}
+
+/// The calls `apply` makes, so tests can watch them being made.
+pub trait Scheduler {
+    fn set_nice(&mut self, nice: i32) -> io::Result<()>;
+    fn set_io_class(&mut self, class: IoClass) -> io::Result<()>;
+}
+
+/// This process, through the OS.
+pub struct System;
+
+impl Scheduler for System {
+    #[cfg(unix)]
+    fn set_nice(&mut self, nice: i32) -> io::Result<()> {
+        // SAFETY: plain integer arguments; `0` is the calling process.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn set_nice(&mut self, _nice: i32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_io_class(&mut self, class: IoClass) -> io::Result<()> {
+        // linux/ioprio.h: the class sits above a 13-bit level.
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        let prio = match class {
+            IoClass::BestEffort(n) => (2 << IOPRIO_CLASS_SHIFT) | libc::c_int::from(n),
+            IoClass::Idle => 3 << IOPRIO_CLASS_SHIFT,
+        };
+        // SAFETY: plain integer arguments; `0` is the calling process.
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, prio) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_io_class(&mut self, _class: IoClass) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// What `apply` managed: lines for `-v` and warnings for what it could not.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Applied {
+    pub verbose: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Sets whichever of `nice` and `io_class` are given. Neither failing is
+/// fatal: the copy still runs, only at normal priority.
+pub fn apply(sched: &mut impl Scheduler, nice: Option<i32>, io_class: Option<IoClass>) -> Applied {
+    let mut applied = Applied::default();
+    if let Some(nice) = nice {
+        match sched.set_nice(nice) {
+            Ok(()) => applied.verbose.push(format!("niceness set to {}", nice)),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => applied
+                .warnings
+                .push("--nice is not supported on this platform; ignoring it".to_string()),
+            Err(e) => applied
+                .warnings
+                .push(format!("cannot set niceness {}: {}", nice, e)),
+        }
+    }
+    if let Some(class) = io_class {
+        match sched.set_io_class(class) {
+            Ok(()) => applied.verbose.push(format!("I/O class set to {}", class)),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => applied
+                .warnings
+                .push("--ionice is only supported on Linux; ignoring it".to_string()),
+            Err(e) => applied
+                .warnings
+                .push(format!("cannot set I/O class {}: {}", class, e)),
+        }
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records calls, failing them with `error` when set.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        error: Option<io::ErrorKind>,
+    }
+
+    impl Recorder {
+        fn result(&self) -> io::Result<()> {
+            self.error.map_or(Ok(()), |kind| Err(kind.into()))
+        }
+    }
+
+    impl Scheduler for Recorder {
+        fn set_nice(&mut self, nice: i32) -> io::Result<()> {
+            self.calls.push(format!("nice {}", nice));
+            self.result()
+        }
+
+        fn set_io_class(&mut self, class: IoClass) -> io::Result<()> {
+            self.calls.push(format!("ionice {}", class));
+            self.result()
+        }
+    }
+
+    #[test]
+    fn test_parse_io_class() {
+        assert_eq!("idle".parse(), Ok(IoClass::Idle));
+        assert_eq!("best-effort".parse(), Ok(IoClass::BestEffort(4)));
+        assert_eq!("Best-Effort:7".parse(), Ok(IoClass::BestEffort(7)));
+        assert!("best-effort:8".parse::<IoClass>().is_err());
+        assert!("idle:3".parse::<IoClass>().is_err());
+        assert!("realtime".parse::<IoClass>().is_err());
+    }
+
+    #[test]
+    fn test_apply_makes_both_calls() {
+        let mut rec = Recorder::default();
+        let applied = apply(&mut rec, Some(10), Some(IoClass::Idle));
+        assert_eq!(rec.calls, ["nice 10", "ionice idle"]);
+        assert_eq!(
+            applied.verbose,
+            ["niceness set to 10", "I/O class set to idle"]
+        );
+        assert!(applied.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_apply_without_options_does_nothing() {
+        let mut rec = Recorder::default();
+        assert_eq!(apply(&mut rec, None, None), Applied::default());
+        assert!(rec.calls.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_platform_only_warns() {
+        let mut rec = Recorder {
+            error: Some(io::ErrorKind::Unsupported),
+            ..Recorder::default()
+        };
+        let applied = apply(&mut rec, None, Some(IoClass::BestEffort(7)));
+        assert!(applied.verbose.is_empty());
+        assert_eq!(
+            applied.warnings,
+            ["--ionice is only supported on Linux; ignoring it"]
+        );
+    }
+
+    #[test]
+    fn test_refused_call_names_the_value() {
+        let mut rec = Recorder {
+            error: Some(io::ErrorKind::PermissionDenied),
+            ..Recorder::default()
+        };
+        let applied = apply(&mut rec, Some(-5), None);
+        assert_eq!(applied.warnings.len(), 1);
+        assert!(
+            applied.warnings[0].starts_with("cannot set niceness -5: "),
+            "{:?}",
+            applied.warnings
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_accepts_current_niceness() {
+        // SAFETY: reads this process's niceness; errno is not consulted.
+        let current = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, 0) };
+        assert!(System.set_nice(current).is_ok());
+    }
+}
@@ -1,4 +1,5 @@
 mod attrs;
+pub mod confine;
 mod ops;
 mod resume;
 mod ssh_cmd;
@@ -13,7 +14,8 @@ pub use ops::{
 };
 pub use resume::{check_resume_state, ResumeDecision};
 pub use transfer::{
-    download_directory, download_file, ensure_remote_tree, upload_directory, upload_file,
+    download_directory, download_file, ensure_remote_tree, relay_directory, relay_file,
+    upload_directory, upload_file,
 };
 
 pub fn set_ssh_compression(enabled: bool) {
@@ -35,8 +37,17 @@ impl RemotePath {
         }
     }
 
+    /// The `[user@]host:path` form, with an IPv6 host in brackets again.
     pub fn display(&self) -> String {
-        format!("{}:{}", self.ssh_target(), self.path)
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match &self.user {
+            Some(user) => format!("{}@{}:{}", user, host, self.path),
+            None => format!("{}:{}", host, self.path),
+        }
     }
 
     pub fn join(&self, subpath: &str) -> Self {
@@ -54,6 +65,9 @@ impl std::fmt::Display for RemotePath {
     }
 }
 
+/// `[user@]host:path` as scp reads it: the first colon outside `[...]`
+/// (an IPv6 address, `[::1]:path`) ends the host, and a slash before it
+/// makes the argument a local path, so `./weird:name` stays local.
 pub fn parse_remote_path(s: &str) -> Option<RemotePath> {
     if s.starts_with('/')
         || s.starts_with("./")
@@ -66,13 +80,24 @@ pub fn parse_remote_path(s: &str) -> Option<RemotePath> {
     }
 
     if s.len() >= 2 && s.as_bytes()[0].is_ascii_alphabetic() && s.as_bytes()[1] == b':' {
-        let colon_pos = s.find(':')?;
-        if colon_pos == 1 {
-            return None;
-        }
+        return None;
     }
 
-    let colon_pos = s.find(':')?;
+    let mut in_brackets = false;
+    let mut colon_pos = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => {
+                colon_pos = Some(i);
+                break;
+            }
+            '/' if !in_brackets => return None,
+            _ => {}
+        }
+    }
+    let colon_pos = colon_pos?;
     if colon_pos == 0 {
         return None;
     }
@@ -80,20 +105,21 @@ pub fn parse_remote_path(s: &str) -> Option<RemotePath> {
     let host_part = &s[..colon_pos];
     let path_part = &s[colon_pos + 1..];
 
-    if host_part.contains('/') || host_part.contains(' ') {
+    if host_part.contains(' ') {
         return None;
     }
 
-    let (user, host) = if let Some(at_pos) = host_part.find('@') {
-        let user = &host_part[..at_pos];
-        let host = &host_part[at_pos + 1..];
-        if user.is_empty() || host.is_empty() {
-            return None;
-        }
-        (Some(user.to_string()), host.to_string())
-    } else {
-        (None, host_part.to_string())
+    let (user, host) = match host_part.find('@') {
+        Some(at_pos) => (Some(&host_part[..at_pos]), &host_part[at_pos + 1..]),
+        None => (None, host_part),
+    };
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.strip_suffix(']')?,
+        None => host,
     };
+    if user.is_some_and(str::is_empty) || host.is_empty() || host.contains(['[', ']']) {
+        return None;
+    }
 
     let path = if path_part.is_empty() {
         ".".to_string()
@@ -101,7 +127,11 @@ pub fn parse_remote_path(s: &str) -> Option<RemotePath> {
         path_part.to_string()
     };
 
-    Some(RemotePath { user, host, path })
+    Some(RemotePath {
+        user: user.map(str::to_string),
+        host: host.to_string(),
+        path,
+    })
 }
 
 #[derive(Debug)]
@@ -162,6 +192,32 @@ mod tests {
         assert!(parse_remote_path("user@:path").is_none());
     }
 
+    #[test]
+    fn test_parse_remote_path_ipv6() {
+        let r = parse_remote_path("user@[fe80::1]:/srv/data").unwrap();
+        assert_eq!(r.user, Some("user".to_string()));
+        assert_eq!(r.host, "fe80::1");
+        assert_eq!(r.path, "/srv/data");
+        assert_eq!(r.ssh_target(), "user@fe80::1");
+        assert_eq!(r.display(), "user@[fe80::1]:/srv/data");
+
+        let r = parse_remote_path("[::1]:").unwrap();
+        assert_eq!(r.host, "::1");
+        assert_eq!(r.path, ".");
+
+        assert!(parse_remote_path("[::1").is_none());
+        assert!(parse_remote_path("[]:path").is_none());
+        assert!(parse_remote_path("a[b]:path").is_none());
+    }
+
+    #[test]
+    fn test_parse_remote_path_colon_after_slash_is_local() {
+        assert!(parse_remote_path("dir/weird:name").is_none());
+        assert!(parse_remote_path("./weird:name").is_none());
+        let r = parse_remote_path("host:dir/with:colon").unwrap();
+        assert_eq!(r.path, "dir/with:colon");
+    }
+
     #[test]
     fn test_parse_remote_path_with_spaces_in_host() {
         assert!(parse_remote_path("host name:path").is_none());
@@ -216,6 +272,41 @@ mod tests {
         assert_eq!(super::ssh_cmd::shell_escape("simple"), "simple");
     }
 
+    #[test]
+    fn test_ssh_error_kinds() {
+        use super::ssh_cmd::ssh_error;
+        use crate::core::error::BcmrError;
+        let kind = |stderr| ssh_error(stderr, "ctx");
+        assert!(matches!(
+            kind("alice@srv: Permission denied (publickey,password)."),
+            BcmrError::SshAuth(_)
+        ));
+        assert!(matches!(
+            kind("Host key verification failed."),
+            BcmrError::SshAuth(_)
+        ));
+        assert!(matches!(
+            kind("ssh: connect to host srv port 22: Connection refused"),
+            BcmrError::SshConnection(_)
+        ));
+        assert!(matches!(
+            kind("ssh: Could not resolve hostname srv: Name or service not known"),
+            BcmrError::SshConnection(_)
+        ));
+        // The remote command's own failure, not ssh's.
+        assert!(matches!(
+            kind("cat: /root/secret: Permission denied"),
+            BcmrError::RemoteIo(_)
+        ));
+        assert!(matches!(
+            kind("sh: cannot create /full/x: No space left on device"),
+            BcmrError::RemoteIo(_)
+        ));
+        assert_eq!(kind("Connection timed out").exit_code(), 3);
+        assert_eq!(kind("Permission denied (publickey).").exit_code(), 4);
+        assert_eq!(kind("No such file or directory").exit_code(), 5);
+    }
+
     #[test]
     fn test_shell_escape_with_quotes() {
         assert_eq!(super::ssh_cmd::shell_escape("it's"), "it'\\''s");
@@ -1,5 +1,5 @@
 use super::ops::remote_file_hash;
-use super::ssh_cmd::{shell_escape, ssh_command, ssh_error_message};
+use super::ssh_cmd::{shell_escape, ssh_command, ssh_error};
 use super::RemotePath;
 use crate::core::error::BcmrError;
 use std::path::Path;
@@ -38,8 +38,23 @@ pub async fn preserve_remote_attrs(local_src: &Path, remote: &RemotePath) -> Res
         }
     };
 
-    let mtime_ts = unix_to_touch_ts(mtime_secs as i64);
-    let atime_ts = unix_to_touch_ts(atime_secs as i64);
+    set_remote_attrs(remote, atime_secs as i64, mtime_secs as i64, mode).await
+}
+
+/// Carries times and mode from one remote file to another, for a relay.
+pub async fn copy_remote_attrs(src: &RemotePath, dst: &RemotePath) -> Result<(), BcmrError> {
+    let (atime_secs, mtime_secs, mode) = get_remote_attrs(src).await?;
+    set_remote_attrs(dst, atime_secs, mtime_secs, mode).await
+}
+
+async fn set_remote_attrs(
+    remote: &RemotePath,
+    atime_secs: i64,
+    mtime_secs: i64,
+    mode: u32,
+) -> Result<(), BcmrError> {
+    let mtime_ts = unix_to_touch_ts(mtime_secs);
+    let atime_ts = unix_to_touch_ts(atime_secs);
     let cmd = format!(
         "TZ=UTC touch -m -t '{}' '{}'; TZ=UTC touch -a -t '{}' '{}'; chmod {:o} '{}'",
         mtime_ts,
@@ -52,10 +67,10 @@ pub async fn preserve_remote_attrs(local_src: &Path, remote: &RemotePath) -> Res
     let attr_out = ssh_command(&remote.ssh_target()).arg(cmd).output().await?;
     if !attr_out.status.success() {
         let stderr = String::from_utf8_lossy(&attr_out.stderr);
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr,
             &format!("Failed to set attributes on '{}'", remote),
-        )));
+        ));
     }
     Ok(())
 }
@@ -121,9 +136,11 @@ pub async fn verify_remote_file(local_src: &Path, remote: &RemotePath) -> Result
     use crate::core::checksum;
 
     let local_path = local_src.to_path_buf();
-    let local_hash = tokio::task::spawn_blocking(move || checksum::calculate_hash(&local_path))
-        .await
-        .map_err(|e| BcmrError::InvalidInput(e.to_string()))??;
+    let local_hash = tokio::task::spawn_blocking(move || {
+        checksum::calculate_hash(&local_path, checksum::Algorithm::Blake3)
+    })
+    .await
+    .map_err(|e| BcmrError::InvalidInput(e.to_string()))??;
 
     let remote_hash = remote_file_hash(remote, None).await?;
 
@@ -0,0 +1,259 @@
+use super::ssh_cmd::{shell_escape, ssh_command, ssh_error};
+use super::RemotePath;
+use crate::core::error::BcmrError;
+use std::path::{Path, PathBuf};
+
+/// A remote path as transfers use it: `~/` and `.` segments dropped (ssh
+/// starts in the remote home), repeated slashes collapsed, a trailing slash
+/// kept. `..` is refused rather than resolved, since what it climbs out of
+/// is only known on the remote side.
+pub fn normalize(path: &str) -> Result<String, BcmrError> {
+    if path.contains('\0') {
+        return Err(BcmrError::InvalidInput(format!(
+            "Refusing remote path '{}': it contains a null byte",
+            path.escape_default()
+        )));
+    }
+    let absolute = path.starts_with('/');
+    let mut parts = Vec::new();
+    for (i, part) in path.split('/').enumerate() {
+        match part {
+            "" | "." => {}
+            "~" if i == 0 => {}
+            ".." => {
+                return Err(BcmrError::InvalidInput(format!(
+                    "Refusing remote path '{}': '..' is not allowed",
+                    path
+                )))
+            }
+            _ => parts.push(part),
+        }
+    }
+    let mut out = match (absolute, parts.is_empty()) {
+        (true, _) => format!("/{}", parts.join("/")),
+        (false, true) => ".".to_string(),
+        (false, false) => parts.join("/"),
+    };
+    if path.ends_with('/') && !out.ends_with('/') && out != "." {
+        out.push('/');
+    }
+    Ok(out)
+}
+
+/// Checks a path taken from a directory listing or walk before it is joined
+/// onto a destination. A hostile source can list anything, so an absolute
+/// path or a `..` is refused rather than trusted.
+pub fn check_relative(rel: &str) -> Result<(), BcmrError> {
+    let hostile = rel.is_empty()
+        || rel.contains('\0')
+        || rel.starts_with('/')
+        || rel.split('/').any(|part| part == "..");
+    if hostile {
+        return Err(BcmrError::InvalidInput(format!(
+            "Refusing '{}' from the source listing: it would leave the destination",
+            rel.escape_default()
+        )));
+    }
+    Ok(())
+}
+
+/// `base.join(rel)` for a remote destination, once `rel` has been checked.
+pub fn join(base: &RemotePath, rel: &str) -> Result<RemotePath, BcmrError> {
+    check_relative(rel)?;
+    Ok(base.join(rel))
+}
+
+/// `base.join(rel)` for a local destination, once `rel` has been checked.
+pub fn local_join(base: &Path, rel: &str) -> Result<PathBuf, BcmrError> {
+    check_relative(rel)?;
+    Ok(base.join(rel))
+}
+
+/// Whether `path` is `base` or below it, both normalized.
+fn within(path: &str, base: &str) -> bool {
+    match base {
+        "." => !path.starts_with('/'),
+        "/" => path.starts_with('/'),
+        _ => path == base || path.strip_prefix(base).is_some_and(|r| r.starts_with('/')),
+    }
+}
+
+/// What a destination looks like on the remote side, read once before a
+/// transfer writes into it: where it really is, and which entries below it
+/// are symlinks a write would follow out of it.
+#[derive(Debug)]
+pub struct DestinationGuard {
+    prefix: String,
+    links: Vec<String>,
+}
+
+impl DestinationGuard {
+    /// Reads the destination in one ssh round trip. With `allowed_root`
+    /// set, the destination must resolve (symlinks and all) to a place
+    /// inside it.
+    pub async fn scan(dest: &RemotePath, allowed_root: Option<&str>) -> Result<Self, BcmrError> {
+        let prefix = normalize(&dest.path)?;
+        let prefix = match prefix.trim_end_matches('/') {
+            "" => "/".to_string(),
+            p => p.to_string(),
+        };
+        let quoted = shell_escape(&prefix);
+        let mut script = format!(
+            "d='{quoted}'; while [ ! -d \"$d\" ] && [ \"$d\" != . ] && [ \"$d\" != / ]; \
+             do d=$(dirname \"$d\"); done; printf '%s\\n' \"$d\"; (cd \"$d\" && pwd -P) || echo; "
+        );
+        if let Some(root) = allowed_root {
+            script.push_str(&format!(
+                "(cd '{}' && pwd -P) || echo; ",
+                shell_escape(&normalize(root)?)
+            ));
+        }
+        script.push_str(&format!("find -H '{quoted}' -type l 2>/dev/null; exit 0"));
+
+        let output = ssh_command(&dest.ssh_target()).arg(script).output().await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ssh_error(
+                &stderr,
+                &format!("Cannot inspect remote destination '{}'", dest),
+            ));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        let existing = lines.next().unwrap_or(".");
+        let canonical = lines.next().unwrap_or("");
+
+        if let Some(root) = allowed_root {
+            let root_canonical = lines.next().unwrap_or("");
+            if root_canonical.is_empty() {
+                return Err(BcmrError::InvalidInput(format!(
+                    "Allowed root '{}' does not exist on {}",
+                    root, dest.host
+                )));
+            }
+            let tail = if existing == "." && prefix != "." {
+                format!("/{}", prefix)
+            } else {
+                prefix.get(existing.len()..).unwrap_or("").to_string()
+            };
+            let resolved = format!("{}{}", canonical, tail);
+            if canonical.is_empty() || !within(&resolved, root_canonical) {
+                return Err(BcmrError::InvalidInput(format!(
+                    "Destination {} resolves to '{}', outside the allowed root '{}'",
+                    dest, resolved, root
+                )));
+            }
+        }
+
+        let links = lines
+            .filter_map(|l| normalize(l).ok())
+            .map(|l| l.trim_end_matches('/').to_string())
+            .filter(|l| *l != prefix)
+            .collect();
+        Ok(Self { prefix, links })
+    }
+
+    /// Refuses a path a transfer derived for this destination if it lies
+    /// outside it or would be written through a symlink below it.
+    pub fn check(&self, path: &str) -> Result<(), BcmrError> {
+        let normalized = normalize(path)?;
+        let normalized = normalized.trim_end_matches('/');
+        if !within(normalized, &self.prefix) {
+            return Err(BcmrError::InvalidInput(format!(
+                "Refusing to write '{}': it is outside the destination '{}'",
+                path, self.prefix
+            )));
+        }
+        if let Some(link) = self.links.iter().find(|l| within(normalized, l)) {
+            return Err(BcmrError::InvalidInput(format!(
+                "Refusing to write '{}': '{}' on the remote side is a symbolic link",
+                path, link
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("dst/./a//b").unwrap(), "dst/a/b");
+        assert_eq!(normalize("dst/").unwrap(), "dst/");
+        assert_eq!(normalize("~/dst").unwrap(), "dst");
+        assert_eq!(normalize("~").unwrap(), ".");
+        assert_eq!(normalize("./").unwrap(), ".");
+        assert_eq!(normalize("/").unwrap(), "/");
+        assert_eq!(normalize("//srv//x/").unwrap(), "/srv/x/");
+        assert_eq!(normalize("a/~/b").unwrap(), "a/~/b");
+    }
+
+    #[test]
+    fn test_normalize_refuses_parent_segments() {
+        for hostile in ["..", "dst/..", "dst/../../etc", "/srv/../etc/", "a\0b"] {
+            assert!(normalize(hostile).is_err(), "{hostile:?}");
+        }
+    }
+
+    #[test]
+    fn test_check_relative_refuses_hostile_listings() {
+        for hostile in ["", "..", "../x", "a/../../x", "a/..", "/etc/passwd", "a\0b"] {
+            assert!(check_relative(hostile).is_err(), "{hostile:?}");
+        }
+        for fine in ["a", "a/b.txt", "..hidden", "a/...", "a/b..c"] {
+            assert!(check_relative(fine).is_ok(), "{fine:?}");
+        }
+    }
+
+    #[test]
+    fn test_joins_refuse_identically() {
+        let base = RemotePath {
+            user: None,
+            host: "h".into(),
+            path: "dst".into(),
+        };
+        let remote = join(&base, "../x").unwrap_err().to_string();
+        let local = local_join(Path::new("dst"), "../x")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(remote, local);
+        assert_eq!(join(&base, "a/b").unwrap().path, "dst/a/b");
+    }
+
+    fn guard(prefix: &str, links: &[&str]) -> DestinationGuard {
+        DestinationGuard {
+            prefix: prefix.into(),
+            links: links.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_guard_refuses_writes_through_symlinks() {
+        let g = guard("dst", &["dst/link", "dst/a/file"]);
+        assert!(g.check("dst/b/c.txt").is_ok());
+        assert!(g.check("dst//b/./c.txt").is_ok());
+        assert!(g.check("dst/link").is_err());
+        assert!(g.check("dst/link/c.txt").is_err());
+        assert!(g.check("dst/a/file").is_err());
+        assert!(g.check("dst/linked.txt").is_ok());
+    }
+
+    #[test]
+    fn test_guard_refuses_paths_outside_prefix() {
+        let g = guard("dst", &[]);
+        assert!(g.check("dst").is_ok());
+        assert!(g.check("dstx/a").is_err());
+        assert!(g.check("/etc/passwd").is_err());
+        assert!(g.check("dst/../etc").is_err());
+
+        let home = guard(".", &["link"]);
+        assert!(home.check("./a.txt").is_ok());
+        assert!(home.check("link/a.txt").is_err());
+        assert!(home.check("/tmp/a").is_err());
+
+        let root = guard("/", &[]);
+        assert!(root.check("/a").is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use super::ssh_cmd::{shell_escape, ssh_command, ssh_error_message};
+use super::ssh_cmd::{shell_escape, ssh_command, ssh_error};
 use super::{parse_remote_path, RemoteFileInfo, RemotePath};
 use crate::core::error::BcmrError;
 use tokio::io::AsyncReadExt;
@@ -9,10 +9,10 @@ pub async fn validate_ssh_connection(remote: &RemotePath) -> Result<(), BcmrErro
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr,
             &format!("Cannot connect to '{}'", target),
-        )));
+        ));
     }
     Ok(())
 }
@@ -47,10 +47,10 @@ pub async fn remote_stat(remote: &RemotePath) -> Result<RemoteFileInfo, BcmrErro
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr,
             &format!("Cannot stat remote path '{}'", remote),
-        )));
+        ));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -110,10 +110,10 @@ pub async fn remote_list_files(remote: &RemotePath) -> Result<Vec<(String, u64,
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr,
             &format!("Cannot list remote directory '{}'", remote),
-        )));
+        ));
     }
 
     let raw = String::from_utf8_lossy(&output.stdout);
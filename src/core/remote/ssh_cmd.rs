@@ -1,3 +1,4 @@
+use crate::core::error::BcmrError;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::process::Command;
 
@@ -105,31 +106,50 @@ pub(super) fn shell_escape(s: &str) -> String {
     s.replace('\'', "'\\''")
 }
 
-pub(super) fn ssh_error_message(stderr: &str, context: &str) -> String {
+/// Reads what ssh printed on failure as the kind of error it is: the
+/// connection, the login, or the command on the remote side.
+pub(super) fn ssh_error(stderr: &str, context: &str) -> BcmrError {
     let stderr_lower = stderr.to_lowercase();
-    if stderr_lower.contains("connection refused") {
-        format!(
+    let has = |needles: &[&str]| needles.iter().any(|n| stderr_lower.contains(n));
+    // ssh lists the methods it tried ("Permission denied (publickey).");
+    // a remote command's "Permission denied" is about a file.
+    if has(&[
+        "permission denied (",
+        "too many authentication failures",
+        "authentication failed",
+    ]) {
+        BcmrError::SshAuth(format!(
+            "{}: SSH authentication failed (check credentials/keys)",
+            context
+        ))
+    } else if has(&["host key verification failed"]) {
+        BcmrError::SshAuth(format!(
+            "{}: host key verification failed (check known_hosts)",
+            context
+        ))
+    } else if has(&["connection refused"]) {
+        BcmrError::SshConnection(format!(
             "{}: SSH connection refused (is sshd running on the host?)",
             context
-        )
-    } else if stderr_lower.contains("no route to host")
-        || stderr_lower.contains("network is unreachable")
-    {
-        format!("{}: host unreachable (check network connectivity)", context)
-    } else if stderr_lower.contains("permission denied") {
-        format!(
-            "{}: SSH authentication failed (check credentials/keys)",
+        ))
+    } else if has(&["no route to host", "network is unreachable"]) {
+        BcmrError::SshConnection(format!(
+            "{}: host unreachable (check network connectivity)",
             context
-        )
-    } else if stderr_lower.contains("could not resolve")
-        || stderr_lower.contains("name or service not known")
-    {
-        format!("{}: unknown host (check hostname)", context)
-    } else if stderr_lower.contains("no such file") || stderr_lower.contains("not a regular file") {
-        format!("{}: remote file not found", context)
-    } else if stderr_lower.contains("timed out") || stderr_lower.contains("connection timed out") {
-        format!("{}: SSH connection timed out", context)
+        ))
+    } else if has(&["could not resolve", "name or service not known"]) {
+        BcmrError::SshConnection(format!("{}: unknown host (check hostname)", context))
+    } else if has(&["timed out"]) {
+        BcmrError::SshConnection(format!("{}: SSH connection timed out", context))
+    } else if has(&[
+        "connection closed by",
+        "connection reset by",
+        "kex_exchange_identification",
+    ]) {
+        BcmrError::SshConnection(format!("{}: {}", context, stderr.trim()))
+    } else if has(&["no such file", "not a regular file"]) {
+        BcmrError::RemoteIo(format!("{}: remote file not found", context))
     } else {
-        format!("{}: {}", context, stderr.trim())
+        BcmrError::RemoteIo(format!("{}: {}", context, stderr.trim()))
     }
 }
@@ -1,7 +1,10 @@
-use super::attrs::{apply_remote_attrs_locally, preserve_remote_attrs, verify_remote_file};
+use super::attrs::{
+    apply_remote_attrs_locally, copy_remote_attrs, preserve_remote_attrs, verify_remote_file,
+};
+use super::confine;
 use super::ops::{remote_file_hash, remote_file_size, remote_list_files};
 use super::resume::check_resume_state;
-use super::ssh_cmd::{make_ssh_cmd, shell_escape, ssh_command, ssh_error_message};
+use super::ssh_cmd::{make_ssh_cmd, shell_escape, ssh_command, ssh_error};
 use super::{RemotePath, RemoteTransferOptions, TransferCallbacks};
 use crate::core::error::BcmrError;
 use std::path::Path;
@@ -39,7 +42,10 @@ pub async fn download_file(
         file_size,
         async move || {
             tokio::task::spawn_blocking(move || {
-                crate::core::checksum::calculate_hash(&local_path_for_hash)
+                crate::core::checksum::calculate_hash(
+                    &local_path_for_hash,
+                    crate::core::checksum::Algorithm::Blake3,
+                )
             })
             .await
             .map_err(|e| BcmrError::InvalidInput(e.to_string()))?
@@ -98,6 +104,7 @@ pub async fn download_file(
                 break;
             }
             dst_file.write_all(&buffer[..n]).await?;
+            crate::core::bwlimit::wait_async(n as u64).await;
             (cb.on_progress)(n as u64);
         }
         Ok(())
@@ -123,10 +130,10 @@ pub async fn download_file(
         if !decision.use_append_mode {
             let _ = tokio::fs::remove_file(local_dst).await;
         }
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr_buf,
             &format!("Download failed for '{}'", remote),
-        )));
+        ));
     }
 
     if opts.sync {
@@ -137,10 +144,14 @@ pub async fn download_file(
 
     if opts.verify {
         let local_path = local_dst.to_path_buf();
-        let local_hash =
-            tokio::task::spawn_blocking(move || crate::core::checksum::calculate_hash(&local_path))
-                .await
-                .map_err(|e| BcmrError::InvalidInput(e.to_string()))??;
+        let local_hash = tokio::task::spawn_blocking(move || {
+            crate::core::checksum::calculate_hash(
+                &local_path,
+                crate::core::checksum::Algorithm::Blake3,
+            )
+        })
+        .await
+        .map_err(|e| BcmrError::InvalidInput(e.to_string()))??;
         let remote_hash = remote_file_hash(remote, None).await?;
         if local_hash != remote_hash {
             return Err(BcmrError::InvalidInput(format!(
@@ -181,10 +192,10 @@ pub async fn upload_file(
                 .await?;
             if !mkdir_out.status.success() {
                 let stderr = String::from_utf8_lossy(&mkdir_out.stderr);
-                return Err(BcmrError::InvalidInput(ssh_error_message(
+                return Err(ssh_error(
                     &stderr,
                     &format!("Failed to create remote directory '{}'", parent.0),
-                )));
+                ));
             }
         }
     }
@@ -201,7 +212,10 @@ pub async fn upload_file(
         async move || remote_file_hash(&remote_for_hash, None).await,
         async move || {
             tokio::task::spawn_blocking(move || {
-                crate::core::checksum::calculate_hash(&local_path_for_hash)
+                crate::core::checksum::calculate_hash(
+                    &local_path_for_hash,
+                    crate::core::checksum::Algorithm::Blake3,
+                )
             })
             .await
             .map_err(|e| BcmrError::InvalidInput(e.to_string()))?
@@ -209,7 +223,11 @@ pub async fn upload_file(
         },
         async move |limit| {
             tokio::task::spawn_blocking(move || {
-                crate::core::checksum::calculate_partial_hash(&local_path_for_partial, limit)
+                crate::core::checksum::calculate_partial_hash(
+                    &local_path_for_partial,
+                    limit,
+                    crate::core::checksum::Algorithm::Blake3,
+                )
             })
             .await
             .map_err(|e| BcmrError::InvalidInput(e.to_string()))?
@@ -257,6 +275,7 @@ pub async fn upload_file(
                 break;
             }
             stdin.write_all(&buffer[..n]).await?;
+            crate::core::bwlimit::wait_async(n as u64).await;
             (cb.on_progress)(n as u64);
         }
         Ok(())
@@ -273,10 +292,10 @@ pub async fn upload_file(
     let output = child.wait_with_output().await?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(BcmrError::InvalidInput(ssh_error_message(
+        return Err(ssh_error(
             &stderr,
             &format!("Upload failed for '{}' -> {}", local_src.display(), remote),
-        )));
+        ));
     }
 
     if opts.verify && !verify_remote_file(local_src, remote).await? {
@@ -312,7 +331,7 @@ pub async fn download_directory(
 
     for (rel_path, _, is_dir) in &entries {
         if *is_dir {
-            let dir_path = local_dst.join(rel_path);
+            let dir_path = confine::local_join(local_dst, rel_path)?;
             if !dir_path.exists() {
                 tokio::fs::create_dir_all(&dir_path).await?;
             }
@@ -328,7 +347,7 @@ pub async fn download_directory(
             host: remote.host.clone(),
             path: format!("{}/{}", remote.path, rel_path),
         };
-        let local_file = local_dst.join(rel_path);
+        let local_file = confine::local_join(local_dst, rel_path)?;
         download_file(
             &file_remote,
             &local_file,
@@ -466,3 +485,159 @@ pub async fn upload_directory(
 
     Ok(())
 }
+
+/// Streams one remote file to another host through this machine: `cat` on
+/// the source host feeds `cat >` on the destination. Progress counts what
+/// the destination has taken, so it moves at the pace of the slower leg.
+pub async fn relay_file(
+    src: &RemotePath,
+    dst: &RemotePath,
+    cb: TransferCallbacks<'_>,
+    file_size: u64,
+    opts: &RemoteTransferOptions,
+) -> Result<(), BcmrError> {
+    let file_name = src.path.rsplit('/').next().unwrap_or(&src.path);
+    (cb.on_new_file)(file_name, file_size);
+
+    if let Some(parent) = dst.path.rsplit_once('/') {
+        if !parent.0.is_empty() {
+            let mkdir_out = ssh_command(&dst.ssh_target())
+                .arg(format!("mkdir -p '{}'", shell_escape(parent.0)))
+                .output()
+                .await?;
+            if !mkdir_out.status.success() {
+                let stderr = String::from_utf8_lossy(&mkdir_out.stderr);
+                return Err(ssh_error(
+                    &stderr,
+                    &format!("Failed to create remote directory '{}'", parent.0),
+                ));
+            }
+        }
+    }
+
+    let mut reader = ssh_command(&src.ssh_target())
+        .arg(format!("cat '{}'", shell_escape(&src.path)))
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let mut writer = ssh_command(&dst.ssh_target())
+        .arg(format!("cat > '{}'", shell_escape(&dst.path)))
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    let mut stdout = reader
+        .stdout
+        .take()
+        .ok_or_else(|| BcmrError::InvalidInput("Failed to capture SSH stdout".to_string()))?;
+    let mut stdin = writer
+        .stdin
+        .take()
+        .ok_or_else(|| BcmrError::InvalidInput("Failed to capture SSH stdin".to_string()))?;
+
+    let mut buffer = vec![0u8; 4 * 1024 * 1024];
+
+    let io_result: Result<(), BcmrError> = async {
+        loop {
+            let n = stdout.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            stdin.write_all(&buffer[..n]).await?;
+            crate::core::bwlimit::wait_async(n as u64).await;
+            (cb.on_progress)(n as u64);
+        }
+        Ok(())
+    }
+    .await;
+
+    drop(stdin);
+
+    if let Err(e) = io_result {
+        let _ = reader.kill().await;
+        let _ = writer.kill().await;
+        return Err(e);
+    }
+
+    let read_out = reader.wait_with_output().await?;
+    let write_out = writer.wait_with_output().await?;
+    if !read_out.status.success() {
+        let stderr = String::from_utf8_lossy(&read_out.stderr);
+        return Err(ssh_error(&stderr, &format!("Relay failed reading {}", src)));
+    }
+    if !write_out.status.success() {
+        let stderr = String::from_utf8_lossy(&write_out.stderr);
+        return Err(ssh_error(&stderr, &format!("Relay failed writing {}", dst)));
+    }
+
+    if opts.verify && remote_file_hash(src, None).await? != remote_file_hash(dst, None).await? {
+        return Err(BcmrError::InvalidInput(format!(
+            "Verification failed: {} -> {}",
+            src, dst
+        )));
+    }
+
+    if opts.preserve {
+        copy_remote_attrs(src, dst).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn relay_directory(
+    src: &RemotePath,
+    dst: &RemotePath,
+    cb: TransferCallbacks<'_>,
+    excludes: &[regex::Regex],
+    opts: &RemoteTransferOptions,
+    guard: &confine::DestinationGuard,
+) -> Result<(), BcmrError> {
+    let mut dirs = vec![dst.clone()];
+    let mut files = Vec::new();
+    for (rel_path, size, is_dir) in remote_list_files(src).await? {
+        if crate::core::traversal::is_excluded(std::path::Path::new(&rel_path), excludes) {
+            continue;
+        }
+        let target = confine::join(dst, &rel_path)?;
+        guard.check(&target.path)?;
+        if is_dir {
+            dirs.push(target);
+        } else {
+            files.push((confine::join(src, &rel_path)?, target, size));
+        }
+    }
+
+    let mkdir_cmd = dirs
+        .iter()
+        .map(|d| format!("'{}'", shell_escape(&d.path)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let output = ssh_command(&dst.ssh_target())
+        .arg(format!("mkdir -p {}", mkdir_cmd))
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ssh_error(
+            &stderr,
+            &format!("Failed to create remote directory '{}'", dst),
+        ));
+    }
+
+    for (file_src, file_dst, size) in &files {
+        relay_file(
+            file_src,
+            file_dst,
+            TransferCallbacks {
+                on_progress: cb.on_progress,
+                on_skip: cb.on_skip,
+                on_new_file: cb.on_new_file,
+            },
+            *size,
+            opts,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
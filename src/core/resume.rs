@@ -21,11 +21,11 @@ pub async fn resolve(
     dst: &Path,
     file_size: u64,
     resume: bool,
-    strict: bool,
+    strict: Option<checksum::Algorithm>,
     append: bool,
     callback: &impl Fn(u64),
 ) -> Result<ResumeState, BcmrError> {
-    if !(resume || append || strict) || !dst.exists() {
+    if !(resume || append || strict.is_some()) || !dst.exists() {
         return Ok(ResumeState {
             start_offset: 0,
             already_complete: false,
@@ -43,8 +43,8 @@ pub async fn resolve(
         })
         .await??;
 
-    let decision = if strict {
-        resolve_strict(src, dst, file_size, dst_len).await?
+    let decision = if let Some(algo) = strict {
+        resolve_strict(src, dst, file_size, dst_len, algo).await?
     } else if append {
         resolve_append(file_size, dst_len)
     } else if let Some(ref session) = loaded_session {
@@ -127,13 +127,14 @@ async fn resolve_strict(
     dst: &Path,
     file_size: u64,
     dst_len: u64,
+    algo: checksum::Algorithm,
 ) -> Result<Decision, BcmrError> {
     if dst_len == file_size {
         let src_path = src.to_path_buf();
         let dst_path = dst.to_path_buf();
         let (src_hash, dst_hash) = tokio::join!(
-            tokio::task::spawn_blocking(move || checksum::calculate_hash(&src_path)),
-            tokio::task::spawn_blocking(move || checksum::calculate_hash(&dst_path)),
+            tokio::task::spawn_blocking(move || checksum::calculate_hash(&src_path, algo)),
+            tokio::task::spawn_blocking(move || checksum::calculate_hash(&dst_path, algo)),
         );
         if src_hash?? == dst_hash?? {
             return Ok(Decision::AlreadyComplete);
@@ -144,8 +145,10 @@ async fn resolve_strict(
         let dst_path = dst.to_path_buf();
         let limit = dst_len;
         let (dst_hash, src_partial) = tokio::join!(
-            tokio::task::spawn_blocking(move || checksum::calculate_hash(&dst_path)),
-            tokio::task::spawn_blocking(move || checksum::calculate_partial_hash(&src_path, limit)),
+            tokio::task::spawn_blocking(move || checksum::calculate_hash(&dst_path, algo)),
+            tokio::task::spawn_blocking(move || {
+                checksum::calculate_partial_hash(&src_path, limit, algo)
+            }),
         );
         Ok(if dst_hash?? == src_partial?? {
             Decision::Resume
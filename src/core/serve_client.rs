@@ -157,10 +157,25 @@ impl ServeClient {
                 "ServeClient poisoned after a prior pipelined error; drop and reconnect".into(),
             ));
         }
-        self.rx
+        let msg = self
+            .rx
             .read_message(&mut self.reader)
             .await?
-            .ok_or_else(|| BcmrError::InvalidInput("server closed connection unexpectedly".into()))
+            .ok_or_else(|| {
+                BcmrError::InvalidInput("server closed connection unexpectedly".into())
+            })?;
+        // File data counts against --bwlimit uncompressed, as progress
+        // counts it.
+        match &msg {
+            Message::Data { payload } => {
+                crate::core::bwlimit::wait_async(payload.len() as u64).await
+            }
+            Message::DataCompressed { original_size, .. } => {
+                crate::core::bwlimit::wait_async(*original_size as u64).await
+            }
+            _ => {}
+        }
+        Ok(msg)
     }
 }
 
@@ -207,6 +222,7 @@ where
         }
         let frame = compress::encode_block(algo, buf[..n].to_vec());
         tx.write_message(writer, &frame).await?;
+        crate::core::bwlimit::wait_async(n as u64).await;
         on_chunk(n as u64);
     }
     Ok(())
@@ -229,6 +229,7 @@ impl ServeClient {
             }
             let frame = compress::encode_block(algo, buf[..n].to_vec());
             tx.write_message(w, &frame).await?;
+            crate::core::bwlimit::wait_async(n as u64).await;
             remaining -= n as u64;
         }
         tx.write_message(w, &Message::Done).await?;
@@ -353,6 +354,7 @@ impl ServeClient {
             if (bits.get(idx / 8).copied().unwrap_or(0) >> (idx % 8)) & 1 == 1 {
                 let frame = compress::encode_block(self.algo, buf[..filled].to_vec());
                 self.send(&frame).await?;
+                crate::core::bwlimit::wait_async(filled as u64).await;
             }
         }
         Ok(())
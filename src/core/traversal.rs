@@ -1,9 +1,65 @@
-use std::path::Path;
+use crate::core::error::BcmrError;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::SystemTime;
+use tokio::sync::mpsc;
 use walkdir::{DirEntry, WalkDir};
 
+/// How many patterns at the front of an exclude list are the config's
+/// default excludes, and the paths those have kept out. A set, since a
+/// copy meets the same path in its scan and again in its walk.
+static DEFAULT_EXCLUDES: AtomicUsize = AtomicUsize::new(0);
+static DEFAULT_EXCLUDED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// Entries `skip_unreadable` has warned about, so a path met again by a
+/// later walk of the same run is not reported twice.
+static UNREADABLE: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+pub fn set_default_excludes(count: usize) {
+    DEFAULT_EXCLUDES.store(count, Ordering::Relaxed);
+}
+
+/// Paths the default excludes have kept out of this run so far.
+pub fn default_excluded_count() -> usize {
+    DEFAULT_EXCLUDED.lock().as_ref().map_or(0, HashSet::len)
+}
+
 pub fn is_excluded(path: &Path, excludes: &[regex::Regex]) -> bool {
+    excluded_by(path, excludes).is_some()
+}
+
+/// Index of the first pattern in `excludes` that matches `path`.
+pub fn excluded_by(path: &Path, excludes: &[regex::Regex]) -> Option<usize> {
     let path_str = path.to_string_lossy();
-    excludes.iter().any(|re| re.is_match(&path_str))
+    let found = excludes.iter().position(|re| re.is_match(&path_str));
+    if found.is_some_and(|i| i < DEFAULT_EXCLUDES.load(Ordering::Relaxed)) {
+        DEFAULT_EXCLUDED
+            .lock()
+            .get_or_insert_with(HashSet::new)
+            .insert(path.to_path_buf());
+    }
+    found
+}
+
+/// How a file found under `root` (a source argument) is named in progress
+/// output: from `root`'s own name down, e.g. `frontend/app/routes/index.js`,
+/// so same-named files in different subtrees stay distinguishable.
+pub fn display_relative(path: &Path, root: &Path) -> String {
+    let base = root.parent().filter(|_| root.file_name().is_some());
+    let relative = base
+        .and_then(|b| path.strip_prefix(b).ok())
+        .or_else(|| path.strip_prefix(root).ok())
+        .filter(|r| !r.as_os_str().is_empty());
+    match relative {
+        Some(r) => r.to_string_lossy().into_owned(),
+        None => path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .into_owned(),
+    }
 }
 
 pub fn walk(
@@ -19,19 +75,422 @@ pub fn walk(
         walker = walker.min_depth(min_depth);
     }
 
-    if contents_first {
-        walker = walker.contents_first(true);
+    if !recursive {
+        walker = walker.max_depth(1);
     }
 
+    let excludes = excludes.to_vec();
+
+    let entries = walker
+        .into_iter()
+        .filter_entry(move |e| !is_excluded(e.path(), &excludes));
+    ContentsFirst::new(entries, contents_first)
+}
+
+/// `walk` with what it would filter out left in: each entry comes with the
+/// index of the pattern that excludes it, if any. Excluded directories are
+/// yielded but, as in `walk`, not entered.
+pub fn walk_tagged(
+    root: &Path,
+    recursive: bool,
+    min_depth: usize,
+    excludes: &[regex::Regex],
+) -> impl Iterator<Item = walkdir::Result<(DirEntry, Option<usize>)>> {
+    let mut walker = WalkDir::new(root).min_depth(min_depth);
     if !recursive {
         walker = walker.max_depth(1);
     }
+    let excludes = excludes.to_vec();
+    let mut entries = walker.into_iter();
+    std::iter::from_fn(move || {
+        let entry = match entries.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e)),
+        };
+        let hit = excluded_by(entry.path(), &excludes);
+        if hit.is_some() && entry.file_type().is_dir() {
+            entries.skip_current_dir();
+        }
+        Some(Ok((entry, hit)))
+    })
+}
+
+/// Turns a pre-order walk contents-first by holding each directory back
+/// until the walk has left it. walkdir's own `contents_first` can't be
+/// combined with `filter_entry`: an excluded directory is only seen after
+/// its contents were yielded, and skipping it then cuts its parent short.
+struct ContentsFirst<I> {
+    entries: I,
+    enabled: bool,
+    held: Vec<DirEntry>,
+    next: Option<DirEntry>,
+}
+
+impl<I> ContentsFirst<I> {
+    fn new(entries: I, enabled: bool) -> Self {
+        Self {
+            entries,
+            enabled,
+            held: Vec::new(),
+            next: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = walkdir::Result<DirEntry>>> Iterator for ContentsFirst<I> {
+    type Item = walkdir::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.enabled {
+            return self.entries.next();
+        }
+        loop {
+            let entry = match self.next.take() {
+                Some(entry) => entry,
+                None => match self.entries.next() {
+                    Some(Ok(entry)) => entry,
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => return self.held.pop().map(Ok),
+                },
+            };
+            if self.held.last().is_some_and(|d| d.depth() >= entry.depth()) {
+                self.next = Some(entry);
+                return self.held.pop().map(Ok);
+            }
+            if !entry.file_type().is_dir() {
+                return Some(Ok(entry));
+            }
+            self.held.push(entry);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EntryKind {
+    File,
+    Dir,
+    /// Sockets, fifos, devices, dangling links and Windows directory
+    /// junctions: nothing to copy.
+    Other,
+}
 
+/// One entry of a source tree, with everything the overwrite check, the
+/// progress total and the copy need from it.
+#[derive(Clone, Debug)]
+pub struct ScanEntry {
+    pub path: PathBuf,
+    /// `path` below the scanned root, `depth` components deep.
+    pub relative: PathBuf,
+    pub depth: usize,
+    pub kind: EntryKind,
+    /// Zero for anything but files.
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+}
+
+/// Walks everything below `root` once, pre-order, statting each entry a
+/// single time. Links are judged by what they point at, as the copy has
+/// always done, but directories behind them are not entered, and Windows
+/// junctions are not judged at all but reported as `Other`. Entries that
+/// cannot be read are passed over with a warning (see `skip_unreadable`).
+/// Entries come out as they are found, so callers can act on them right
+/// away or keep them as a manifest.
+pub fn scan(
+    root: &Path,
+    excludes: &[regex::Regex],
+) -> impl Iterator<Item = Result<ScanEntry, BcmrError>> {
+    scan_within(root, root, excludes)
+}
+
+/// `scan` of `root`, a directory somewhere below `top`, the path the user
+/// named: only `top` failing to read is an error.
+fn scan_within(
+    root: &Path,
+    top: &Path,
+    excludes: &[regex::Regex],
+) -> impl Iterator<Item = Result<ScanEntry, BcmrError>> {
+    let root = root.to_path_buf();
+    let top = top.to_path_buf();
     let excludes = excludes.to_vec();
+    // A directory `--fix-perms` opened up gets a walk of its own, run
+    // before the one that could not read it goes on.
+    let mut walks = vec![walk(&root, true, false, 1, &excludes)];
+    std::iter::from_fn(move || loop {
+        let Some(entry) = walks.last_mut()?.next() else {
+            walks.pop();
+            continue;
+        };
+        match entry.map(|entry| scan_entry(&root, entry)) {
+            Err(e) if relaxed(&e) => {
+                let dir = e.path().unwrap_or(&root).to_path_buf();
+                walks.push(walk(&dir, true, false, 1, &excludes));
+            }
+            Err(e) | Ok(Err(BcmrError::WalkDir(e))) if skip_unreadable(&e, &top) => {}
+            Err(e) => return Some(Err(e.into())),
+            Ok(entry) => return Some(entry),
+        }
+    })
+}
+
+/// Whether `err`, met walking below `top`, costs only the entry it is
+/// about, as with a mount point whose server has gone away or a directory
+/// that cannot be read. The entry is then warned about, with the OS error,
+/// and left out. `top` itself failing is no such thing: the user named it.
+pub fn skip_unreadable(err: &walkdir::Error, top: &Path) -> bool {
+    let (Some(path), Some(io)) = (err.path(), err.io_error()) else {
+        return false;
+    };
+    if path == top {
+        return false;
+    }
+    let first = UNREADABLE
+        .lock()
+        .get_or_insert_with(HashSet::new)
+        .insert(path.to_path_buf());
+    if first {
+        crate::core::oplog::warn(path, &format!("skipped: cannot read: {}", io));
+    }
+    true
+}
+
+/// Entries below `root` that `skip_unreadable` has passed over in this
+/// run. What lies under them is unknown rather than missing, which a
+/// caller comparing two trees has to tell apart.
+pub fn skipped_below(root: &Path) -> Vec<PathBuf> {
+    UNREADABLE.lock().as_ref().map_or_else(Vec::new, |set| {
+        set.iter()
+            .filter(|path| path.starts_with(root))
+            .cloned()
+            .collect()
+    })
+}
+
+/// `entries` of a walk of `top` with what `skip_unreadable` passes over
+/// left out.
+fn tolerant<'a>(
+    entries: impl Iterator<Item = walkdir::Result<DirEntry>> + 'a,
+    top: &'a Path,
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+    entries.filter(move |entry| !matches!(entry, Err(e) if skip_unreadable(e, top)))
+}
+
+/// Whether `err` was a directory that could not be read and now can.
+fn relaxed(err: &walkdir::Error) -> bool {
+    match (err.path(), err.io_error()) {
+        (Some(path), Some(io)) => crate::core::perms::relax(path, io),
+        _ => false,
+    }
+}
+
+fn scan_entry(root: &Path, entry: DirEntry) -> Result<ScanEntry, BcmrError> {
+    let md = if is_dir_link(&entry.file_type()) {
+        None
+    } else if entry.path_is_symlink() {
+        std::fs::metadata(entry.path()).ok()
+    } else {
+        Some(entry.metadata()?)
+    };
+    let kind = match &md {
+        Some(md) if md.is_dir() => EntryKind::Dir,
+        Some(md) if md.is_file() => EntryKind::File,
+        _ => EntryKind::Other,
+    };
+    let relative = entry.path().strip_prefix(root)?.to_path_buf();
+    Ok(ScanEntry {
+        depth: relative.components().count(),
+        relative,
+        size: md
+            .as_ref()
+            .filter(|_| kind == EntryKind::File)
+            .map_or(0, |md| md.len()),
+        mtime: md.as_ref().and_then(|md| md.modified().ok()),
+        path: entry.into_path(),
+        kind,
+    })
+}
 
-    walker
+/// Bytes and files a size scan found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeTotals {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl TreeTotals {
+    fn count(
+        &mut self,
+        entry: &ScanEntry,
+        record: &impl Fn(&Path, u64) -> Result<(), BcmrError>,
+    ) -> Result<(), BcmrError> {
+        if entry.kind == EntryKind::File {
+            record(&entry.path, entry.size)?;
+            self.bytes += entry.size;
+            self.files += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Subtrees per scan thread, so one deep subtree doesn't leave the other
+/// threads idle for long.
+const SHARDS_PER_JOB: usize = 4;
+
+/// Sums the files `scan` finds below `root`, walking up to `jobs` subtrees
+/// at once. On a network mount every readdir and stat waits on the
+/// server, so overlapping walks finish far sooner than one. `record` sees
+/// each file from whichever thread found it; an error from it stops the
+/// scan.
+pub fn sum_tree(
+    root: &Path,
+    excludes: &[regex::Regex],
+    jobs: usize,
+    record: impl Fn(&Path, u64) -> Result<(), BcmrError> + Sync,
+) -> Result<TreeTotals, BcmrError> {
+    let jobs = jobs.max(1);
+    let mut totals = TreeTotals::default();
+
+    // Split the tree a level at a time until there are enough subtrees to
+    // share out, counting the files met on the way down.
+    let mut shards = vec![root.to_path_buf()];
+    while jobs > 1 && shards.len() < jobs * SHARDS_PER_JOB {
+        let mut next = Vec::new();
+        for dir in &shards {
+            for entry in tolerant(walk(dir, false, false, 1, excludes), root) {
+                let entry = entry?;
+                // Linked directories are counted by `scan` but not entered.
+                if entry.file_type().is_dir() {
+                    next.push(entry.into_path());
+                    continue;
+                }
+                match scan_entry(dir, entry) {
+                    Ok(entry) => totals.count(&entry, &record)?,
+                    Err(BcmrError::WalkDir(e)) if skip_unreadable(&e, root) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        shards = next;
+        if shards.is_empty() {
+            return Ok(totals);
+        }
+    }
+
+    let taken = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let worker = || sum_shards(root, &shards, &taken, &failed, excludes, &record);
+    let threads = jobs.min(shards.len());
+    let results: Vec<_> = if threads == 1 {
+        vec![worker()]
+    } else {
+        std::thread::scope(|s| {
+            let handles: Vec<_> = (0..threads).map(|_| s.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+                .collect()
+        })
+    };
+    for result in results {
+        let sum = result?;
+        totals.bytes += sum.bytes;
+        totals.files += sum.files;
+    }
+    Ok(totals)
+}
+
+fn sum_shards(
+    top: &Path,
+    shards: &[PathBuf],
+    taken: &AtomicUsize,
+    failed: &AtomicBool,
+    excludes: &[regex::Regex],
+    record: &impl Fn(&Path, u64) -> Result<(), BcmrError>,
+) -> Result<TreeTotals, BcmrError> {
+    let mut totals = TreeTotals::default();
+    while let Some(dir) = shards.get(taken.fetch_add(1, Ordering::Relaxed)) {
+        for entry in scan_within(dir, top, excludes) {
+            if failed.load(Ordering::Relaxed) {
+                return Ok(totals);
+            }
+            if let Err(e) = entry.and_then(|entry| totals.count(&entry, record)) {
+                failed.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+    }
+    Ok(totals)
+}
+
+/// Runs a blocking walk on the blocking pool and hands its items over a
+/// bounded channel, so async code can await each one without readdir and
+/// stat calls holding up a runtime worker. Dropping the receiver stops the
+/// walk.
+pub fn in_background<I>(make: impl FnOnce() -> I + Send + 'static) -> mpsc::Receiver<I::Item>
+where
+    I: Iterator,
+    I::Item: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(256);
+    tokio::task::spawn_blocking(move || {
+        for item in make() {
+            if tx.blocking_send(item).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Recursive, contents-first `walk` for removal. With `one_file_system`
+/// a directory mounted from another file system is listed but not entered.
+pub fn walk_for_removal(
+    root: &Path,
+    one_file_system: bool,
+    excludes: &[regex::Regex],
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> {
+    let excludes = excludes.to_vec();
+    let entries = WalkDir::new(root)
+        .same_file_system(one_file_system)
         .into_iter()
-        .filter_entry(move |e| !is_excluded(e.path(), &excludes))
+        .filter_entry(move |e| !is_excluded(e.path(), &excludes));
+    ContentsFirst::new(entries, true)
+}
+
+/// The device a file lives on, where the platform says.
+#[cfg(unix)]
+pub fn device_id(md: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(md.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_md: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// A Windows directory junction or directory symlink: a reparse point
+/// (`FILE_ATTRIBUTE_REPARSE_POINT`) on a directory entry. std reports it
+/// as a link, so walks never enter it, but it can only be deleted with
+/// `remove_dir`, which takes the link and leaves its target alone.
+#[cfg(windows)]
+pub fn is_dir_link(ft: &std::fs::FileType) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+    ft.is_symlink_dir()
+}
+
+#[cfg(not(windows))]
+pub fn is_dir_link(_ft: &std::fs::FileType) -> bool {
+    false
+}
+
+/// Why an `EntryKind::Other` entry is left out of a copy.
+pub fn skip_reason(path: &Path) -> &'static str {
+    match path.symlink_metadata() {
+        Ok(md) if is_dir_link(&md.file_type()) => {
+            "skipped: directory junction or link, not followed"
+        }
+        _ => "skipped: not a regular file or directory",
+    }
 }
 
 #[cfg(test)]
@@ -62,6 +521,84 @@ mod tests {
         assert!(!is_excluded(Path::new("file.txt"), &excludes));
     }
 
+    #[test]
+    fn test_excluded_by_names_the_first_matching_pattern() {
+        let excludes = vec![
+            regex::Regex::new(r"\.tmp$").unwrap(),
+            regex::Regex::new(r"cache").unwrap(),
+            regex::Regex::new(r"\.log$").unwrap(),
+        ];
+        assert_eq!(excluded_by(Path::new("cache/a.log"), &excludes), Some(1));
+        assert_eq!(excluded_by(Path::new("a.log"), &excludes), Some(2));
+        assert_eq!(excluded_by(Path::new("a.txt"), &excludes), None);
+    }
+
+    #[test]
+    fn test_walk_tagged_matches_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("keep/cache/deep")).unwrap();
+        std::fs::write(dir.path().join("keep/a.txt"), b"a").unwrap();
+        std::fs::write(dir.path().join("keep/b.log"), b"b").unwrap();
+        std::fs::write(dir.path().join("keep/cache/deep/c.txt"), b"c").unwrap();
+        let excludes = vec![
+            regex::Regex::new(r"\.log$").unwrap(),
+            regex::Regex::new(r"/cache$").unwrap(),
+        ];
+
+        let tagged: Vec<(PathBuf, Option<usize>)> = walk_tagged(dir.path(), true, 1, &excludes)
+            .map(|e| e.map(|(e, hit)| (e.into_path(), hit)).unwrap())
+            .collect();
+        let kept: Vec<PathBuf> = walk(dir.path(), true, false, 1, &excludes)
+            .map(|e| e.unwrap().into_path())
+            .collect();
+        let untagged: Vec<PathBuf> = tagged
+            .iter()
+            .filter(|(_, hit)| hit.is_none())
+            .map(|(p, _)| p.clone())
+            .collect();
+        assert_eq!(untagged, kept);
+
+        let mut hits: Vec<(String, usize)> = tagged
+            .into_iter()
+            .filter_map(|(p, hit)| Some((display_relative(&p, dir.path()), hit?)))
+            .collect();
+        hits.sort();
+        let base = dir
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        assert_eq!(
+            hits,
+            vec![
+                (format!("{base}/keep/b.log"), 0),
+                (format!("{base}/keep/cache"), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display_relative() {
+        let root = Path::new("/home/me/frontend");
+        assert_eq!(
+            display_relative(Path::new("/home/me/frontend/app/index.js"), root),
+            "frontend/app/index.js"
+        );
+        assert_eq!(
+            display_relative(Path::new("frontend/a.js"), Path::new("frontend")),
+            "frontend/a.js"
+        );
+        assert_eq!(
+            display_relative(Path::new("./x/a.js"), Path::new(".")),
+            "x/a.js"
+        );
+        assert_eq!(
+            display_relative(Path::new("/tmp/a.js"), Path::new("/tmp/a.js")),
+            "a.js"
+        );
+    }
+
     #[test]
     fn test_walk_flat_directory() {
         let dir = tempfile::tempdir().unwrap();
@@ -116,4 +653,285 @@ mod tests {
         let dir_idx = entries.iter().position(|e| e.path() == sub).unwrap();
         assert!(file_idx < dir_idx);
     }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_in_background_keeps_the_runtime_free() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        // A walk as slow as a cold network mount: were it driven on the
+        // only runtime thread, the ticker could not run until it ended.
+        let slow_walk = || (0..5).inspect(|_| std::thread::sleep(Duration::from_millis(40)));
+        let ticks = Arc::new(AtomicU32::new(0));
+        let ticker = {
+            let ticks = Arc::clone(&ticks);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let mut rx = in_background(slow_walk);
+        let mut seen = Vec::new();
+        let mut ticks_before_last = 0;
+        while let Some(i) = rx.recv().await {
+            seen.push(i);
+            ticks_before_last = ticks.load(Ordering::Relaxed);
+        }
+        ticker.abort();
+        assert_eq!(seen, [0, 1, 2, 3, 4]);
+        assert!(ticks_before_last >= 5, "ticked {ticks_before_last} times");
+    }
+
+    #[test]
+    fn test_sum_tree_matches_a_serial_scan() {
+        use std::sync::atomic::AtomicU64;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("tree");
+        for a in 0..3 {
+            for b in 0..4 {
+                let sub = root.join(format!("a{a}/b{b}/deep"));
+                std::fs::create_dir_all(&sub).unwrap();
+                std::fs::write(sub.join("f.txt"), vec![0u8; a * 10 + b]).unwrap();
+                std::fs::write(sub.join("skip.log"), "excluded").unwrap();
+                std::fs::write(root.join(format!("a{a}/b{b}/g.bin")), "12345").unwrap();
+            }
+        }
+        std::fs::write(root.join("top.txt"), "top").unwrap();
+        std::fs::create_dir_all(root.join("empty/also-empty")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("a0"), root.join("linked")).unwrap();
+
+        let excludes = vec![regex::Regex::new(r"\.log$").unwrap()];
+        let mut serial = TreeTotals::default();
+        for entry in scan(&root, &excludes) {
+            serial.count(&entry.unwrap(), &|_, _| Ok(())).unwrap();
+        }
+        assert_eq!(serial.files, 25);
+
+        for jobs in [1, 2, 3, 16] {
+            let (files, bytes) = (AtomicU64::new(0), AtomicU64::new(0));
+            let totals = sum_tree(&root, &excludes, jobs, |_, size| {
+                files.fetch_add(1, Ordering::Relaxed);
+                bytes.fetch_add(size, Ordering::Relaxed);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(totals, serial, "jobs = {jobs}");
+            assert_eq!(files.into_inner(), serial.files);
+            assert_eq!(bytes.into_inner(), serial.bytes);
+        }
+    }
+
+    #[test]
+    fn test_sum_tree_stops_when_record_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            let sub = dir.path().join(format!("d{i}"));
+            std::fs::create_dir(&sub).unwrap();
+            std::fs::write(sub.join("f"), "x").unwrap();
+        }
+        let result = sum_tree(dir.path(), &[], 4, |_, _| Err(BcmrError::Cancelled));
+        assert!(matches!(result, Err(BcmrError::Cancelled)));
+    }
+
+    #[test]
+    fn test_scan_records_each_entry_once() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub/deeper")).unwrap();
+        std::fs::write(dir.path().join("sub/a.txt"), "abc").unwrap();
+        std::fs::write(dir.path().join("sub/deeper/b.log"), "skipped").unwrap();
+        std::fs::write(dir.path().join("c.txt"), "hello").unwrap();
+
+        let excludes = vec![regex::Regex::new(r"\.log$").unwrap()];
+        let mut entries: Vec<_> = scan(dir.path(), &excludes)
+            .map(|e| e.unwrap())
+            .map(|e| (e.relative.to_string_lossy().into_owned(), e.kind, e.size))
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                ("c.txt".to_string(), EntryKind::File, 5),
+                ("sub".to_string(), EntryKind::Dir, 0),
+                ("sub/a.txt".to_string(), EntryKind::File, 3),
+                ("sub/deeper".to_string(), EntryKind::Dir, 0),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_follows_links_without_entering_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        std::fs::create_dir_all(dir.path().join("elsewhere")).unwrap();
+        std::fs::write(dir.path().join("elsewhere/inner"), "x").unwrap();
+        std::fs::write(dir.path().join("target"), "1234").unwrap();
+        std::fs::create_dir(&tree).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("target"), tree.join("file-link")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("elsewhere"), tree.join("dir-link")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("gone"), tree.join("dangling")).unwrap();
+
+        let mut entries: Vec<_> = scan(&tree, &[])
+            .map(|e| e.unwrap())
+            .map(|e| (e.relative.to_string_lossy().into_owned(), e.kind, e.size))
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                ("dangling".to_string(), EntryKind::Other, 0),
+                ("dir-link".to_string(), EntryKind::Dir, 0),
+                ("file-link".to_string(), EntryKind::File, 4),
+            ]
+        );
+    }
+
+    /// `mklink /J`, which unlike a directory symlink needs no privilege.
+    #[cfg(windows)]
+    fn junction(link: &Path, target: &Path) {
+        let status = std::process::Command::new("cmd")
+            .args(["/C", "mklink", "/J"])
+            .arg(link)
+            .arg(target)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_scan_and_sum_skip_junctions() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        std::fs::create_dir_all(dir.path().join("elsewhere")).unwrap();
+        std::fs::write(dir.path().join("elsewhere/inner"), "12345678").unwrap();
+        std::fs::create_dir(&tree).unwrap();
+        std::fs::write(tree.join("real.txt"), "x").unwrap();
+        junction(&tree.join("out"), &dir.path().join("elsewhere"));
+        junction(&tree.join("loop"), &tree);
+
+        let mut entries: Vec<_> = scan(&tree, &[])
+            .map(|e| e.unwrap())
+            .map(|e| (e.relative.to_string_lossy().into_owned(), e.kind, e.size))
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                ("loop".to_string(), EntryKind::Other, 0),
+                ("out".to_string(), EntryKind::Other, 0),
+                ("real.txt".to_string(), EntryKind::File, 1),
+            ]
+        );
+        assert_eq!(
+            skip_reason(&tree.join("out")),
+            "skipped: directory junction or link, not followed"
+        );
+        let totals = sum_tree(&tree, &[], 2, |_, _| Ok(())).unwrap();
+        assert_eq!(totals, TreeTotals { bytes: 1, files: 1 });
+    }
+
+    #[test]
+    fn test_walk_contents_first_with_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["a/b", "a/skip", "c", "skip"] {
+            std::fs::create_dir_all(dir.path().join(d)).unwrap();
+        }
+        for f in ["a/1", "a/b/2", "a/skip/3", "c/4", "skip/5", "6"] {
+            std::fs::write(dir.path().join(f), "x").unwrap();
+        }
+
+        let excludes = vec![regex::Regex::new(r"skip$").unwrap()];
+        let entries: Vec<_> = walk(dir.path(), true, true, 0, &excludes)
+            .map(|e| {
+                e.unwrap()
+                    .path()
+                    .strip_prefix(dir.path())
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect();
+        let mut names: Vec<_> = entries.iter().map(|p| p.to_string_lossy()).collect();
+        names.sort();
+        assert_eq!(names, ["", "6", "a", "a/1", "a/b", "a/b/2", "c", "c/4"]);
+
+        let pos = |p: &str| entries.iter().position(|e| e == Path::new(p)).unwrap();
+        assert!(pos("a/b/2") < pos("a/b") && pos("a/b") < pos("a"));
+        assert!(pos("c/4") < pos("c"));
+        assert_eq!(pos(""), entries.len() - 1);
+    }
+
+    fn unreadable(path: &Path) -> bool {
+        UNREADABLE
+            .lock()
+            .as_ref()
+            .is_some_and(|set| set.contains(path))
+    }
+
+    #[test]
+    fn test_unreadable_entries_are_passed_over_but_not_the_top() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("f");
+        std::fs::write(&file, "x").unwrap();
+        let entry = WalkDir::new(dir.path())
+            .into_iter()
+            .map(|e| e.unwrap())
+            .find(|e| e.path() == file)
+            .unwrap();
+        std::fs::remove_file(&file).unwrap();
+        let err = entry.metadata().unwrap_err();
+        assert!(skip_unreadable(&err, dir.path()));
+        assert!(unreadable(&file));
+
+        let missing = dir.path().join("missing");
+        let top_err = WalkDir::new(&missing)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(!skip_unreadable(&top_err, &missing));
+
+        let kept = WalkDir::new(dir.path()).into_iter().next().unwrap();
+        let left: Vec<_> = tolerant(vec![kept, Err(err)].into_iter(), dir.path()).collect();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].is_ok());
+    }
+
+    #[test]
+    fn test_scan_goes_on_past_a_directory_that_vanishes() {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["d1", "d2"] {
+            std::fs::create_dir(dir.path().join(d)).unwrap();
+            std::fs::write(dir.path().join(d).join("f"), "x").unwrap();
+        }
+
+        // As a mount point whose server goes away mid-scan.
+        let mut found = Vec::new();
+        let mut gone = None;
+        for entry in scan(dir.path(), &[]) {
+            let entry = entry.unwrap();
+            if gone.is_none() {
+                let other = if entry.relative == Path::new("d1") {
+                    "d2"
+                } else {
+                    "d1"
+                };
+                let path = dir.path().join(other);
+                std::fs::rename(&path, dir.path().join("elsewhere")).unwrap();
+                gone = Some(path);
+            }
+            found.push(entry.relative);
+        }
+        let gone = gone.unwrap();
+        assert_eq!(found.len(), 2, "{found:?}");
+        assert!(found[1].starts_with(&found[0]));
+        assert!(unreadable(&gone));
+    }
 }
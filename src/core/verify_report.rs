@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::core::checksum::Algorithm;
+use crate::core::oplog::timestamp;
+
+struct Report {
+    file: File,
+    algorithm: Algorithm,
+    files: u64,
+    passed: u64,
+    failed: u64,
+    bytes: u64,
+}
+
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Header {
+        command: &'a [String],
+        cwd: String,
+        started: String,
+        algorithm: String,
+    },
+    File {
+        src: &'a Path,
+        dst: &'a Path,
+        size: u64,
+        algorithm: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        digest: Option<&'a str>,
+        /// Only when it differs from the source's.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        dst_digest: Option<&'a str>,
+        result: &'static str,
+    },
+    Summary {
+        finished: String,
+        files: u64,
+        passed: u64,
+        failed: u64,
+        bytes: u64,
+    },
+}
+
+/// What one verified file came to. `src_digest` is `None` when the file
+/// failed before it could be hashed (missing, wrong size).
+pub struct Outcome<'a> {
+    pub src: &'a Path,
+    pub dst: &'a Path,
+    pub size: u64,
+    pub src_digest: Option<&'a str>,
+    pub dst_digest: Option<&'a str>,
+}
+
+impl<'a> Outcome<'a> {
+    fn passed(&self) -> bool {
+        self.src_digest.is_some() && self.src_digest == self.dst_digest
+    }
+
+    fn to_record(&self, algorithm: Algorithm) -> Record<'a> {
+        let passed = self.passed();
+        Record::File {
+            src: self.src,
+            dst: self.dst,
+            size: self.size,
+            algorithm: algorithm.to_string(),
+            digest: self.src_digest,
+            dst_digest: self.dst_digest.filter(|_| !passed),
+            result: if passed { "PASS" } else { "FAIL" },
+        }
+    }
+}
+
+/// Starts a report at `path`, replacing any earlier one, and writes its
+/// header. Records go out one JSON line at a time as files are verified, so
+/// a run that dies leaves everything verified up to then.
+pub fn open(path: &Path, args: &[String], algorithm: Algorithm) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    let cwd = std::env::current_dir()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    write_record(
+        &mut file,
+        &Record::Header {
+            command: args,
+            cwd,
+            started: timestamp(SystemTime::now()),
+            algorithm: algorithm.to_string(),
+        },
+    )?;
+    *REPORT.lock() = Some(Report {
+        file,
+        algorithm,
+        files: 0,
+        passed: 0,
+        failed: 0,
+        bytes: 0,
+    });
+    Ok(())
+}
+
+pub fn is_open() -> bool {
+    REPORT.lock().is_some()
+}
+
+pub fn record(outcome: &Outcome<'_>) {
+    let mut report = REPORT.lock();
+    let Some(report) = report.as_mut() else {
+        return;
+    };
+    report.files += 1;
+    report.bytes += outcome.size;
+    if outcome.passed() {
+        report.passed += 1;
+    } else {
+        report.failed += 1;
+    }
+    let record = outcome.to_record(report.algorithm);
+    // Like the log, a report that cannot be written must not abort the
+    // copy it describes.
+    let _ = write_record(&mut report.file, &record);
+}
+
+/// Writes the totals and syncs the report to disk. Does nothing when no
+/// report is open, so it is safe to call on every exit path.
+pub fn finish() -> io::Result<()> {
+    let Some(mut report) = REPORT.lock().take() else {
+        return Ok(());
+    };
+    write_record(
+        &mut report.file,
+        &Record::Summary {
+            finished: timestamp(SystemTime::now()),
+            files: report.files,
+            passed: report.passed,
+            failed: report.failed,
+            bytes: report.bytes,
+        },
+    )?;
+    report.file.sync_all()
+}
+
+fn write_record(file: &mut File, record: &Record<'_>) -> io::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_records_carry_the_outcome() {
+        let outcome = |src_digest, dst_digest| Outcome {
+            src: Path::new("a/x.bin"),
+            dst: Path::new("b/x.bin"),
+            size: 42,
+            src_digest,
+            dst_digest,
+        };
+        let line =
+            |o: &Outcome<'_>| serde_json::to_string(&o.to_record(Algorithm::Blake3)).unwrap();
+
+        assert_eq!(
+            line(&outcome(Some("ab"), Some("ab"))),
+            r#"{"type":"file","src":"a/x.bin","dst":"b/x.bin","size":42,"algorithm":"blake3","digest":"ab","result":"PASS"}"#
+        );
+        assert_eq!(
+            line(&outcome(Some("ab"), Some("cd"))),
+            r#"{"type":"file","src":"a/x.bin","dst":"b/x.bin","size":42,"algorithm":"blake3","digest":"ab","dst_digest":"cd","result":"FAIL"}"#
+        );
+        assert!(!outcome(None, None).passed());
+    }
+}
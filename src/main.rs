@@ -7,26 +7,29 @@ mod output;
 mod ui;
 
 use crate::app::commands::{
-    handle_check_command, handle_copy_command, handle_init_command, handle_move_command,
-    handle_remove_command,
-};
-use crate::app::completions::{
-    build_completion_command, remote_completion_script, POWERSHELL_REMOTE_INJECT,
+    handle_bench_command, handle_check_command, handle_checksum_command, handle_copy_command,
+    handle_du_command, handle_init_command, handle_list_command, handle_move_command,
+    handle_remove_command, handle_sync_command, handle_undo_command, handle_verify_command,
 };
+use crate::app::completions::completion_script;
 use crate::app::status::handle_status_command;
 use crate::app::updates::background_update_check;
 use crate::config::{is_json_mode, set_json_mode};
+use crate::core::checksum::Algorithm;
 use anyhow::Result;
 use cli::Commands;
 use std::sync::mpsc;
 
-fn maybe_detach(cli: &cli::Cli) -> Result<bool> {
-    let is_operation = matches!(
-        cli.command,
-        Commands::Copy { .. } | Commands::Move { .. } | Commands::Remove { .. }
-    );
+/// Commands that change files, as opposed to inspecting them.
+fn is_operation(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Copy(_) | Commands::Move(_) | Commands::Remove(_) | Commands::Sync(_)
+    )
+}
 
-    if !cli.json || !is_operation {
+fn maybe_detach(cli: &cli::Cli) -> Result<bool> {
+    if !cli.global.json || !is_operation(&cli.command) {
         return Ok(false);
     }
 
@@ -69,24 +72,269 @@ fn maybe_detach(cli: &cli::Cli) -> Result<bool> {
     Ok(true)
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli = cli::parse_args();
 
     if maybe_detach(&cli)? {
         return Ok(());
     }
 
-    set_json_mode(cli.json || cli._bg.is_some());
+    set_json_mode(cli.global.json || cli._bg.is_some());
+    config::set_quiet(cli.global.quiet);
+    config::set_stats(cli.global.stats);
+
+    // Before the runtime starts its threads, which take their niceness and
+    // I/O class from the thread that spawns them.
+    set_priority(&cli)?;
+
+    tokio::runtime::Runtime::new()?.block_on(start(cli))
+}
+
+/// `--nice` / `--ionice`, else `[behavior]`. Failing to lower the priority
+/// is a warning; the run goes ahead at normal priority.
+fn set_priority(cli: &cli::Cli) -> Result<()> {
+    let behavior = &config::CONFIG.behavior;
+    let nice = cli.global.nice.or(behavior.nice);
+    if let Some(n) = nice.filter(|n| !(-20..=19).contains(n)) {
+        anyhow::bail!(
+            "invalid config: behavior.nice must be between -20 and 19 (got {})",
+            n
+        );
+    }
+    let io_class = match cli.global.ionice {
+        Some(class) => Some(class),
+        None => behavior
+            .ionice
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("invalid config: behavior.ionice: {}", e))?,
+    };
+    let applied = core::priority::apply(&mut core::priority::System, nice, io_class);
+    if cli.global.verbose && !config::is_quiet() {
+        for line in &applied.verbose {
+            eprintln!("{}", line);
+        }
+    }
+    ui::progress::print_warnings(&applied.warnings);
+    Ok(())
+}
+
+/// Warns about a config file that did not load or has keys nothing reads,
+/// and carries on with what did load; `strict` makes either fatal.
+fn check_config(strict: bool) -> Result<()> {
+    let mut warnings = Vec::new();
+    if let Some(e) = config::load_error() {
+        if strict {
+            anyhow::bail!("invalid config: {}", e);
+        }
+        warnings.push(format!("invalid config, using the defaults: {}", e));
+    }
+    config::CONFIG
+        .progress
+        .validate()
+        .map_err(|e| anyhow::anyhow!("invalid config: {}", e))?;
+    let unknown = config::unknown_keys();
+    if strict && !unknown.is_empty() {
+        anyhow::bail!("invalid config: unknown key '{}'", unknown.join("', '"));
+    }
+    warnings.extend(
+        unknown
+            .iter()
+            .map(|key| format!("unknown config key '{}' ignored", key)),
+    );
+    ui::progress::print_warnings(&warnings);
+    Ok(())
+}
+
+async fn start(cli: cli::Cli) -> Result<()> {
+    // `bcmr config` has to work on a broken file, and reports on it itself.
+    if !matches!(cli.command, Commands::Config { .. }) {
+        check_config(cli.global.strict_config)?;
+    }
+    if let Some(ms) = cli.global.refresh_ms {
+        config::set_refresh_ms(ms);
+    }
+    if let Some(fd) = cli.global.progress_fd {
+        config::set_progress_fd(fd);
+    }
+    core::checksum::set_read_options(core::checksum::ReadOptions {
+        buffer_size: config::CONFIG.hash.buffer_size,
+        mmap_threshold: if cli.global.hash_mmap {
+            Some(0)
+        } else {
+            config::CONFIG.hash.mmap_threshold
+        },
+        direct: false,
+    });
+
+    if let Some(dst) = lock_target(&cli) {
+        // Without a state directory there is nowhere to keep locks; the run
+        // goes ahead unlocked, as it did before locking existed.
+        if let Some(dir) = core::history::state_dir() {
+            core::lock::acquire(&dir.join("locks"), dst, cli.global.wait_lock).await?;
+        }
+    }
+
+    if let Some(path) = &cli.global.log {
+        core::oplog::open(path)
+            .map_err(|e| anyhow::anyhow!("cannot open log file '{}': {}", path.display(), e))?;
+        core::oplog::header(&std::env::args().collect::<Vec<_>>());
+    }
+
+    if let Some(path) = cli
+        .global
+        .journal
+        .as_ref()
+        .filter(|_| is_operation(&cli.command))
+    {
+        core::journal::open(
+            path,
+            &std::env::args().collect::<Vec<_>>(),
+            cli.global.journal_strict,
+        )
+        .map_err(|e| anyhow::anyhow!("cannot open journal '{}': {}", path.display(), e))?;
+    }
+
+    if config::CONFIG.undo.journal && is_operation(&cli.command) {
+        // Undo is a convenience: a journal that cannot be opened does not
+        // stop the run.
+        if let Some(path) = core::history::default_path() {
+            let _ = core::history::open(
+                &path,
+                &std::env::args().collect::<Vec<_>>(),
+                config::CONFIG.undo.max_size,
+            );
+        }
+    }
+
+    if let Some((path, algo)) = verify_report_target(&cli.command) {
+        core::verify_report::open(path, &std::env::args().collect::<Vec<_>>(), algo).map_err(
+            |e| anyhow::anyhow!("cannot open verify report '{}': {}", path.display(), e),
+        )?;
+    }
 
     let update_rx = background_update_check(&cli.command);
 
+    let outcome = run(&cli).await;
+    report_default_excludes(&cli);
+    core::lock::release();
+    core::perms::restore();
+    let report = finish_verify_report();
+    let outcome = outcome.and(report);
+    // Raised where no renderer was running to show them (dry runs, scans
+    // that failed before a renderer started).
+    ui::progress::print_warnings(&core::oplog::take_warnings());
+    let error = outcome.as_ref().err().map(|e| e.to_string());
+    if cli.global.log.is_some() {
+        core::oplog::footer(error.as_deref());
+    }
+    if let Err(e) = core::journal::finish(error.as_deref()) {
+        eprintln!("bcmr: cannot finish journal: {}", e);
+        if cli.global.journal_strict {
+            std::process::exit(1);
+        }
+    }
+    // The renderer has already closed with "Interrupted"/"Terminated";
+    // all that is left is the exit status a shell expects for the signal.
+    if let Err(e) = &outcome {
+        if let (Some(core::error::BcmrError::Cancelled), Some(signal)) =
+            (e.downcast_ref(), ui::runner::last_interrupt())
+        {
+            std::process::exit(signal.exit_code());
+        }
+        // Reported the way returning the error would, under its own status.
+        if let Some(code) = e
+            .downcast_ref::<core::error::BcmrError>()
+            .map(|e| e.exit_code())
+            .filter(|&code| code != 1)
+        {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(code);
+        }
+    }
+    outcome?;
+
+    if !is_json_mode() && !cli.global.quiet {
+        show_update_hint(update_rx);
+    }
+
+    if core::oplog::stats().warnings > 0 && !cli.global.warnings_ok {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        std::process::exit(core::error::EXIT_WARNINGS);
+    }
+    Ok(())
+}
+
+/// The line `-v` and `-n` end with when `[filters] default_excludes` kept
+/// anything out, so a file missing from the destination is no mystery.
+fn report_default_excludes(cli: &cli::Cli) {
+    let count = core::traversal::default_excluded_count();
+    if count == 0
+        || is_json_mode()
+        || config::is_quiet()
+        || !(cli.global.verbose || cli.global.dry_run)
+    {
+        return;
+    }
+    eprintln!(
+        "{} {} skipped by default excludes (--no-default-excludes to include them)",
+        count,
+        if count == 1 { "entry" } else { "entries" }
+    );
+}
+
+/// The local destination an operation writes to, locked for the run so
+/// that two runs cannot write into it at once. Dry runs write nothing.
+fn lock_target(cli: &cli::Cli) -> Option<&std::path::Path> {
+    if cli.global.dry_run {
+        return None;
+    }
+    let dst = match &cli.command {
+        Commands::Copy(args) => args.common.paths.last()?,
+        Commands::Move(args) => args.common.paths.last()?,
+        Commands::Sync(args) => &args.dst,
+        Commands::Watch(args) => &args.dst,
+        _ => return None,
+    };
+    core::remote::parse_remote_path(&dst.to_string_lossy())
+        .is_none()
+        .then_some(dst.as_path())
+}
+
+fn verify_report_target(command: &Commands) -> Option<(&std::path::Path, Algorithm)> {
+    match command {
+        Commands::Copy(args) => args
+            .common
+            .verify_report
+            .as_deref()
+            .map(|p| (p, args.common.checksum_algo())),
+        Commands::Move(args) => args
+            .common
+            .verify_report
+            .as_deref()
+            .map(|p| (p, args.common.checksum_algo())),
+        Commands::Verify(args) => args
+            .verify_report
+            .as_deref()
+            .map(|p| (p, Algorithm::Sha256)),
+        _ => None,
+    }
+}
+
+fn finish_verify_report() -> Result<()> {
+    core::verify_report::finish().map_err(|e| anyhow::anyhow!("cannot write verify report: {}", e))
+}
+
+async fn run(cli: &cli::Cli) -> Result<()> {
     match &cli.command {
-        Commands::Copy { .. } => handle_copy_command(&cli.command).await?,
-        Commands::Move { .. } => handle_move_command(&cli.command).await?,
-        Commands::Remove { .. } => handle_remove_command(&cli.command).await?,
-        Commands::Check { .. } => {
-            let result = handle_check_command(&cli.command).await;
+        Commands::Copy(args) => handle_copy_command(args).await?,
+        Commands::Move(args) => handle_move_command(args).await?,
+        Commands::Remove(args) => handle_remove_command(args).await?,
+        Commands::Sync(args) => handle_sync_command(args).await?,
+        Commands::Watch(args) => commands::watch::run(args).await?,
+        Commands::Check(args) => {
+            let result = handle_check_command(args).await;
             match result {
                 Ok(r) => {
                     if is_json_mode() {
@@ -115,11 +363,110 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Commands::Verify(args) => {
+            let result = handle_verify_command(args).await;
+            // The exits below skip the caller's cleanup.
+            finish_verify_report()?;
+            match result {
+                Ok(r) => {
+                    if is_json_mode() {
+                        let out = output::CommandOutput::Verify(r);
+                        println!("{}", out.to_json());
+                        let code = out.exit_code();
+                        if code != 0 {
+                            std::process::exit(code);
+                        }
+                    } else {
+                        output::print_verify_human(&r);
+                        if !r.matched {
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if is_json_mode() {
+                        let out = output::error_output("verify", &e);
+                        println!("{}", out.to_json());
+                        std::process::exit(2);
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Commands::List(args) => handle_list_command(args).await?,
+        Commands::Du(args) => match handle_du_command(args).await {
+            Ok(r) => {
+                if is_json_mode() {
+                    println!("{}", output::CommandOutput::Du(r).to_json());
+                } else {
+                    output::print_du_human(&r);
+                }
+            }
+            Err(e) => {
+                if is_json_mode() {
+                    println!("{}", output::error_output("du", &e).to_json());
+                    std::process::exit(2);
+                } else {
+                    return Err(e);
+                }
+            }
+        },
+        Commands::Bench(args) => match handle_bench_command(args).await {
+            Ok(r) => {
+                if is_json_mode() {
+                    println!("{}", output::CommandOutput::Bench(r).to_json());
+                } else {
+                    output::print_bench_human(&r);
+                }
+            }
+            Err(e) => {
+                if is_json_mode() {
+                    println!("{}", output::error_output("bench", &e).to_json());
+                    std::process::exit(2);
+                } else {
+                    return Err(e);
+                }
+            }
+        },
+        Commands::Undo(args) => {
+            let code = handle_undo_command(args, cli.global.dry_run)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Journal {
+            action: cli::JournalAction::Show { path },
+        } => commands::journal::show(path)?,
+        Commands::Config { action } => match action {
+            cli::ConfigAction::Path => commands::config::path(),
+            cli::ConfigAction::Show { default } => {
+                commands::config::show(*default, cli.global.profile.as_deref())?
+            }
+            cli::ConfigAction::Init { force } => commands::config::init(*force)?,
+            cli::ConfigAction::Edit => commands::config::edit()?,
+        },
+        Commands::Checksum(args) => {
+            let code = handle_checksum_command(args).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
         Commands::Status { job_id } => {
             handle_status_command(job_id);
         }
         Commands::Init { .. } => handle_init_command(&cli.command)?,
         Commands::Update => commands::update::run()?,
+        Commands::Doctor { dir } => {
+            let checks = commands::doctor::run(dir.as_deref().unwrap_or(std::path::Path::new(".")));
+            commands::doctor::print(&checks);
+            if checks
+                .iter()
+                .any(|c| c.level == commands::doctor::Level::Fail)
+            {
+                std::process::exit(1);
+            }
+        }
         Commands::Serve { root, listen } => {
             if let Some(addr) = listen {
                 let parsed: std::net::SocketAddr = addr.parse().map_err(|e| {
@@ -140,32 +487,10 @@ async fn main() -> Result<()> {
             }
         }
         Commands::Completions { shell } => {
-            let mut cmd = build_completion_command();
-            let mut buf = Vec::new();
-            clap_complete::generate(*shell, &mut cmd, "bcmr", &mut buf);
-            let base = String::from_utf8(buf).expect("clap generated invalid UTF-8");
-
-            if *shell == clap_complete::Shell::PowerShell {
-                let injected = base.replacen(
-                    "param($wordToComplete, $commandAst, $cursorPosition)\n",
-                    &format!(
-                        "param($wordToComplete, $commandAst, $cursorPosition)\n{}\n",
-                        POWERSHELL_REMOTE_INJECT
-                    ),
-                    1,
-                );
-                print!("{}", injected);
-            } else {
-                print!("{}", base);
-                print!("{}", remote_completion_script(shell));
-            }
+            print!("{}", completion_script(*shell));
         }
     }
 
-    if !is_json_mode() {
-        show_update_hint(update_rx);
-    }
-
     Ok(())
 }
 
@@ -5,6 +5,9 @@ use std::path::PathBuf;
 #[serde(tag = "command", rename_all = "snake_case")]
 pub enum CommandOutput {
     Check(CheckResult),
+    Verify(VerifyResult),
+    Du(DuResult),
+    Bench(BenchResult),
     Error(ErrorResult),
 }
 
@@ -45,6 +48,107 @@ pub struct CheckSummary {
     pub total_bytes: u64,
 }
 
+#[derive(Serialize)]
+pub struct VerifyResult {
+    pub status: Status,
+    pub matched: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub differences: Vec<Difference>,
+    pub summary: VerifySummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    MissingDst,
+    MissingSrc,
+    Type,
+    Size,
+    Hash,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dst_size: Option<u64>,
+    /// First differing byte of a hash mismatch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    pub is_dir: bool,
+}
+
+#[derive(Serialize, Default)]
+pub struct VerifySummary {
+    pub files: u64,
+    pub hashed: u64,
+    pub missing_dst: u64,
+    pub missing_src: u64,
+    pub type_mismatch: u64,
+    pub size_mismatch: u64,
+    pub hash_mismatch: u64,
+}
+
+#[derive(Serialize)]
+pub struct DuResult {
+    pub status: Status,
+    /// Allocated blocks rather than file lengths.
+    pub blocks: bool,
+    pub paths: Vec<DuUsage>,
+    pub total_bytes: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub largest: Vec<DuFile>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DuUsage {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub files: u64,
+    /// Directories down to --max-depth, in path order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<DuFile>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct DuFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct BenchResult {
+    pub status: Status,
+    pub dir: PathBuf,
+    /// Bytes copied per run.
+    pub size: u64,
+    pub files: u64,
+    pub runs: Vec<BenchRun>,
+}
+
+/// One data set copied with one engine.
+#[derive(Serialize, Debug)]
+pub struct BenchRun {
+    /// `random` or `sparse`.
+    pub data: &'static str,
+    pub engine: String,
+    pub seconds: f64,
+    pub bytes_per_sec: f64,
+    /// Disk space the copies take up, holes not counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocated: Option<u64>,
+    /// Why the run did not finish, e.g. no reflink support.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResult {
     pub status: Status,
@@ -79,6 +183,44 @@ impl CheckResult {
     }
 }
 
+impl VerifyResult {
+    pub fn new(differences: Vec<Difference>, files: u64, hashed: u64) -> Self {
+        let mut summary = VerifySummary {
+            files,
+            hashed,
+            ..VerifySummary::default()
+        };
+        for d in &differences {
+            let count = match d.kind {
+                DifferenceKind::MissingDst => &mut summary.missing_dst,
+                DifferenceKind::MissingSrc => &mut summary.missing_src,
+                DifferenceKind::Type => &mut summary.type_mismatch,
+                DifferenceKind::Size => &mut summary.size_mismatch,
+                DifferenceKind::Hash => &mut summary.hash_mismatch,
+            };
+            *count += 1;
+        }
+        Self {
+            status: Status::Success,
+            matched: differences.is_empty(),
+            differences,
+            summary,
+            error: None,
+            error_kind: None,
+        }
+    }
+
+    pub fn error(msg: impl Into<String>, kind: impl Into<String>) -> Self {
+        Self {
+            status: Status::Error,
+            matched: false,
+            error: Some(msg.into()),
+            error_kind: Some(kind.into()),
+            ..Self::new(Vec::new(), 0, 0)
+        }
+    }
+}
+
 impl CommandOutput {
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).expect("CommandOutput must be serializable")
@@ -95,6 +237,29 @@ impl CommandOutput {
                     1
                 }
             }
+            CommandOutput::Verify(r) => {
+                if matches!(r.status, Status::Error) {
+                    2
+                } else if r.matched {
+                    0
+                } else {
+                    1
+                }
+            }
+            CommandOutput::Du(r) => {
+                if matches!(r.status, Status::Error) {
+                    2
+                } else {
+                    0
+                }
+            }
+            CommandOutput::Bench(r) => {
+                if matches!(r.status, Status::Error) {
+                    2
+                } else {
+                    0
+                }
+            }
             CommandOutput::Error(_) => 2,
         }
     }
@@ -172,11 +337,94 @@ pub fn print_check_human(r: &CheckResult) {
     );
 }
 
+pub fn print_verify_human(r: &VerifyResult) {
+    use crossterm::style::{Color, ResetColor, SetForegroundColor};
+
+    for d in &r.differences {
+        crate::ui::display::print_difference(d);
+    }
+
+    let s = &r.summary;
+    if r.matched {
+        println!(
+            "{}Trees match:{} {} files compared, {} hashed",
+            SetForegroundColor(Color::Green),
+            ResetColor,
+            s.files,
+            s.hashed
+        );
+        return;
+    }
+    println!(
+        "\nSummary: {} files compared, {} hashed; {} missing in destination, {} missing in source, {} type, {} size and {} hash mismatches",
+        s.files,
+        s.hashed,
+        s.missing_dst,
+        s.missing_src,
+        s.type_mismatch,
+        s.size_mismatch,
+        s.hash_mismatch
+    );
+}
+
+pub fn print_du_human(r: &DuResult) {
+    use crate::ui::utils::format_bytes;
+
+    let line = |bytes: u64, path: &std::path::Path| {
+        println!("{:>12}  {}", format_bytes(bytes as f64), path.display());
+    };
+    for usage in &r.paths {
+        for dir in &usage.dirs {
+            line(dir.bytes, &dir.path);
+        }
+        line(usage.bytes, &usage.path);
+    }
+    if r.paths.len() > 1 {
+        line(r.total_bytes, std::path::Path::new("total"));
+    }
+    if !r.largest.is_empty() {
+        println!("\nLargest files:");
+        for file in &r.largest {
+            line(file.bytes, &file.path);
+        }
+    }
+}
+
+pub fn print_bench_human(r: &BenchResult) {
+    use crate::ui::utils::format_bytes;
+
+    println!(
+        "{} in {} file(s) under {}\n",
+        format_bytes(r.size as f64),
+        r.files,
+        r.dir.display()
+    );
+    println!(
+        "{:<8}{:<9}{:>14}{:>10}{:>14}",
+        "data", "engine", "speed", "time", "allocated"
+    );
+    for run in &r.runs {
+        match &run.error {
+            Some(error) => println!("{:<8}{:<9}  {}", run.data, run.engine, error),
+            None => println!(
+                "{:<8}{:<9}{:>14}{:>9.2}s{:>14}",
+                run.data,
+                run.engine,
+                format!("{}/s", format_bytes(run.bytes_per_sec)),
+                run.seconds,
+                run.allocated
+                    .map_or_else(|| "-".to_string(), |b| format_bytes(b as f64))
+            ),
+        }
+    }
+}
+
 pub fn error_output(command: &str, err: &anyhow::Error) -> CommandOutput {
     let kind = error_kind_from(err.as_ref());
     let msg = format!("{:#}", err);
     match command {
         "check" => CommandOutput::Check(CheckResult::error(msg, kind)),
+        "verify" => CommandOutput::Verify(VerifyResult::error(msg, kind)),
         _ => CommandOutput::Error(ErrorResult {
             status: Status::Error,
             error: msg,
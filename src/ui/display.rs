@@ -1,6 +1,9 @@
+use crate::output::{Difference, DifferenceKind};
 use crossterm::style::{Color, ResetColor, SetForegroundColor};
 use std::fmt;
+use std::io::IsTerminal;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ActionType {
     Remove,
     Add,
@@ -36,15 +39,55 @@ pub fn print_dry_run(action: ActionType, path: &str, details: Option<&str>) {
         ActionType::Skip => Color::DarkGrey,
     };
 
-    print!("{}", SetForegroundColor(color));
-    print!("{:<10} ", action);
-    print!("{}", ResetColor);
-
-    print!("{}", path);
-
+    print_tagged(&action, color, 10, path);
     if let Some(detail) = details {
         print!(" -> {}", detail);
     }
+    println!();
+}
 
+impl fmt::Display for DifferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifferenceKind::MissingDst => write!(f, "MISSING-DST"),
+            DifferenceKind::MissingSrc => write!(f, "MISSING-SRC"),
+            DifferenceKind::Type => write!(f, "TYPE"),
+            DifferenceKind::Size => write!(f, "SIZE"),
+            DifferenceKind::Hash => write!(f, "HASH"),
+        }
+    }
+}
+
+/// One line of `bcmr verify` output; directories carry a trailing `/`.
+pub fn print_difference(d: &Difference) {
+    let color = match d.kind {
+        DifferenceKind::MissingDst => Color::Red,
+        DifferenceKind::MissingSrc => Color::Green,
+        DifferenceKind::Type | DifferenceKind::Size => Color::Yellow,
+        DifferenceKind::Hash => Color::Magenta,
+    };
+    let mut path = d.path.display().to_string();
+    if d.is_dir {
+        path.push('/');
+    }
+    print_tagged(&d.kind, color, 11, &path);
+    if let (Some(src), Some(dst)) = (d.src_size, d.dst_size) {
+        print!(" ({} -> {} bytes)", src, dst);
+    }
+    if let Some(offset) = d.offset {
+        print!(" (first difference at offset {})", offset);
+    }
     println!();
 }
+
+// Dry-run and verify output is often piped into a file or `grep`; keep it
+// plain text there.
+fn print_tagged(tag: &dyn fmt::Display, color: Color, width: usize, path: &str) {
+    let tag = format!("{:<width$} ", tag.to_string());
+    if std::io::stdout().is_terminal() {
+        print!("{}{}{}", SetForegroundColor(color), tag, ResetColor);
+    } else {
+        print!("{}", tag);
+    }
+    print!("{}", path);
+}
@@ -1,17 +1,23 @@
-use crate::ui::progress::ProgressRenderer;
+use crate::core::oplog;
+use crate::ui::progress::{print_warnings, ProgressRenderer};
 use crate::ui::state::ProgressData;
-use crate::ui::utils::{format_bytes, format_eta};
+use crate::ui::term;
+use crate::ui::utils::{
+    display_width, fit_to_width, format_bytes, format_eta, format_operation_stats,
+    format_transfer_stats, truncate_path, truncate_to_width,
+};
 use crossterm::{
-    cursor::MoveToColumn,
-    execute,
+    cursor::{MoveToColumn, MoveUp},
+    queue,
     terminal::{Clear, ClearType},
 };
-use std::io::{self, stdout, Write};
+use std::io::{self, Write};
+use std::time::Instant;
 
 pub struct InlineProgress {
     data: ProgressData,
-    initialized: bool,
-    lines_printed: u16,
+    frames: u64,
+    last_line_widths: Vec<usize>,
 }
 
 impl InlineProgress {
@@ -19,39 +25,65 @@ impl InlineProgress {
         let data = ProgressData::new(total_bytes);
         Ok(Self {
             data,
-            initialized: false,
-            lines_printed: 0,
+            frames: 0,
+            last_line_widths: Vec::new(),
         })
     }
 
-    fn initialize(&mut self) -> io::Result<()> {
-        if self.initialized {
-            return Ok(());
+    // Over a slow link the write syscalls dominate, so a frame is queued in
+    // full and leaves in one write on the final flush.
+    fn redraw(&mut self) -> io::Result<()> {
+        let mut out = term::out();
+
+        let size = term::size();
+        let term_width = size.map(|(w, _)| w as usize).unwrap_or(80);
+
+        // Rows are measured against the current width: after a resize the
+        // terminal has already reflowed what we printed last time.
+        let rows_up = rows_above_cursor(&self.last_line_widths, term_width);
+        if rows_up > 0 {
+            queue!(out, MoveUp(rows_up))?;
+        }
+        if !self.last_line_widths.is_empty() {
+            queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
         }
-        self.initialized = true;
-        Ok(())
-    }
 
-    fn redraw(&mut self) -> io::Result<()> {
-        if !self.initialized {
-            self.initialize()?;
+        let lines = self.frame_lines(term_width, size.is_some());
+        for (i, line) in lines.iter().enumerate() {
+            if i + 1 < lines.len() {
+                writeln!(out, "{}", line)?;
+            } else {
+                write!(out, "{}", line)?;
+            }
         }
+        out.flush()?;
 
-        let mut stdout = stdout();
+        self.last_line_widths = lines.iter().map(|l| display_width(l)).collect();
+        self.frames += 1;
+        self.data.mark_drawn(Instant::now());
+        Ok(())
+    }
 
-        if self.lines_printed > 0 {
-            execute!(
-                stdout,
-                crossterm::cursor::MoveUp(self.lines_printed),
-                MoveToColumn(0)
-            )?;
+    /// On a terminal every line is cut to its width, so none of them wraps
+    /// and the row count only changes when the terminal itself is resized.
+    /// Output going to a file keeps the full lines.
+    fn frame_lines(&mut self, term_width: usize, clip: bool) -> Vec<String> {
+        let mut lines = self.render_lines(term_width);
+        if !self.data.paths.is_empty() {
+            lines.insert(0, self.data.paths.clone());
+        }
+        if clip {
+            for line in &mut lines {
+                *line = truncate_to_width(line, term_width);
+            }
         }
+        lines
+    }
 
-        let total_progress =
-            (self.data.current_bytes as f64 / self.data.total_bytes.max(1) as f64 * 100.0) as u16;
-        let current_progress = (self.data.current_file_progress as f64
-            / self.data.current_file_size.max(1) as f64
-            * 100.0) as u16;
+    fn render_lines(&mut self, term_width: usize) -> Vec<String> {
+        let total_fraction = self.data.total_fraction();
+        let percent = self.data.percent_text();
+        let current_progress = self.data.file_percent();
         let speed = self.data.calculate_speed();
         let eta_opt = self.data.estimate_eta();
 
@@ -60,130 +92,142 @@ impl InlineProgress {
         } else {
             self.data.operation_type.clone()
         };
+        let operation = if self.data.is_single_file() && !self.data.current_file.is_empty() {
+            let name = truncate_to_width(&self.data.current_file, term_width / 3);
+            format!("{} {}", operation, name)
+        } else {
+            operation
+        };
 
         let eta_str = match eta_opt {
             Some(d) => format_eta(d.as_secs()),
             None => "--".to_string(),
         };
 
-        let (term_width, _) = terminal_size::terminal_size()
-            .map(|(w, h)| (w.0, h.0))
-            .unwrap_or((80, 24));
-        let term_width = term_width as usize;
-
-        execute!(stdout, Clear(ClearType::CurrentLine))?;
         let op_label = format!("{}: ", operation);
-        write!(stdout, "{}", op_label)?;
-
         let suffix = if self.data.parallel_total > 0 {
             format!(
-                " {}% [{}/{}w]",
-                total_progress,
+                " {} [{}/{}w]",
+                percent,
                 self.data.active_worker_count(),
                 self.data.parallel_total
             )
         } else {
-            format!(" {}%", total_progress)
+            format!(" {}", percent)
         };
         let bar_width = term_width
-            .saturating_sub(op_label.len() + suffix.len() + 2)
+            .saturating_sub(display_width(&op_label) + suffix.len() + 2)
             .max(10);
-
-        write!(stdout, "[")?;
-        let filled = (bar_width * total_progress as usize / 100).min(bar_width);
+        let filled = ((bar_width as f64 * total_fraction) as usize).min(bar_width);
         let empty = bar_width - filled;
-        write!(stdout, "{}", "=".repeat(filled))?;
-        write!(stdout, "{}", "-".repeat(empty))?;
-        writeln!(stdout, "]{}", suffix)?;
+        let total_line = format!(
+            "{}[{}{}]{}",
+            op_label,
+            "=".repeat(filled),
+            "-".repeat(empty),
+            suffix
+        );
 
-        let speed_str = if speed > 0.0 {
-            format!("{}/s", format_bytes(speed * 1024.0 * 1024.0))
-        } else {
-            "-- /s".to_string()
-        };
-        execute!(stdout, Clear(ClearType::CurrentLine))?;
-        if self.data.scanning {
+        let speed_str = self
+            .data
+            .stall_notice(Instant::now())
+            .unwrap_or_else(|| self.data.rate_text(speed));
+        let mut stats_line = if self.data.scanning {
             let dots = ".".repeat((self.data.elapsed().as_millis() / 500 % 4) as usize);
-            writeln!(
-                stdout,
+            format!(
                 "{} (scanning{} {} files found) | {}",
                 format_bytes(self.data.current_bytes as f64),
                 dots,
                 self.data.files_found,
                 speed_str,
-            )?;
+            )
         } else {
-            writeln!(
-                stdout,
-                "{} / {} | {} | ETA: {}",
+            let mut line = format!(
+                "{} / {} | {} | Elapsed: {} | ETA: {}",
                 format_bytes(self.data.current_bytes as f64),
-                format_bytes(self.data.total_bytes as f64),
+                self.data.total_text(format_bytes),
                 speed_str,
+                format_eta(self.data.elapsed().as_secs()),
                 eta_str
-            )?;
+            );
+            if let Some(total) = self.data.items_total {
+                line.push_str(&format!(
+                    " | Items: {} / {}",
+                    self.data.items_processed, total
+                ));
+            }
+            line
+        };
+        if let Some(verified) = self.data.verify_text() {
+            stats_line.push_str(&format!(" | {}", verified));
+        }
+
+        if self.data.is_single_file() {
+            return vec![total_line, stats_line];
         }
 
-        execute!(stdout, Clear(ClearType::CurrentLine))?;
-        if self.data.parallel_total > 0 {
+        let file_line = if self.data.parallel_total > 0 {
             let mut parts = Vec::new();
             for (i, worker) in self.data.workers.iter().enumerate() {
                 if worker.active {
-                    let pct = if worker.file_size > 0 {
-                        (worker.progress as f64 / worker.file_size as f64 * 100.0).min(100.0) as u16
-                    } else {
-                        0
-                    };
+                    let pct = worker.percent();
                     let active = self.data.active_worker_count().max(1);
                     let per_worker = term_width.saturating_sub(active * 14) / active;
                     let name_max = per_worker.max(12);
-                    let display_name = if worker.file_name.chars().count() > name_max {
-                        let end = worker
-                            .file_name
-                            .floor_char_boundary(name_max.saturating_sub(3));
-                        format!("{}...", &worker.file_name[..end])
-                    } else {
-                        worker.file_name.clone()
-                    };
+                    let display_name = truncate_path(&worker.file_name, name_max);
                     parts.push(format!("[{}] {} {}%", i + 1, display_name, pct));
                 } else {
                     parts.push(format!("[{}] idle", i + 1));
                 }
             }
-            write!(stdout, "{}", parts.join(" | "))?;
+            parts.join(" | ")
         } else {
             let file_label = "File: ";
-            write!(stdout, "{}", file_label)?;
-
-            let file_suffix = format!(" {}%", current_progress);
+            let file_speed = self.data.calculate_file_speed();
+            let file_suffix = if file_speed > 0.0 {
+                format!(
+                    " {}% {}/s",
+                    current_progress,
+                    format_bytes(file_speed * 1024.0 * 1024.0)
+                )
+            } else {
+                format!(" {}%", current_progress)
+            };
             let avail = term_width.saturating_sub(file_label.len() + file_suffix.len() + 2);
             let bar_width_file = (avail / 2).max(10);
             let name_width = avail.saturating_sub(bar_width_file + 1);
 
-            let file_info = &self.data.current_file;
-            let display_file = if file_info.len() > name_width {
-                let mut end = name_width.saturating_sub(3);
-                if !file_info.is_char_boundary(end) {
-                    end = file_info.floor_char_boundary(end);
-                }
-                format!("{}...", &file_info[..end])
-            } else {
-                format!("{:width$}", file_info, width = name_width)
-            };
-
-            write!(stdout, "{} ", display_file)?;
-
-            write!(stdout, "[")?;
+            let display_file = fit_to_width(
+                &truncate_path(&self.data.current_file, name_width),
+                name_width,
+            );
             let filled = (bar_width_file * current_progress as usize / 100).min(bar_width_file);
             let empty = bar_width_file - filled;
-            write!(stdout, "{}", "=".repeat(filled))?;
-            write!(stdout, "{}", "-".repeat(empty))?;
-            write!(stdout, "]{}", file_suffix)?;
-        }
+            format!(
+                "{}{} [{}{}]{}",
+                file_label,
+                display_file,
+                "=".repeat(filled),
+                "-".repeat(empty),
+                file_suffix
+            )
+        };
 
-        stdout.flush()?;
+        vec![total_line, stats_line, file_line]
+    }
+}
 
-        self.lines_printed = 3;
-        Ok(())
+/// Number of terminal rows between the first printed line and the cursor,
+/// which sits at the end of the last line (no trailing newline).
+fn rows_above_cursor(line_widths: &[usize], term_width: usize) -> u16 {
+    let term_width = term_width.max(1);
+    let rows = |w: usize| w.max(1).div_ceil(term_width);
+    match line_widths.split_last() {
+        Some((last, rest)) => {
+            let above: usize = rest.iter().map(|&w| rows(w)).sum();
+            (above + rows(*last) - 1) as u16
+        }
+        None => 0,
     }
 }
 
@@ -193,33 +237,39 @@ impl ProgressRenderer for InlineProgress {
         let _ = self.redraw();
     }
 
-    fn inc_items_processed(&mut self) {
-        self.data.items_processed += 1;
-        let _ = self.redraw();
+    fn inc_items_processed(&mut self, count: usize) {
+        self.data.items_processed += count;
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    fn add_verify_queued(&mut self, count: usize) {
+        self.data.verify_queued += count;
+    }
+
+    fn inc_verified(&mut self, count: usize) {
+        self.data.verified += count;
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
     }
 
     fn set_current_file(&mut self, file_name: &str, file_size: u64) {
-        self.data.current_file = file_name.to_string();
-        self.data.current_file_size = file_size;
-        self.data.current_file_progress = 0;
+        self.data.set_current_file(file_name, file_size);
         let _ = self.redraw();
     }
 
     fn inc_current(&mut self, delta: u64) {
-        self.data.current_bytes += delta;
-        self.data.current_file_progress += delta;
-        if self.data.current_bytes.is_multiple_of(1024 * 1024)
-            || self.data.current_bytes >= self.data.total_bytes
-        {
+        self.data.inc_current(delta);
+        if self.data.should_redraw(Instant::now()) {
             let _ = self.redraw();
         }
     }
 
     fn inc_skipped(&mut self, delta: u64) {
         self.data.inc_skipped(delta);
-        if self.data.current_bytes.is_multiple_of(1024 * 1024)
-            || self.data.current_bytes >= self.data.total_bytes
-        {
+        if self.data.should_redraw(Instant::now()) {
             let _ = self.redraw();
         }
     }
@@ -229,11 +279,25 @@ impl ProgressRenderer for InlineProgress {
         let _ = self.redraw();
     }
 
+    fn set_paths(&mut self, src: &str, dst: &str) {
+        self.data.set_paths(src, dst);
+        let _ = self.redraw();
+    }
+
     fn set_total_bytes(&mut self, total: u64) {
         self.data.total_bytes = total;
         let _ = self.redraw();
     }
 
+    fn set_size_unknown(&mut self, unknown: bool) {
+        self.data.size_unknown = unknown;
+        let _ = self.redraw();
+    }
+
+    fn set_codec_sizes(&mut self, raw: u64, compressed: u64) {
+        self.data.codec_sizes = Some((raw, compressed));
+    }
+
     fn set_scanning(&mut self, scanning: bool) {
         self.data.scanning = scanning;
         let _ = self.redraw();
@@ -258,20 +322,170 @@ impl ProgressRenderer for InlineProgress {
         let _ = self.redraw();
     }
 
+    fn worker_file(&mut self, slot: usize, file_name: &str, file_size: u64) {
+        self.data.worker_file(slot, file_name, file_size);
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    fn worker_progress(&mut self, slot: usize, delta: u64) {
+        self.data.worker_progress(slot, delta);
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    fn log(&mut self, line: &str) {
+        let _ = self.print_above(line);
+    }
+
+    // Printed mid-run the warning would land inside the lines we redraw
+    // in place, so it waits for `finish`.
+    fn warn(&mut self, message: &str) {
+        self.data.warnings.push(message.to_string());
+    }
+
     fn tick(&mut self) {
-        let _ = self.redraw();
+        let now = Instant::now();
+        self.data.check_stall(now);
+        if self.data.should_redraw(now) {
+            let _ = self.redraw();
+        }
     }
 
     fn finish(&mut self) -> io::Result<()> {
-        let elapsed = self.data.elapsed();
-        let avg_bps = self.data.average_bytes_per_sec().unwrap_or(0.0);
-        println!();
-        println!(
-            "Done: {} in {:.1}s | avg {}/s",
-            format_bytes(self.data.current_bytes as f64),
-            elapsed.as_secs_f64(),
-            format_bytes(avg_bps)
-        );
+        self.close(ProgressData::done_line)
+    }
+
+    fn finish_interrupted(&mut self, label: &str, partial: Option<&str>) -> io::Result<()> {
+        self.close(|data| data.interrupted_line(label, partial))
+    }
+}
+
+impl InlineProgress {
+    /// Wipes the frame, leaves `line` where it was and draws the frame
+    /// again underneath.
+    fn print_above(&mut self, line: &str) -> io::Result<()> {
+        let term_width = term::size().map(|(w, _)| w as usize).unwrap_or(80);
+        let mut out = term::out();
+        let rows_up = rows_above_cursor(&self.last_line_widths, term_width);
+        if rows_up > 0 {
+            queue!(out, MoveUp(rows_up))?;
+        }
+        if !self.last_line_widths.is_empty() {
+            queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+        }
+        writeln!(out, "{}", line)?;
+        out.flush()?;
+        self.last_line_widths.clear();
+        self.redraw()
+    }
+
+    fn close(&mut self, summary: impl FnOnce(&ProgressData) -> String) -> io::Result<()> {
+        self.data.stop_clock();
+        let _ = self.redraw();
+        let mut out = term::out();
+        writeln!(out)?;
+        writeln!(out, "{}", summary(&self.data))?;
+        let stats = oplog::stats();
+        if !stats.is_empty() {
+            writeln!(out, "{}", format_operation_stats(&stats))?;
+        }
+        if crate::config::show_stats() {
+            writeln!(
+                out,
+                "{}",
+                format_transfer_stats(&self.data.transfer_stats())
+            )?;
+        }
+        out.flush()?;
+        print_warnings(&self.data.warnings);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn rows_above_cursor_without_wrapping() {
+        assert_eq!(rows_above_cursor(&[], 80), 0);
+        assert_eq!(rows_above_cursor(&[40, 30, 50], 80), 2);
+        assert_eq!(rows_above_cursor(&[80, 80, 80], 80), 2);
+    }
+
+    #[test]
+    fn rows_above_cursor_after_shrink() {
+        // 100-column lines reflowed into a 40-column terminal take 3 rows each.
+        assert_eq!(rows_above_cursor(&[100, 100, 100], 40), 8);
+        assert_eq!(rows_above_cursor(&[100, 0, 41], 40), 5);
+    }
+
+    #[test]
+    fn rendered_lines_fit_terminal_width() {
+        let mut p = InlineProgress::new(1000).unwrap();
+        p.data.current_file = "日本語のとても長いファイル名です-🎉🎉🎉.bin".repeat(4);
+        p.data.current_file_size = 100;
+        p.data.items_total = Some(5);
+        for width in [40, 80, 200] {
+            let lines = p.render_lines(width);
+            assert!(display_width(&lines[0]) <= width, "{width}: {}", lines[0]);
+            assert!(display_width(&lines[2]) <= width, "{width}: {}", lines[2]);
+        }
+    }
+
+    #[test]
+    fn single_file_has_no_file_line() {
+        let mut p = InlineProgress::new(1000).unwrap();
+        p.data.operation_type = "Copying".to_string();
+        p.data.set_current_file("big.iso", 1000);
+        let lines = p.render_lines(80);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Copying big.iso: ["), "{}", lines[0]);
+
+        p.data.set_current_file("small.txt", 10);
+        assert_eq!(p.render_lines(80).len(), 3);
+    }
+
+    #[test]
+    fn redraws_follow_the_clock_not_the_callbacks() {
+        let mut p = InlineProgress::new(1 << 40).unwrap();
+        p.data.refresh_interval = Duration::from_millis(100);
+        p.data.mark_drawn(Instant::now());
+
+        let start = Instant::now();
+        for _ in 0..10_000 {
+            p.inc_current(1);
+        }
+        let allowed = start.elapsed().as_millis() as u64 / 100 + 1;
+        assert!(
+            p.frames <= allowed,
+            "{} frames for 10,000 updates",
+            p.frames
+        );
+    }
+
+    #[test]
+    fn long_names_never_wrap() {
+        let mut p = InlineProgress::new(1 << 30).unwrap();
+        p.data
+            .set_paths(&"/very/long/source/directory/".repeat(8), "/dst");
+        p.data
+            .set_current_file(&"node_modules/some-package/".repeat(10), 100);
+        p.data.set_current_file("日本語のファイル名-🎉.bin", 100);
+        p.data.items_total = Some(3);
+        for width in [40, 200] {
+            let lines = p.frame_lines(width, true);
+            let widths: Vec<usize> = lines.iter().map(|l| display_width(l)).collect();
+            assert!(widths.iter().all(|&w| w <= width), "{width}: {lines:?}");
+            assert_eq!(
+                rows_above_cursor(&widths, width) as usize,
+                lines.len() - 1,
+                "{width}: {lines:?}"
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::core::oplog::{self, OperationStats, TransferStats};
 use crate::ui::progress::ProgressRenderer;
 use crate::ui::state::ProgressData;
 
@@ -70,6 +71,10 @@ struct ProgressLine<'a> {
     items_done: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     items_total: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verified: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verify_total: Option<usize>,
     scanning: bool,
 }
 
@@ -82,10 +87,26 @@ struct ResultLine<'a> {
     duration_secs: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     avg_speed_bps: Option<u64>,
+    files: OperationStats,
+    /// Only with `--stats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<TransferStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stalled_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compressed_bytes: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<&'a str>,
 }
 
+#[derive(Serialize)]
+struct WarningLine<'a> {
+    r#type: &'static str,
+    message: &'a str,
+}
+
 const EMIT_INTERVAL_MS: u128 = 200;
 
 impl JsonProgress {
@@ -111,6 +132,11 @@ impl JsonProgress {
         })
     }
 
+    fn stalled_secs(&self) -> Option<f64> {
+        let stalled = self.data.total_stall(Instant::now());
+        (!stalled.is_zero()).then_some(stalled.as_secs_f64())
+    }
+
     fn emit_progress(&mut self) {
         let now = Instant::now();
         if let Some(last) = self.last_emit {
@@ -142,6 +168,8 @@ impl JsonProgress {
             file_progress: self.data.current_file_progress,
             items_done: Some(self.data.items_processed),
             items_total: self.data.items_total,
+            verified: (self.data.verify_queued > 0).then_some(self.data.verified),
+            verify_total: (self.data.verify_queued > 0).then_some(self.data.verify_queued),
             scanning: self.data.scanning,
         };
 
@@ -154,19 +182,24 @@ impl ProgressRenderer for JsonProgress {
         self.data.items_total = Some(total);
     }
 
-    fn inc_items_processed(&mut self) {
-        self.data.items_processed += 1;
+    fn inc_items_processed(&mut self, count: usize) {
+        self.data.items_processed += count;
     }
 
     fn set_current_file(&mut self, file_name: &str, file_size: u64) {
-        self.data.current_file = file_name.to_string();
-        self.data.current_file_size = file_size;
-        self.data.current_file_progress = 0;
+        self.data.set_current_file(file_name, file_size);
+    }
+
+    fn add_verify_queued(&mut self, count: usize) {
+        self.data.verify_queued += count;
+    }
+
+    fn inc_verified(&mut self, count: usize) {
+        self.data.verified += count;
     }
 
     fn inc_current(&mut self, delta: u64) {
-        self.data.current_bytes += delta;
-        self.data.current_file_progress += delta;
+        self.data.inc_current(delta);
         self.emit_progress();
     }
 
@@ -182,6 +215,14 @@ impl ProgressRenderer for JsonProgress {
         self.data.total_bytes = total;
     }
 
+    fn set_size_unknown(&mut self, unknown: bool) {
+        self.data.size_unknown = unknown;
+    }
+
+    fn set_codec_sizes(&mut self, raw: u64, compressed: u64) {
+        self.data.codec_sizes = Some((raw, compressed));
+    }
+
     fn set_scanning(&mut self, scanning: bool) {
         self.data.scanning = scanning;
     }
@@ -203,7 +244,15 @@ impl ProgressRenderer for JsonProgress {
         self.data.finish_worker(slot);
     }
 
+    fn warn(&mut self, message: &str) {
+        self.writer.write_line(&WarningLine {
+            r#type: "warning",
+            message,
+        });
+    }
+
     fn tick(&mut self) {
+        self.data.check_stall(Instant::now());
         if !self.finished {
             self.emit_progress();
         }
@@ -215,7 +264,7 @@ impl ProgressRenderer for JsonProgress {
         }
         self.finished = true;
 
-        let elapsed = self.data.elapsed();
+        let elapsed = self.data.stop_clock();
         let avg_bps = self.data.average_bytes_per_sec().map(|b| b as u64);
 
         let line = ResultLine {
@@ -225,6 +274,11 @@ impl ProgressRenderer for JsonProgress {
             bytes_total: self.data.current_bytes,
             duration_secs: elapsed.as_secs_f64(),
             avg_speed_bps: avg_bps,
+            files: oplog::stats(),
+            stats: crate::config::show_stats().then(|| self.data.transfer_stats()),
+            stalled_secs: self.stalled_secs(),
+            raw_bytes: self.data.codec_sizes.map(|(raw, _)| raw),
+            compressed_bytes: self.data.codec_sizes.map(|(_, compressed)| compressed),
             error: None,
         };
 
@@ -237,7 +291,7 @@ impl ProgressRenderer for JsonProgress {
         }
         self.finished = true;
 
-        let elapsed = self.data.elapsed();
+        let elapsed = self.data.stop_clock();
         let line = ResultLine {
             r#type: "result",
             status: "error",
@@ -245,6 +299,11 @@ impl ProgressRenderer for JsonProgress {
             bytes_total: self.data.current_bytes,
             duration_secs: elapsed.as_secs_f64(),
             avg_speed_bps: None,
+            files: oplog::stats(),
+            stats: crate::config::show_stats().then(|| self.data.transfer_stats()),
+            stalled_secs: self.stalled_secs(),
+            raw_bytes: self.data.codec_sizes.map(|(raw, _)| raw),
+            compressed_bytes: self.data.codec_sizes.map(|(_, compressed)| compressed),
             error: Some(msg),
         };
 
@@ -1,9 +1,12 @@
 pub mod display;
 pub mod inline;
 pub mod json;
+pub mod plain;
 pub mod progress;
 pub mod runner;
+pub mod scan;
 pub mod state;
 pub mod suspend;
+pub mod term;
 pub mod tui;
 pub mod utils;
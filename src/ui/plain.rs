@@ -0,0 +1,262 @@
+use crate::core::oplog;
+use crate::ui::progress::ProgressRenderer;
+use crate::ui::state::ProgressData;
+use crate::ui::term;
+use crate::ui::utils::{format_bytes, format_eta, format_operation_stats, format_transfer_stats};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// A log file gains a line per redraw, so plain output never comes faster
+/// than this whatever `refresh_ms` says.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `style = "plain"`: a status line now and then, never redrawn or
+/// coloured, for logs, CI and terminals that cannot move the cursor.
+pub struct PlainProgress {
+    data: ProgressData,
+    interval: Duration,
+    last_print: Option<Instant>,
+    last_line: String,
+}
+
+impl PlainProgress {
+    pub fn new(total_bytes: u64) -> Self {
+        let data = ProgressData::new(total_bytes);
+        Self {
+            interval: data.refresh_interval.max(MIN_INTERVAL),
+            data,
+            last_print: None,
+            last_line: String::new(),
+        }
+    }
+
+    fn status_line(&mut self) -> String {
+        let operation = if self.data.operation_type.is_empty() {
+            "Progress"
+        } else {
+            &self.data.operation_type
+        };
+        if self.data.scanning {
+            return format!(
+                "{}: scanning, {} files found",
+                operation, self.data.files_found
+            );
+        }
+        let mut line = format!(
+            "{}: {} | {} / {}",
+            operation,
+            self.data.percent_text().trim_start(),
+            format_bytes(self.data.current_bytes as f64),
+            self.data.total_text(format_bytes),
+        );
+        let speed = self.data.calculate_speed();
+        let rate = self
+            .data
+            .stall_notice(Instant::now())
+            .unwrap_or_else(|| self.data.rate_text(speed));
+        let eta = self
+            .data
+            .estimate_eta()
+            .map_or_else(|| "--".to_string(), |d| format_eta(d.as_secs()));
+        line.push_str(&format!(" | {} | ETA: {}", rate, eta));
+        if let Some(total) = self.data.items_total {
+            line.push_str(&format!(
+                " | Items: {} / {}",
+                self.data.items_processed, total
+            ));
+        }
+        if let Some(verified) = self.data.verify_text() {
+            line.push_str(&format!(" | {}", verified));
+        }
+        line
+    }
+
+    fn print_line(&mut self, line: &str) -> io::Result<()> {
+        let mut out = term::out();
+        writeln!(out, "{}", line)?;
+        out.flush()
+    }
+
+    /// Prints the status when it is due and says something new. Unlike
+    /// a redraw, reaching 100% is not a reason to print early: the done
+    /// line follows straight after.
+    fn report(&mut self) {
+        let now = Instant::now();
+        let due = match self.last_print {
+            None => !self.data.operation_type.is_empty(),
+            Some(last) => now.saturating_duration_since(last) >= self.interval,
+        };
+        if !due {
+            return;
+        }
+        self.last_print = Some(now);
+        let line = self.status_line();
+        if line != self.last_line {
+            let _ = self.print_line(&line);
+            self.last_line = line;
+        }
+    }
+
+    fn close(&mut self, summary: impl FnOnce(&ProgressData) -> String) -> io::Result<()> {
+        self.data.stop_clock();
+        let mut out = term::out();
+        writeln!(out, "{}", summary(&self.data))?;
+        let stats = oplog::stats();
+        if !stats.is_empty() {
+            writeln!(out, "{}", format_operation_stats(&stats))?;
+        }
+        if crate::config::show_stats() {
+            writeln!(
+                out,
+                "{}",
+                format_transfer_stats(&self.data.transfer_stats())
+            )?;
+        }
+        out.flush()
+    }
+}
+
+impl ProgressRenderer for PlainProgress {
+    fn set_total_items(&mut self, total: usize) {
+        self.data.items_total = Some(total);
+    }
+
+    fn inc_items_processed(&mut self, count: usize) {
+        self.data.items_processed += count;
+        self.report();
+    }
+
+    fn add_verify_queued(&mut self, count: usize) {
+        self.data.verify_queued += count;
+    }
+
+    fn inc_verified(&mut self, count: usize) {
+        self.data.verified += count;
+        self.report();
+    }
+
+    fn set_current_file(&mut self, file_name: &str, file_size: u64) {
+        self.data.set_current_file(file_name, file_size);
+    }
+
+    fn inc_current(&mut self, delta: u64) {
+        self.data.inc_current(delta);
+        self.report();
+    }
+
+    fn inc_skipped(&mut self, delta: u64) {
+        self.data.inc_skipped(delta);
+        self.report();
+    }
+
+    fn set_operation_type(&mut self, operation: &str) {
+        self.data.operation_type = operation.to_string();
+    }
+
+    fn set_paths(&mut self, src: &str, dst: &str) {
+        self.data.set_paths(src, dst);
+        let paths = self.data.paths.clone();
+        let _ = self.print_line(&paths);
+    }
+
+    fn set_total_bytes(&mut self, total: u64) {
+        self.data.total_bytes = total;
+    }
+
+    fn set_size_unknown(&mut self, unknown: bool) {
+        self.data.size_unknown = unknown;
+    }
+
+    fn set_codec_sizes(&mut self, raw: u64, compressed: u64) {
+        self.data.codec_sizes = Some((raw, compressed));
+    }
+
+    fn set_scanning(&mut self, scanning: bool) {
+        self.data.scanning = scanning;
+    }
+
+    fn set_files_found(&mut self, count: u64) {
+        self.data.files_found = count;
+    }
+
+    fn set_parallel_mode(&mut self, worker_count: usize) {
+        self.data.init_workers(worker_count);
+    }
+
+    fn update_worker(&mut self, slot: usize, file_name: &str, file_size: u64, progress: u64) {
+        self.data
+            .update_worker(slot, file_name, file_size, progress);
+    }
+
+    fn finish_worker(&mut self, slot: usize) {
+        self.data.finish_worker(slot);
+    }
+
+    fn worker_file(&mut self, slot: usize, file_name: &str, file_size: u64) {
+        self.data.worker_file(slot, file_name, file_size);
+    }
+
+    fn worker_progress(&mut self, slot: usize, delta: u64) {
+        self.data.worker_progress(slot, delta);
+        self.report();
+    }
+
+    fn tick(&mut self) {
+        self.data.check_stall(Instant::now());
+        self.report();
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.close(ProgressData::done_line)
+    }
+
+    fn finish_interrupted(&mut self, label: &str, partial: Option<&str>) -> io::Result<()> {
+        self.close(|data| data.interrupted_line(label, partial))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line_has_no_bar_or_escapes() {
+        let mut p = PlainProgress::new(4 << 20);
+        p.set_operation_type("Copying");
+        p.data.current_bytes = 1 << 20;
+        p.data.items_total = Some(4);
+        p.data.items_processed = 1;
+        let line = p.status_line();
+        assert!(
+            line.starts_with("Copying: 25% | 1.00 MiB / 4.00 MiB | "),
+            "{line}"
+        );
+        assert!(line.ends_with(" | Items: 1 / 4"), "{line}");
+        assert!(!line.contains('\x1b') && !line.contains('['), "{line}");
+    }
+
+    #[test]
+    fn test_scanning_line() {
+        let mut p = PlainProgress::new(0);
+        p.set_operation_type("Removing");
+        p.set_scanning(true);
+        p.set_files_found(12);
+        assert_eq!(p.status_line(), "Removing: scanning, 12 files found");
+    }
+
+    #[test]
+    fn test_lines_come_at_most_once_a_second() {
+        let mut p = PlainProgress::new(100);
+        assert!(p.interval >= MIN_INTERVAL);
+        p.report();
+        assert_eq!(
+            p.last_print, None,
+            "nothing to say before the operation is named"
+        );
+        p.set_operation_type("Copying");
+        p.report();
+        let first = p.last_print.unwrap();
+        p.inc_current(100);
+        assert_eq!(p.last_print, Some(first));
+    }
+}
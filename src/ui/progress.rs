@@ -1,8 +1,70 @@
+use crate::core::oplog;
 use crate::ui::inline::InlineProgress;
 use crate::ui::json::JsonProgress;
+use crate::ui::plain::PlainProgress;
+use crate::ui::term;
 use crate::ui::tui::TuiProgress;
+use std::fmt;
 use std::io;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How progress is drawn: `--progress`, else `[progress] style`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressStyle {
+    /// The framed box with gradient bars.
+    #[default]
+    Fancy,
+    /// Three lines redrawn in place (`--tui`, and accepted as "tui").
+    Inline,
+    /// A status line now and then, never redrawn.
+    Plain,
+    /// Nothing until the command ends.
+    None,
+}
+
+impl FromStr for ProgressStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fancy" => Ok(ProgressStyle::Fancy),
+            "inline" | "tui" => Ok(ProgressStyle::Inline),
+            "plain" => Ok(ProgressStyle::Plain),
+            "none" => Ok(ProgressStyle::None),
+            _ => Err(format!(
+                "Invalid progress style '{}'. Expected fancy, inline, plain or none",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ProgressStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProgressStyle::Fancy => "fancy",
+            ProgressStyle::Inline => "inline",
+            ProgressStyle::Plain => "plain",
+            ProgressStyle::None => "none",
+        })
+    }
+}
+
+// Config files name the style the way `--progress` does.
+impl<'de> serde::Deserialize<'de> for ProgressStyle {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for ProgressStyle {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 
 pub trait ProgressRenderer: Send {
     fn inc_current(&mut self, delta: u64);
@@ -12,22 +74,81 @@ pub trait ProgressRenderer: Send {
         self.finish()
     }
 
+    /// Teardown for a run stopped by a signal: the closing line reports how
+    /// far it got (`label` is "Interrupted" or "Terminated") and what became
+    /// of the partial file, rather than claiming it is done.
+    fn finish_interrupted(&mut self, label: &str, _partial: Option<&str>) -> io::Result<()> {
+        self.finish_err(&label.to_lowercase())
+    }
+
     fn set_total_items(&mut self, _total: usize) {}
-    fn inc_items_processed(&mut self) {}
+    fn inc_items_processed(&mut self, _count: usize) {}
     fn set_current_file(&mut self, _file_name: &str, _file_size: u64) {}
+    /// Files queued for `--verify` and files it has finished, shown as a
+    /// second counter next to the items.
+    fn add_verify_queued(&mut self, _count: usize) {}
+    fn inc_verified(&mut self, _count: usize) {}
     fn inc_skipped(&mut self, delta: u64) {
         self.inc_current(delta);
     }
     fn set_operation_type(&mut self, _operation: &str) {}
+    /// Header naming what goes where; an empty `dst` (remove) shows `src`
+    /// on its own.
+    fn set_paths(&mut self, _src: &str, _dst: &str) {}
     fn set_total_bytes(&mut self, _total: u64) {}
     fn set_scanning(&mut self, _scanning: bool) {}
+    /// No total to measure against: drop the percentage and ETA.
+    fn set_size_unknown(&mut self, _unknown: bool) {}
+    /// Sizes either side of `--compress`/`--decompress`, for the result.
+    fn set_codec_sizes(&mut self, _raw: u64, _compressed: u64) {}
     fn set_files_found(&mut self, _count: u64) {}
 
     fn set_parallel_mode(&mut self, _worker_count: usize) {}
     fn update_worker(&mut self, _slot: usize, _file_name: &str, _file_size: u64, _progress: u64) {}
     fn finish_worker(&mut self, _slot: usize) {}
 
+    /// Worker-scoped counterparts of `set_current_file`/`inc_current` for
+    /// local parallel copies. Renderers without worker rows fold them into
+    /// the single current-file view.
+    fn worker_file(&mut self, _slot: usize, file_name: &str, file_size: u64) {
+        self.set_current_file(file_name, file_size);
+    }
+    fn worker_progress(&mut self, _slot: usize, delta: u64) {
+        self.inc_current(delta);
+    }
+
     fn tick(&mut self) {}
+
+    /// A verbose line (`-v`) printed while the frame is up. Renderers that
+    /// redraw in place put it above the frame.
+    fn log(&mut self, line: &str) {
+        eprintln!("{}", line);
+    }
+
+    /// A non-fatal problem. Renderers that own the screen hold on to it
+    /// and show everything again once the terminal is restored.
+    fn warn(&mut self, message: &str) {
+        print_warnings(&[message.to_string()]);
+    }
+}
+
+pub fn print_warnings(warnings: &[String]) {
+    if crate::config::is_quiet() {
+        return;
+    }
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+/// Hands queued `oplog` warnings and verbose lines to the renderer.
+pub fn forward_warnings(renderer: &mut dyn ProgressRenderer) {
+    for line in oplog::take_verbose() {
+        renderer.log(&line);
+    }
+    for warning in oplog::take_warnings() {
+        renderer.warn(&warning);
+    }
 }
 
 struct SilentProgress;
@@ -39,23 +160,180 @@ impl ProgressRenderer for SilentProgress {
     }
 }
 
+/// Which renderer `create_renderer` builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Renderer {
+    Json,
+    Silent,
+    Tui,
+    Inline,
+    Plain,
+}
+
+fn choose_renderer(style: ProgressStyle, silent: bool, json: bool) -> Renderer {
+    if json {
+        return Renderer::Json;
+    }
+    match style {
+        _ if silent => Renderer::Silent,
+        ProgressStyle::Fancy => Renderer::Tui,
+        ProgressStyle::Inline => Renderer::Inline,
+        ProgressStyle::Plain => Renderer::Plain,
+        ProgressStyle::None => Renderer::Silent,
+    }
+}
+
+/// The renderer a terminal with `support` can actually draw, stepping
+/// `Tui` down to `Inline` and either down to `Plain`, with a note on why.
+fn fit_to_terminal(renderer: Renderer, support: term::Support) -> (Renderer, Option<String>) {
+    let (fallback, missing) = match renderer {
+        Renderer::Tui | Renderer::Inline if !support.vt => {
+            (Renderer::Plain, "does not interpret escape sequences")
+        }
+        Renderer::Tui if !support.raw_mode => (Renderer::Inline, "rejects raw mode"),
+        Renderer::Tui if !support.cursor => {
+            (Renderer::Inline, "does not report the cursor position")
+        }
+        _ => return (renderer, None),
+    };
+    let name = if fallback == Renderer::Plain {
+        ProgressStyle::Plain
+    } else {
+        ProgressStyle::Inline
+    };
+    let note = format!("this terminal {}; showing {} progress", missing, name);
+    (fallback, Some(note))
+}
+
 pub fn create_renderer(
     total_bytes: u64,
-    plain: bool,
+    style: ProgressStyle,
     silent: bool,
     json: bool,
     log_file: Option<&PathBuf>,
 ) -> io::Result<Box<dyn ProgressRenderer>> {
-    if json {
-        match log_file {
-            Some(path) => Ok(Box::new(JsonProgress::with_log_file(total_bytes, path)?)),
-            None => Ok(Box::new(JsonProgress::new(total_bytes))),
+    let mut renderer = choose_renderer(style, silent, json);
+    if matches!(renderer, Renderer::Tui | Renderer::Inline) {
+        if !term::is_terminal() {
+            // A file or pipe would keep the escape codes as text.
+            renderer = Renderer::Plain;
+        } else {
+            let (fitted, note) = fit_to_terminal(renderer, term::probe());
+            if let Some(note) = note {
+                eprintln!("note: {}", note);
+            }
+            renderer = fitted;
         }
-    } else if silent {
-        Ok(Box::new(SilentProgress))
-    } else if plain {
-        Ok(Box::new(InlineProgress::new(total_bytes)?))
-    } else {
-        Ok(Box::new(TuiProgress::new(total_bytes)?))
+    }
+    Ok(match renderer {
+        Renderer::Json => match log_file {
+            Some(path) => Box::new(JsonProgress::with_log_file(total_bytes, path)?),
+            None => Box::new(JsonProgress::new(total_bytes)),
+        },
+        Renderer::Silent => Box::new(SilentProgress),
+        Renderer::Tui => Box::new(TuiProgress::new(total_bytes)?),
+        Renderer::Inline => Box::new(InlineProgress::new(total_bytes)?),
+        Renderer::Plain => Box::new(PlainProgress::new(total_bytes)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_style_gets_its_renderer() {
+        let cases = [
+            ("fancy", Renderer::Tui),
+            ("inline", Renderer::Inline),
+            ("Plain", Renderer::Plain),
+            ("none", Renderer::Silent),
+        ];
+        for (name, renderer) in cases {
+            let style: ProgressStyle = name.parse().unwrap();
+            assert_eq!(choose_renderer(style, false, false), renderer, "{name}");
+            assert_eq!(style.to_string(), name.to_ascii_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_json_and_quiet_override_the_style() {
+        assert_eq!(
+            choose_renderer(ProgressStyle::Inline, false, true),
+            Renderer::Json
+        );
+        assert_eq!(
+            choose_renderer(ProgressStyle::Fancy, true, false),
+            Renderer::Silent
+        );
+    }
+
+    #[test]
+    fn test_a_capable_terminal_keeps_the_renderer() {
+        let full = term::Support {
+            vt: true,
+            raw_mode: true,
+            cursor: true,
+        };
+        for renderer in [Renderer::Tui, Renderer::Inline, Renderer::Plain] {
+            assert_eq!(
+                fit_to_terminal(renderer, full),
+                (renderer, None),
+                "{renderer:?}"
+            );
+        }
+        assert_eq!(
+            fit_to_terminal(Renderer::Plain, term::Support::NONE),
+            (Renderer::Plain, None)
+        );
+    }
+
+    #[test]
+    fn test_legacy_console_steps_down() {
+        let no_raw = term::Support {
+            vt: true,
+            raw_mode: false,
+            cursor: true,
+        };
+        assert_eq!(
+            fit_to_terminal(Renderer::Tui, no_raw),
+            (
+                Renderer::Inline,
+                Some("this terminal rejects raw mode; showing inline progress".to_string())
+            )
+        );
+        let no_cursor = term::Support {
+            vt: true,
+            raw_mode: true,
+            cursor: false,
+        };
+        assert_eq!(
+            fit_to_terminal(Renderer::Tui, no_cursor).0,
+            Renderer::Inline
+        );
+        assert_eq!(fit_to_terminal(Renderer::Inline, no_cursor).1, None);
+        for renderer in [Renderer::Tui, Renderer::Inline] {
+            assert_eq!(
+                fit_to_terminal(renderer, term::Support::NONE),
+                (
+                    Renderer::Plain,
+                    Some(
+                        "this terminal does not interpret escape sequences; \
+                         showing plain progress"
+                            .to_string()
+                    )
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_style_lists_the_valid_ones() {
+        assert_eq!("tui".parse(), Ok(ProgressStyle::Inline));
+        let err = "box".parse::<ProgressStyle>().unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid progress style 'box'. Expected fancy, inline, plain or none"
+        );
     }
 }
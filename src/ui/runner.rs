@@ -1,95 +1,446 @@
+use crate::core::error::BcmrError;
+use crate::core::oplog;
 use crate::ui::progress::{self, ProgressRenderer};
+use crate::ui::term;
 
 use anyhow::{bail, Result};
 use parking_lot::Mutex;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::signal::ctrl_c;
 use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Totals the copy loop publishes per chunk without taking the renderer
+/// lock. The ticker drains them into the renderer; anything that changes
+/// which file the bytes belong to drains first.
+#[derive(Default)]
+struct Counters {
+    bytes: AtomicU64,
+    skipped: AtomicU64,
+    items: AtomicU64,
+    workers: OnceLock<Box<[AtomicU64]>>,
+}
+
+impl Counters {
+    fn drain_into(&self, p: &mut dyn ProgressRenderer) {
+        let bytes = self.bytes.swap(0, Ordering::Relaxed);
+        if bytes > 0 {
+            p.inc_current(bytes);
+        }
+        let skipped = self.skipped.swap(0, Ordering::Relaxed);
+        if skipped > 0 {
+            p.inc_skipped(skipped);
+        }
+        let items = self.items.swap(0, Ordering::Relaxed);
+        if items > 0 {
+            p.inc_items_processed(items as usize);
+        }
+        for (slot, worker) in self.workers.get().into_iter().flatten().enumerate() {
+            let delta = worker.swap(0, Ordering::Relaxed);
+            if delta > 0 {
+                p.worker_progress(slot, delta);
+            }
+        }
+    }
+
+    fn add_worker(&self, slot: usize, delta: u64) {
+        if let Some(worker) = self.workers.get().and_then(|w| w.get(slot)) {
+            worker.fetch_add(delta, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Signals that stop a run. Both take the same teardown path; only the
+/// closing line and the exit status differ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    Int,
+    Term,
+}
+
+impl Interrupt {
+    pub fn label(self) -> &'static str {
+        match self {
+            Interrupt::Int => "Interrupted",
+            Interrupt::Term => "Terminated",
+        }
+    }
+
+    /// 128 + the signal number, as a shell would report it.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Interrupt::Int => 130,
+            Interrupt::Term => 143,
+        }
+    }
+}
+
+/// Resolves on the first Ctrl+C or, on unix, SIGTERM (systemd, timeout(1)
+/// and CI runners stop jobs with the latter).
+pub async fn interrupted() -> Interrupt {
+    tokio::select! {
+        Ok(()) = ctrl_c() => Interrupt::Int,
+        () = terminate() => Interrupt::Term,
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            term.recv().await;
+        }
+        Err(_) => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate() {
+    std::future::pending().await
+}
+
+static INTERRUPT: OnceLock<Interrupt> = OnceLock::new();
+
+/// Remembers the first signal, so whoever unwinds the cancelled operation
+/// can close with the right line and exit status.
+pub fn record_interrupt(signal: Interrupt) {
+    let _ = INTERRUPT.set(signal);
+}
+
+pub fn last_interrupt() -> Option<Interrupt> {
+    INTERRUPT.get().copied()
+}
+
+// Cleanup waits for copy loops to reach a chunk boundary; on a
+// multi-threaded runtime the async ones can still move to another worker.
+fn wait_blocking<T>(f: impl FnOnce() -> T) -> T {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+    match Handle::try_current().map(|h| h.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
+
+// Per-chunk counts only show up on screen at tick time, so the tick follows
+// `refresh_ms` down to a floor rather than staying at a fixed rate.
+const MIN_TICK: Duration = Duration::from_millis(10);
+const MAX_TICK: Duration = Duration::from_millis(100);
 
 pub struct ProgressRunner {
     progress: Arc<Mutex<Box<dyn ProgressRenderer>>>,
+    counters: Arc<Counters>,
     ticker_handle: tokio::task::JoinHandle<()>,
+    ticker_stopped: Arc<AtomicBool>,
+    on_interrupt: fn() -> Option<String>,
+    cancel: Arc<OnceLock<CancellationToken>>,
 }
 
 impl ProgressRunner {
     pub fn new(
         total_size: u64,
-        plain: bool,
+        style: progress::ProgressStyle,
         silent: bool,
         json: bool,
-        on_interrupt: fn(),
+        on_interrupt: fn() -> Option<String>,
     ) -> std::io::Result<Self> {
         let log = crate::config::log_file();
-        let renderer = progress::create_renderer(total_size, plain, silent, json, log.as_ref())?;
+        let silent = silent || crate::config::is_quiet() || style == progress::ProgressStyle::None;
+        let renderer = progress::create_renderer(total_size, style, silent, json, log.as_ref())?;
         let progress = Arc::new(Mutex::new(renderer));
+        let counters = Arc::new(Counters::default());
+        // `-v` lines printed straight to the terminal would be drawn over
+        // by the next frame, so the renderer prints them for us.
+        if !silent && !json && term::is_terminal() {
+            oplog::hold_verbose(true);
+        }
 
+        // The ticker is the only place per-chunk counts reach the renderer,
+        // and it keeps speed/ETA moving while no bytes arrive. The flag is
+        // checked under the renderer lock, so once `stop_ticker` returns no
+        // tick can land on top of the final frame (abort alone is not
+        // synchronous).
         let ticker = Arc::clone(&progress);
+        let ticker_counters = Arc::clone(&counters);
+        let ticker_stopped = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&ticker_stopped);
+        let period = crate::config::refresh_interval().clamp(MIN_TICK, MAX_TICK);
         let ticker_handle = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_millis(100));
+            let mut interval = tokio::time::interval(period);
             loop {
                 interval.tick().await;
-                ticker.lock().tick();
+                let mut p = ticker.lock();
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                ticker_counters.drain_into(p.as_mut());
+                progress::forward_warnings(p.as_mut());
+                p.tick();
             }
         });
 
-        let signal = Arc::clone(&progress);
+        // With a token registered the first signal only cancels it and the
+        // operation unwinds through `finish_failed`; a second one (or a
+        // runner nobody can cancel) takes the hard path. That path holds the
+        // renderer lock so a copy failing with `Cancelled` cannot report it
+        // before we exit; partial files go next, then the terminal is put
+        // back (the TUI re-raises the Ctrl+C it reads in raw mode).
+        let signal_progress = Arc::clone(&progress);
+        let signal_counters = Arc::clone(&counters);
+        let cancel: Arc<OnceLock<CancellationToken>> = Arc::default();
+        let signal_cancel = Arc::clone(&cancel);
         tokio::spawn(async move {
-            if let Ok(()) = ctrl_c().await {
-                on_interrupt();
-                let _ = signal.lock().finish_err("interrupted");
-                std::process::exit(130);
+            let mut signal = interrupted().await;
+            record_interrupt(signal);
+            if let Some(token) = signal_cancel.get() {
+                token.cancel();
+                signal = interrupted().await;
             }
+            let mut p = signal_progress.lock();
+            let note = wait_blocking(on_interrupt);
+            signal_counters.drain_into(p.as_mut());
+            progress::forward_warnings(p.as_mut());
+            let _ = p.finish_interrupted(signal.label(), note.as_deref());
+            std::process::exit(signal.exit_code());
         });
 
         Ok(Self {
             progress,
+            counters,
             ticker_handle,
+            ticker_stopped,
+            on_interrupt,
+            cancel,
         })
     }
 
+    /// Makes the first Ctrl+C or SIGTERM cancel `token` instead of ending
+    /// the process, so the operation stops at its next check and unwinds.
+    pub fn cancel_on_interrupt(&self, token: &CancellationToken) {
+        let _ = self.cancel.set(token.clone());
+    }
+
+    fn stop_ticker(&self) {
+        self.ticker_stopped.store(true, Ordering::SeqCst);
+        self.ticker_handle.abort();
+    }
+
     pub fn progress(&self) -> &Arc<Mutex<Box<dyn ProgressRenderer>>> {
         &self.progress
     }
 
     pub fn inc_callback(&self) -> impl Fn(u64) + Send + Sync + Clone + 'static {
-        let p = Arc::clone(&self.progress);
-        move |n| p.lock().inc_current(n)
+        let c = Arc::clone(&self.counters);
+        move |n| {
+            c.bytes.fetch_add(n, Ordering::Relaxed);
+        }
     }
 
     pub fn skip_callback(&self) -> impl Fn(u64) + Send + Sync + Clone + 'static {
-        let p = Arc::clone(&self.progress);
-        move |n| p.lock().inc_skipped(n)
+        let c = Arc::clone(&self.counters);
+        move |n| {
+            c.skipped.fetch_add(n, Ordering::Relaxed);
+        }
     }
 
+    // Bytes still queued belong to the previous file, so they are handed
+    // over before the renderer resets its per-file progress.
     pub fn file_callback(&self) -> impl Fn(&str, u64) + Send + Sync + Clone + 'static {
         let p = Arc::clone(&self.progress);
-        move |name, size| p.lock().set_current_file(name, size)
+        let c = Arc::clone(&self.counters);
+        move |name, size| {
+            let mut p = p.lock();
+            c.drain_into(p.as_mut());
+            p.set_current_file(name, size);
+        }
+    }
+
+    pub fn items_callback(&self) -> impl Fn(usize) + Send + Sync + Clone + 'static {
+        let c = Arc::clone(&self.counters);
+        move |n| {
+            c.items.fetch_add(n as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn verify_queued_callback(&self) -> impl Fn(usize) + Send + Sync + Clone + 'static {
+        let p = Arc::clone(&self.progress);
+        move |n| p.lock().add_verify_queued(n)
+    }
+
+    pub fn verified_callback(&self) -> impl Fn(usize) + Send + Sync + Clone + 'static {
+        let p = Arc::clone(&self.progress);
+        move |n| p.lock().inc_verified(n)
+    }
+
+    pub fn worker_file_callback(
+        &self,
+    ) -> impl Fn(usize, &str, u64) + Send + Sync + Clone + 'static {
+        let p = Arc::clone(&self.progress);
+        let c = Arc::clone(&self.counters);
+        move |slot, name, size| {
+            let mut p = p.lock();
+            c.drain_into(p.as_mut());
+            p.worker_file(slot, name, size);
+        }
+    }
+
+    pub fn worker_progress_callback(&self) -> impl Fn(usize, u64) + Send + Sync + Clone + 'static {
+        let c = Arc::clone(&self.counters);
+        move |slot, n| c.add_worker(slot, n)
+    }
+
+    pub fn worker_done_callback(&self) -> impl Fn(usize) + Send + Sync + Clone + 'static {
+        let p = Arc::clone(&self.progress);
+        let c = Arc::clone(&self.counters);
+        move |slot| {
+            let mut p = p.lock();
+            c.drain_into(p.as_mut());
+            p.finish_worker(slot);
+        }
+    }
+
+    pub fn set_paths(&self, src: &str, dst: &str) {
+        self.progress.lock().set_paths(src, dst);
     }
 
     pub fn set_parallel_mode(&self, worker_count: usize) {
+        let _ = self
+            .counters
+            .workers
+            .set((0..worker_count).map(|_| AtomicU64::new(0)).collect());
         self.progress.lock().set_parallel_mode(worker_count);
     }
 
     pub fn finish_ok(self) -> Result<()> {
-        self.ticker_handle.abort();
-        self.progress.lock().finish()?;
+        self.stop_ticker();
+        let mut p = self.progress.lock();
+        self.counters.drain_into(p.as_mut());
+        progress::forward_warnings(p.as_mut());
+        p.finish()?;
         Ok(())
     }
 
     pub fn finish_err(self, msg: String) -> Result<()> {
-        self.ticker_handle.abort();
-        let _ = self.progress.lock().finish_err(&msg);
+        self.stop_with_error(&msg);
         bail!("{}", msg);
     }
 
+    /// Closes out a failed operation. One cancelled by a signal gets the
+    /// same closing line and partial-file cleanup the hard teardown gives.
+    pub fn finish_failed(self, err: BcmrError) -> Result<()> {
+        let signal = last_interrupt().filter(|_| matches!(err, BcmrError::Cancelled));
+        if let Some(signal) = signal {
+            self.stop_ticker();
+            let mut p = self.progress.lock();
+            let note = wait_blocking(self.on_interrupt);
+            self.counters.drain_into(p.as_mut());
+            progress::forward_warnings(p.as_mut());
+            let _ = p.finish_interrupted(signal.label(), note.as_deref());
+        } else {
+            self.stop_with_error(&err.to_string());
+        }
+        Err(err.into())
+    }
+
     pub fn finish_with_error(self, msg: &str) {
-        self.ticker_handle.abort();
-        let _ = self.progress.lock().finish_err(msg);
+        self.stop_with_error(msg);
+    }
+
+    fn stop_with_error(&self, msg: &str) {
+        self.stop_ticker();
+        let mut p = self.progress.lock();
+        self.counters.drain_into(p.as_mut());
+        progress::forward_warnings(p.as_mut());
+        let _ = p.finish_err(msg);
     }
 }
 
 impl Drop for ProgressRunner {
     fn drop(&mut self) {
-        self.ticker_handle.abort();
+        self.stop_ticker();
+        oplog::hold_verbose(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ProgressRenderer for Recorder {
+        fn inc_current(&mut self, delta: u64) {
+            self.events.lock().push(format!("bytes {delta}"));
+        }
+        fn inc_items_processed(&mut self, count: usize) {
+            self.events.lock().push(format!("items {count}"));
+        }
+        fn set_current_file(&mut self, file_name: &str, _file_size: u64) {
+            self.events.lock().push(format!("file {file_name}"));
+        }
+        fn finish(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn queued_bytes_land_before_the_next_file() {
+        let counters = Counters::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut p: Box<dyn ProgressRenderer> = Box::new(Recorder {
+            events: Arc::clone(&events),
+        });
+        counters.bytes.fetch_add(10, Ordering::Relaxed);
+        counters.bytes.fetch_add(5, Ordering::Relaxed);
+        counters.items.fetch_add(1, Ordering::Relaxed);
+        counters.drain_into(p.as_mut());
+        p.set_current_file("b", 0);
+        counters.drain_into(p.as_mut());
+        assert_eq!(*events.lock(), ["bytes 15", "items 1", "file b"]);
+    }
+
+    // Stands in for a slow frame: the renderer lock is held for the whole
+    // run, and the copy-side callbacks must still get through.
+    #[tokio::test]
+    async fn hot_path_does_not_wait_for_the_renderer() {
+        const THREADS: usize = 8;
+        const CHUNKS: u64 = 200_000;
+
+        let runner =
+            ProgressRunner::new(0, progress::ProgressStyle::default(), true, false, || None)
+                .unwrap();
+        let inc = runner.inc_callback();
+        let items = runner.items_callback();
+        let frame = runner.progress().lock();
+
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..THREADS {
+            let (inc, items, tx) = (inc.clone(), items.clone(), tx.clone());
+            std::thread::spawn(move || {
+                for _ in 0..CHUNKS {
+                    inc(4096);
+                }
+                items(1);
+                let _ = tx.send(());
+            });
+        }
+        for _ in 0..THREADS {
+            rx.recv_timeout(std::time::Duration::from_secs(30))
+                .expect("copy loop blocked on the renderer lock");
+        }
+        assert_eq!(
+            runner.counters.bytes.load(Ordering::Relaxed),
+            THREADS as u64 * CHUNKS * 4096
+        );
+        assert_eq!(
+            runner.counters.items.load(Ordering::Relaxed),
+            THREADS as u64
+        );
+        drop(frame);
     }
 }
@@ -0,0 +1,172 @@
+use crate::core::error::BcmrError;
+use crate::ui::runner::{interrupted, record_interrupt};
+use crate::ui::utils::{format_bytes, format_count, truncate_to_width};
+use parking_lot::Mutex;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const FRAME_INTERVAL: Duration = Duration::from_millis(150);
+
+/// Running totals a size scan publishes while it walks the tree.
+#[derive(Default)]
+pub struct ScanCounters {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    current: Mutex<String>,
+    cancel: CancellationToken,
+}
+
+impl ScanCounters {
+    /// Fails once the command is cancelled, which is where a scan stops.
+    pub fn record(&self, path: &Path, size: u64) -> Result<(), BcmrError> {
+        if self.cancel.is_cancelled() {
+            return Err(BcmrError::Cancelled);
+        }
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+        *self.current.lock() = path.display().to_string();
+        Ok(())
+    }
+
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    fn status_line(&self, frame: usize, width: usize) -> String {
+        let line = format!(
+            "{} Scanning: {} files, {} so far — current: {}",
+            SPINNER[frame % SPINNER.len()],
+            format_count(self.files()),
+            format_bytes(self.bytes() as f64),
+            self.current.lock()
+        );
+        truncate_to_width(&line, width.saturating_sub(1))
+    }
+}
+
+/// One-line spinner on stderr shown while a pre-copy scan runs, before the
+/// real progress renderer exists (overwrite prompts may still come first).
+pub struct ScanIndicator {
+    counters: Arc<ScanCounters>,
+    done: Arc<AtomicBool>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl ScanIndicator {
+    /// Draws nothing unless `enabled`, stderr is a terminal and the run
+    /// is not `--quiet`. Ctrl+C or SIGTERM while it is up cancels `cancel`.
+    pub fn start(enabled: bool, cancel: &CancellationToken) -> Self {
+        let counters = Arc::new(ScanCounters {
+            cancel: cancel.clone(),
+            ..ScanCounters::default()
+        });
+        let done = Arc::new(AtomicBool::new(false));
+        let enabled = enabled && !crate::config::is_quiet();
+        let ticker = (enabled && std::io::stderr().is_terminal()).then(|| {
+            let counters = Arc::clone(&counters);
+            let done = Arc::clone(&done);
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(FRAME_INTERVAL);
+                let mut frame = 0usize;
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            if done.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            let width = terminal_size::terminal_size()
+                                .map(|(w, _)| w.0 as usize)
+                                .unwrap_or(80);
+                            let mut err = std::io::stderr().lock();
+                            let _ = write!(err, "\r\x1b[2K{}", counters.status_line(frame, width));
+                            let _ = err.flush();
+                            frame += 1;
+                        }
+                        signal = interrupted() => {
+                            record_interrupt(signal);
+                            cancel.cancel();
+                            break;
+                        }
+                    }
+                }
+            })
+        });
+        Self {
+            counters,
+            done,
+            ticker,
+        }
+    }
+
+    pub fn counters(&self) -> Arc<ScanCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    /// Stops the spinner and wipes its line so the renderer that follows
+    /// starts on a clean row. Returns the final file count.
+    pub async fn finish(mut self) -> u64 {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+            let _ = ticker.await;
+            clear_line();
+        }
+        self.counters.files()
+    }
+}
+
+fn clear_line() {
+    let mut err = std::io::stderr().lock();
+    let _ = write!(err, "\r\x1b[2K");
+    let _ = err.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::utils::display_width;
+
+    #[test]
+    fn status_line_reports_counts_and_fits_width() {
+        let c = ScanCounters::default();
+        for i in 0..1500 {
+            c.record(Path::new(&format!("dir/sub/file-{i}.bin")), 1024)
+                .unwrap();
+        }
+        let line = c.status_line(0, 200);
+        assert!(line.contains("1,500 files"), "{line}");
+        assert!(line.contains("1.46 MiB so far"), "{line}");
+        assert!(line.ends_with("dir/sub/file-1499.bin"), "{line}");
+
+        for width in [20, 40, 60] {
+            assert!(display_width(&c.status_line(3, width)) < width);
+        }
+    }
+
+    #[test]
+    fn record_stops_a_cancelled_scan() {
+        let cancel = CancellationToken::new();
+        let c = ScanCounters {
+            cancel: cancel.clone(),
+            ..ScanCounters::default()
+        };
+        assert!(c.record(Path::new("a"), 1).is_ok());
+        cancel.cancel();
+        assert!(matches!(
+            c.record(Path::new("b"), 1),
+            Err(BcmrError::Cancelled)
+        ));
+        assert_eq!(c.files(), 1);
+    }
+}
@@ -1,5 +1,36 @@
+use crate::core::oplog::{self, StatsScope, TransferStats};
+use crate::ui::utils::{format_bytes, format_count, format_eta, truncate_path};
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+/// With no bytes for this long the smoothed speed is dropped to zero rather
+/// than left to decay geometrically, so a stalled transfer reads as stalled.
+pub const STALL_AFTER: Duration = Duration::from_secs(2);
+
+/// Past this total a whole percent takes long enough that the display
+/// looks frozen, so the percentage gains a decimal place.
+pub const FRACTIONAL_PERCENT_ABOVE: u64 = 100 * 1024 * 1024 * 1024;
+
+/// Speed samples kept for the sparkline, one per `SPEED_SAMPLE_EVERY`.
+pub const SPEED_HISTORY: usize = 60;
+pub const SPEED_SAMPLE_EVERY: Duration = Duration::from_secs(1);
+
+/// Below this average file size, files per second says more about progress
+/// than bytes per second does.
+pub const SMALL_FILE_AVERAGE: u64 = 256 * 1024;
+
+/// Exponential smoothing shared by the overall, per-file and per-worker
+/// speeds, so the three figures react at the same rate.
+fn smoothed(previous: f64, sample: f64) -> f64 {
+    if !sample.is_finite() {
+        previous
+    } else if previous > 0.0 {
+        previous * 0.8 + sample * 0.2
+    } else {
+        sample
+    }
+}
+
 pub struct WorkerState {
     pub file_name: String,
     pub file_size: u64,
@@ -11,6 +42,14 @@ pub struct WorkerState {
 }
 
 impl WorkerState {
+    /// Whole percent of this worker's file; empty files count as done.
+    pub fn percent(&self) -> u16 {
+        if self.file_size == 0 {
+            return 100;
+        }
+        (self.progress as f64 / self.file_size as f64 * 100.0).min(100.0) as u16
+    }
+
     pub fn new() -> Self {
         Self {
             file_name: String::new(),
@@ -29,12 +68,7 @@ impl WorkerState {
             return self.speed;
         }
         let bytes_per_sec = self.progress.saturating_sub(self.last_bytes) as f64 / elapsed;
-        let speed = bytes_per_sec / (1024.0 * 1024.0);
-        self.speed = if self.speed > 0.0 {
-            self.speed * 0.8 + speed * 0.2
-        } else {
-            speed
-        };
+        self.speed = smoothed(self.speed, bytes_per_sec / (1024.0 * 1024.0));
         self.last_update = Instant::now();
         self.last_bytes = self.progress;
         self.speed
@@ -48,19 +82,52 @@ pub struct ProgressData {
     pub current_file: String,
     pub current_file_size: u64,
     pub current_file_progress: u64,
+    pub file_skipped: u64,
+    pub file_last_update: Instant,
+    pub file_last_transferred: u64,
+    pub file_speed: f64,
 
     pub start_time: Instant,
     pub last_update: Instant,
     pub last_transferred: u64,
     pub last_speed: f64,
+    pub last_progress: Instant,
+    pub last_items: usize,
+    pub items_speed: f64,
+    pub last_item_progress: Instant,
 
     pub operation_type: String,
+    pub paths: String,
+    pub warnings: Vec<String>,
     pub items_total: Option<usize>,
     pub items_processed: usize,
+    pub verify_queued: usize,
+    pub verified: usize,
     pub scanning: bool,
+    /// The total can't be known up front (a download without a
+    /// Content-Length): no percentage or ETA is claimed.
+    pub size_unknown: bool,
+    /// Uncompressed and compressed size of a `--compress`/`--decompress`
+    /// copy, reported with the closing line.
+    pub codec_sizes: Option<(u64, u64)>,
     pub files_found: u64,
     pub workers: Vec<WorkerState>,
     pub parallel_total: usize,
+    pub files_started: usize,
+    /// Minimum gap between frames, from `[progress] refresh_ms` or
+    /// `--refresh-ms`; zero redraws on every event.
+    pub refresh_interval: Duration,
+    /// `[progress] decimals`; 1 forces a decimal place at any size.
+    pub decimals: u8,
+    pub speed_history: VecDeque<f64>,
+    last_speed_sample: Option<Instant>,
+    /// `[progress] stall_secs`; zero turns stall reporting off.
+    pub stall_threshold: Duration,
+    last_activity: Instant,
+    stalled_since: Option<Instant>,
+    stall_total: Duration,
+    last_draw: Option<Instant>,
+    stopped_after: Option<Duration>,
 }
 
 impl ProgressData {
@@ -73,22 +140,112 @@ impl ProgressData {
             current_file: String::new(),
             current_file_size: 0,
             current_file_progress: 0,
+            file_skipped: 0,
+            file_last_update: now,
+            file_last_transferred: 0,
+            file_speed: 0.0,
 
             start_time: now,
             last_update: now,
             last_transferred: 0,
             last_speed: 0.0,
+            last_progress: now,
+            last_items: 0,
+            items_speed: 0.0,
+            last_item_progress: now,
 
             operation_type: String::new(),
+            paths: String::new(),
+            warnings: Vec::new(),
             items_total: None,
             items_processed: 0,
+            verify_queued: 0,
+            verified: 0,
             scanning: false,
+            size_unknown: false,
+            codec_sizes: None,
             files_found: 0,
             workers: Vec::new(),
             parallel_total: 0,
+            files_started: 0,
+            refresh_interval: crate::config::refresh_interval(),
+            decimals: crate::config::CONFIG.progress.decimals,
+            speed_history: VecDeque::with_capacity(SPEED_HISTORY),
+            last_speed_sample: None,
+            stall_threshold: Duration::from_secs(crate::config::CONFIG.progress.stall_secs),
+            last_activity: now,
+            stalled_since: None,
+            stall_total: Duration::ZERO,
+            last_draw: None,
+            stopped_after: None,
         }
     }
 
+    /// Byte callbacks arrive in arbitrary chunk sizes, so renderers gate
+    /// redraws on wall-clock time rather than on how many bytes moved.
+    pub fn should_redraw(&self, now: Instant) -> bool {
+        match self.last_draw {
+            None => true,
+            Some(last) => {
+                now.saturating_duration_since(last) >= self.refresh_interval
+                    || (self.total_bytes > 0 && self.current_bytes >= self.total_bytes)
+            }
+        }
+    }
+
+    pub fn mark_drawn(&mut self, now: Instant) {
+        self.last_draw = Some(now);
+    }
+
+    /// Called from the ticker; keeps at most `SPEED_HISTORY` samples of the
+    /// smoothed speed, spaced `SPEED_SAMPLE_EVERY` apart.
+    pub fn sample_speed(&mut self, now: Instant) {
+        if self
+            .last_speed_sample
+            .is_some_and(|t| now.saturating_duration_since(t) < SPEED_SAMPLE_EVERY)
+        {
+            return;
+        }
+        self.last_speed_sample = Some(now);
+        if self.speed_history.len() == SPEED_HISTORY {
+            self.speed_history.pop_front();
+        }
+        self.speed_history.push_back(self.last_speed);
+    }
+
+    pub fn force_redraw(&mut self) {
+        self.last_draw = None;
+    }
+
+    pub fn set_paths(&mut self, src: &str, dst: &str) {
+        self.paths = if dst.is_empty() {
+            src.to_string()
+        } else {
+            format!("{} → {}", src, dst)
+        };
+    }
+
+    /// "⚠ 14 warnings — <latest>", or None while there are none.
+    pub fn warning_summary(&self) -> Option<String> {
+        let latest = self.warnings.last()?;
+        let count = self.warnings.len();
+        Some(format!(
+            "⚠ {} warning{} — {}",
+            format_count(count as u64),
+            if count == 1 { "" } else { "s" },
+            latest
+        ))
+    }
+
+    /// Only one file is (so far) involved, so a separate per-file bar would
+    /// just repeat the total. Flips back as soon as a second file starts.
+    pub fn is_single_file(&self) -> bool {
+        self.parallel_total == 0
+            && !self.scanning
+            && matches!(self.items_total, None | Some(1))
+            && self.files_started <= 1
+    }
+
     pub fn init_workers(&mut self, count: usize) {
         self.parallel_total = count;
         self.workers = (0..count).map(|_| WorkerState::new()).collect();
@@ -113,6 +270,34 @@ impl ProgressData {
         w.active = true;
     }
 
+    pub fn worker_file(&mut self, slot: usize, file_name: &str, file_size: u64) {
+        let Some(w) = self.workers.get_mut(slot) else {
+            return;
+        };
+        w.file_name = file_name.to_string();
+        w.file_size = file_size;
+        w.progress = 0;
+        w.active = true;
+        w.last_bytes = 0;
+        w.last_update = Instant::now();
+        w.speed = 0.0;
+    }
+
+    /// Counts toward the overall total as well as the worker's own file.
+    pub fn inc_current(&mut self, delta: u64) {
+        self.current_bytes += delta;
+        self.current_file_progress += delta;
+        self.record_activity(Instant::now());
+    }
+
+    pub fn worker_progress(&mut self, slot: usize, delta: u64) {
+        self.current_bytes += delta;
+        self.record_activity(Instant::now());
+        if let Some(w) = self.workers.get_mut(slot) {
+            w.progress += delta;
+        }
+    }
+
     pub fn finish_worker(&mut self, slot: usize) {
         let Some(w) = self.workers.get_mut(slot) else {
             return;
@@ -131,28 +316,184 @@ impl ProgressData {
             return self.last_speed;
         }
 
+        let now = Instant::now();
         let transferred = self.current_bytes.saturating_sub(self.skipped_bytes);
+        if transferred > self.last_transferred {
+            self.last_progress = now;
+        }
         let bytes_per_sec = transferred.saturating_sub(self.last_transferred) as f64 / elapsed;
         let speed = bytes_per_sec / (1024.0 * 1024.0);
 
-        self.last_speed = if self.last_speed > 0.0 {
-            self.last_speed * 0.8 + speed * 0.2
+        self.last_speed = if now.saturating_duration_since(self.last_progress) >= STALL_AFTER {
+            0.0
         } else {
-            speed
+            smoothed(self.last_speed, speed)
         };
 
-        self.last_update = Instant::now();
+        let items_per_sec = self.items_processed.saturating_sub(self.last_items) as f64 / elapsed;
+        if self.items_processed > self.last_items {
+            self.last_item_progress = now;
+        }
+        self.items_speed = if now.saturating_duration_since(self.last_item_progress) >= STALL_AFTER
+        {
+            0.0
+        } else {
+            smoothed(self.items_speed, items_per_sec)
+        };
+
+        self.last_update = now;
         self.last_transferred = transferred;
+        self.last_items = self.items_processed;
 
         self.last_speed
     }
 
+    /// True once the item total is known and the files are small on
+    /// average; rate and ETA then follow the file count.
+    pub fn prefers_item_rate(&self) -> bool {
+        match self.items_total {
+            Some(total) if total > 0 => self.total_bytes / (total as u64) < SMALL_FILE_AVERAGE,
+            _ => false,
+        }
+    }
+
+    /// "3.10 MiB/s", or "1,498 files/s | 3.10 MiB/s" for small-file work,
+    /// with "(limit 5.00 MiB/s)" after it while `--bwlimit` is in force.
+    pub fn rate_text(&self, speed: f64) -> String {
+        let mut bytes = if speed > 0.0 {
+            format!("{}/s", format_bytes(speed * 1024.0 * 1024.0))
+        } else {
+            "-- /s".to_string()
+        };
+        if let Some(limit) = crate::core::bwlimit::current() {
+            bytes.push_str(&format!(" (limit {}/s)", format_bytes(limit as f64)));
+        }
+        if !self.prefers_item_rate() {
+            return bytes;
+        }
+        let files = if self.items_speed > 0.0 {
+            format_count(self.items_speed.round() as u64)
+        } else {
+            "--".to_string()
+        };
+        format!("{} files/s | {}", files, bytes)
+    }
+
+    /// "Verified: 812 / 1,204" once `--verify` has queued anything.
+    pub fn verify_text(&self) -> Option<String> {
+        (self.verify_queued > 0).then(|| {
+            format!(
+                "Verified: {} / {}",
+                format_count(self.verified as u64),
+                format_count(self.verify_queued as u64)
+            )
+        })
+    }
+
+    pub fn set_current_file(&mut self, file_name: &str, file_size: u64) {
+        // The first-file hint shown while scanning is re-announced when the
+        // copy starts; only a different name means another file.
+        if file_name != self.current_file {
+            self.files_started += 1;
+        }
+        self.current_file = file_name.to_string();
+        self.current_file_size = file_size;
+        self.current_file_progress = 0;
+        self.file_skipped = 0;
+        self.file_last_update = Instant::now();
+        self.file_last_transferred = 0;
+        self.file_speed = 0.0;
+    }
+
+    /// Resume credit fills the file's bar but is kept out of both speeds.
     pub fn inc_skipped(&mut self, delta: u64) {
         self.current_bytes += delta;
         self.skipped_bytes += delta;
+        self.current_file_progress += delta;
+        self.file_skipped += delta;
+        self.record_activity(Instant::now());
+    }
+
+    /// Bytes arrived, so any stall is over.
+    pub fn record_activity(&mut self, now: Instant) {
+        self.end_stall(now);
+        self.last_activity = now;
+    }
+
+    /// Ticker-driven: a stall starts being reported once nothing has
+    /// arrived for `stall_threshold`, and is dated from the last byte.
+    pub fn check_stall(&mut self, now: Instant) {
+        let done = self.total_bytes > 0 && self.current_bytes >= self.total_bytes;
+        if self.stall_threshold.is_zero() || done || self.stopped_after.is_some() {
+            return;
+        }
+        if self.stalled_since.is_none()
+            && now.saturating_duration_since(self.last_activity) >= self.stall_threshold
+        {
+            self.stalled_since = Some(self.last_activity);
+        }
+    }
+
+    fn end_stall(&mut self, now: Instant) {
+        if let Some(since) = self.stalled_since.take() {
+            self.stall_total += now.saturating_duration_since(since);
+        }
+    }
+
+    pub fn stalled_for(&self, now: Instant) -> Option<Duration> {
+        self.stalled_since
+            .map(|since| now.saturating_duration_since(since))
+    }
+
+    /// Time spent in reported stalls, including one still in progress.
+    pub fn total_stall(&self, now: Instant) -> Duration {
+        self.stall_total + self.stalled_for(now).unwrap_or_default()
+    }
+
+    /// "⚠ stalled for 12 s on current/file", shown instead of a speed that
+    /// would otherwise still describe the last moment bytes moved.
+    pub fn stall_notice(&self, now: Instant) -> Option<String> {
+        let stalled = self.stalled_for(now)?;
+        let file = if self.parallel_total > 0 {
+            self.workers
+                .iter()
+                .find(|w| w.active)
+                .map_or("", |w| w.file_name.as_str())
+        } else {
+            self.current_file.as_str()
+        };
+        let mut notice = format!("⚠ stalled for {} s", stalled.as_secs());
+        if !file.is_empty() {
+            notice.push_str(&format!(" on {}", truncate_path(file, 40)));
+        }
+        Some(notice)
+    }
+
+    /// Speed of the current file alone, in MiB/s, smoothed like
+    /// `calculate_speed`.
+    pub fn calculate_file_speed(&mut self) -> f64 {
+        let now = Instant::now();
+        let elapsed = now
+            .saturating_duration_since(self.file_last_update)
+            .as_secs_f64();
+        if elapsed < 0.1 {
+            return self.file_speed;
+        }
+        let transferred = self.current_file_progress.saturating_sub(self.file_skipped);
+        let bytes_per_sec = transferred.saturating_sub(self.file_last_transferred) as f64 / elapsed;
+        self.file_speed = smoothed(self.file_speed, bytes_per_sec / (1024.0 * 1024.0));
+        self.file_last_update = now;
+        self.file_last_transferred = transferred;
+        self.file_speed
     }
 
     pub fn estimate_eta(&self) -> Option<Duration> {
+        if self.size_unknown {
+            return None;
+        }
+        if self.prefers_item_rate() {
+            return self.estimate_eta_from_items();
+        }
         if self.total_bytes == 0 || self.current_bytes >= self.total_bytes {
             return Some(Duration::from_secs(0));
         }
@@ -168,8 +509,160 @@ impl ProgressData {
         Some(Duration::from_secs(secs))
     }
 
+    fn estimate_eta_from_items(&self) -> Option<Duration> {
+        let total = self.items_total?;
+        if self.items_processed >= total {
+            return Some(Duration::from_secs(0));
+        }
+        if self.items_speed <= 0.0 {
+            return None;
+        }
+        let remaining = (total - self.items_processed) as f64;
+        Some(Duration::from_secs(
+            (remaining / self.items_speed).ceil() as u64
+        ))
+    }
+
     pub fn elapsed(&self) -> Duration {
-        self.start_time.elapsed()
+        self.stopped_after
+            .unwrap_or_else(|| self.start_time.elapsed())
+    }
+
+    /// Freezes `elapsed()` so the last frame and the Done summary report
+    /// the same duration.
+    pub fn stop_clock(&mut self) -> Duration {
+        self.end_stall(Instant::now());
+        *self
+            .stopped_after
+            .get_or_insert_with(|| self.start_time.elapsed())
+    }
+
+    /// Whole percent of the current file. An empty file is done the moment
+    /// it starts, rather than sitting at 0%.
+    pub fn file_percent(&self) -> u16 {
+        if self.current_file_size == 0 {
+            return if self.current_file.is_empty() { 0 } else { 100 };
+        }
+        (self.current_file_progress as f64 / self.current_file_size as f64 * 100.0).min(100.0)
+            as u16
+    }
+
+    /// Completed share of the total, clamped to `0.0..=1.0`.
+    pub fn total_fraction(&self) -> f64 {
+        if self.size_unknown {
+            return 0.0;
+        }
+        (self.current_bytes as f64 / self.total_bytes.max(1) as f64).min(1.0)
+    }
+
+    fn fractional_percent(&self) -> bool {
+        self.decimals > 0 || self.total_bytes > FRACTIONAL_PERCENT_ABOVE
+    }
+
+    /// Columns taken by `percent_text`, so a bar next to it can be sized
+    /// once and the other bars aligned with it.
+    pub fn percent_width(&self) -> usize {
+        if self.fractional_percent() {
+            6
+        } else {
+            4
+        }
+    }
+
+    /// Total percentage, right-aligned to `percent_width`: `" 41%"` or
+    /// `" 41.3%"`. Rounds down so 100 only shows when everything is done.
+    pub fn percent_text(&self) -> String {
+        if self.size_unknown {
+            return format!("{:>width$}", "?%", width = self.percent_width());
+        }
+        let pct = self.total_fraction() * 100.0;
+        if self.fractional_percent() {
+            format!("{:>5.1}%", (pct * 10.0).floor() / 10.0)
+        } else {
+            format!("{:>3}%", pct as u64)
+        }
+    }
+
+    /// The total for a "done / total" figure, or "?" when it is unknown.
+    pub fn total_text(&self, format: fn(f64) -> String) -> String {
+        if self.size_unknown {
+            "?".to_string()
+        } else {
+            format(self.total_bytes as f64)
+        }
+    }
+
+    /// "Copying" → "Copy", for labels that read better without the -ing.
+    pub fn operation_verb(&self) -> Option<&'static str> {
+        match self.operation_type.as_str() {
+            "Copying" => Some("Copy"),
+            "Moving" => Some("Move"),
+            "Removing" => Some("Remove"),
+            "Uploading" => Some("Upload"),
+            "Downloading" => Some("Download"),
+            "Relaying" => Some("Relay"),
+            "Archiving" => Some("Archive"),
+            "Extracting" => Some("Extract"),
+            "Compressing" => Some("Compress"),
+            "Decompressing" => Some("Decompress"),
+            _ => None,
+        }
+    }
+
+    /// Scrollback record printed by every interactive renderer, e.g.
+    /// "Copy done: 2.40 GiB in 31.2s | avg 78.77 MiB/s".
+    pub fn done_line(&self) -> String {
+        let label = match self.operation_verb() {
+            Some(verb) => format!("{} done", verb),
+            None => "Done".to_string(),
+        };
+        let mut line = format!(
+            "{}: {} in {:.1}s | avg {}/s",
+            label,
+            format_bytes(self.current_bytes as f64),
+            self.elapsed().as_secs_f64(),
+            format_bytes(self.average_bytes_per_sec().unwrap_or(0.0))
+        );
+        if let Some((raw, compressed)) = self.codec_sizes {
+            line.push_str(&format!(
+                " | {} raw, {} compressed ({:.2}:1)",
+                format_bytes(raw as f64),
+                format_bytes(compressed as f64),
+                raw as f64 / compressed.max(1) as f64
+            ));
+        }
+        if !self.stall_total.is_zero() {
+            line.push_str(&format!(
+                " | stalled {}",
+                format_eta(self.stall_total.as_secs())
+            ));
+        }
+        line
+    }
+
+    /// Closing line for a run stopped by a signal, e.g.
+    /// "Interrupted: copied 1.00 GiB of 2.40 GiB, partial 'big.iso' removed".
+    pub fn interrupted_line(&self, label: &str, partial: Option<&str>) -> String {
+        let done = match self.operation_verb() {
+            Some("Move") => "moved",
+            Some("Remove") => "removed",
+            Some("Upload") => "uploaded",
+            Some("Download") => "downloaded",
+            Some("Relay") => "relayed",
+            _ => "copied",
+        };
+        let mut line = format!(
+            "{}: {} {} of {}",
+            label,
+            done,
+            format_bytes(self.current_bytes as f64),
+            format_bytes(self.total_bytes as f64)
+        );
+        if let Some(partial) = partial {
+            line.push_str(", ");
+            line.push_str(partial);
+        }
+        line
     }
 
     pub fn average_bytes_per_sec(&self) -> Option<f64> {
@@ -179,6 +672,30 @@ impl ProgressData {
         }
         Some(self.current_bytes as f64 / secs)
     }
+
+    /// The `--stats` block for this run. Files considered are the ones the
+    /// scan counted, or where nothing was counted, those with an outcome.
+    pub fn transfer_stats(&self) -> TransferStats {
+        let scope = match self.operation_verb() {
+            Some("Move") => StatsScope::Move,
+            Some("Remove") => StatsScope::Remove,
+            _ => StatsScope::Copy,
+        };
+        let considered = self.items_total.map_or_else(
+            || {
+                let s = oplog::stats();
+                s.created + s.overwritten + s.appended + s.skipped + s.moved + s.removed
+            },
+            |n| n as u64,
+        );
+        oplog::transfer_stats(
+            scope,
+            considered,
+            self.total_bytes,
+            self.elapsed(),
+            self.average_bytes_per_sec().unwrap_or(0.0),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -249,6 +766,421 @@ mod tests {
         assert!(pd.workers[0].file_name.is_empty());
     }
 
+    #[test]
+    fn test_worker_file_and_progress() {
+        let mut pd = ProgressData::new(1000);
+        pd.init_workers(2);
+
+        pd.worker_file(1, "a.bin", 400);
+        pd.worker_progress(1, 150);
+        pd.worker_progress(1, 50);
+        assert_eq!(pd.workers[1].progress, 200);
+        assert_eq!(pd.current_bytes, 200);
+
+        // The next file on the same slot starts from zero.
+        pd.finish_worker(1);
+        pd.worker_file(1, "b.bin", 100);
+        assert_eq!(pd.workers[1].progress, 0);
+        assert_eq!(pd.active_worker_count(), 1);
+        assert_eq!(pd.current_bytes, 200);
+    }
+
+    #[test]
+    fn test_redraw_throttle_is_time_based() {
+        let mut pd = ProgressData::new(1024 * 1024 * 1024);
+        pd.refresh_interval = Duration::from_millis(100);
+        let t0 = Instant::now();
+        assert!(pd.should_redraw(t0));
+        pd.mark_drawn(t0);
+
+        // Irregular chunk sizes over one simulated second, one callback per ms.
+        let chunks = [4096u64, 1, 65_537, 131_072, 7, 3000];
+        let mut draws = 0;
+        for ms in 1..=1000u64 {
+            pd.current_bytes += chunks[ms as usize % chunks.len()];
+            let now = t0 + Duration::from_millis(ms);
+            if pd.should_redraw(now) {
+                pd.mark_drawn(now);
+                draws += 1;
+            }
+        }
+        assert_eq!(draws, 10);
+    }
+
+    fn draws_in_one_second(refresh: Duration) -> usize {
+        let mut pd = ProgressData::new(u64::MAX);
+        pd.refresh_interval = refresh;
+        let t0 = Instant::now();
+        pd.mark_drawn(t0);
+        let mut draws = 0;
+        for ms in 1..=1000u64 {
+            let now = t0 + Duration::from_millis(ms);
+            if pd.should_redraw(now) {
+                pd.mark_drawn(now);
+                draws += 1;
+            }
+        }
+        draws
+    }
+
+    #[test]
+    fn test_redraw_follows_configured_interval() {
+        assert_eq!(draws_in_one_second(Duration::from_millis(50)), 20);
+        assert_eq!(draws_in_one_second(Duration::from_millis(250)), 4);
+        assert_eq!(draws_in_one_second(Duration::ZERO), 1000);
+    }
+
+    #[test]
+    fn test_redraw_forced_at_completion() {
+        let mut pd = ProgressData::new(100);
+        pd.refresh_interval = Duration::from_millis(100);
+        let t0 = Instant::now();
+        pd.mark_drawn(t0);
+        pd.current_bytes = 50;
+        assert!(!pd.should_redraw(t0 + Duration::from_millis(10)));
+        pd.current_bytes = 100;
+        assert!(pd.should_redraw(t0 + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_speed_decays_then_zeroes_when_idle() {
+        let mut pd = ProgressData::new(1024 * 1024 * 100);
+        pd.current_bytes = 1024 * 1024 * 10;
+        pd.last_speed = 10.0;
+        pd.last_transferred = pd.current_bytes;
+
+        // One idle tick shortly after the last byte: decays but stays positive.
+        pd.last_update = Instant::now() - Duration::from_millis(200);
+        pd.last_progress = Instant::now() - Duration::from_millis(200);
+        let decayed = pd.calculate_speed();
+        assert!(decayed > 0.0 && decayed < 10.0, "{decayed}");
+
+        // Idle past the stall threshold: reported as stopped, ETA unknown.
+        pd.last_update = Instant::now() - Duration::from_millis(200);
+        pd.last_progress = Instant::now() - STALL_AFTER;
+        assert_eq!(pd.calculate_speed(), 0.0);
+        assert_eq!(pd.estimate_eta(), None);
+
+        // Bytes arriving again revive the estimate.
+        pd.current_bytes += 1024 * 1024;
+        pd.last_update = Instant::now() - Duration::from_millis(200);
+        assert!(pd.calculate_speed() > 0.0);
+    }
+
+    #[test]
+    fn test_file_speed_follows_current_file() {
+        const MIB: u64 = 1024 * 1024;
+        let mut pd = ProgressData::new(1000 * MIB);
+        pd.set_current_file("big.iso", 600 * MIB);
+
+        // Scripted one-second steps of 10 MiB, then 20 MiB.
+        for (step, expected) in [(10, 10.0), (10, 10.0), (20, 12.0)] {
+            pd.current_file_progress += step * MIB;
+            pd.file_last_update = Instant::now() - Duration::from_secs(1);
+            let speed = pd.calculate_file_speed();
+            assert!((speed - expected).abs() < 0.1, "{speed} != {expected}");
+        }
+
+        // A new file starts from zero instead of inheriting the old rate.
+        pd.set_current_file("small.txt", MIB);
+        assert_eq!(pd.calculate_file_speed(), 0.0);
+    }
+
+    #[test]
+    fn test_file_speed_ignores_resume_credit() {
+        const MIB: u64 = 1024 * 1024;
+        let mut pd = ProgressData::new(1000 * MIB);
+        pd.set_current_file("big.iso", 600 * MIB);
+        pd.inc_skipped(500 * MIB);
+        assert_eq!(pd.current_file_progress, 500 * MIB);
+
+        pd.current_file_progress += 5 * MIB;
+        pd.file_last_update = Instant::now() - Duration::from_secs(1);
+        let speed = pd.calculate_file_speed();
+        assert!((speed - 5.0).abs() < 0.1, "{speed}");
+    }
+
+    #[test]
+    fn test_item_rate_selected_for_small_files() {
+        let mut pd = ProgressData::new(1024 * 1024 * 1024);
+        assert!(!pd.prefers_item_rate());
+
+        // 1 GiB over 100 files: bytes stay the headline.
+        pd.items_total = Some(100);
+        assert!(!pd.prefers_item_rate());
+        assert_eq!(pd.rate_text(2.0), "2.00 MiB/s");
+
+        // 2 KiB files: files/s leads.
+        pd.total_bytes = 2048 * 1_000_000;
+        pd.items_total = Some(1_000_000);
+        assert!(pd.prefers_item_rate());
+        pd.items_speed = 1498.4;
+        assert_eq!(pd.rate_text(3.1), "1,498 files/s | 3.10 MiB/s");
+
+        // Just at the threshold counts as large.
+        pd.total_bytes = SMALL_FILE_AVERAGE * 10;
+        pd.items_total = Some(10);
+        assert!(!pd.prefers_item_rate());
+    }
+
+    #[test]
+    fn test_items_speed_smoothing_and_eta() {
+        let mut pd = ProgressData::new(0);
+        pd.items_total = Some(10_000);
+
+        for (done, expected) in [(1000, 1000.0), (2000, 1000.0), (4000, 1200.0)] {
+            pd.items_processed = done;
+            pd.last_update = Instant::now() - Duration::from_secs(1);
+            pd.calculate_speed();
+            assert!(
+                (pd.items_speed - expected).abs() < 1.0,
+                "{}",
+                pd.items_speed
+            );
+        }
+
+        // 6,000 files left at 1,200/s, even though the byte total is zero.
+        pd.items_speed = 1200.0;
+        assert_eq!(pd.estimate_eta(), Some(Duration::from_secs(5)));
+        pd.items_processed = 10_000;
+        assert_eq!(pd.estimate_eta(), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_percent_text_gains_a_decimal_for_large_totals() {
+        let mut data = ProgressData::new(FRACTIONAL_PERCENT_ABOVE);
+        data.decimals = 0;
+        data.current_bytes = FRACTIONAL_PERCENT_ABOVE / 1000 * 413;
+        assert_eq!(data.percent_text(), " 41%");
+        assert_eq!(data.percent_width(), 4);
+
+        data.total_bytes = FRACTIONAL_PERCENT_ABOVE + 1;
+        assert_eq!(data.percent_text(), " 41.2%");
+        assert_eq!(data.percent_width(), 6);
+
+        data.current_bytes = data.total_bytes - 1;
+        assert_eq!(data.percent_text(), " 99.9%");
+        data.current_bytes = data.total_bytes;
+        assert_eq!(data.percent_text(), "100.0%");
+    }
+
+    #[test]
+    fn test_percent_text_decimals_opt_in() {
+        let mut data = ProgressData::new(1000);
+        data.current_bytes = 5;
+        data.decimals = 0;
+        assert_eq!(data.percent_text(), "  0%");
+        data.decimals = 1;
+        assert_eq!(data.percent_text(), "  0.5%");
+        assert_eq!(data.percent_text().len(), data.percent_width());
+    }
+
+    #[test]
+    fn test_unknown_size_claims_no_percent_or_eta() {
+        let mut data = ProgressData::new(0);
+        data.decimals = 0;
+        data.size_unknown = true;
+        data.current_bytes = 5 * 1024 * 1024;
+        data.last_speed = 1.0;
+        assert_eq!(data.total_fraction(), 0.0);
+        assert_eq!(data.percent_text(), "  ?%");
+        assert_eq!(data.estimate_eta(), None);
+        assert_eq!(data.total_text(format_bytes), "?");
+    }
+
+    #[test]
+    fn test_speed_history_is_a_bounded_ring() {
+        let mut data = ProgressData::new(0);
+        let t0 = Instant::now();
+        for i in 0..(SPEED_HISTORY as u64 + 10) {
+            data.last_speed = i as f64;
+            data.sample_speed(t0 + SPEED_SAMPLE_EVERY * i as u32);
+            // Ticks between sample points are ignored.
+            data.sample_speed(t0 + SPEED_SAMPLE_EVERY * i as u32 + Duration::from_millis(100));
+        }
+        assert_eq!(data.speed_history.len(), SPEED_HISTORY);
+        assert_eq!(data.speed_history.front(), Some(&10.0));
+        assert_eq!(
+            data.speed_history.back(),
+            Some(&(SPEED_HISTORY as f64 + 9.0))
+        );
+    }
+
+    #[test]
+    fn test_stall_flag_toggles_at_threshold() {
+        let mut data = ProgressData::new(1000);
+        data.stall_threshold = Duration::from_secs(5);
+        let t0 = Instant::now();
+        data.record_activity(t0);
+
+        data.check_stall(t0 + Duration::from_millis(4999));
+        assert_eq!(data.stalled_for(t0 + Duration::from_millis(4999)), None);
+
+        let t5 = t0 + Duration::from_secs(5);
+        data.check_stall(t5);
+        assert_eq!(data.stalled_for(t5), Some(Duration::from_secs(5)));
+        data.current_file = "dir/file.bin".to_string();
+        assert_eq!(
+            data.stall_notice(t0 + Duration::from_secs(12)).as_deref(),
+            Some("⚠ stalled for 12 s on dir/file.bin")
+        );
+
+        // Bytes flowing again clear it at once and bank the stalled time.
+        let t12 = t0 + Duration::from_secs(12);
+        data.record_activity(t12);
+        assert_eq!(data.stalled_for(t12), None);
+        assert_eq!(data.stall_notice(t12), None);
+        assert_eq!(data.total_stall(t12), Duration::from_secs(12));
+
+        // A second, shorter gap stays under the threshold.
+        data.check_stall(t12 + Duration::from_secs(3));
+        data.record_activity(t12 + Duration::from_secs(3));
+        assert_eq!(data.total_stall(t12), Duration::from_secs(12));
+    }
+
+    #[test]
+    fn test_stall_disabled_and_after_completion() {
+        let mut data = ProgressData::new(1000);
+        let t0 = Instant::now();
+        data.record_activity(t0);
+        data.stall_threshold = Duration::ZERO;
+        data.check_stall(t0 + Duration::from_secs(60));
+        assert_eq!(data.stalled_for(t0 + Duration::from_secs(60)), None);
+
+        data.stall_threshold = Duration::from_secs(5);
+        data.current_bytes = 1000;
+        data.check_stall(t0 + Duration::from_secs(60));
+        assert_eq!(data.stalled_for(t0 + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn test_warning_summary() {
+        let mut data = ProgressData::new(0);
+        assert_eq!(data.warning_summary(), None);
+        data.warnings.push("a: first".to_string());
+        assert_eq!(
+            data.warning_summary().as_deref(),
+            Some("⚠ 1 warning — a: first")
+        );
+        data.warnings.push("b: second".to_string());
+        assert_eq!(
+            data.warning_summary().as_deref(),
+            Some("⚠ 2 warnings — b: second")
+        );
+    }
+
+    #[test]
+    fn test_empty_file_reads_as_complete() {
+        let mut data = ProgressData::new(0);
+        assert_eq!(data.file_percent(), 0);
+        data.set_current_file("empty.txt", 0);
+        assert_eq!(data.file_percent(), 100);
+        data.set_current_file("half.bin", 10);
+        data.current_file_progress = 5;
+        assert_eq!(data.file_percent(), 50);
+
+        let worker = WorkerState::new();
+        assert_eq!(worker.percent(), 100);
+    }
+
+    #[test]
+    fn test_speed_stays_finite_with_no_bytes() {
+        let mut data = ProgressData::new(0);
+        data.last_update = Instant::now() - Duration::from_millis(150);
+        assert_eq!(data.calculate_speed(), 0.0);
+        assert_eq!(data.calculate_file_speed(), 0.0);
+        assert!(data.estimate_eta().is_some());
+        assert_eq!(smoothed(3.0, f64::NAN), 3.0);
+        assert_eq!(smoothed(3.0, f64::INFINITY), 3.0);
+    }
+
+    #[test]
+    fn test_verify_counter_appears_once_files_are_queued() {
+        let mut data = ProgressData::new(0);
+        assert_eq!(data.verify_text(), None);
+        data.verify_queued = 1_204;
+        data.verified = 812;
+        assert_eq!(data.verify_text().as_deref(), Some("Verified: 812 / 1,204"));
+    }
+
+    #[test]
+    fn test_set_paths() {
+        let mut data = ProgressData::new(0);
+        data.set_paths("a, b", "~/dst");
+        assert_eq!(data.paths, "a, b → ~/dst");
+        data.set_paths("removing: x", "");
+        assert_eq!(data.paths, "removing: x");
+    }
+
+    #[test]
+    fn test_single_file_until_a_second_name_appears() {
+        let mut data = ProgressData::new(1000);
+        assert!(data.is_single_file());
+        data.set_current_file("big.iso", 1000);
+        data.set_current_file("big.iso", 1000);
+        assert!(data.is_single_file());
+        data.set_current_file("other.iso", 1000);
+        assert!(!data.is_single_file());
+
+        let mut data = ProgressData::new(1000);
+        data.items_total = Some(3);
+        assert!(!data.is_single_file());
+        data.items_total = Some(1);
+        data.scanning = true;
+        assert!(!data.is_single_file());
+        data.scanning = false;
+        data.init_workers(2);
+        assert!(!data.is_single_file());
+    }
+
+    #[test]
+    fn test_done_line_names_the_operation() {
+        let mut pd = ProgressData::new(2048);
+        pd.current_bytes = 2048;
+        pd.start_time = Instant::now() - Duration::from_secs(2);
+        pd.stop_clock();
+        let line = pd.done_line();
+        assert!(line.starts_with("Done: 2.00 KiB in 2.0s | avg "), "{line}");
+
+        pd.operation_type = "Moving".to_string();
+        assert!(pd.done_line().starts_with("Move done: 2.00 KiB"));
+
+        pd.operation_type = "Compressing".to_string();
+        pd.codec_sizes = Some((2048, 512));
+        let line = pd.done_line();
+        assert!(line.starts_with("Compress done: 2.00 KiB"), "{line}");
+        assert!(
+            line.ends_with(" | 2.00 KiB raw, 512 B compressed (4.00:1)"),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn test_interrupted_line_reports_partial_totals() {
+        let mut pd = ProgressData::new(4096);
+        pd.current_bytes = 1024;
+        pd.operation_type = "Copying".to_string();
+        assert_eq!(
+            pd.interrupted_line("Terminated", None),
+            "Terminated: copied 1.00 KiB of 4.00 KiB"
+        );
+        assert_eq!(
+            pd.interrupted_line("Interrupted", Some("partial 'a.bin' removed")),
+            "Interrupted: copied 1.00 KiB of 4.00 KiB, partial 'a.bin' removed"
+        );
+    }
+
+    #[test]
+    fn test_stop_clock_freezes_elapsed() {
+        let mut pd = ProgressData::new(1000);
+        pd.start_time = Instant::now() - Duration::from_secs(90);
+        let stopped = pd.stop_clock();
+        assert!(stopped >= Duration::from_secs(90));
+        pd.start_time = Instant::now() - Duration::from_secs(500);
+        assert_eq!(pd.elapsed(), stopped);
+        assert_eq!(pd.stop_clock(), stopped);
+    }
+
     #[test]
     fn test_worker_calculate_speed() {
         let mut w = WorkerState::new();
@@ -1,76 +1,110 @@
+use crate::ui::term;
 #[cfg(unix)]
 use signal_hook::consts::signal::{SIGCONT, SIGTSTP};
 #[cfg(unix)]
 use signal_hook::iterator::Signals;
 use std::io;
-use std::sync::atomic::AtomicBool;
-#[cfg(unix)]
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use std::sync::Arc;
 #[cfg(unix)]
 use std::thread;
 
+/// Shared between the renderer and the signal thread. The renderer publishes
+/// where the shell prompt should land while stopped; the signal thread tells
+/// it when the box has to be re-anchored after `fg`.
+#[derive(Default)]
+pub struct SuspendState {
+    suspended: AtomicBool,
+    resumed: AtomicBool,
+    park_row: AtomicU16,
+}
+
+impl SuspendState {
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+
+    pub fn take_resumed(&self) -> bool {
+        self.resumed.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn set_park_row(&self, row: u16) {
+        self.park_row.store(row, Ordering::Relaxed);
+    }
+}
+
 #[cfg(unix)]
-pub fn suspend_now(suspended: &AtomicBool) {
-    use crossterm::cursor::Show;
+fn leave_terminal(state: &SuspendState) {
+    use crossterm::cursor::{MoveTo, Show};
     use crossterm::execute;
     use crossterm::terminal::disable_raw_mode;
+    use std::io::Write;
 
-    let _ = execute!(std::io::stdout(), Show);
+    state.suspended.store(true, Ordering::SeqCst);
+    let mut out = term::out();
+    let _ = execute!(out, MoveTo(0, state.park_row.load(Ordering::Relaxed)), Show);
     let _ = disable_raw_mode();
-    suspended.store(true, Ordering::SeqCst);
+    let _ = writeln!(out);
+}
+
+#[cfg(unix)]
+fn reenter_terminal(state: &SuspendState) {
+    use crossterm::cursor::Hide;
+    use crossterm::execute;
+    use crossterm::terminal::enable_raw_mode;
 
-    unsafe {
-        libc::raise(libc::SIGSTOP);
+    let in_foreground = unsafe {
+        let fg = libc::tcgetpgrp(libc::STDIN_FILENO);
+        fg >= 0 && fg == libc::getpgrp()
+    };
+    // Continued into the background (`bg`): keep quiet until a later `fg`.
+    if !in_foreground {
+        return;
     }
+    let _ = enable_raw_mode();
+    let _ = execute!(term::out(), Hide);
+    state.resumed.store(true, Ordering::SeqCst);
+    state.suspended.store(false, Ordering::SeqCst);
+}
+
+// Stops the whole process the way the default SIGTSTP disposition would, so
+// the shell sees a normal job-control stop and no copy task runs until SIGCONT.
+#[cfg(unix)]
+fn stop_self() {
+    let _ = signal_hook::low_level::emulate_default_handler(SIGTSTP);
+}
+
+/// Ctrl+Z arrives as a key press in raw mode rather than as SIGTSTP.
+#[cfg(unix)]
+pub fn suspend_now(state: &SuspendState) {
+    leave_terminal(state);
+    stop_self();
 }
 
 #[cfg(not(unix))]
-pub fn suspend_now(_suspended: &AtomicBool) {}
+pub fn suspend_now(_state: &SuspendState) {}
 
-pub fn install_suspend_handler() -> io::Result<Arc<AtomicBool>> {
-    let suspended = Arc::new(AtomicBool::new(false));
+pub fn install_suspend_handler() -> io::Result<Arc<SuspendState>> {
+    let state = Arc::new(SuspendState::default());
 
     #[cfg(unix)]
     {
-        let suspended_clone = Arc::clone(&suspended);
+        let state_clone = Arc::clone(&state);
         let mut signals = Signals::new([SIGTSTP, SIGCONT]).map_err(io::Error::other)?;
 
         thread::spawn(move || {
-            use crossterm::cursor::Show;
-            use crossterm::execute;
-            use crossterm::terminal::disable_raw_mode;
-
             for sig in signals.forever() {
                 match sig {
                     SIGTSTP => {
-                        let _ = execute!(std::io::stdout(), Show);
-                        let _ = disable_raw_mode();
-                        suspended_clone.store(true, Ordering::SeqCst);
-
-                        unsafe {
-                            libc::raise(libc::SIGSTOP);
-                        }
-                    }
-                    SIGCONT => {
-                        let in_foreground = unsafe {
-                            let fg = libc::tcgetpgrp(libc::STDIN_FILENO);
-                            fg >= 0 && fg == libc::getpgrp()
-                        };
-
-                        if in_foreground {
-                            use crossterm::cursor::Hide;
-                            use crossterm::terminal::enable_raw_mode;
-                            let _ = enable_raw_mode();
-                            let _ = execute!(std::io::stdout(), Hide);
-                            suspended_clone.store(false, Ordering::SeqCst);
-                        }
+                        leave_terminal(&state_clone);
+                        stop_self();
                     }
+                    SIGCONT => reenter_terminal(&state_clone),
                     _ => {}
                 }
             }
         });
     }
 
-    Ok(suspended)
+    Ok(state)
 }
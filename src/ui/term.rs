@@ -0,0 +1,294 @@
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::sync::OnceLock;
+
+/// Where progress is drawn. Stdout is left to data — dry-run listings,
+/// `--json` events, or whatever the user redirected it into.
+enum Sink {
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+fn sink() -> &'static Sink {
+    SINK.get_or_init(|| match crate::config::progress_fd() {
+        Some(1) => Sink::Stdout,
+        Some(2) => Sink::Stderr,
+        Some(fd) => open_fd(fd).map_or(Sink::Stderr, Sink::File),
+        None if io::stderr().is_terminal() => Sink::Stderr,
+        None => open_tty().map_or(Sink::Stderr, Sink::File),
+    })
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Option<File> {
+    use std::os::fd::{BorrowedFd, OwnedFd};
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: only borrowed long enough to dup; fcntl rejects a closed fd.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    borrowed
+        .try_clone_to_owned()
+        .ok()
+        .map(|owned: OwnedFd| File::from(owned))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> Option<File> {
+    None
+}
+
+// Stderr went to a file but someone is still watching: draw on the
+// controlling terminal directly, like ssh and gpg prompts do.
+#[cfg(unix)]
+fn open_tty() -> Option<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()
+}
+
+#[cfg(not(unix))]
+fn open_tty() -> Option<File> {
+    None
+}
+
+/// Buffered handle on the progress sink. Everything written goes out as
+/// one write on `flush` (or drop), so a frame never reaches the terminal
+/// half-drawn.
+#[derive(Default)]
+pub struct Out {
+    buf: Vec<u8>,
+}
+
+pub fn out() -> Out {
+    Out::default()
+}
+
+impl Write for Out {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let result = match sink() {
+            Sink::Stdout => {
+                let mut out = io::stdout().lock();
+                out.write_all(&self.buf).and_then(|_| out.flush())
+            }
+            Sink::Stderr => io::stderr().lock().write_all(&self.buf),
+            Sink::File(f) => {
+                let mut f: &File = f;
+                f.write_all(&self.buf)
+            }
+        };
+        self.buf.clear();
+        result
+    }
+}
+
+impl Drop for Out {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+pub fn is_terminal() -> bool {
+    match sink() {
+        Sink::Stdout => io::stdout().is_terminal(),
+        Sink::Stderr => io::stderr().is_terminal(),
+        Sink::File(f) => f.is_terminal(),
+    }
+}
+
+/// Columns and rows of the terminal progress is drawn on.
+pub fn size() -> Option<(u16, u16)> {
+    let size = match sink() {
+        Sink::Stdout => terminal_size::terminal_size_of(io::stdout()),
+        Sink::Stderr => terminal_size::terminal_size_of(io::stderr()),
+        Sink::File(f) => terminal_size::terminal_size_of(f),
+    };
+    size.or_else(terminal_size::terminal_size)
+        .map(|(w, h)| (w.0, h.0))
+}
+
+/// Cursor position on the progress terminal. crossterm's own query is
+/// written to stdout, which is only right when stdout is that terminal.
+pub fn cursor_position() -> io::Result<(u16, u16)> {
+    if !is_terminal() {
+        return Err(io::Error::other("progress is not drawn on a terminal"));
+    }
+    if matches!(sink(), Sink::Stdout) {
+        return crossterm::cursor::position();
+    }
+    query_position()
+}
+
+#[cfg(unix)]
+fn query_position() -> io::Result<(u16, u16)> {
+    query_position_within(REPLY_TIMEOUT_MS)
+}
+
+/// How long a terminal gets to report the cursor position.
+#[cfg(unix)]
+const REPLY_TIMEOUT_MS: i32 = 2000;
+
+/// The probe's shorter wait: a terminal that answers at all answers fast,
+/// and nobody should sit through two seconds before every copy.
+#[cfg(unix)]
+const PROBE_TIMEOUT_MS: i32 = 500;
+
+#[cfg(unix)]
+fn query_position_within(timeout_ms: i32) -> io::Result<(u16, u16)> {
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+
+    let was_raw = is_raw_mode_enabled()?;
+    if !was_raw {
+        enable_raw_mode()?;
+    }
+    let result = (|| {
+        let mut out = out();
+        out.write_all(b"\x1b[6n")?;
+        out.flush()?;
+        read_position_reply(&File::open("/dev/tty")?, timeout_ms)
+    })();
+    if !was_raw {
+        disable_raw_mode()?;
+    }
+    result
+}
+
+#[cfg(not(unix))]
+fn query_position() -> io::Result<(u16, u16)> {
+    crossterm::cursor::position()
+}
+
+#[cfg(unix)]
+fn read_position_reply(tty: &File, timeout_ms: i32) -> io::Result<(u16, u16)> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+
+    let mut reply = Vec::new();
+    let mut byte = [0u8; 1];
+    while reply.len() < 32 {
+        let mut pfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, timeout_ms) } <= 0 {
+            break;
+        }
+        let mut reader: &File = tty;
+        if reader.read(&mut byte)? == 0 {
+            break;
+        }
+        reply.push(byte[0]);
+        if byte[0] == b'R' {
+            if let Some(pos) = parse_position_reply(&reply) {
+                return Ok(pos);
+            }
+        }
+    }
+    Err(io::Error::other("the cursor position could not be read"))
+}
+
+/// Set to `1` to treat the progress terminal as a legacy console that
+/// handles neither escape sequences nor raw mode, as if the probe failed.
+pub const LEGACY_CONSOLE_ENV: &str = "BCMR_LEGACY_CONSOLE";
+
+/// What the progress terminal turned out to handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Support {
+    /// Escape sequences are interpreted rather than printed.
+    pub vt: bool,
+    /// Raw mode could be switched on.
+    pub raw_mode: bool,
+    /// The terminal reported where the cursor is.
+    pub cursor: bool,
+}
+
+impl Support {
+    pub const NONE: Support = Support {
+        vt: false,
+        raw_mode: false,
+        cursor: false,
+    };
+}
+
+/// Tries out what the renderers that move the cursor rely on. Raw mode
+/// is left as it was found, and the cursor query reads the controlling
+/// terminal with a short timeout, so a redirected stdin cannot stall it.
+pub fn probe() -> Support {
+    if std::env::var(LEGACY_CONSOLE_ENV).is_ok_and(|v| v == "1") {
+        return Support::NONE;
+    }
+    let vt = vt_supported();
+    Support {
+        vt,
+        raw_mode: raw_mode_works(),
+        cursor: vt && probe_position().is_ok(),
+    }
+}
+
+#[cfg(windows)]
+fn vt_supported() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+fn vt_supported() -> bool {
+    true
+}
+
+fn raw_mode_works() -> bool {
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+
+    match is_raw_mode_enabled() {
+        Ok(true) => true,
+        Ok(false) => enable_raw_mode().is_ok() && disable_raw_mode().is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(unix)]
+fn probe_position() -> io::Result<(u16, u16)> {
+    query_position_within(PROBE_TIMEOUT_MS)
+}
+
+// The console API answers from the screen buffer; nothing is read.
+#[cfg(not(unix))]
+fn probe_position() -> io::Result<(u16, u16)> {
+    crossterm::cursor::position()
+}
+
+/// Parses a `ESC [ row ; col R` report into zero-based (col, row).
+fn parse_position_reply(reply: &[u8]) -> Option<(u16, u16)> {
+    let text = std::str::from_utf8(reply).ok()?;
+    let start = text.rfind("\x1b[")?;
+    let (row, col) = text[start + 2..].strip_suffix('R')?.split_once(';')?;
+    let row: u16 = row.parse().ok()?;
+    let col: u16 = col.parse().ok()?;
+    Some((col.saturating_sub(1), row.saturating_sub(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_reply_is_zero_based() {
+        assert_eq!(parse_position_reply(b"\x1b[12;40R"), Some((39, 11)));
+        // Stray input typed before the report is ignored.
+        assert_eq!(parse_position_reply(b"ab\x1b[1;1R"), Some((0, 0)));
+        assert_eq!(parse_position_reply(b"\x1b[12R"), None);
+    }
+}
@@ -1,27 +1,255 @@
 use crate::config::CONFIG;
-use crate::ui::progress::ProgressRenderer;
-use crate::ui::state::ProgressData;
-use crate::ui::suspend::{install_suspend_handler, suspend_now};
-use crate::ui::utils::{format_bytes, format_eta, get_gradient_color, parse_hex_color};
+use crate::core::oplog;
+use crate::ui::progress::{print_warnings, ProgressRenderer};
+use crate::ui::state::{ProgressData, WorkerState};
+use crate::ui::suspend::{install_suspend_handler, suspend_now, SuspendState};
+use crate::ui::term;
+use crate::ui::utils::{
+    display_width, fit_to_width, format_bytes, format_bytes_short, format_eta,
+    format_operation_stats, format_transfer_stats, get_gradient_color, parse_hex_color,
+    truncate_path, truncate_to_width,
+};
 use crossterm::{
-    cursor::{position, Hide, MoveTo, Show},
+    cursor::{Hide, MoveTo, Show},
     event::{self, Event, KeyCode},
     execute,
-    style::{Attribute, SetAttribute, SetForegroundColor},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    style::{Attribute, Color, SetAttribute, SetForegroundColor},
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, ScrollUp},
+    Command,
 };
-use std::io::{self, stdout, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+struct BoxLayout {
+    box_width: usize,
+    right_border_col: u16,
+    bar_width: usize,
+}
+
+impl BoxLayout {
+    fn for_width(term_width: u16) -> Self {
+        let box_width = term_width as usize;
+        Self {
+            box_width,
+            right_border_col: term_width.saturating_sub(2),
+            bar_width: (box_width.saturating_sub(20)).max(20),
+        }
+    }
+}
+
+/// Keeps a `height`-row box starting at `start_row` on screen after the
+/// terminal shrinks; rows that still fit are left where they are.
+fn anchor_row(start_row: u16, height: u16, term_height: u16) -> u16 {
+    if start_row.saturating_add(height) > term_height {
+        term_height.saturating_sub(height)
+    } else {
+        start_row
+    }
+}
+
+/// Title, total bar, detail line and bottom border: a lone file needs no
+/// second bar, its name moves into the title instead.
+const SINGLE_FILE_LINES: u16 = 4;
+
+fn title_text(data: &ProgressData, max_width: usize) -> String {
+    let operation = if data.operation_type.is_empty() {
+        "Progress"
+    } else {
+        data.operation_type.as_str()
+    };
+    let title = if data.is_single_file() && !data.current_file.is_empty() {
+        format!("{}: {}", operation, data.current_file)
+    } else if data.operation_type.is_empty() {
+        operation.to_string()
+    } else {
+        format!("{} Progress", operation)
+    };
+    truncate_to_width(&title, max_width)
+}
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Index into `SPARK_LEVELS` for `value` on a scale whose top is `max`.
+fn spark_level(value: f64, max: f64) -> usize {
+    if max <= 0.0 || value <= 0.0 {
+        return 0;
+    }
+    ((value / max * 7.0).round() as usize).min(7)
+}
+
+/// "▁▂▃▅▇▇▆ now 38.0 MiB/s, peak 412.0 MiB/s". The newest samples win
+/// when there are more than fit; early in a run the graph is just short.
+fn sparkline_text(history: &VecDeque<f64>, now: f64, width: usize) -> String {
+    let peak = history.iter().copied().fold(now, f64::max);
+    let label = format!(
+        " now {}/s, peak {}/s",
+        format_bytes(now * 1024.0 * 1024.0),
+        format_bytes(peak * 1024.0 * 1024.0)
+    );
+    let room = width.saturating_sub(display_width(&label));
+    let graph: String = history
+        .iter()
+        .skip(history.len().saturating_sub(room))
+        .map(|&v| SPARK_LEVELS[spark_level(v, peak)])
+        .collect();
+    format!("{}{}", graph, label)
+}
+
+/// Narrower than this, the box is replaced by two unframed lines.
+const COMPACT_BELOW: u16 = 60;
+const COMPACT_LINES: u16 = 2;
+
+fn is_compact(term_width: u16) -> bool {
+    term_width > 0 && term_width < COMPACT_BELOW
+}
+
+/// "Copy 42% [=====>   ] 1.2G/2.9G 38M/s 00:42" and "→ name.ext 17%". The
+/// bar absorbs whatever width is left and is dropped when it would be tiny.
+fn compact_lines(
+    data: &ProgressData,
+    speed: f64,
+    eta: Option<Duration>,
+    width: usize,
+) -> [String; 2] {
+    let total_pct = (data.total_fraction() * 100.0) as usize;
+    let file_pct = data.file_percent();
+
+    let head = format!(
+        "{} {}",
+        data.operation_verb().unwrap_or("Progress"),
+        data.percent_text()
+    );
+    let speed = if speed > 0.0 {
+        format!("{}/s", format_bytes_short(speed * 1024.0 * 1024.0))
+    } else {
+        "--/s".to_string()
+    };
+    let tail = format!(
+        "{}/{} {} {}",
+        format_bytes_short(data.current_bytes as f64),
+        data.total_text(format_bytes_short),
+        speed,
+        eta.map(|d| format_eta(d.as_secs()))
+            .unwrap_or_else(|| "--:--".to_string())
+    );
+
+    let bar_width = width.saturating_sub(display_width(&head) + display_width(&tail) + 4);
+    let top = if bar_width >= 5 {
+        let filled = bar_width * total_pct / 100;
+        let bar = match filled {
+            0 => " ".repeat(bar_width),
+            n if n >= bar_width => "=".repeat(bar_width),
+            n => format!("{}>{}", "=".repeat(n - 1), " ".repeat(bar_width - n)),
+        };
+        format!("{} [{}] {}", head, bar, tail)
+    } else {
+        format!("{} {}", head, tail)
+    };
+
+    let file_suffix = format!(" {}%", file_pct);
+    let name_width = width.saturating_sub(2 + display_width(&file_suffix));
+    let bottom = format!(
+        "→ {}{}",
+        truncate_path(&data.current_file, name_width),
+        file_suffix
+    );
+
+    [
+        truncate_to_width(&top, width),
+        truncate_to_width(&bottom, width),
+    ]
+}
+
+/// Border, total bar, detail line and workers header around the worker rows.
+const PARALLEL_CHROME_ROWS: u16 = 5;
+
+/// Worker rows that fit under the chrome; always at least one. A height of
+/// zero means the terminal has not been measured yet.
+fn worker_rows(workers: usize, term_height: u16) -> usize {
+    if term_height == 0 {
+        return workers;
+    }
+    let room = term_height.saturating_sub(PARALLEL_CHROME_ROWS) as usize;
+    workers.min(room.max(1))
+}
+
+/// Slots to draw, top to bottom. With room for everyone rows stay in slot
+/// order; otherwise busy workers take the rows and idle ones are dropped.
+fn visible_workers(workers: &[WorkerState], rows: usize) -> Vec<usize> {
+    if rows >= workers.len() {
+        return (0..workers.len()).collect();
+    }
+    let active = (0..workers.len()).filter(|&i| workers[i].active);
+    let idle = (0..workers.len()).filter(|&i| !workers[i].active);
+    active.chain(idle).take(rows).collect()
+}
+
+const INCOMPLETE_COLOR: &str = "#444444";
+
+/// Per-cell gradient colors, computed once per bar width rather than
+/// re-interpolated for every cell of every frame.
+#[derive(Default)]
+struct GradientCache {
+    bars: HashMap<usize, Vec<Color>>,
+}
+
+impl GradientCache {
+    fn bar(&mut self, width: usize, filled: usize) -> String {
+        // Old widths pile up only across resizes; no need to keep them.
+        if self.bars.len() > 8 {
+            self.bars.clear();
+        }
+        let theme = &CONFIG.progress.theme;
+        let colors = self
+            .bars
+            .entry(width)
+            .or_insert_with(|| gradient_colors(&theme.bar_gradient, width));
+        bar_string(
+            colors,
+            filled,
+            &theme.bar_complete_char,
+            &theme.bar_incomplete_char,
+        )
+    }
+}
+
+fn gradient_colors(gradient: &[String], width: usize) -> Vec<Color> {
+    (0..width)
+        .map(|i| get_gradient_color(gradient, i as f32 / width as f32))
+        .collect()
+}
+
+/// A whole bar as one string of cells and color escapes, with a color
+/// emitted only where it differs from the previous cell's.
+fn bar_string(colors: &[Color], filled: usize, complete: &str, incomplete: &str) -> String {
+    let filled = filled.min(colors.len());
+    let mut bar = String::new();
+    let mut last = None;
+    for &color in &colors[..filled] {
+        if last != Some(color) {
+            let _ = SetForegroundColor(color).write_ansi(&mut bar);
+            last = Some(color);
+        }
+        bar.push_str(complete);
+    }
+    let _ = SetForegroundColor(parse_hex_color(INCOMPLETE_COLOR)).write_ansi(&mut bar);
+    bar.push_str(&incomplete.repeat(colors.len() - filled));
+    bar
+}
+
 pub struct TuiProgress {
     data: ProgressData,
+    gradient: GradientCache,
     start_row: u16,
     raw_mode_enabled: bool,
     initialized: bool,
     finished: bool,
-    suspended: Arc<AtomicBool>,
+    suspend: Arc<SuspendState>,
     last_rendered_lines: u16,
+    last_size: (u16, u16),
+    sparkline: bool,
 }
 
 impl TuiProgress {
@@ -29,44 +257,88 @@ impl TuiProgress {
         let data = ProgressData::new(total_bytes);
         Ok(Self {
             data,
+            gradient: GradientCache::default(),
             start_row: 0,
             raw_mode_enabled: false,
             initialized: false,
             finished: false,
-            suspended: Arc::new(AtomicBool::new(false)),
+            suspend: Arc::default(),
             last_rendered_lines: 0,
+            last_size: (0, 0),
+            sparkline: CONFIG.progress.sparkline,
         })
     }
 
     fn total_lines(&self) -> u16 {
-        if self.data.parallel_total > 0 {
-            5 + self.data.parallel_total as u16
+        if is_compact(self.last_size.0) {
+            COMPACT_LINES
+        } else if self.data.parallel_total > 0 {
+            self.optional_rows()
+                + PARALLEL_CHROME_ROWS
+                + self.visible_worker_rows(self.last_size.1) as u16
+        } else if self.data.is_single_file() {
+            self.optional_rows() + SINGLE_FILE_LINES
         } else {
-            7
+            self.optional_rows() + 7
         }
     }
 
+    /// The `src → dst` line sits between the title and the total bar.
+    fn header_rows(&self) -> u16 {
+        u16::from(!self.data.paths.is_empty())
+    }
+
+    /// The sparkline sits under the detail line.
+    fn sparkline_rows(&self) -> u16 {
+        u16::from(self.sparkline)
+    }
+
+    fn optional_rows(&self) -> u16 {
+        self.header_rows() + self.sparkline_rows() + self.warning_rows()
+    }
+
+    /// The latest warning sits just above the bottom border.
+    fn warning_rows(&self) -> u16 {
+        u16::from(!self.data.warnings.is_empty())
+    }
+
+    fn visible_worker_rows(&self, term_height: u16) -> usize {
+        let room = match term_height {
+            0 => 0,
+            h => h.saturating_sub(self.optional_rows()).max(1),
+        };
+        worker_rows(self.data.parallel_total, room)
+    }
+
     fn initialize(&mut self) -> io::Result<()> {
         if self.initialized {
             return Ok(());
         }
 
-        self.suspended = install_suspend_handler()?;
+        self.suspend = install_suspend_handler()?;
+        self.place_box();
 
-        let required_height = self.total_lines();
+        let _ = enable_raw_mode();
+        let _ = execute!(term::out(), Hide);
+
+        self.raw_mode_enabled = true;
+        self.initialized = true;
 
-        let (_, term_height) = terminal_size::terminal_size()
-            .map(|(w, h)| (w.0, h.0))
-            .unwrap_or((80, 24));
+        Ok(())
+    }
+
+    /// Anchors the box at the cursor, scrolling first if it would not fit.
+    fn place_box(&mut self) {
+        let (term_width, term_height) = terminal_dimensions();
+        self.last_size = (term_width, term_height);
+        let required_height = self.total_lines();
 
-        let (_col, mut row) = position().unwrap_or((0, 0));
+        let (_col, mut row) = term::cursor_position().unwrap_or((0, 0));
 
         if row + required_height > term_height {
             let lines_to_scroll = (row + required_height).saturating_sub(term_height);
-            for _ in 0..lines_to_scroll {
-                println!();
-            }
-            let (_new_col, new_row) = position().unwrap_or((0, 0));
+            let _ = term::out().write_all(&b"\n".repeat(lines_to_scroll as usize));
+            let (_new_col, new_row) = term::cursor_position().unwrap_or((0, 0));
             row = new_row;
             if row + required_height > term_height {
                 row = term_height.saturating_sub(required_height);
@@ -74,22 +346,54 @@ impl TuiProgress {
         }
 
         self.start_row = row;
+    }
 
-        let _ = enable_raw_mode();
-        let _ = execute!(stdout(), Hide);
-
-        self.raw_mode_enabled = true;
-        self.initialized = true;
-
+    // Wipes everything the old-sized box left behind (including lines the
+    // terminal reflowed) so the next frame starts from a clean region.
+    fn handle_resize(&mut self, size: (u16, u16)) -> io::Result<()> {
+        let (_, term_height) = size;
+        execute!(
+            term::out(),
+            MoveTo(0, self.start_row),
+            Clear(ClearType::FromCursorDown)
+        )?;
+        self.last_size = size;
+        self.start_row = anchor_row(self.start_row, self.total_lines(), term_height);
+        // The region was just wiped; don't leave it blank until the next
+        // scheduled frame.
+        self.data.force_redraw();
         Ok(())
     }
 
+    /// Raw mode turns Ctrl+C/Ctrl+Z into key events, so they are read here
+    /// on every tick even when the frame itself is not due. Returns false
+    /// once the process has been suspended.
+    fn handle_input(&mut self) -> io::Result<bool> {
+        while event::poll(Duration::from_millis(0))? {
+            match event::read()? {
+                Event::Key(key) if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                    match key.code {
+                        KeyCode::Char('c') => interrupt_self(),
+                        KeyCode::Char('z') => {
+                            suspend_now(&self.suspend);
+                            return Ok(false);
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Resize(w, h) => self.handle_resize((w, h))?,
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
     fn redraw(&mut self) -> io::Result<()> {
         if self.finished {
             return Ok(());
         }
 
-        if self.suspended.load(Ordering::SeqCst) {
+        if self.suspend.is_suspended() {
             return Ok(());
         }
 
@@ -97,16 +401,31 @@ impl TuiProgress {
             self.initialize()?;
         }
 
+        // The shell printed its job-control chatter where the box used to
+        // be, so start a fresh box below it instead of overdrawing.
+        if self.suspend.take_resumed() {
+            self.place_box();
+            self.last_rendered_lines = 0;
+        }
+
         let new_lines = self.total_lines();
         if self.last_rendered_lines > new_lines {
-            let mut stdout = stdout();
+            let mut out = term::out();
             for i in new_lines..self.last_rendered_lines {
                 let _ = execute!(
-                    stdout,
+                    out,
                     MoveTo(0, self.start_row + i),
                     Clear(ClearType::CurrentLine)
                 );
             }
+        } else if self.last_rendered_lines > 0 {
+            // A box that grows (a second file, workers appearing) must not
+            // run off the bottom of the screen.
+            let overflow = (self.start_row + new_lines).saturating_sub(self.last_size.1);
+            if overflow > 0 {
+                execute!(term::out(), ScrollUp(overflow))?;
+                self.start_row = self.start_row.saturating_sub(overflow);
+            }
         }
 
         let display_file = if self.data.current_file.is_empty() {
@@ -115,46 +434,39 @@ impl TuiProgress {
             self.data.current_file.clone()
         };
 
-        if event::poll(Duration::from_millis(0))? {
-            if let Event::Key(key) = event::read()? {
-                if key.modifiers.contains(event::KeyModifiers::CONTROL) {
-                    match key.code {
-                        KeyCode::Char('c') => {
-                            self.finish()?;
-                            std::process::exit(130);
-                        }
-                        KeyCode::Char('z') => {
-                            suspend_now(&self.suspended);
-                            return Ok(());
-                        }
-                        _ => {}
-                    }
-                }
-            }
+        if !self.handle_input()? {
+            return Ok(());
+        }
+
+        // Resize events only arrive while we are polling; a size change
+        // between frames is caught here instead.
+        let size = terminal_dimensions();
+        if size != self.last_size {
+            self.handle_resize(size)?;
         }
 
-        let total_progress = (self.data.current_bytes as f64 / self.data.total_bytes.max(1) as f64
-            * 100.0)
-            .min(100.0) as u16;
-        let current_progress = (self.data.current_file_progress as f64
-            / self.data.current_file_size.max(1) as f64
-            * 100.0)
-            .min(100.0) as u16;
+        let total_fraction = self.data.total_fraction();
+        let percent = self.data.percent_text();
+        let current_progress = self.data.file_percent();
         let speed = self.data.calculate_speed();
         let eta_opt = self.data.estimate_eta();
 
-        let operation = if self.data.operation_type.is_empty() {
-            "Progress".to_string()
-        } else {
-            format!("{} Progress", self.data.operation_type)
-        };
+        if is_compact(size.0) {
+            return self.draw_compact(speed, eta_opt, size.0);
+        }
 
-        let mut stdout = stdout();
+        let operation = title_text(&self.data, (size.0 as usize).saturating_sub(6));
 
-        use terminal_size::{terminal_size, Height, Width};
-        let (term_width, _) = terminal_size().unwrap_or((Width(80), Height(24)));
-        let box_width = term_width.0 as usize;
-        let right_border_col = (term_width.0).saturating_sub(2);
+        let mut out = term::out();
+
+        let BoxLayout {
+            box_width,
+            right_border_col,
+            bar_width,
+        } = BoxLayout::for_width(size.0);
+        // A decimal place widens the percentage; both bars give up the
+        // columns so their right ends stay aligned.
+        let bar_width = bar_width.saturating_sub(self.data.percent_width() - 4);
 
         let theme = &CONFIG.progress.theme;
         let layout = &CONFIG.progress.layout;
@@ -173,42 +485,45 @@ impl TuiProgress {
 
         let current_row = self.start_row;
 
-        let title_len = operation.len();
+        let title_len = display_width(&operation);
 
         execute!(
-            stdout,
+            out,
             MoveTo(0, current_row),
             SetForegroundColor(border_color)
         )?;
-        write!(stdout, "{}{} ", top_left, horizontal)?;
+        write!(out, "{}{} ", top_left, horizontal)?;
         execute!(
-            stdout,
+            out,
             SetForegroundColor(title_color),
             SetAttribute(Attribute::Bold)
         )?;
-        write!(stdout, "{}", operation)?;
+        write!(out, "{}", operation)?;
         execute!(
-            stdout,
+            out,
             SetAttribute(Attribute::Reset),
             SetForegroundColor(border_color)
         )?;
 
         let remaining_len = box_width.saturating_sub(title_len + 4);
         write!(
-            stdout,
+            out,
             " {}",
             horizontal
                 .to_string()
                 .repeat(remaining_len.saturating_sub(1))
         )?;
-        execute!(stdout, MoveTo(right_border_col, current_row))?;
-        write!(stdout, "{}", top_right)?;
-        execute!(stdout, Clear(ClearType::UntilNewLine))?;
+        execute!(out, MoveTo(right_border_col, current_row))?;
+        write!(out, "{}", top_right)?;
+        execute!(out, Clear(ClearType::UntilNewLine))?;
 
-        let bar_width = (box_width.saturating_sub(20)).max(20);
+        // Everything below the title is drawn relative to the row above the
+        // total bar, so the optional header just pushes it down.
+        let header_rows = self.header_rows();
+        let current_row = current_row + header_rows;
 
         let draw_line_content =
-            |out: &mut io::Stdout, row_offset: u16, content: &str| -> io::Result<()> {
+            |out: &mut term::Out, row_offset: u16, content: &str| -> io::Result<()> {
                 execute!(
                     out,
                     MoveTo(0, current_row + row_offset),
@@ -218,19 +533,7 @@ impl TuiProgress {
                 execute!(out, SetForegroundColor(text_color))?;
 
                 let available_width = box_width.saturating_sub(3);
-                let content_len = content.chars().count();
-                let display_content = if content_len > available_width {
-                    &content[..content.floor_char_boundary(available_width)]
-                } else {
-                    content
-                };
-
-                write!(out, "{}", display_content)?;
-
-                let padding = available_width.saturating_sub(display_content.chars().count());
-                if padding > 0 {
-                    write!(out, "{}", " ".repeat(padding))?;
-                }
+                write!(out, "{}", fit_to_width(content, available_width))?;
 
                 execute!(
                     out,
@@ -242,47 +545,41 @@ impl TuiProgress {
                 Ok(())
             };
 
+        if header_rows > 0 {
+            draw_line_content(&mut out, 0, &self.data.paths)?;
+        }
+
         execute!(
-            stdout,
+            out,
             MoveTo(0, current_row + 1),
             SetForegroundColor(border_color)
         )?;
-        write!(stdout, "{} Total:   [", vertical)?;
+        write!(out, "{} Total:   [", vertical)?;
 
-        let filled_len = (bar_width * total_progress as usize / 100).min(bar_width);
-        let empty_len = bar_width - filled_len;
-
-        for i in 0..filled_len {
-            let progress_fraction = i as f32 / bar_width as f32;
-            let color = get_gradient_color(&theme.bar_gradient, progress_fraction);
-            execute!(stdout, SetForegroundColor(color))?;
-            write!(stdout, "{}", theme.bar_complete_char)?;
-        }
+        let filled_len = ((bar_width as f64 * total_fraction) as usize).min(bar_width);
 
-        execute!(stdout, SetForegroundColor(parse_hex_color("#444444")))?;
-        write!(stdout, "{}", theme.bar_incomplete_char.repeat(empty_len))?;
+        write!(out, "{}", self.gradient.bar(bar_width, filled_len))?;
 
-        execute!(stdout, SetForegroundColor(text_color))?;
-        write!(stdout, "] {:>3}%", total_progress)?;
+        execute!(out, SetForegroundColor(text_color))?;
+        write!(out, "] {}", percent)?;
 
         execute!(
-            stdout,
+            out,
             MoveTo(right_border_col, current_row + 1),
             SetForegroundColor(border_color)
         )?;
-        write!(stdout, "{}", vertical)?;
-        execute!(stdout, Clear(ClearType::UntilNewLine))?;
+        write!(out, "{}", vertical)?;
+        execute!(out, Clear(ClearType::UntilNewLine))?;
 
         let eta_str = match eta_opt {
             Some(d) => format_eta(d.as_secs()),
             None => "--".to_string(),
         };
 
-        let speed_str = if speed > 0.0 {
-            format!("{}/s", format_bytes(speed * 1024.0 * 1024.0))
-        } else {
-            "-- /s".to_string()
-        };
+        let speed_str = self
+            .data
+            .stall_notice(Instant::now())
+            .unwrap_or_else(|| self.data.rate_text(speed));
         let details = if self.data.scanning {
             let dots = ".".repeat((self.data.elapsed().as_millis() / 500 % 4) as usize);
             format!(
@@ -294,22 +591,48 @@ impl TuiProgress {
             )
         } else {
             format!(
-                "Detail:  {} / {} | {} | ETA: {}",
+                "Detail:  {} / {} | {} | Elapsed: {} | ETA: {}",
                 format_bytes(self.data.current_bytes as f64),
-                format_bytes(self.data.total_bytes as f64),
+                self.data.total_text(format_bytes),
                 speed_str,
+                format_eta(self.data.elapsed().as_secs()),
                 eta_str
             )
         };
-        draw_line_content(&mut stdout, 2, &details)?;
+        draw_line_content(&mut out, 2, &details)?;
+
+        if self.sparkline {
+            let line = sparkline_text(
+                &self.data.speed_history,
+                speed,
+                box_width.saturating_sub(3 + "Speed:   ".len()),
+            );
+            draw_line_content(&mut out, 3, &format!("Speed:   {}", line))?;
+        }
+        // First row below the detail (and sparkline) lines.
+        let body = 3 + self.sparkline_rows();
 
         if self.data.parallel_total > 0 {
-            let workers_header = format!(
+            let rows = visible_workers(&self.data.workers, self.visible_worker_rows(size.1));
+            let mut workers_header = format!(
                 "Workers: {}/{}",
                 self.data.active_worker_count(),
                 self.data.parallel_total
             );
-            draw_line_content(&mut stdout, 3, &workers_header)?;
+            let hidden = self.data.parallel_total - rows.len();
+            if hidden > 0 {
+                workers_header.push_str(&format!(" (+{} not shown)", hidden));
+            }
+            if let Some(total) = self.data.items_total {
+                workers_header.push_str(&format!(
+                    " | Items: {} / {}",
+                    self.data.items_processed, total
+                ));
+            }
+            if let Some(verified) = self.data.verify_text() {
+                workers_header.push_str(&format!(" | {}", verified));
+            }
+            draw_line_content(&mut out, body, &workers_header)?;
 
             let num_width = if self.data.parallel_total >= 10 { 2 } else { 1 };
             let worker_bar_width = 20usize.min(box_width.saturating_sub(30));
@@ -318,173 +641,201 @@ impl TuiProgress {
                 .saturating_sub(worker_bar_width + fixed_chars)
                 .max(8);
 
-            for (i, worker) in self.data.workers.iter_mut().enumerate() {
-                let row_offset = 4 + i as u16;
+            for (row, &i) in rows.iter().enumerate() {
+                let worker = &mut self.data.workers[i];
+                let row_offset = body + 1 + row as u16;
                 if worker.active {
-                    let pct = if worker.file_size > 0 {
-                        (worker.progress as f64 / worker.file_size as f64 * 100.0).min(100.0) as u16
-                    } else {
-                        0
-                    };
+                    let pct = worker.percent();
                     let spd = worker.calculate_speed();
                     let spd_str = if spd > 0.0 {
                         format!("{}/s", format_bytes(spd * 1024.0 * 1024.0))
                     } else {
                         "-- /s".to_string()
                     };
-                    let display_name = if worker.file_name.chars().count() > name_max {
-                        let end = worker
-                            .file_name
-                            .floor_char_boundary(name_max.saturating_sub(3));
-                        format!("{}...", &worker.file_name[..end])
-                    } else {
-                        worker.file_name.clone()
-                    };
+                    let display_name =
+                        fit_to_width(&truncate_path(&worker.file_name, name_max), name_max);
                     let filled = (worker_bar_width * pct as usize / 100).min(worker_bar_width);
-                    let empty = worker_bar_width - filled;
 
                     execute!(
-                        stdout,
+                        out,
                         MoveTo(0, current_row + row_offset),
                         SetForegroundColor(border_color)
                     )?;
-                    write!(stdout, "{} ", vertical)?;
-                    execute!(stdout, SetForegroundColor(text_color))?;
+                    write!(out, "{} ", vertical)?;
+                    execute!(out, SetForegroundColor(text_color))?;
                     write!(
-                        stdout,
-                        "[{:>width$}] {:name_w$} [",
+                        out,
+                        "[{:>width$}] {} [",
                         i + 1,
                         display_name,
                         width = num_width,
-                        name_w = name_max
                     )?;
 
-                    for j in 0..filled {
-                        let frac = j as f32 / worker_bar_width as f32;
-                        let color = get_gradient_color(&theme.bar_gradient, frac);
-                        execute!(stdout, SetForegroundColor(color))?;
-                        write!(stdout, "{}", theme.bar_complete_char)?;
-                    }
-                    execute!(stdout, SetForegroundColor(parse_hex_color("#444444")))?;
-                    write!(stdout, "{}", theme.bar_incomplete_char.repeat(empty))?;
+                    write!(out, "{}", self.gradient.bar(worker_bar_width, filled))?;
 
-                    execute!(stdout, SetForegroundColor(text_color))?;
-                    write!(stdout, "] {:>3}% {}", pct, spd_str)?;
+                    execute!(out, SetForegroundColor(text_color))?;
+                    write!(out, "] {:>3}% {}", pct, spd_str)?;
 
                     let content_len =
                         num_width + 3 + name_max + 2 + worker_bar_width + 2 + 4 + 1 + spd_str.len();
                     let padding = box_width.saturating_sub(content_len + 3);
-                    write!(stdout, "{}", " ".repeat(padding))?;
+                    write!(out, "{}", " ".repeat(padding))?;
 
                     execute!(
-                        stdout,
+                        out,
                         MoveTo(right_border_col, current_row + row_offset),
                         SetForegroundColor(border_color)
                     )?;
-                    write!(stdout, "{}", vertical)?;
-                    execute!(stdout, Clear(ClearType::UntilNewLine))?;
+                    write!(out, "{}", vertical)?;
+                    execute!(out, Clear(ClearType::UntilNewLine))?;
                 } else {
                     let line = format!("[{:>width$}] idle", i + 1, width = num_width);
-                    draw_line_content(&mut stdout, row_offset, &line)?;
+                    draw_line_content(&mut out, row_offset, &line)?;
                 }
             }
-
-            let bottom_row = 4 + self.data.parallel_total as u16;
-            execute!(
-                stdout,
-                MoveTo(0, current_row + bottom_row),
-                SetForegroundColor(border_color)
-            )?;
-            write!(
-                stdout,
-                "{}{}{}",
-                bottom_left,
-                horizontal
-                    .to_string()
-                    .repeat(right_border_col.saturating_sub(1) as usize),
-                bottom_right
-            )?;
-            execute!(stdout, SetAttribute(Attribute::Reset))?;
-            execute!(stdout, Clear(ClearType::UntilNewLine))?;
-        } else {
-            let items_line = if let Some(total) = self.data.items_total {
+        } else if !self.data.is_single_file() {
+            let mut items_line = if let Some(total) = self.data.items_total {
                 format!("Items:   {} / {}", self.data.items_processed, total)
             } else {
                 String::new()
             };
-            draw_line_content(&mut stdout, 3, &items_line)?;
-
-            let file_info = format!("Current: {}", display_file);
-            let max_text_width = box_width.saturating_sub(4);
-            let display_file_info = if file_info.len() > max_text_width {
-                let truncated =
-                    &file_info[..file_info.floor_char_boundary(max_text_width.saturating_sub(3))];
-                format!("{truncated}...")
+            if let Some(verified) = self.data.verify_text() {
+                if !items_line.is_empty() {
+                    items_line.push_str(" | ");
+                }
+                items_line.push_str(&verified);
+            }
+            draw_line_content(&mut out, body, &items_line)?;
+
+            let file_speed = self.data.calculate_file_speed();
+            let speed_suffix = if file_speed > 0.0 {
+                format!(" — {}/s", format_bytes(file_speed * 1024.0 * 1024.0))
             } else {
-                file_info
+                String::new()
             };
-            draw_line_content(&mut stdout, 4, &display_file_info)?;
+            // The name gives way first so a long path never hides the speed.
+            let name_width = box_width
+                .saturating_sub(4 + "Current: ".len())
+                .saturating_sub(display_width(&speed_suffix));
+            let display_file_info = format!(
+                "Current: {}{}",
+                truncate_path(&display_file, name_width),
+                speed_suffix
+            );
+            draw_line_content(&mut out, body + 1, &display_file_info)?;
 
             execute!(
-                stdout,
-                MoveTo(0, current_row + 5),
+                out,
+                MoveTo(0, current_row + body + 2),
                 SetForegroundColor(border_color)
             )?;
-            write!(stdout, "{}          [", vertical)?;
+            write!(out, "{}          [", vertical)?;
             let filled_len = (bar_width * current_progress as usize / 100).min(bar_width);
-            let empty_len = bar_width - filled_len;
 
-            for i in 0..filled_len {
-                let progress_fraction = i as f32 / bar_width as f32;
-                let color = get_gradient_color(&theme.bar_gradient, progress_fraction);
-                execute!(stdout, SetForegroundColor(color))?;
-                write!(stdout, "{}", theme.bar_complete_char)?;
-            }
-            execute!(stdout, SetForegroundColor(parse_hex_color("#444444")))?;
-            write!(stdout, "{}", theme.bar_incomplete_char.repeat(empty_len))?;
+            write!(out, "{}", self.gradient.bar(bar_width, filled_len))?;
 
-            execute!(stdout, SetForegroundColor(text_color))?;
-            write!(stdout, "] {:>3}%", current_progress)?;
+            execute!(out, SetForegroundColor(text_color))?;
+            write!(
+                out,
+                "] {:>width$}%",
+                current_progress,
+                width = self.data.percent_width() - 1
+            )?;
 
             execute!(
-                stdout,
-                MoveTo(right_border_col, current_row + 5),
+                out,
+                MoveTo(right_border_col, current_row + body + 2),
                 SetForegroundColor(border_color)
             )?;
-            write!(stdout, "{}", vertical)?;
-            execute!(stdout, Clear(ClearType::UntilNewLine))?;
+            write!(out, "{}", vertical)?;
+            execute!(out, Clear(ClearType::UntilNewLine))?;
+        }
 
+        let bottom_row = self.total_lines() - 1;
+        if let Some(summary) = self.data.warning_summary() {
+            let row = self.start_row + bottom_row - 1;
+            execute!(out, MoveTo(0, row), SetForegroundColor(border_color))?;
+            write!(out, "{} ", vertical)?;
+            execute!(out, SetForegroundColor(Color::Yellow))?;
+            write!(
+                out,
+                "{}",
+                fit_to_width(&summary, box_width.saturating_sub(3))
+            )?;
             execute!(
-                stdout,
-                MoveTo(0, current_row + 6),
+                out,
+                MoveTo(right_border_col, row),
                 SetForegroundColor(border_color)
             )?;
-            write!(
-                stdout,
-                "{}{}{}",
-                bottom_left,
-                horizontal
-                    .to_string()
-                    .repeat(right_border_col.saturating_sub(1) as usize),
-                bottom_right
-            )?;
-            execute!(stdout, SetAttribute(Attribute::Reset))?;
-            execute!(stdout, Clear(ClearType::UntilNewLine))?;
+            write!(out, "{}", vertical)?;
+            execute!(out, Clear(ClearType::UntilNewLine))?;
         }
 
+        execute!(
+            out,
+            MoveTo(0, self.start_row + bottom_row),
+            SetForegroundColor(border_color)
+        )?;
+        write!(
+            out,
+            "{}{}{}",
+            bottom_left,
+            horizontal
+                .to_string()
+                .repeat(right_border_col.saturating_sub(1) as usize),
+            bottom_right
+        )?;
+        execute!(out, SetAttribute(Attribute::Reset))?;
+        execute!(out, Clear(ClearType::UntilNewLine))?;
+
         self.last_rendered_lines = self.total_lines();
-        stdout.flush()?;
+        self.data.mark_drawn(Instant::now());
+        self.suspend
+            .set_park_row(self.start_row + self.last_rendered_lines);
+        out.flush()?;
         Ok(())
     }
 }
 
+impl TuiProgress {
+    fn draw_compact(&mut self, speed: f64, eta: Option<Duration>, width: u16) -> io::Result<()> {
+        let text_color = parse_hex_color(&CONFIG.progress.theme.text_color);
+        // One column spare so a full-width line never triggers an autowrap.
+        let lines = compact_lines(&self.data, speed, eta, width.saturating_sub(1) as usize);
+        let mut out = term::out();
+        for (i, line) in lines.iter().enumerate() {
+            execute!(
+                out,
+                MoveTo(0, self.start_row + i as u16),
+                SetForegroundColor(text_color)
+            )?;
+            write!(out, "{}", line)?;
+            execute!(
+                out,
+                SetAttribute(Attribute::Reset),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+
+        self.last_rendered_lines = COMPACT_LINES;
+        self.data.mark_drawn(Instant::now());
+        self.suspend
+            .set_park_row(self.start_row + self.last_rendered_lines);
+        out.flush()
+    }
+}
+
+fn terminal_dimensions() -> (u16, u16) {
+    term::size().unwrap_or((80, 24))
+}
+
 impl Drop for TuiProgress {
     fn drop(&mut self) {
         if self.raw_mode_enabled && !self.finished {
             let _ = execute!(
-                stdout(),
+                term::out(),
                 Show,
-                MoveTo(0, self.start_row + self.total_lines())
+                MoveTo(0, self.start_row + self.last_rendered_lines)
             );
             let _ = disable_raw_mode();
         }
@@ -497,33 +848,39 @@ impl ProgressRenderer for TuiProgress {
         let _ = self.redraw();
     }
 
-    fn inc_items_processed(&mut self) {
-        self.data.items_processed += 1;
-        let _ = self.redraw();
+    fn inc_items_processed(&mut self, count: usize) {
+        self.data.items_processed += count;
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    fn add_verify_queued(&mut self, count: usize) {
+        self.data.verify_queued += count;
+    }
+
+    fn inc_verified(&mut self, count: usize) {
+        self.data.verified += count;
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
     }
 
     fn set_current_file(&mut self, file_name: &str, file_size: u64) {
-        self.data.current_file = file_name.to_string();
-        self.data.current_file_size = file_size;
-        self.data.current_file_progress = 0;
+        self.data.set_current_file(file_name, file_size);
         let _ = self.redraw();
     }
 
     fn inc_current(&mut self, delta: u64) {
-        self.data.current_bytes += delta;
-        self.data.current_file_progress += delta;
-        if self.data.current_bytes.is_multiple_of(1024 * 1024)
-            || self.data.current_bytes >= self.data.total_bytes
-        {
+        self.data.inc_current(delta);
+        if self.data.should_redraw(Instant::now()) {
             let _ = self.redraw();
         }
     }
 
     fn inc_skipped(&mut self, delta: u64) {
         self.data.inc_skipped(delta);
-        if self.data.current_bytes.is_multiple_of(1024 * 1024)
-            || self.data.current_bytes >= self.data.total_bytes
-        {
+        if self.data.should_redraw(Instant::now()) {
             let _ = self.redraw();
         }
     }
@@ -533,11 +890,25 @@ impl ProgressRenderer for TuiProgress {
         let _ = self.redraw();
     }
 
+    fn set_paths(&mut self, src: &str, dst: &str) {
+        self.data.set_paths(src, dst);
+        let _ = self.redraw();
+    }
+
     fn set_total_bytes(&mut self, total: u64) {
         self.data.total_bytes = total;
         let _ = self.redraw();
     }
 
+    fn set_size_unknown(&mut self, unknown: bool) {
+        self.data.size_unknown = unknown;
+        let _ = self.redraw();
+    }
+
+    fn set_codec_sizes(&mut self, raw: u64, compressed: u64) {
+        self.data.codec_sizes = Some((raw, compressed));
+    }
+
     fn set_scanning(&mut self, scanning: bool) {
         self.data.scanning = scanning;
         let _ = self.redraw();
@@ -562,37 +933,363 @@ impl ProgressRenderer for TuiProgress {
         let _ = self.redraw();
     }
 
-    fn tick(&mut self) {
+    fn worker_file(&mut self, slot: usize, file_name: &str, file_size: u64) {
+        self.data.worker_file(slot, file_name, file_size);
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    fn worker_progress(&mut self, slot: usize, delta: u64) {
+        self.data.worker_progress(slot, delta);
+        if self.data.should_redraw(Instant::now()) {
+            let _ = self.redraw();
+        }
+    }
+
+    // Raw mode is on, so the line needs its own carriage return. The box
+    // moves down by the rows the line took, scrolling if it would run off
+    // the bottom.
+    fn log(&mut self, line: &str) {
+        if !self.initialized || self.finished || self.suspend.is_suspended() {
+            let _ = writeln!(term::out(), "{}", line);
+            return;
+        }
+        let (term_width, term_height) = self.last_size;
+        let rows = display_width(line)
+            .max(1)
+            .div_ceil(usize::from(term_width.max(1))) as u16;
+        let mut out = term::out();
+        let _ = execute!(
+            out,
+            MoveTo(0, self.start_row),
+            Clear(ClearType::FromCursorDown)
+        );
+        let _ = write!(out, "{}\r\n", line);
+        let _ = out.flush();
+        self.start_row = (self.start_row + rows).min(term_height.saturating_sub(1));
+        let overflow = (self.start_row + self.total_lines()).saturating_sub(term_height);
+        if overflow > 0 {
+            let _ = execute!(out, ScrollUp(overflow));
+            self.start_row = self.start_row.saturating_sub(overflow);
+        }
+        self.last_rendered_lines = 0;
+        self.data.force_redraw();
         let _ = self.redraw();
     }
 
+    fn warn(&mut self, message: &str) {
+        self.data.warnings.push(message.to_string());
+        let _ = self.redraw();
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        self.data.sample_speed(now);
+        self.data.check_stall(now);
+        if self.data.should_redraw(now) {
+            let _ = self.redraw();
+        } else if self.initialized && !self.finished && !self.suspend.is_suspended() {
+            let _ = self.handle_input();
+        }
+    }
+
     fn finish(&mut self) -> io::Result<()> {
+        self.close(ProgressData::done_line)
+    }
+
+    fn finish_interrupted(&mut self, label: &str, partial: Option<&str>) -> io::Result<()> {
+        self.close(|data| data.interrupted_line(label, partial))
+    }
+}
+
+/// Raw mode swallows the SIGINT Ctrl+C would send, so it is re-raised for
+/// the runner's interrupt handler, the one place that cleans up and exits.
+#[cfg(unix)]
+fn interrupt_self() {
+    unsafe {
+        libc::raise(libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn interrupt_self() {
+    let _ = disable_raw_mode();
+    std::process::exit(130);
+}
+
+impl TuiProgress {
+    /// Restores the terminal and leaves `summary` plus the per-outcome
+    /// counts in the scrollback.
+    fn close(&mut self, summary: impl FnOnce(&ProgressData) -> String) -> io::Result<()> {
         if self.finished {
             return Ok(());
         }
 
-        let was_suspended = self.suspended.load(Ordering::SeqCst);
+        let was_suspended = self.suspend.is_suspended();
 
+        self.data.stop_clock();
         let _ = self.redraw();
 
         if self.raw_mode_enabled && !was_suspended {
-            let lines_used = self.total_lines();
-            execute!(stdout(), Show, MoveTo(0, self.start_row + lines_used))?;
+            // Whichever layout drew last decides where the box ends.
+            let lines_used = self.last_rendered_lines;
+            execute!(term::out(), Show, MoveTo(0, self.start_row + lines_used))?;
             disable_raw_mode()?;
             self.raw_mode_enabled = false;
-            println!();
+            writeln!(term::out())?;
         }
 
-        let elapsed = self.data.elapsed();
-        let avg_bps = self.data.average_bytes_per_sec().unwrap_or(0.0);
-        println!(
-            "Done: {} in {:.1}s | avg {}/s",
-            format_bytes(self.data.current_bytes as f64),
-            elapsed.as_secs_f64(),
-            format_bytes(avg_bps)
-        );
+        let mut out = term::out();
+        writeln!(out, "{}", summary(&self.data))?;
+        let stats = oplog::stats();
+        if !stats.is_empty() {
+            writeln!(out, "{}", format_operation_stats(&stats))?;
+        }
+        if crate::config::show_stats() {
+            writeln!(
+                out,
+                "{}",
+                format_transfer_stats(&self.data.transfer_stats())
+            )?;
+        }
+        out.flush()?;
+        print_warnings(&self.data.warnings);
 
         self.finished = true;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_tracks_terminal_width() {
+        let wide = BoxLayout::for_width(120);
+        assert_eq!(wide.box_width, 120);
+        assert_eq!(wide.right_border_col, 118);
+        assert_eq!(wide.bar_width, 100);
+
+        let narrow = BoxLayout::for_width(30);
+        assert_eq!(narrow.right_border_col, 28);
+        assert_eq!(narrow.bar_width, 20);
+
+        let degenerate = BoxLayout::for_width(1);
+        assert_eq!(degenerate.right_border_col, 0);
+    }
+
+    #[test]
+    fn worker_rows_fit_small_terminals() {
+        // Plenty of room: one row per job.
+        assert_eq!(worker_rows(1, 24), 1);
+        assert_eq!(worker_rows(4, 24), 4);
+        assert_eq!(worker_rows(16, 40), 16);
+        // Capped so the box (chrome + rows) never exceeds the terminal.
+        assert_eq!(worker_rows(16, 12), 7);
+        assert_eq!(worker_rows(4, 8), 3);
+        assert_eq!(worker_rows(16, 10), 5);
+        // Degenerate heights still show one worker.
+        assert_eq!(worker_rows(1, 3), 1);
+        assert_eq!(worker_rows(4, 5), 1);
+        assert_eq!(worker_rows(16, 0), 16);
+    }
+
+    #[test]
+    fn visible_workers_prefers_busy_slots_when_capped() {
+        let mut workers: Vec<WorkerState> = (0..4).map(|_| WorkerState::new()).collect();
+        workers[1].active = true;
+        workers[3].active = true;
+        assert_eq!(visible_workers(&workers, 4), vec![0, 1, 2, 3]);
+        assert_eq!(visible_workers(&workers, 3), vec![1, 3, 0]);
+        assert_eq!(visible_workers(&workers, 1), vec![1]);
+    }
+
+    #[test]
+    fn single_file_drops_the_file_bar() {
+        let mut tui = TuiProgress::new(1000).unwrap();
+        tui.last_size = (80, 24);
+        tui.data.operation_type = "Copying".to_string();
+        tui.data.set_current_file("big.iso", 1000);
+        assert_eq!(tui.total_lines(), SINGLE_FILE_LINES);
+        assert_eq!(title_text(&tui.data, 74), "Copying: big.iso");
+
+        tui.data.set_current_file("small.txt", 10);
+        assert_eq!(tui.total_lines(), 7);
+        assert_eq!(title_text(&tui.data, 74), "Copying Progress");
+    }
+
+    #[test]
+    fn paths_header_adds_one_row() {
+        let mut tui = TuiProgress::new(1000).unwrap();
+        tui.last_size = (80, 24);
+        tui.data.items_total = Some(5);
+        assert_eq!(tui.total_lines(), 7);
+        tui.data.set_paths("~/src", "/mnt/backup");
+        assert_eq!(tui.data.paths, "~/src → /mnt/backup");
+        assert_eq!(tui.total_lines(), 8);
+
+        tui.data.init_workers(16);
+        tui.last_size = (80, 12);
+        // The header's row comes out of the worker rows, not off-screen.
+        assert_eq!(tui.total_lines(), 12);
+    }
+
+    #[test]
+    fn single_file_title_is_truncated() {
+        let mut data = ProgressData::new(1000);
+        data.operation_type = "Copying".to_string();
+        data.set_current_file(&"long-name-".repeat(20), 1000);
+        assert!(display_width(&title_text(&data, 30)) <= 30);
+    }
+
+    #[test]
+    fn spark_levels_scale_to_peak() {
+        assert_eq!(spark_level(0.0, 100.0), 0);
+        assert_eq!(spark_level(100.0, 100.0), 7);
+        assert_eq!(spark_level(50.0, 100.0), 4);
+        assert_eq!(spark_level(7.0, 100.0), 0);
+        assert_eq!(spark_level(8.0, 100.0), 1);
+        assert_eq!(spark_level(250.0, 100.0), 7);
+        assert_eq!(spark_level(5.0, 0.0), 0);
+    }
+
+    #[test]
+    fn sparkline_handles_short_and_long_history() {
+        let history: VecDeque<f64> = [0.0, 100.0, 50.0].into_iter().collect();
+        let line = sparkline_text(&history, 50.0, 80);
+        assert!(line.starts_with("▁█▅ now 50.00 MiB/s"), "{line}");
+        assert!(line.ends_with("peak 100.00 MiB/s"), "{line}");
+
+        let history: VecDeque<f64> = (0..60).map(f64::from).collect();
+        let line = sparkline_text(&history, 59.0, 50);
+        assert_eq!(display_width(&line), 50);
+        assert!(line.starts_with("▆▆▆▇"), "{line}");
+    }
+
+    #[test]
+    fn warning_line_sits_above_the_bottom_border() {
+        let mut tui = TuiProgress::new(1000).unwrap();
+        tui.last_size = (80, 24);
+        tui.data.items_total = Some(5);
+        assert_eq!(tui.total_lines(), 7);
+        tui.data
+            .warnings
+            .push("a/b: could not preserve xattr".to_string());
+        tui.data.warnings.push("fifo: skipped".to_string());
+        assert_eq!(tui.total_lines(), 8);
+        assert_eq!(
+            fit_to_width(&tui.data.warning_summary().unwrap(), 30),
+            "⚠ 2 warnings — fifo: skipped  "
+        );
+        assert_eq!(
+            fit_to_width(&tui.data.warning_summary().unwrap(), 20),
+            "⚠ 2 warnings — fi..."
+        );
+    }
+
+    #[test]
+    fn sparkline_adds_a_row() {
+        let mut tui = TuiProgress::new(1000).unwrap();
+        tui.last_size = (80, 24);
+        tui.data.items_total = Some(5);
+        tui.sparkline = false;
+        assert_eq!(tui.total_lines(), 7);
+        tui.sparkline = true;
+        assert_eq!(tui.total_lines(), 8);
+    }
+
+    fn compact_sample() -> ProgressData {
+        let gib = 1024 * 1024 * 1024;
+        let mut data = ProgressData::new(29 * gib / 10);
+        data.operation_type = "Copying".to_string();
+        data.current_bytes = 12 * gib / 10;
+        data.set_current_file("current/file/name.ext", 100);
+        data.current_file_progress = 17;
+        data
+    }
+
+    #[test]
+    fn compact_layout_threshold() {
+        assert!(is_compact(40));
+        assert!(is_compact(59));
+        assert!(!is_compact(60));
+        assert!(!is_compact(120));
+        assert!(!is_compact(0));
+    }
+
+    #[test]
+    fn compact_lines_snapshots() {
+        let data = compact_sample();
+        let eta = Some(Duration::from_secs(42));
+
+        assert_eq!(
+            compact_lines(&data, 38.0, eta, 40),
+            [
+                "Copy  41% [=>    ] 1.2G/2.9G 38M/s 00:42".to_string(),
+                "→ current/file/name.ext 17%".to_string(),
+            ]
+        );
+        assert_eq!(
+            compact_lines(&data, 38.0, eta, 60),
+            [
+                "Copy  41% [=========>                ] 1.2G/2.9G 38M/s 00:42".to_string(),
+                "→ current/file/name.ext 17%".to_string(),
+            ]
+        );
+        let wide = compact_lines(&data, 0.0, None, 120);
+        assert_eq!(wide[0].len(), 120);
+        assert!(wide[0].ends_with("] 1.2G/2.9G --/s --:--"), "{}", wide[0]);
+    }
+
+    #[test]
+    fn compact_lines_fit_tiny_widths() {
+        let mut data = compact_sample();
+        data.set_current_file(&"深い/ディレクトリ/".repeat(10), 100);
+        for width in [10, 20, 30, 40, 59] {
+            for line in compact_lines(&data, 38.0, None, width) {
+                assert!(display_width(&line) <= width, "{width}: {line}");
+            }
+        }
+    }
+
+    #[test]
+    fn anchor_row_moves_box_up_only_when_it_no_longer_fits() {
+        assert_eq!(anchor_row(10, 7, 40), 10);
+        assert_eq!(anchor_row(30, 7, 40), 30);
+        assert_eq!(anchor_row(30, 7, 24), 17);
+        assert_eq!(anchor_row(3, 7, 5), 0);
+    }
+
+    #[test]
+    fn bar_string_snapshot() {
+        let colors = [
+            Color::Rgb { r: 1, g: 2, b: 3 },
+            Color::Rgb { r: 1, g: 2, b: 3 },
+            Color::Rgb { r: 4, g: 5, b: 6 },
+            Color::Rgb { r: 7, g: 8, b: 9 },
+            Color::Rgb { r: 7, g: 8, b: 9 },
+        ];
+        assert_eq!(
+            bar_string(&colors, 3, "█", "░"),
+            "\x1b[38;2;1;2;3m██\x1b[38;2;4;5;6m█\x1b[38;2;68;68;68m░░"
+        );
+        assert_eq!(bar_string(&colors, 0, "█", "░"), "\x1b[38;2;68;68;68m░░░░░");
+    }
+
+    #[test]
+    fn bar_string_skips_repeated_colors() {
+        // Per-cell emission cost one escape per filled cell plus the grey.
+        let before = 151;
+
+        let gradient = ["#CABBE9".to_string(), "#7E6EAC".to_string()];
+        let bar = bar_string(&gradient_colors(&gradient, 150), 150, "█", "░");
+        assert_eq!(bar.matches('█').count(), 150);
+        assert!(bar.matches("\x1b[").count() < before);
+
+        let solid = ["#CABBE9".to_string()];
+        let bar = bar_string(&gradient_colors(&solid, 150), 150, "█", "░");
+        assert_eq!(bar.matches("\x1b[").count(), 2);
+    }
+}
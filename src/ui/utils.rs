@@ -1,4 +1,7 @@
+use crate::core::oplog::{OperationStats, TransferStats};
 use crossterm::style::Color;
+use std::path::{Path, PathBuf};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub fn format_bytes(bytes: f64) -> String {
     const KB: f64 = 1024.0;
@@ -19,6 +22,35 @@ pub fn format_bytes(bytes: f64) -> String {
     }
 }
 
+/// Terse sizes for cramped layouts: `"512B"`, `"1.2G"`, `"38M"`.
+pub fn format_bytes_short(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+/// `1234567` → `"1,234,567"`.
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
 pub fn format_eta(seconds: u64) -> String {
     let hours = seconds / 3600;
     let minutes = (seconds % 3600) / 60;
@@ -30,6 +62,69 @@ pub fn format_eta(seconds: u64) -> String {
     }
 }
 
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Shortens `s` to at most `max` terminal columns, ending in `...` when
+/// anything was cut. Wide (CJK, emoji) characters occupy two columns, so
+/// the result can be one column short of `max`.
+pub fn truncate_to_width(s: &str, max: usize) -> String {
+    if display_width(s) <= max {
+        return s.to_string();
+    }
+    let budget = max.saturating_sub(3);
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str(&"..."[..max.min(3)]);
+    out
+}
+
+/// Shortens a relative path to at most `max` columns by dropping whole
+/// middle components (`frontend/…/routes/index.js`), keeping the file name
+/// intact where possible. Falls back to `truncate_to_width` when even the
+/// first and last components don't fit.
+pub fn truncate_path(path: &str, max: usize) -> String {
+    if display_width(path) <= max {
+        return path.to_string();
+    }
+    let sep = std::path::MAIN_SEPARATOR;
+    let parts: Vec<&str> = path.split(sep).collect();
+    if parts.len() > 2 {
+        let head = parts[0];
+        let fits = |start: usize| {
+            let tail = parts[start..].join(&sep.to_string());
+            display_width(head) + 3 + display_width(&tail) <= max
+        };
+        let mut start = parts.len() - 1;
+        if fits(start) {
+            while start > 2 && fits(start - 1) {
+                start -= 1;
+            }
+            let tail = parts[start..].join(&sep.to_string());
+            return format!("{head}{sep}…{sep}{tail}");
+        }
+    }
+    truncate_to_width(path, max)
+}
+
+/// `truncate_to_width`, then right-padded with spaces to exactly `width`
+/// columns so fixed-position borders after it stay aligned.
+pub fn fit_to_width(s: &str, width: usize) -> String {
+    let mut out = truncate_to_width(s, width);
+    let pad = width.saturating_sub(display_width(&out));
+    out.push_str(&" ".repeat(pad));
+    out
+}
+
 pub fn parse_hex_color(hex: &str) -> Color {
     if hex.starts_with('#') && hex.len() == 7 {
         let r = u8::from_str_radix(&hex[1..3], 16).unwrap_or(255);
@@ -97,6 +192,112 @@ pub fn get_gradient_color(colors: &[String], progress: f32) -> Color {
     interpolate_color(c1, c2, t)
 }
 
+/// `$HOME/src/app` → `~/src/app`; other paths are shown as given.
+pub fn display_path(path: &Path) -> String {
+    let home = directories::UserDirs::new().map(|u| u.home_dir().to_path_buf());
+    abbreviate_home(path, home.as_deref())
+}
+
+fn abbreviate_home(path: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|h| path.strip_prefix(h).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// `a, b, +3 more`: the first two paths, then a count of the rest.
+pub fn summarize_paths(paths: &[PathBuf]) -> String {
+    const SHOWN: usize = 2;
+    let mut out = paths
+        .iter()
+        .take(SHOWN)
+        .map(|p| display_path(p))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if paths.len() > SHOWN {
+        out.push_str(&format!(", +{} more", paths.len() - SHOWN));
+    }
+    out
+}
+
+/// "Files: 1,204 created, 96 skipped, 3 appended, 0 failed, 2 warnings" —
+/// zero counts are left out except `failed` and the warnings, always shown.
+pub fn format_operation_stats(stats: &OperationStats) -> String {
+    let mut parts: Vec<String> = [
+        (stats.created, "created"),
+        (stats.overwritten, "overwritten"),
+        (stats.skipped, "skipped"),
+        (stats.appended, "appended"),
+        (stats.moved, "moved"),
+        (stats.removed, "removed"),
+        (stats.dirs_created, "dirs created"),
+    ]
+    .iter()
+    .filter(|(n, _)| *n > 0)
+    .map(|(n, label)| format!("{} {}", format_count(*n), label))
+    .collect();
+    parts.push(format!("{} failed", format_count(stats.failed)));
+    parts.push(format!(
+        "{} {}",
+        format_count(stats.warnings),
+        if stats.warnings == 1 {
+            "warning"
+        } else {
+            "warnings"
+        }
+    ));
+    format!("Files: {}", parts.join(", "))
+}
+
+/// The `--stats` block, one aligned row per count the operation has:
+///
+/// ```text
+/// Files considered: 4
+/// Created:          3
+/// Total file size:  6.00 KiB (6,144 bytes)
+/// Transferred:      4.00 KiB (4,096 bytes)
+///   literal:        2.00 KiB (2,048 bytes)
+/// Elapsed:          0.2s, avg 20.00 KiB/s
+/// ```
+pub fn format_transfer_stats(stats: &TransferStats) -> String {
+    let size = |n: u64| format!("{} ({} bytes)", format_bytes(n as f64), format_count(n));
+    let rows: Vec<(&str, Option<String>)> = vec![
+        (
+            "Files considered",
+            Some(format_count(stats.files_considered)),
+        ),
+        ("Created", stats.created.map(format_count)),
+        ("Updated", stats.updated.map(format_count)),
+        ("Moved", stats.moved.map(format_count)),
+        ("Skipped", Some(format_count(stats.skipped))),
+        ("Deleted", stats.deleted.map(format_count)),
+        ("Metadata only", stats.metadata_only.map(format_count)),
+        ("Total file size", Some(size(stats.total_size))),
+        ("Transferred", stats.transferred_bytes.map(size)),
+        ("  literal", stats.literal_bytes.map(size)),
+        ("  reflinked", stats.reflinked_bytes.map(size)),
+        ("  hardlinked", stats.hardlinked_bytes.map(size)),
+        (
+            "Elapsed",
+            Some(format!(
+                "{:.1}s, avg {}/s",
+                stats.elapsed.as_secs_f64(),
+                format_bytes(stats.bytes_per_sec)
+            )),
+        ),
+    ];
+    let rows: Vec<_> = rows
+        .into_iter()
+        .filter_map(|(label, value)| Some((label, value?)))
+        .collect();
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 2;
+    rows.iter()
+        .map(|(label, value)| format!("{:<width$}{}", format!("{}:", label), value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,6 +327,128 @@ mod tests {
         assert_eq!(format_bytes(2.5 * 1024.0 * 1024.0 * 1024.0), "2.50 GiB");
     }
 
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(184_233), "184,233");
+        assert_eq!(format_count(12_345_678), "12,345,678");
+    }
+
+    #[test]
+    fn test_format_bytes_short() {
+        assert_eq!(format_bytes_short(512.0), "512B");
+        assert_eq!(format_bytes_short(1.2 * 1024.0 * 1024.0 * 1024.0), "1.2G");
+        assert_eq!(format_bytes_short(38.0 * 1024.0 * 1024.0), "38M");
+        assert_eq!(
+            format_bytes_short(2048.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+            "2048T"
+        );
+    }
+
+    #[test]
+    fn test_format_operation_stats() {
+        let stats = OperationStats {
+            created: 1204,
+            skipped: 96,
+            appended: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_operation_stats(&stats),
+            "Files: 1,204 created, 96 skipped, 3 appended, 0 failed, 0 warnings"
+        );
+        let warned = OperationStats {
+            created: 2,
+            warnings: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_operation_stats(&warned),
+            "Files: 2 created, 0 failed, 1 warning"
+        );
+        assert!(!stats.is_empty());
+        assert!(OperationStats::default().is_empty());
+    }
+
+    #[test]
+    fn test_format_transfer_stats() {
+        let copy = TransferStats {
+            files_considered: 1204,
+            created: Some(1000),
+            updated: Some(4),
+            skipped: 200,
+            deleted: Some(0),
+            metadata_only: Some(2),
+            total_size: 3 << 20,
+            transferred_bytes: Some(2 << 20),
+            literal_bytes: Some(1 << 20),
+            reflinked_bytes: Some(1 << 20),
+            hardlinked_bytes: Some(0),
+            elapsed: std::time::Duration::from_millis(2500),
+            bytes_per_sec: 1024.0 * 1024.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_transfer_stats(&copy),
+            "Files considered: 1,204\n\
+             Created:          1,000\n\
+             Updated:          4\n\
+             Skipped:          200\n\
+             Deleted:          0\n\
+             Metadata only:    2\n\
+             Total file size:  3.00 MiB (3,145,728 bytes)\n\
+             Transferred:      2.00 MiB (2,097,152 bytes)\n  \
+             literal:        1.00 MiB (1,048,576 bytes)\n  \
+             reflinked:      1.00 MiB (1,048,576 bytes)\n  \
+             hardlinked:     0 B (0 bytes)\n\
+             Elapsed:          2.5s, avg 1.00 MiB/s"
+        );
+
+        let remove = TransferStats {
+            files_considered: 3,
+            skipped: 1,
+            deleted: Some(2),
+            total_size: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_transfer_stats(&remove),
+            "Files considered: 3\n\
+             Skipped:          1\n\
+             Deleted:          2\n\
+             Total file size:  10 B (10 bytes)\n\
+             Elapsed:          0.0s, avg 0 B/s"
+        );
+    }
+
+    #[test]
+    fn test_abbreviate_home() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            abbreviate_home(Path::new("/home/me/src/app"), Some(home)),
+            "~/src/app"
+        );
+        assert_eq!(abbreviate_home(Path::new("/home/me"), Some(home)), "~");
+        assert_eq!(
+            abbreviate_home(Path::new("/home/meow/x"), Some(home)),
+            "/home/meow/x"
+        );
+        assert_eq!(abbreviate_home(Path::new("rel/x"), None), "rel/x");
+    }
+
+    #[test]
+    fn test_summarize_paths() {
+        let paths: Vec<PathBuf> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(summarize_paths(&paths[..1]), "a");
+        assert_eq!(summarize_paths(&paths[..2]), "a, b");
+        assert_eq!(summarize_paths(&paths), "a, b, +3 more");
+    }
+
     #[test]
     fn test_format_eta_seconds_only() {
         assert_eq!(format_eta(45), "00:45");
@@ -146,6 +469,63 @@ mod tests {
         assert_eq!(format_eta(0), "00:00");
     }
 
+    #[test]
+    fn test_format_eta_boundaries() {
+        assert_eq!(format_eta(59), "00:59");
+        assert_eq!(format_eta(60), "01:00");
+        assert_eq!(format_eta(3599), "59:59");
+        assert_eq!(format_eta(3600), "01:00:00");
+        assert_eq!(format_eta(100 * 3600), "100:00:00");
+    }
+
+    #[test]
+    fn test_truncate_to_width_ascii() {
+        assert_eq!(truncate_to_width("short.txt", 20), "short.txt");
+        assert_eq!(truncate_to_width("a_very_long_name.bin", 10), "a_very_...");
+        assert_eq!(truncate_to_width("abcdef", 2), "..");
+    }
+
+    #[test]
+    fn test_truncate_to_width_cjk_and_emoji() {
+        let name = "日本語のとても長いファイル名です.txt";
+        for max in [5, 8, 11, 16, 30] {
+            let out = truncate_to_width(name, max);
+            assert!(display_width(&out) <= max, "{max}: {out}");
+            assert!(out.ends_with("..."));
+        }
+        assert_eq!(truncate_to_width("日本語日本語", 8), "日本...");
+        assert_eq!(truncate_to_width("日本語日本語", 9), "日本語...");
+
+        let emoji = "🎉🎉🎉🎉🎉🎉🎉🎉-party.png";
+        for max in [4, 7, 10, 13] {
+            let out = truncate_to_width(emoji, max);
+            assert!(display_width(&out) <= max, "{max}: {out}");
+        }
+    }
+
+    #[test]
+    fn test_truncate_path_keeps_both_ends() {
+        let path = "frontend/app/routes/index.js";
+        assert_eq!(truncate_path(path, 40), path);
+        assert_eq!(truncate_path(path, 26), "frontend/…/routes/index.js");
+        assert_eq!(truncate_path(path, 20), "frontend/…/index.js");
+        assert_eq!(truncate_path(path, 10), "fronten...");
+        assert_eq!(truncate_path("a/b", 2), "..");
+    }
+
+    #[test]
+    fn test_fit_to_width_pads_exactly() {
+        for width in [6, 9, 12, 40] {
+            for s in ["plain", "日本語のファイル名.txt", "café-🎉-naïve.md"] {
+                assert_eq!(
+                    display_width(&fit_to_width(s, width)),
+                    width,
+                    "{s} @ {width}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_parse_hex_color_valid() {
         match parse_hex_color("#FF0000") {
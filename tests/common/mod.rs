@@ -3,7 +3,7 @@
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
+use std::process::{Command, Stdio};
 
 pub fn bcmr_bin() -> PathBuf {
     let exe = std::env::current_exe().unwrap();
@@ -11,6 +11,17 @@ pub fn bcmr_bin() -> PathBuf {
     exe.parent().unwrap().parent().unwrap().join(bin_name)
 }
 
+/// Where e2e runs of bcmr keep their state, the undo journal among it:
+/// Cargo's scratch directory for the tests, not the developer's own.
+pub fn scratch_state() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("state")
+}
+
+/// Points `cmd`, or a shell that ends up running bcmr, at `scratch_state`.
+pub fn isolate(cmd: &mut Command) -> &mut Command {
+    cmd.env("XDG_STATE_HOME", scratch_state())
+}
+
 pub struct ServeChild {
     pub child: tokio::process::Child,
     pub stdin: tokio::process::ChildStdin,
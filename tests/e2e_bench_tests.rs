@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn e2e_bench_json_reports_each_run_and_cleans_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let (code, stdout, stderr) = run_bcmr(&[
+        "bench",
+        "--json",
+        "--size",
+        "2M",
+        "--files",
+        "2",
+        "--engines",
+        "stream,kernel",
+        "--dir",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+
+    let json: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(json["command"], "bench");
+    assert_eq!(json["size"], 2 * 1024 * 1024);
+    let runs = json["runs"].as_array().unwrap();
+    let names: Vec<(String, String)> = runs
+        .iter()
+        .map(|r| {
+            (
+                r["data"].as_str().unwrap().to_string(),
+                r["engine"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("random", "stream"),
+            ("random", "kernel"),
+            ("sparse", "stream"),
+            ("sparse", "kernel")
+        ]
+        .map(|(d, e)| (d.to_string(), e.to_string()))
+    );
+    assert!(runs
+        .iter()
+        .all(|r| r["bytes_per_sec"].as_f64().unwrap() > 0.0));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn e2e_bench_refuses_without_the_space() {
+    let dir = tempfile::tempdir().unwrap();
+    let (code, _, stderr) = run_bcmr(&[
+        "bench",
+        "--size",
+        "1000000T",
+        "--dir",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("Not enough free space"), "stderr: {stderr}");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    let (code, _, _) = run_bcmr(&["bench", "--size", "lots"]);
+    assert_eq!(code, Some(2));
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use bcmr::core::checksum::{self, Algorithm};
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+const FIXTURE: &[&str] = &[
+    "README",
+    "a b.txt",
+    "docs/guide.md",
+    "docs/img/logo.png",
+    "empty",
+    "src-old/main.rs",
+    "src/main.rs",
+];
+
+fn make_fixture(root: &Path) {
+    for (i, rel) in FIXTURE.iter().enumerate() {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let data: Vec<u8> = (0..i * 7919).map(|n| (n * 31 + i) as u8).collect();
+        fs::write(path, data).unwrap();
+    }
+}
+
+/// What `cd root && LC_ALL=C sort`-ordered `sha256sum` prints, or `None`
+/// where the tool is not installed.
+fn sha256sum_of(root: &Path) -> Option<String> {
+    let mut names: Vec<&str> = FIXTURE.to_vec();
+    names.sort();
+    let output = Command::new("sha256sum")
+        .args(&names)
+        .current_dir(root)
+        .output()
+        .ok()?;
+    assert!(output.status.success());
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn e2e_checksum_matches_sha256sum() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let Some(expected) = sha256sum_of(dir.path()) else {
+        eprintln!("sha256sum not installed; skipping");
+        return;
+    };
+
+    let (ok, stdout, stderr) = run_bcmr(&["checksum", "-r", dir.path().to_str().unwrap()]);
+    assert!(ok, "stderr: {stderr}");
+    assert_eq!(stdout, format!("# algorithm: sha256\n{expected}"));
+}
+
+#[test]
+fn e2e_checksum_output_file_leaves_itself_out() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let manifest = dir.path().join("MANIFEST.sha256");
+
+    let (ok, stdout, stderr) = run_bcmr(&[
+        "checksum",
+        "-r",
+        "-j",
+        "3",
+        dir.path().to_str().unwrap(),
+        "-o",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(ok, "stderr: {stderr}");
+    assert!(stdout.is_empty());
+    let written = fs::read_to_string(&manifest).unwrap();
+    assert!(!written.contains("MANIFEST"), "{written}");
+    if let Some(expected) = sha256sum_of(dir.path()) {
+        assert_eq!(written, format!("# algorithm: sha256\n{expected}"));
+    }
+
+    // The manifest checks out with the tool it imitates.
+    if let Ok(check) = Command::new("sha256sum")
+        .args(["--strict", "-c", "MANIFEST.sha256"])
+        .current_dir(dir.path())
+        .output()
+    {
+        assert!(check.status.success(), "{:?}", check);
+    }
+}
+
+#[test]
+fn e2e_checksum_other_algorithms() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+
+    for (name, algo) in [("blake3", Algorithm::Blake3), ("xxh3", Algorithm::Xxh3)] {
+        let (ok, stdout, stderr) = run_bcmr(&[
+            "checksum",
+            "-r",
+            "--algo",
+            name,
+            dir.path().to_str().unwrap(),
+        ]);
+        assert!(ok, "stderr: {stderr}");
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), FIXTURE.len() + 1);
+        assert_eq!(lines[0], format!("# algorithm: {name}"));
+        let expected =
+            checksum::hash_file(&dir.path().join("docs/guide.md"), algo, |_| {}).unwrap();
+        assert!(
+            lines.contains(&format!("{}  docs/guide.md", expected).as_str()),
+            "{name}: {stdout}"
+        );
+    }
+}
+
+#[test]
+fn e2e_checksum_directory_needs_recursive() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+
+    let (ok, _, stderr) = run_bcmr(&["checksum", dir.path().to_str().unwrap()]);
+    assert!(!ok);
+    assert!(stderr.contains("-r"), "{stderr}");
+
+    let file = dir.path().join("README");
+    let (ok, stdout, _) = run_bcmr(&["checksum", file.to_str().unwrap()]);
+    assert!(ok);
+    assert!(stdout.ends_with("  README\n"), "{stdout}");
+}
+
+fn check(manifest: &Path, extra: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(bcmr_bin())
+        .args(["checksum", "-c", manifest.to_str().unwrap()])
+        .args(extra)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+    )
+}
+
+fn write_manifest(root: &Path) -> PathBuf {
+    let manifest = root.join("SHA256SUMS");
+    let (ok, _, stderr) = run_bcmr(&[
+        "checksum",
+        "-r",
+        root.to_str().unwrap(),
+        "-o",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(ok, "stderr: {stderr}");
+    manifest
+}
+
+#[test]
+fn e2e_checksum_check_good_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let manifest = write_manifest(dir.path());
+
+    let (code, stdout) = check(&manifest, &[]);
+    assert_eq!(code, Some(0), "{stdout}");
+    assert!(stdout.contains("docs/img/logo.png: OK\n"), "{stdout}");
+    assert!(stdout.contains("7 OK, 0 FAILED, 0 MISSING"), "{stdout}");
+
+    let (code, stdout) = check(&manifest, &["--quiet"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.is_empty(), "{stdout}");
+
+    // Written on Windows, in binary mode, and checked from elsewhere.
+    let other = tempfile::tempdir().unwrap();
+    let crlf = other.path().join("sums.txt");
+    let text = fs::read_to_string(&manifest)
+        .unwrap()
+        .replace("  ", " *")
+        .replace('\n', "\r\n");
+    fs::write(&crlf, text).unwrap();
+    let (code, stdout) = check(&crlf, &["--root", dir.path().to_str().unwrap()]);
+    assert_eq!(code, Some(0), "{stdout}");
+}
+
+#[test]
+fn e2e_checksum_check_reports_a_corrupted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let manifest = write_manifest(dir.path());
+    let victim = dir.path().join("docs/guide.md");
+    let mut data = fs::read(&victim).unwrap();
+    data[100] ^= 0x80;
+    fs::write(&victim, data).unwrap();
+
+    let (code, stdout) = check(&manifest, &["-q"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "docs/guide.md: FAILED\n");
+}
+
+#[test]
+fn e2e_checksum_check_reports_a_deleted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let manifest = write_manifest(dir.path());
+    fs::remove_file(dir.path().join("src/main.rs")).unwrap();
+
+    let (code, stdout) = check(&manifest, &[]);
+    assert_eq!(code, Some(2));
+    assert!(stdout.contains("src/main.rs: MISSING\n"), "{stdout}");
+    assert!(stdout.contains("6 OK, 0 FAILED, 1 MISSING"), "{stdout}");
+
+    let (code, stdout) = check(&manifest, &["--ignore-missing"]);
+    assert_eq!(code, Some(0), "{stdout}");
+    assert!(!stdout.contains("src/main.rs"), "{stdout}");
+}
+
+#[test]
+fn e2e_checksum_check_takes_the_algorithm_from_the_header() {
+    let dir = tempfile::tempdir().unwrap();
+    make_fixture(dir.path());
+    let manifest = dir.path().join("B3SUMS");
+    let (ok, _, stderr) = run_bcmr(&[
+        "checksum",
+        "-r",
+        "--algo",
+        "blake3",
+        dir.path().to_str().unwrap(),
+        "-o",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(ok, "stderr: {stderr}");
+
+    let (code, stdout) = check(&manifest, &[]);
+    assert_eq!(code, Some(0), "{stdout}");
+    assert!(stdout.contains("7 OK, 0 FAILED"), "{stdout}");
+
+    // Overriding it hashes with the wrong algorithm.
+    let (code, stdout) = check(&manifest, &["--algo", "sha256", "-q"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout.lines().count(), FIXTURE.len(), "{stdout}");
+}
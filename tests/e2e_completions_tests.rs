@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+mod common;
+
+use common::isolate;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn e2e_completions_name_the_newer_subcommands() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let (code, stdout, stderr) = run_bcmr(&["completions", shell]);
+        assert_eq!(code, Some(0), "{shell}: {stderr}");
+        for sub in ["sync", "du", "list", "verify", "checksum"] {
+            assert!(stdout.contains(sub), "{shell}: no '{sub}'");
+        }
+    }
+}
+
+#[test]
+fn e2e_init_loads_completions_unless_told_not_to() {
+    let (code, stdout, _) = run_bcmr(&["init", "zsh", "--cmd", "b"]);
+    assert_eq!(code, Some(0));
+    let alias = stdout.find("function bcp()").expect(&stdout);
+    let bootstrap = stdout
+        .find("source <(command bcmr completions zsh)")
+        .expect(&stdout);
+    let compdef = stdout.find("compdef _bcp bcp").expect(&stdout);
+    assert!(alias < bootstrap && bootstrap < compdef);
+
+    let (code, stdout, _) = run_bcmr(&["init", "bash", "--cmd", "b", "--no-completions"]);
+    assert_eq!(code, Some(0));
+    assert!(!stdout.contains("completions bash"), "{stdout}");
+    assert!(!stdout.contains("complete -F"), "{stdout}");
+
+    let (code, stdout, _) = run_bcmr(&["init", "fish", "--no-completions", "--completions"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("completions fish | source"), "{stdout}");
+
+    let (code, _, stderr) = run_bcmr(&["copy", "--reflink=sometimes", "a", "b"]);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("possible values"), "{stderr}");
+}
+
+#[test]
+fn e2e_init_refuses_unusable_names_before_printing() {
+    let (code, stdout, stderr) = run_bcmr(&["init", "bash", "--cmd", "my cmd"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.is_empty(), "{stdout}");
+    assert!(
+        stderr.contains("--cmd 'my cmd' cannot be part of a bash command name"),
+        "{stderr}"
+    );
+
+    let (code, stdout, _) = run_bcmr(&["init", "zsh", "--prefix", "9"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.is_empty(), "{stdout}");
+}
+
+#[test]
+fn e2e_init_nu_refuses_completions() {
+    let (code, stdout, stderr) = run_bcmr(&["init", "nu", "--cmd", "b", "--completions"]);
+    assert_ne!(code, Some(0));
+    assert!(stdout.is_empty(), "{stdout}");
+    assert!(
+        stderr.contains("--completions is not supported for nu"),
+        "{stderr}"
+    );
+}
+
+/// Sources the generated script in a real nu and copies through `bcp`.
+/// Skipped where nu is not installed.
+#[test]
+fn e2e_init_nu_script_runs_in_nu() {
+    if Command::new("nu").arg("--version").output().is_err() {
+        eprintln!("nu not found; skipping");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let bin_dir = bcmr_bin().parent().unwrap().display().to_string();
+    let (code, script, stderr) = run_bcmr(&["init", "nu", "--cmd", "b", "--path", &bin_dir]);
+    assert_eq!(code, Some(0), "{stderr}");
+    let init = dir.path().join("init.nu");
+    std::fs::write(&init, script).unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"data").unwrap();
+
+    let output = Command::new("nu")
+        .arg("--no-config-file")
+        .arg("--commands")
+        .arg(format!(
+            "source '{}'; cd '{}'; bcp a.txt b.txt",
+            init.display(),
+            dir.path().display()
+        ))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"data");
+}
+
+/// The functions find bcmr on PATH when called, so they survive the binary
+/// moving after the script was generated.
+#[cfg(unix)]
+#[test]
+fn e2e_init_functions_follow_a_moved_binary() {
+    // Beside the real binary, so a hard link stays on one filesystem.
+    let dir = tempfile::tempdir_in(bcmr_bin().parent().unwrap()).unwrap();
+    let old = dir.path().join("old");
+    std::fs::create_dir(&old).unwrap();
+    std::fs::hard_link(bcmr_bin(), old.join("bcmr")).unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"data").unwrap();
+
+    let generate = |extra: &[&str]| {
+        let output = Command::new(old.join("bcmr"))
+            .args(["init", "bash", "--cmd", "b", "--always"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let script = generate(&[]);
+    let pinned = generate(&["--absolute-path"]);
+    let new = dir.path().join("new");
+    std::fs::rename(&old, &new).unwrap();
+
+    let run = |script: &str, dst: &str| {
+        isolate(&mut Command::new("bash"))
+            .arg("-c")
+            .arg(format!("{}\nbcp a.txt {}", script, dst))
+            .current_dir(dir.path())
+            .env("PATH", format!("{}:/usr/bin:/bin", new.display()))
+            .output()
+            .unwrap()
+    };
+    let output = run(&script, "b.txt");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("not on PATH"));
+    assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"data");
+
+    let output = run(&pinned, "c.txt");
+    assert!(!output.status.success());
+    assert!(!dir.path().join("c.txt").exists());
+}
+
+/// Options bcmr refuses send the function to the system cp, with a note.
+#[cfg(target_os = "linux")]
+#[test]
+fn e2e_init_functions_fall_back_to_the_system_command() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"data").unwrap();
+    let (code, script, stderr) =
+        run_bcmr(&["init", "bash", "--cmd", "b", "--absolute-path", "--always"]);
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let run = |args: &str| {
+        isolate(&mut Command::new("bash"))
+            .arg("-c")
+            .arg(format!("{}\nbcp {}", script, args))
+            .current_dir(dir.path())
+            .output()
+            .unwrap()
+    };
+    let output = run("a.txt b.txt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(!stderr.contains("running the system cp"), "{stderr}");
+
+    let output = run("-T a.txt c.txt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("bcmr: unexpected argument '-T' found; running the system cp"),
+        "{stderr}"
+    );
+    assert_eq!(std::fs::read(dir.path().join("c.txt")).unwrap(), b"data");
+}
+
+/// A name the shell already has is left alone unless --force-override.
+#[cfg(unix)]
+#[test]
+fn e2e_init_skips_names_already_in_use() {
+    let (code, script, stderr) =
+        run_bcmr(&["init", "bash", "--cmd", "b", "--absolute-path", "--always"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    let (code, forced, stderr) = run_bcmr(&[
+        "init",
+        "bash",
+        "--cmd",
+        "b",
+        "--absolute-path",
+        "--force-override",
+        "--always",
+    ]);
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let run = |script: &str| {
+        let output = Command::new("bash")
+            .arg("-c")
+            .arg(format!(
+                "alias bcp='echo mine'\nbmv() {{ echo mine; }}\n{}\n{}\n\
+                 declare -f bcp bmv brm | grep -c -- --check-args",
+                script, script
+            ))
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (defined, stderr) = run(&script);
+    assert_eq!(defined, "1", "only brm: {stderr}");
+    assert!(
+        stderr
+            .contains("bcmr init: bcp is already an alias: alias bcp='echo mine'; not defining it"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("bcmr init: bmv is already a function from "),
+        "{stderr}"
+    );
+    // Loading the script again does not count its own brm as a conflict.
+    assert!(!stderr.contains("brm is already"), "{stderr}");
+
+    let (defined, stderr) = run(&forced);
+    assert_eq!(defined, "3", "{stderr}");
+    assert!(
+        stderr.contains("bcmr init: replacing bcp, an alias"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,323 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod common;
+
+use common::isolate;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_config(home: &Path, args: &[&str], editor: Option<&str>) -> (bool, String, String) {
+    let mut cmd = Command::new(bcmr_bin());
+    cmd.arg("config")
+        .args(args)
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("VISUAL");
+    if let Some(editor) = editor {
+        cmd.env("EDITOR", editor);
+    }
+    let output = cmd.output().expect("failed to execute bcmr");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_config_init_writes_once_and_path_finds_it() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join(".config/bcmr/config.toml");
+
+    let (ok, stdout, _) = run_config(home.path(), &["path"], None);
+    assert!(ok);
+    assert!(
+        stdout.starts_with(&format!("missing  {}\n", file.display())),
+        "{stdout}"
+    );
+
+    let (ok, _, stderr) = run_config(home.path(), &["init"], None);
+    assert!(ok, "{stderr}");
+    let written = fs::read_to_string(&file).unwrap();
+    assert!(written.starts_with("# bcmr configuration"));
+
+    fs::write(&file, "[copy]\nverify = true\n").unwrap();
+    let (ok, _, stderr) = run_config(home.path(), &["init"], None);
+    assert!(!ok);
+    assert!(stderr.contains("already exists"), "{stderr}");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "[copy]\nverify = true\n"
+    );
+
+    let (ok, _, _) = run_config(home.path(), &["init", "-f"], None);
+    assert!(ok);
+    assert_eq!(fs::read_to_string(&file).unwrap(), written);
+
+    let (_, stdout, _) = run_config(home.path(), &["path"], None);
+    assert!(
+        stdout.starts_with(&format!("found    {}\n", file.display())),
+        "{stdout}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_config_show_merges_the_file_over_the_defaults() {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join(".config/bcmr");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("config.toml"),
+        "[copy]\nverify = true\n\n[scp]\nparallel_transfers = 9\n",
+    )
+    .unwrap();
+
+    let (ok, stdout, stderr) = run_config(home.path(), &["show"], None);
+    assert!(ok, "{stderr}");
+    assert!(stdout.contains("verify = true"), "{stdout}");
+    assert!(stdout.contains("parallel_transfers = 9"), "{stdout}");
+    assert!(stdout.contains("reflink = \"auto\""), "{stdout}");
+
+    let (ok, stdout, _) = run_config(home.path(), &["show", "--default"], None);
+    assert!(ok);
+    assert!(!stdout.contains("verify = true"), "{stdout}");
+    assert!(stdout.contains("parallel_transfers = 4"), "{stdout}");
+
+    fs::write(dir.join("config.toml"), "[copy\n").unwrap();
+    let (ok, _, stderr) = run_config(home.path(), &["show"], None);
+    assert!(!ok);
+    assert!(stderr.contains("TOML parse error"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_config_show_profile_prints_the_merged_result() {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join(".config/bcmr");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("config.toml"),
+        "[copy]\npreserve = true\n\n[profile.backup.copy]\nverify = true\n\n\
+         [profile.backup.progress]\nstyle = \"plain\"\n\n[profile.fast.copy]\nsync = false\n",
+    )
+    .unwrap();
+
+    let (ok, stdout, stderr) = run_config(home.path(), &["show", "--profile", "backup"], None);
+    assert!(ok, "{stderr}");
+    assert!(stdout.contains("preserve = true"), "{stdout}");
+    assert!(stdout.contains("verify = true"), "{stdout}");
+    assert!(stdout.contains("style = \"plain\""), "{stdout}");
+    assert!(!stdout.contains("[profile"), "{stdout}");
+
+    let (ok, stdout, _) = run_config(home.path(), &["show"], None);
+    assert!(ok);
+    assert!(!stdout.contains("verify = true"), "{stdout}");
+
+    let (ok, _, stderr) = run_config(home.path(), &["show", "--profile", "nope"], None);
+    assert!(!ok);
+    assert!(
+        stderr.contains("unknown profile 'nope' (available: backup, fast)"),
+        "{stderr}"
+    );
+
+    let (ok, _, stderr) = run_config(
+        home.path(),
+        &["show", "--default", "--profile", "backup"],
+        None,
+    );
+    assert!(!ok);
+    assert!(stderr.contains("--default"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_config_edit_creates_the_file_and_checks_it() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join(".config/bcmr/config.toml");
+    let script = home.path().join("editor.sh");
+    fs::write(
+        &script,
+        "#!/bin/sh\nprintf '[copy]\\nverfy = true\\n' >> \"$1\"\n",
+    )
+    .unwrap();
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let (ok, _, stderr) = run_config(home.path(), &["edit"], Some(script.to_str().unwrap()));
+    assert!(ok, "{stderr}");
+    assert!(stderr.contains("Created"), "{stderr}");
+    // The template already has a [copy] table, so the appended one breaks it.
+    assert!(stderr.contains("does not load"), "{stderr}");
+    assert!(fs::read_to_string(&file)
+        .unwrap()
+        .ends_with("verfy = true\n"));
+
+    fs::write(&file, "[copy]\nverfy = true\n").unwrap();
+    let (ok, _, stderr) = run_config(home.path(), &["edit"], Some("true"));
+    assert!(ok);
+    assert!(
+        stderr.contains("unknown config key 'copy.verfy'"),
+        "{stderr}"
+    );
+
+    let (ok, _, stderr) = run_config(home.path(), &["edit"], Some("false"));
+    assert!(!ok);
+    assert!(stderr.contains("editor 'false' exited"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_broken_config_warns_and_strict_config_refuses() {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join(".config/bcmr");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("config.toml");
+    fs::write(&file, "[progress.theme]\nbar_gradient = \"#fff\"\n").unwrap();
+    let work = tempfile::tempdir().unwrap();
+    fs::write(work.path().join("a.txt"), b"data").unwrap();
+
+    let copy = |extra: &[&str], dst: &str| {
+        isolate(&mut Command::new(bcmr_bin()))
+            .arg("copy")
+            .args(extra)
+            .args(["a.txt", dst])
+            .current_dir(work.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .output()
+            .expect("failed to execute bcmr")
+    };
+
+    let output = copy(&[], "b.txt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains(&format!(
+            "warning: invalid config, using the defaults: {}: progress.theme.bar_gradient: \
+             invalid type: string \"#fff\", expected a sequence",
+            file.display()
+        )),
+        "{stderr}"
+    );
+    assert_eq!(fs::read(work.path().join("b.txt")).unwrap(), b"data");
+
+    let output = copy(&["--strict-config"], "c.txt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("invalid config: "), "{stderr}");
+    assert!(!work.path().join("c.txt").exists());
+
+    fs::write(&file, "[copy]\nverfy = true\n").unwrap();
+    let output = copy(&["--strict-config"], "d.txt");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("unknown key 'copy.verfy'"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_required_confirmation_without_a_tty_fails_instead_of_waiting() {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join(".config/bcmr");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("config.toml"),
+        "[confirm]\nrequire_confirmation_for = [\"remove-recursive\"]\n",
+    )
+    .unwrap();
+    let work = tempfile::tempdir().unwrap();
+    let tree = work.path().join("tree");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    fs::write(tree.join("sub/a.txt"), b"data").unwrap();
+
+    // A pipe nobody writes to: reading it would hang.
+    let remove = |extra: &[&str]| {
+        let mut child = isolate(&mut Command::new(bcmr_bin()))
+            .arg("remove")
+            .args(extra)
+            .arg("tree")
+            .current_dir(work.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to execute bcmr");
+        let _stdin = child.stdin.take();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = remove(&["-rf"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("confirmation required but no TTY; pass --yes"),
+        "{stderr}"
+    );
+    assert!(tree.join("sub/a.txt").exists());
+
+    let output = remove(&["-rf", "--yes"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!tree.exists());
+}
+
+#[test]
+fn e2e_interactive_remove_without_a_tty_fails_instead_of_waiting() {
+    let work = tempfile::tempdir().unwrap();
+    fs::write(work.path().join("a.txt"), b"data").unwrap();
+
+    // A pipe nobody writes to: reading it would hang.
+    let remove = |extra: &[&str]| {
+        let mut child = isolate(&mut Command::new(bcmr_bin()))
+            .args(["remove", "-i"])
+            .args(extra)
+            .arg("a.txt")
+            .current_dir(work.path())
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to execute bcmr");
+        let _stdin = child.stdin.take();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = remove(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("confirmation required but no TTY; pass --yes"),
+        "{stderr}"
+    );
+    assert!(work.path().join("a.txt").exists());
+
+    let output = remove(&["--yes"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!work.path().join("a.txt").exists());
+}
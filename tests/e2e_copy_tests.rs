@@ -9,6 +9,10 @@ use bcmr::core::checksum;
 use bcmr::core::io as durable_io;
 use bcmr::core::session::Session;
 
+mod common;
+
+use common::isolate;
+
 fn bcmr_bin() -> PathBuf {
     let mut path = std::env::current_exe()
         .unwrap()
@@ -25,7 +29,7 @@ fn bcmr_bin() -> PathBuf {
 }
 
 fn run_bcmr(args: &[&str]) -> (bool, String, String) {
-    let output = Command::new(bcmr_bin())
+    let output = isolate(&mut Command::new(bcmr_bin()))
         .args(args)
         .output()
         .expect("failed to execute bcmr");
@@ -36,6 +40,14 @@ fn run_bcmr(args: &[&str]) -> (bool, String, String) {
     )
 }
 
+/// Like `run_bcmr`, but with progress pinned to stderr so a test run from a
+/// terminal doesn't send it to /dev/tty. Returns (ok, stdout, progress).
+fn run_bcmr_progress(args: &[&str]) -> (bool, String, String) {
+    let mut args = args.to_vec();
+    args.extend(["--progress-fd", "2"]);
+    run_bcmr(&args)
+}
+
 fn create_random_file(path: &Path, size: usize) {
     let mut f = fs::File::create(path).unwrap();
     let mut buf = vec![0u8; 4096];
@@ -54,8 +66,8 @@ fn create_random_file(path: &Path, size: usize) {
 }
 
 fn files_match(a: &Path, b: &Path) -> bool {
-    let ha = checksum::calculate_hash(a).unwrap();
-    let hb = checksum::calculate_hash(b).unwrap();
+    let ha = checksum::calculate_hash(a, checksum::Algorithm::Blake3).unwrap();
+    let hb = checksum::calculate_hash(b, checksum::Algorithm::Blake3).unwrap();
     ha == hb
 }
 
@@ -116,6 +128,188 @@ fn e2e_copy_with_verify_flag() {
     assert!(files_match(&src, &dst));
 }
 
+#[test]
+fn e2e_copy_verify_and_strict_with_other_algorithms() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    create_random_file(&src, 3 * 1024 * 1024);
+
+    for algo in ["sha256", "xxh3"] {
+        let dst = dir.path().join(format!("{algo}.bin"));
+        let (ok, _, stderr) = run_bcmr(&[
+            "copy",
+            "-V",
+            "--checksum-algo",
+            algo,
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ]);
+        assert!(ok, "{algo}: {stderr}");
+        assert!(files_match(&src, &dst));
+
+        // A truncated copy whose prefix hashes the same is resumed.
+        let data = fs::read(&src).unwrap();
+        fs::write(&dst, &data[..1024 * 1024]).unwrap();
+        let (ok, _, stderr) = run_bcmr(&[
+            "copy",
+            "-s",
+            "--checksum-algo",
+            algo,
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ]);
+        assert!(ok, "{algo}: {stderr}");
+        assert!(files_match(&src, &dst));
+    }
+
+    let (ok, _, _) = run_bcmr(&[
+        "copy",
+        "--checksum-algo",
+        "md5",
+        src.to_str().unwrap(),
+        dir.path().join("md5.bin").to_str().unwrap(),
+    ]);
+    assert!(!ok);
+}
+
+#[test]
+fn e2e_copy_verify_report_lists_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    create_random_file(&src.join("a.bin"), 300_000);
+    create_random_file(&src.join("sub/b.bin"), 5_000);
+    let dst = dir.path().join("dst");
+    let report = dir.path().join("report.jsonl");
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-V",
+        "--checksum-algo",
+        "xxh3",
+        "--verify-report",
+        report.to_str().unwrap(),
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "{stderr}");
+
+    let records: Vec<serde_json::Value> = fs::read_to_string(&report)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records.len(), 4, "{records:?}");
+    assert_eq!(records[0]["type"], "header");
+    assert_eq!(records[0]["algorithm"], "xxh3");
+    assert!(records[0]["command"]
+        .as_array()
+        .unwrap()
+        .contains(&"--verify-report".into()));
+
+    let mut files: Vec<&serde_json::Value> = records[1..3].iter().collect();
+    files.sort_by_key(|r| r["size"].as_u64());
+    assert_eq!(files[0]["dst"], dst.join("sub/b.bin").to_str().unwrap());
+    for (record, name) in files.iter().zip(["sub/b.bin", "a.bin"]) {
+        assert_eq!(record["type"], "file");
+        assert_eq!(record["result"], "PASS");
+        let digest = checksum::calculate_hash(&src.join(name), checksum::Algorithm::Xxh3).unwrap();
+        assert_eq!(record["digest"], digest.as_str());
+    }
+
+    let summary = &records[3];
+    assert_eq!(summary["type"], "summary");
+    assert_eq!(summary["passed"], 2);
+    assert_eq!(summary["failed"], 0);
+    assert_eq!(summary["bytes"], 305_000);
+
+    // The report only makes sense alongside verification.
+    let (ok, _, _) = run_bcmr(&[
+        "copy",
+        "--verify-report",
+        report.to_str().unwrap(),
+        src.join("a.bin").to_str().unwrap(),
+        dir.path().join("c.bin").to_str().unwrap(),
+    ]);
+    assert!(!ok);
+}
+
+#[test]
+fn e2e_copy_verify_jobs_checks_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    for i in 0..40 {
+        let path = src.join(format!("d{}/f{}.bin", i % 4, i));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        create_random_file(&path, 1_000 + i * 4_099);
+    }
+    let dst = dir.path().join("dst");
+    let report = dir.path().join("report.jsonl");
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-V",
+        "--verify-jobs",
+        "3",
+        "--verify-report",
+        report.to_str().unwrap(),
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "{stderr}");
+    for i in 0..40 {
+        let rel = format!("d{}/f{}.bin", i % 4, i);
+        assert!(files_match(&src.join(&rel), &dst.join(&rel)), "{rel}");
+    }
+    let summary = fs::read_to_string(&report).unwrap();
+    assert!(
+        summary.lines().last().unwrap().contains("\"passed\":40"),
+        "{summary}"
+    );
+
+    let (ok, _, _) = run_bcmr(&[
+        "copy",
+        "--verify-jobs",
+        "3",
+        src.join("d0/f0.bin").to_str().unwrap(),
+        dir.path().join("x.bin").to_str().unwrap(),
+    ]);
+    assert!(!ok, "--verify-jobs without -V should be rejected");
+}
+
+#[test]
+fn e2e_copy_verify_direct_reads_past_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    // Not a multiple of any block size, so the last direct read is short.
+    create_random_file(&src, 3 * 1024 * 1024 + 517);
+
+    for (i, algo) in ["blake3", "sha256"].iter().enumerate() {
+        let dst = dir.path().join(format!("dst{i}.bin"));
+        let (ok, _, stderr) = run_bcmr(&[
+            "copy",
+            "-V",
+            "--verify-direct",
+            "--checksum-algo",
+            algo,
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ]);
+        assert!(ok, "{algo}: {stderr}");
+        assert!(files_match(&src, &dst));
+    }
+
+    let (ok, _, _) = run_bcmr(&[
+        "copy",
+        "--verify-direct",
+        src.to_str().unwrap(),
+        dir.path().join("x.bin").to_str().unwrap(),
+    ]);
+    assert!(!ok, "--verify-direct without -V should be rejected");
+}
+
 #[test]
 fn e2e_resume_after_simulated_crash() {
     let dir = tempfile::tempdir().unwrap();
@@ -462,12 +656,12 @@ fn e2e_copy_preserves_existing_on_no_force() {
     create_random_file(&src, 1024);
     create_random_file(&dst, 512);
 
-    let dst_hash_before = checksum::calculate_hash(&dst).unwrap();
+    let dst_hash_before = checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap();
 
     let (ok, _, _) = run_bcmr(&["copy", "-t", src.to_str().unwrap(), dst.to_str().unwrap()]);
     assert!(!ok, "copy without -f should fail when target exists");
 
-    let dst_hash_after = checksum::calculate_hash(&dst).unwrap();
+    let dst_hash_after = checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap();
     assert_eq!(dst_hash_before, dst_hash_after);
 }
 
@@ -659,3 +853,1440 @@ fn test_xattr_preserves_binary_value() {
     let got = xattr::get(&dst, "user.bcmr.bin").unwrap().unwrap();
     assert_eq!(got, binary_value);
 }
+
+#[test]
+fn e2e_log_file_records_actions_and_failures() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    let dst = tmp.path().join("dst");
+    let log = tmp.path().join("ops.log");
+    fs::create_dir(&src).unwrap();
+    fs::create_dir(&dst).unwrap();
+    fs::write(src.join("a.txt"), b"hello").unwrap();
+    fs::write(src.join("b.txt"), b"world!!").unwrap();
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-t",
+        "-r",
+        "--log",
+        log.to_str().unwrap(),
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy should succeed: {}", stderr);
+
+    let existing = dst.join("src").join("a.txt");
+    let (ok, _, _) = run_bcmr(&[
+        "copy",
+        "-t",
+        "--log",
+        log.to_str().unwrap(),
+        src.join("a.txt").to_str().unwrap(),
+        existing.to_str().unwrap(),
+    ]);
+    assert!(!ok, "copying onto an existing file without -f must fail");
+
+    let content = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 7, "log:\n{}", content);
+    assert!(lines[0].contains(" BEGIN ") && lines[0].contains("--log"));
+    let copies: Vec<&&str> = lines.iter().filter(|l| l.contains(" COPY ")).collect();
+    assert_eq!(copies.len(), 2);
+    assert!(content.contains(&format!(
+        "COPY {} -> {} 5 bytes ok",
+        src.join("a.txt").display(),
+        existing.display()
+    )));
+    assert!(
+        lines[3].ends_with("END ok: 2 copied, 0 moved, 0 removed, 0 skipped, 0 errors, 12 bytes")
+    );
+    assert!(lines[4].contains(" BEGIN "));
+    assert!(lines[5].contains(&format!(" ERROR {}: ", existing.display())));
+    assert!(lines[6].contains(" END failed: 0 copied"));
+}
+
+/// A move onto another filesystem copies and deletes, but is logged as the
+/// move it is, one line per file.
+#[cfg(target_os = "linux")]
+#[test]
+fn e2e_log_file_records_a_cross_device_move_as_moves() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let Ok(shm) = tempfile::tempdir_in("/dev/shm") else {
+        return;
+    };
+    let dev = |p: &Path| fs::metadata(p).unwrap().dev();
+    if dev(tmp.path()) == dev(shm.path()) {
+        return;
+    }
+    let src = tmp.path().join("tree");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.txt"), b"hello").unwrap();
+    fs::write(src.join("sub/b.txt"), b"world!!").unwrap();
+    let log = tmp.path().join("ops.log");
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "move",
+        "-r",
+        "--log",
+        log.to_str().unwrap(),
+        src.to_str().unwrap(),
+        shm.path().to_str().unwrap(),
+    ]);
+    assert!(ok, "move should succeed: {}", stderr);
+    assert!(!src.exists());
+
+    let content = fs::read_to_string(&log).unwrap();
+    assert!(!content.contains(" COPY "), "log:\n{}", content);
+    assert!(
+        content.contains(&format!(
+            "MOVE {} -> {} ok",
+            src.join("sub/b.txt").display(),
+            shm.path().join("tree/sub/b.txt").display()
+        )),
+        "log:\n{}",
+        content
+    );
+    assert_eq!(content.matches(" MOVE ").count(), 2, "log:\n{}", content);
+    assert!(
+        content.contains("END ok: 0 copied, 2 moved, 0 removed, 0 skipped, 0 errors, 12 bytes"),
+        "log:\n{}",
+        content
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_sigtstp_stops_copy_and_sigcont_resumes_it() {
+    const FILES: usize = 3;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dst_dir = dir.path().join("dst");
+    fs::create_dir(&dst_dir).unwrap();
+
+    let mut args: Vec<String> = vec![
+        "copy".to_string(),
+        "--jobs".to_string(),
+        "1".to_string(),
+        "--test-mode".to_string(),
+        "delay:300".to_string(),
+        "--progress-fd".to_string(),
+        "2".to_string(),
+    ];
+    for i in 0..FILES {
+        let src = dir.path().join(format!("src-{i}.txt"));
+        fs::write(&src, b"x").unwrap();
+        args.push(src.to_string_lossy().into_owned());
+    }
+    args.push(dst_dir.to_string_lossy().into_owned());
+
+    let child = isolate(&mut Command::new(bcmr_bin()))
+        .args(&args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id() as libc::pid_t;
+
+    std::thread::sleep(Duration::from_millis(200));
+    unsafe { libc::kill(pid, libc::SIGTSTP) };
+    std::thread::sleep(Duration::from_millis(1200));
+
+    let copied = (0..FILES)
+        .filter(|i| dst_dir.join(format!("src-{i}.txt")).exists())
+        .count();
+    assert!(copied < FILES, "copy kept running while stopped");
+
+    unsafe { libc::kill(pid, libc::SIGCONT) };
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "copy should finish after SIGCONT: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for i in 0..FILES {
+        assert!(dst_dir.join(format!("src-{i}.txt")).exists());
+    }
+
+    // Whatever the renderer managed to draw, it must not leave the cursor hidden.
+    let progress = String::from_utf8_lossy(&output.stderr);
+    if let Some(hide) = progress.rfind("\x1b[?25l") {
+        let show = progress.rfind("\x1b[?25h");
+        assert!(show.is_some_and(|s| s > hide), "cursor left hidden");
+    }
+}
+
+/// Starts a 4 MiB copy throttled to 1 MiB/s, sends `signal` a second in,
+/// and returns the output, the destination directory and its tempdir.
+#[cfg(unix)]
+fn interrupt_throttled_copy(
+    signal: libc::c_int,
+    extra: &[&str],
+) -> (std::process::Output, PathBuf, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("big.bin");
+    fs::write(&src, vec![7u8; 4 * 1024 * 1024]).unwrap();
+    let dst_dir = dir.path().join("dst");
+    fs::create_dir(&dst_dir).unwrap();
+
+    let child = isolate(&mut Command::new(bcmr_bin()))
+        .args(["copy", "--bwlimit", "1M"])
+        .args(["--progress-fd", "2"])
+        .args(extra)
+        .args([src.to_str().unwrap(), dst_dir.to_str().unwrap()])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(1000));
+    unsafe { libc::kill(child.id() as libc::pid_t, signal) };
+    let output = child.wait_with_output().unwrap();
+    (output, dst_dir, dir)
+}
+
+#[cfg(unix)]
+fn assert_cursor_shown(progress: &str) {
+    if let Some(hide) = progress.rfind("\x1b[?25l") {
+        let show = progress.rfind("\x1b[?25h");
+        assert!(show.is_some_and(|s| s > hide), "cursor left hidden");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_sigterm_removes_partial_file_and_exits_143() {
+    let (output, dst_dir, _dir) = interrupt_throttled_copy(libc::SIGTERM, &[]);
+    let progress = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(143), "{progress}");
+    assert!(progress.contains("Terminated: copied "), "{progress}");
+    let left: Vec<_> = fs::read_dir(&dst_dir).unwrap().collect();
+    assert!(left.is_empty(), "partial destination left behind: {left:?}");
+    assert_cursor_shown(&progress);
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_ctrl_c_removes_partial_file_and_exits_130() {
+    let (output, dst_dir, _dir) = interrupt_throttled_copy(libc::SIGINT, &[]);
+    let progress = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(130), "{progress}");
+    let dst = dst_dir.join("big.bin");
+    let expected = format!("partial '{}' removed", dst.display());
+    assert!(progress.contains("Interrupted: copied "), "{progress}");
+    assert!(progress.contains(&expected), "{progress}");
+    let left: Vec<_> = fs::read_dir(&dst_dir).unwrap().collect();
+    assert!(left.is_empty(), "partial destination left behind: {left:?}");
+    assert_cursor_shown(&progress);
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_ctrl_c_with_keep_partial_leaves_resumable_file() {
+    let (output, dst_dir, _dir) =
+        interrupt_throttled_copy(libc::SIGINT, &["--resume", "--keep-partial"]);
+    let progress = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(130), "{progress}");
+    assert!(progress.contains("' kept"), "{progress}");
+    let partial = dst_dir.join("big.bin");
+    assert!(partial.exists());
+
+    let src = dst_dir.parent().unwrap().join("big.bin");
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "--resume",
+        src.to_str().unwrap(),
+        dst_dir.to_str().unwrap(),
+    ]);
+    assert!(ok, "resume after interrupt failed: {stderr}");
+    assert_eq!(fs::read(&partial).unwrap(), fs::read(&src).unwrap());
+}
+
+// The signal lands partway through the tree, so the copy has to notice the
+// cancellation at its next chunk and unwind rather than be torn down.
+#[cfg(unix)]
+#[test]
+fn e2e_ctrl_c_stops_recursive_copy_between_files() {
+    const FILES: usize = 20;
+
+    let dir = tempfile::tempdir().unwrap();
+    let src_dir = dir.path().join("src");
+    fs::create_dir(&src_dir).unwrap();
+    for i in 0..FILES {
+        fs::write(src_dir.join(format!("f{i:02}.bin")), vec![3u8; 256 * 1024]).unwrap();
+    }
+    let dst_dir = dir.path().join("dst");
+
+    let child = isolate(&mut Command::new(bcmr_bin()))
+        .args(["copy", "-r", "--bwlimit", "1M"])
+        .args(["--progress-fd", "2"])
+        .args([src_dir.to_str().unwrap(), dst_dir.to_str().unwrap()])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(1000));
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let output = child.wait_with_output().unwrap();
+    let progress = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(130), "{progress}");
+    assert!(progress.contains("Interrupted: copied "), "{progress}");
+    assert!(!progress.contains("panicked"), "{progress}");
+    assert!(!progress.contains("Error:"), "{progress}");
+    assert_cursor_shown(&progress);
+
+    let copied: Vec<_> = fs::read_dir(&dst_dir)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .collect();
+    assert!(copied.len() < FILES, "copy ran to completion");
+    for entry in copied {
+        assert_eq!(
+            fs::read(entry.path()).unwrap(),
+            fs::read(src_dir.join(entry.file_name())).unwrap(),
+            "partial file left behind"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_sigterm_stops_remove_and_exits_143() {
+    const FILES: usize = 20;
+
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target");
+    fs::create_dir(&target).unwrap();
+    for i in 0..FILES {
+        fs::write(target.join(format!("f{i:02}.txt")), b"hello").unwrap();
+    }
+
+    let child = isolate(&mut Command::new(bcmr_bin()))
+        .args(["remove", "-r", "-f", "--test-mode", "delay:200"])
+        .args(["--progress-fd", "2"])
+        .arg(&target)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(1000));
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+    let output = child.wait_with_output().unwrap();
+    let progress = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(143), "{progress}");
+    assert!(progress.contains("Terminated: removed "), "{progress}");
+    assert!(!progress.contains("panicked"), "{progress}");
+    assert_cursor_shown(&progress);
+    let left = fs::read_dir(&target).unwrap().count();
+    assert!(left > 0 && left < FILES, "{left} files left");
+}
+
+#[test]
+fn e2e_plain_progress_counts_items() {
+    const FILES: usize = 50;
+
+    let dir = tempfile::tempdir().unwrap();
+    let src_dir = dir.path().join("src");
+    fs::create_dir(&src_dir).unwrap();
+    for i in 0..FILES {
+        fs::write(src_dir.join(format!("f{i}.txt")), b"hello").unwrap();
+    }
+    let dst_dir = dir.path().join("dst");
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-r",
+        "-t",
+        src_dir.to_str().unwrap(),
+        dst_dir.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    assert!(
+        progress.contains(&format!("Files: {FILES} created")),
+        "final item count missing: {progress}"
+    );
+}
+
+#[test]
+fn e2e_summary_counts_each_file_outcome() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::create_dir_all(&dst).unwrap();
+
+    fs::write(src.join("new.txt"), b"brand new").unwrap();
+    fs::write(src.join("same.txt"), b"hello").unwrap();
+    fs::write(src.join("partial.txt"), b"hello world").unwrap();
+    fs::write(src.join("sub/shrunk.txt"), b"abc").unwrap();
+
+    let dst_root = dst.join("src");
+    fs::create_dir_all(&dst_root).unwrap();
+    fs::write(dst_root.join("same.txt"), b"hello").unwrap();
+    fs::write(dst_root.join("partial.txt"), b"hello").unwrap();
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-r",
+        "-t",
+        "--append",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    assert!(
+        progress.contains("Files: 2 created, 1 skipped, 1 appended, 1 dirs created, 0 failed"),
+        "unexpected summary: {progress}"
+    );
+    assert_eq!(
+        fs::read(dst_root.join("partial.txt")).unwrap(),
+        b"hello world"
+    );
+}
+
+#[test]
+fn e2e_plain_mode_prints_done_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    let dst = dir.path().join("dst.bin");
+    create_random_file(&src, 64 * 1024);
+
+    let (ok, _, progress) =
+        run_bcmr_progress(&["copy", "-t", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(ok, "copy failed: {progress}");
+    assert!(
+        progress.contains("Copy done: 64.00 KiB in "),
+        "missing done line: {progress}"
+    );
+}
+
+/// Fancy and inline progress step down to plain lines when the progress
+/// stream is a file or pipe, which would keep the escape codes as text.
+#[test]
+fn e2e_redirected_progress_has_no_escape_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    create_random_file(&src, 64 * 1024);
+
+    for style in ["fancy", "inline"] {
+        let dst = dir.path().join(format!("{style}.bin"));
+        let (ok, _, progress) = run_bcmr_progress(&[
+            "copy",
+            "--progress",
+            style,
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ]);
+        assert!(ok, "copy failed: {progress}");
+        assert!(!progress.contains('\x1b'), "{style}: {progress:?}");
+        assert!(progress.contains("Copy done: 64.00 KiB in "), "{progress}");
+    }
+}
+
+#[test]
+fn e2e_plain_mode_shows_source_and_destination() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    let dst = dir.path().join("dst.bin");
+    create_random_file(&src, 1024);
+
+    let (ok, _, progress) =
+        run_bcmr_progress(&["copy", "-t", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(ok, "copy failed: {progress}");
+    assert!(
+        progress.contains("src.bin → ") && progress.contains("dst.bin"),
+        "missing paths header: {progress}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_skipped_special_file_is_reported_after_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    create_random_file(&src.join("a.bin"), 1024);
+    let fifo = src.join("pipe");
+    let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+    let dst = dir.path().join("dst");
+    let (ok, _stdout, stderr) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-t",
+        "--warnings-ok",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {stderr}");
+    assert!(dst.join("a.bin").exists());
+    assert!(
+        stderr.contains("warning: ") && stderr.contains("pipe: skipped"),
+        "missing warning: {stderr}"
+    );
+}
+
+#[test]
+fn e2e_dry_run_output_is_plain_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    create_random_file(&src.join("a.bin"), 1024);
+    create_random_file(&src.join("b.bin"), 2048);
+    let dst = dir.path().join("dst");
+    let (s, d) = (src.to_str().unwrap(), dst.to_str().unwrap());
+
+    for args in [
+        vec!["copy", "-r", "--dry-run", s, d],
+        vec!["move", "-r", "--dry-run", s, d],
+        vec!["remove", "-r", "-y", "--dry-run", s],
+    ] {
+        let (ok, stdout, stderr) = run_bcmr(&args);
+        assert!(ok, "{args:?} failed: {stderr}");
+        assert!(stdout.contains("DRY RUN MODE"), "{args:?}: {stdout}");
+        assert!(stdout.contains("a.bin"), "{args:?}: {stdout}");
+        assert!(
+            !stdout.contains('\x1b'),
+            "{args:?} wrote escapes: {stdout:?}"
+        );
+        assert!(!stdout.contains(" done: "), "{args:?}: {stdout}");
+    }
+    assert!(src.join("a.bin").exists());
+    assert!(!dst.exists());
+}
+
+#[test]
+fn e2e_plain_mode_counts_empty_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    for i in 0..10_000 {
+        fs::File::create(src.join(format!("empty-{i}"))).unwrap();
+    }
+    let dst = dir.path().join("dst");
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-r",
+        "-t",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    assert_eq!(fs::read_dir(&dst).unwrap().count(), 10_000);
+    assert!(progress.contains("Copy done: 0 B in "), "{progress}");
+    assert!(
+        progress.contains("Files: 10,000 created"),
+        "missing summary: {progress}"
+    );
+    assert!(
+        !progress.contains("NaN") && !progress.contains("inf"),
+        "{progress}"
+    );
+}
+
+#[test]
+fn e2e_progress_stays_off_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.bin");
+    create_random_file(&src, 256 * 1024);
+
+    for plain in [false, true] {
+        let dst = dir.path().join(format!("dst-{plain}.bin"));
+        let mut args = vec!["copy", src.to_str().unwrap(), dst.to_str().unwrap()];
+        if plain {
+            args.push("-t");
+        }
+        let (ok, stdout, progress) = run_bcmr_progress(&args);
+        assert!(ok, "copy failed: {progress}");
+        assert!(stdout.is_empty(), "progress leaked into stdout: {stdout:?}");
+        assert!(progress.contains("Copy done: "), "{progress:?}");
+
+        // Without the override progress may go to /dev/tty instead, but
+        // never to stdout.
+        fs::remove_file(&dst).unwrap();
+        let (ok, stdout, stderr) = run_bcmr(&args);
+        assert!(ok, "copy failed: {stderr}");
+        assert!(stdout.is_empty(), "progress leaked into stdout: {stdout:?}");
+    }
+}
+
+#[test]
+fn e2e_quiet_copy_prints_nothing_on_success() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.txt"), b"a").unwrap();
+    fs::write(src.join("sub/b.txt"), b"b").unwrap();
+    let dst = dir.path().join("dst");
+
+    for extra in [&[][..], &["-t", "-v"][..]] {
+        let _ = fs::remove_dir_all(&dst);
+        let mut args = vec!["copy", "-r", "--quiet"];
+        args.extend_from_slice(extra);
+        args.extend([src.to_str().unwrap(), dst.to_str().unwrap()]);
+        let (ok, stdout, stderr) = run_bcmr(&args);
+        assert!(ok, "{args:?} failed: {stderr}");
+        assert!(stdout.is_empty(), "{args:?} stdout: {stdout:?}");
+        assert!(stderr.is_empty(), "{args:?} stderr: {stderr:?}");
+        assert_eq!(fs::read(dst.join("sub/b.txt")).unwrap(), b"b");
+    }
+}
+
+#[test]
+fn e2e_quiet_copy_still_reports_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.txt");
+    let dst = dir.path().join("dst.txt");
+    fs::write(&src, b"new").unwrap();
+    fs::write(&dst, b"old").unwrap();
+
+    let (ok, stdout, stderr) =
+        run_bcmr(&["copy", "-q", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(!ok, "copy onto an existing file should fail");
+    assert!(stdout.is_empty(), "{stdout:?}");
+    // A backtrace may follow when RUST_BACKTRACE is set; the message itself
+    // is one line.
+    let message: Vec<&str> = stderr.lines().take_while(|l| !l.is_empty()).collect();
+    assert_eq!(message.len(), 1, "{stderr:?}");
+    assert!(message[0].contains("dst.txt"), "{stderr:?}");
+}
+
+#[test]
+fn e2e_trivial_copy_returns_promptly_with_final_frame() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src.txt");
+    let dst = dir.path().join("dst.txt");
+    fs::write(&src, b"hello").unwrap();
+
+    let start = Instant::now();
+    let (ok, _, progress) =
+        run_bcmr_progress(&["copy", "-t", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    let elapsed = start.elapsed();
+    assert!(ok, "copy failed: {progress}");
+    assert!(elapsed < Duration::from_secs(1), "took {elapsed:?}");
+    assert!(
+        progress.contains("Copy done: 5 B in "),
+        "done line missing: {progress}"
+    );
+}
+
+#[test]
+fn e2e_cp_spellings_recursive_no_clobber_and_verbose() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("tree");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    create_random_file(&src.join("sub/a.bin"), 4096);
+    let dst = dir.path().join("out");
+
+    let (ok, _, err) = run_bcmr(&["copy", "-R", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(ok, "copy -R failed: {err}");
+    assert!(files_match(&src.join("sub/a.bin"), &dst.join("sub/a.bin")));
+
+    let file = dir.path().join("f.bin");
+    let existing = dir.path().join("existing.bin");
+    create_random_file(&file, 1024);
+    create_random_file(&existing, 512);
+    let before = checksum::calculate_hash(&existing, checksum::Algorithm::Blake3).unwrap();
+    let (ok, _, err) = run_bcmr(&[
+        "copy",
+        "--no-clobber",
+        file.to_str().unwrap(),
+        existing.to_str().unwrap(),
+    ]);
+    assert!(ok, "--no-clobber should skip, not fail: {err}");
+    assert_eq!(
+        checksum::calculate_hash(&existing, checksum::Algorithm::Blake3).unwrap(),
+        before
+    );
+
+    let fresh = dir.path().join("fresh.bin");
+    let (ok, _, err) = run_bcmr(&[
+        "copy",
+        "-v",
+        file.to_str().unwrap(),
+        fresh.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy -v failed: {err}");
+    assert!(
+        err.contains(&format!("'{}' -> '{}'", file.display(), fresh.display())),
+        "{err}"
+    );
+}
+
+/// rm invocations from existing scripts, run through the function
+/// `bcmr init bash --cmd '' --absolute-path --always` defines.
+#[cfg(unix)]
+#[test]
+fn e2e_rm_function_accepts_rm_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = dir.path().join("tree");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    create_random_file(&tree.join("sub/a.bin"), 1024);
+    let loose: Vec<PathBuf> = (0..3).map(|i| dir.path().join(format!("f{i}"))).collect();
+    for f in &loose {
+        create_random_file(f, 16);
+    }
+
+    let rm = |args: &str| {
+        let script = format!(
+            "eval \"$('{}' init bash --cmd '' --absolute-path --always)\"; cd '{}' && rm {}",
+            bcmr_bin().display(),
+            dir.path().display(),
+            args
+        );
+        let output = isolate(&mut Command::new("bash"))
+            .args(["-c", &script])
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("failed to run bash");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (ok, err) = rm("-rf --no-preserve-root --one-file-system --zap tree");
+    assert!(ok, "{err}");
+    assert!(!tree.exists());
+    assert!(err.contains("ignoring unsupported option '--zap'"), "{err}");
+
+    // -I asks nothing for three files, so this runs without a terminal.
+    let (ok, err) = rm("-Iv f0 f1 f2");
+    assert!(ok, "{err}");
+    assert!(loose.iter().all(|f| !f.exists()));
+
+    let (ok, err) = rm("--strict-flags --zap missing");
+    assert!(!ok);
+    assert!(err.contains("--zap"), "{err}");
+}
+
+/// Every entry under `root` with its type, contents and permission bits,
+/// keyed by path relative to `root`.
+#[cfg(unix)]
+fn tree_listing(root: &Path) -> Vec<(PathBuf, bool, Vec<u8>, u32, i64)> {
+    use std::os::unix::fs::MetadataExt;
+    let mut entries: Vec<_> = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .map(|e| {
+            let e = e.unwrap();
+            let md = e.metadata().unwrap();
+            let contents = if md.is_file() {
+                fs::read(e.path()).unwrap()
+            } else {
+                Vec::new()
+            };
+            (
+                e.path().strip_prefix(root).unwrap().to_path_buf(),
+                md.is_dir(),
+                contents,
+                md.mode() & 0o7777,
+                if md.is_dir() { md.mtime() } else { 0 },
+            )
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+#[cfg(unix)]
+fn make_fixture_tree(root: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    for d in ["a/b/c", "a/d", "e", "skip/me"] {
+        fs::create_dir_all(root.join(d)).unwrap();
+    }
+    for (i, f) in ["a/1", "a/b/2", "a/b/c/3", "a/d/4", "e/5", "6", "skip/me/7"]
+        .iter()
+        .enumerate()
+    {
+        create_random_file(&root.join(f), 100 + i * 1000);
+    }
+    fs::set_permissions(root.join("a/b"), fs::Permissions::from_mode(0o750)).unwrap();
+    fs::set_permissions(root.join("a/1"), fs::Permissions::from_mode(0o640)).unwrap();
+    // Every compared directory gets a fixed time: left to the clock, the
+    // two fixtures can land on either side of a second boundary.
+    for (d, t) in [
+        ("a/b/c", 1_000_000),
+        ("a/b", 2_000_000),
+        ("e", 3_000_000),
+        ("a/d", 4_000_000),
+        ("a", 5_000_000),
+    ] {
+        let t = filetime::FileTime::from_unix_time(t, 0);
+        filetime::set_file_mtime(root.join(d), t).unwrap();
+    }
+}
+
+/// `move -e` goes through the streaming recursive copy; with -p it must
+/// leave the same tree a planned `copy -p -e` does, directory times included.
+#[cfg(unix)]
+#[test]
+fn e2e_streaming_recursive_copy_matches_planned_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let copy_src = dir.path().join("copy_src/tree");
+    let move_src = dir.path().join("move_src/tree");
+    make_fixture_tree(&copy_src);
+    make_fixture_tree(&move_src);
+    let copied = dir.path().join("copied");
+    let moved = dir.path().join("moved");
+    fs::create_dir_all(&copied).unwrap();
+    fs::create_dir_all(&moved).unwrap();
+
+    let (ok, _, err) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-p",
+        "-e",
+        "skip",
+        copy_src.to_str().unwrap(),
+        copied.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {err}");
+    let (ok, _, err) = run_bcmr(&[
+        "move",
+        "-r",
+        "-p",
+        "-e",
+        "skip",
+        move_src.to_str().unwrap(),
+        moved.to_str().unwrap(),
+    ]);
+    assert!(ok, "move failed: {err}");
+
+    let listing = tree_listing(&copied.join("tree"));
+    assert!(!listing.iter().any(|(p, ..)| p.starts_with("skip")));
+    assert_eq!(listing.len(), 11, "{listing:?}");
+    assert_eq!(listing, tree_listing(&moved.join("tree")));
+    assert!(move_src.join("skip/me/7").exists());
+    assert!(!move_src.join("a").exists());
+}
+
+/// A tree with many small files streams through without the memory
+/// growing with it. Slow, so run with `cargo test -- --ignored`.
+#[cfg(target_os = "linux")]
+#[test]
+#[ignore = "creates 300k files"]
+fn e2e_streaming_recursive_copy_memory_stays_bounded() {
+    const FILES: usize = 300_000;
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src/many");
+    for d in 0..300 {
+        let sub = src.join(format!("dir-with-a-longish-name-{d:04}"));
+        fs::create_dir_all(&sub).unwrap();
+        for f in 0..FILES / 300 {
+            fs::write(
+                sub.join(format!("small-file-with-a-long-name-{f:05}.txt")),
+                b"x",
+            )
+            .unwrap();
+        }
+    }
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(&dst).unwrap();
+
+    let (ok, _, err) = run_bcmr(&[
+        "move",
+        "-r",
+        "-e",
+        "never-matches",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "move failed: {err}");
+    let moved = walkdir::WalkDir::new(dst.join("many"))
+        .into_iter()
+        .filter(|e| e.as_ref().unwrap().file_type().is_file())
+        .count();
+    assert_eq!(moved, FILES);
+
+    // Peak resident set of the largest child so far, in KiB on Linux.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) },
+        0
+    );
+    let peak_mib = usage.ru_maxrss / 1024;
+    assert!(peak_mib < 96, "peak RSS {peak_mib} MiB");
+}
+
+#[test]
+fn e2e_compress_then_decompress_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("huge.log");
+    let text: String = (0..200_000).map(|i| format!("line {}\n", i)).collect();
+    fs::write(&src, &text).unwrap();
+    let out = dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-V",
+        "--compress",
+        "zstd:7",
+        src.to_str().unwrap(),
+        out.to_str().unwrap(),
+    ]);
+    assert!(ok, "{progress}");
+    let packed = out.join("huge.log.zst");
+    assert!(fs::metadata(&packed).unwrap().len() < text.len() as u64 / 4);
+    assert!(progress.contains("compressed ("), "{progress}");
+
+    let back = dir.path().join("back.log");
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-V",
+        "--decompress",
+        packed.to_str().unwrap(),
+        back.to_str().unwrap(),
+    ]);
+    assert!(ok, "{stderr}");
+    assert_eq!(fs::read_to_string(&back).unwrap(), text);
+}
+
+#[test]
+fn e2e_decompress_needs_a_known_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("data.bin");
+    fs::write(&src, b"not compressed").unwrap();
+    let dst = dir.path().join("out.bin");
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "--decompress",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(!ok);
+    assert!(stderr.contains("--decompress=zstd"), "{stderr}");
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "--decompress=zstd",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(!ok);
+    assert!(stderr.contains("not valid zstd data"), "{stderr}");
+    assert!(!dst.exists());
+}
+
+#[test]
+fn e2e_compress_rejects_resume() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"abc").unwrap();
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "--resume",
+        "--compress",
+        "zstd",
+        src.to_str().unwrap(),
+        dir.path().join("a.txt.zst").to_str().unwrap(),
+    ]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("cannot be combined with --compress"),
+        "{stderr}"
+    );
+}
+
+/// Copies `args` and checks 10 MiB at `--bwlimit 2M` takes about 5 s.
+#[cfg(not(windows))]
+fn assert_paced_to_two_mib_per_sec(args: &[&str]) {
+    let mut args = args.to_vec();
+    args.extend(["--bwlimit", "2M"]);
+    let start = Instant::now();
+    let (ok, _, stderr) = run_bcmr(&args);
+    let elapsed = start.elapsed().as_secs_f64();
+    assert!(ok, "{stderr}");
+    assert!(
+        (4.6..6.0).contains(&elapsed),
+        "10 MiB at 2 MiB/s took {elapsed:.2}s"
+    );
+}
+
+#[cfg(not(windows))]
+#[test]
+fn e2e_bwlimit_paces_a_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("ten.bin");
+    create_random_file(&src, 10 * 1024 * 1024);
+    let dst = dir.path().join("out.bin");
+
+    assert_paced_to_two_mib_per_sec(&["copy", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert_eq!(fs::read(&src).unwrap(), fs::read(&dst).unwrap());
+}
+
+#[cfg(not(windows))]
+#[test]
+fn e2e_bwlimit_is_shared_by_parallel_workers() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    for i in 0..4 {
+        create_random_file(&src.join(format!("f{i}.bin")), 10 * 1024 * 1024 / 4);
+    }
+    let dst = dir.path().join("dst");
+
+    assert_paced_to_two_mib_per_sec(&[
+        "copy",
+        "-r",
+        "--jobs",
+        "4",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert_eq!(fs::read_dir(&dst).unwrap().count(), 4);
+}
+
+/// `bcmr args` with `config` as the user's config file.
+#[cfg(unix)]
+fn run_bcmr_with_config(config: &str, args: &[&str]) -> (bool, String, String) {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join(".config/bcmr");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), config).unwrap();
+    let output = isolate(&mut Command::new(bcmr_bin()))
+        .args(args)
+        .env("HOME", home.path())
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_default_excludes_apply_unless_disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("keep.txt"), b"keep").unwrap();
+    fs::write(src.join(".DS_Store"), b"junk").unwrap();
+    fs::write(src.join("sub/notes.txt.swp"), b"junk").unwrap();
+    let config = "[filters]\ndefault_excludes = ['(^|/)\\.DS_Store$', '\\.swp$']\n";
+
+    let dst = dir.path().join("dst");
+    let (ok, _, stderr) = run_bcmr_with_config(
+        config,
+        &[
+            "copy",
+            "-r",
+            "-v",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert!(ok, "{stderr}");
+    assert!(dst.join("keep.txt").exists());
+    assert!(!dst.join(".DS_Store").exists());
+    assert!(!dst.join("sub/notes.txt.swp").exists());
+    assert!(
+        stderr.contains("2 entries skipped by default excludes"),
+        "{stderr}"
+    );
+
+    let all = dir.path().join("all");
+    let (ok, _, stderr) = run_bcmr_with_config(
+        config,
+        &[
+            "copy",
+            "-r",
+            "--no-default-excludes",
+            "-e",
+            "keep",
+            src.to_str().unwrap(),
+            all.to_str().unwrap(),
+        ],
+    );
+    assert!(ok, "{stderr}");
+    assert!(all.join(".DS_Store").exists());
+    assert!(all.join("sub/notes.txt.swp").exists());
+    assert!(!all.join("keep.txt").exists());
+}
+
+#[cfg(windows)]
+#[test]
+fn e2e_junctions_are_skipped_by_copy_and_unlinked_by_remove() {
+    let dir = tempfile::tempdir().unwrap();
+    let elsewhere = dir.path().join("elsewhere");
+    let tree = dir.path().join("tree");
+    fs::create_dir_all(&elsewhere).unwrap();
+    fs::write(elsewhere.join("inner.txt"), b"keep me").unwrap();
+    fs::create_dir(&tree).unwrap();
+    fs::write(tree.join("a.txt"), b"data").unwrap();
+    let status = Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(tree.join("link"))
+        .arg(&elsewhere)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+
+    let dst = dir.path().join("dst");
+    let (ok, _, stderr) = run_bcmr(&["copy", "-r", tree.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(ok, "{stderr}");
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"data");
+    assert!(fs::symlink_metadata(dst.join("link")).is_err());
+    assert!(stderr.contains("directory junction or link"), "{stderr}");
+
+    let (ok, _, stderr) = run_bcmr(&["remove", "-r", "-f", tree.to_str().unwrap()]);
+    assert!(ok, "{stderr}");
+    assert!(!tree.exists());
+    assert_eq!(fs::read(elsewhere.join("inner.txt")).unwrap(), b"keep me");
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_preserve_copies_a_locked_directory_with_its_mode() {
+    use std::os::unix::fs::PermissionsExt;
+    // Root reads a mode 000 directory anyway, so nothing here would need
+    // the relaxing this test is about.
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipping locked directory test: running as root");
+        return;
+    }
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+    let unlock = |path: &Path| fs::set_permissions(path, fs::Permissions::from_mode(0o755));
+
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    let locked = src.join("locked");
+    fs::create_dir_all(&locked).unwrap();
+    fs::write(locked.join("a.txt"), b"data").unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    let dst = dir.path().join("dst");
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-p",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    let (src_mode, dst_mode) = (mode(&locked), mode(&dst.join("locked")));
+    unlock(&locked).unwrap();
+    let _ = unlock(&dst.join("locked"));
+    assert!(ok, "{stderr}");
+    assert_eq!(src_mode, 0o000);
+    assert_eq!(dst_mode, 0o000);
+    assert_eq!(fs::read(dst.join("locked/a.txt")).unwrap(), b"data");
+}
+
+/// Mounts a read-only tmpfs at `dir`, which needs root; false where that fails.
+#[cfg(target_os = "linux")]
+fn mount_read_only(dir: &Path) -> bool {
+    Command::new("mount")
+        .args(["-t", "tmpfs", "-o", "ro,size=1m", "tmpfs"])
+        .arg(dir)
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn e2e_read_only_destination_fails_before_the_scan() {
+    let dir = tempfile::tempdir().unwrap();
+    let ro = dir.path().join("ro");
+    fs::create_dir(&ro).unwrap();
+    if !mount_read_only(&ro) {
+        return;
+    }
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"data").unwrap();
+
+    let copy = run_bcmr(&[
+        "copy",
+        src.to_str().unwrap(),
+        ro.join("sub/a.txt").to_str().unwrap(),
+    ]);
+    let remove = run_bcmr(&["remove", "-r", "-f", ro.to_str().unwrap()]);
+    let skipped = run_bcmr(&[
+        "copy",
+        "--no-preflight",
+        src.to_str().unwrap(),
+        ro.to_str().unwrap(),
+    ]);
+    let _ = Command::new("umount").arg(&ro).status();
+
+    let message = format!("'{}' is mounted read-only", ro.display());
+    let (ok, _, stderr) = copy;
+    assert!(!ok);
+    assert!(stderr.contains(&message), "{stderr}");
+    let (ok, _, stderr) = remove;
+    assert!(!ok);
+    assert!(stderr.contains(&message), "{stderr}");
+    let (ok, _, stderr) = skipped;
+    assert!(!ok);
+    assert!(!stderr.contains(&message), "{stderr}");
+}
+
+/// A preserved xattr that the destination (ramfs, which keeps none) cannot
+/// take: the copy finishes, warns once and exits 10 unless --warnings-ok.
+#[cfg(target_os = "linux")]
+#[test]
+fn e2e_warnings_get_their_own_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"data").unwrap();
+    xattr::set(&src, "user.origin", b"camera").unwrap();
+    let ram = dir.path().join("ram");
+    fs::create_dir(&ram).unwrap();
+    let mounted = Command::new("mount")
+        .args(["-t", "ramfs", "ramfs"])
+        .arg(&ram)
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !mounted {
+        return;
+    }
+
+    let copy = |extra: &[&str], name: &str| {
+        isolate(&mut Command::new(bcmr_bin()))
+            .args(["copy", "-p", "--progress", "plain", "--progress-fd", "2"])
+            .args(extra)
+            .arg(&src)
+            .arg(ram.join(name))
+            .output()
+            .expect("failed to execute bcmr")
+    };
+    let warned = copy(&[], "b.txt");
+    let accepted = copy(&["--warnings-ok"], "c.txt");
+    let copied = fs::read(ram.join("b.txt"));
+    let _ = Command::new("umount").arg(&ram).status();
+
+    let stderr = String::from_utf8_lossy(&warned.stderr);
+    assert_eq!(warned.status.code(), Some(10), "{stderr}");
+    assert!(
+        stderr.contains("could not preserve xattr user.origin"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Files: 1 created, 0 failed, 1 warning\n"),
+        "{stderr}"
+    );
+    assert_eq!(copied.unwrap(), b"data");
+    assert_eq!(
+        accepted.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&accepted.stderr)
+    );
+}
+
+/// Links on either side of a `--preserve` copy that point outside it: the
+/// source's is read through, the destination's is written through, and
+/// neither target's mode or mtime changes.
+#[cfg(unix)]
+#[test]
+fn e2e_preserve_leaves_link_targets_outside_the_copy_alone() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+    let mtime =
+        |path: &Path| filetime::FileTime::from_last_modification_time(&fs::metadata(path).unwrap());
+    let old = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+
+    let dir = tempfile::tempdir().unwrap();
+    let outside = dir.path().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let target = outside.join("target.txt");
+    fs::write(&target, b"external").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+    filetime::set_file_mtime(&target, old).unwrap();
+    fs::set_permissions(&outside, fs::Permissions::from_mode(0o750)).unwrap();
+    filetime::set_file_mtime(&outside, old).unwrap();
+
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o700)).unwrap();
+    symlink(&target, src.join("link.txt")).unwrap();
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(dst.join("src")).unwrap();
+    symlink(&outside, dst.join("src/sub")).unwrap();
+
+    let (ok, _, stderr) = run_bcmr(&[
+        "copy",
+        "-r",
+        "-p",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "{stderr}");
+    assert_eq!(fs::read(dst.join("src/link.txt")).unwrap(), b"external");
+    assert!(!fs::symlink_metadata(dst.join("src/link.txt"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(mode(&target), 0o640);
+    assert_eq!(mtime(&target), old);
+    assert_eq!(mode(&outside), 0o750);
+    assert_eq!(mtime(&outside), old);
+}
+
+/// The `--stats` block, without its timing line.
+fn stats_block(progress: &str) -> String {
+    let start = progress
+        .find("Files considered:")
+        .unwrap_or_else(|| panic!("no stats block: {progress}"));
+    progress[start..]
+        .lines()
+        .take_while(|line| !line.starts_with("Elapsed:"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn e2e_stats_block_counts_a_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    create_random_file(&src.join("a.bin"), 1000);
+    create_random_file(&src.join("sub/b.bin"), 2000);
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(dst.join("src")).unwrap();
+    fs::write(dst.join("src/a.bin"), b"old").unwrap();
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-r",
+        "-f",
+        "--yes",
+        "--reflink=disable",
+        "--progress",
+        "plain",
+        "--stats",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    assert_eq!(
+        stats_block(&progress),
+        "Files considered: 2\n\
+         Created:          1\n\
+         Updated:          1\n\
+         Skipped:          0\n\
+         Deleted:          0\n\
+         Metadata only:    0\n\
+         Total file size:  2.93 KiB (3,000 bytes)\n\
+         Transferred:      2.93 KiB (3,000 bytes)\n  \
+         literal:        2.93 KiB (3,000 bytes)\n  \
+         reflinked:      0 B (0 bytes)\n  \
+         hardlinked:     0 B (0 bytes)"
+    );
+    let done = progress.find("Copy done:").unwrap();
+    assert!(done < progress.find("Files considered:").unwrap());
+}
+
+#[test]
+fn e2e_stats_block_for_move_and_remove() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("sub")).unwrap();
+    create_random_file(&src.join("a.bin"), 1000);
+    create_random_file(&src.join("sub/b.bin"), 2000);
+    let moved = dir.path().join("moved");
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "move",
+        "-r",
+        "--progress",
+        "plain",
+        "--stats",
+        src.to_str().unwrap(),
+        moved.to_str().unwrap(),
+    ]);
+    assert!(ok, "move failed: {progress}");
+    let block = stats_block(&progress);
+    assert!(block.contains("\nMoved:            1\n"), "{block}");
+    assert!(!block.contains("Deleted:"), "{block}");
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "remove",
+        "-r",
+        "-y",
+        "--progress",
+        "plain",
+        "--stats",
+        moved.to_str().unwrap(),
+    ]);
+    assert!(ok, "remove failed: {progress}");
+    assert_eq!(
+        stats_block(&progress),
+        "Files considered: 4\n\
+         Skipped:          0\n\
+         Deleted:          4\n\
+         Total file size:  2.93 KiB (3,000 bytes)"
+    );
+}
+
+#[test]
+fn e2e_no_stats_block_without_the_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.bin");
+    create_random_file(&src, 100);
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "--progress",
+        "plain",
+        src.to_str().unwrap(),
+        dir.path().join("b.bin").to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    assert!(!progress.contains("Files considered:"), "{progress}");
+}
+
+/// A resumed copy whose data is already there but whose mode is not:
+/// `-p` only sets the mode, and the block counts it as metadata only.
+#[cfg(unix)]
+#[test]
+fn e2e_stats_count_attribute_only_updates() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.bin");
+    create_random_file(&src, 1000);
+    // Whole seconds, which `-p` carries over exactly.
+    filetime::set_file_mtime(&src, filetime::FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+    let dst = dir.path().join("b.bin");
+    let (ok, _, stderr) = run_bcmr(&["copy", "-p", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(ok, "{stderr}");
+    fs::set_permissions(&src, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let (ok, _, progress) = run_bcmr_progress(&[
+        "copy",
+        "-p",
+        "--resume",
+        "--progress",
+        "plain",
+        "--stats",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert!(ok, "copy failed: {progress}");
+    let block = stats_block(&progress);
+    assert!(block.contains("\nSkipped:          1\n"), "{block}");
+    assert!(block.contains("\nMetadata only:    1\n"), "{block}");
+    assert!(
+        block.contains("\nTransferred:      0 B (0 bytes)\n"),
+        "{block}"
+    );
+    assert_eq!(
+        fs::metadata(&dst).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_doctor(home: &Path, dir: &Path) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(["doctor", dir.to_str().unwrap()])
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn line<'a>(stdout: &'a str, name: &str) -> &'a str {
+    stdout
+        .lines()
+        .find(|l| l.split_whitespace().any(|word| word == name))
+        .unwrap_or_else(|| panic!("no {name} line in: {stdout}"))
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_doctor_reports_each_check_and_leaves_no_trace() {
+    let home = tempfile::tempdir().unwrap();
+    let probe = tempfile::tempdir().unwrap();
+
+    let (code, stdout, stderr) = run_doctor(home.path(), probe.path());
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    for name in [
+        "version", "config", "terminal", "reflink", "sparse", "shell",
+    ] {
+        assert!(!line(&stdout, name).contains("FAIL"), "{stdout}");
+    }
+    assert!(line(&stdout, "config").contains("using defaults"));
+    assert_eq!(fs::read_dir(probe.path()).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_doctor_fails_on_a_broken_config() {
+    let home = tempfile::tempdir().unwrap();
+    let probe = tempfile::tempdir().unwrap();
+    let config = home.path().join(".config/bcmr");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[progress]\nrefresh_ms = \"soon\"\n",
+    )
+    .unwrap();
+
+    let (code, stdout, _) = run_doctor(home.path(), probe.path());
+    assert_eq!(code, Some(1));
+    let config_line = line(&stdout, "config");
+    assert!(config_line.contains("FAIL"), "{stdout}");
+    assert!(config_line.contains("refresh_ms"), "{stdout}");
+}
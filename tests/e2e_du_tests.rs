@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn make_tree(root: &Path) {
+    fs::create_dir_all(root.join("sub/deep")).unwrap();
+    fs::write(root.join("a.txt"), vec![b'a'; 1000]).unwrap();
+    fs::write(root.join("sub/b.bin"), vec![7u8; 2048]).unwrap();
+    fs::write(root.join("sub/deep/c.log"), vec![b'c'; 500]).unwrap();
+}
+
+#[test]
+fn e2e_du_json_totals_match_the_fixture() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    make_tree(&root);
+    let root_str = root.to_str().unwrap();
+
+    let (code, stdout, stderr) = run_bcmr(&["du", "--json", "-r", "--files", "1", root_str]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    let v: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(v["total_bytes"], 3548);
+    assert_eq!(v["paths"][0]["files"], 3);
+    assert_eq!(v["largest"][0]["bytes"], 2048);
+
+    // The same exclude a copy would be given.
+    let (code, stdout, _) = run_bcmr(&["du", "--json", "-r", "-e", r"\.log$", root_str]);
+    assert_eq!(code, Some(0));
+    let v: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(v["total_bytes"], 3048);
+}
+
+#[test]
+fn e2e_du_human_breakdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    make_tree(&root);
+
+    let (code, stdout, stderr) = run_bcmr(&["du", "-r", "-d", "1", root.to_str().unwrap()]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "got: {stdout}");
+    assert!(lines[0].contains("2.49 KiB") && lines[0].ends_with("sub"));
+    assert!(lines[1].contains("3.46 KiB") && lines[1].ends_with("tree"));
+
+    let (code, _, stderr) = run_bcmr(&["du", root.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("Use -r"), "stderr: {stderr}");
+}
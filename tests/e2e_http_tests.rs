@@ -0,0 +1,277 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    path
+}
+
+fn run_bcmr(dir: &Path, args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .env("HOME", dir)
+        .env_remove("XDG_CONFIG_HOME")
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("NO_PROXY", "*")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn body() -> Vec<u8> {
+    (0..200_000u32).map(|i| (i % 251) as u8).collect()
+}
+
+/// A one-request-per-connection HTTP/1.1 server on a free port:
+/// `/file.bin` honors `Range: bytes=N-`, `/norange.bin` ignores it,
+/// `/nolength.bin` sends no Content-Length, `/auth` wants a bearer token,
+/// `/secret` is 403 and anything else 404.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(move || respond(stream));
+        }
+    });
+    format!("http://{}", addr)
+}
+
+fn respond(mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    let (mut range, mut auth) = (None, None);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        match name.to_ascii_lowercase().as_str() {
+            "range" => range = value.trim().strip_prefix("bytes=").map(str::to_string),
+            "authorization" => auth = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+
+    let body = body();
+    let len = body.len();
+    let (status, headers, payload): (&str, String, &[u8]) = match path.as_str() {
+        "/file.bin" => match range.and_then(|r| r.trim_end_matches('-').parse::<usize>().ok()) {
+            Some(start) if start >= len => (
+                "416 Range Not Satisfiable",
+                format!("Content-Range: bytes */{len}\r\nContent-Length: 0\r\n"),
+                &[],
+            ),
+            Some(start) => (
+                "206 Partial Content",
+                format!(
+                    "Content-Range: bytes {start}-{}/{len}\r\nContent-Length: {}\r\n",
+                    len - 1,
+                    len - start
+                ),
+                &body[start..],
+            ),
+            None => ("200 OK", format!("Content-Length: {len}\r\n"), &body),
+        },
+        "/norange.bin" => ("200 OK", format!("Content-Length: {len}\r\n"), &body),
+        "/nolength.bin" => ("200 OK", String::new(), &body),
+        "/auth" if auth.as_deref() == Some("Bearer t0k") => {
+            ("200 OK", "Content-Length: 2\r\n".to_string(), b"ok")
+        }
+        "/auth" => ("401 Unauthorized", "Content-Length: 0\r\n".to_string(), &[]),
+        "/secret" => ("403 Forbidden", "Content-Length: 0\r\n".to_string(), &[]),
+        _ => ("404 Not Found", "Content-Length: 0\r\n".to_string(), &[]),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\n{headers}Connection: close\r\n\r\n"
+    );
+    let _ = stream.write_all(payload);
+}
+
+#[test]
+fn test_download_into_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let out = dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let url = format!("{base}/file.bin");
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &url, out.to_str().unwrap()]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(out.join("file.bin")).unwrap(), body());
+    assert!(!out.join(".file.bin.bcmr.tmp").exists());
+
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &url, out.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("already exists"), "{stderr}");
+}
+
+#[test]
+fn test_download_without_content_length() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let dst = dir.path().join("renamed.bin");
+
+    let url = format!("{base}/nolength.bin");
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &url, dst.to_str().unwrap()]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(&dst).unwrap(), body());
+}
+
+#[test]
+fn test_resume_continues_with_a_range_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let dst = dir.path().join("file.bin");
+    // A tail the server will not send again proves the range was used.
+    let mut partial = body()[..50_000].to_vec();
+    fs::write(&dst, &partial).unwrap();
+
+    let url = format!("{base}/file.bin");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &["copy", "--resume", &url, dst.to_str().unwrap()],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    partial.extend_from_slice(&body()[50_000..]);
+    assert_eq!(fs::read(&dst).unwrap(), partial);
+
+    // Already whole: the server answers 416 and nothing changes.
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &["copy", "--resume", &url, dst.to_str().unwrap()],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(&dst).unwrap(), body());
+}
+
+#[test]
+fn test_resume_restarts_when_range_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let dst = dir.path().join("norange.bin");
+    fs::write(&dst, b"stale partial").unwrap();
+
+    let url = format!("{base}/norange.bin");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &["copy", "--resume", &url, dst.to_str().unwrap()],
+    );
+    // The restart is a warning, which the exit status reports.
+    assert_eq!(code, Some(10), "{stderr}");
+    assert!(stderr.contains("ignored the range request"), "{stderr}");
+    assert_eq!(fs::read(&dst).unwrap(), body());
+}
+
+#[test]
+fn test_expected_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let hash = blake3::hash(&body()).to_hex().to_uppercase();
+
+    let url = format!("{base}/file.bin");
+    let dst = dir.path().join("good.bin");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--expected-hash",
+            &hash,
+            &url,
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert!(dst.exists());
+
+    let dst = dir.path().join("bad.bin");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--expected-hash",
+            "00ff",
+            &url,
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("Verification failed"), "{stderr}");
+    assert!(!dst.exists());
+
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", "-V", &url, dst.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("--expected-hash"), "{stderr}");
+}
+
+#[test]
+fn test_header_is_sent() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let url = format!("{base}/auth");
+    let dst = dir.path().join("auth.txt");
+
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &url, dst.to_str().unwrap()]);
+    assert_eq!(code, Some(4), "{stderr}");
+    assert!(stderr.contains("HTTP 401"), "{stderr}");
+
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--header",
+            "Authorization: Bearer t0k",
+            &url,
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(&dst).unwrap(), b"ok");
+}
+
+#[test]
+fn test_http_errors_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = serve();
+    let dst = dir.path().join("out.bin");
+    let dst = dst.to_str().unwrap();
+
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &format!("{base}/missing"), dst]);
+    assert_eq!(code, Some(5), "{stderr}");
+    assert!(stderr.contains("HTTP 404 Not Found"), "{stderr}");
+
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &format!("{base}/secret"), dst]);
+    assert_eq!(code, Some(4), "{stderr}");
+    assert!(stderr.contains("HTTP 403 Forbidden"), "{stderr}");
+
+    // Bound and dropped: nothing listens there any more.
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (code, stderr) = run_bcmr(dir.path(), &["copy", &format!("http://{closed}/x"), dst]);
+    assert_eq!(code, Some(3), "{stderr}");
+    assert!(!Path::new(dst).exists());
+}
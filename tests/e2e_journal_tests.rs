@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+/// Runs bcmr with its undo journal kept out of the real state directory.
+fn run_bcmr(state: &Path, args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .env("XDG_STATE_HOME", state)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn records(path: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn e2e_journal_records_header_actions_verify_and_footer() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.txt"), b"aaa").unwrap();
+    fs::write(src.join("sub/b.txt"), b"bb").unwrap();
+    let journal = dir.path().join("audit.jsonl");
+    let s = |p: &Path| p.to_str().unwrap().to_string();
+
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &[
+            "copy",
+            "-r",
+            "--verify",
+            "--journal",
+            &s(&journal),
+            &s(&src),
+            &s(&dst),
+        ],
+    );
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+
+    let lines = records(&journal);
+    assert_eq!(lines[0]["type"], "header");
+    assert_eq!(lines[0]["version"], 1);
+    assert!(lines[0]["args"].as_array().unwrap().len() >= 5);
+
+    let created: Vec<&serde_json::Value> = lines
+        .iter()
+        .filter(|l| l["type"] == "action" && l["action"] == "create")
+        .collect();
+    assert_eq!(created.len(), 2, "{lines:?}");
+    let a = created
+        .iter()
+        .find(|l| l["path"].as_str().unwrap().ends_with("a.txt"))
+        .unwrap();
+    assert_eq!(a["size"], 3);
+    assert_eq!(a["outcome"], "ok");
+    assert!(a["src"].as_str().unwrap().ends_with("src/a.txt"));
+
+    let verified = lines.iter().filter(|l| l["type"] == "verify").count();
+    assert_eq!(verified, 2, "{lines:?}");
+
+    let footer = lines.last().unwrap();
+    assert_eq!(footer["type"], "footer");
+    assert_eq!(footer["outcome"], "ok");
+    assert_eq!(footer["stats"]["created"], 2);
+
+    // A second run appends a journal of its own after the first.
+    let (code, _, _) = run_bcmr(
+        &state,
+        &[
+            "remove",
+            "-y",
+            "--journal",
+            &s(&journal),
+            &s(&dst.join("a.txt")),
+        ],
+    );
+    assert_eq!(code, Some(0));
+    let lines = records(&journal);
+    let headers = lines.iter().filter(|l| l["type"] == "header").count();
+    assert_eq!(headers, 2);
+    assert!(lines
+        .iter()
+        .any(|l| l["type"] == "action" && l["action"] == "remove"));
+
+    let (code, stdout, stderr) = run_bcmr(&state, &["journal", "show", &s(&journal)]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert!(stdout.contains("create"), "{stdout}");
+    assert!(stdout.contains("verified"), "{stdout}");
+    assert!(stdout.contains("2 created"), "{stdout}");
+    assert!(stdout.contains("1 removed"), "{stdout}");
+}
+
+#[test]
+fn e2e_journal_that_cannot_be_opened_stops_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"a").unwrap();
+    let dst = dir.path().join("b.txt");
+    let journal = dir.path().join("missing/audit.jsonl");
+
+    let (code, _, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--journal",
+            journal.to_str().unwrap(),
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_ne!(code, Some(0));
+    assert!(stderr.contains("cannot open journal"), "{stderr}");
+    assert!(!dst.exists());
+}
+
+#[test]
+fn e2e_journal_show_rejects_a_corrupt_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = dir.path().join("audit.jsonl");
+    fs::write(&journal, "not json\n").unwrap();
+    let (code, _, stderr) = run_bcmr(dir.path(), &["journal", "show", journal.to_str().unwrap()]);
+    assert_ne!(code, Some(0));
+    assert!(stderr.contains("line 1"), "{stderr}");
+}
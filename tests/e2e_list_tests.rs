@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn make_tree(root: &Path) {
+    fs::create_dir_all(root.join("src/cache")).unwrap();
+    fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+    fs::write(root.join("src/debug.log"), b"log").unwrap();
+    fs::write(root.join("src/cache/blob"), vec![0u8; 100]).unwrap();
+    fs::write(root.join("README"), b"readme").unwrap();
+}
+
+#[test]
+fn e2e_list_nul_separated_paths_skip_excluded() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    make_tree(&root);
+
+    let (code, stdout, stderr) = run_bcmr(&[
+        "list",
+        "-r",
+        "-0",
+        "-e",
+        r"\.log$",
+        "-e",
+        "/cache$",
+        root.to_str().unwrap(),
+    ]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    let mut paths: Vec<&str> = stdout.split('\0').filter(|p| !p.is_empty()).collect();
+    paths.sort();
+    let expected: Vec<String> = ["README", "src", "src/main.rs"]
+        .iter()
+        .map(|p| root.join(p).to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(paths, expected);
+}
+
+#[test]
+fn e2e_list_excluded_names_the_pattern_and_count_totals() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    make_tree(&root);
+    let root_str = root.to_str().unwrap();
+
+    let (code, stdout, stderr) = run_bcmr(&[
+        "list",
+        "-r",
+        "--excluded",
+        "-e",
+        r"\.log$",
+        "-e",
+        "/cache$",
+        root_str,
+    ]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines.len(), 2, "got: {stdout}");
+    assert!(
+        lines[0].ends_with(r"cache  (excluded by '/cache$')"),
+        "got: {stdout}"
+    );
+    assert!(
+        lines[1].ends_with(r"debug.log  (excluded by '\.log$')"),
+        "got: {stdout}"
+    );
+
+    let (code, stdout, _) = run_bcmr(&["list", "-r", "--count", "-e", "/cache$", root_str]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.trim(), "3 files, 1 directories, 21 B");
+
+    let (code, _, _) = run_bcmr(&["list", dir.path().join("missing").to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+/// bcmr with its locks kept in `state`.
+fn bcmr(state: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new(bcmr_bin());
+    cmd.args(args).env("XDG_STATE_HOME", state);
+    cmd
+}
+
+fn run_bcmr(state: &Path, args: &[&str]) -> (Option<i32>, String, String) {
+    let output = bcmr(state, args).output().expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn locks(state: &Path) -> usize {
+    fs::read_dir(state.join("bcmr/locks")).map_or(0, |d| d.count())
+}
+
+/// A slow copy of `src` into `dst`, returned once it holds the lock.
+fn start_slow_copy(state: &Path, src: &Path, dst: &Path) -> Child {
+    let child = bcmr(
+        state,
+        &[
+            "copy",
+            "-r",
+            "--test-mode",
+            "delay:300",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    )
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while locks(state) == 0 {
+        assert!(
+            Instant::now() < deadline,
+            "the first copy never took the lock"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+fn tree(dir: &Path) -> (PathBuf, PathBuf) {
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    fs::create_dir(&src).unwrap();
+    fs::create_dir(&dst).unwrap();
+    for i in 0..8 {
+        fs::write(src.join(format!("f{i}.txt")), b"data").unwrap();
+    }
+    (src, dst)
+}
+
+#[test]
+fn e2e_second_run_on_a_locked_destination_fails_fast() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = tree(dir.path());
+    let other = dir.path().join("other.txt");
+    fs::write(&other, b"other").unwrap();
+
+    let mut first = start_slow_copy(&state, &src, &dst);
+    // Another spelling of the same destination.
+    let same = dir.path().join("src/../dst");
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &["copy", other.to_str().unwrap(), same.to_str().unwrap()],
+    );
+    assert_ne!(code, Some(0));
+    assert!(
+        stderr.contains(&format!("in use by bcmr pid {}", first.id())),
+        "{stderr}"
+    );
+    assert!(stderr.contains("--wait-lock"), "{stderr}");
+    assert!(!dst.join("other.txt").exists());
+
+    // A different destination is not held up.
+    let elsewhere = dir.path().join("elsewhere.txt");
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &["copy", other.to_str().unwrap(), elsewhere.to_str().unwrap()],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+
+    assert!(first.wait().unwrap().success());
+    assert_eq!(locks(&state), 0);
+}
+
+#[test]
+fn e2e_wait_lock_runs_after_the_holder_finishes() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = tree(dir.path());
+    let other = dir.path().join("other.txt");
+    fs::write(&other, b"other").unwrap();
+
+    let mut first = start_slow_copy(&state, &src, &dst);
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &[
+            "copy",
+            "--wait-lock",
+            "60s",
+            other.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert!(stderr.contains("Waiting for bcmr pid"), "{stderr}");
+    // The holder had finished by the time the waiting run got the lock.
+    assert!(first.try_wait().unwrap().is_some());
+    assert_eq!(fs::read(dst.join("other.txt")).unwrap(), b"other");
+    assert_eq!(fs::read_dir(dst.join("src")).unwrap().count(), 8);
+    assert_eq!(locks(&state), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn e2e_lock_is_released_on_sigterm() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = tree(dir.path());
+
+    let mut first = start_slow_copy(&state, &src, &dst);
+    unsafe { libc::kill(first.id() as libc::pid_t, libc::SIGTERM) };
+    assert_eq!(first.wait().unwrap().code(), Some(143));
+    assert_eq!(locks(&state), 0);
+
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &[
+            "copy",
+            "-r",
+            "-f",
+            "-y",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+}
@@ -0,0 +1,294 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    path
+}
+
+/// An `ssh` that runs every remote command on this machine, so `host1:` and
+/// `host2:` are both the local filesystem. Connections to `down` fail the
+/// way a refused one does. With `serve`, the "remote" side has a `bcmr` to
+/// run the fast path; without, it has none and copies fall back to legacy.
+fn fake_ssh(dir: &Path, serve: bool) -> PathBuf {
+    let bin = dir.join(if serve { "bin-serve" } else { "bin-legacy" });
+    fs::create_dir_all(&bin).unwrap();
+    let ssh = bin.join("ssh");
+    fs::write(
+        &ssh,
+        "#!/bin/sh\n\
+         while [ \"$1\" = -o ]; do shift 2; done\n\
+         if [ \"$1\" = down ]; then\n\
+         echo 'ssh: connect to host down port 22: Connection refused' >&2; exit 255\n\
+         fi\n\
+         shift\n\
+         exec sh -c \"$*\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    let bcmr = bin.join("bcmr");
+    if serve {
+        std::os::unix::fs::symlink(bcmr_bin(), &bcmr).unwrap();
+    } else {
+        fs::write(&bcmr, "#!/bin/sh\nexit 127\n").unwrap();
+        fs::set_permissions(&bcmr, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    bin
+}
+
+fn run_bcmr(dir: &Path, args: &[&str]) -> (Option<i32>, String, String) {
+    run_bcmr_with(dir, false, args)
+}
+
+/// Runs bcmr with `dir` as its home, which is also the serve root.
+fn run_bcmr_with(dir: &Path, serve: bool, args: &[&str]) -> (Option<i32>, String, String) {
+    let bin = dir.join(if serve { "bin-serve" } else { "bin-legacy" });
+    if !bin.exists() {
+        fake_ssh(dir, serve);
+    }
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .env("PATH", path)
+        .env("HOME", dir)
+        .env_remove("XDG_CONFIG_HOME")
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+/// The error bcmr exited with, without whatever came before or after it.
+fn error_line(stderr: &str) -> &str {
+    stderr
+        .lines()
+        .find(|l| l.starts_with("Error:"))
+        .unwrap_or_else(|| panic!("no error in {stderr}"))
+}
+
+/// Runs `args` over both transports, checks they refuse the same way, and
+/// returns the refusal.
+fn refused_by_both(dir: &Path, args: &[&str]) -> String {
+    let (fast_code, _, fast_err) = run_bcmr_with(dir, true, args);
+    let (legacy_code, _, legacy_err) = run_bcmr_with(dir, false, args);
+    assert_eq!(fast_code, Some(1), "{fast_err}");
+    assert_eq!(legacy_code, Some(1), "{legacy_err}");
+    assert_eq!(error_line(&fast_err), error_line(&legacy_err));
+    error_line(&fast_err).to_string()
+}
+
+fn on(host: &str, path: &Path) -> String {
+    format!("{}:{}", host, path.display())
+}
+
+#[test]
+fn e2e_relay_copies_a_file_between_hosts() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("a.txt"), dir.path().join("b"));
+    fs::write(&src, b"relayed").unwrap();
+    fs::create_dir(&dst).unwrap();
+
+    let (code, _, err) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            &on("host1", &src),
+            &format!("{}/", on("host2", &dst)),
+        ],
+    );
+    assert_eq!(code, Some(0), "{err}");
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"relayed");
+}
+
+#[test]
+fn e2e_relay_copies_a_directory_between_hosts() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("tree");
+    fs::create_dir_all(src.join("sub/empty")).unwrap();
+    fs::write(src.join("top.txt"), b"top").unwrap();
+    fs::write(src.join("sub/deep.txt"), b"deep").unwrap();
+    let dst = dir.path().join("mirror");
+
+    let (code, _, err) = run_bcmr(
+        dir.path(),
+        &["copy", "-r", &on("host1", &src), &on("host2", &dst)],
+    );
+    assert_eq!(code, Some(0), "{err}");
+    assert_eq!(fs::read(dst.join("top.txt")).unwrap(), b"top");
+    assert_eq!(fs::read(dst.join("sub/deep.txt")).unwrap(), b"deep");
+    assert!(dst.join("sub/empty").is_dir());
+}
+
+#[test]
+fn e2e_relay_missing_source_names_host_and_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("nope.txt");
+    let dst = dir.path().join("b");
+    fs::create_dir(&dst).unwrap();
+
+    let (code, out, err) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            &on("host1", &missing),
+            &format!("{}/", on("host2", &dst)),
+        ],
+    );
+    assert_eq!(code, Some(5), "{err}");
+    assert!(err.contains(&on("host1", &missing)), "{err}");
+    assert!(!out.contains("Done") && !err.contains("Done"), "{out}{err}");
+    assert_eq!(fs::read_dir(&dst).unwrap().count(), 0);
+}
+
+#[test]
+fn e2e_relay_unreachable_host_exits_with_connection_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"a").unwrap();
+
+    let (code, _, err) = run_bcmr(
+        dir.path(),
+        &["copy", &on("host1", &src), &on("down", dir.path())],
+    );
+    assert_eq!(code, Some(3), "{err}");
+}
+
+#[test]
+fn e2e_no_remote_relay_refuses_with_a_suggestion() {
+    let dir = tempfile::tempdir().unwrap();
+    let (code, _, err) = run_bcmr(
+        dir.path(),
+        &["copy", "--no-remote-relay", "down:a", "down:b/"],
+    );
+    assert_eq!(code, Some(1), "{err}");
+    assert!(err.contains("--no-remote-relay"), "{err}");
+    assert!(
+        err.contains("ssh down cat 'a' | ssh down 'cat > b/a'"),
+        "{err}"
+    );
+}
+
+#[test]
+fn e2e_remote_destination_with_parent_segments_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"a").unwrap();
+    fs::create_dir(dir.path().join("dst")).unwrap();
+
+    let dest = format!("{}/dst/../escaped.txt", on("host1", dir.path()));
+    let err = refused_by_both(dir.path(), &["copy", src.to_str().unwrap(), &dest]);
+    assert!(err.contains("'..' is not allowed"), "{err}");
+    assert!(!dir.path().join("escaped.txt").exists());
+}
+
+#[test]
+fn e2e_upload_refuses_to_write_through_a_destination_symlink() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = dir.path().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let tree = dir.path().join("tree");
+    fs::create_dir_all(tree.join("link")).unwrap();
+    fs::write(tree.join("ok.txt"), b"ok").unwrap();
+    fs::write(tree.join("link/payload.txt"), b"payload").unwrap();
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(dst.join("tree")).unwrap();
+    std::os::unix::fs::symlink(&outside, dst.join("tree/link")).unwrap();
+
+    let err = refused_by_both(
+        dir.path(),
+        &[
+            "copy",
+            "-r",
+            tree.to_str().unwrap(),
+            &format!("{}/", on("host1", &dst)),
+        ],
+    );
+    assert!(err.contains("is a symbolic link"), "{err}");
+    assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+    assert!(!dst.join("tree/ok.txt").exists());
+}
+
+#[test]
+fn e2e_relay_refuses_to_write_through_a_destination_symlink() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = dir.path().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let tree = dir.path().join("tree");
+    fs::create_dir_all(tree.join("link")).unwrap();
+    fs::write(tree.join("link/payload.txt"), b"payload").unwrap();
+    let dst = dir.path().join("dst");
+    fs::create_dir_all(dst.join("tree")).unwrap();
+    std::os::unix::fs::symlink(&outside, dst.join("tree/link")).unwrap();
+
+    let (code, _, err) = run_bcmr(
+        dir.path(),
+        &["copy", "-r", &on("host1", &tree), &on("host2", &dst)],
+    );
+    assert_eq!(code, Some(1), "{err}");
+    assert!(err.contains("is a symbolic link"), "{err}");
+    assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+}
+
+#[test]
+fn e2e_allowed_root_confines_remote_destinations() {
+    let dir = tempfile::tempdir().unwrap();
+    let allowed = dir.path().join("allowed");
+    let other = dir.path().join("other");
+    fs::create_dir_all(&allowed).unwrap();
+    fs::create_dir_all(&other).unwrap();
+    std::os::unix::fs::symlink(&other, allowed.join("jump")).unwrap();
+    let config = dir.path().join(".config/bcmr");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!("[scp]\nallowed_root = \"{}\"\n", allowed.display()),
+    )
+    .unwrap();
+    let src = dir.path().join("a.txt");
+    fs::write(&src, b"a").unwrap();
+    let src = src.to_str().unwrap();
+
+    let err = refused_by_both(
+        dir.path(),
+        &["copy", src, &format!("{}/", on("host1", &other))],
+    );
+    assert!(err.contains("outside the allowed root"), "{err}");
+
+    let err = refused_by_both(
+        dir.path(),
+        &["copy", src, &format!("{}/jump/", on("host1", &allowed))],
+    );
+    assert!(err.contains("outside the allowed root"), "{err}");
+    assert_eq!(fs::read_dir(&other).unwrap().count(), 0);
+
+    for serve in [true, false] {
+        let dest = allowed.join(if serve { "fast" } else { "legacy" });
+        let (code, _, err) = run_bcmr_with(
+            dir.path(),
+            serve,
+            &["copy", src, &format!("{}/", on("host1", &dest))],
+        );
+        assert_eq!(code, Some(0), "{err}");
+        assert_eq!(err.contains("falling back"), !serve, "{err}");
+        assert_eq!(fs::read(dest.join("a.txt")).unwrap(), b"a");
+    }
+}
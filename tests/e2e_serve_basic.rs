@@ -241,7 +241,7 @@ async fn serve_hash_file() {
     let file_path = dir.path().join("data.bin");
     create_file(&file_path, 4 * 1024 * 1024);
 
-    let local_hex = checksum::calculate_hash(&file_path).unwrap();
+    let local_hex = checksum::calculate_hash(&file_path, checksum::Algorithm::Blake3).unwrap();
 
     let mut client = ServeClient::connect_local().await.unwrap();
     let server_hash = client
@@ -275,8 +275,8 @@ async fn serve_get_download() {
     let data = Arc::try_unwrap(received).unwrap().into_inner().unwrap();
     fs::write(&dst, &data).unwrap();
 
-    let src_hash = checksum::calculate_hash(&src).unwrap();
-    let dst_hash = checksum::calculate_hash(&dst).unwrap();
+    let src_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
+    let dst_hash = checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap();
     assert_eq!(src_hash, dst_hash);
 
     if let Some(hash) = server_hash {
@@ -380,19 +380,25 @@ async fn serve_dedup_repeats_use_cas() {
     let dst1 = dir.path().join("dst1.bin");
     let dst2 = dir.path().join("dst2.bin");
     create_file(&src, 32 * 1024 * 1024);
-    let src_hash = checksum::calculate_hash(&src).unwrap();
+    let src_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut client = ServeClient::connect_local().await.unwrap();
     let h1 = client.put(dst1.to_str().unwrap(), &src).await.unwrap();
     client.close().await.unwrap();
     assert_eq!(bytes_to_hex(&h1), src_hash);
-    assert_eq!(checksum::calculate_hash(&dst1).unwrap(), src_hash);
+    assert_eq!(
+        checksum::calculate_hash(&dst1, checksum::Algorithm::Blake3).unwrap(),
+        src_hash
+    );
 
     let mut client = ServeClient::connect_local().await.unwrap();
     let h2 = client.put(dst2.to_str().unwrap(), &src).await.unwrap();
     client.close().await.unwrap();
     assert_eq!(bytes_to_hex(&h2), src_hash);
-    assert_eq!(checksum::calculate_hash(&dst2).unwrap(), src_hash);
+    assert_eq!(
+        checksum::calculate_hash(&dst2, checksum::Algorithm::Blake3).unwrap(),
+        src_hash
+    );
 
     std::env::remove_var("BCMR_CAS_DIR");
 }
@@ -404,7 +410,7 @@ async fn serve_get_fast_returns_no_hash_but_correct_bytes() {
     let dir = tempfile::tempdir().unwrap();
     let src = dir.path().join("fast.bin");
     create_file(&src, 8 * 1024 * 1024);
-    let src_hash = checksum::calculate_hash(&src).unwrap();
+    let src_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let received: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
     let received_clone = Arc::clone(&received);
@@ -428,7 +434,9 @@ async fn serve_get_fast_returns_no_hash_but_correct_bytes() {
     let mut tmp = std::fs::File::create(dir.path().join("fast.dst")).unwrap();
     use std::io::Write as _;
     tmp.write_all(&data).unwrap();
-    let dst_hash = checksum::calculate_hash(&dir.path().join("fast.dst")).unwrap();
+    let dst_hash =
+        checksum::calculate_hash(&dir.path().join("fast.dst"), checksum::Algorithm::Blake3)
+            .unwrap();
     assert_eq!(dst_hash, src_hash, "fast-mode download must match source");
 }
 
@@ -473,14 +481,14 @@ async fn serve_put_compressible_roundtrip() {
     let text = "function foo() { return 42; }\n".repeat(50_000);
     fs::write(&src, text.as_bytes()).unwrap();
 
-    let local_hash = checksum::calculate_hash(&src).unwrap();
+    let local_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut client = ServeClient::connect_local().await.unwrap();
     let server_hash = client.put(dst.to_str().unwrap(), &src).await.unwrap();
     client.close().await.unwrap();
 
     assert_eq!(bytes_to_hex(&server_hash), local_hash);
-    let dst_hash = checksum::calculate_hash(&dst).unwrap();
+    let dst_hash = checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap();
     assert_eq!(dst_hash, local_hash);
 }
 
@@ -491,7 +499,7 @@ async fn serve_put_upload() {
     let dst_path = dir.path().join("upload_dst.bin");
     create_file(&src, 3 * 1024 * 1024);
 
-    let local_hash = checksum::calculate_hash(&src).unwrap();
+    let local_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut client = ServeClient::connect_local().await.unwrap();
     let server_hash = client.put(dst_path.to_str().unwrap(), &src).await.unwrap();
@@ -500,7 +508,7 @@ async fn serve_put_upload() {
     assert_eq!(bytes_to_hex(&server_hash), local_hash);
     assert!(dst_path.exists(), "uploaded file should exist at dst");
 
-    let dst_hash = checksum::calculate_hash(&dst_path).unwrap();
+    let dst_hash = checksum::calculate_hash(&dst_path, checksum::Algorithm::Blake3).unwrap();
     assert_eq!(dst_hash, local_hash);
 }
 
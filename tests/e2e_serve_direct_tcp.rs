@@ -111,7 +111,7 @@ async fn serve_direct_tcp_put_get_roundtrip() {
     let src = dir.path().join("src.bin");
     let remote_dst = dir.path().join("remote_dst.bin");
     create_file(&src, 3 * 1024 * 1024);
-    let src_hash = checksum::calculate_hash(&src).unwrap();
+    let src_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut client = ServeClient::connect_direct_local().await.unwrap();
     assert!(
@@ -137,8 +137,14 @@ async fn serve_direct_tcp_put_get_roundtrip() {
     let got = Arc::try_unwrap(received).unwrap().into_inner().unwrap();
     let got_path = dir.path().join("got.bin");
     fs::write(&got_path, &got).unwrap();
-    assert_eq!(checksum::calculate_hash(&got_path).unwrap(), src_hash);
-    assert_eq!(checksum::calculate_hash(&remote_dst).unwrap(), src_hash);
+    assert_eq!(
+        checksum::calculate_hash(&got_path, checksum::Algorithm::Blake3).unwrap(),
+        src_hash
+    );
+    assert_eq!(
+        checksum::calculate_hash(&remote_dst, checksum::Algorithm::Blake3).unwrap(),
+        src_hash
+    );
 }
 
 #[tokio::test]
@@ -410,7 +416,7 @@ async fn serve_direct_tcp_pipelined_put_many_files_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("p_{i}.bin"));
         create_file(&p, 4096 + i * 64);
-        expected.push(checksum::calculate_hash(&p).unwrap());
+        expected.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -441,7 +447,10 @@ async fn serve_direct_tcp_pipelined_put_many_files_succeeds() {
     for (i, h) in hashes.iter().enumerate() {
         assert_eq!(bytes_to_hex(h), expected[i]);
         let dst = dst_dir.join(format!("p_{i}.bin"));
-        assert_eq!(checksum::calculate_hash(&dst).unwrap(), expected[i]);
+        assert_eq!(
+            checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+            expected[i]
+        );
     }
     client.close().await.unwrap();
 }
@@ -460,7 +469,7 @@ async fn serve_direct_tcp_pipelined_get_many_files_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("g_{i}.bin"));
         create_file(&p, 4096 + i * 64);
-        expected.push(checksum::calculate_hash(&p).unwrap());
+        expected.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -484,7 +493,10 @@ async fn serve_direct_tcp_pipelined_get_many_files_succeeds() {
 
     for (i, expected_hash) in expected.iter().enumerate() {
         let dst = dst_dir.join(format!("g_{i}.bin"));
-        assert_eq!(&checksum::calculate_hash(&dst).unwrap(), expected_hash);
+        assert_eq!(
+            &checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+            expected_hash
+        );
     }
 }
 
@@ -502,7 +514,7 @@ async fn serve_direct_tcp_pool_striped_put_n4_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("s_{i}.bin"));
         create_file(&p, 512 + i * 16);
-        expected.push(checksum::calculate_hash(&p).unwrap());
+        expected.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -540,7 +552,10 @@ async fn serve_direct_tcp_pool_striped_put_n4_succeeds() {
     for (i, h) in hashes.iter().enumerate() {
         assert_eq!(bytes_to_hex(h), expected[i]);
         let dst = dst_dir.join(format!("s_{i}.bin"));
-        assert_eq!(checksum::calculate_hash(&dst).unwrap(), expected[i]);
+        assert_eq!(
+            checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+            expected[i]
+        );
     }
     let total_expected: u64 = (0..n).map(|i| (512 + i * 16) as u64).sum();
     assert_eq!(*received.lock().unwrap(), total_expected);
@@ -552,7 +567,7 @@ async fn serve_direct_tcp_striped_put_single_large_file() {
     let src = dir.path().join("big.bin");
     let dst = dir.path().join("big_dst.bin");
     create_file(&src, 16 * 1024 * 1024 + 731);
-    let src_hash_hex = checksum::calculate_hash(&src).unwrap();
+    let src_hash_hex = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut pool = ServeClientPool::connect_direct_local(4).await.unwrap();
     let returned_hash = pool
@@ -567,7 +582,7 @@ async fn serve_direct_tcp_striped_put_single_large_file() {
         "pool.striped_put_file must return the whole-file BLAKE3 computed client-side",
     );
     assert_eq!(
-        checksum::calculate_hash(&dst).unwrap(),
+        checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
         src_hash_hex,
         "dst on the server side must be byte-identical to src after striped PUT",
     );
@@ -585,7 +600,7 @@ async fn serve_direct_tcp_striped_get_single_large_file() {
     let dst = dir.path().join("big_got.bin");
     create_file(&src, 16 * 1024 * 1024 + 501);
     let src_size = std::fs::metadata(&src).unwrap().len();
-    let src_hash_hex = checksum::calculate_hash(&src).unwrap();
+    let src_hash_hex = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut pool = ServeClientPool::connect_direct_local(4).await.unwrap();
     let got_hash = pool
@@ -595,7 +610,10 @@ async fn serve_direct_tcp_striped_get_single_large_file() {
     pool.close().await.unwrap();
 
     assert_eq!(bytes_to_hex(&got_hash), src_hash_hex);
-    assert_eq!(checksum::calculate_hash(&dst).unwrap(), src_hash_hex);
+    assert_eq!(
+        checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+        src_hash_hex
+    );
     assert_eq!(std::fs::metadata(&dst).unwrap().len(), src_size);
 }
 
@@ -606,7 +624,7 @@ async fn serve_direct_tcp_striped_put_truncates_existing_dst() {
     let dst = dir.path().join("dst.bin");
     std::fs::write(&dst, vec![0xEEu8; 20 * 1024 * 1024]).unwrap();
     create_file(&src, 2 * 1024 * 1024 + 17);
-    let src_hash_hex = checksum::calculate_hash(&src).unwrap();
+    let src_hash_hex = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut pool = ServeClientPool::connect_direct_local(4).await.unwrap();
     let _ = pool
@@ -621,7 +639,7 @@ async fn serve_direct_tcp_striped_put_truncates_existing_dst() {
         "dst size must match new src exactly — no stale tail bytes",
     );
     assert_eq!(
-        checksum::calculate_hash(&dst).unwrap(),
+        checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
         src_hash_hex,
         "dst content must be byte-identical to new src (no residue of old 0xEE payload)",
     );
@@ -658,7 +676,7 @@ async fn serve_direct_tcp_striped_put_tiny_file_skips_empty_buckets() {
     let src = dir.path().join("tiny.bin");
     let dst = dir.path().join("tiny_dst.bin");
     create_file(&src, 7);
-    let src_hash_hex = checksum::calculate_hash(&src).unwrap();
+    let src_hash_hex = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut pool = ServeClientPool::connect_direct_local(4).await.unwrap();
     let returned_hash = pool
@@ -668,5 +686,8 @@ async fn serve_direct_tcp_striped_put_tiny_file_skips_empty_buckets() {
     pool.close().await.unwrap();
 
     assert_eq!(bytes_to_hex(&returned_hash), src_hash_hex);
-    assert_eq!(checksum::calculate_hash(&dst).unwrap(), src_hash_hex);
+    assert_eq!(
+        checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+        src_hash_hex
+    );
 }
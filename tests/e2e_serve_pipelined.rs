@@ -24,7 +24,7 @@ async fn serve_pipelined_put_many_files_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("f_{i}.bin"));
         create_file(&p, 1024 + i * 16);
-        expected_hashes.push(checksum::calculate_hash(&p).unwrap());
+        expected_hashes.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -70,7 +70,7 @@ async fn serve_pipelined_put_many_files_succeeds() {
         assert_eq!(bytes_to_hex(h), expected_hashes[i]);
         let dst_file = dst_dir.join(format!("f_{i}.bin"));
         assert_eq!(
-            checksum::calculate_hash(&dst_file).unwrap(),
+            checksum::calculate_hash(&dst_file, checksum::Algorithm::Blake3).unwrap(),
             expected_hashes[i]
         );
     }
@@ -95,7 +95,7 @@ async fn serve_pipelined_get_many_files_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("g_{i}.bin"));
         create_file(&p, 2048 + i * 32);
-        expected_hashes.push(checksum::calculate_hash(&p).unwrap());
+        expected_hashes.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -135,7 +135,10 @@ async fn serve_pipelined_get_many_files_succeeds() {
     );
     for (i, expected) in expected_hashes.iter().enumerate() {
         let dst_file = dst_dir.join(format!("g_{i}.bin"));
-        assert_eq!(&checksum::calculate_hash(&dst_file).unwrap(), expected);
+        assert_eq!(
+            &checksum::calculate_hash(&dst_file, checksum::Algorithm::Blake3).unwrap(),
+            expected
+        );
     }
 
     let (probe_size, _, _) = client.stat(srcs[0].to_str().unwrap()).await.unwrap();
@@ -231,7 +234,7 @@ async fn serve_pool_pipelined_put_n4_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("p_{i}.bin"));
         create_file(&p, 512 + i * 8);
-        expected_hashes.push(checksum::calculate_hash(&p).unwrap());
+        expected_hashes.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -277,7 +280,10 @@ async fn serve_pool_pipelined_put_n4_succeeds() {
             "hash at index {i} must match input-order source file"
         );
         let dst = dst_dir.join(format!("p_{i}.bin"));
-        assert_eq!(checksum::calculate_hash(&dst).unwrap(), expected_hashes[i]);
+        assert_eq!(
+            checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+            expected_hashes[i]
+        );
     }
     assert_eq!(completions.load(std::sync::atomic::Ordering::Relaxed), n);
     let total_size: u64 = (0..n).map(|i| (512 + i * 8) as u64).sum();
@@ -304,7 +310,7 @@ async fn serve_pool_pipelined_get_n4_succeeds() {
     for i in 0..n {
         let p = src_dir.join(format!("g_{i}.bin"));
         create_file(&p, 1024 + i * 16);
-        expected_hashes.push(checksum::calculate_hash(&p).unwrap());
+        expected_hashes.push(checksum::calculate_hash(&p, checksum::Algorithm::Blake3).unwrap());
         srcs.push(p);
     }
 
@@ -346,7 +352,10 @@ async fn serve_pool_pipelined_get_n4_succeeds() {
     );
     for (i, expected) in expected_hashes.iter().enumerate() {
         let dst = dst_dir.join(format!("g_{i}.bin"));
-        assert_eq!(&checksum::calculate_hash(&dst).unwrap(), expected);
+        assert_eq!(
+            &checksum::calculate_hash(&dst, checksum::Algorithm::Blake3).unwrap(),
+            expected
+        );
     }
 
     pool.close().await.unwrap();
@@ -362,7 +371,7 @@ async fn serve_pool_n1_degenerate_behaves_like_single_client() {
 
     let src = src_dir.join("one.bin");
     create_file(&src, 8192);
-    let expected = checksum::calculate_hash(&src).unwrap();
+    let expected = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let files = vec![FileTransfer {
         remote: dst_dir.join("one.bin").to_string_lossy().to_string(),
@@ -379,7 +388,7 @@ async fn serve_pool_n1_degenerate_behaves_like_single_client() {
     assert_eq!(hashes.len(), 1);
     assert_eq!(bytes_to_hex(&hashes[0]), expected);
     assert_eq!(
-        checksum::calculate_hash(&dst_dir.join("one.bin")).unwrap(),
+        checksum::calculate_hash(&dst_dir.join("one.bin"), checksum::Algorithm::Blake3).unwrap(),
         expected
     );
     pool.close().await.unwrap();
@@ -0,0 +1,244 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod common;
+
+use common::isolate;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = isolate(&mut Command::new(bcmr_bin()))
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn make_tree(root: &Path) {
+    fs::create_dir_all(root.join("sub/deep")).unwrap();
+    fs::write(root.join("a.txt"), b"alpha").unwrap();
+    fs::write(root.join("sub/b.bin"), vec![7u8; 64 * 1024]).unwrap();
+    fs::write(root.join("sub/deep/c.txt"), b"gamma").unwrap();
+}
+
+fn sync(src: &Path, dst: &Path, extra: &[&str]) -> (Option<i32>, String, String) {
+    let mut args = vec!["sync"];
+    args.extend_from_slice(extra);
+    args.push(src.to_str().unwrap());
+    args.push(dst.to_str().unwrap());
+    run_bcmr(&args)
+}
+
+fn dry_run_summary(src: &Path, dst: &Path, extra: &[&str]) -> String {
+    let mut args = vec!["-n"];
+    args.extend_from_slice(extra);
+    let (code, stdout, stderr) = sync(src, dst, &args);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    stdout
+}
+
+#[test]
+fn e2e_sync_converges_and_second_run_copies_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+
+    let (code, _, stderr) = sync(&src, &dst, &[]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert_eq!(fs::read(dst.join("sub/deep/c.txt")).unwrap(), b"gamma");
+    assert_eq!(fs::read(dst.join("sub/b.bin")).unwrap().len(), 64 * 1024);
+
+    let stdout = dry_run_summary(&src, &dst, &[]);
+    assert!(
+        stdout.contains("0 to add, 0 to overwrite, 3 up to date"),
+        "got: {stdout}"
+    );
+
+    // A second real run leaves the copies alone.
+    let marker = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_atime(dst.join("a.txt"), marker).unwrap();
+    let (code, _, stderr) = sync(&src, &dst, &[]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    let atime =
+        filetime::FileTime::from_last_access_time(&fs::metadata(dst.join("a.txt")).unwrap());
+    assert_eq!(atime, marker);
+}
+
+#[test]
+fn e2e_sync_dry_run_names_each_action() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    assert_eq!(sync(&src, &dst, &[]).0, Some(0));
+
+    fs::write(src.join("new.txt"), b"new").unwrap();
+    fs::write(src.join("a.txt"), b"alpha, longer now").unwrap();
+    fs::write(dst.join("stale.txt"), b"stale").unwrap();
+
+    let stdout = dry_run_summary(&src, &dst, &["--delete"]);
+    let action = |path: &str| {
+        stdout
+            .lines()
+            .find(|l| l.trim_end().ends_with(path))
+            .and_then(|l| l.split_whitespace().next())
+            .unwrap_or_else(|| panic!("no line for {path} in: {stdout}"))
+            .to_string()
+    };
+    assert_eq!(action("new.txt"), "ADD");
+    assert_eq!(action("a.txt"), "OVERWRITE");
+    assert_eq!(action("sub/b.bin"), "SKIP");
+    assert_eq!(action("stale.txt"), "REMOVE");
+    assert!(stdout.contains("1 to add, 1 to overwrite, 2 up to date, 1 to remove"));
+
+    // Nothing changed.
+    assert!(!dst.join("new.txt").exists());
+    assert!(dst.join("stale.txt").exists());
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"alpha");
+}
+
+#[test]
+fn e2e_sync_comparison_modes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    assert_eq!(sync(&src, &dst, &[]).0, Some(0));
+
+    // Same size and the same mtime, different bytes: only a digest notices.
+    let mtime =
+        filetime::FileTime::from_last_modification_time(&fs::metadata(dst.join("a.txt")).unwrap());
+    fs::write(dst.join("a.txt"), b"ALPHA").unwrap();
+    filetime::set_file_mtime(dst.join("a.txt"), mtime).unwrap();
+
+    assert_eq!(sync(&src, &dst, &[]).0, Some(0));
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"ALPHA");
+    assert_eq!(sync(&src, &dst, &["--checksum"]).0, Some(0));
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"alpha");
+
+    // A newer mtime alone is not a change under --size-only.
+    filetime::set_file_mtime(
+        src.join("a.txt"),
+        filetime::FileTime::from_unix_time(2_000_000_000, 0),
+    )
+    .unwrap();
+    let stdout = dry_run_summary(&src, &dst, &["--size-only"]);
+    assert!(stdout.contains("0 to overwrite"), "got: {stdout}");
+    let stdout = dry_run_summary(&src, &dst, &["--mtime"]);
+    assert!(stdout.contains("1 to overwrite"), "got: {stdout}");
+
+    let (code, _, stderr) = sync(&src, &dst, &["--checksum", "--size-only"]);
+    assert_eq!(code, Some(2), "stderr: {stderr}");
+}
+
+#[test]
+fn e2e_sync_delete_removes_extras_and_keeps_excluded() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    assert_eq!(sync(&src, &dst, &[]).0, Some(0));
+
+    fs::write(dst.join("stale.txt"), b"stale").unwrap();
+    fs::create_dir_all(dst.join("old/inner")).unwrap();
+    fs::write(dst.join("old/inner/x"), b"x").unwrap();
+    fs::write(dst.join("keep.log"), b"log").unwrap();
+    // A file where the source has a directory is replaced.
+    fs::remove_dir_all(dst.join("sub/deep")).unwrap();
+    fs::write(dst.join("sub/deep"), b"not a dir").unwrap();
+
+    // Without --delete extras stay.
+    assert_eq!(sync(&src, &dst, &["-e", r"\.log$"]).0, Some(0));
+    assert!(dst.join("stale.txt").exists());
+    assert_eq!(fs::read(dst.join("sub/deep/c.txt")).unwrap(), b"gamma");
+
+    let (code, _, stderr) = sync(&src, &dst, &["--delete", "-e", r"\.log$"]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert!(!dst.join("stale.txt").exists());
+    assert!(!dst.join("old").exists());
+    assert!(dst.join("keep.log").exists());
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"alpha");
+}
+
+#[test]
+fn e2e_sync_delete_refuses_an_empty_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    fs::create_dir(&src).unwrap();
+    make_tree(&dst);
+
+    let (code, _, stderr) = sync(&src, &dst, &["--delete"]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("--force-delete"), "stderr: {stderr}");
+    assert!(dst.join("sub/deep/c.txt").exists());
+
+    let (code, _, stderr) = sync(&src, &dst, &["--delete", "--force-delete"]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert!(dst.exists());
+    assert_eq!(fs::read_dir(&dst).unwrap().count(), 0);
+
+    // A missing source is an error, not an empty one.
+    let (code, _, _) = sync(&dir.path().join("nope"), &dst, &["--delete"]);
+    assert_eq!(code, Some(1));
+}
+
+/// A source directory the scan cannot read says nothing about what its
+/// destination counterpart should hold, so --delete leaves that alone.
+#[cfg(unix)]
+#[test]
+fn e2e_sync_delete_keeps_what_is_under_an_unreadable_source_directory() {
+    use std::os::unix::fs::PermissionsExt;
+    // Root reads a mode 000 directory anyway.
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("skipping unreadable source test: running as root");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    fs::create_dir_all(src.join("locked")).unwrap();
+    fs::write(src.join("locked/b"), b"b").unwrap();
+    assert_eq!(sync(&src, &dst, &[]).0, Some(0));
+    fs::create_dir_all(dst.join("locked/deep")).unwrap();
+    fs::write(dst.join("stale.txt"), b"stale").unwrap();
+
+    let set_mode = |path: &Path, mode: u32| {
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(mode));
+    };
+    set_mode(&src.join("locked"), 0o000);
+    let dry = sync(&src, &dst, &["-n", "--delete"]);
+    let run = sync(&src, &dst, &["--delete"]);
+    set_mode(&src.join("locked"), 0o755);
+    set_mode(&dst.join("locked"), 0o755);
+
+    let (_, stdout, stderr) = dry;
+    assert!(
+        stdout.contains("stale.txt"),
+        "stdout: {stdout}\nstderr: {stderr}"
+    );
+    assert!(
+        !stdout.contains("locked/"),
+        "stdout: {stdout}\nstderr: {stderr}"
+    );
+    let (code, _, stderr) = run;
+    assert_eq!(code, Some(10), "stderr: {stderr}");
+    assert!(!dst.join("stale.txt").exists());
+    assert_eq!(fs::read(dst.join("locked/b")).unwrap(), b"b");
+    assert!(dst.join("locked/deep").is_dir());
+}
@@ -0,0 +1,318 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    path
+}
+
+fn bcmr(dir: &Path, args: &[&str], stdin: Option<&[u8]>) -> Output {
+    let mut child = Command::new(bcmr_bin())
+        .args(args)
+        .env("HOME", dir)
+        .env_remove("XDG_CONFIG_HOME")
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("RUST_BACKTRACE", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute bcmr");
+    let mut pipe = child.stdin.take().unwrap();
+    if let Some(input) = stdin {
+        pipe.write_all(input).unwrap();
+    }
+    drop(pipe);
+    child.wait_with_output().unwrap()
+}
+
+fn run_bcmr(dir: &Path, args: &[&str]) -> (Option<i32>, String) {
+    let out = bcmr(dir, args, None);
+    (
+        out.status.code(),
+        String::from_utf8_lossy(&out.stderr).to_string(),
+    )
+}
+
+fn set_mtime(path: &Path, secs: i64) {
+    let t = filetime::FileTime::from_unix_time(secs, 0);
+    filetime::set_symlink_file_times(path, t, t).unwrap();
+}
+
+/// `tree/` with nested directories, an empty file, odd modes, a symlink, a
+/// hardlink pair and fixed mtimes.
+fn fixture(dir: &Path) -> PathBuf {
+    let root = dir.join("tree");
+    fs::create_dir_all(root.join("sub/deeper")).unwrap();
+    fs::write(root.join("a.txt"), b"alpha").unwrap();
+    fs::write(root.join("empty"), b"").unwrap();
+    let big: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+    fs::write(root.join("sub/big.bin"), big).unwrap();
+    fs::write(root.join("sub/deeper/run.sh"), b"#!/bin/sh\n").unwrap();
+    fs::set_permissions(
+        root.join("sub/deeper/run.sh"),
+        fs::Permissions::from_mode(0o751),
+    )
+    .unwrap();
+    fs::set_permissions(root.join("a.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+    fs::hard_link(root.join("a.txt"), root.join("sub/a-again.txt")).unwrap();
+    std::os::unix::fs::symlink("../a.txt", root.join("sub/link")).unwrap();
+
+    for (i, rel) in [
+        "a.txt",
+        "empty",
+        "sub/big.bin",
+        "sub/deeper/run.sh",
+        "sub/link",
+        "sub/deeper",
+        "sub",
+        "",
+    ]
+    .iter()
+    .enumerate()
+    {
+        set_mtime(&root.join(rel), 1_600_000_000 + i as i64 * 1000);
+    }
+    root
+}
+
+/// Every entry below `root` with what a round trip must keep: contents or
+/// link target, permission bits and mtime.
+fn describe(root: &Path) -> Vec<(PathBuf, String, u32, i64)> {
+    let mut out = Vec::new();
+    for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+        let entry = entry.unwrap();
+        let md = entry.path().symlink_metadata().unwrap();
+        let what = if md.is_symlink() {
+            format!("-> {}", fs::read_link(entry.path()).unwrap().display())
+        } else if md.is_file() {
+            format!("{:x?}", blake3::hash(&fs::read(entry.path()).unwrap()))
+        } else {
+            "dir".to_string()
+        };
+        let mode = if md.is_symlink() {
+            0
+        } else {
+            md.mode() & 0o7777
+        };
+        out.push((
+            entry.path().strip_prefix(root).unwrap().to_path_buf(),
+            what,
+            mode,
+            md.mtime(),
+        ));
+    }
+    out
+}
+
+#[test]
+fn test_tar_round_trip_keeps_content_and_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fixture(dir.path());
+    let archive = dir.path().join("tree.tar");
+
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "-r",
+            src.to_str().unwrap(),
+            archive.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert!(!dir.path().join(".tree.tar.bcmr.tmp").exists());
+
+    let out = dir.path().join("out");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--from-tar",
+            archive.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let back = out.join("tree");
+    assert_eq!(describe(&src), describe(&back));
+    let ino = |p: &Path| fs::metadata(p).unwrap().ino();
+    assert_eq!(ino(&back.join("a.txt")), ino(&back.join("sub/a-again.txt")));
+}
+
+#[test]
+fn test_tar_through_a_pipe() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fixture(dir.path());
+
+    let created = bcmr(
+        dir.path(),
+        &["copy", "-r", "--to-tar", src.to_str().unwrap(), "-"],
+        None,
+    );
+    assert!(created.status.success(), "{:?}", created);
+    assert!(!created.stdout.is_empty());
+
+    let out = dir.path().join("piped");
+    let extracted = bcmr(
+        dir.path(),
+        &["copy", "--from-tar", "-", out.to_str().unwrap()],
+        Some(&created.stdout),
+    );
+    assert!(extracted.status.success(), "{:?}", extracted);
+    assert_eq!(describe(&src), describe(&out.join("tree")));
+}
+
+#[test]
+fn test_tar_honors_exclude() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fixture(dir.path());
+    let archive = dir.path().join("tree.tar");
+
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "-r",
+            "--exclude",
+            "big\\.bin$",
+            src.to_str().unwrap(),
+            archive.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let out = dir.path().join("out");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--from-tar",
+            archive.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert!(out.join("tree/sub/deeper/run.sh").exists());
+    assert!(!out.join("tree/sub/big.bin").exists());
+}
+
+#[test]
+fn test_extract_over_existing_files_needs_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fixture(dir.path());
+    let archive = dir.path().join("tree.tar");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "-r",
+            src.to_str().unwrap(),
+            archive.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let out = dir.path().join("out");
+    fs::create_dir_all(out.join("tree")).unwrap();
+    fs::write(out.join("tree/empty"), b"local edits").unwrap();
+    let extract = |extra: &[&str]| {
+        let mut args = vec!["copy", "--from-tar"];
+        args.extend_from_slice(extra);
+        args.extend([archive.to_str().unwrap(), out.to_str().unwrap()]);
+        run_bcmr(dir.path(), &args)
+    };
+
+    let (code, stderr) = extract(&[]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("already exists"), "{stderr}");
+    assert_eq!(fs::read(out.join("tree/empty")).unwrap(), b"local edits");
+
+    let (code, stderr) = extract(&["-n"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(out.join("tree/empty")).unwrap(), b"local edits");
+
+    let (code, stderr) = extract(&["-f", "-y"]);
+    assert_eq!(code, Some(0), "{stderr}");
+    assert_eq!(fs::read(out.join("tree/empty")).unwrap(), b"");
+}
+
+#[test]
+fn test_extract_refuses_entries_outside_the_destination() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("evil.tar");
+
+    // tar::Builder will not write `..` itself, so the name goes in raw.
+    let mut header = tar::Header::new_gnu();
+    let name = b"../escaped.txt";
+    header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+    header.set_size(3);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+    builder.append(&header, &b"bad"[..]).unwrap();
+    builder.finish().unwrap();
+
+    let out = dir.path().join("out");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--from-tar",
+            archive.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("would leave the destination"), "{stderr}");
+    assert!(!dir.path().join("escaped.txt").exists());
+}
+
+#[test]
+fn test_extract_stats_count_hard_linked_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = fixture(dir.path());
+    let archive = dir.path().join("tree.tar");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "-r",
+            src.to_str().unwrap(),
+            archive.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+
+    let out = dir.path().join("out");
+    let (code, stderr) = run_bcmr(
+        dir.path(),
+        &[
+            "copy",
+            "--from-tar",
+            "--stats",
+            "--progress",
+            "plain",
+            "--progress-fd",
+            "2",
+            archive.to_str().unwrap(),
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(code, Some(0), "{stderr}");
+    assert!(
+        stderr.contains("\n  hardlinked:     5 B (5 bytes)\n"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+/// Runs bcmr with its journal kept in `state`.
+fn run_bcmr(state: &Path, args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .env("XDG_STATE_HOME", state)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn e2e_undo_reverses_the_last_move_then_the_one_before() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    fs::create_dir_all(src.join("tree/sub")).unwrap();
+    fs::create_dir(&dst).unwrap();
+    fs::write(src.join("tree/sub/a.txt"), b"a").unwrap();
+    fs::write(src.join("b.txt"), b"b").unwrap();
+    let s = |p: &Path| p.to_str().unwrap().to_string();
+
+    let tree = s(&src.join("tree"));
+    let file = s(&src.join("b.txt"));
+    assert_eq!(
+        run_bcmr(&state, &["move", "-r", &tree, &s(&dst)]).0,
+        Some(0)
+    );
+    assert_eq!(run_bcmr(&state, &["move", &file, &s(&dst)]).0, Some(0));
+    assert!(!src.join("b.txt").exists());
+
+    let (code, stdout, stderr) = run_bcmr(&state, &["undo"]);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    assert_eq!(fs::read(src.join("b.txt")).unwrap(), b"b");
+    assert!(dst.join("tree").exists());
+
+    let (code, _, stderr) = run_bcmr(&state, &["undo", "--last"]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert_eq!(fs::read(src.join("tree/sub/a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read_dir(&dst).unwrap().count(), 0);
+
+    let (code, stdout, _) = run_bcmr(&state, &["undo", "--list"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout.matches("(undone)").count(), 2, "{stdout}");
+    let (code, _, stderr) = run_bcmr(&state, &["undo"]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("Nothing left to undo"), "{stderr}");
+}
+
+#[test]
+fn e2e_undo_keeps_what_changed_since() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    fs::create_dir_all(&src).unwrap();
+    fs::create_dir(&dst).unwrap();
+    fs::write(src.join("a.txt"), b"a").unwrap();
+    fs::write(src.join("b.txt"), b"b").unwrap();
+    let s = |p: &Path| p.to_str().unwrap().to_string();
+
+    let (code, _, stderr) = run_bcmr(&state, &["copy", "-r", &s(&src), &s(&dst)]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    fs::write(dst.join("src/b.txt"), b"edited").unwrap();
+
+    let (code, stdout, stderr) = run_bcmr(&state, &["undo"]);
+    assert_eq!(code, Some(1), "stdout: {stdout}");
+    assert!(stderr.contains("changed since the copy"), "{stderr}");
+    assert!(!dst.join("src/a.txt").exists());
+    assert_eq!(fs::read(dst.join("src/b.txt")).unwrap(), b"edited");
+    assert!(dst.join("src").is_dir());
+    assert!(stdout.contains("Undid 1 of 3"), "{stdout}");
+}
+
+#[test]
+fn e2e_undo_removes_the_directories_a_copy_made() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    fs::create_dir_all(src.join("a/b/c")).unwrap();
+    fs::create_dir_all(src.join("empty")).unwrap();
+    fs::create_dir_all(dst.join("src/a")).unwrap();
+    fs::write(src.join("a/b/c/x.txt"), b"x").unwrap();
+    fs::write(src.join("a/y.txt"), b"y").unwrap();
+    let s = |p: &Path| p.to_str().unwrap().to_string();
+
+    let (code, _, stderr) = run_bcmr(&state, &["copy", "-r", &s(&src), &s(&dst)]);
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert!(dst.join("src/a/b/c/x.txt").exists());
+
+    let (code, stdout, stderr) = run_bcmr(&state, &["undo"]);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    // What was there before the copy stays; everything it made goes.
+    assert!(dst.join("src/a").is_dir());
+    assert_eq!(fs::read_dir(dst.join("src/a")).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(dst.join("src")).unwrap().count(), 1);
+}
+
+#[test]
+fn e2e_undo_journal_can_be_turned_off() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state");
+    let config = dir.path().join("home/.config/bcmr");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "[undo]\njournal = false\n").unwrap();
+    fs::write(dir.path().join("a.txt"), b"a").unwrap();
+
+    let output = Command::new(bcmr_bin())
+        .args(["copy"])
+        .arg(dir.path().join("a.txt"))
+        .arg(dir.path().join("b.txt"))
+        .env("XDG_STATE_HOME", &state)
+        .env("HOME", dir.path().join("home"))
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.path().join("b.txt").exists());
+    assert!(!state.join("bcmr/journal.jsonl").exists());
+}
+
+/// A move across filesystems copies and removes instead of renaming; undo
+/// still finds it a move, of each file, and brings each one back.
+#[cfg(target_os = "linux")]
+#[test]
+fn e2e_undo_reverses_a_move_across_filesystems() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let Ok(shm) = tempfile::tempdir_in("/dev/shm") else {
+        return;
+    };
+    let dev = |p: &Path| fs::metadata(p).unwrap().dev();
+    if dev(dir.path()) == dev(shm.path()) {
+        return;
+    }
+    let state = dir.path().join("state");
+    let src = dir.path().join("src");
+    fs::create_dir_all(src.join("tree/sub")).unwrap();
+    fs::write(src.join("tree/sub/a.txt"), b"a").unwrap();
+    fs::write(src.join("b.txt"), b"b").unwrap();
+    let s = |p: &Path| p.to_str().unwrap().to_string();
+
+    let (code, _, stderr) = run_bcmr(
+        &state,
+        &[
+            "move",
+            "-r",
+            &s(&src.join("tree")),
+            &s(&src.join("b.txt")),
+            &s(shm.path()),
+        ],
+    );
+    assert_eq!(code, Some(0), "stderr: {stderr}");
+    assert!(!src.join("b.txt").exists());
+    assert!(!src.join("tree").exists());
+
+    let (code, stdout, stderr) = run_bcmr(&state, &["undo"]);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    assert!(stdout.contains("Undid 4 of 4"), "{stdout}");
+    assert_eq!(fs::read(src.join("b.txt")).unwrap(), b"b");
+    assert_eq!(fs::read(src.join("tree/sub/a.txt")).unwrap(), b"a");
+    assert!(!shm.path().join("b.txt").exists());
+    assert!(!shm.path().join("tree").exists());
+}
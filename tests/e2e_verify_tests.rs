@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn run_bcmr(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(bcmr_bin())
+        .args(args)
+        .output()
+        .expect("failed to execute bcmr");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+fn make_tree(root: &Path) {
+    fs::create_dir_all(root.join("sub/deep")).unwrap();
+    fs::write(root.join("a.txt"), b"alpha").unwrap();
+    fs::write(root.join("sub/b.bin"), vec![7u8; 64 * 1024]).unwrap();
+    fs::write(root.join("sub/deep/c.txt"), b"gamma").unwrap();
+}
+
+fn verify(src: &Path, dst: &Path, extra: &[&str]) -> (Option<i32>, String, String) {
+    let mut args = vec!["verify", "-r"];
+    args.extend_from_slice(extra);
+    args.push(src.to_str().unwrap());
+    args.push(dst.to_str().unwrap());
+    run_bcmr(&args)
+}
+
+#[test]
+fn e2e_verify_identical_trees_exit_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    make_tree(&dst);
+
+    let (code, stdout, stderr) = verify(&src, &dst, &["--hash", "--hash-jobs", "2"]);
+    assert_eq!(code, Some(0), "stdout: {stdout}\nstderr: {stderr}");
+    assert!(
+        stdout.contains("3 files compared, 3 hashed"),
+        "got: {stdout}"
+    );
+}
+
+#[test]
+fn e2e_verify_reports_an_extra_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    make_tree(&dst);
+    fs::write(dst.join("sub/stray.log"), b"left over").unwrap();
+
+    let (code, stdout, _) = verify(&src, &dst, &[]);
+    assert_eq!(code, Some(1));
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "MISSING-SRC sub/stray.log", "got: {stdout}");
+    assert!(stdout.contains("1 missing in source"), "got: {stdout}");
+
+    // The same scope copy would use: excluding the file hides it.
+    let (code, _, _) = run_bcmr(&[
+        "-e",
+        r"\.log$",
+        "verify",
+        "-r",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ]);
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn e2e_verify_hash_finds_a_single_corrupted_byte() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    make_tree(&dst);
+    let victim = dst.join("sub/b.bin");
+    let mut data = fs::read(&victim).unwrap();
+    data[40_000] ^= 0x01;
+    fs::write(&victim, data).unwrap();
+
+    // Sizes alone cannot tell.
+    let (code, _, _) = verify(&src, &dst, &[]);
+    assert_eq!(code, Some(0));
+
+    let (code, stdout, _) = verify(&src, &dst, &["--hash"]);
+    assert_eq!(code, Some(1));
+    assert!(
+        stdout.starts_with("HASH        sub/b.bin (first difference at offset 40000)\n"),
+        "got: {stdout}"
+    );
+    assert!(stdout.contains("1 hash mismatches"), "got: {stdout}");
+
+    let (code, stdout, _) = verify(&src, &dst, &["--hash", "--json"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("\"matched\":false"), "got: {stdout}");
+    assert!(
+        stdout.contains(
+            "{\"kind\":\"hash\",\"path\":\"sub/b.bin\",\"offset\":40000,\"is_dir\":false}"
+        ),
+        "got: {stdout}"
+    );
+}
+
+#[test]
+fn e2e_verify_report_marks_the_corrupted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    make_tree(&src);
+    make_tree(&dst);
+    let victim = dst.join("sub/b.bin");
+    let mut data = fs::read(&victim).unwrap();
+    data[10] ^= 0x01;
+    fs::write(&victim, data).unwrap();
+    fs::remove_file(dst.join("a.txt")).unwrap();
+    let report = dir.path().join("report.jsonl");
+
+    let (code, _, _) = verify(
+        &src,
+        &dst,
+        &["--hash", "--verify-report", report.to_str().unwrap()],
+    );
+    assert_eq!(code, Some(1));
+
+    let text = fs::read_to_string(&report).unwrap();
+    let records: Vec<serde_json::Value> = text
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records[0]["type"], "header");
+    assert_eq!(records[0]["algorithm"], "sha256");
+    let result_of = |name: &str| {
+        records
+            .iter()
+            .find(|r| r["src"] == src.join(name).to_str().unwrap())
+            .map(|r| r["result"].as_str().unwrap().to_string())
+    };
+    assert_eq!(result_of("a.txt").as_deref(), Some("FAIL"));
+    assert_eq!(result_of("sub/b.bin").as_deref(), Some("FAIL"));
+    assert_eq!(result_of("sub/deep/c.txt").as_deref(), Some("PASS"));
+    let summary = records.last().unwrap();
+    assert_eq!(summary["type"], "summary", "{text}");
+    assert_eq!(summary["failed"], 2);
+    assert_eq!(summary["passed"], 1);
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn bcmr_bin() -> PathBuf {
+    let mut path = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    path.push("bcmr");
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    path
+}
+
+fn start_watch(dir: &Path, args: &[&str]) -> Child {
+    Command::new(bcmr_bin())
+        .arg("watch")
+        .args(["--debounce", "100ms"])
+        .args(args)
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start bcmr watch")
+}
+
+/// Every file below `root` with its contents, and every directory.
+fn snapshot(root: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+    walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|e| {
+            let rel = e.path().strip_prefix(root).unwrap().to_path_buf();
+            let data = e
+                .file_type()
+                .is_file()
+                .then(|| fs::read(e.path()).ok())
+                .flatten();
+            (rel, data)
+        })
+        .collect()
+}
+
+/// Waits for `dst` to look like `want` says, failing after ten seconds.
+fn converge(
+    child: &mut Child,
+    dst: &Path,
+    want: impl Fn(&BTreeMap<PathBuf, Option<Vec<u8>>>) -> bool,
+) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if want(&snapshot(dst)) {
+            return;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let (out, err) = output(child);
+            panic!(
+                "mirror never converged: {:?}\nstdout:\n{out}\nstderr:\n{err}",
+                snapshot(dst)
+            );
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn output(child: &mut Child) -> (String, String) {
+    let (mut out, mut err) = (String::new(), String::new());
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut out)
+        .unwrap();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut err)
+        .unwrap();
+    (out, err)
+}
+
+/// Stops the watch the way a user would, returning what it printed.
+fn stop(mut child: Child) -> (String, String) {
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+        let status = child.wait().unwrap();
+        assert_eq!(status.code(), Some(0));
+    }
+    #[cfg(not(unix))]
+    {
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    output(&mut child)
+}
+
+fn tree(dir: &Path) -> (PathBuf, PathBuf) {
+    let (src, dst) = (dir.join("src"), dir.join("dst"));
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.txt"), b"a").unwrap();
+    fs::write(src.join("sub/b.txt"), b"b").unwrap();
+    (src, dst)
+}
+
+#[test]
+fn e2e_watch_mirrors_scripted_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = tree(dir.path());
+    let mut child = start_watch(
+        dir.path(),
+        &[
+            "--delete",
+            "-e",
+            r"\.tmp$",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ],
+    );
+    converge(&mut child, &dst, |d| d == &snapshot(&src));
+
+    fs::write(src.join("new.txt"), b"new").unwrap();
+    fs::write(src.join("a.txt"), b"a, changed").unwrap();
+    fs::rename(src.join("sub"), src.join("moved")).unwrap();
+    fs::create_dir_all(src.join("deep/er")).unwrap();
+    fs::write(src.join("deep/er/c.txt"), b"c").unwrap();
+    fs::write(src.join("scratch.tmp"), b"excluded").unwrap();
+    converge(&mut child, &dst, |d| {
+        let mut want = snapshot(&src);
+        want.remove(Path::new("scratch.tmp"));
+        d == &want
+    });
+
+    fs::remove_file(src.join("new.txt")).unwrap();
+    fs::remove_dir_all(src.join("deep")).unwrap();
+    converge(&mut child, &dst, |d| {
+        !d.contains_key(Path::new("new.txt")) && !d.contains_key(Path::new("deep"))
+    });
+
+    let (out, err) = stop(child);
+    assert!(out.contains("OVERWRITE  a.txt"), "{out}");
+    assert!(out.contains("ADD        moved/"), "{out}");
+    assert!(out.contains("REMOVE     sub/"), "{out}");
+    assert!(out.contains("REMOVE     deep/"), "{out}");
+    assert!(!out.contains("scratch.tmp"), "{out}");
+    assert!(err.contains("Stopped watching"), "{err}");
+    assert!(!dst.join("scratch.tmp").exists());
+}
+
+#[test]
+fn e2e_watch_keeps_removed_files_without_delete() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, dst) = tree(dir.path());
+    fs::create_dir_all(&dst).unwrap();
+    fs::write(dst.join("extra.txt"), b"only here").unwrap();
+    let mut child = start_watch(dir.path(), &[src.to_str().unwrap(), dst.to_str().unwrap()]);
+    converge(&mut child, &dst, |d| d.contains_key(Path::new("sub/b.txt")));
+
+    fs::remove_file(src.join("a.txt")).unwrap();
+    fs::write(src.join("later.txt"), b"later").unwrap();
+    converge(&mut child, &dst, |d| d.contains_key(Path::new("later.txt")));
+
+    stop(child);
+    assert_eq!(fs::read(dst.join("a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(dst.join("extra.txt")).unwrap(), b"only here");
+}
+
+#[test]
+fn e2e_watch_refuses_a_destination_inside_the_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let (src, _) = tree(dir.path());
+    let inside = src.join("mirror");
+    let output = Command::new(bcmr_bin())
+        .args(["watch", src.to_str().unwrap(), inside.to_str().unwrap()])
+        .env("XDG_STATE_HOME", dir.path().join("state"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("inside the source"));
+    assert!(!inside.exists());
+}
@@ -236,7 +236,7 @@ fn test_inline_hash_matches_standalone() {
     let src = dir.path().join("src.bin");
     create_test_file(&src, 17 * 1024 * 1024);
 
-    let standalone_hash = checksum::calculate_hash(&src).unwrap();
+    let standalone_hash = checksum::calculate_hash(&src, checksum::Algorithm::Blake3).unwrap();
 
     let mut file = fs::File::open(&src).unwrap();
     let mut hasher = blake3::Hasher::new();